@@ -0,0 +1,103 @@
+//! Builds the runtime exported web pages run programs with: time_warp_core's
+//! `time_warp_web` for wasm32-wasip1, with the languages this IDE has. It
+//! lands in `$OUT_DIR/web_runtime.rs` as `WEB_RUNTIME`, which is `None` when
+//! the `web_runtime` feature is off or the wasm target isn't installed; the
+//! pages then replay their recorded run instead.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const TARGET: &str = "wasm32-wasip1";
+const LANGUAGES: [&str; 4] = ["logo", "pilot", "pascal", "prolog"];
+
+/// Enough for Pascal's deepest calls. Logo procedures nested that deep run
+/// out of the browser's own call stack first, which ends the run with an
+/// error on the page.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=time_warp_core/src");
+    println!("cargo:rerun-if-changed=time_warp_core/Cargo.toml");
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+
+    let runtime = if env::var_os("CARGO_FEATURE_WEB_RUNTIME").is_some() {
+        build_runtime(&out_dir)
+    } else {
+        None
+    };
+    let source = match runtime {
+        Some(wasm) => format!(
+            "pub const WEB_RUNTIME: Option<&[u8]> = Some(include_bytes!({:?}));\n",
+            wasm
+        ),
+        None => "pub const WEB_RUNTIME: Option<&[u8]> = None;\n".to_string(),
+    };
+    fs::write(out_dir.join("web_runtime.rs"), source).expect("OUT_DIR is writable");
+}
+
+/// Build the runtime and copy it into `out_dir`, or say why not
+fn build_runtime(out_dir: &Path) -> Option<PathBuf> {
+    // Beside the IDE's own target folder (OUT_DIR is
+    // <target>/<profile>/build/<package>/out), so every profile shares one
+    // build of it
+    let target_dir = out_dir
+        .ancestors()
+        .nth(4)
+        .unwrap_or(out_dir)
+        .join("web_runtime");
+    let features: Vec<&str> = LANGUAGES
+        .into_iter()
+        .filter(|language| {
+            env::var_os(format!("CARGO_FEATURE_{}", language.to_uppercase())).is_some()
+        })
+        .collect();
+
+    let mut command = Command::new(env::var_os("CARGO").unwrap_or("cargo".into()));
+    command
+        .args([
+            "build",
+            "--package",
+            "time_warp_core",
+            "--bin",
+            "time_warp_web",
+        ])
+        .args([
+            "--target",
+            TARGET,
+            "--profile",
+            "lite",
+            "--no-default-features",
+        ])
+        .args(["--features", &features.join(",")])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        // Flags meant for the IDE's own build (and clippy's wrapper) aren't
+        // for this one. Logo and Pascal can't have a thread with a big stack
+        // here, so the whole runtime gets one.
+        .env(
+            "CARGO_ENCODED_RUSTFLAGS",
+            format!("-Clink-arg=-zstack-size={}", STACK_SIZE),
+        )
+        .env_remove("RUSTFLAGS")
+        .env_remove("RUSTC_WORKSPACE_WRAPPER");
+    let built = target_dir
+        .join(TARGET)
+        .join("lite")
+        .join("time_warp_web.wasm");
+    let copied = out_dir.join("time_warp_web.wasm");
+    match command.status() {
+        Ok(status) if status.success() && fs::copy(&built, &copied).is_ok() => Some(copied),
+        _ => {
+            println!(
+                "cargo:warning=exported web pages will replay their recorded run instead of \
+                 running the program: building the web runtime needs the {} target \
+                 (rustup target add {})",
+                TARGET, TARGET
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Sound for BEEP, SOUND and PLAY. No audio library is built in, so each
+//! batch of notes a program plays is written out as a WAV file and handed
+//! to the system's own player: `aplay` on Linux, `afplay` on macOS and
+//! PowerShell's SoundPlayer on Windows. Batches play one after another, as
+//! the program queued them.
+//!
+//! The WAV files go in a folder of the IDE's own, made fresh in the temp
+//! folder and readable only by this user, so nobody else on a shared
+//! machine can put a file or link where the IDE is about to write.
+
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::collections::VecDeque;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use time_warp_core::basic::music::{self, Note};
+
+/// How often a frame is asked for while music plays, to start the next batch
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+
+/// After the system player fails to start, music is skipped this long
+/// before it is tried again, so the error isn't shown for every note
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub(crate) struct MusicPlayer {
+    folder: Option<PathBuf>, // where the WAV files go, made on first use
+    queue: VecDeque<PathBuf>,
+    playing: Option<(Child, PathBuf)>,
+    written: usize,             // WAV files written so far, to name the next one
+    failed_at: Option<Instant>, // when the system player last couldn't be started
+}
+
+impl MusicPlayer {
+    pub fn busy(&self) -> bool {
+        self.playing.is_some() || !self.queue.is_empty()
+    }
+
+    /// Queue `notes` to play after what is already playing
+    pub fn queue(&mut self, notes: &[Note]) -> std::io::Result<()> {
+        if self
+            .failed_at
+            .is_some_and(|failed| failed.elapsed() < RETRY_AFTER)
+        {
+            return Ok(());
+        }
+        self.failed_at = None;
+        let name = format!("{}.wav", self.written);
+        let path = self.folder()?.join(name);
+        self.written += 1;
+        // A file already there isn't ours, so it is never written through
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?
+            .write_all(&music::to_wav(notes))?;
+        self.queue.push_back(path);
+        Ok(())
+    }
+
+    /// The folder the WAV files go in, made the first time it's needed
+    fn folder(&mut self) -> std::io::Result<&Path> {
+        if self.folder.is_none() {
+            self.folder = Some(private_folder()?);
+        }
+        Ok(self.folder.as_deref().expect("made above"))
+    }
+
+    /// Start the next batch once the last has finished
+    pub fn poll(&mut self) -> std::io::Result<()> {
+        if let Some((child, path)) = &mut self.playing {
+            if child.try_wait()?.is_none() {
+                return Ok(());
+            }
+            let _ = std::fs::remove_file(path);
+            self.playing = None;
+        }
+        let Some(path) = self.queue.pop_front() else {
+            return Ok(());
+        };
+        match player(&path).spawn() {
+            Ok(child) => {
+                self.playing = Some((child, path));
+                Ok(())
+            }
+            Err(err) => {
+                let _ = std::fs::remove_file(&path);
+                self.stop();
+                self.failed_at = Some(Instant::now());
+                Err(err)
+            }
+        }
+    }
+
+    /// Silence the music and forget what was still to play
+    pub fn stop(&mut self) {
+        if let Some((mut child, path)) = self.playing.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = std::fs::remove_file(path);
+        }
+        for path in self.queue.drain(..) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for MusicPlayer {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(folder) = &self.folder {
+            let _ = std::fs::remove_dir(folder);
+        }
+    }
+}
+
+/// A new folder in the temp folder that only this user can open. A name
+/// that is already taken is passed over rather than reused.
+fn private_folder() -> std::io::Result<PathBuf> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut attempt = 0;
+    loop {
+        let folder = std::env::temp_dir().join(format!(
+            "tw_play_{}_{}_{}",
+            std::process::id(),
+            started,
+            attempt
+        ));
+        match builder.create(&folder) {
+            Ok(()) => return Ok(folder),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The system command that plays the WAV file at `path`
+fn player(path: &std::path::Path) -> Command {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("afplay");
+        command.arg(path);
+        command
+    } else if cfg!(windows) {
+        // -Command runs its text as a script, which has no $args of its own,
+        // so the path goes in the script as a quoted string
+        let path = path.display().to_string().replace('\'', "''");
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path),
+        ]);
+        command
+    } else {
+        let mut command = Command::new("aplay");
+        command.arg("-q").arg(path);
+        command
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+impl TimeWarpApp {
+    /// Play what the program BEEPed, SOUNDed and PLAYed, unless it is muted
+    pub(crate) fn play_music(&mut self, notes: Vec<Note>) {
+        if notes.is_empty() || !self.settings.play_sound {
+            return;
+        }
+        if let Err(err) = self.music_player.queue(&notes) {
+            self.show_error(format!("Could not play music: {}", err));
+        }
+    }
+
+    /// Keep the music going between frames, or silence it once muted
+    pub(crate) fn poll_music(&mut self, ctx: &egui::Context) {
+        if !self.settings.play_sound {
+            self.music_player.stop();
+            return;
+        }
+        if let Err(err) = self.music_player.poll() {
+            self.show_error(format!(
+                "Could not play music: {}. Sound needs the system's sound player \
+                 (aplay on Linux).",
+                err
+            ));
+        }
+        if self.music_player.busy() {
+            ctx.request_repaint_after(POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use crate::turtle::{self, TurtleOp};
+use eframe::egui;
+
+/// Empty border around the drawing when exporting in fit-to-content mode
+pub const FIT_MARGIN: f32 = 20.0;
+
+/// An RGBA image the turtle drawing is rasterized into for export
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>, // 4 bytes per pixel, rows top to bottom
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, background: egui::Color32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: background.to_array().repeat((width * height) as usize),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> egui::Color32 {
+        let i = ((y * self.width + x) * 4) as usize;
+        let p = &self.pixels[i..i + 4];
+        egui::Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3])
+    }
+
+    /// Blend `color` over the pixel, `coverage` (0-1) of the pixel being covered
+    fn blend(&mut self, x: i64, y: i64, color: egui::Color32, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        // Color32 is premultiplied, so source channels already carry its alpha
+        let keep = 1.0 - color.a() as f32 / 255.0 * coverage;
+        for (channel, source) in self.pixels[i..i + 4].iter_mut().zip(color.to_array()) {
+            *channel = (source as f32 * coverage + *channel as f32 * keep).round() as u8;
+        }
+    }
+
+    /// Paint every pixel whose centre lies inside `rect`
+    fn fill_rect(&mut self, rect: egui::Rect, color: egui::Color32) {
+        let min_x = (rect.min.x - 0.5).ceil().max(0.0) as i64;
+        let min_y = (rect.min.y - 0.5).ceil().max(0.0) as i64;
+        let max_x = (rect.max.x - 0.5).ceil().min(self.width as f32) as i64;
+        let max_y = (rect.max.y - 0.5).ceil().min(self.height as f32) as i64;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    /// Draw an anti-aliased line `width` pixels wide with round ends
+    fn stroke_segment(&mut self, a: egui::Pos2, b: egui::Pos2, width: f32, color: egui::Color32) {
+        let reach = width / 2.0 + 1.0;
+        let min_x = (a.x.min(b.x) - reach).floor() as i64;
+        let max_x = (a.x.max(b.x) + reach).ceil() as i64;
+        let min_y = (a.y.min(b.y) - reach).floor() as i64;
+        let max_y = (a.y.max(b.y) + reach).ceil() as i64;
+        let (min_x, min_y) = (min_x.max(0), min_y.max(0));
+        let (max_x, max_y) = (
+            max_x.min(self.width as i64 - 1),
+            max_y.min(self.height as i64 - 1),
+        );
+
+        let along = b - a;
+        let length_sq = along.length_sq();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let p = egui::pos2(x as f32 + 0.5, y as f32 + 0.5);
+                let t = if length_sq > 0.0 {
+                    ((p - a).dot(along) / length_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = (p - (a + along * t)).length();
+                let coverage = (width / 2.0 + 0.5 - distance).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+}
+
+/// The part of the turtle plane an export shows, and at what size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    scale: f32,
+    offset: egui::Vec2, // where the turtle origin lands in the picture
+    pub background: egui::Color32,
+}
+
+impl Frame {
+    /// The canvas as it is on screen: `size` pixels with the current pan and zoom
+    pub fn view(size: egui::Vec2, pan: egui::Vec2, zoom: f32) -> Self {
+        Self {
+            width: size.x as u32,
+            height: size.y as u32,
+            scale: zoom,
+            offset: size / 2.0 + pan * zoom,
+            background: turtle::DEFAULT_BACKGROUND,
+        }
+    }
+
+    /// The whole drawing scaled to fit inside `max_size` with a margin, cropped
+    /// to the drawing's shape. An empty drawing gives a blank `max_size` frame.
+    pub fn fit(ops: &[TurtleOp], max_size: egui::Vec2) -> Self {
+        let Some(bounds) = turtle::bounds(ops) else {
+            return Self::view(max_size, egui::Vec2::ZERO, 1.0);
+        };
+        let room = max_size - egui::vec2(FIT_MARGIN, FIT_MARGIN) * 2.0;
+        // A straight line has no width or height; don't let it divide by zero
+        let drawn = bounds.size().max(egui::vec2(1.0, 1.0));
+        let scale = (room.x / drawn.x).min(room.y / drawn.y);
+        let size = drawn * scale + egui::vec2(FIT_MARGIN, FIT_MARGIN) * 2.0;
+        Self {
+            width: size.x.round() as u32,
+            height: size.y.round() as u32,
+            scale,
+            offset: (size - bounds.size() * scale) / 2.0 - bounds.min.to_vec2() * scale,
+            background: turtle::DEFAULT_BACKGROUND,
+        }
+    }
+
+    /// `area` of the turtle plane at `scale` pixels per unit
+    pub fn covering(area: egui::Rect, scale: f32) -> Self {
+        let size = area.size() * scale;
+        Self {
+            width: size.x.ceil().max(1.0) as u32,
+            height: size.y.ceil().max(1.0) as u32,
+            scale,
+            offset: -area.min.to_vec2() * scale,
+            background: turtle::DEFAULT_BACKGROUND,
+        }
+    }
+
+    /// Where a point in turtle coordinates ends up in the picture
+    pub fn to_pixel(self, point: egui::Pos2) -> egui::Pos2 {
+        egui::Pos2::ZERO + point.to_vec2() * self.scale + self.offset
+    }
+
+    /// Inverse of `to_pixel`: the turtle coordinates at a point in the picture
+    pub fn to_turtle(self, pixel: egui::Pos2) -> egui::Pos2 {
+        egui::Pos2::ZERO + (pixel.to_vec2() - self.offset) / self.scale
+    }
+}
+
+/// Rasterize `ops` the way `turtle::paint` draws them on screen. Text is
+/// left out because there is no font rasterizer outside egui's renderer.
+pub fn render(ops: &[TurtleOp], frame: &Frame) -> Image {
+    let mut image = Image::new(frame.width, frame.height, frame.background);
+    let mut color = turtle::DEFAULT_PEN_COLOR;
+    let mut pen_width = turtle::DEFAULT_PEN_WIDTH;
+
+    for op in ops {
+        match op {
+            TurtleOp::Line { from, to } => {
+                image.stroke_segment(frame.to_pixel(*from), frame.to_pixel(*to), pen_width, color)
+            }
+            TurtleOp::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => {
+                let points = turtle::arc_points(*center, *radius, *start_angle, *sweep);
+                for pair in points.windows(2) {
+                    image.stroke_segment(
+                        frame.to_pixel(pair[0]),
+                        frame.to_pixel(pair[1]),
+                        pen_width,
+                        color,
+                    );
+                }
+            }
+            TurtleOp::Text { .. } => {}
+            TurtleOp::Fill { color, spans, .. } => {
+                for span in spans {
+                    let rect = egui::Rect::from_two_pos(
+                        frame.to_pixel(span.min),
+                        frame.to_pixel(span.max),
+                    );
+                    image.fill_rect(rect, *color);
+                }
+            }
+            TurtleOp::ColorChange(new_color) => color = *new_color,
+            TurtleOp::PenWidth(new_width) => pen_width = *new_width,
+        }
+    }
+    image
+}
+
+/// The drawing as an SVG document, with the same framing, pen colours and
+/// widths as the PNG export. Vector output stays crisp when printed.
+pub fn to_svg(ops: &[TurtleOp], frame: &Frame) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = frame.width,
+        h = frame.height
+    );
+    svg.push_str(&format!(
+        "  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+        svg_color(frame.background)
+    ));
+    svg.push_str("  <g fill=\"none\" stroke-linecap=\"round\" stroke-linejoin=\"round\">\n");
+
+    let mut color = turtle::DEFAULT_PEN_COLOR;
+    let mut pen_width = turtle::DEFAULT_PEN_WIDTH;
+    for op in ops {
+        match op {
+            TurtleOp::Line { from, to } => {
+                let (from, to) = (frame.to_pixel(*from), frame.to_pixel(*to));
+                svg.push_str(&format!(
+                    "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>\n",
+                    svg_number(from.x),
+                    svg_number(from.y),
+                    svg_number(to.x),
+                    svg_number(to.y),
+                    svg_color(color),
+                    svg_number(pen_width)
+                ));
+            }
+            TurtleOp::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => {
+                let points: Vec<String> =
+                    turtle::arc_points(*center, *radius, *start_angle, *sweep)
+                        .into_iter()
+                        .map(|point| {
+                            let point = frame.to_pixel(point);
+                            format!("{},{}", svg_number(point.x), svg_number(point.y))
+                        })
+                        .collect();
+                svg.push_str(&format!(
+                    "    <polyline points=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>\n",
+                    points.join(" "),
+                    svg_color(color),
+                    svg_number(pen_width)
+                ));
+            }
+            TurtleOp::Text {
+                at,
+                text,
+                angle,
+                size,
+            } => {
+                let at = frame.to_pixel(*at);
+                let (x, y) = (svg_number(at.x), svg_number(at.y));
+                let rotate = if *angle == 0.0 {
+                    String::new()
+                } else {
+                    format!(" transform=\"rotate({} {} {})\"", svg_number(*angle), x, y)
+                };
+                svg.push_str(&format!(
+                    "    <text x=\"{}\" y=\"{}\"{} font-family=\"sans-serif\" font-size=\"{}\" fill=\"{}\" stroke=\"none\">{}</text>\n",
+                    x,
+                    y,
+                    rotate,
+                    svg_number(size * frame.scale),
+                    svg_color(color),
+                    escape_xml(text)
+                ));
+            }
+            TurtleOp::Fill { color, spans, .. } => {
+                // One path per fill, so the rectangles don't show seams
+                let mut path = String::new();
+                for span in spans {
+                    let (min, max) = (frame.to_pixel(span.min), frame.to_pixel(span.max));
+                    path.push_str(&format!(
+                        "M{} {}H{}V{}H{}Z",
+                        svg_number(min.x),
+                        svg_number(min.y),
+                        svg_number(max.x),
+                        svg_number(max.y),
+                        svg_number(min.x)
+                    ));
+                }
+                svg.push_str(&format!(
+                    "    <path d=\"{}\" fill=\"{}\" stroke=\"none\"/>\n",
+                    path,
+                    svg_color(*color)
+                ));
+            }
+            TurtleOp::ColorChange(new_color) => color = *new_color,
+            TurtleOp::PenWidth(new_width) => pen_width = *new_width,
+        }
+    }
+    svg.push_str("  </g>\n</svg>\n");
+    svg
+}
+
+/// Write the drawing to `path` as an SVG file
+pub fn save_svg(ops: &[TurtleOp], frame: &Frame, path: &std::path::Path) -> std::io::Result<()> {
+    std::fs::write(path, to_svg(ops, frame))
+}
+
+/// Coordinates to two decimal places, without trailing zeros
+fn svg_number(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn svg_color(color: egui::Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+/// `text` with the characters that mean something in XML and HTML escaped
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Encode `image` as a PNG file. The pixel data is stored uncompressed,
+/// which keeps the encoder tiny; turtle drawings are small anyway.
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    // Every row starts with filter type 0 (none)
+    let row_bytes = image.width as usize * 4;
+    let mut raw = Vec::with_capacity((row_bytes + 1) * image.height as usize);
+    for row in image.pixels.chunks(row_bytes.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8-bit RGBA, no interlace
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Write `image` to `path` as a PNG file
+pub fn save_png(image: &Image, path: &std::path::Path) -> std::io::Result<()> {
+    std::fs::write(path, encode_png(image))
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// A zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none())); // final block flag
+        let length = block.len() as u16;
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Marker drawn where the pen is during a replay, as on the canvas
+const PEN_MARKER_COLOR: egui::Color32 = egui::Color32::from_rgb(0, 150, 0);
+const PEN_MARKER_RADIUS: f32 = 4.0;
+
+/// `count` evenly spaced snapshots of the turtle drawing itself, ending with
+/// the finished picture
+pub fn replay_frames(ops: &[TurtleOp], frame: &Frame, count: usize) -> Vec<Image> {
+    let strokes = turtle::stroke_count(ops) as f32;
+    (1..=count)
+        .map(|i| {
+            let (shown, pen) = turtle::replay(ops, strokes * i as f32 / count as f32);
+            let mut image = render(&shown, frame);
+            if let Some(pen) = pen.filter(|_| i < count) {
+                let at = frame.to_pixel(pen);
+                image.stroke_segment(at, at, PEN_MARKER_RADIUS * 2.0, PEN_MARKER_COLOR);
+            }
+            image
+        })
+        .collect()
+}
+
+/// Encode same-sized `frames` as an animated GIF that loops forever. `delays`
+/// is how long each frame shows, in hundredths of a second.
+pub fn encode_gif(frames: &[Image], delays: &[u16]) -> Vec<u8> {
+    let (width, height) = frames
+        .first()
+        .map_or((1, 1), |first| (first.width as u16, first.height as u16));
+    let palette = Palette::for_frames(frames);
+
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    gif.extend_from_slice(&[0xf7, 0, 0]); // 256-colour global palette
+    for i in 0..256 {
+        gif.extend_from_slice(&palette.colors.get(i).copied().unwrap_or([0, 0, 0]));
+    }
+    // NETSCAPE2.0 application extension: repeat forever
+    gif.extend_from_slice(&[0x21, 0xff, 0x0b]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[3, 1, 0, 0, 0]);
+
+    for (image, delay) in frames.iter().zip(delays) {
+        // Graphic control extension: leave the frame in place, then wait `delay`
+        gif.extend_from_slice(&[0x21, 0xf9, 4, 0x04]);
+        gif.extend_from_slice(&delay.to_le_bytes());
+        gif.extend_from_slice(&[0, 0]);
+
+        gif.extend_from_slice(&[0x2c, 0, 0, 0, 0]);
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.push(0);
+
+        let indices: Vec<u8> = image
+            .pixels
+            .chunks(4)
+            .map(|pixel| palette.index([pixel[0], pixel[1], pixel[2]]))
+            .collect();
+        gif.push(8); // minimum LZW code size
+        for block in lzw_encode(&indices).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3b);
+    gif
+}
+
+/// The 256 colours a GIF may use. Drawings with few colours keep them
+/// exactly; busier ones (anti-aliased edges in many pen colours) fall back to
+/// a 6x6x6 colour cube plus a grey ramp.
+struct Palette {
+    colors: Vec<[u8; 3]>,
+    exact: std::collections::HashMap<[u8; 3], u8>,
+}
+
+impl Palette {
+    const CUBE_LEVELS: u32 = 6;
+    const GREYS: u32 = 40;
+
+    fn for_frames(frames: &[Image]) -> Self {
+        let mut seen = std::collections::HashMap::new();
+        'frames: for image in frames {
+            for pixel in image.pixels.chunks(4) {
+                let color = [pixel[0], pixel[1], pixel[2]];
+                if !seen.contains_key(&color) {
+                    if seen.len() == 256 {
+                        seen.clear();
+                        break 'frames;
+                    }
+                    seen.insert(color, seen.len() as u8);
+                }
+            }
+        }
+        if !seen.is_empty() {
+            let mut colors = vec![[0; 3]; seen.len()];
+            for (color, &index) in &seen {
+                colors[index as usize] = *color;
+            }
+            return Self {
+                colors,
+                exact: seen,
+            };
+        }
+
+        let level = |i: u32| (i * 255 / (Self::CUBE_LEVELS - 1)) as u8;
+        let mut colors = Vec::new();
+        for r in 0..Self::CUBE_LEVELS {
+            for g in 0..Self::CUBE_LEVELS {
+                for b in 0..Self::CUBE_LEVELS {
+                    colors.push([level(r), level(g), level(b)]);
+                }
+            }
+        }
+        for i in 0..Self::GREYS {
+            let grey = (i * 255 / (Self::GREYS - 1)) as u8;
+            colors.push([grey, grey, grey]);
+        }
+        Self {
+            colors,
+            exact: std::collections::HashMap::new(),
+        }
+    }
+
+    fn index(&self, color: [u8; 3]) -> u8 {
+        if !self.exact.is_empty() {
+            return self.exact.get(&color).copied().unwrap_or(0);
+        }
+        let steps = Self::CUBE_LEVELS - 1;
+        let [r, g, b] = color.map(|c| (c as u32 * steps + 127) / 255);
+        let cube = (r * Self::CUBE_LEVELS + g) * Self::CUBE_LEVELS + b;
+        let average = color.iter().map(|&c| c as u32).sum::<u32>() / 3;
+        let grey = Self::CUBE_LEVELS.pow(3) + (average * (Self::GREYS - 1) + 127) / 255;
+
+        let distance = |index: u32| -> u32 {
+            let candidate = self.colors[index as usize];
+            color
+                .iter()
+                .zip(candidate)
+                .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+                .sum()
+        };
+        if distance(grey) < distance(cube) {
+            grey as u8
+        } else {
+            cube as u8
+        }
+    }
+}
+
+/// Variable-width LZW compression of 8-bit palette indices, as GIF uses it
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    const MAX_CODES: u16 = 4096;
+
+    let mut bits = BitWriter::default();
+    let mut table = std::collections::HashMap::new();
+    let mut next_code = END + 1;
+    let mut code_size = 9;
+    bits.write(CLEAR, code_size);
+
+    // Readers add table entries one code behind us; widen codes when theirs
+    // would stop fitting
+    let emit = |bits: &mut BitWriter, code: u16, code_size: &mut u32, next_code: u16| {
+        bits.write(code, *code_size);
+        if next_code >= 1 << *code_size && *code_size < 12 {
+            *code_size += 1;
+        }
+    };
+
+    let Some((&first, rest)) = indices.split_first() else {
+        bits.write(END, code_size);
+        return bits.finish();
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        emit(&mut bits, prefix, &mut code_size, next_code);
+        if next_code < MAX_CODES {
+            table.insert((prefix, index), next_code);
+            next_code += 1;
+        } else {
+            bits.write(CLEAR, code_size);
+            table.clear();
+            next_code = END + 1;
+            code_size = 9;
+        }
+        prefix = index as u16;
+    }
+    emit(&mut bits, prefix, &mut code_size, next_code);
+    bits.write(END, code_size);
+    bits.finish()
+}
+
+/// Packs codes least-significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
@@ -0,0 +1,180 @@
+use crate::editor::line_selection;
+use crate::TimeWarpApp;
+use eframe::egui;
+use time_warp_core::basic::dialect::Dialect;
+use time_warp_core::basic::lint;
+use time_warp_core::basic::strict::{Diagnostic, Severity};
+use time_warp_core::basic::Interpreter;
+use time_warp_core::Language;
+
+/// What the Diagnostics panel lists for one version of the program, kept
+/// until the program or its dialect changes
+pub(crate) struct DiagnosticsCache {
+    code: String,
+    dialect: Dialect,
+    found: Vec<Diagnostic>,
+}
+
+/// The line of the program itself that an INCLUDE error names, as in
+/// "Could not INCLUDE lib.twb at line 3 of the program: ..."
+fn include_error_line(message: &str) -> usize {
+    message
+        .split("at line ")
+        .skip(1)
+        .find_map(|rest| {
+            let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+            rest[digits.len()..]
+                .starts_with(" of the program")
+                .then(|| digits.parse().ok())?
+        })
+        .unwrap_or(1)
+}
+
+/// The line of the program an error in the Output tab happened on, as in
+/// "Error: E201 Division by zero (line 3, column 4) in 30". Errors in
+/// included files ("line 2 of lib.twb") aren't in the editor to select.
+pub(crate) fn error_line(text: &str) -> Option<usize> {
+    if !text.starts_with("Error:") {
+        return None;
+    }
+    text.split("line ").skip(1).find_map(|rest| {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let line = rest[..digits].parse().ok()?;
+        let after = rest[digits..].trim_start_matches(|c: char| c == ',' || c.is_ascii_digit());
+        let after = after
+            .trim_start_matches(" column ")
+            .trim_start_matches(|c: char| c.is_ascii_digit());
+        (!after.starts_with(" of ")).then_some(line)
+    })
+}
+
+impl TimeWarpApp {
+    /// Every parse error in the program in the editor, or when it parses,
+    /// what the linter finds. A parse error in an included file is shown
+    /// on its INCLUDE line; lint findings there are left out.
+    pub(crate) fn check_program(&self) -> Vec<Diagnostic> {
+        let expanded = match self.expand_includes(&self.code) {
+            Ok(expanded) => expanded,
+            Err(err) => {
+                let message = err.message();
+                return vec![Diagnostic {
+                    line: include_error_line(&message),
+                    severity: Severity::Error,
+                    message,
+                    span: None,
+                }];
+            }
+        };
+        let dialect = self.settings.basic_dialect;
+        let errors = lint::parse_errors(&expanded.code, dialect);
+        if !errors.is_empty() {
+            return errors
+                .into_iter()
+                .map(|error| Diagnostic {
+                    line: expanded.main_line(error.line),
+                    severity: error.severity,
+                    message: expanded.locate_at_lines(&error.message),
+                    // Only a line of the program itself reads as it was parsed
+                    span: expanded
+                        .origin(error.line)
+                        .filter(|origin| origin.file.is_none())
+                        .and(error.span),
+                })
+                .collect();
+        }
+        let mut interpreter = Interpreter::new();
+        interpreter.set_dialect(dialect);
+        if let Err(err) = interpreter.load(&expanded.code) {
+            return vec![Diagnostic {
+                line: 1,
+                severity: Severity::Error,
+                message: expanded.locate_at_lines(&err.message()),
+                span: None,
+            }];
+        }
+        interpreter
+            .lint()
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let origin = expanded.origin(diagnostic.line)?;
+                origin.file.is_none().then_some(Diagnostic {
+                    line: origin.line,
+                    ..diagnostic
+                })
+            })
+            .collect()
+    }
+
+    /// The panel's list, worked out again only after an edit
+    fn current_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let fresh = self.diagnostics_cache.as_ref().is_some_and(|cache| {
+            cache.code == self.code && cache.dialect == self.settings.basic_dialect
+        });
+        if !fresh {
+            self.diagnostics_cache = Some(DiagnosticsCache {
+                code: self.code.clone(),
+                dialect: self.settings.basic_dialect,
+                found: self.check_program(),
+            });
+        }
+        self.diagnostics_cache
+            .as_ref()
+            .map_or(Vec::new(), |cache| cache.found.clone())
+    }
+
+    /// The Diagnostics panel under the editor: parse errors and lint
+    /// warnings that follow each edit, and what strict mode found on the
+    /// last run. Clicking one selects its line.
+    pub(crate) fn render_diagnostics_panel(&mut self, ui: &mut egui::Ui) {
+        let basic = self.language == Language::Basic;
+        let mut found = Vec::new();
+        if basic {
+            found = self.current_diagnostics();
+            found.extend(self.diagnostics.iter().cloned());
+            found.sort_by_key(|diagnostic| diagnostic.line);
+            found.dedup();
+        }
+        ui.horizontal(|ui| {
+            ui.strong(format!("⚠ Diagnostics: {}", found.len()));
+            if ui.small_button("✖").on_hover_text("Close").clicked() {
+                self.show_diagnostics = false;
+            }
+        });
+        if !basic {
+            ui.label(format!(
+                "Diagnostics are found for TW BASIC programs, not {}.",
+                self.language.name()
+            ));
+            return;
+        }
+        if found.is_empty() {
+            ui.label("Nothing to report.");
+            return;
+        }
+        let palette = self.palette();
+        egui::ScrollArea::vertical()
+            .id_source("diagnostics")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for diagnostic in &found {
+                    let (icon, color) = match diagnostic.severity {
+                        Severity::Error => ("⛔", palette.error),
+                        Severity::Warning => ("⚠", palette.warning),
+                    };
+                    let text = egui::RichText::new(format!(
+                        "{} {:>4}: {}",
+                        icon, diagnostic.line, diagnostic.message
+                    ))
+                    .monospace()
+                    .color(color);
+                    if ui
+                        .add(egui::Label::new(text).sense(egui::Sense::click()))
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.pending_selection = Some(line_selection(&self.code, diagnostic.line));
+                    }
+                }
+            });
+    }
+}
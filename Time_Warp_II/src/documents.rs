@@ -0,0 +1,584 @@
+use crate::editor::Selection;
+use crate::file_watch::FileWatcher;
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use time_warp_core::Language;
+
+/// A program open in one of the editor tabs. The tab being edited keeps its
+/// state in the app's own fields (`code`, `last_file_path`, the undo history
+/// and so on); switching tabs swaps those with the `Document` stored here.
+pub(crate) struct Document {
+    code: String,
+    path: Option<String>,
+    language: Language,
+    new_file_extension: String,
+    untitled_number: usize, // tells unsaved tabs apart: "untitled", "untitled 2", …
+    undo_history: Vec<String>,
+    undo_position: usize,
+    previous_code: String,
+    selection: Selection,
+    file_watcher: FileWatcher,
+    external_conflict: Option<String>,
+    folded_lines: BTreeSet<usize>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            code: String::new(),
+            path: None,
+            language: Language::default(),
+            new_file_extension: "twb".to_string(),
+            untitled_number: 1,
+            undo_history: Vec::new(),
+            undo_position: 0,
+            previous_code: String::new(),
+            selection: Selection::default(),
+            file_watcher: FileWatcher::new(),
+            external_conflict: None,
+            folded_lines: BTreeSet::new(),
+        }
+    }
+}
+
+/// FNV-1a of `location`. Unlike the standard library's hasher it is the
+/// same in every build, so sandbox folders and remembered answers stay
+/// where they are across updates.
+fn location_hash(location: &str) -> u64 {
+    location.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The file name of `path`, or the untitled name for a tab never saved
+pub(crate) fn document_name(path: Option<&str>, untitled_number: usize) -> String {
+    match path.and_then(|path| Path::new(path).file_name()) {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None if untitled_number <= 1 => "untitled".to_string(),
+        None => format!("untitled {}", untitled_number),
+    }
+}
+
+impl TimeWarpApp {
+    /// Name of the file being edited, which also keys its breakpoints
+    pub(crate) fn document_name(&self) -> String {
+        document_name(self.last_file_path.as_deref(), self.untitled_number)
+    }
+
+    /// The document's name without its extension and, once it is saved, a
+    /// short hash of where: "game-1c9f04ad". It names the program's sandbox
+    /// folder and keys the answers remembered for it, so two programs both
+    /// called game.twb in different folders don't share either.
+    pub(crate) fn program_name(&self) -> String {
+        let name = self.document_name();
+        let stem = match Path::new(&name).file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => name,
+        };
+        match self.last_file_path.as_deref() {
+            Some(location) => {
+                let location = std::fs::canonicalize(location)
+                    .map_or_else(|_| location.to_string(), |path| path.display().to_string());
+                format!("{}-{:08x}", stem, location_hash(&location) as u32)
+            }
+            None => stem,
+        }
+    }
+
+    /// The folder the program's OPEN files live in, one per document
+    pub(crate) fn files_dir(&self) -> PathBuf {
+        self.storage.sandbox_dir.join(self.program_name())
+    }
+
+    /// Whether the editor holds edits that haven't been saved
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.file_watcher.is_dirty(&self.code)
+    }
+
+    /// Each tab's name and whether it has unsaved edits, in order
+    pub(crate) fn document_tabs(&self) -> Vec<(String, bool)> {
+        self.documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| {
+                if index == self.active_document {
+                    (self.document_name(), self.is_dirty())
+                } else {
+                    (
+                        document_name(document.path.as_deref(), document.untitled_number),
+                        document.file_watcher.is_dirty(&document.code),
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// Each tab's file, buffer and language, for the saved session
+    pub(crate) fn document_sessions(&self) -> Vec<(Option<&str>, &str, Language)> {
+        self.documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| match index == self.active_document {
+                true => (
+                    self.last_file_path.as_deref(),
+                    self.code.as_str(),
+                    self.language,
+                ),
+                false => (
+                    document.path.as_deref(),
+                    document.code.as_str(),
+                    document.language,
+                ),
+            })
+            .collect()
+    }
+
+    /// Move the active tab's state out of the app, leaving an empty program
+    fn take_document(&mut self) -> Document {
+        let enabled = self.file_watcher.enabled;
+        let document = Document {
+            code: std::mem::take(&mut self.code),
+            path: self.last_file_path.take(),
+            language: std::mem::take(&mut self.language),
+            new_file_extension: std::mem::replace(&mut self.new_file_extension, "twb".into()),
+            untitled_number: std::mem::replace(&mut self.untitled_number, 1),
+            undo_history: std::mem::take(&mut self.undo_history),
+            undo_position: std::mem::take(&mut self.undo_position),
+            previous_code: std::mem::take(&mut self.previous_code),
+            selection: std::mem::take(&mut self.selection),
+            file_watcher: std::mem::replace(&mut self.file_watcher, FileWatcher::new()),
+            external_conflict: self.external_conflict.take(),
+            folded_lines: std::mem::take(&mut self.folded_lines),
+        };
+        self.file_watcher.enabled = enabled;
+        document
+    }
+
+    /// Make `document` the one being edited
+    fn load_document(&mut self, document: Document) {
+        let enabled = self.file_watcher.enabled;
+        self.code = document.code;
+        self.last_file_path = document.path;
+        self.language = document.language;
+        self.new_file_extension = document.new_file_extension;
+        self.untitled_number = document.untitled_number;
+        self.undo_history = document.undo_history;
+        self.undo_position = document.undo_position;
+        self.previous_code = document.previous_code;
+        self.selection = document.selection;
+        self.pending_selection = Some(document.selection);
+        self.file_watcher = document.file_watcher;
+        self.file_watcher.enabled = enabled;
+        self.external_conflict = document.external_conflict;
+        self.folded_lines = document.folded_lines;
+        self.show_completion = false;
+    }
+
+    pub(crate) fn switch_document(&mut self, index: usize) {
+        if index == self.active_document || index >= self.documents.len() {
+            return;
+        }
+        let current = self.take_document();
+        self.documents[self.active_document] = current;
+        let next = std::mem::take(&mut self.documents[index]);
+        self.active_document = index;
+        self.load_document(next);
+    }
+
+    /// Open an empty tab and switch to it
+    pub(crate) fn new_document(&mut self) {
+        let untitled_number = (0..self.documents.len())
+            .map(|index| match index == self.active_document {
+                true => (self.last_file_path.is_none(), self.untitled_number),
+                false => {
+                    let document = &self.documents[index];
+                    (document.path.is_none(), document.untitled_number)
+                }
+            })
+            .filter(|&(untitled, _)| untitled)
+            .map(|(_, number)| number)
+            .max()
+            .map_or(1, |number| number + 1);
+        let current = self.take_document();
+        self.documents[self.active_document] = current;
+        self.documents.push(Document::default());
+        self.active_document = self.documents.len() - 1;
+        self.untitled_number = untitled_number;
+        self.pending_selection = Some(Selection::default());
+    }
+
+    /// Whether the active tab is an untouched new program that opening a
+    /// file can reuse instead of adding another tab
+    pub(crate) fn is_blank_document(&self) -> bool {
+        self.last_file_path.is_none() && self.code.is_empty() && self.undo_history.is_empty()
+    }
+
+    /// The tab already showing `location`, if any
+    pub(crate) fn find_document(&self, location: &str) -> Option<usize> {
+        (0..self.documents.len()).find(|&index| {
+            let path = match index == self.active_document {
+                true => self.last_file_path.as_deref(),
+                false => self.documents[index].path.as_deref(),
+            };
+            path == Some(location)
+        })
+    }
+
+    /// Close a tab, unsaved edits and all. Closing the last tab leaves an
+    /// empty program.
+    pub(crate) fn close_document(&mut self, index: usize) {
+        if index >= self.documents.len() {
+            return;
+        }
+        self.split_document = match self.split_document {
+            Some(split) if split == index => None,
+            Some(split) if split > index => Some(split - 1),
+            split => split,
+        };
+        if self.documents.len() == 1 {
+            self.take_document();
+            self.pending_selection = Some(Selection::default());
+            return;
+        }
+        if index == self.active_document {
+            self.take_document();
+            self.documents.remove(index);
+            self.active_document = index.min(self.documents.len() - 1);
+            let next = std::mem::take(&mut self.documents[self.active_document]);
+            self.load_document(next);
+        } else {
+            self.documents.remove(index);
+            if index < self.active_document {
+                self.active_document -= 1;
+            }
+        }
+    }
+
+    /// Close a tab, asking first if it has unsaved edits
+    fn request_close_document(&mut self, index: usize, dirty: bool) {
+        if dirty {
+            self.closing_document = Some(index);
+        } else {
+            self.close_document(index);
+        }
+    }
+
+    /// The strip of open files above the editor; ● marks unsaved edits
+    pub(crate) fn render_document_tabs(&mut self, ui: &mut egui::Ui) {
+        let tabs = self.document_tabs();
+        egui::ScrollArea::horizontal()
+            .id_source("document_tabs")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (index, (name, dirty)) in tabs.into_iter().enumerate() {
+                        let title = if dirty { format!("{} ●", name) } else { name };
+                        let hover = match dirty {
+                            true => "Unsaved changes",
+                            false => "Saved",
+                        };
+                        if ui
+                            .selectable_label(index == self.active_document, title)
+                            .on_hover_text(hover)
+                            .clicked()
+                        {
+                            self.switch_document(index);
+                        }
+                        if ui.small_button("✖").on_hover_text("Close").clicked() {
+                            self.request_close_document(index, dirty);
+                        }
+                        ui.add_space(6.0);
+                    }
+                    if ui.small_button("➕").on_hover_text("New tab").clicked() {
+                        self.new_document();
+                    }
+                });
+            });
+    }
+
+    /// Show a second pane beside the editor, starting on the same file, or
+    /// hide it again
+    pub(crate) fn toggle_split_view(&mut self) {
+        self.split_document = match self.split_document {
+            Some(_) => None,
+            None => Some(self.active_document),
+        };
+    }
+
+    /// Edit the tab in the split pane, showing the one that was in the
+    /// editor there instead
+    pub(crate) fn swap_split_view(&mut self) {
+        if let Some(split) = self.split_document {
+            let editor = self.active_document;
+            self.switch_document(split);
+            self.split_document = Some(editor);
+        }
+    }
+
+    /// The right-hand pane of the split view, scrolled separately from the
+    /// editor. Showing the file being edited it is a second editor on it;
+    /// another tab is shown read-only, and ⇄ swaps it into the editor.
+    pub(crate) fn render_split_view(&mut self, ui: &mut egui::Ui) {
+        let Some(split) = self.split_document else {
+            return;
+        };
+        let tabs = self.document_tabs();
+        let mut index = split.min(tabs.len() - 1);
+        let (mut swap, mut close) = (false, false);
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("split_document")
+                .selected_text(&tabs[index].0)
+                .show_ui(ui, |ui| {
+                    for (tab, (name, _)) in tabs.iter().enumerate() {
+                        ui.selectable_value(&mut index, tab, name);
+                    }
+                });
+            if index != self.active_document {
+                swap = ui
+                    .button("⇄")
+                    .on_hover_text("Swap with the file in the editor")
+                    .clicked();
+            }
+            close = ui
+                .small_button("✖")
+                .on_hover_text("Close the split view")
+                .clicked();
+        });
+        self.split_document = Some(index);
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .id_source("split_view")
+            .show(ui, |ui| {
+                let language = match index == self.active_document {
+                    true => self.language,
+                    false => self.documents[index].language,
+                };
+                let style = self.code_style(language);
+                let cache = &mut self.split_highlight_cache;
+                let mut layouter = |ui: &egui::Ui, code: &str, wrap_width: f32| {
+                    style.layout(ui, code, wrap_width, cache)
+                };
+                fn editor(text: &mut dyn egui::TextBuffer) -> egui::TextEdit<'_> {
+                    egui::TextEdit::multiline(text)
+                        .id_source("split_editor")
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(20)
+                }
+                if index == self.active_document {
+                    let response = editor(&mut self.code).layouter(&mut layouter).show(ui);
+                    if response.response.changed() && self.code != self.previous_code {
+                        self.save_undo_state();
+                        self.previous_code = self.code.clone();
+                    }
+                } else {
+                    let mut text = self.documents[index].code.as_str();
+                    editor(&mut text).layouter(&mut layouter).show(ui);
+                }
+            });
+
+        if swap {
+            self.swap_split_view();
+        }
+        if close {
+            self.split_document = None;
+        }
+    }
+
+    /// Open the files dropped on the window this frame, and shade the window
+    /// while files are dragged over it
+    pub(crate) fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let (hovering, dropped) = ctx.input(|i| {
+            let dropped: Vec<PathBuf> = i
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect();
+            (!i.raw.hovered_files.is_empty(), dropped)
+        });
+        if hovering {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("file_drop"),
+            ));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(160));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop to open",
+                egui::FontId::proportional(28.0),
+                egui::Color32::WHITE,
+            );
+        }
+        for path in dropped.into_iter().filter(|path| path.is_file()) {
+            self.open_dropped_file(&path.display().to_string());
+        }
+    }
+
+    /// Open a dropped file, in its own tab. If it is open already with
+    /// unsaved edits, ask before reading it again over them.
+    pub(crate) fn open_dropped_file(&mut self, location: &str) {
+        let unsaved = self
+            .find_document(location)
+            .is_some_and(|index| self.document_tabs()[index].1);
+        if crate::projects::is_project_file(location) {
+            self.open_project(std::path::Path::new(location));
+        } else if unsaved {
+            self.reloading_document = Some(location.to_string());
+        } else if self.open_location(location) {
+            self.remember_recent_file(location);
+        }
+    }
+
+    /// Confirm reloading a dropped file over its unsaved edits
+    pub(crate) fn render_reload_prompt(&mut self, ctx: &egui::Context) {
+        let Some(location) = self.reloading_document.clone() else {
+            return;
+        };
+        let name = document_name(Some(&location), 1);
+        let (mut reload, mut keep) = (false, false);
+        egui::Window::new("Reload File")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} is open with unsaved changes. Reload it from disk and lose them?",
+                    name
+                ));
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    reload = ui.button("🔄 Reload").clicked();
+                    keep = ui.button("Keep My Changes").clicked();
+                });
+            });
+        if reload && self.open_location(&location) {
+            self.remember_recent_file(&location);
+        }
+        if keep {
+            if let Some(index) = self.find_document(&location) {
+                self.switch_document(index);
+            }
+        }
+        if reload || keep {
+            self.reloading_document = None;
+        }
+    }
+
+    /// Names of the tabs with unsaved edits
+    pub(crate) fn unsaved_documents(&self) -> Vec<String> {
+        self.document_tabs()
+            .into_iter()
+            .filter(|(_, dirty)| *dirty)
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Save every tab with unsaved edits, asking where to for new ones.
+    /// Returns whether they all got saved.
+    pub(crate) fn save_all_documents(&mut self) -> bool {
+        let active = self.active_document;
+        for index in 0..self.documents.len() {
+            if self.document_tabs()[index].1 {
+                self.switch_document(index);
+                self.save_current_file();
+            }
+        }
+        self.switch_document(active);
+        self.unsaved_documents().is_empty()
+    }
+
+    /// Show the file name in the window title, with ● while it has unsaved edits
+    pub(crate) fn update_window_title(&mut self, ctx: &egui::Context) {
+        let marker = if self.is_dirty() { "● " } else { "" };
+        let title = format!("{}{} — Time Warp IDE", marker, self.document_name());
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    /// Hold the window open when it is closed with unsaved edits, and ask
+    pub(crate) fn confirm_quit(&mut self, ctx: &egui::Context) {
+        let closing = ctx.input(|i| i.viewport().close_requested());
+        if closing && !self.quit_confirmed && !self.unsaved_documents().is_empty() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.show_quit_prompt = true;
+        }
+    }
+
+    /// Save, discard or cancel when quitting with unsaved edits
+    pub(crate) fn render_quit_prompt(&mut self, ctx: &egui::Context) {
+        if !self.show_quit_prompt {
+            return;
+        }
+        let unsaved = self.unsaved_documents();
+        let (mut save, mut quit, mut cancel) = (false, false, false);
+        egui::Window::new("Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label("These files have unsaved changes:");
+                for name in &unsaved {
+                    ui.label(format!("  ● {}", name));
+                }
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    save = ui.button("💾 Save All and Quit").clicked();
+                    quit = ui
+                        .button("Quit Without Saving")
+                        .on_hover_text(
+                            "The edits are still in the session when the IDE next starts",
+                        )
+                        .clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if save && self.save_all_documents() {
+            quit = true;
+        }
+        if quit {
+            self.quit_confirmed = true;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        if save || quit || cancel {
+            self.show_quit_prompt = false;
+        }
+    }
+
+    /// Confirm closing a tab with unsaved edits
+    pub(crate) fn render_close_document_prompt(&mut self, ctx: &egui::Context) {
+        let Some(index) = self.closing_document else {
+            return;
+        };
+        let Some((name, _)) = self.document_tabs().into_iter().nth(index) else {
+            self.closing_document = None;
+            return;
+        };
+        let mut close = false;
+        let mut cancel = false;
+        egui::Window::new("Close Tab")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("{} has unsaved changes. Close it anyway?", name));
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    close = ui.button("🗑 Close Without Saving").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if close {
+            self.close_document(index);
+        }
+        if close || cancel {
+            self.closing_document = None;
+        }
+    }
+}
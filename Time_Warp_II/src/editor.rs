@@ -0,0 +1,948 @@
+use crate::highlight::{self, HighlightCache};
+use crate::{search, theme, TimeWarpApp};
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::Arc;
+use time_warp_core::basic::strict::Severity;
+use time_warp_core::{Language, LanguageBackend};
+
+/// The caret and selection in the code editor, as character indexes into
+/// the code. `anchor` is where a selection started and `head` is the caret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    pub fn caret(at: usize) -> Self {
+        Self {
+            anchor: at,
+            head: at,
+        }
+    }
+
+    /// The selected characters, first to last whichever way it was dragged
+    pub fn range(self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.anchor == self.head
+    }
+
+    fn shifted(self, by: isize) -> Self {
+        let shift = |index: usize| index.saturating_add_signed(by);
+        Self {
+            anchor: shift(self.anchor),
+            head: shift(self.head),
+        }
+    }
+
+    fn from_egui(range: CCursorRange) -> Self {
+        Self {
+            anchor: range.secondary.index,
+            head: range.primary.index,
+        }
+    }
+
+    fn to_egui(self) -> CCursorRange {
+        CCursorRange::two(CCursor::new(self.anchor), CCursor::new(self.head))
+    }
+}
+
+/// Byte offset of the character at `index`, or the end of `text` past it
+pub(crate) fn byte_index(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(byte, _)| byte)
+}
+
+/// 1-based line and column of the character at `index`
+pub(crate) fn line_and_column(text: &str, index: usize) -> (usize, usize) {
+    let before = &text[..byte_index(text, index)];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// The word that ends at `caret`, which completion replaces
+pub(crate) fn word_before(text: &str, caret: usize) -> Range<usize> {
+    let end = byte_index(text, caret);
+    let start = text[..end]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| !(c.is_whitespace() || "(),;:=".contains(c)))
+        .last()
+        .map_or(end, |(byte, _)| byte);
+    let start_index = text[..start].chars().count();
+    start_index..start_index + text[start..end].chars().count()
+}
+
+/// The characters `selection` covers
+pub(crate) fn selected_text(text: &str, selection: Selection) -> String {
+    let range = selection.range();
+    text.chars().skip(range.start).take(range.len()).collect()
+}
+
+/// Replace the characters in `range` with `with`, returning the index just
+/// after the inserted text
+pub(crate) fn replace(text: &mut String, range: Range<usize>, with: &str) -> usize {
+    let bytes = byte_index(text, range.start)..byte_index(text, range.end);
+    text.replace_range(bytes, with);
+    range.start + with.chars().count()
+}
+
+/// Lines the selection touches, as indexes into `text.split('\n')`
+fn selected_lines(text: &str, selection: Selection) -> Range<usize> {
+    let range = selection.range();
+    let first = line_and_column(text, range.start).0 - 1;
+    let mut last = line_and_column(text, range.end).0 - 1;
+    // A selection ending at the start of a line doesn't include that line
+    if last > first && line_and_column(text, range.end).1 == 1 {
+        last -= 1;
+    }
+    first..last + 1
+}
+
+/// Characters in `lines` plus the newline after each
+fn span(lines: &[&str]) -> isize {
+    lines
+        .iter()
+        .map(|line| line.chars().count() + 1)
+        .sum::<usize>() as isize
+}
+
+/// Copy the selected lines below themselves and select the copy
+pub(crate) fn duplicate_lines(text: &mut String, selection: Selection) -> Selection {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    let chosen = selected_lines(text, selection);
+    let block = lines[chosen.clone()].to_vec();
+    let shift = span(&block);
+    lines.splice(chosen.end..chosen.end, block);
+    *text = lines.join("\n");
+    selection.shifted(shift)
+}
+
+/// Remove the selected lines, leaving the caret where they started
+pub(crate) fn delete_lines(text: &mut String, selection: Selection) -> Selection {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    let chosen = selected_lines(text, selection);
+    let start = span(&lines[..chosen.start]) as usize;
+    lines.drain(chosen);
+    if lines.is_empty() {
+        lines.push("");
+    }
+    *text = lines.join("\n");
+    Selection::caret(start.min(text.chars().count()))
+}
+
+/// Swap the selected lines with the line above or below them. Nothing
+/// happens at the top or bottom of the code.
+pub(crate) fn move_lines(text: &mut String, selection: Selection, up: bool) -> Selection {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    let chosen = selected_lines(text, selection);
+    let (neighbour, shift) = if up {
+        if chosen.start == 0 {
+            return selection;
+        }
+        (
+            chosen.start - 1,
+            -span(&lines[chosen.start - 1..chosen.start]),
+        )
+    } else {
+        if chosen.end >= lines.len() {
+            return selection;
+        }
+        (chosen.end, span(&lines[chosen.end..chosen.end + 1]))
+    };
+    let line = lines.remove(neighbour);
+    let to = if up { chosen.end - 1 } else { chosen.start };
+    lines.insert(to, line);
+    *text = lines.join("\n");
+    selection.shifted(shift)
+}
+
+/// The bracket at `caret`, or just before it, and the one it pairs with
+pub(crate) fn matching_bracket(
+    text: &str,
+    caret: usize,
+    pairs: &[(char, char)],
+) -> Option<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    for at in [Some(caret), caret.checked_sub(1)].into_iter().flatten() {
+        let Some(&c) = chars.get(at) else {
+            continue;
+        };
+        for &(open, close) in pairs {
+            let (forward, other) = match c {
+                _ if c == open => (true, close),
+                _ if c == close => (false, open),
+                _ => continue,
+            };
+            let mut depth = 0;
+            let mut scan: Box<dyn Iterator<Item = usize>> = if forward {
+                Box::new(at..chars.len())
+            } else {
+                Box::new((0..=at).rev())
+            };
+            if let Some(found) = scan.find(|&i| {
+                if chars[i] == c {
+                    depth += 1;
+                } else if chars[i] == other {
+                    depth -= 1;
+                }
+                depth == 0
+            }) {
+                return Some((at, found));
+            }
+        }
+    }
+    None
+}
+
+/// `line` without its comment and with string contents blanked out
+fn code_part(line: &str, comments: &[&str]) -> String {
+    let mut code = String::new();
+    let mut in_string = false;
+    for (byte, c) in line.char_indices() {
+        let rest = &line[byte..];
+        if !in_string
+            && comments
+                .iter()
+                .any(|prefix| rest.starts_with(prefix) || rest == prefix.trim_end())
+        {
+            break;
+        }
+        if c == '"' {
+            in_string = !in_string;
+        }
+        code.push(if in_string { ' ' } else { c });
+    }
+    code
+}
+
+/// How many blocks `line` opens, less the ones it closes
+fn block_change(line: &str, backend: &dyn LanguageBackend) -> isize {
+    let code = code_part(line, backend.comment_prefixes());
+    let mut change = 0;
+    for c in code.chars() {
+        for &(open, close) in backend.brackets() {
+            change += (c == open) as isize - (c == close) as isize;
+        }
+    }
+    for word in code.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        let is = |words: &[&str]| words.iter().any(|w| w.eq_ignore_ascii_case(word));
+        change += is(backend.block_openers()) as isize - is(backend.block_closers()) as isize;
+    }
+    change
+}
+
+/// Whether `line` starts, after any line number, by closing a block
+fn starts_with_closer(line: &str, backend: &dyn LanguageBackend) -> bool {
+    let code = code_part(line, backend.comment_prefixes());
+    let code = code
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit());
+    let code = code.trim_start();
+    let word = code
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or("");
+    backend
+        .block_closers()
+        .iter()
+        .any(|closer| closer.eq_ignore_ascii_case(word))
+        || backend
+            .brackets()
+            .iter()
+            .any(|&(_, close)| code.starts_with(close))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+/// Indent the line just started at `caret` to match the one before it,
+/// a step further inside a block. A line that closed a block is moved
+/// back a step first. Returns where the caret ends up.
+pub(crate) fn auto_indent(
+    text: &mut String,
+    caret: usize,
+    backend: &dyn LanguageBackend,
+    step: usize,
+) -> usize {
+    let caret_byte = byte_index(text, caret);
+    let Some(finished_end) = text[..caret_byte].rfind('\n') else {
+        return caret;
+    };
+    let finished_start = text[..finished_end].rfind('\n').map_or(0, |i| i + 1);
+    let finished = &text[finished_start..finished_end];
+    let mut indent = indent_of(finished);
+    let mut change = block_change(finished, backend);
+
+    let mut dedent = 0;
+    if starts_with_closer(finished, backend) {
+        change += 1; // the closer is dealt with by moving its line back
+        if finished_start > 0 {
+            let above_start = text[..finished_start - 1].rfind('\n').map_or(0, |i| i + 1);
+            let above = &text[above_start..finished_start - 1];
+            let opens = block_change(above, backend) > 0;
+            let expected = indent_of(above) + if opens { step } else { 0 };
+            if indent == expected {
+                dedent = step.min(indent);
+            }
+        }
+    }
+    indent -= dedent;
+    let new_indent = indent + if change > 0 { step } else { 0 };
+
+    text.insert_str(caret_byte, &" ".repeat(new_indent));
+    text.replace_range(finished_start..finished_start + dedent, "");
+    caret + new_indent - dedent
+}
+
+/// Character index where line `line` (0-based) starts, or the end of `text`
+/// for a line past the last
+pub(crate) fn line_start(text: &str, line: usize) -> usize {
+    let start: usize = text
+        .split('\n')
+        .take(line)
+        .map(|line| line.chars().count() + 1)
+        .sum();
+    start.min(text.chars().count())
+}
+
+/// The whole of line `line` (1-based), selected
+pub(crate) fn line_selection(text: &str, line: usize) -> Selection {
+    let index = line.saturating_sub(1);
+    let start = line_start(text, index);
+    let length = text
+        .split('\n')
+        .nth(index)
+        .map_or(0, |line| line.chars().count());
+    Selection {
+        anchor: start,
+        head: start + length,
+    }
+}
+
+/// Character indices in `text` of `span`, bytes of line `line` (1-based),
+/// cut short at the end of the line after it has been edited
+pub(crate) fn span_characters(text: &str, line: usize, span: &Range<usize>) -> Range<usize> {
+    let index = line.saturating_sub(1);
+    let start = line_start(text, index);
+    let content = text.split('\n').nth(index).unwrap_or("");
+    let characters = |bytes: usize| {
+        content
+            .char_indices()
+            .take_while(|&(byte, _)| byte < bytes)
+            .count()
+    };
+    start + characters(span.start)..start + characters(span.end)
+}
+
+/// A wavy line along the bottom of `row` from `left` to `right`, under the
+/// token a parse error points at
+fn paint_squiggle(ui: &egui::Ui, row: egui::Rect, left: f32, right: f32, color: egui::Color32) {
+    let (step, height) = (3.0, 2.0);
+    let bottom = row.bottom() - 1.0;
+    let points: Vec<egui::Pos2> = (0..)
+        .map(|n| left + n as f32 * step)
+        .take_while(|&x| x < right + step)
+        .enumerate()
+        .map(|(n, x)| egui::pos2(x.min(right), bottom - height * (n % 2) as f32))
+        .collect();
+    ui.painter()
+        .add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+}
+
+/// The ribbon marking a bookmarked line, in the gutter `cell` beside it
+pub(crate) fn paint_bookmark(ui: &egui::Ui, cell: egui::Rect, color: egui::Color32) {
+    let ribbon =
+        egui::Rect::from_center_size(cell.center(), egui::vec2(6.0, cell.height().min(14.0)));
+    ui.painter().rect_filled(ribbon, 1.5, color);
+}
+
+/// Words in the code part of `line`
+fn words_of(line: &str, backend: &dyn LanguageBackend) -> Vec<String> {
+    code_part(line, backend.comment_prefixes())
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_uppercase())
+        .collect()
+}
+
+/// Blocks the editor can fold, as the lines from the one opening each block
+/// to the one closing it. Loops and the language's other blocks count, and
+/// so does a subroutine, from the line a call goes to down to its return.
+pub(crate) fn fold_regions(text: &str, backend: &dyn LanguageBackend) -> Vec<Range<usize>> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut regions = Vec::new();
+    let mut open = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let change = block_change(line, backend);
+        for _ in change..0 {
+            match open.pop() {
+                Some(start) if start < index => regions.push(start..index + 1),
+                _ => {}
+            }
+        }
+        for _ in 0..change {
+            open.push(index);
+        }
+    }
+
+    if let Some((call, ret)) = backend.subroutine_keywords() {
+        let words: Vec<Vec<String>> = lines.iter().map(|line| words_of(line, backend)).collect();
+        let targets: Vec<&String> = words
+            .iter()
+            .flat_map(|line| line.windows(2))
+            .filter(|pair| pair[0] == call)
+            .map(|pair| &pair[1])
+            .collect();
+        for (start, line) in words.iter().enumerate() {
+            // A line number, or a label
+            if !line.first().is_some_and(|label| targets.contains(&label)) {
+                continue;
+            }
+            let end = (start..lines.len()).find(|&end| words[end].iter().any(|word| word == ret));
+            match end {
+                Some(end) if end > start => regions.push(start..end + 1),
+                _ => {}
+            }
+        }
+    }
+    regions.sort_by_key(|region| (region.start, std::cmp::Reverse(region.end)));
+    regions.dedup_by_key(|region| region.start);
+    regions
+}
+
+/// The lines hidden by folding the blocks that start on `folded` lines
+pub(crate) fn hidden_lines(
+    regions: &[Range<usize>],
+    folded: &BTreeSet<usize>,
+) -> Vec<Range<usize>> {
+    regions
+        .iter()
+        .filter(|region| folded.contains(&region.start))
+        .map(|region| region.start + 1..region.end)
+        .collect()
+}
+
+/// Where the code in `line` starts, after its indentation and any line number
+fn code_start(line: &str) -> usize {
+    let indent = indent_of(line);
+    let rest = &line[indent..];
+    let after_number = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    let after_space = after_number.trim_start();
+    if after_number.len() < rest.len() && after_space.len() < after_number.len() {
+        line.len() - after_space.len()
+    } else {
+        indent
+    }
+}
+
+/// Length of the comment mark at the start of `code` and the space after it
+fn comment_mark(code: &str, prefixes: &[&str]) -> Option<usize> {
+    prefixes.iter().find_map(|prefix| {
+        let mark = prefix.trim_end();
+        let found = code
+            .get(..mark.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(mark));
+        found.then(|| mark.len() + code[mark.len()..].starts_with(' ') as usize)
+    })
+}
+
+/// Comment out the selected lines with the first of `prefixes`, or take the
+/// comments off if every line already has one. Blank lines are left alone,
+/// and BASIC line numbers stay in front so the line keeps its place.
+pub(crate) fn toggle_line_comments(
+    text: &mut String,
+    selection: Selection,
+    prefixes: &[&str],
+) -> Selection {
+    let Some(first) = prefixes.first() else {
+        return selection;
+    };
+    let chosen = selected_lines(text, selection);
+    let lines: Vec<&str> = text.split('\n').collect();
+    let code_lines = || {
+        lines[chosen.clone()]
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+    };
+    if code_lines().next().is_none() {
+        return selection;
+    }
+    let uncomment =
+        code_lines().all(|line| comment_mark(&line[code_start(line)..], prefixes).is_some());
+    let mark = format!("{} ", first.trim_end());
+
+    // Each change as the character it happens at and how many are added
+    let mut changes: Vec<(usize, isize)> = Vec::new();
+    let mut result = Vec::new();
+    let mut line_start = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let start = code_start(line);
+        let at = line_start + line[..start].chars().count();
+        line_start += line.chars().count() + 1;
+        if !chosen.contains(&index) || line.trim().is_empty() {
+            result.push(line.to_string());
+        } else if uncomment {
+            let length = comment_mark(&line[start..], prefixes).unwrap_or(0);
+            changes.push((at, -(length as isize)));
+            result.push(format!("{}{}", &line[..start], &line[start + length..]));
+        } else {
+            changes.push((at, mark.chars().count() as isize));
+            result.push(format!("{}{}{}", &line[..start], mark, &line[start..]));
+        }
+    }
+    *text = result.join("\n");
+
+    let moved = |index: usize| {
+        let shift: isize = changes
+            .iter()
+            .filter(|&&(at, _)| at <= index)
+            .map(|&(at, added)| added.max(at as isize - index as isize))
+            .sum();
+        index.saturating_add_signed(shift)
+    };
+    Selection {
+        anchor: moved(selection.anchor),
+        head: moved(selection.head),
+    }
+}
+
+/// Take a typed character that came with Ctrl held, such as the `/` of
+/// Ctrl+/, which egui reports as text rather than as a key
+pub(crate) fn consume_command_text(input: &mut egui::InputState, text: &str) -> bool {
+    if !input.modifiers.command {
+        return false;
+    }
+    let count = input.events.len();
+    input
+        .events
+        .retain(|event| !matches!(event, egui::Event::Text(typed) if typed == text));
+    input.events.len() < count
+}
+
+/// How an editor pane colours code
+pub(crate) struct CodeStyle {
+    highlighting: bool,
+    keywords: Vec<String>,
+    comments: &'static [&'static str],
+    palette: &'static theme::Palette,
+    find: Option<regex::Regex>, // every match is marked while Find/Replace is open
+    hidden: Vec<Range<usize>>,  // lines of folded blocks
+}
+
+impl CodeStyle {
+    /// `code` laid out for `TextEdit::layouter`, reusing the lines in `cache`
+    pub fn layout(
+        &self,
+        ui: &egui::Ui,
+        code: &str,
+        wrap_width: f32,
+        cache: &mut HighlightCache,
+    ) -> Arc<egui::Galley> {
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let palette = self.palette;
+        let mut job = if self.highlighting {
+            highlight::layout_job(code, cache, &self.keywords, self.comments, palette, font_id)
+        } else {
+            egui::text::LayoutJob::simple(code.to_string(), font_id, palette.text, f32::INFINITY)
+        };
+        if let Some(find) = &self.find {
+            let matches: Vec<Range<usize>> = find
+                .find_iter(code)
+                .map(|found| found.range())
+                .filter(|range| !range.is_empty())
+                .collect();
+            highlight::mark_ranges(&mut job, &matches, palette.find_match);
+        }
+        highlight::hide_lines(&mut job, code, &self.hidden);
+        job.wrap.max_width = wrap_width;
+        ui.fonts(|fonts| fonts.layout_job(job))
+    }
+}
+
+impl TimeWarpApp {
+    /// How code in `language` is coloured with the current settings and search
+    pub(crate) fn code_style(&self, language: Language) -> CodeStyle {
+        let backend = language.backend();
+        CodeStyle {
+            highlighting: self.syntax_highlighting_enabled,
+            keywords: backend.keywords().iter().map(|k| k.to_string()).collect(),
+            comments: backend.comment_prefixes(),
+            palette: self.palette(),
+            find: (self.show_find_replace && !self.find_text.is_empty())
+                .then(|| search::pattern(&self.find_text, self.search).ok())
+                .flatten(),
+            hidden: Vec::new(),
+        }
+    }
+
+    /// The editable code view. Tab types `tab_width` spaces rather than
+    /// moving focus to the next widget. Reports the caret and selection
+    /// back to `self.selection`, and moves them when `pending_selection` is set.
+    /// New lines are indented and the bracket at the caret is matched up
+    /// following the rules of the current language, and blocks can be
+    /// folded from the gutter, where strict mode marks what it found.
+    pub(crate) fn code_text_edit(&mut self, ui: &mut egui::Ui) -> egui::Response {
+        // Colour the code inside the editor itself
+        let backend = self.language.backend();
+        let palette = self.palette();
+        let regions = fold_regions(&self.code, &*backend);
+        let mut style = self.code_style(self.language);
+        style.hidden = hidden_lines(&regions, &self.folded_lines);
+        let cache = &mut self.highlight_cache;
+        let mut layouter =
+            |ui: &egui::Ui, code: &str, wrap_width: f32| style.layout(ui, code, wrap_width, cache);
+
+        let enter = ui.input(|i| i.key_pressed(egui::Key::Enter));
+        let line_count = self.code.matches('\n').count();
+        let edited_line = line_and_column(&self.code, self.selection.range().start).0 - 1;
+        let (gutter, mut output) = ui
+            .horizontal_top(|ui| {
+                let (gutter, _) =
+                    ui.allocate_exact_size(egui::vec2(48.0, 0.0), egui::Sense::hover());
+                let output = egui::TextEdit::multiline(&mut self.code)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(20)
+                    .lock_focus(true)
+                    .layouter(&mut layouter)
+                    .show(ui);
+                (gutter, output)
+            })
+            .inner;
+        if let Some(range) = output.state.ccursor_range() {
+            self.selection = Selection::from_egui(range);
+        }
+        if output.response.has_focus() && self.selection.is_empty() {
+            if let Some((at, other)) =
+                matching_bracket(&self.code, self.selection.head, backend.brackets())
+            {
+                for index in [at, other] {
+                    let galley = &output.galley;
+                    let left = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index)));
+                    let right =
+                        galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index + 1)));
+                    let rect = left
+                        .union(egui::Rect::from_min_max(right.min, right.min))
+                        .translate(output.text_draw_pos.to_vec2());
+                    ui.painter()
+                        .rect_stroke(rect, 2.0, egui::Stroke::new(1.0, palette.bracket));
+                }
+            }
+        }
+        if output.response.changed() && self.code.contains('\t') {
+            let cursor = crate::settings::expand_tabs(
+                &mut self.code,
+                self.settings.tab_width,
+                self.selection.head,
+            );
+            self.pending_selection = Some(Selection::caret(cursor));
+        }
+        let caret = self.pending_selection.unwrap_or(self.selection).head;
+        if output.response.changed()
+            && enter
+            && self.selection.is_empty()
+            && caret > 0
+            && self.code.chars().nth(caret - 1) == Some('\n')
+        {
+            let caret = auto_indent(&mut self.code, caret, &*backend, self.settings.tab_width);
+            self.pending_selection = Some(Selection::caret(caret));
+        }
+        if let Some(selection) = self.pending_selection.take() {
+            output.state.set_ccursor_range(Some(selection.to_egui()));
+            output.state.clone().store(ui.ctx(), output.response.id);
+            // Bring the new selection into view, e.g. after Find jumps to it
+            output.response.request_focus();
+            let galley = &output.galley;
+            let caret = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(selection.head)));
+            ui.scroll_to_rect(caret.translate(output.text_draw_pos.to_vec2()), None);
+            self.selection = selection;
+            ui.ctx().request_repaint();
+        }
+        (self.cursor_line, self.cursor_column) = line_and_column(&self.code, self.selection.head);
+
+        if output.response.changed() {
+            // Folds, bookmarks and diagnostics below the edit move with their lines
+            let added = self.code.matches('\n').count() as isize - line_count as isize;
+            let shift = |line: usize| match line > edited_line {
+                true => line.saturating_add_signed(added),
+                false => line,
+            };
+            self.folded_lines = self.folded_lines.iter().map(|&line| shift(line)).collect();
+            if let Some(bookmarks) = self.bookmarks.get_mut(&self.document_name()) {
+                for bookmark in bookmarks.iter_mut() {
+                    *bookmark = shift(*bookmark as usize - 1) as u32 + 1;
+                }
+                bookmarks.dedup();
+            }
+            for diagnostic in &mut self.diagnostics {
+                // What a span pointed at on the edited line may have moved
+                if diagnostic.line - 1 == edited_line {
+                    diagnostic.span = None;
+                }
+                diagnostic.line = shift(diagnostic.line - 1) + 1;
+            }
+            // The last run's profile and coverage describe the code before the edit
+            #[cfg(feature = "profiler")]
+            self.profile_results.clear();
+            self.coverage = None;
+        }
+        // The last run's marks on the left of the gutter, then diagnostics,
+        // bookmarks and fold arrows
+        let quarter = gutter.width() / 4.0;
+        let column = |n: f32| {
+            let left = gutter.left() + quarter * n;
+            egui::Rect::from_x_y_ranges(left..=left + quarter, gutter.y_range())
+        };
+        let (runs, problems, marks, folds) = (column(0.0), column(1.0), column(2.0), column(3.0));
+        self.paint_run_marks(ui, runs, &output);
+        self.paint_diagnostics(ui, problems, &output);
+        for line in self.current_bookmarks() {
+            let index = line_start(&self.code, line as usize - 1);
+            let galley = &output.galley;
+            let row = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index)));
+            // A line folded away has no height to mark
+            if row.height() >= 1.0 {
+                let row = row.translate(output.text_draw_pos.to_vec2());
+                let cell = egui::Rect::from_x_y_ranges(marks.x_range(), row.y_range());
+                paint_bookmark(ui, cell, palette.bookmark);
+            }
+        }
+        self.paint_folds(ui, folds, &output, &*backend);
+        output.response
+    }
+
+    /// A strip beside each line the last run has something to say about,
+    /// saying what on hover
+    fn paint_run_marks(
+        &self,
+        ui: &mut egui::Ui,
+        gutter: egui::Rect,
+        output: &egui::text_edit::TextEditOutput,
+    ) {
+        let galley = &output.galley;
+        for (line, color, hover) in self.run_marks() {
+            let index = line_start(&self.code, line - 1);
+            let row = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index)));
+            if row.height() < 1.0 {
+                continue; // folded away
+            }
+            let row = row.translate(output.text_draw_pos.to_vec2());
+            let cell = egui::Rect::from_x_y_ranges(gutter.x_range(), row.y_range()).shrink(1.0);
+            ui.painter().rect_filled(cell, 2.0, color);
+            ui.interact(
+                cell,
+                output.response.id.with(("run mark", line)),
+                egui::Sense::hover(),
+            )
+            .on_hover_text(hover);
+        }
+    }
+
+    /// A dot beside each line strict mode found something on, red for an
+    /// error and amber for a warning, saying what it found on hover. A parse
+    /// error is also underlined where it stopped.
+    fn paint_diagnostics(
+        &self,
+        ui: &mut egui::Ui,
+        gutter: egui::Rect,
+        output: &egui::text_edit::TextEditOutput,
+    ) {
+        let palette = self.palette();
+        let galley = &output.galley;
+        for diagnostic in &self.diagnostics {
+            let Some(span) = &diagnostic.span else {
+                continue;
+            };
+            let characters = span_characters(&self.code, diagnostic.line, span);
+            let left = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(characters.start)));
+            let right = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(characters.end)));
+            // Folded away, or cut down to nothing by an edit
+            if left.height() < 1.0 || right.left() <= left.left() {
+                continue;
+            }
+            let row = left.translate(output.text_draw_pos.to_vec2());
+            let width = right.left() - left.left();
+            paint_squiggle(ui, row, row.left(), row.left() + width, palette.error);
+        }
+        let mut lines: Vec<usize> = self.diagnostics.iter().map(|found| found.line).collect();
+        lines.dedup();
+        for line in lines {
+            let found: Vec<_> = self
+                .diagnostics
+                .iter()
+                .filter(|found| found.line == line)
+                .collect();
+            let index = line_start(&self.code, line - 1);
+            let row = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index)));
+            if row.height() < 1.0 {
+                continue; // folded away
+            }
+            let row = row.translate(output.text_draw_pos.to_vec2());
+            let cell = egui::Rect::from_x_y_ranges(gutter.x_range(), row.y_range());
+            let error = found.iter().any(|found| found.severity == Severity::Error);
+            let color = if error {
+                palette.error
+            } else {
+                palette.warning
+            };
+            ui.painter()
+                .circle_filled(cell.center(), cell.width().min(cell.height()) * 0.3, color);
+            let messages: Vec<&str> = found.iter().map(|found| found.message.as_str()).collect();
+            ui.interact(
+                cell,
+                output.response.id.with(("diagnostic", line)),
+                egui::Sense::hover(),
+            )
+            .on_hover_text(messages.join("\n"));
+        }
+    }
+
+    /// Lines bookmarked in the file being edited, 1-based and in order
+    pub(crate) fn current_bookmarks(&self) -> Vec<u32> {
+        self.bookmarks
+            .get(&self.document_name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Bookmark the caret's line, or take its bookmark off
+    pub(crate) fn toggle_bookmark(&mut self) {
+        let line = line_and_column(&self.code, self.selection.head).0 as u32;
+        let bookmarks = self.bookmarks.entry(self.document_name()).or_default();
+        match bookmarks.binary_search(&line) {
+            Ok(index) => {
+                bookmarks.remove(index);
+            }
+            Err(index) => bookmarks.insert(index, line),
+        }
+    }
+
+    /// Move the caret to the next bookmark below it, round to the first
+    pub(crate) fn next_bookmark(&mut self) {
+        let bookmarks = self.current_bookmarks();
+        let line = line_and_column(&self.code, self.selection.head).0 as u32;
+        if let Some(&next) = bookmarks.iter().find(|&&b| b > line).or(bookmarks.first()) {
+            self.pending_selection =
+                Some(Selection::caret(line_start(&self.code, next as usize - 1)));
+        }
+    }
+
+    /// The fold arrows in the gutter beside each block, and a note of how
+    /// much is hidden after a folded one. A fold the caret moves into opens.
+    fn paint_folds(
+        &mut self,
+        ui: &mut egui::Ui,
+        gutter: egui::Rect,
+        output: &egui::text_edit::TextEditOutput,
+        backend: &dyn LanguageBackend,
+    ) {
+        let regions = fold_regions(&self.code, backend);
+        self.folded_lines
+            .retain(|line| regions.iter().any(|region| region.start == *line));
+        let caret_line = self.cursor_line - 1;
+        let galley = &output.galley;
+        let mut line_start = 0;
+        let mut line_starts = vec![0];
+        for line in self.code.split('\n') {
+            line_start += line.chars().count() + 1;
+            line_starts.push(line_start);
+        }
+        for region in &regions {
+            let mut folded = self.folded_lines.contains(&region.start);
+            if folded && caret_line > region.start && caret_line < region.end {
+                self.folded_lines.remove(&region.start);
+                ui.ctx().request_repaint();
+                continue;
+            }
+            let cursor = galley.from_ccursor(CCursor::new(line_starts[region.start]));
+            let row = galley
+                .pos_from_cursor(&cursor)
+                .translate(output.text_draw_pos.to_vec2());
+            let arrow = egui::Rect::from_center_size(
+                egui::pos2(gutter.center().x, row.center().y),
+                egui::vec2(gutter.width(), gutter.width()) * 0.8,
+            );
+            let response = ui
+                .interact(
+                    arrow,
+                    output.response.id.with(("fold", region.start)),
+                    egui::Sense::click(),
+                )
+                .on_hover_text(if folded { "Unfold" } else { "Fold" });
+            if response.clicked() {
+                folded = !folded;
+                if folded {
+                    self.folded_lines.insert(region.start);
+                    // A caret left on a hidden line would open the fold again
+                    if caret_line > region.start && caret_line < region.end {
+                        self.pending_selection =
+                            Some(Selection::caret(line_starts[region.start + 1] - 1));
+                    }
+                } else {
+                    self.folded_lines.remove(&region.start);
+                }
+            }
+            egui::collapsing_header::paint_default_icon(
+                ui,
+                if folded { 0.0 } else { 1.0 },
+                &response,
+            );
+            if folded {
+                let end = galley.pos_from_cursor(
+                    &galley.from_ccursor(CCursor::new(line_starts[region.start + 1] - 1)),
+                );
+                ui.painter().text(
+                    egui::pos2(end.max.x + output.text_draw_pos.x + 8.0, row.center().y),
+                    egui::Align2::LEFT_CENTER,
+                    format!("… {} lines", region.end - region.start - 1),
+                    egui::TextStyle::Monospace.resolve(ui.style()),
+                    self.palette().comment,
+                );
+            }
+        }
+    }
+
+    /// Fold every block in the program, or open them all again
+    pub(crate) fn fold_all(&mut self, fold: bool) {
+        self.folded_lines.clear();
+        if fold {
+            let backend = self.language.backend();
+            let regions = fold_regions(&self.code, &*backend);
+            self.folded_lines
+                .extend(regions.iter().map(|region| region.start));
+        }
+    }
+
+    /// Put `item` in place of the word before the caret
+    pub(crate) fn insert_completion(&mut self, item: &str) {
+        let word = word_before(&self.code, self.selection.head);
+        let caret = replace(&mut self.code, word, item);
+        self.pending_selection = Some(Selection::caret(caret));
+        self.show_completion = false;
+    }
+
+    /// Ctrl+/: comment or uncomment the selected lines in the current language
+    pub(crate) fn toggle_comment(&mut self) {
+        let prefixes = self.language.backend().line_comments();
+        self.edit_code(|code, at| toggle_line_comments(code, at, prefixes));
+    }
+
+    /// Apply an edit to the code at the current selection, recorded for undo
+    pub(crate) fn edit_code(&mut self, edit: impl FnOnce(&mut String, Selection) -> Selection) {
+        let selection = edit(&mut self.code, self.selection);
+        self.pending_selection = Some(selection);
+        self.selection = selection;
+        self.save_undo_state();
+        self.previous_code = self.code.clone();
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, Write};
+use std::path::Path;
+use time_warp_core::basic::detokenize;
+
+/// Somewhere program files can be opened from and saved to. A location is a
+/// path for the local backends and a URL for WebDAV. Backends are `Send` so
+/// a slow one can run on its own thread.
+pub trait FileBackend: Send {
+    fn name(&self) -> &'static str;
+    fn read(&self, location: &str) -> io::Result<String>;
+    fn write(&self, location: &str, content: &str) -> io::Result<()>;
+}
+
+/// Read a local program
+fn read_local(location: &str) -> io::Result<String> {
+    decode_program(std::fs::read(location)?)
+}
+
+/// The source text of a program file. One saved by GW-BASIC in its tokenized
+/// binary form is turned back into source, so saving it again writes plain text.
+pub fn decode_program(bytes: Vec<u8>) -> io::Result<String> {
+    if detokenize::is_tokenized(&bytes) {
+        return detokenize::detokenize(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+    }
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Plain reads and writes on the local filesystem
+pub struct LocalBackend;
+
+impl FileBackend for LocalBackend {
+    fn name(&self) -> &'static str {
+        "Local"
+    }
+
+    fn read(&self, location: &str) -> io::Result<String> {
+        read_local(location)
+    }
+
+    fn write(&self, location: &str, content: &str) -> io::Result<()> {
+        std::fs::write(location, content)
+    }
+}
+
+/// Local filesystem, but saves go to a temporary file that is flushed to the
+/// device and then renamed over the original, so pulling a USB stick out
+/// mid-save leaves either the old or the new program, never half of one.
+pub struct UsbSafeBackend;
+
+impl FileBackend for UsbSafeBackend {
+    fn name(&self) -> &'static str {
+        "Local (USB-safe)"
+    }
+
+    fn read(&self, location: &str) -> io::Result<String> {
+        read_local(location)
+    }
+
+    fn write(&self, location: &str, content: &str) -> io::Result<()> {
+        let target = Path::new(location);
+        let file_name = target
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        let temp = target.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temp, target).inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+    }
+}
+
+/// Whether a location names a network share rather than a local path
+pub fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// How file locations are mapped to backends
+pub struct BackendSettings {
+    pub usb_safe_saves: bool,
+    #[cfg(feature = "webdav")]
+    pub network_allowed: bool, // sandbox permission for WebDAV shares
+    #[cfg(feature = "webdav")]
+    pub webdav_username: String,
+    #[cfg(feature = "webdav")]
+    pub webdav_password: String,
+}
+
+impl Default for BackendSettings {
+    fn default() -> Self {
+        Self {
+            usb_safe_saves: true,
+            #[cfg(feature = "webdav")]
+            network_allowed: false,
+            #[cfg(feature = "webdav")]
+            webdav_username: String::new(),
+            #[cfg(feature = "webdav")]
+            webdav_password: String::new(),
+        }
+    }
+}
+
+impl BackendSettings {
+    /// The backend to use for `location`, refusing network shares unless the
+    /// sandbox permission has been granted
+    pub fn backend_for(&self, location: &str) -> io::Result<Box<dyn FileBackend>> {
+        if is_remote(location) {
+            self.remote_backend()
+        } else if self.usb_safe_saves {
+            Ok(Box::new(UsbSafeBackend))
+        } else {
+            Ok(Box::new(LocalBackend))
+        }
+    }
+
+    #[cfg(feature = "webdav")]
+    fn remote_backend(&self) -> io::Result<Box<dyn FileBackend>> {
+        if !self.network_allowed {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "network shares are turned off; allow them under File → Class Share",
+            ));
+        }
+        Ok(Box::new(crate::webdav::WebDavBackend {
+            username: self.webdav_username.clone(),
+            password: self.webdav_password.clone(),
+        }))
+    }
+
+    #[cfg(not(feature = "webdav"))]
+    fn remote_backend(&self) -> io::Result<Box<dyn FileBackend>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this build has no class share support (the webdav feature)",
+        ))
+    }
+
+    pub fn read(&self, location: &str) -> io::Result<String> {
+        self.backend_for(location)?.read(location)
+    }
+
+    pub fn write(&self, location: &str, content: &str) -> io::Result<()> {
+        self.backend_for(location)?.write(location, content)
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the open file is checked for changes made by other editors
+pub const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// Notices when the open file is changed on disk by another program
+pub struct FileWatcher {
+    pub enabled: bool,
+    known_modified: Option<SystemTime>,
+    saved_content: String,
+    last_check: Option<Instant>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            known_modified: None,
+            saved_content: String::new(),
+            last_check: None,
+        }
+    }
+
+    /// Remember `content` as what is on disk at `path`, after opening or saving it
+    pub fn track(&mut self, path: &Path, content: &str) {
+        self.known_modified = modified_time(path);
+        self.saved_content = content.to_string();
+    }
+
+    /// Whether the editor buffer differs from the last version on disk
+    pub fn is_dirty(&self, buffer: &str) -> bool {
+        buffer != self.saved_content
+    }
+
+    /// The file's new content if another program changed it since we last
+    /// looked. Checks at most once per `POLL_INTERVAL`.
+    pub fn check(&mut self, path: &Path, now: Instant) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        if let Some(last) = self.last_check {
+            if now.duration_since(last) < POLL_INTERVAL {
+                return None;
+            }
+        }
+        self.last_check = Some(now);
+        self.check_now(path)
+    }
+
+    /// Like `check`, but looks straight away, even with watching turned off.
+    /// Used before saving so a newer version on disk isn't overwritten.
+    pub fn check_now(&mut self, path: &Path) -> Option<String> {
+        let modified = modified_time(path)?;
+        if self.known_modified == Some(modified) {
+            return None;
+        }
+        let content = std::fs::read_to_string(path).ok()?;
+        self.known_modified = Some(modified);
+        // Touched but not changed, e.g. our own save or an editor's autosave
+        if content == self.saved_content {
+            return None;
+        }
+        Some(content)
+    }
+
+    /// Adopt content read from disk as the saved version
+    pub fn accept(&mut self, content: &str) {
+        self.saved_content = content.to_string();
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
+
+/// Editor command to suggest by default: $VISUAL, then $EDITOR, then VS Code
+pub fn default_editor_command() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| "code".to_string())
+}
+
+/// Launch `command` (program followed by any arguments) on `path`
+pub fn open_in_editor(command: &str, path: &Path) -> std::io::Result<()> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no editor configured")
+    })?;
+    std::process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+}
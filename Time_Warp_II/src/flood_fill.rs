@@ -0,0 +1,126 @@
+//! FILL for turtle graphics. The drawing is rasterized, the area of matching
+//! colour around the turtle is flooded, and the flooded pixels are turned
+//! back into rectangles in turtle coordinates, so a fill zooms, replays and
+//! exports like every other op.
+
+use crate::canvas_export::{self, Frame, Image};
+use crate::turtle::{self, TurtleOp};
+use eframe::egui;
+use std::collections::BTreeMap;
+
+/// Pixels per turtle unit the drawing is rasterized at
+const RESOLUTION: f32 = 2.0;
+
+/// Longest side of the raster; bigger drawings are filled more coarsely
+const MAX_SIDE: f32 = 1000.0;
+
+/// How far each channel may be from the colour under the turtle and still be
+/// flooded, so the anti-aliased inside edge of a line doesn't leave a halo
+const TOLERANCE: u8 = 40;
+
+/// Fill the closed area around `seed` with `color`. `None` when the turtle
+/// isn't inside a closed shape, because the fill would leak off the drawing.
+pub fn fill(ops: &[TurtleOp], seed: egui::Pos2, color: egui::Color32) -> Option<TurtleOp> {
+    let bounds = turtle::bounds(ops)?;
+    if !bounds.contains(seed) {
+        return None;
+    }
+    // Room for the widest pen's strokes, plus a clear border a leak will reach
+    let widest = ops
+        .iter()
+        .filter_map(|op| match op {
+            TurtleOp::PenWidth(width) => Some(*width),
+            _ => None,
+        })
+        .fold(turtle::DEFAULT_PEN_WIDTH, f32::max);
+    let area = bounds.expand(widest / 2.0 + 2.0);
+    let scale = RESOLUTION.min(MAX_SIDE / area.width().max(area.height()));
+    let frame = Frame::covering(area, scale);
+
+    let image = canvas_export::render(ops, &frame);
+    let start = frame.to_pixel(seed);
+    let flooded = flood(&image, start.x as u32, start.y as u32)?;
+    let spans = rectangles(&flooded, image.width)
+        .into_iter()
+        .map(|rect| egui::Rect::from_min_max(frame.to_turtle(rect.min), frame.to_turtle(rect.max)))
+        .collect();
+    Some(TurtleOp::Fill { seed, color, spans })
+}
+
+/// Pixels 4-connected to (x, y) that match its colour, or `None` if they
+/// reach the edge of the image
+fn flood(image: &Image, x: u32, y: u32) -> Option<Vec<bool>> {
+    let (width, height) = (image.width, image.height);
+    let target = image.pixel(x.min(width - 1), y.min(height - 1)).to_array();
+    let matches = |x: u32, y: u32| {
+        image
+            .pixel(x, y)
+            .to_array()
+            .iter()
+            .zip(target)
+            .all(|(channel, wanted)| channel.abs_diff(wanted) <= TOLERANCE)
+    };
+
+    let mut flooded = vec![false; (width * height) as usize];
+    let mut todo = vec![(x.min(width - 1), y.min(height - 1))];
+    while let Some((x, y)) = todo.pop() {
+        let i = (y * width + x) as usize;
+        if flooded[i] || !matches(x, y) {
+            continue;
+        }
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            return None;
+        }
+        flooded[i] = true;
+        todo.extend([(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]);
+    }
+    Some(flooded)
+}
+
+/// Cover the flooded pixels with rectangles: runs along each row, with runs
+/// that line up in consecutive rows merged into one taller rectangle
+fn rectangles(flooded: &[bool], width: u32) -> Vec<egui::Rect> {
+    let mut done = Vec::new();
+    // Runs still growing downwards: (start x, end x) -> first row
+    let mut open: BTreeMap<(u32, u32), u32> = BTreeMap::new();
+
+    for (y, row) in flooded.chunks(width as usize).enumerate() {
+        let y = y as u32;
+        let mut runs = Vec::new();
+        let mut x = 0;
+        while x < width {
+            if row[x as usize] {
+                let start = x;
+                while x < width && row[x as usize] {
+                    x += 1;
+                }
+                runs.push((start, x));
+            } else {
+                x += 1;
+            }
+        }
+
+        let mut still_open = BTreeMap::new();
+        for run in runs {
+            let top = open.remove(&run).unwrap_or(y);
+            still_open.insert(run, top);
+        }
+        for ((start, end), top) in std::mem::take(&mut open) {
+            done.push(pixel_rect(start, end, top, y));
+        }
+        open = still_open;
+    }
+    let bottom = (flooded.len() / width.max(1) as usize) as u32;
+    done.extend(
+        open.into_iter()
+            .map(|((start, end), top)| pixel_rect(start, end, top, bottom)),
+    );
+    done
+}
+
+fn pixel_rect(start: u32, end: u32, top: u32, bottom: u32) -> egui::Rect {
+    egui::Rect::from_min_max(
+        egui::pos2(start as f32, top as f32),
+        egui::pos2(end as f32, bottom as f32),
+    )
+}
@@ -0,0 +1,107 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::path::{Path, PathBuf};
+
+/// Name under which the chosen code font is registered with egui
+const CODE_FONT: &str = "code_font";
+
+/// A font file found on this computer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InstalledFont {
+    pub name: String, // file name without the extension
+    pub path: PathBuf,
+}
+
+/// Folders the platforms keep fonts in
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+        PathBuf::from("/Library/Fonts"),
+        PathBuf::from("/System/Library/Fonts"),
+    ];
+    if let Some(windows) = std::env::var_os("WINDIR") {
+        dirs.push(PathBuf::from(windows).join("Fonts"));
+    }
+    if let Some(base) = directories::BaseDirs::new() {
+        let home = base.home_dir();
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join("Library/Fonts"));
+    }
+    dirs
+}
+
+/// TrueType and OpenType fonts in `dirs` and their subfolders, by name
+pub(crate) fn scan(dirs: &[PathBuf]) -> Vec<InstalledFont> {
+    let mut fonts = Vec::new();
+    let mut pending: Vec<PathBuf> = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let is_font = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("ttf") || ext.eq_ignore_ascii_case("otf")
+                });
+            if let (true, Some(name)) = (is_font, path.file_stem().and_then(|s| s.to_str())) {
+                fonts.push(InstalledFont {
+                    name: name.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+    fonts.sort_by_key(|font| font.name.to_lowercase());
+    fonts.dedup_by(|a, b| a.name == b.name);
+    fonts
+}
+
+/// egui's fonts with `code_font` in front of the monospace family. The
+/// built-in monospace fonts stay behind it for characters the font lacks.
+pub(crate) fn definitions(code_font: Option<&Path>) -> Result<egui::FontDefinitions, String> {
+    let mut fonts = egui::FontDefinitions::default();
+    if let Some(path) = code_font {
+        let data = std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        fonts
+            .font_data
+            .insert(CODE_FONT.to_string(), egui::FontData::from_owned(data));
+        fonts
+            .families
+            .entry(egui::FontFamily::Monospace)
+            .or_default()
+            .insert(0, CODE_FONT.to_string());
+    }
+    Ok(fonts)
+}
+
+impl TimeWarpApp {
+    /// Load the code font from the settings if it changed since it was last applied
+    pub(crate) fn apply_code_font(&mut self, ctx: &egui::Context) {
+        if self.applied_code_font.as_ref() == Some(&self.settings.code_font) {
+            return;
+        }
+        self.applied_code_font = Some(self.settings.code_font.clone());
+        match definitions(self.settings.code_font.as_deref()) {
+            Ok(fonts) => ctx.set_fonts(fonts),
+            Err(err) => {
+                self.show_error(format!("Could not load the code font: {}", err));
+                ctx.set_fonts(egui::FontDefinitions::default());
+            }
+        }
+    }
+
+    /// The fonts offered in Settings, found the first time they are needed
+    pub(crate) fn installed_fonts(&mut self) -> &[InstalledFont] {
+        self.installed_fonts
+            .get_or_insert_with(|| scan(&font_dirs()))
+    }
+}
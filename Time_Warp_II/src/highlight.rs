@@ -0,0 +1,212 @@
+use crate::theme::Palette;
+use eframe::egui;
+use egui::text::{LayoutJob, TextFormat};
+use egui::Color32;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+const OPERATORS: &str = "+-*/=<>!&|^%";
+const BRACKETS: &str = "(){}[]";
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Split one line of code into coloured pieces. The pieces always add up
+/// to exactly `line`, so they can be laid out in an editable text box.
+pub(crate) fn highlight_line(
+    line: &str,
+    keywords: &[String],
+    comments: &[&str],
+    palette: &Palette,
+) -> Vec<(String, Color32)> {
+    let mut pieces: Vec<(String, Color32)> = Vec::new();
+    let mut push = |text: &str, color: Color32| match pieces.last_mut() {
+        Some((last, last_color)) if *last_color == color => last.push_str(text),
+        _ => pieces.push((text.to_string(), color)),
+    };
+
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let c = rest.chars().next().unwrap_or(' ');
+
+        // A comment runs to the end of the line. "REM" on its own counts too.
+        if comments
+            .iter()
+            .any(|prefix| rest.starts_with(prefix) || rest == prefix.trim_end())
+        {
+            push(rest, palette.comment);
+            break;
+        }
+
+        let (length, color) = if c == '"' {
+            let length = rest[1..].find('"').map_or(rest.len(), |close| close + 2);
+            (length, palette.string)
+        } else if c.is_ascii_digit() {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            (length, palette.number)
+        } else if OPERATORS.contains(c) {
+            let length = match rest[1..].chars().next() {
+                Some(next) if OPERATORS.contains(next) => 2,
+                _ => 1,
+            };
+            (length, palette.operator)
+        } else if BRACKETS.contains(c) {
+            (1, palette.bracket)
+        } else if is_word_char(c) {
+            let length = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            let word = rest[..length].to_uppercase();
+            let color = if keywords
+                .iter()
+                .any(|keyword| keyword.to_uppercase() == word)
+            {
+                palette.keyword
+            } else {
+                palette.text
+            };
+            (length, color)
+        } else {
+            (c.len_utf8(), palette.text)
+        };
+        push(&rest[..length], color);
+        i += length;
+    }
+
+    if pieces.is_empty() {
+        pieces.push((line.to_string(), palette.text));
+    }
+    pieces
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Coloured lines from earlier frames, kept by the hash of their text, so
+/// only lines that were edited are split up again
+#[derive(Debug, Default)]
+pub(crate) struct HighlightCache {
+    context: u64, // the language and theme the lines were coloured for
+    lines: HashMap<u64, Vec<(String, Color32)>>,
+    pub misses: usize, // lines highlighted from scratch, for tests
+}
+
+impl HighlightCache {
+    /// Forget every line if the keywords, comments or colours changed
+    fn set_context(&mut self, keywords: &[String], comments: &[&str], palette: &Palette) {
+        let context = hash_of((keywords, comments, palette));
+        if context != self.context {
+            self.context = context;
+            self.lines.clear();
+        }
+    }
+
+    /// `line` highlighted, from the cache when it has been seen before
+    pub fn line(
+        &mut self,
+        line: &str,
+        keywords: &[String],
+        comments: &[&str],
+        palette: &Palette,
+    ) -> &[(String, Color32)] {
+        self.set_context(keywords, comments, palette);
+        let misses = &mut self.misses;
+        self.lines.entry(hash_of(line)).or_insert_with(|| {
+            *misses += 1;
+            highlight_line(line, keywords, comments, palette)
+        })
+    }
+}
+
+/// Give the text in each of `ranges` (byte ranges, in order) a `background`
+pub(crate) fn mark_ranges(job: &mut LayoutJob, ranges: &[Range<usize>], background: Color32) {
+    format_ranges(job, ranges, |format| format.background = background);
+}
+
+/// Squash `lines` (line indexes into `code`) to nothing, for folded blocks.
+/// The text stays in the job, so the editor's text and layout still agree.
+pub(crate) fn hide_lines(job: &mut LayoutJob, code: &str, lines: &[Range<usize>]) {
+    let line_ends: Vec<usize> = code
+        .match_indices('\n')
+        .map(|(byte, _)| byte)
+        .chain([code.len()])
+        .collect();
+    let ranges: Vec<Range<usize>> = lines
+        .iter()
+        .filter(|lines| lines.start > 0 && lines.end <= line_ends.len() && !lines.is_empty())
+        // From the newline before the first line, so even a blank line takes no room
+        .map(|lines| line_ends[lines.start - 1]..line_ends[lines.end - 1])
+        .collect();
+    format_ranges(job, &ranges, |format| {
+        format.line_height = Some(0.0);
+        format.color = Color32::TRANSPARENT;
+        format.background = Color32::TRANSPARENT;
+    });
+}
+
+/// Apply `change` to the text in each of `ranges`, splitting sections where
+/// a range starts or ends inside one
+fn format_ranges(job: &mut LayoutJob, ranges: &[Range<usize>], change: impl Fn(&mut TextFormat)) {
+    if ranges.is_empty() {
+        return;
+    }
+    let mut sections = Vec::new();
+    for section in std::mem::take(&mut job.sections) {
+        let mut start = section.byte_range.start;
+        let end = section.byte_range.end;
+        // Cut the section wherever a range starts or ends inside it
+        let mut cuts: Vec<usize> = ranges
+            .iter()
+            .flat_map(|range| [range.start, range.end])
+            .filter(|&cut| cut > start && cut < end)
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.push(end);
+        for cut in cuts {
+            let mut part = section.clone();
+            part.byte_range = start..cut;
+            if start != section.byte_range.start {
+                part.leading_space = 0.0;
+            }
+            if ranges.iter().any(|range| range.contains(&start)) {
+                change(&mut part.format);
+            }
+            sections.push(part);
+            start = cut;
+        }
+    }
+    job.sections = sections;
+}
+
+/// The whole program, coloured line by line, for `TextEdit::layouter`.
+/// Lines no longer in the program are dropped from `cache`.
+pub(crate) fn layout_job(
+    code: &str,
+    cache: &mut HighlightCache,
+    keywords: &[String],
+    comments: &[&str],
+    palette: &Palette,
+    font_id: egui::FontId,
+) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let mut used = HashSet::new();
+    for (index, line) in code.split('\n').enumerate() {
+        if index > 0 {
+            job.append("\n", 0.0, TextFormat::simple(font_id.clone(), palette.text));
+        }
+        used.insert(hash_of(line));
+        for (text, color) in cache.line(line, keywords, comments, palette) {
+            job.append(text, 0.0, TextFormat::simple(font_id.clone(), *color));
+        }
+    }
+    cache.lines.retain(|hash, _| used.contains(hash));
+    job
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use time_warp_core::basic::ErrorCode;
+
+/// A short explanation shown when a learner keeps hitting the same error
+#[derive(Debug, Clone, PartialEq)]
+pub struct HintCard {
+    pub category: &'static str,
+    pub error_label: &'static str, // how the error reads to the learner
+    pub topic: &'static str,
+    pub body: &'static str,
+}
+
+const HINT_CARDS: &[HintCard] = &[
+    HintCard {
+        category: "expected_token",
+        error_label: "Something is missing",
+        topic: "statements",
+        body: "Each statement has a fixed form, and a word or symbol is missing from this one. \
+               The most common are THEN after an IF condition and TO in a FOR:\n\n\
+               10 IF X > 5 THEN PRINT \"BIG\"\n\
+               20 FOR I = 1 TO 10\n\n\
+               Use = to compare (not ==), and check the keyword help for the statement's form.",
+    },
+    HintCard {
+        category: "unterminated_string",
+        error_label: "Unterminated string literal",
+        topic: "strings",
+        body: "Text must start and end with a double quote on the same line:\n\n\
+               10 PRINT \"HELLO, WORLD\"\n\n\
+               Check that every opening \" has a matching closing \".",
+    },
+    HintCard {
+        category: "next_without_for",
+        error_label: "NEXT without FOR",
+        topic: "FOR loops",
+        body: "Every NEXT closes a FOR that ran earlier:\n\n\
+               10 FOR I = 1 TO 10\n\
+               20 PRINT I\n\
+               30 NEXT I\n\n\
+               Make sure the FOR line exists and that a GOTO doesn't jump into the middle of a loop.",
+    },
+    HintCard {
+        category: "return_without_gosub",
+        error_label: "RETURN without GOSUB",
+        topic: "subroutines",
+        body: "RETURN goes back to the line after the GOSUB that called it. If the program \
+               runs into a subroutine by falling through, put an END before it:\n\n\
+               10 GOSUB 100\n\
+               20 END\n\
+               100 PRINT \"IN SUBROUTINE\"\n\
+               110 RETURN",
+    },
+    HintCard {
+        category: "type_mismatch",
+        error_label: "Type mismatch",
+        topic: "numbers and strings",
+        body: "Numbers and text can't be mixed directly. String variables end in $:\n\n\
+               10 LET NAME$ = \"ADA\"\n\
+               20 LET AGE = 36\n\
+               30 PRINT NAME$; \" IS \"; AGE",
+    },
+    HintCard {
+        category: "undefined_variable",
+        error_label: "Undefined variable",
+        topic: "variables",
+        body: "Give a variable a value before you use it:\n\n\
+               10 LET SCORE = 0\n\
+               20 LET SCORE = SCORE + 1\n\n\
+               Check the spelling - SCORE and SCOR are different variables.",
+    },
+    HintCard {
+        category: "undefined_function",
+        error_label: "Undefined function",
+        topic: "functions",
+        body: "Built-in functions need parentheses, and your own functions must be defined \
+               with DEF FN before they are used:\n\n\
+               10 DEF FNSQ(X) = X * X\n\
+               20 PRINT FNSQ(4)",
+    },
+    HintCard {
+        category: "division_by_zero",
+        error_label: "Division by zero",
+        topic: "safe division",
+        body: "Check the divisor before dividing:\n\n\
+               10 IF B = 0 THEN PRINT \"CAN'T DIVIDE BY ZERO\" : END\n\
+               20 PRINT A / B",
+    },
+    HintCard {
+        category: "index_out_of_bounds",
+        error_label: "Index out of bounds",
+        topic: "arrays",
+        body: "DIM A(10) makes room for A(0) to A(10). Keep loop counters inside that range:\n\n\
+               10 DIM A(10)\n\
+               20 FOR I = 0 TO 10\n\
+               30 LET A(I) = I * I\n\
+               40 NEXT I",
+    },
+    HintCard {
+        category: "timeout",
+        error_label: "Execution timeout",
+        topic: "loops that end",
+        body: "The program ran too many statements, which usually means a loop never stops. \
+               Make sure the loop condition eventually changes, or that a GOTO loop has a way out:\n\n\
+               10 LET I = 0\n\
+               20 LET I = I + 1\n\
+               30 IF I < 10 THEN GOTO 20",
+    },
+    HintCard {
+        category: "argument_count",
+        error_label: "Wrong number of arguments",
+        topic: "function arguments",
+        body: "Each built-in function takes a fixed number of values in its parentheses, \
+               for example LEN(A$), TAB(10) or RND(1). Check the function reference for \
+               what it expects.",
+    },
+];
+
+/// Group an error into a hint category by its code alone, so rewording a
+/// message never changes which card it gets
+pub fn categorize(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::ExpectedToken => "expected_token",
+        ErrorCode::UnterminatedString => "unterminated_string",
+        ErrorCode::NextWithoutFor => "next_without_for",
+        ErrorCode::ReturnWithoutGosub => "return_without_gosub",
+        ErrorCode::TypeMismatch => "type_mismatch",
+        ErrorCode::UndefinedVariable => "undefined_variable",
+        ErrorCode::UndefinedFunction => "undefined_function",
+        ErrorCode::DivisionByZero => "division_by_zero",
+        ErrorCode::IndexOutOfBounds => "index_out_of_bounds",
+        ErrorCode::ExecutionTimeout => "timeout",
+        ErrorCode::ArgumentCount => "argument_count",
+        code if code.is_parse() => "syntax",
+        _ => "runtime",
+    }
+}
+
+/// Hint card for a category, if one has been written
+pub fn hint_for(category: &str) -> Option<&'static HintCard> {
+    HINT_CARDS.iter().find(|card| card.category == category)
+}
+
+/// Counts repeated error categories per file and decides when a hint is due
+pub struct ErrorTracker {
+    pub threshold: usize,
+    counts: HashMap<(String, &'static str), usize>,
+}
+
+impl ErrorTracker {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Record an error for `file`. Returns the hint card and repeat count once
+    /// the same category has been seen `threshold` times; the count then starts over.
+    pub fn record(&mut self, file: &str, code: ErrorCode) -> Option<(&'static HintCard, usize)> {
+        let category = categorize(code);
+        let card = hint_for(category)?;
+
+        let count = self.counts.entry((file.to_string(), category)).or_insert(0);
+        *count += 1;
+        if *count < self.threshold.max(1) {
+            return None;
+        }
+
+        let seen = *count;
+        *count = 0;
+        Some((card, seen))
+    }
+
+    /// Forget all counts for a file, e.g. after it is closed or replaced
+    pub fn clear_file(&mut self, file: &str) {
+        self.counts.retain(|(name, _), _| name != file);
+    }
+}
@@ -0,0 +1,168 @@
+//! File → Export → Web Page: the program and its last run as one HTML file
+//! that opens in any browser. The page shows the recorded output and replays
+//! the drawing stroke by stroke. When the IDE was built with the web runtime
+//! (see build.rs), the page also carries the interpreters compiled to
+//! WebAssembly, so ▶ Run runs the program again in the browser, asking for
+//! INPUT in a dialog.
+
+use crate::canvas_export::{self, escape_xml};
+use crate::{TimeWarpApp, FIT_EXPORT_SIZE};
+use rfd::FileDialog;
+
+include!(concat!(env!("OUT_DIR"), "/web_runtime.rs"));
+
+/// The script that runs the program with the embedded runtime
+const RUNNER: &str = include_str!("web_runner.js");
+
+/// What goes on an exported page: the program and a recording of its last run
+pub(crate) struct Page<'a> {
+    pub title: &'a str,
+    pub language: &'a str,
+    pub code: &'a str,
+    pub output: &'a str,
+    pub svg: Option<String>, // the drawing, if the run drew anything
+    pub strokes_per_second: f32,
+    pub runtime: Option<&'a [u8]>, // the wasm build of time_warp_web, to run the program
+}
+
+/// One HTML file that needs nothing else: the drawing is inline SVG that a
+/// few lines of script draw again stroke by stroke, and the output and
+/// program are plain text. With a runtime, a Run button runs the program
+/// again right there.
+pub(crate) fn to_html(page: &Page) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; }}\n\
+         #drawing svg, #drawing canvas {{ max-width: 100%; height: auto; border: 1px solid #888; }}\n\
+         pre {{ background: #f4f4f4; padding: 0.75rem; overflow-x: auto; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>A {language} program made with Time Warp IDE.</p>\n",
+        title = escape_xml(page.title),
+        language = escape_xml(page.language),
+    );
+    let delay = (1000.0 / page.strokes_per_second.max(1.0)).round();
+    if page.runtime.is_some() {
+        html.push_str(&format!(
+            "<p><button id=\"run\" data-language=\"{}\" data-delay=\"{}\">▶ Run</button></p>\n",
+            escape_xml(page.language),
+            delay
+        ));
+    }
+    match &page.svg {
+        Some(svg) => html.push_str(&format!(
+            "<div id=\"drawing\">\n{}</div>\n\
+             <p id=\"replay\"><button onclick=\"replay()\">▶ Replay</button></p>\n",
+            svg
+        )),
+        None if page.runtime.is_some() => html.push_str("<div id=\"drawing\"></div>\n"),
+        None => {}
+    }
+    if !page.output.is_empty() || page.runtime.is_some() {
+        html.push_str(&format!(
+            "<h2>Output</h2>\n<pre id=\"output\">{}</pre>\n",
+            escape_xml(page.output)
+        ));
+    }
+    html.push_str(&format!(
+        "<details>\n<summary>The program</summary>\n<pre id=\"program\">{}</pre>\n</details>\n",
+        escape_xml(page.code)
+    ));
+    if page.svg.is_some() {
+        html.push_str(&format!(
+            "<script>\n\
+             const shapes = Array.from(document.querySelectorAll(\"#drawing g > *\"));\n\
+             let timer;\n\
+             function replay() {{\n\
+             \x20 clearInterval(timer);\n\
+             \x20 shapes.forEach(shape => shape.style.visibility = \"hidden\");\n\
+             \x20 let next = 0;\n\
+             \x20 timer = setInterval(() => {{\n\
+             \x20   if (next < shapes.length) shapes[next++].style.visibility = \"visible\";\n\
+             \x20   else clearInterval(timer);\n\
+             \x20 }}, {});\n\
+             }}\n\
+             replay();\n\
+             </script>\n",
+            delay
+        ));
+    }
+    if let Some(runtime) = page.runtime {
+        html.push_str(&format!(
+            "<script id=\"runtime\" type=\"application/wasm\">{}</script>\n\
+             <script type=\"module\">\n{}</script>\n",
+            base64_encode(runtime),
+            RUNNER
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Standard base64 with padding
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | ((*byte as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+impl TimeWarpApp {
+    /// The program and its last run as a page, with the whole drawing in view
+    pub(crate) fn html_page(&self) -> String {
+        let svg = (!self.turtle_commands.is_empty()).then(|| {
+            let mut frame = canvas_export::Frame::fit(&self.turtle_commands, FIT_EXPORT_SIZE);
+            frame.background = self.canvas_background;
+            canvas_export::to_svg(&self.turtle_commands, &frame)
+        });
+        let name = self.document_name();
+        let title = std::path::Path::new(&name)
+            .file_stem()
+            .map_or(name.clone(), |stem| stem.to_string_lossy().into_owned());
+        to_html(&Page {
+            title: &title,
+            language: self.language.name(),
+            code: &self.code,
+            output: &self.output,
+            svg,
+            strokes_per_second: self.turtle_speed,
+            runtime: WEB_RUNTIME,
+        })
+    }
+
+    /// File → Export → Web Page: save the program and its last run as one
+    /// HTML file that opens in any browser
+    pub(crate) fn export_html(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("Web page", &["html"])
+            .set_file_name(
+                std::path::Path::new(&self.document_name())
+                    .with_extension("html")
+                    .display()
+                    .to_string(),
+            )
+            .save_file()
+        else {
+            return;
+        };
+        let path = path.with_extension("html");
+        match std::fs::write(&path, self.html_page()) {
+            Ok(()) => self
+                .output
+                .push_str(&format!("Web page saved to {}\n", path.display())),
+            Err(err) => self.show_error(format!("Could not save web page: {}", err)),
+        }
+    }
+}
@@ -0,0 +1,574 @@
+use crate::{editor, DebugState, TimeWarpApp};
+use eframe::egui;
+use std::collections::HashMap;
+use std::fmt;
+use time_warp_core::basic::StepMode;
+use time_warp_core::json::Json;
+
+/// Everything that can be bound to a keyboard shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Action {
+    NewFile,
+    OpenFile,
+    SaveFile,
+    Find,
+    FindNext,
+    FindPrevious,
+    Replace,
+    Run,
+    ToggleDebugMode,
+    StartDebug,
+    StepOver,
+    StepInto,
+    StepOut,
+    ClearOutput,
+    DuplicateLine,
+    DeleteLine,
+    MoveLineUp,
+    MoveLineDown,
+    ToggleBookmark,
+    NextBookmark,
+}
+
+impl Action {
+    pub const ALL: [Action; 20] = [
+        Action::NewFile,
+        Action::OpenFile,
+        Action::SaveFile,
+        Action::Find,
+        Action::FindNext,
+        Action::FindPrevious,
+        Action::Replace,
+        Action::Run,
+        Action::ToggleDebugMode,
+        Action::StartDebug,
+        Action::StepOver,
+        Action::StepInto,
+        Action::StepOut,
+        Action::ClearOutput,
+        Action::DuplicateLine,
+        Action::DeleteLine,
+        Action::MoveLineUp,
+        Action::MoveLineDown,
+        Action::ToggleBookmark,
+        Action::NextBookmark,
+    ];
+
+    /// Shown in the Settings window
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::NewFile => "New File",
+            Action::OpenFile => "Open File",
+            Action::SaveFile => "Save File",
+            Action::Find => "Find",
+            Action::FindNext => "Find Next",
+            Action::FindPrevious => "Find Previous",
+            Action::Replace => "Replace",
+            Action::Run => "Run",
+            Action::ToggleDebugMode => "Toggle Debug Mode",
+            Action::StartDebug => "Start Debugging",
+            Action::StepOver => "Step Over",
+            Action::StepInto => "Step Into",
+            Action::StepOut => "Step Out",
+            Action::ClearOutput => "Clear Output",
+            Action::DuplicateLine => "Duplicate Line",
+            Action::DeleteLine => "Delete Line",
+            Action::MoveLineUp => "Move Line Up",
+            Action::MoveLineDown => "Move Line Down",
+            Action::ToggleBookmark => "Toggle Bookmark",
+            Action::NextBookmark => "Next Bookmark",
+        }
+    }
+
+    /// Key in the settings file
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::NewFile => "new_file",
+            Action::OpenFile => "open_file",
+            Action::SaveFile => "save_file",
+            Action::Find => "find",
+            Action::FindNext => "find_next",
+            Action::FindPrevious => "find_previous",
+            Action::Replace => "replace",
+            Action::Run => "run",
+            Action::ToggleDebugMode => "toggle_debug_mode",
+            Action::StartDebug => "start_debug",
+            Action::StepOver => "step_over",
+            Action::StepInto => "step_into",
+            Action::StepOut => "step_out",
+            Action::ClearOutput => "clear_output",
+            Action::DuplicateLine => "duplicate_line",
+            Action::DeleteLine => "delete_line",
+            Action::MoveLineUp => "move_line_up",
+            Action::MoveLineDown => "move_line_down",
+            Action::ToggleBookmark => "toggle_bookmark",
+            Action::NextBookmark => "next_bookmark",
+        }
+    }
+
+    fn default_shortcut(self) -> Shortcut {
+        let ctrl = Shortcut::ctrl;
+        let plain = Shortcut::plain;
+        let alt = |key| Shortcut {
+            alt: true,
+            ..plain(key)
+        };
+        match self {
+            Action::NewFile => ctrl(egui::Key::N),
+            Action::OpenFile => ctrl(egui::Key::O),
+            Action::SaveFile => ctrl(egui::Key::S),
+            Action::Find => ctrl(egui::Key::F),
+            Action::FindNext => plain(egui::Key::F3),
+            Action::FindPrevious => Shortcut {
+                shift: true,
+                ..plain(egui::Key::F3)
+            },
+            Action::Replace => ctrl(egui::Key::R),
+            Action::Run => plain(egui::Key::F5),
+            Action::ToggleDebugMode => plain(egui::Key::F9),
+            Action::StartDebug => ctrl(egui::Key::F5),
+            Action::StepOver => plain(egui::Key::F10),
+            Action::StepInto => plain(egui::Key::F11),
+            Action::StepOut => Shortcut {
+                shift: true,
+                ..plain(egui::Key::F11)
+            },
+            Action::ClearOutput => Shortcut {
+                shift: true,
+                ..ctrl(egui::Key::C)
+            },
+            Action::DuplicateLine => ctrl(egui::Key::D),
+            Action::DeleteLine => Shortcut {
+                shift: true,
+                ..ctrl(egui::Key::K)
+            },
+            Action::MoveLineUp => alt(egui::Key::ArrowUp),
+            Action::MoveLineDown => alt(egui::Key::ArrowDown),
+            Action::ToggleBookmark => ctrl(egui::Key::F2),
+            Action::NextBookmark => plain(egui::Key::F2),
+        }
+    }
+}
+
+/// A key and exactly the modifiers that must be held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Shortcut {
+    pub key: egui::Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Shortcut {
+    pub fn plain(key: egui::Key) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn ctrl(key: egui::Key) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::plain(key)
+        }
+    }
+
+    /// Read the `Ctrl+Shift+C` form written by `Display`
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key = key_from_name(parts.pop()?)?;
+        let mut shortcut = Self::plain(key);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => shortcut.ctrl = true,
+                "shift" => shortcut.shift = true,
+                "alt" => shortcut.alt = true,
+                _ => return None,
+            }
+        }
+        Some(shortcut)
+    }
+
+    /// Whether `key` with `modifiers` is this shortcut. Extra modifiers don't
+    /// count, so Ctrl+F5 doesn't also trigger whatever F5 is bound to.
+    pub fn matches(&self, key: egui::Key, modifiers: egui::Modifiers) -> bool {
+        key == self.key
+            && modifiers.ctrl == self.ctrl
+            && modifiers.shift == self.shift
+            && modifiers.alt == self.alt
+    }
+
+    pub fn pressed(&self, input: &egui::InputState) -> bool {
+        input.key_pressed(self.key) && self.matches(self.key, input.modifiers)
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+/// Every key egui reports, for looking a key up by its `name()`
+const ALL_KEYS: [egui::Key; 73] = {
+    use egui::Key;
+    [
+        Key::ArrowDown,
+        Key::ArrowLeft,
+        Key::ArrowRight,
+        Key::ArrowUp,
+        Key::Escape,
+        Key::Tab,
+        Key::Backspace,
+        Key::Enter,
+        Key::Space,
+        Key::Insert,
+        Key::Delete,
+        Key::Home,
+        Key::End,
+        Key::PageUp,
+        Key::PageDown,
+        Key::Minus,
+        Key::PlusEquals,
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::F13,
+        Key::F14,
+        Key::F15,
+        Key::F16,
+        Key::F17,
+        Key::F18,
+        Key::F19,
+        Key::F20,
+    ]
+};
+
+/// The key whose `name()` is `name`, ignoring case
+fn key_from_name(name: &str) -> Option<egui::Key> {
+    ALL_KEYS
+        .into_iter()
+        .find(|key| key.name().eq_ignore_ascii_case(name))
+}
+
+/// Shortcuts the code editor handles itself, which a binding would shadow
+pub(crate) const EDITOR_SHORTCUTS: [(&str, &str); 9] = [
+    ("Ctrl+Z", "Undo"),
+    ("Ctrl+Y", "Redo"),
+    ("Ctrl+Shift+Z", "Redo"),
+    ("Ctrl+Space", "Code Completion"),
+    ("Ctrl+C", "Copy"),
+    ("Ctrl+X", "Cut"),
+    ("Ctrl+V", "Paste"),
+    ("Ctrl+A", "Select All"),
+    ("Ctrl+/", "Toggle Comment"),
+];
+
+/// The shortcut bound to each action; an action with none is unbound
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Keybindings {
+    bindings: HashMap<Action, Shortcut>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|&action| (action, action.default_shortcut()))
+                .collect(),
+        }
+    }
+}
+
+impl Keybindings {
+    pub fn get(&self, action: Action) -> Option<Shortcut> {
+        self.bindings.get(&action).copied()
+    }
+
+    pub fn set(&mut self, action: Action, shortcut: Option<Shortcut>) {
+        match shortcut {
+            Some(shortcut) => self.bindings.insert(action, shortcut),
+            None => self.bindings.remove(&action),
+        };
+    }
+
+    /// For tooltips: "F5", or "unbound"
+    pub fn label(&self, action: Action) -> String {
+        self.get(action)
+            .map_or_else(|| "unbound".to_string(), |shortcut| shortcut.to_string())
+    }
+
+    /// What `shortcut` is already used for, other than `action`: another
+    /// action's name or an editor command's
+    pub fn conflict(&self, action: Action, shortcut: Shortcut) -> Option<&'static str> {
+        let text = shortcut.to_string();
+        Action::ALL
+            .iter()
+            .find(|&&other| other != action && self.get(other) == Some(shortcut))
+            .map(|other| other.name())
+            .or_else(|| {
+                EDITOR_SHORTCUTS
+                    .iter()
+                    .find(|(editor, _)| *editor == text)
+                    .map(|(_, name)| *name)
+            })
+    }
+
+    /// Every action whose shortcut clashes with something else, with what it
+    /// clashes with, for example after hand-editing the settings file
+    pub fn conflicts(&self) -> Vec<(Action, &'static str)> {
+        Action::ALL
+            .iter()
+            .filter_map(|&action| {
+                let shortcut = self.get(action)?;
+                Some((action, self.conflict(action, shortcut)?))
+            })
+            .collect()
+    }
+
+    /// The actions whose shortcuts were pressed this frame
+    pub fn triggered(&self, input: &egui::InputState) -> Vec<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .filter(|&action| self.get(action).is_some_and(|s| s.pressed(input)))
+            .collect()
+    }
+
+    /// Every action, with `null` for unbound ones
+    pub fn to_json(&self) -> Json {
+        Json::Object(
+            Action::ALL
+                .iter()
+                .map(|&action| {
+                    let value = self
+                        .get(action)
+                        .map_or(Json::Null, |shortcut| shortcut.to_string().into());
+                    (action.id().to_string(), value)
+                })
+                .collect(),
+        )
+    }
+
+    /// Actions missing from `json`, or with a shortcut that doesn't parse,
+    /// keep their default
+    pub fn from_json(json: &Json) -> Self {
+        let mut keybindings = Self::default();
+        for action in Action::ALL {
+            match json.get(action.id()) {
+                Some(Json::Null) => keybindings.set(action, None),
+                Some(Json::String(text)) => {
+                    if let Some(shortcut) = Shortcut::parse(text) {
+                        keybindings.set(action, Some(shortcut));
+                    }
+                }
+                _ => {}
+            }
+        }
+        keybindings
+    }
+}
+
+impl TimeWarpApp {
+    /// Run whatever the shortcuts pressed this frame are bound to. Nothing
+    /// fires while a new shortcut is being recorded.
+    pub(crate) fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if self.recording_shortcut.is_some() {
+            return;
+        }
+        let triggered = ctx.input(|i| self.settings.keybindings.triggered(i));
+        for action in triggered {
+            self.run_action(action);
+        }
+    }
+
+    pub(crate) fn run_action(&mut self, action: Action) {
+        let paused = self.debug_mode && self.debug_state == DebugState::Paused;
+        match action {
+            Action::NewFile => self.open_new_file_dialog(),
+            Action::OpenFile => self.open_file(),
+            Action::SaveFile => self.save_current_file(),
+            Action::Find | Action::Replace => self.show_find_replace = true,
+            Action::FindNext => {
+                self.show_find_replace = true;
+                self.find_next();
+            }
+            Action::FindPrevious => {
+                self.show_find_replace = true;
+                self.find_previous();
+            }
+            Action::Run => {
+                self.active_tab = 1;
+                self.execute_code();
+            }
+            Action::ToggleDebugMode => {
+                self.debug_mode = !self.debug_mode;
+                if !self.debug_mode {
+                    self.stop_debug_session();
+                }
+            }
+            Action::StartDebug => {
+                if self.debug_mode {
+                    self.start_debug_session();
+                }
+            }
+            Action::StepOver if paused => self.step_debug(StepMode::StepOver),
+            Action::StepInto if paused => self.step_debug(StepMode::StepInto),
+            Action::StepOut if paused => self.step_debug(StepMode::StepOut),
+            Action::StepOver | Action::StepInto | Action::StepOut => {}
+            Action::ClearOutput => {
+                self.output = String::new();
+                self.clear_canvas();
+                self.reset_canvas_view();
+            }
+            Action::DuplicateLine => self.edit_code(editor::duplicate_lines),
+            Action::DeleteLine => self.edit_code(editor::delete_lines),
+            Action::MoveLineUp => self.edit_code(|code, at| editor::move_lines(code, at, true)),
+            Action::MoveLineDown => self.edit_code(|code, at| editor::move_lines(code, at, false)),
+            Action::ToggleBookmark => self.toggle_bookmark(),
+            Action::NextBookmark => self.next_bookmark(),
+        }
+    }
+
+    /// The Keyboard Shortcuts section of the Settings window. Clicking a
+    /// shortcut records the next key pressed; one already in use is refused.
+    pub(crate) fn render_keybindings(&mut self, ui: &mut egui::Ui) {
+        if let Some(action) = self.recording_shortcut {
+            let pressed = ui.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => Some((*key, *modifiers)),
+                    _ => None,
+                })
+            });
+            if let Some((key, modifiers)) = pressed {
+                self.recording_shortcut = None;
+                if key != egui::Key::Escape {
+                    let shortcut = Shortcut {
+                        key,
+                        ctrl: modifiers.ctrl,
+                        shift: modifiers.shift,
+                        alt: modifiers.alt,
+                    };
+                    match self.settings.keybindings.conflict(action, shortcut) {
+                        Some(other) => {
+                            self.shortcut_message =
+                                Some(format!("{} is already used by {}", shortcut, other));
+                        }
+                        None => {
+                            self.settings.keybindings.set(action, Some(shortcut));
+                            self.shortcut_message = None;
+                        }
+                    }
+                }
+            }
+        }
+
+        egui::Grid::new("keybindings_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(action.name());
+                    let text = if self.recording_shortcut == Some(action) {
+                        "Press a key… (Esc cancels)".to_string()
+                    } else {
+                        self.settings.keybindings.label(action)
+                    };
+                    if ui.button(text).clicked() {
+                        self.recording_shortcut = Some(action);
+                        self.shortcut_message = None;
+                    }
+                    if ui
+                        .small_button("✖")
+                        .on_hover_text("Remove this shortcut")
+                        .clicked()
+                    {
+                        self.settings.keybindings.set(action, None);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(message) = &self.shortcut_message {
+            ui.colored_label(egui::Color32::RED, message);
+        }
+        for (action, other) in self.settings.keybindings.conflicts() {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "⚠ {} ({}) clashes with {}",
+                    action.name(),
+                    self.settings.keybindings.label(action),
+                    other
+                ),
+            );
+        }
+        if ui.button("↺ Default Shortcuts").clicked() {
+            self.settings.keybindings = Keybindings::default();
+            self.shortcut_message = None;
+        }
+    }
+}
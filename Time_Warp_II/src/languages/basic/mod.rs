@@ -1,13 +0,0 @@
-pub mod ast;
-pub mod interpreter;
-pub mod parser;
-pub mod tokenizer;
-
-// Re-export main types for convenience
-pub use ast::{
-    ExecutionResult, Expression, GraphicsCommand, InterpreterError, Program, Statement, Token,
-    Value,
-};
-pub use interpreter::Interpreter;
-pub use parser::Parser;
-pub use tokenizer::Tokenizer;
@@ -1,8 +1,46 @@
 use eframe::egui;
 use rfd::FileDialog;
-use std::collections::HashMap;
-
-mod languages;
+use std::collections::{BTreeSet, HashMap};
+use time_warp_core::basic::include::Expanded;
+use time_warp_core::basic::ErrorCode;
+use time_warp_core::Language;
+
+#[cfg(feature = "audio")]
+mod audio;
+mod canvas_export;
+mod diagnostics_panel;
+mod documents;
+mod editor;
+mod file_backends;
+mod file_watch;
+mod flood_fill;
+mod fonts;
+mod highlight;
+#[cfg(feature = "hints")]
+mod hints;
+mod html_export;
+mod keybindings;
+mod permissions;
+mod printer;
+#[cfg(feature = "profiler")]
+mod profiler;
+mod program_input;
+mod projects;
+mod recent_files;
+mod search;
+mod session;
+mod settings;
+mod standalone;
+mod storage;
+#[cfg(feature = "templates")]
+mod templates;
+mod theme;
+mod token_inspector;
+mod turtle;
+#[cfg(feature = "webdav")]
+mod webdav;
+#[cfg(feature = "workspaces")]
+mod workspaces;
 
 #[derive(Clone)]
 struct TurtleState {
@@ -10,6 +48,23 @@ struct TurtleState {
     y: f32,
     angle: f32, // in degrees
     color: egui::Color32,
+    pen_width: f32,
+    label_size: f32,
+    visible: bool, // HIDETURTLE hides the cursor, not the drawing
+}
+
+impl Default for TurtleState {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            angle: 0.0,
+            color: turtle::DEFAULT_PEN_COLOR,
+            pen_width: turtle::DEFAULT_PEN_WIDTH,
+            label_size: turtle::DEFAULT_LABEL_SIZE,
+            visible: true,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -19,44 +74,216 @@ enum DebugState {
     Paused,
 }
 
+/// Statement count and timing for the most recent run or debug session
+#[derive(Clone, Default)]
+struct ExecutionStats {
+    statements: usize,
+    elapsed: std::time::Duration,
+    max_instructions: usize,
+}
+
+impl ExecutionStats {
+    /// How much of the instruction limit the program used, in percent
+    fn percent_of_limit(&self) -> f32 {
+        if self.max_instructions == 0 {
+            return 0.0;
+        }
+        self.statements as f32 * 100.0 / self.max_instructions as f32
+    }
+}
+
+/// Which source lines ran during the last execution
+#[derive(Clone, Default)]
+struct CoverageReport {
+    executed: std::collections::HashSet<usize>,
+    executable: std::collections::HashSet<usize>,
+}
+
+impl CoverageReport {
+    /// Percentage of statement lines that were reached
+    fn percent(&self) -> f32 {
+        if self.executable.is_empty() {
+            return 100.0;
+        }
+        let reached = self.executable.intersection(&self.executed).count();
+        reached as f32 * 100.0 / self.executable.len() as f32
+    }
+
+    /// Editor gutter marks for every statement line, green where the run
+    /// went and red where it never did
+    fn marks(&self, palette: &theme::Palette) -> Vec<(usize, egui::Color32, String)> {
+        let mut lines: Vec<usize> = self.executable.union(&self.executed).copied().collect();
+        lines.sort_unstable();
+        lines
+            .into_iter()
+            .map(|line| match self.executed.contains(&line) {
+                true => (line, palette.covered, format!("Line {} was executed", line)),
+                false => (
+                    line,
+                    palette.uncovered,
+                    format!("Line {} was never reached", line),
+                ),
+            })
+            .collect()
+    }
+}
+
+/// Just the default turtle, at home
+fn default_turtles() -> std::collections::BTreeMap<String, TurtleState> {
+    std::collections::BTreeMap::from([(
+        time_warp_core::basic::DEFAULT_TURTLE.to_string(),
+        TurtleState::default(),
+    )])
+}
+
+/// Smallest the turtle canvas shrinks to, and its size before the first
+/// layout. Otherwise it fills whatever room the Output & Graphics tab has.
+const TURTLE_CANVAS_SIZE: egui::Vec2 = egui::vec2(400.0, 300.0);
+
+/// Largest image "Fit drawing" exports produce
+const FIT_EXPORT_SIZE: egui::Vec2 = egui::vec2(1200.0, 900.0);
+
+/// Most frames an animated GIF recording captures; longer drawings add
+/// several strokes per frame
+const GIF_MAX_FRAMES: usize = 60;
+
+/// How long a recorded GIF shows the finished drawing before looping, in
+/// hundredths of a second
+const GIF_FINAL_HOLD: u16 = 200;
+
+/// How long watch mode waits after the last save before re-running
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(400);
+
 struct TimeWarpApp {
     code: String,
     output: String,
-    active_tab: usize, // 0 = Editor, 1 = Output & Turtle, 2 = Debug
+    active_tab: usize, // 0 = Editor, 1 = Output & Turtle, 2 = Debug, 3 = Syntax Tree
     last_file_path: Option<String>,
+    untitled_number: usize, // which untitled tab this is, while never saved
     show_line_numbers: bool,
     find_text: String,
     replace_text: String,
     show_find_replace: bool,
-    turtle_state: TurtleState,
-    turtle_commands: Vec<String>,
+    search: search::SearchOptions,
+    search_error: Option<String>, // the Find box holds a bad regular expression
+    show_find_results: bool,      // the Find All list under the Find/Replace bar
+    turtles: std::collections::BTreeMap<String, TurtleState>, // by name
+    turtle_commands: Vec<turtle::TurtleOp>,
+    drawn_commands: Vec<time_warp_core::basic::TurtleCommand>, // since the last clear, for saved runs
+    canvas_pen: (egui::Color32, f32), // colour and width the last canvas op left set
+    turtle_animate: bool,
+    turtle_speed: f32,    // lines per second while animating
+    turtle_progress: f32, // strokes replayed so far
     variables: HashMap<String, String>,
     is_executing: bool,
     waiting_for_input: bool,
     input_prompt: String,
     user_input: String,
+    syntax_tree: Result<Vec<time_warp_core::basic::tree::SyntaxNode>, String>, // for the Syntax Tree tab
+    scripted_input: String, // answers for INPUT, one per line, used before asking
     current_input_var: String,
+    pending_permission: Option<time_warp_core::basic::Permission>, // the prompt the run waits on
+    remember_permission: bool, // the prompt's "Remember for this program" box
+    polling_input: bool, // the run waits on the keyboard, mouse or a SLEEP and carries on each frame
+    grab_keyboard: bool, // take keys away from the editor once that run starts
+    canvas_mouse: time_warp_core::basic::pump::Mouse, // for MOUSEX, MOUSEY and MOUSEB
+    canvas_clicked: bool, // a button went down over the canvas since the run last went on
     show_about: bool,
     turtle_zoom: f32,
     turtle_pan: egui::Vec2,
+    export_fit: bool,        // export the whole drawing instead of the current view
+    show_grid: bool,         // coordinate grid, axes and mouse readout on the canvas
+    canvas_size: egui::Vec2, // size the canvas was last laid out at
+    canvas_maximized: bool,  // output text hidden so the canvas gets the whole tab
+    canvas_background: egui::Color32, // set by SETBACKGROUND or the picker; kept by clears
+
+    // New File dialog
+    #[cfg(feature = "templates")]
+    show_new_file_dialog: bool,
+    #[cfg(feature = "templates")]
+    new_file_templates: Vec<templates::ProgramTemplate>,
+    #[cfg(feature = "templates")]
+    new_file_selected: usize,
+    new_file_extension: String,
 
     // Debug state
     debug_mode: bool,
     debug_state: DebugState,
-    breakpoints: HashMap<String, Vec<u32>>, // filename -> line numbers
+    debug_step_mode: time_warp_core::basic::StepMode, // of the step a sleeping run carries on
+    breakpoints: HashMap<String, Vec<u32>>,           // filename -> line numbers
+    bookmarks: HashMap<String, Vec<u32>>,             // filename -> line numbers
     current_debug_line: Option<u32>,
     debug_variables: HashMap<String, String>,
     debug_call_stack: Vec<String>,
 
+    // Execution profiler
+    #[cfg(feature = "profiler")]
+    profiling_enabled: bool,
+    #[cfg(feature = "profiler")]
+    profile_results: Vec<time_warp_core::basic::LineProfile>,
+    #[cfg(feature = "profiler")]
+    profile_sort: profiler::ProfileSort,
+    #[cfg(feature = "profiler")]
+    profile_sort_descending: bool,
+    #[cfg(feature = "profiler")]
+    show_profiler: bool,
+    show_token_inspector: bool,
+    show_diagnostics: bool,
+    printout: String, // what programs have LPRINTed
+    #[cfg(feature = "audio")]
+    music_player: audio::MusicPlayer,
+    show_printer: bool,
+    diagnostics_cache: Option<diagnostics_panel::DiagnosticsCache>,
+    #[cfg(feature = "profiler")]
+    show_profile_heatmap: bool,
+
+    // Hints after repeated errors
+    #[cfg(feature = "hints")]
+    error_tracker: hints::ErrorTracker,
+    #[cfg(feature = "hints")]
+    active_hint: Option<(&'static hints::HintCard, usize)>,
+
+    // Watch mode: re-run the program shortly after each save
+    watch_mode: bool,
+    watch_run_due: Option<std::time::Instant>,
+
+    // Run Tests: summarize the program's ASSERTs once the run finishes
+    testing: bool,
+    assertion_results: Vec<time_warp_core::basic::AssertionResult>,
+
+    // Changes made to the open file by other editors
+    file_watcher: file_watch::FileWatcher,
+    external_conflict: Option<String>, // disk content waiting on the user
+    external_editor: String,
+
+    // Where files are read from and written to (local disk or a class share)
+    file_backends: file_backends::BackendSettings,
+    #[cfg(feature = "webdav")]
+    show_class_share: bool,
+    #[cfg(feature = "webdav")]
+    class_share_url: String,
+    #[cfg(feature = "webdav")]
+    share_transfer: Option<webdav::Transfer>,
+
+    // Code coverage from the last run
+    coverage: Option<CoverageReport>,
+    show_coverage: bool,
+
+    // What strict mode found in the last BASIC program run, on editor lines
+    diagnostics: Vec<time_warp_core::basic::strict::Diagnostic>,
+
     // Code completion
-    code_completion_enabled: bool,
     show_completion: bool,
     completion_items: Vec<String>,
     completion_selected: usize,
     completion_query: String,
 
     // BASIC interpreter instance for continuation after input
-    basic_interpreter: Option<crate::languages::basic::Interpreter>,
+    basic_interpreter: Option<time_warp_core::basic::Interpreter>,
+    included: Expanded, // where each line of the BASIC program it runs was written
+    player: bool,       // a standalone copy, showing only the running program
+    waiting_program: Option<Box<dyn time_warp_core::LanguageBackend>>, // a PILOT A: or Pascal readln
+    language: Language, // what Run executes the editor contents as
 
     // General prompt system
     general_prompt_active: bool,
@@ -68,7 +295,7 @@ struct TimeWarpApp {
     cursor_line: usize,
     cursor_column: usize,
     total_lines: usize,
-    execution_timeout_ms: u64,
+    execution_stats: Option<ExecutionStats>,
 
     // Error notification
     error_message: Option<String>,
@@ -83,14 +310,49 @@ struct TimeWarpApp {
     // Syntax highlighting
     #[allow(dead_code)]
     syntax_highlighting_enabled: bool,
+    highlight_cache: highlight::HighlightCache, // coloured lines, reused between frames
+
+    // Additional workspace windows (only used by the main window)
+    #[cfg(feature = "workspaces")]
+    workspaces: Vec<workspaces::Workspace>,
+    #[cfg(feature = "workspaces")]
+    next_workspace_number: usize,
+    #[cfg(feature = "workspaces")]
+    new_workspace_requested: bool,
+
+    // Preferences from the Settings window
+    settings: settings::Settings,
+    show_settings: bool,
+    recording_shortcut: Option<keybindings::Action>, // waiting for the next key press
+    installed_fonts: Option<Vec<fonts::InstalledFont>>, // scanned when Settings first lists them
+    applied_code_font: Option<Option<std::path::PathBuf>>, // the font egui has loaded
+    shortcut_message: Option<String>,                // why the last key wasn't bound
+
+    // On-disk locations for settings, sessions, autosaves and the like
+    storage: storage::StoragePaths,
+    storage_migration_log: Vec<String>,
+    window_geometry: Option<session::WindowGeometry>, // main window, for the next launch
+    saved_session: String,                            // session as last written
+    session_checked: std::time::Instant,
+
+    // Editor tabs; the active one's state is in the fields above
+    documents: Vec<documents::Document>,
+    active_document: usize,
+    closing_document: Option<usize>, // tab waiting on "close without saving?"
+    reloading_document: Option<String>, // dropped file waiting on "reload over your edits?"
+    show_quit_prompt: bool,          // closing the window with unsaved tabs
+    quit_confirmed: bool,
+    window_title: String,
+    split_document: Option<usize>, // tab shown beside the editor in split view
+    split_highlight_cache: highlight::HighlightCache,
+    folded_lines: BTreeSet<usize>, // first lines of the folded blocks in the editor
+    project: Option<projects::Project>,
+    show_project_panel: bool,
 
     // Clipboard operations
-    #[allow(dead_code)]
-    clipboard_content: String,
-    #[allow(dead_code)]
-    selected_text: String,
-    #[allow(dead_code)]
-    cursor_position: usize,
+    clipboard_content: String, // last copied or cut, for the Paste menu item
+    selection: editor::Selection, // caret and selection in the code editor
+    pending_selection: Option<editor::Selection>, // moves the editor's caret next frame
 }
 
 impl Default for TimeWarpApp {
@@ -100,37 +362,116 @@ impl Default for TimeWarpApp {
             output: String::new(),
             active_tab: 0, // Start with Editor tab
             last_file_path: None,
+            untitled_number: 1,
             show_line_numbers: false,
             find_text: String::new(),
             replace_text: String::new(),
             show_find_replace: false,
-            turtle_state: TurtleState {
-                x: 0.0,
-                y: 0.0,
-                angle: 0.0,
-                color: egui::Color32::BLACK,
-            },
+            search: search::SearchOptions::default(),
+            search_error: None,
+            show_find_results: false,
+            turtles: default_turtles(),
             turtle_commands: Vec::new(),
+            drawn_commands: Vec::new(),
+            canvas_pen: (turtle::DEFAULT_PEN_COLOR, turtle::DEFAULT_PEN_WIDTH),
+            turtle_animate: true,
+            turtle_speed: turtle::DEFAULT_ANIMATION_SPEED,
+            turtle_progress: 0.0,
             variables: HashMap::new(),
             is_executing: false,
             waiting_for_input: false,
             input_prompt: String::new(),
             user_input: String::new(),
+            syntax_tree: Ok(Vec::new()),
+            scripted_input: String::new(),
             current_input_var: String::new(),
+            pending_permission: None,
+            polling_input: false,
+            grab_keyboard: false,
+            canvas_mouse: Default::default(),
+            canvas_clicked: false,
+            remember_permission: false,
             show_about: false,
             turtle_zoom: 1.0,
             turtle_pan: egui::vec2(0.0, 0.0),
+            export_fit: false,
+            show_grid: false,
+            canvas_size: TURTLE_CANVAS_SIZE,
+            canvas_maximized: false,
+            canvas_background: turtle::DEFAULT_BACKGROUND,
+
+            // New File dialog defaults
+            #[cfg(feature = "templates")]
+            show_new_file_dialog: false,
+            #[cfg(feature = "templates")]
+            new_file_templates: Vec::new(),
+            #[cfg(feature = "templates")]
+            new_file_selected: 0,
+            new_file_extension: "twb".to_string(),
 
             // Debug defaults
             debug_mode: false,
             debug_state: DebugState::Stopped,
+            debug_step_mode: time_warp_core::basic::StepMode::Continue,
             breakpoints: HashMap::new(),
+            bookmarks: HashMap::new(),
             current_debug_line: None,
             debug_variables: HashMap::new(),
             debug_call_stack: Vec::new(),
 
+            // Profiler defaults
+            #[cfg(feature = "profiler")]
+            profiling_enabled: false,
+            #[cfg(feature = "profiler")]
+            profile_results: Vec::new(),
+            #[cfg(feature = "profiler")]
+            profile_sort: profiler::ProfileSort::Hits,
+            #[cfg(feature = "profiler")]
+            profile_sort_descending: true,
+            #[cfg(feature = "profiler")]
+            show_profiler: false,
+            show_token_inspector: false,
+            show_diagnostics: false,
+            printout: String::new(),
+            #[cfg(feature = "audio")]
+            music_player: audio::MusicPlayer::default(),
+            show_printer: false,
+            diagnostics_cache: None,
+            #[cfg(feature = "profiler")]
+            show_profile_heatmap: true,
+
+            // Hint defaults
+            #[cfg(feature = "hints")]
+            error_tracker: hints::ErrorTracker::new(3),
+            #[cfg(feature = "hints")]
+            active_hint: None,
+
+            // Watch mode defaults
+            watch_mode: false,
+            watch_run_due: None,
+            testing: false,
+            assertion_results: Vec::new(),
+
+            // External editor defaults
+            file_watcher: file_watch::FileWatcher::new(),
+            external_conflict: None,
+            external_editor: file_watch::default_editor_command(),
+
+            // File backend defaults
+            file_backends: file_backends::BackendSettings::default(),
+            #[cfg(feature = "webdav")]
+            show_class_share: false,
+            #[cfg(feature = "webdav")]
+            class_share_url: "https://".to_string(),
+            #[cfg(feature = "webdav")]
+            share_transfer: None,
+
+            // Coverage defaults
+            coverage: None,
+            diagnostics: Vec::new(),
+            show_coverage: true,
+
             // Completion defaults
-            code_completion_enabled: false,
             show_completion: false,
             completion_items: Vec::new(),
             completion_selected: 0,
@@ -138,6 +479,10 @@ impl Default for TimeWarpApp {
 
             // BASIC interpreter instance for continuation after input
             basic_interpreter: None,
+            included: Expanded::plain(""),
+            player: false,
+            waiting_program: None,
+            language: Language::default(),
 
             // General prompt system
             general_prompt_active: false,
@@ -149,7 +494,7 @@ impl Default for TimeWarpApp {
             cursor_line: 1,
             cursor_column: 1,
             total_lines: 1,
-            execution_timeout_ms: 5000, // 5 seconds default timeout
+            execution_stats: None,
 
             // Error notification defaults
             error_message: None,
@@ -163,11 +508,48 @@ impl Default for TimeWarpApp {
 
             // Syntax highlighting defaults
             syntax_highlighting_enabled: true,
+            highlight_cache: highlight::HighlightCache::default(),
+
+            // Workspace defaults
+            #[cfg(feature = "workspaces")]
+            workspaces: Vec::new(),
+            #[cfg(feature = "workspaces")]
+            next_workspace_number: 2,
+            #[cfg(feature = "workspaces")]
+            new_workspace_requested: false,
+
+            // Settings defaults, replaced by the saved ones in `new`
+            settings: settings::Settings::default(),
+            show_settings: false,
+            recording_shortcut: None,
+            installed_fonts: None,
+            applied_code_font: None,
+            shortcut_message: None,
+
+            // Storage defaults
+            storage: storage::StoragePaths::resolve(),
+            storage_migration_log: Vec::new(),
+            window_geometry: None,
+            saved_session: String::new(),
+            session_checked: std::time::Instant::now(),
+
+            documents: vec![documents::Document::default()],
+            active_document: 0,
+            closing_document: None,
+            reloading_document: None,
+            show_quit_prompt: false,
+            quit_confirmed: false,
+            window_title: String::new(),
+            split_document: None,
+            split_highlight_cache: highlight::HighlightCache::default(),
+            folded_lines: BTreeSet::new(),
+            project: None,
+            show_project_panel: false,
 
             // Clipboard defaults
             clipboard_content: String::new(),
-            selected_text: String::new(),
-            cursor_position: 0,
+            selection: editor::Selection::default(),
+            pending_selection: None,
         }
     }
 }
@@ -232,165 +614,36 @@ impl TimeWarpApp {
         }
     }
 
-    fn render_syntax_highlighted_text(&self, ui: &mut egui::Ui, text: &str) {
-        // Basic syntax highlighting for BASIC keywords
-        let keywords = [
-            "PRINT",
-            "WRITELN",
-            "INPUT",
-            "READLN",
-            "LET",
-            "IF",
-            "THEN",
-            "ELSE",
-            "END",
-            "STOP",
-            "FOR",
-            "TO",
-            "STEP",
-            "NEXT",
-            "WHILE",
-            "WEND",
-            "GOTO",
-            "GOSUB",
-            "RETURN",
-            "REM",
-            "CLS",
-            "COLOR",
-            "LOCATE",
-            "BEEP",
-            "SLEEP",
-            "RANDOMIZE",
-            "DIM",
-            "DATA",
-            "READ",
-            "RESTORE",
-            "FORWARD",
-            "FD",
-            "BACK",
-            "BK",
-            "LEFT",
-            "LT",
-            "RIGHT",
-            "RT",
-            "PENUP",
-            "PU",
-            "PENDOWN",
-            "PD",
-            "AND",
-            "OR",
-            "NOT",
-            "SIN",
-            "COS",
-            "TAN",
-            "SQR",
-            "ABS",
-            "INT",
-            "LOG",
-            "EXP",
-            "ATN",
-            "RND",
-        ];
-
-        let lines: Vec<&str> = text.lines().collect();
-
-        for (line_num, line) in lines.iter().enumerate() {
-            // Show line number
-            ui.horizontal(|ui| {
-                ui.label(
-                    egui::RichText::new(format!("{:3}: ", line_num + 1))
-                        .weak()
-                        .monospace(),
-                );
-
-                // Split line into tokens and highlight
-                let mut remaining = *line;
-                let mut first = true;
-
-                while !remaining.is_empty() {
-                    let mut found_keyword = false;
-
-                    // Check for keywords at start of remaining text
-                    for keyword in &keywords {
-                        if remaining
-                            .to_uppercase()
-                            .starts_with(&keyword.to_uppercase())
-                        {
-                            let keyword_len = keyword.len();
-                            if remaining.len() == keyword_len
-                                || !remaining
-                                    .chars()
-                                    .nth(keyword_len)
-                                    .unwrap_or(' ')
-                                    .is_alphanumeric()
-                            {
-                                if !first {
-                                    ui.add_space(4.0);
-                                }
-                                ui.label(
-                                    egui::RichText::new(&remaining[..keyword_len])
-                                        .color(egui::Color32::from_rgb(86, 156, 214)) // Blue for keywords
-                                        .monospace(),
-                                );
-                                remaining = &remaining[keyword_len..];
-                                found_keyword = true;
-                                first = false;
-                                break;
-                            }
-                        }
-                    }
-
-                    if !found_keyword {
-                        // Find next space or end
-                        let space_pos = remaining.find(' ').unwrap_or(remaining.len());
-                        let token = &remaining[..space_pos];
-
-                        if !first {
-                            ui.add_space(4.0);
-                        }
-
-                        // Check if it's a number
-                        if token.parse::<f64>().is_ok() {
-                            ui.label(
-                                egui::RichText::new(token)
-                                    .color(egui::Color32::from_rgb(181, 206, 168)) // Green for numbers
-                                    .monospace(),
-                            );
-                        } else if token.starts_with('"') && token.ends_with('"') {
-                            ui.label(
-                                egui::RichText::new(token)
-                                    .color(egui::Color32::from_rgb(206, 145, 120)) // Orange for strings
-                                    .monospace(),
-                            );
-                        } else {
-                            ui.label(
-                                egui::RichText::new(token)
-                                    .color(egui::Color32::WHITE) // White for identifiers/comments
-                                    .monospace(),
-                            );
-                        }
+    /// Colours for the current theme
+    fn palette(&self) -> &'static theme::Palette {
+        self.settings.theme.palette()
+    }
 
-                        remaining = &remaining[space_pos..];
-                        if space_pos < remaining.len() {
-                            remaining = &remaining[1..]; // Skip space
-                        }
-                        first = false;
-                    }
-                }
-            });
+    /// Pick the language Run uses. An unsaved program also takes its
+    /// extension, so saving it later suggests the right file type.
+    fn set_language(&mut self, language: Language) {
+        self.language = language;
+        if self.last_file_path.is_none() {
+            self.new_file_extension = language.extension().to_string();
         }
     }
 
     fn execute_code(&mut self) {
         self.active_tab = 1; // Switch to Output tab when running
         self.is_executing = true;
+        self.polling_input = false;
         // Clear output before execution so only current program output is shown
         self.output.clear();
         let code = self.code.clone();
-        let result = self.execute_tw_basic(&code);
+        let result = match self.language {
+            // BASIC keeps its interpreter for the debugger, coverage and profiler
+            Language::Basic => self.execute_tw_basic(&code),
+            language => self.execute_with(language, &code),
+        };
 
-        // Check if execution needs input
-        if self.waiting_for_input {
+        // Check if execution needs input, is asking before it changes files,
+        // or is polling the keyboard
+        if self.run_unfinished() {
             // Program is waiting for input - don't mark as complete yet
             // The output will be updated when input is provided
             self.is_executing = false;
@@ -400,1149 +653,2695 @@ impl TimeWarpApp {
         // Set output to the result (which may be empty)
         self.output = result;
         self.is_executing = false;
+        self.finish_test_run();
     }
 
-    fn execute_tw_basic(&mut self, code: &str) -> String {
-        use crate::languages::basic::Interpreter;
+    /// Run the program and summarize how its ASSERT statements went
+    fn run_tests(&mut self) {
+        if self.language != Language::Basic {
+            self.active_tab = 1;
+            self.output = format!(
+                "Run Tests checks ASSERT statements in TW BASIC programs, not {}.\n",
+                self.language.name()
+            );
+            return;
+        }
+        self.testing = true;
+        self.execute_code();
+    }
 
-        // Convert line-numbered BASIC to statements without line numbers
-        let mut statements = Vec::new();
-        for line in code.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
+    /// Once a Run Tests run has ended, add its summary to the output
+    fn finish_test_run(&mut self) {
+        if !self.testing || self.run_unfinished() {
+            return;
+        }
+        self.testing = false;
+        let summary = Self::test_summary(&self.assertion_results);
+        self.output.push_str(&summary);
+    }
 
-            // Try to parse line number and extract the statement
-            if let Some((line_num_str, command)) = line.split_once(' ') {
-                if line_num_str.parse::<u32>().is_ok() {
-                    statements.push(command.trim().to_string());
-                } else {
-                    statements.push(line.to_string());
-                }
-            } else {
-                statements.push(line.to_string());
+    /// Whether the run is still going: waiting for an answer or a
+    /// permission, or carrying on between frames while it sleeps or waits
+    /// on the keyboard or mouse
+    fn run_unfinished(&self) -> bool {
+        self.waiting_for_input || self.pending_permission.is_some() || self.polling_input
+    }
+
+    fn test_summary(results: &[time_warp_core::basic::AssertionResult]) -> String {
+        if results.is_empty() {
+            return "\n🧪 No ASSERT statements ran.\n".to_string();
+        }
+        let failed: Vec<_> = results.iter().filter(|result| !result.passed).collect();
+        let mut summary = format!(
+            "\n🧪 Tests: {} passed, {} failed\n",
+            results.len() - failed.len(),
+            failed.len()
+        );
+        for result in failed {
+            summary.push_str(&format!("  ✗ line {}: {}\n", result.line, result.message));
+        }
+        summary
+    }
+
+    /// Ask for a file and load it into the editor
+    fn open_file(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter(
+                "Programs",
+                &[
+                    "twb", "bas", "twl", "logo", "twp", "pas", "tpr", "pro", "plt", "pilot",
+                ],
+            )
+            .add_filter("Text", &["txt"])
+            .add_filter("Time Warp Projects", &[projects::PROJECT_EXTENSION])
+            .pick_file()
+        {
+            let location = path.display().to_string();
+            if projects::is_project_file(&location) {
+                self.open_project(&path);
+            } else if self.open_location(&location) {
+                self.remember_recent_file(&location);
             }
         }
+    }
 
-        // Join statements with colons for the interpreter (BASIC statement separator)
-        let program_code = statements.join(" : ");
+    /// Load a local path or share URL through the matching file backend. A
+    /// share URL is fetched in the background and shows up once it arrives.
+    fn open_location(&mut self, location: &str) -> bool {
+        #[cfg(feature = "webdav")]
+        if file_backends::is_remote(location) {
+            return self.start_share_transfer(location, None);
+        }
+        match self.file_backends.read(location) {
+            Ok(content) => {
+                self.show_opened(location, content);
+                true
+            }
+            Err(err) => {
+                self.show_error(format!("Could not open {}: {}", location, err));
+                false
+            }
+        }
+    }
 
-        let mut interpreter = Interpreter::new();
-        // Set execution timeout based on instruction limit
-        // Rough estimate: 1000 instructions per second
-        interpreter.max_instructions = (self.execution_timeout_ms * 1000) as usize;
+    /// Put a file that was just read in the editor, in a new tab unless the
+    /// current one is an empty new program. A file that is already open is
+    /// switched to and replaced.
+    fn show_opened(&mut self, location: &str, content: String) {
+        match self.find_document(location) {
+            Some(index) => self.switch_document(index),
+            None if self.is_blank_document() => {}
+            None => self.new_document(),
+        }
+        self.code = content;
+        if let Some(language) = std::path::Path::new(location)
+            .extension()
+            .and_then(|extension| Language::from_extension(&extension.to_string_lossy()))
+        {
+            self.language = language;
+        }
+        // Don't set output for file operations - keep output clean for program results only
+        self.last_file_path = Some(location.to_string());
+        self.file_watcher
+            .track(std::path::Path::new(location), &self.code);
+        self.external_conflict = None;
+    }
 
-        match interpreter.execute(&program_code) {
-            Ok(result) => match result {
-                crate::languages::basic::ExecutionResult::Complete {
-                    output,
-                    graphics_commands,
-                } => {
-                    // Process graphics commands
-                    self.process_graphics_commands(&graphics_commands);
-                    self.basic_interpreter = None; // Clear stored interpreter
-                    output
-                }
-                crate::languages::basic::ExecutionResult::NeedInput {
-                    variable,
-                    prompt,
-                    partial_output,
-                    partial_graphics,
-                } => {
-                    self.waiting_for_input = true;
-                    self.input_prompt = prompt.clone();
-                    self.current_input_var = variable;
-                    // Process any graphics commands that were executed before input was needed
-                    self.process_graphics_commands(&partial_graphics);
-                    // Store the interpreter for continuation
-                    self.basic_interpreter = Some(interpreter);
-                    // For now, just return the partial output with the prompt
-                    format!("{}{}", partial_output, prompt)
-                }
-                crate::languages::basic::ExecutionResult::Error(err) => {
-                    self.basic_interpreter = None; // Clear on error
-                    format!("Error: {:?}", err)
-                }
-            },
+    /// Write the buffer to a local path or share URL and make it the current
+    /// file. A share URL is saved in the background.
+    fn save_to_location(&mut self, location: &str) -> bool {
+        #[cfg(feature = "webdav")]
+        if file_backends::is_remote(location) {
+            return self.start_share_transfer(location, Some(self.code.clone()));
+        }
+        match self.file_backends.write(location, &self.code) {
+            Ok(()) => {
+                self.last_file_path = Some(location.to_string());
+                self.file_saved();
+                true
+            }
             Err(err) => {
-                format!("Error: {:?}", err)
+                self.show_error(format!("Could not save {}: {}", location, err));
+                false
             }
         }
     }
 
-    fn move_turtle(&mut self, distance: f32, draw: bool) {
-        let angle_rad = self.turtle_state.angle.to_radians();
-        let new_x = self.turtle_state.x + distance * angle_rad.cos();
-        let new_y = self.turtle_state.y + distance * angle_rad.sin();
-
-        if draw {
-            // Store the line for rendering
-            self.turtle_commands.push(format!(
-                "LINE {} {} {} {}",
-                self.turtle_state.x, self.turtle_state.y, new_x, new_y
-            ));
+    /// Save to the current file, asking for a name if it has never been saved
+    fn save_current_file(&mut self) {
+        if let Some(path) = self.last_file_path.clone() {
+            // Another program changed the file since it was opened: ask first
+            if let Some(content) = self.file_watcher.check_now(std::path::Path::new(&path)) {
+                self.external_conflict = Some(content);
+                return;
+            }
+            // Don't set output for file operations - keep output clean for program results only
+            self.save_to_location(&path);
+        } else if let Some(path) = FileDialog::new()
+            .set_file_name(self.untitled_file_name())
+            .save_file()
+        {
+            let location = path.display().to_string();
+            if self.save_to_location(&location) {
+                self.remember_recent_file(&location);
+            }
         }
+    }
 
-        self.turtle_state.x = new_x;
-        self.turtle_state.y = new_y;
+    /// Called after every successful save. In watch mode this schedules a
+    /// re-run; saving again before it fires pushes it back (debounce).
+    fn file_saved(&mut self) {
+        if let Some(path) = &self.last_file_path {
+            self.file_watcher
+                .track(std::path::Path::new(path), &self.code);
+        }
+        self.external_conflict = None;
+        if self.watch_mode {
+            self.watch_run_due = Some(std::time::Instant::now() + WATCH_DEBOUNCE);
+        }
     }
 
-    fn process_graphics_commands(&mut self, commands: &[crate::languages::basic::GraphicsCommand]) {
-        for cmd in commands {
-            match cmd.command.as_str() {
-                "FORWARD" => {
-                    self.move_turtle(cmd.value, true);
-                }
-                "RIGHT" => {
-                    self.turtle_state.angle = (self.turtle_state.angle + cmd.value) % 360.0;
-                }
-                _ => {
-                    // Unknown command, ignore
-                }
+    /// Whether a scheduled watch-mode run is due at `now`, consuming it if so
+    fn take_due_watch_run(&mut self, now: std::time::Instant) -> bool {
+        match self.watch_run_due {
+            Some(due) if self.watch_mode && now >= due => {
+                self.watch_run_due = None;
+                true
             }
+            Some(_) if !self.watch_mode => {
+                self.watch_run_due = None;
+                false
+            }
+            _ => false,
         }
     }
 
-    // Clipboard operations
-    fn copy_text(&mut self, ctx: &egui::Context) {
-        // For now, copy the entire code content
-        // In a full implementation, this would copy selected text
-        ctx.output_mut(|o| o.copied_text = self.code.clone());
-        self.clipboard_content = self.code.clone();
+    /// Run the program if a watch-mode run is due, otherwise make sure we
+    /// wake up in time for it
+    fn poll_watch_mode(&mut self, ctx: &egui::Context) {
+        let now = std::time::Instant::now();
+        if self.take_due_watch_run(now) {
+            self.rerun_for_watch();
+        } else if let Some(due) = self.watch_run_due {
+            ctx.request_repaint_after(due.saturating_duration_since(now));
+        }
     }
 
-    fn cut_text(&mut self, ctx: &egui::Context) {
-        // For now, cut the entire code content
-        // In a full implementation, this would cut selected text
-        ctx.output_mut(|o| o.copied_text = self.code.clone());
-        self.clipboard_content = self.code.clone();
-        self.code.clear();
+    /// Abandon a run that is waiting for input or paused in the debugger
+    fn cancel_running_program(&mut self) {
+        if self.waiting_for_input {
+            self.waiting_for_input = false;
+            self.input_prompt.clear();
+            self.current_input_var.clear();
+            self.user_input.clear();
+        }
+        self.pending_permission = None;
+        self.polling_input = false;
+        #[cfg(feature = "audio")]
+        self.music_player.stop();
+        if self.debug_state != DebugState::Stopped {
+            self.debug_state = DebugState::Stopped;
+            self.current_debug_line = None;
+        }
+        self.basic_interpreter = None;
+        self.waiting_program = None;
     }
 
-    fn paste_text(&mut self, ctx: &egui::Context) {
-        // Check for paste events
-        let paste_text = ctx.input(|i| {
-            i.events.iter().find_map(|e| {
-                if let egui::Event::Paste(text) = e {
-                    Some(text.clone())
-                } else {
-                    None
-                }
-            })
-        });
+    /// Pick up changes another editor made to the open file. A clean buffer is
+    /// reloaded straight away (and re-run in watch mode); a dirty one waits for
+    /// the user to choose in the conflict prompt.
+    fn poll_external_changes(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.last_file_path.clone() else {
+            return;
+        };
+        if !self.file_watcher.enabled {
+            return;
+        }
+        ctx.request_repaint_after(file_watch::POLL_INTERVAL);
 
-        if let Some(text) = paste_text {
-            // Insert clipboard content at cursor position
-            // For now, replace entire content - in full implementation would insert at cursor
-            self.code = text;
+        let now = std::time::Instant::now();
+        if let Some(content) = self.file_watcher.check(std::path::Path::new(&path), now) {
+            self.external_file_changed(content);
         }
     }
-}
 
-impl TimeWarpApp {
-    // Debug methods
-    fn start_debug_session(&mut self) {
-        self.debug_state = DebugState::Running;
-        self.debug_variables.clear();
-        self.debug_call_stack.clear();
-        self.current_debug_line = Some(1);
-        self.output = "Debug session started.\n".to_string();
+    fn external_file_changed(&mut self, content: String) {
+        if self.file_watcher.is_dirty(&self.code) {
+            self.external_conflict = Some(content);
+        } else {
+            self.reload_from_disk(content);
+        }
     }
 
-    fn stop_debug_session(&mut self) {
-        self.debug_state = DebugState::Stopped;
-        self.current_debug_line = None;
-        self.output = "Debug session stopped.\n".to_string();
+    /// Replace the buffer with what another editor saved
+    fn reload_from_disk(&mut self, content: String) {
+        self.save_undo_state();
+        self.code = content;
+        self.file_watcher.accept(&self.code);
+        self.external_conflict = None;
+        if self.watch_mode {
+            self.watch_run_due = Some(std::time::Instant::now() + WATCH_DEBOUNCE);
+        }
     }
 
-    fn step_debug(&mut self) {
-        if let Some(current_line) = self.current_debug_line {
-            self.current_debug_line = Some(current_line + 1);
-            // In a full implementation, this would execute one line of code
-            self.output = format!("Stepped to line {}\n", current_line + 1);
+    /// Open the current file in the configured external editor
+    fn open_in_external_editor(&mut self) {
+        let Some(path) = self.last_file_path.clone() else {
+            self.show_error("Save the file before opening it in another editor".to_string());
+            return;
+        };
+        if let Err(err) =
+            file_watch::open_in_editor(&self.external_editor, std::path::Path::new(&path))
+        {
+            self.show_error(format!(
+                "Could not start editor '{}': {}",
+                self.external_editor, err
+            ));
         }
     }
 
-    fn render_debug_editor(&mut self, ui: &mut egui::Ui) {
-        let filename = self
-            .last_file_path
-            .as_ref()
-            .and_then(|p| std::path::Path::new(p).file_name())
-            .and_then(|n| n.to_str())
-            .unwrap_or("untitled");
+    fn render_external_conflict(&mut self, ctx: &egui::Context) {
+        let Some(disk_content) = self.external_conflict.clone() else {
+            return;
+        };
+        let mut reload = false;
+        let mut keep = false;
 
-        let syntax_enabled = self.syntax_highlighting_enabled;
-        let current_debug_line = self.current_debug_line;
-        let language = "TW BASIC".to_string();
-        let keywords: Vec<String> = self
-            .get_language_keywords()
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect();
+        egui::Window::new("⚠️ File Changed on Disk")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} was changed by another program, but you have unsaved edits here.",
+                    self.last_file_path.as_deref().unwrap_or("The file")
+                ));
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("📥 Reload from Disk")
+                        .on_hover_text("Discard the edits in Time Warp (Undo brings them back)")
+                        .clicked()
+                    {
+                        reload = true;
+                    }
+                    if ui
+                        .button("✏️ Keep My Version")
+                        .on_hover_text("Keep editing; the next save overwrites the file on disk")
+                        .clicked()
+                    {
+                        keep = true;
+                    }
+                });
+            });
 
-        egui::ScrollArea::vertical().show(ui, |ui| {
-            ui.set_width(ui.available_width());
+        if reload {
+            self.reload_from_disk(disk_content);
+        } else if keep {
+            self.external_conflict = None;
+        }
+    }
 
-            let lines: Vec<String> = self.code.lines().map(|s| s.to_string()).collect();
-            let breakpoints = self
-                .breakpoints
-                .entry(filename.to_string())
-                .or_insert_with(Vec::new);
+    /// Cancel whatever is still running and start again on a fresh canvas,
+    /// keeping the current zoom and pan
+    fn rerun_for_watch(&mut self) {
+        self.cancel_running_program();
+        self.clear_canvas();
+        self.execute_code();
+    }
 
-            for (line_idx, line) in lines.iter().enumerate() {
-                ui.horizontal(|ui| {
-                    // Breakpoint column
-                    let line_number = (line_idx + 1) as u32;
-                    let has_breakpoint = breakpoints.contains(&line_number);
-
-                    let breakpoint_button =
-                        egui::Button::new(if has_breakpoint { "🔴" } else { "⚪" })
-                            .frame(false)
-                            .small();
-
-                    if ui
-                        .add(breakpoint_button)
-                        .on_hover_text(if has_breakpoint {
-                            "Click to remove breakpoint"
-                        } else {
-                            "Click to add breakpoint"
-                        })
-                        .clicked()
-                    {
-                        if has_breakpoint {
-                            breakpoints.retain(|&x| x != line_number);
-                        } else {
-                            breakpoints.push(line_number);
-                            breakpoints.sort();
-                        }
-                    }
-
-                    // Line number
-                    ui.label(
-                        egui::RichText::new(format!("{:4}", line_number))
-                            .color(egui::Color32::from_rgb(100, 100, 100))
-                            .font(egui::FontId::monospace(12.0)),
-                    );
+    /// Hand the answer box to the program that is waiting for it and carry on
+    fn submit_program_input(&mut self) {
+        let input = std::mem::take(&mut self.user_input);
+        // Store the input in the variable
+        self.variables
+            .insert(std::mem::take(&mut self.current_input_var), input.clone());
+        self.waiting_for_input = false;
+        self.input_prompt.clear();
+
+        // Provide input to the BASIC interpreter and continue execution
+        if let Some(ref mut interpreter) = self.basic_interpreter {
+            // The interpreter carries on from the statement after the INPUT
+            let started = std::time::Instant::now();
+            let result = interpreter.provide_input(&input);
+            self.continue_basic_run(result, started.elapsed());
+        }
 
-                    // Current debug line indicator
-                    if Some(line_number) == current_debug_line {
-                        ui.label(egui::RichText::new("▶").color(egui::Color32::YELLOW));
-                    } else {
-                        ui.add_space(12.0);
-                    }
+        if let Some(mut program) = self.waiting_program.take() {
+            let result = program.continue_with_input(&input);
+            let more = self.waiting_result(result, program);
+            self.output.push_str(&more);
+        }
+        self.finish_test_run();
+    }
 
-                    // Line content with syntax highlighting
-                    if syntax_enabled {
-                        // Simple syntax highlighting for debug view
-                        let highlighted = Self::highlight_line_static(&line, &keywords, &language);
-                        for (text, color) in highlighted {
-                            ui.label(
-                                egui::RichText::new(text)
-                                    .color(color)
-                                    .font(egui::FontId::monospace(12.0)),
-                            );
-                        }
-                    } else {
-                        ui.label(egui::RichText::new(line).font(egui::FontId::monospace(12.0)));
-                    }
-                });
+    /// Show how the stored BASIC run went after carrying on from an answer:
+    /// it may have ended, be waiting again or have reached a breakpoint
+    fn continue_basic_run(
+        &mut self,
+        result: Result<
+            time_warp_core::basic::ExecutionResult,
+            time_warp_core::basic::InterpreterError,
+        >,
+        ran_for: std::time::Duration,
+    ) {
+        if let Some(interpreter) = self.basic_interpreter.as_mut() {
+            // Statements accumulate across slices; time only counts while running
+            let stats = self
+                .execution_stats
+                .get_or_insert_with(ExecutionStats::default);
+            stats.statements = interpreter.instruction_count();
+            stats.max_instructions = interpreter.max_instructions;
+            stats.elapsed += ran_for;
+            let printed = interpreter.take_printer_output();
+            #[cfg(feature = "audio")]
+            let music = interpreter.take_music();
+            let assertions = interpreter.assertion_results().to_vec();
+            if interpreter.cleared_screen() {
+                self.output.clear();
             }
+            self.assertion_results = self.included_assertions(&assertions);
+            self.receive_printout(printed);
+            #[cfg(feature = "audio")]
+            self.play_music(music);
+        }
+        match result {
+            Ok(result) => match result {
+                time_warp_core::basic::ExecutionResult::Complete {
+                    output,
+                    graphics_commands,
+                } => {
+                    self.process_graphics_commands(&graphics_commands);
+                    self.output.push_str(&output);
+                    self.basic_interpreter = None;
+                }
+                time_warp_core::basic::ExecutionResult::NeedInput {
+                    variable,
+                    prompt,
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    self.process_graphics_commands(&partial_graphics);
+                    self.waiting_for_input = true;
+                    self.input_prompt = prompt.clone();
+                    self.current_input_var = variable;
+                    self.output = format!("{}{}{}", self.output, partial_output, prompt);
+                    // Keep waiting for more input
+                }
+                time_warp_core::basic::ExecutionResult::Break {
+                    line,
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    // Debug session hit a breakpoint after the input
+                    self.process_graphics_commands(&partial_graphics);
+                    self.output = format!("{}{}", self.output, partial_output);
+                    self.current_debug_line = Some(self.included.main_line(line) as u32);
+                    self.debug_state = DebugState::Paused;
+                }
+                time_warp_core::basic::ExecutionResult::NeedPermission {
+                    permission,
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    self.process_graphics_commands(&partial_graphics);
+                    self.output.push_str(&partial_output);
+                    self.pending_permission = Some(permission);
+                }
+                time_warp_core::basic::ExecutionResult::Polling {
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    self.process_graphics_commands(&partial_graphics);
+                    self.output.push_str(&partial_output);
+                    self.polling_input = true;
+                }
+                time_warp_core::basic::ExecutionResult::Error(err) => {
+                    let message = format!("Error: {}", err);
+                    self.output.push_str(&message);
+                    self.basic_interpreter = None;
+                }
+            },
+            Err(err) => {
+                let message = self.run_error(&err);
+                self.output.push_str(&message);
+                self.basic_interpreter = None;
+            }
+        }
+    }
 
-            // Handle empty last line
-            if self.code.ends_with('\n') || self.code.is_empty() {
-                ui.horizontal(|ui| {
-                    let line_number = (lines.len() + 1) as u32;
-                    let has_breakpoint = breakpoints.contains(&line_number);
-
-                    let breakpoint_button =
-                        egui::Button::new(if has_breakpoint { "🔴" } else { "⚪" })
-                            .frame(false)
-                            .small();
+    /// Run a program through its language's backend. A run that stops for
+    /// an answer stays in `waiting_program` until the answer is submitted.
+    fn execute_with(&mut self, language: Language, code: &str) -> String {
+        let mut backend = language.backend();
+        let max_instructions = (self.execution_timeout_ms() * 1000) as usize;
+
+        backend.queue_input(self.scripted_answers());
+        let started = std::time::Instant::now();
+        let result = backend.execute(code, max_instructions);
+        self.execution_stats = Some(ExecutionStats {
+            statements: backend.instruction_count(),
+            elapsed: started.elapsed(),
+            max_instructions,
+        });
+        self.coverage = None;
+        self.diagnostics.clear();
+        self.basic_interpreter = None;
 
-                    if ui
-                        .add(breakpoint_button)
-                        .on_hover_text(if has_breakpoint {
-                            "Click to remove breakpoint"
-                        } else {
-                            "Click to add breakpoint"
-                        })
-                        .clicked()
-                    {
-                        if has_breakpoint {
-                            breakpoints.retain(|&x| x != line_number);
-                        } else {
-                            breakpoints.push(line_number);
-                            breakpoints.sort();
-                        }
-                    }
+        let output = self.waiting_result(result, backend);
+        if self.waiting_for_input {
+            // Show what was written so far while the program waits
+            self.output = output.clone();
+        }
+        output
+    }
 
-                    ui.label(
-                        egui::RichText::new(format!("{:4}", line_number))
-                            .color(egui::Color32::from_rgb(100, 100, 100))
-                            .font(egui::FontId::monospace(12.0)),
-                    );
-                    ui.add_space(12.0);
-                });
+    /// Text to show for one stretch of a run, drawing its turtle moves and
+    /// keeping the program if it stopped to wait for an answer
+    fn waiting_result(
+        &mut self,
+        result: Result<
+            time_warp_core::basic::ExecutionResult,
+            time_warp_core::basic::InterpreterError,
+        >,
+        mut program: Box<dyn time_warp_core::LanguageBackend>,
+    ) -> String {
+        use time_warp_core::basic::ExecutionResult;
+
+        self.waiting_program = None;
+        match result {
+            Ok(ExecutionResult::NeedInput {
+                variable,
+                prompt,
+                partial_output,
+                partial_graphics,
+            }) => {
+                self.process_graphics_commands(&partial_graphics);
+                self.waiting_for_input = true;
+                self.input_prompt = prompt.clone();
+                self.current_input_var = variable;
+                self.waiting_program = Some(program);
+                format!("{}{}", partial_output, prompt)
             }
-        });
+            // Only BASIC asks before changing files or polls a live keyboard,
+            // and only when run by execute_tw_basic
+            Ok(ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            })
+            | Ok(ExecutionResult::Break {
+                partial_output: output,
+                partial_graphics: graphics_commands,
+                ..
+            })
+            | Ok(ExecutionResult::NeedPermission {
+                partial_output: output,
+                partial_graphics: graphics_commands,
+                ..
+            })
+            | Ok(ExecutionResult::Polling {
+                partial_output: output,
+                partial_graphics: graphics_commands,
+            }) => {
+                self.process_graphics_commands(&graphics_commands);
+                output
+            }
+            Ok(ExecutionResult::Error(message)) => {
+                self.record_error_for_hints(ErrorCode::Runtime);
+                format!("Error: {}", message)
+            }
+            Err(err) => {
+                let message = format!("Error: {}", err);
+                self.record_error_for_hints(err.code());
+                self.process_graphics_commands(&program.take_graphics());
+                format!("{}{}", program.take_output(), message)
+            }
+        }
+    }
+
+    /// Parse the editor's program again for the Syntax Tree tab
+    fn refresh_syntax_tree(&mut self) {
+        self.syntax_tree = if self.language == Language::Basic {
+            time_warp_core::basic::tree::parse_to_tree(&self.code)
+                .map_err(|err| format!("Error: {}", err))
+        } else {
+            Err(format!(
+                "The syntax tree shows TW BASIC programs, not {}.",
+                self.language.name()
+            ))
+        };
     }
 
-    fn highlight_line_static(
-        line: &str,
-        keywords: &[String],
-        language: &str,
-    ) -> Vec<(String, egui::Color32)> {
-        if line.trim().is_empty() {
-            return vec![(line.to_string(), egui::Color32::BLACK)];
+    /// Show the parsed program as JSON in the output, for tool developers
+    fn dump_ast(&mut self) {
+        if self.language != Language::Basic {
+            self.output = format!(
+                "Dump AST shows TW BASIC programs, not {}.\n",
+                self.language.name()
+            );
+            return;
         }
+        self.output = match time_warp_core::basic::dump::parse_to_json(&self.code) {
+            Ok(ast) => format!("{}\n", ast.pretty()),
+            Err(err) => format!("Error: {}\n", err),
+        };
+    }
 
-        let mut highlighted = Vec::new();
-        let chars: Vec<char> = line.chars().collect();
-        let mut i = 0;
+    /// The Scripted Input answers, one per line, in the order INPUT takes them
+    fn scripted_answers(&self) -> Vec<String> {
+        self.scripted_input.lines().map(str::to_string).collect()
+    }
 
-        // Create keyword set from provided keywords
-        let keyword_set: std::collections::HashSet<String> =
-            keywords.iter().map(|k| k.to_uppercase()).collect();
+    fn execute_tw_basic(&mut self, code: &str) -> String {
+        use time_warp_core::basic::Interpreter;
 
-        while i < chars.len() {
-            // Check for comments first
-            if Self::is_comment_start_static(&line[i..], language) {
-                highlighted.push((line[i..].to_string(), egui::Color32::from_rgb(0, 128, 0)));
-                break;
+        // The parser understands line numbers itself and keeps one source line per
+        // editor line, so GOTO/GOSUB targets and debugger positions stay accurate
+        let mut interpreter = Interpreter::new();
+        // Set execution timeout based on instruction limit
+        // Rough estimate: 1000 instructions per second
+        interpreter.max_instructions = (self.execution_timeout_ms() * 1000) as usize;
+        interpreter.set_dialect(self.settings.basic_dialect);
+        interpreter.set_strictness(self.settings.basic_strictness);
+        self.set_up_files(&mut interpreter);
+        interpreter.set_live_input(true);
+        #[cfg(feature = "profiler")]
+        interpreter.set_profiling(self.profiling_enabled);
+        interpreter.queue_input(self.scripted_answers());
+
+        self.included = match self.expand_includes(code) {
+            Ok(expanded) => expanded,
+            Err(err) => {
+                self.basic_interpreter = None;
+                self.coverage = None;
+                self.diagnostics.clear();
+                self.show_diagnostics = true;
+                self.record_error_for_hints(err.code());
+                return format!("Error: {}", err);
             }
+        };
+        let started = std::time::Instant::now();
+        let execution = interpreter.execute(&self.included.code);
+        self.receive_printout(interpreter.take_printer_output());
+        #[cfg(feature = "audio")]
+        self.play_music(interpreter.take_music());
+        self.assertion_results = self.included_assertions(interpreter.assertion_results());
+        self.diagnostics = self.included_diagnostics(interpreter.diagnostics());
+        self.execution_stats = Some(ExecutionStats {
+            statements: interpreter.instruction_count(),
+            elapsed: started.elapsed(),
+            max_instructions: interpreter.max_instructions,
+        });
+        // Coverage is shown on the editor, so lines from included files are left out
+        let own_lines = |lines: Vec<usize>| {
+            lines
+                .into_iter()
+                .filter_map(|line| self.included.origin(line))
+                .filter(|origin| origin.file.is_none())
+                .map(|origin| origin.line)
+                .collect()
+        };
+        self.coverage = Some(CoverageReport {
+            executed: own_lines(interpreter.executed_lines()),
+            executable: own_lines(interpreter.executable_lines()),
+        });
+        #[cfg(feature = "profiler")]
+        if self.profiling_enabled {
+            self.profile_results = interpreter.profile_report();
+            self.show_profiler = true;
+        }
 
-            // Check for strings
-            if chars[i] == '"' {
-                let mut end = i + 1;
-                while end < chars.len() && chars[end] != '"' {
-                    end += 1;
-                }
-                if end < chars.len() {
-                    end += 1;
-                }
-
-                if i > 0 {
-                    highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
+        match execution {
+            Ok(result) => match result {
+                time_warp_core::basic::ExecutionResult::Complete {
+                    output,
+                    graphics_commands,
+                } => {
+                    // Process graphics commands
+                    self.process_graphics_commands(&graphics_commands);
+                    self.basic_interpreter = None; // Clear stored interpreter
+                    output
                 }
-                highlighted.push((
-                    line[i..end].to_string(),
-                    egui::Color32::from_rgb(163, 21, 21),
-                ));
-                i = end;
-                continue;
-            }
-
-            // Check for numbers
-            if chars[i].is_ascii_digit() {
-                let mut end = i + 1;
-                while end < chars.len() && (chars[end].is_ascii_digit() || chars[end] == '.') {
-                    end += 1;
+                time_warp_core::basic::ExecutionResult::NeedInput {
+                    variable,
+                    prompt,
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    self.waiting_for_input = true;
+                    self.input_prompt = prompt.clone();
+                    self.current_input_var = variable;
+                    // Process any graphics commands that were executed before input was needed
+                    self.process_graphics_commands(&partial_graphics);
+                    // Store the interpreter for continuation
+                    self.basic_interpreter = Some(interpreter);
+                    // Show the prompt while the program waits for its answer
+                    self.output = format!("{}{}", partial_output, prompt);
+                    self.output.clone()
                 }
-
-                if i > 0 {
-                    highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
+                time_warp_core::basic::ExecutionResult::Break {
+                    partial_output,
+                    partial_graphics,
+                    ..
+                } => {
+                    // Only debug sessions set breakpoints, so treat this as completion
+                    self.process_graphics_commands(&partial_graphics);
+                    self.basic_interpreter = None;
+                    partial_output
                 }
-                highlighted.push((
-                    line[i..end].to_string(),
-                    egui::Color32::from_rgb(0, 128, 128),
-                ));
-                i = end;
-                continue;
-            }
-
-            // Check for operators
-            if "+-*/=<>!&|^%".contains(chars[i]) {
-                let mut end = i + 1;
-                // Handle compound operators like ==, !=, <=, >=, +=, etc.
-                if end < chars.len() && "+-*/=<>!&|^%".contains(chars[end]) {
-                    end += 1;
+                time_warp_core::basic::ExecutionResult::NeedPermission {
+                    permission,
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    self.process_graphics_commands(&partial_graphics);
+                    // Kept until the permission prompt is answered
+                    self.basic_interpreter = Some(interpreter);
+                    self.pending_permission = Some(permission);
+                    self.output = partial_output;
+                    self.output.clone()
                 }
-
-                if i > 0 {
-                    highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
+                time_warp_core::basic::ExecutionResult::Polling {
+                    partial_output,
+                    partial_graphics,
+                } => {
+                    self.process_graphics_commands(&partial_graphics);
+                    // Carried on each frame by pump_program_input
+                    self.basic_interpreter = Some(interpreter);
+                    self.polling_input = true;
+                    self.grab_keyboard = true;
+                    self.output = partial_output;
+                    self.output.clone()
                 }
-                highlighted.push((
-                    line[i..end].to_string(),
-                    egui::Color32::from_rgb(128, 64, 0),
-                )); // Orange-brown for operators
-                i = end;
-                continue;
-            }
-
-            // Check for brackets and parentheses
-            if "(){}[]".contains(chars[i]) {
-                if i > 0 {
-                    highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
+                time_warp_core::basic::ExecutionResult::Error(err) => {
+                    self.basic_interpreter = None; // Clear on error
+                    self.record_error_for_hints(ErrorCode::Runtime);
+                    format!("Error: {}", err)
                 }
-                highlighted.push((
-                    line[i..i + 1].to_string(),
-                    egui::Color32::from_rgb(128, 0, 128),
-                )); // Purple for brackets
-                i += 1;
-                continue;
-            }
-
-            // Check for keywords
-            let remaining = &line[i..];
-            let mut _found_keyword = false;
-            for keyword in &keyword_set {
-                if remaining.to_uppercase().starts_with(keyword) {
-                    let keyword_len = keyword.len();
-                    let next_char = if i + keyword_len < chars.len() {
-                        chars[i + keyword_len]
-                    } else {
-                        ' '
-                    };
-
-                    if next_char.is_whitespace()
-                        || next_char == '('
-                        || next_char == ')'
-                        || next_char == ','
-                        || next_char == ';'
-                        || next_char == ':'
-                    {
-                        if i > 0 {
-                            highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
-                        }
-                        highlighted.push((
-                            line[i..i + keyword_len].to_string(),
-                            egui::Color32::from_rgb(0, 0, 255),
-                        ));
-                        i += keyword_len;
-                        _found_keyword = true;
-                        break;
+            },
+            Err(err) => {
+                // A program that doesn't parse (or that strict mode turns
+                // away) has its mistakes listed where they can be clicked
+                if matches!(
+                    err.inner(),
+                    time_warp_core::basic::InterpreterError::ParseError { .. }
+                ) {
+                    self.show_diagnostics = true;
+                    // and marked in the editor, underlined where each one stops
+                    if self.diagnostics.is_empty() {
+                        self.diagnostics = self.check_program();
+                        self.diagnostics.retain(|found| {
+                            found.severity == time_warp_core::basic::strict::Severity::Error
+                        });
                     }
                 }
+                self.record_error_for_hints(err.code());
+                self.run_error(&err)
             }
+        }
+    }
 
-            // Check for operators
-            if "+-*/=<>!&|^%".contains(chars[i]) {
-                let mut end = i + 1;
-                // Handle compound operators like ==, !=, <=, >=, +=, etc.
-                if end < chars.len() && "+-*/=<>!&|^%".contains(chars[end]) {
-                    end += 1;
-                }
+    /// A BASIC program with its INCLUDE lines filled in. Included files are
+    /// found next to the program's file, or in the current folder for a
+    /// program that hasn't been saved.
+    fn expand_includes(
+        &self,
+        code: &str,
+    ) -> Result<Expanded, time_warp_core::basic::InterpreterError> {
+        let path = self
+            .last_file_path
+            .as_deref()
+            .filter(|location| !location.contains("://"))
+            .map(std::path::Path::new);
+        time_warp_core::basic::include::expand(code, path, &mut |path| {
+            std::fs::read_to_string(path)
+        })
+    }
 
-                if i > 0 {
-                    highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
-                }
-                highlighted.push((
-                    line[i..end].to_string(),
-                    egui::Color32::from_rgb(128, 64, 0),
-                )); // Orange-brown for operators
-                i = end;
-                continue;
-            }
+    /// An error from the BASIC run, where it happened pointing at the file
+    /// that line was written in, and ending with the BASIC line number of
+    /// that statement
+    fn run_error(&self, err: &time_warp_core::basic::InterpreterError) -> String {
+        let mut message = format!("Error: {}", self.included.locate_error(err));
+        let number = err.position().and_then(|position| {
+            let text = self
+                .included
+                .code
+                .lines()
+                .nth(position.line - 1)?
+                .trim_start();
+            let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            text[..digits].parse::<usize>().ok()
+        });
+        if let Some(number) = number {
+            message.push_str(&format!(" in {}", number));
+        }
+        message
+    }
 
-            // Check for brackets and parentheses
-            if "(){}[]".contains(chars[i]) {
-                if i > 0 {
-                    highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
-                }
-                highlighted.push((
-                    line[i..i + 1].to_string(),
-                    egui::Color32::from_rgb(128, 0, 128),
-                )); // Purple for brackets
-                i += 1;
-                continue;
-            }
+    /// ASSERT results with their lines in the program as the editor shows it
+    fn included_assertions(
+        &self,
+        results: &[time_warp_core::basic::AssertionResult],
+    ) -> Vec<time_warp_core::basic::AssertionResult> {
+        results
+            .iter()
+            .map(|result| time_warp_core::basic::AssertionResult {
+                line: self.included.main_line(result.line),
+                ..result.clone()
+            })
+            .collect()
+    }
 
-            // Check for keywords
-            let remaining = &line[i..];
-            let mut _found_keyword = false;
-            for keyword in &keyword_set {
-                if remaining.to_uppercase().starts_with(keyword) {
-                    let keyword_len = keyword.len();
-                    let next_char = if i + keyword_len < chars.len() {
-                        chars[i + keyword_len]
-                    } else {
-                        ' '
-                    };
+    /// Strict mode's findings on the lines the editor shows; one in an
+    /// included file is marked on its INCLUDE line
+    fn included_diagnostics(
+        &self,
+        found: &[time_warp_core::basic::strict::Diagnostic],
+    ) -> Vec<time_warp_core::basic::strict::Diagnostic> {
+        found
+            .iter()
+            .map(|diagnostic| time_warp_core::basic::strict::Diagnostic {
+                line: self.included.main_line(diagnostic.line),
+                ..diagnostic.clone()
+            })
+            .collect()
+    }
 
-                    if next_char.is_whitespace()
-                        || next_char == '('
-                        || next_char == ')'
-                        || next_char == ','
-                        || next_char == ';'
-                        || next_char == ':'
-                    {
-                        if i > 0 {
-                            highlighted.push((line[..i].to_string(), egui::Color32::BLACK));
-                        }
-                        highlighted.push((
-                            line[i..i + keyword_len].to_string(),
-                            egui::Color32::from_rgb(0, 0, 255),
-                        ));
-                        i += keyword_len;
-                        _found_keyword = true;
-                        break;
-                    }
-                }
-            }
+    /// Name used to group repeated errors by file
+    #[cfg(feature = "hints")]
+    fn hint_file_key(&self) -> String {
+        self.last_file_path
+            .clone()
+            .unwrap_or_else(|| "untitled".to_string())
+    }
 
-            if !_found_keyword {
-                i += 1;
-            }
+    /// Count an error and queue a hint card when the same kind keeps recurring
+    #[cfg(feature = "hints")]
+    fn record_error_for_hints(&mut self, code: ErrorCode) {
+        if !self.settings.hints_enabled {
+            return;
         }
-
-        if i < line.len() {
-            highlighted.push((line[i..].to_string(), egui::Color32::BLACK));
+        let file = self.hint_file_key();
+        if let Some(hint) = self.error_tracker.record(&file, code) {
+            self.active_hint = Some(hint);
         }
-
-        highlighted
     }
 
-    fn is_comment_start_static(text: &str, language: &str) -> bool {
-        match language {
-            "TW BASIC" => text.starts_with("REM ") || text.starts_with("'"),
-            _ => text.starts_with("//") || text.starts_with("#"),
-        }
-    }
+    #[cfg(not(feature = "hints"))]
+    fn record_error_for_hints(&mut self, _code: ErrorCode) {}
 
-    // Code completion methods
-    fn get_language_keywords(&self) -> Vec<&'static str> {
-        vec![
-            "PRINT",
-            "INPUT",
-            "LET",
-            "IF",
-            "THEN",
-            "ELSE",
-            "FOR",
-            "TO",
-            "STEP",
-            "NEXT",
-            "WHILE",
-            "WEND",
-            "GOTO",
-            "GOSUB",
-            "RETURN",
-            "END",
-            "CLS",
-            "LOCATE",
-            "COLOR",
-            "BEEP",
-            "SLEEP",
-            "RANDOMIZE",
-            "RND",
-            "INT",
-            "STR$",
-            "VAL",
-            "LEN",
-            "LEFT$",
-            "RIGHT$",
-            "MID$",
-            "CHR$",
-            "ASC",
-            "ABS",
-            "SIN",
-            "COS",
-            "TAN",
-            "LOG",
-            "EXP",
-            "SQR",
-            "AND",
-            "OR",
-            "NOT",
-            "MOD",
-            "DIM",
-            "READ",
-            "DATA",
-            "RESTORE",
-            "DEF",
-            "FN",
-            "REM",
-        ]
-    }
+    #[cfg(feature = "hints")]
+    fn render_hint_card(&mut self, ctx: &egui::Context) {
+        let Some((card, count)) = self.active_hint else {
+            return;
+        };
+        let mut dismissed = false;
 
-    fn get_completion_suggestions(&self, query: &str) -> Vec<String> {
-        let mut suggestions = Vec::new();
-        let query_lower = query.to_lowercase();
+        egui::Window::new("💡 Hint")
+            .collapsible(false)
+            .resizable(false)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "You've hit '{}' {} times - here's how {} works",
+                        card.error_label, count, card.topic
+                    ))
+                    .strong(),
+                );
+                ui.separator();
+                ui.monospace(card.body);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Got it").clicked() {
+                        dismissed = true;
+                    }
+                    if ui.button("Turn off hints").clicked() {
+                        self.settings.hints_enabled = false;
+                        dismissed = true;
+                    }
+                });
+            });
 
-        // Add language keywords
-        let keywords = self.get_language_keywords();
-        for keyword in keywords {
-            if keyword.to_lowercase().starts_with(&query_lower) {
-                suggestions.push(keyword.to_string());
+        if dismissed {
+            self.active_hint = None;
+            if !self.settings.hints_enabled {
+                self.apply_settings(ctx); // saved, so hints stay off next time
             }
         }
+    }
 
-        // Add variables from debug session
-        for (var_name, _) in &self.debug_variables {
-            if var_name.to_lowercase().starts_with(&query_lower) {
-                suggestions.push(var_name.clone());
-            }
-        }
+    /// What an export shows: the canvas as currently zoomed and panned, or
+    /// the whole drawing
+    fn export_frame(&self) -> canvas_export::Frame {
+        let mut frame = if self.export_fit {
+            canvas_export::Frame::fit(&self.turtle_commands, FIT_EXPORT_SIZE)
+        } else {
+            canvas_export::Frame::view(self.canvas_size, self.turtle_pan, self.turtle_zoom)
+        };
+        frame.background = self.canvas_background;
+        frame
+    }
 
-        // Add TW BASIC functions and commands
-        let basic_functions = vec![
-            "ABS(", "ASC(", "CHR$(", "COS(", "EXP(", "INT(", "LEFT$(", "LEN(", "LOG(", "MID$(",
-            "RIGHT$(", "RND(", "SIN(", "SQR(", "STR$(", "TAN(", "VAL(",
-        ];
+    fn canvas_image(&self) -> canvas_export::Image {
+        canvas_export::render(&self.turtle_commands, &self.export_frame())
+    }
 
-        for func in basic_functions {
-            if func.to_lowercase().starts_with(&query_lower) {
-                suggestions.push(func.to_string());
-            }
+    /// Ask where to save the drawing and write it as a PNG or, for a `.svg`
+    /// file name, as a vector SVG
+    fn export_canvas_image(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .add_filter("SVG drawing", &["svg"])
+            .set_file_name("drawing.png")
+            .save_file()
+        else {
+            return;
+        };
+        if let Err(err) = self.write_canvas_export(&path) {
+            self.show_error(format!("Could not save image: {}", err));
         }
+    }
 
-        // Add BASIC commands that might be partially typed
-        let basic_commands = vec![
-            "PRINT",
-            "WRITELN",
-            "INPUT",
-            "READLN",
-            "LET",
-            "IF",
-            "THEN",
-            "ELSE",
-            "WHILE",
-            "DO",
-            "FOR",
-            "TO",
-            "STEP",
-            "NEXT",
-            "FORWARD",
-            "FD",
-            "BACK",
-            "BK",
-            "LEFT",
-            "LT",
-            "RIGHT",
-            "RT",
-            "PENUP",
-            "PU",
-            "PENDOWN",
-            "PD",
-            "WHILE",
-            "WEND",
-            "GOTO",
-            "GOSUB",
-            "RETURN",
-            "END",
-            "CLS",
-            "LOCATE",
-            "COLOR",
-            "BEEP",
-            "SLEEP",
-            "RANDOMIZE",
-        ];
-
-        for cmd in basic_commands {
-            if cmd.to_lowercase().starts_with(&query_lower) {
-                suggestions.push(cmd.to_string());
-            }
-        }
+    fn write_canvas_export(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        let is_svg = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+        let path = if is_svg {
+            canvas_export::save_svg(&self.turtle_commands, &self.export_frame(), path)?;
+            path.to_path_buf()
+        } else {
+            let path = path.with_extension("png");
+            canvas_export::save_png(&self.canvas_image(), &path)?;
+            path
+        };
+        self.output
+            .push_str(&format!("Image saved to {}\n", path.display()));
+        Ok(())
+    }
 
-        // Sort and deduplicate
-        suggestions.sort();
-        suggestions.dedup();
+    /// The turtle replay as an animated GIF, paced like the on-screen
+    /// animation at the current speed
+    fn animation_gif(&self) -> Vec<u8> {
+        let strokes = turtle::stroke_count(&self.turtle_commands);
+        let count = strokes.clamp(1, GIF_MAX_FRAMES);
+        let frames =
+            canvas_export::replay_frames(&self.turtle_commands, &self.export_frame(), count);
+
+        let seconds = strokes as f32 / self.turtle_speed.max(1.0);
+        // GIF viewers treat delays under 2 hundredths as "as fast as possible"
+        let delay = ((seconds * 100.0 / count as f32).round() as u16).max(2);
+        let mut delays = vec![delay; count];
+        delays[count - 1] = delay.saturating_add(GIF_FINAL_HOLD);
+        canvas_export::encode_gif(&frames, &delays)
+    }
 
-        // Limit to top 10 suggestions
-        suggestions.truncate(10);
+    /// Ask where to save the replay and record it as an animated GIF
+    fn export_animation(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("Animated GIF", &["gif"])
+            .set_file_name("drawing.gif")
+            .save_file()
+        else {
+            return;
+        };
+        let path = path.with_extension("gif");
+        match std::fs::write(&path, self.animation_gif()) {
+            Ok(()) => self
+                .output
+                .push_str(&format!("Animation saved to {}\n", path.display())),
+            Err(err) => self.show_error(format!("Could not save animation: {}", err)),
+        }
+    }
 
-        suggestions
+    /// Frame the whole drawing on the canvas, or reset the view if it is empty
+    fn fit_canvas_to_drawing(&mut self) {
+        let fit = turtle::fit_view(
+            &self.turtle_commands,
+            self.canvas_size,
+            canvas_export::FIT_MARGIN,
+        );
+        match fit {
+            Some((pan, zoom)) => (self.turtle_pan, self.turtle_zoom) = (pan, zoom),
+            None => self.reset_canvas_view(),
+        }
     }
 
-    #[allow(dead_code)]
-    fn apply_completion(&mut self, completion: &str) {
-        // Simple implementation - just append to current code
-        // In a real implementation, this would replace the current word
-        self.code.push_str(completion);
-        self.show_completion = false;
+    /// Centre the origin and show the classic 400x300 area, scaled up to fill
+    /// the canvas at its current size
+    fn reset_canvas_view(&mut self) {
+        let scale = self.canvas_size.min_elem() / TURTLE_CANVAS_SIZE.min_elem();
+        self.turtle_pan = egui::Vec2::ZERO;
+        self.turtle_zoom = scale.clamp(turtle::MIN_ZOOM, turtle::MAX_ZOOM);
     }
 
-    fn render_syntax_highlighted_editor(&mut self, ui: &mut egui::Ui) {
-        // Custom syntax highlighting implementation
-        let response = ui.add(
-            egui::TextEdit::multiline(&mut self.code)
-                .font(egui::TextStyle::Monospace)
-                .desired_width(f32::INFINITY)
-                .desired_rows(20),
-        );
+    /// Mouse-wheel or pinch zoom over the canvas, keeping the point under the
+    /// cursor still
+    fn zoom_canvas_at(&mut self, center: egui::Pos2, anchor: egui::Pos2, factor: f32) {
+        let new_zoom = (self.turtle_zoom * factor).clamp(turtle::MIN_ZOOM, turtle::MAX_ZOOM);
+        self.turtle_pan =
+            turtle::zoom_about(center, self.turtle_pan, self.turtle_zoom, new_zoom, anchor);
+        self.turtle_zoom = new_zoom;
+    }
 
-        // Check if code changed and save undo state
-        if response.changed() && self.code != self.previous_code {
-            self.save_undo_state();
-            self.previous_code = self.code.clone();
+    /// The canvas was laid out at `size`. Scale the zoom with it so the same
+    /// part of the drawing stays in view as the window grows or shrinks.
+    fn resize_canvas(&mut self, size: egui::Vec2) {
+        if size == self.canvas_size {
+            return;
         }
+        let scale = size.min_elem() / self.canvas_size.min_elem();
+        self.turtle_zoom = (self.turtle_zoom * scale).clamp(turtle::MIN_ZOOM, turtle::MAX_ZOOM);
+        self.canvas_size = size;
+    }
 
-        // Handle keyboard shortcuts for completion
-        if ui.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Space)) {
-            self.trigger_completion();
+    /// Show or hide every turtle's cursor, as the toolbar toggle does
+    fn set_turtles_visible(&mut self, visible: bool) {
+        for state in self.turtles.values_mut() {
+            state.visible = visible;
         }
+    }
 
-        // Handle undo/redo keyboard shortcuts
-        if ui.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Z) && !i.modifiers.shift) {
-            self.undo();
-        }
-        if ui.input(|i| {
-            (i.modifiers.ctrl && i.key_pressed(egui::Key::Y))
-                || (i.modifiers.ctrl && i.modifiers.shift && i.key_pressed(egui::Key::Z))
-        }) {
-            self.redo();
-        }
+    /// Erase the drawing and put every turtle back home with a fresh pen
+    fn clear_canvas(&mut self) {
+        self.turtle_commands.clear();
+        self.drawn_commands.clear();
+        self.turtles = default_turtles();
+        self.canvas_pen = (turtle::DEFAULT_PEN_COLOR, turtle::DEFAULT_PEN_WIDTH);
+        self.turtle_progress = 0.0;
+    }
 
-        // Auto-completion triggers
-        if let Some(text) = ui.input(|i| {
-            i.events.iter().find_map(|e| match e {
-                egui::Event::Text(text) => Some(text.clone()),
-                _ => None,
-            })
-        }) {
-            // Trigger completion after typing certain characters
-            if text.chars().any(|c| c == '.' || c == '(' || c == ' ') {
-                // Small delay to avoid triggering on every keystroke
-                self.trigger_completion();
-            }
+    /// Move the replay on by `dt` seconds. Returns true while there is still
+    /// more to draw; with animation off everything shows at once.
+    fn advance_turtle_animation(&mut self, dt: f32) -> bool {
+        let strokes = turtle::stroke_count(&self.turtle_commands) as f32;
+        if !self.turtle_animate {
+            self.turtle_progress = strokes;
+            return false;
         }
+        // A long pause between frames shouldn't skip half the drawing
+        self.turtle_progress =
+            (self.turtle_progress + self.turtle_speed * dt.min(0.1)).min(strokes);
+        self.turtle_progress < strokes
+    }
 
-        // Render syntax highlighted preview below the editor
-        if !self.code.is_empty() && self.active_tab == 0 {
-            ui.separator();
-            ui.label(
-                egui::RichText::new("Syntax Highlighted Preview:")
-                    .small()
-                    .weak(),
-            );
+    /// Add a line in `pen`'s colour and width
+    fn draw_line(&mut self, pen: &TurtleState, from: egui::Pos2, to: egui::Pos2) {
+        self.draw_stroke(pen, turtle::TurtleOp::Line { from, to });
+    }
 
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                self.render_syntax_highlighted_text(ui, &self.code);
-            });
+    /// Add a line, arc or label, switching the canvas pen first if the previous op
+    /// was drawn by a different turtle or pen
+    fn draw_stroke(&mut self, pen: &TurtleState, stroke: turtle::TurtleOp) {
+        if self.canvas_pen.0 != pen.color {
+            self.turtle_commands
+                .push(turtle::TurtleOp::ColorChange(pen.color));
+        }
+        if self.canvas_pen.1 != pen.pen_width {
+            self.turtle_commands
+                .push(turtle::TurtleOp::PenWidth(pen.pen_width));
         }
+        self.canvas_pen = (pen.color, pen.pen_width);
+        self.turtle_commands.push(stroke);
     }
 
-    fn trigger_completion(&mut self) {
-        // Get current word at cursor position (more accurate implementation)
-        let cursor_pos = self.code.len(); // Simplified - in a real implementation we'd track actual cursor
-        let before_cursor = &self.code[..cursor_pos];
-
-        // Find the current word being typed
-        let mut word_start = cursor_pos;
-        for (i, ch) in before_cursor.char_indices().rev() {
-            if ch.is_whitespace()
-                || ch == '('
-                || ch == ')'
-                || ch == ','
-                || ch == ';'
-                || ch == ':'
-                || ch == '='
-            {
-                break;
-            }
-            word_start = i;
-        }
+    fn move_turtle(&mut self, state: &mut TurtleState, distance: f32, draw: bool) {
+        let angle_rad = state.angle.to_radians();
+        let new_x = state.x + distance * angle_rad.cos();
+        let new_y = state.y + distance * angle_rad.sin();
 
-        let current_word = if word_start < cursor_pos {
-            &before_cursor[word_start..cursor_pos]
-        } else {
-            ""
-        };
+        if draw {
+            // Store the line for rendering
+            self.draw_line(
+                state,
+                egui::pos2(state.x, state.y),
+                egui::pos2(new_x, new_y),
+            );
+        }
 
-        self.completion_query = current_word.to_string();
-        self.completion_items = self.get_completion_suggestions(current_word);
-        self.completion_selected = 0;
-        self.show_completion = self.code_completion_enabled && !self.completion_items.is_empty();
+        state.x = new_x;
+        state.y = new_y;
     }
-}
 
-impl eframe::App for TimeWarpApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Enhanced visual styling
-        let mut visuals = egui::Visuals::light();
-        visuals.window_fill = egui::Color32::from_rgb(250, 250, 252);
-        visuals.panel_fill = egui::Color32::from_rgb(255, 255, 255);
-        visuals.faint_bg_color = egui::Color32::from_rgb(248, 248, 250);
-        visuals.widgets.noninteractive.bg_fill = egui::Color32::from_rgb(252, 252, 254);
-        visuals.widgets.inactive.bg_fill = egui::Color32::from_rgb(255, 255, 255);
-        visuals.widgets.hovered.bg_fill = egui::Color32::from_rgb(240, 245, 255);
-        visuals.widgets.active.bg_fill = egui::Color32::from_rgb(230, 240, 255);
-        ctx.set_visuals(visuals);
-
-        // Set a more modern font
-        let mut style = (*ctx.style()).clone();
-        style.text_styles.insert(
-            egui::TextStyle::Heading,
-            egui::FontId::new(20.0, egui::FontFamily::Proportional),
-        );
-        style.text_styles.insert(
-            egui::TextStyle::Body,
-            egui::FontId::new(14.0, egui::FontFamily::Proportional),
-        );
-        style.text_styles.insert(
-            egui::TextStyle::Button,
-            egui::FontId::new(14.0, egui::FontFamily::Proportional),
-        );
-        style.spacing.item_spacing = egui::vec2(8.0, 4.0);
-        style.spacing.button_padding = egui::vec2(8.0, 4.0);
-        ctx.set_style(style);
+    fn process_graphics_commands(&mut self, commands: &[time_warp_core::basic::TurtleCommand]) {
+        use time_warp_core::basic::GraphicsCommand;
 
-        // Handle keyboard shortcuts
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::N)) {
-            self.code.clear();
-            // Don't set output for file operations - keep output clean for program results only
-        }
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::O)) {
-            if let Some(path) = FileDialog::new()
-                .add_filter("Text", &["txt", "twb", "twp", "tpr"])
-                .pick_file()
-            {
-                if let Ok(content) = std::fs::read_to_string(&path) {
-                    self.code = content;
-                    // Don't set output for file operations - keep output clean for program results only
-                    self.last_file_path = Some(path.display().to_string());
+        self.drawn_commands.extend_from_slice(commands);
+        for cmd in commands {
+            // Turtles spring into being, at home, the first time they are used
+            let mut state = self.turtles.remove(&cmd.turtle).unwrap_or_default();
+            match cmd.command {
+                GraphicsCommand::Forward(distance) => self.move_turtle(&mut state, distance, true),
+                GraphicsCommand::Back(distance) => self.move_turtle(&mut state, -distance, true),
+                GraphicsCommand::Move(distance) => self.move_turtle(&mut state, distance, false),
+                GraphicsCommand::Right(degrees) | GraphicsCommand::Turn(degrees) => {
+                    state.angle = (state.angle + degrees) % 360.0;
                 }
-            }
-        }
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::S)) {
-            if let Some(path) = &self.last_file_path {
-                if std::fs::write(path, &self.code).is_ok() {
-                    // Don't set output for file operations - keep output clean for program results only
+                GraphicsCommand::Left(degrees) => {
+                    state.angle = (state.angle - degrees) % 360.0;
+                }
+                GraphicsCommand::Home => {
+                    state.x = 0.0;
+                    state.y = 0.0;
+                    state.angle = 0.0;
+                }
+                GraphicsCommand::SetXY(x, y) => {
+                    self.draw_line(&state, egui::pos2(state.x, state.y), egui::pos2(x, y));
+                    state.x = x;
+                    state.y = y;
+                }
+                GraphicsCommand::MoveTo(x, y) => {
+                    state.x = x;
+                    state.y = y;
+                }
+                GraphicsCommand::SetHeading(degrees) => {
+                    state.angle = degrees % 360.0;
+                }
+                GraphicsCommand::ClearScreen => {
+                    // Every turtle goes home; pens keep their colour and width
+                    self.turtle_commands.clear();
+                    self.canvas_pen = (turtle::DEFAULT_PEN_COLOR, turtle::DEFAULT_PEN_WIDTH);
+                    self.turtle_progress = 0.0;
+                    for other in self.turtles.values_mut().chain(std::iter::once(&mut state)) {
+                        other.x = 0.0;
+                        other.y = 0.0;
+                        other.angle = 0.0;
+                    }
+                }
+                GraphicsCommand::SetPenColor(r, g, b) => {
+                    state.color = egui::Color32::from_rgb(r, g, b);
+                }
+                GraphicsCommand::SetPenSize(width) => {
+                    state.pen_width = width;
+                }
+                GraphicsCommand::Arc(radius, degrees) => {
+                    // Centred on the turtle, starting straight ahead; the turtle stays put
+                    let arc = turtle::TurtleOp::Arc {
+                        center: egui::pos2(state.x, state.y),
+                        radius,
+                        start_angle: state.angle,
+                        sweep: degrees,
+                    };
+                    self.draw_stroke(&state, arc);
+                }
+                GraphicsCommand::Label(ref text) => {
+                    let label = turtle::TurtleOp::Text {
+                        at: egui::pos2(state.x, state.y),
+                        text: text.clone(),
+                        angle: state.angle,
+                        size: state.label_size,
+                    };
+                    self.draw_stroke(&state, label);
+                }
+                GraphicsCommand::SetLabelSize(size) => {
+                    state.label_size = size;
                 }
-            } else if let Some(path) = FileDialog::new().set_file_name("untitled.twb").save_file() {
-                if std::fs::write(&path, &self.code).is_ok() {
-                    // Don't set output for file operations - keep output clean for program results only
-                    self.last_file_path = Some(path.display().to_string());
+                GraphicsCommand::SetBackground(r, g, b) => {
+                    self.canvas_background = egui::Color32::from_rgb(r, g, b);
+                }
+                GraphicsCommand::ShowTurtle(visible) => {
+                    state.visible = visible;
+                }
+                GraphicsCommand::Fill => {
+                    let seed = egui::pos2(state.x, state.y);
+                    if let Some(fill) = flood_fill::fill(&self.turtle_commands, seed, state.color) {
+                        self.turtle_commands.push(fill);
+                    }
                 }
             }
+            self.turtles.insert(cmd.turtle.clone(), state);
         }
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::F)) {
-            self.show_find_replace = true;
-        }
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::R)) {
-            self.show_find_replace = true;
-        }
-        if ctx.input(|i| i.key_pressed(egui::Key::F5)) {
-            self.active_tab = 1;
-            self.execute_code();
+    }
+
+    // Clipboard operations
+    /// Copy the selected code; with nothing selected there is nothing to copy
+    fn copy_text(&mut self, ctx: &egui::Context) {
+        if self.selection.is_empty() {
+            return;
         }
-        // Debug shortcuts
-        if ctx.input(|i| i.key_pressed(egui::Key::F9)) {
-            self.debug_mode = !self.debug_mode;
-            if !self.debug_mode {
-                self.stop_debug_session();
-            }
+        let text = editor::selected_text(&self.code, self.selection);
+        ctx.output_mut(|o| o.copied_text = text.clone());
+        self.clipboard_content = text;
+    }
+
+    fn cut_text(&mut self, ctx: &egui::Context) {
+        if self.selection.is_empty() {
+            return;
         }
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::F5)) {
-            if self.debug_mode {
-                self.start_debug_session();
-            }
+        self.copy_text(ctx);
+        self.replace_selection("");
+    }
+
+    /// Paste at the caret, over any selection. Menus can't read the system
+    /// clipboard, so without a paste event this uses what was last copied here.
+    fn paste_text(&mut self, ctx: &egui::Context) {
+        let paste_text = ctx.input(|i| {
+            i.events.iter().find_map(|e| {
+                if let egui::Event::Paste(text) = e {
+                    Some(text.clone())
+                } else {
+                    None
+                }
+            })
+        });
+
+        let text = paste_text.unwrap_or_else(|| self.clipboard_content.clone());
+        if !text.is_empty() {
+            self.replace_selection(&text);
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::F10)) {
-            if self.debug_mode && self.debug_state == DebugState::Paused {
-                self.step_debug();
-            }
+    }
+
+    /// Put `text` in place of the selection and leave the caret after it
+    fn replace_selection(&mut self, text: &str) {
+        self.edit_code(|code, selection| {
+            editor::Selection::caret(editor::replace(code, selection.range(), text))
+        });
+    }
+}
+
+impl TimeWarpApp {
+    /// Show the New File dialog with a freshly loaded template list
+    #[cfg(feature = "templates")]
+    fn open_new_file_dialog(&mut self) {
+        self.new_file_templates = templates::all_templates(&self.storage.templates_dir);
+        self.new_file_selected = 0;
+        self.show_new_file_dialog = true;
+    }
+
+    /// Without templates, New File simply opens an empty tab
+    #[cfg(not(feature = "templates"))]
+    fn open_new_file_dialog(&mut self) {
+        self.new_document();
+    }
+
+    /// Start a new tab from the given template, or fill the current tab if
+    /// it is still an empty new program
+    #[cfg(feature = "templates")]
+    fn create_file_from_template(&mut self, template: &templates::ProgramTemplate) {
+        if !self.is_blank_document() {
+            self.new_document();
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
-            if self.debug_mode && self.debug_state == DebugState::Running {
-                self.debug_state = DebugState::Paused;
-            } else if self.debug_mode && self.debug_state == DebugState::Paused {
-                self.debug_state = DebugState::Running;
-            }
+        self.save_undo_state();
+        #[cfg(feature = "hints")]
+        {
+            let previous_file = self.hint_file_key();
+            self.error_tracker.clear_file(&previous_file);
         }
-        if ctx.input(|i| i.modifiers.ctrl && i.modifiers.shift && i.key_pressed(egui::Key::C)) {
-            self.output = String::new();
-            self.turtle_commands.clear();
-            self.turtle_state = TurtleState {
-                x: 0.0,
-                y: 0.0,
-                angle: 0.0,
-                color: egui::Color32::BLACK,
-            };
-            self.turtle_zoom = 1.0;
-            self.turtle_pan = egui::vec2(0.0, 0.0);
+        self.code = template.content.clone();
+        self.new_file_extension = template.extension.clone();
+        if let Some(language) = Language::from_name(&template.language) {
+            self.language = language;
         }
+        self.last_file_path = None;
+        self.active_tab = 0;
+        self.show_new_file_dialog = false;
+    }
 
-        egui::TopBottomPanel::top("menu_bar")
-            .min_height(40.0)
+    /// Suggested file name for saving a file that has never been saved
+    fn untitled_file_name(&self) -> String {
+        format!("untitled.{}", self.new_file_extension)
+    }
+
+    #[cfg(feature = "templates")]
+    fn render_new_file_dialog(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_new_file_dialog;
+        let mut create = false;
+
+        egui::Window::new("📄 New File")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(560.0)
+            .open(&mut open)
             .show(ctx, |ui| {
-                ui.painter().rect_filled(
-                    ui.available_rect_before_wrap(),
-                    0.0,
-                    egui::Color32::from_rgb(220, 220, 220),
-                );
-                ui.add_space(6.0);
-                egui::menu::bar(ui, |ui| {
-                    // File menu
-                    ui.menu_button("📁 File", |ui| {
-                        if ui.button("📄 New File").clicked() {
-                            self.code.clear();
-                            // Don't set output for file operations - keep output clean for program results only
-                            ui.close_menu();
-                        }
-                        if ui.button("📂 Open File...").clicked() {
-                            if let Some(path) = FileDialog::new()
-                                .add_filter("Text", &["txt", "twb", "twp", "tpr"])
-                                .pick_file()
-                            {
-                                if let Ok(content) = std::fs::read_to_string(&path) {
-                                    self.code = content;
-                                    // Don't set output for file operations - keep output clean for program results only
-                                    self.last_file_path = Some(path.display().to_string());
-                                }
+                ui.horizontal_top(|ui| {
+                    // Template list grouped by language
+                    ui.vertical(|ui| {
+                        ui.set_width(200.0);
+                        let mut last_language = "";
+                        for (index, template) in self.new_file_templates.iter().enumerate() {
+                            if template.language != last_language {
+                                ui.add_space(4.0);
+                                ui.label(egui::RichText::new(&template.language).strong());
+                                last_language = template.language.as_str();
                             }
-                            ui.close_menu();
-                        }
-                        if ui.button("💾 Save").clicked() {
-                            if let Some(path) = &self.last_file_path {
-                                if std::fs::write(path, &self.code).is_ok() {
-                                    // Don't set output for file operations - keep output clean for program results only
-                                }
-                            } else if let Some(path) =
-                                FileDialog::new().set_file_name("untitled.twb").save_file()
-                            {
-                                if std::fs::write(&path, &self.code).is_ok() {
-                                    // Don't set output for file operations - keep output clean for program results only
-                                    self.last_file_path = Some(path.display().to_string());
-                                }
+                            let response = ui
+                                .selectable_label(index == self.new_file_selected, &template.name);
+                            if response.clicked() {
+                                self.new_file_selected = index;
                             }
-                            ui.close_menu();
-                        }
-                        if ui.button("💾 Save As...").clicked() {
-                            if let Some(path) =
-                                FileDialog::new().set_file_name("untitled.twb").save_file()
-                            {
-                                if std::fs::write(&path, &self.code).is_ok() {
-                                    self.output = format!("Saved to {}", path.display());
-                                    self.last_file_path = Some(path.display().to_string());
-                                }
+                            if response.double_clicked() {
+                                self.new_file_selected = index;
+                                create = true;
                             }
-                            ui.close_menu();
-                        }
-                    });
-                    ui.menu_button("✏️ Edit", |ui| {
-                        if ui.button("🔍 Find...").clicked() {
-                            self.show_find_replace = true;
-                            ui.close_menu();
-                        }
-                        if ui.button("🔄 Replace...").clicked() {
-                            self.show_find_replace = true;
-                            ui.close_menu();
-                        }
-                        ui.separator();
-                        if ui.button("↶ Undo").clicked() {
-                            self.undo();
-                            ui.close_menu();
-                        }
-                        if ui.button("↷ Redo").clicked() {
-                            self.redo();
-                            ui.close_menu();
-                        }
-                        ui.separator();
-                        if ui.button("📋 Copy").clicked() {
-                            self.copy_text(ctx);
-                            ui.close_menu();
-                        }
-                        if ui.button("✂️ Cut").clicked() {
-                            self.cut_text(ctx);
-                            ui.close_menu();
-                        }
-                        if ui.button("📄 Paste").clicked() {
-                            self.paste_text(ctx);
-                            ui.close_menu();
-                        }
-                        if ui.button("↕️ Move Line").clicked() {
-                            // For now, just show a message - full implementation needs cursor tracking
-                            self.show_error(
-                                "Move line functionality not yet implemented".to_string(),
-                            );
-                            ui.close_menu();
-                        }
-                    });
-                    ui.menu_button("👁️ View", |ui| {
-                        if ui
-                            .selectable_label(self.show_line_numbers, "📏 Show Line Numbers")
-                            .clicked()
-                        {
-                            self.show_line_numbers = !self.show_line_numbers;
-                            ui.close_menu();
-                        }
-                        if ui
-                            .selectable_label(
-                                self.syntax_highlighting_enabled,
-                                "🎨 Syntax Highlighting",
-                            )
-                            .clicked()
-                        {
-                            self.syntax_highlighting_enabled = !self.syntax_highlighting_enabled;
-                            ui.close_menu();
-                        }
-                        if ui
-                            .selectable_label(self.code_completion_enabled, "💡 Code Completion")
-                            .clicked()
-                        {
-                            self.code_completion_enabled = !self.code_completion_enabled;
-                            ui.close_menu();
                         }
+                        ui.add_space(8.0);
+                        ui.small(format!(
+                            "Add your own in {}",
+                            self.storage.templates_dir.display()
+                        ));
                     });
-                    ui.menu_button("❓ Help", |ui| {
-                        if ui.button("ℹ️ About").clicked() {
-                            self.show_about = true;
-                            ui.close_menu();
-                        }
-                        if ui.button("💬 Test Prompt").clicked() {
-                            self.prompt_user("Enter some text for testing:", |input| {
-                                println!("User entered: {}", input);
-                                // In a real application, you would do something with the input here
+
+                    ui.separator();
+
+                    // Preview of the selected template
+                    ui.vertical(|ui| {
+                        ui.label("Preview:");
+                        let preview = self
+                            .new_file_templates
+                            .get(self.new_file_selected)
+                            .map(|t| t.content.as_str())
+                            .unwrap_or("");
+                        egui::ScrollArea::vertical()
+                            .max_height(240.0)
+                            .show(ui, |ui| {
+                                if preview.is_empty() {
+                                    ui.weak("(empty file)");
+                                } else {
+                                    ui.monospace(preview);
+                                }
                             });
-                            ui.close_menu();
-                        }
                     });
                 });
-                ui.add_space(6.0);
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Create").clicked() {
+                        create = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.show_new_file_dialog = false;
+                    }
+                });
             });
 
-        // Enhanced Toolbar
-        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
-            ui.add_space(2.0);
-            egui::Frame::none()
-                .fill(ui.style().visuals.window_fill())
-                .stroke(ui.style().visuals.window_stroke())
-                .show(ui, |ui| {
-                    ui.horizontal(|ui| {
-                        ui.add_space(8.0);
+        if create {
+            if let Some(template) = self.new_file_templates.get(self.new_file_selected).cloned() {
+                self.create_file_from_template(&template);
+            }
+        }
+        if !open {
+            self.show_new_file_dialog = false;
+        }
+    }
+}
 
-                        // File operations
-                        if ui
-                            .button("📄 New")
-                            .on_hover_text("New File (Ctrl+N)")
-                            .clicked()
-                        {
-                            self.code.clear();
-                            // Don't set output for file operations - keep output clean for program results only
-                        }
-                        if ui
-                            .button("📂 Open")
-                            .on_hover_text("Open File (Ctrl+O)")
-                            .clicked()
-                        {
-                            if let Some(path) = FileDialog::new()
-                                .add_filter("Text", &["txt", "twb", "twp", "tpr"])
-                                .pick_file()
-                            {
-                                if let Ok(content) = std::fs::read_to_string(&path) {
-                                    self.code = content;
-                                    // Don't set output for file operations - keep output clean for program results only
-                                    self.last_file_path = Some(path.display().to_string());
-                                }
-                            }
-                        }
-                        if ui
-                            .button("💾 Save")
-                            .on_hover_text("Save File (Ctrl+S)")
-                            .clicked()
-                        {
-                            if let Some(path) = &self.last_file_path {
-                                if std::fs::write(path, &self.code).is_ok() {
-                                    // Don't set output for file operations - keep output clean for program results only
-                                }
-                            } else if let Some(path) =
-                                FileDialog::new().set_file_name("untitled.twb").save_file()
-                            {
-                                if std::fs::write(&path, &self.code).is_ok() {
-                                    // Don't set output for file operations - keep output clean for program results only
-                                    self.last_file_path = Some(path.display().to_string());
-                                }
-                            }
-                        }
+impl TimeWarpApp {
+    // Debug methods
+    fn start_debug_session(&mut self) {
+        use time_warp_core::basic::Interpreter;
 
-                        ui.separator();
+        if self.language != Language::Basic {
+            self.output = format!(
+                "The debugger only steps through TW BASIC programs, not {}.\n",
+                self.language.name()
+            );
+            return;
+        }
+        self.debug_variables.clear();
+        self.debug_call_stack.clear();
+        self.output = "Debug session started.\n".to_string();
 
-                        // Edit operations
-                        if ui.button("↶ Undo").on_hover_text("Undo").clicked() {
-                            // Note: egui TextEdit doesn't have built-in undo, this is a placeholder
-                        }
-                        if ui.button("↷ Redo").on_hover_text("Redo").clicked() {
-                            // Note: egui TextEdit doesn't have built-in redo, this is a placeholder
-                        }
-                        if ui.button("📋 Copy").on_hover_text("Copy").clicked() {
-                            self.copy_text(ctx);
-                        }
-                        if ui.button("✂️ Cut").on_hover_text("Cut").clicked() {
-                            self.cut_text(ctx);
-                        }
-                        if ui.button("📄 Paste").on_hover_text("Paste").clicked() {
-                            self.paste_text(ctx);
-                        }
+        let mut interpreter = Interpreter::new();
+        interpreter.max_instructions = (self.execution_timeout_ms() * 1000) as usize;
+        interpreter.set_dialect(self.settings.basic_dialect);
+        interpreter.set_strictness(self.settings.basic_strictness);
+        self.set_up_files(&mut interpreter);
+        // SLEEP, WAIT and PLAY hand back each frame, as in a plain run
+        interpreter.set_live_input(true);
+        interpreter.queue_input(self.scripted_answers());
+        let loaded = self.expand_includes(&self.code).and_then(|expanded| {
+            let loaded = interpreter.load(&expanded.code);
+            self.included = expanded;
+            loaded
+        });
+        self.diagnostics = self.included_diagnostics(interpreter.diagnostics());
+        match loaded {
+            Ok(()) => {
+                // Pause on the first statement so the student can step from the top
+                self.basic_interpreter = Some(interpreter);
+                self.debug_state = DebugState::Paused;
+                self.step_debug(time_warp_core::basic::StepMode::StepInto);
+            }
+            Err(err) => {
+                let message = self.run_error(&err);
+                self.output.push_str(&format!("{}\n", message));
+                self.debug_state = DebugState::Stopped;
+                self.current_debug_line = None;
+            }
+        }
+    }
 
-                        ui.separator();
+    /// Ask where to save the paused TW BASIC run and write it there
+    fn save_run_state(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("Time Warp session", &["twsession"])
+            .set_file_name("run.twsession")
+            .save_file()
+        else {
+            return;
+        };
+        match self.save_run_state_to(&path) {
+            Ok(()) => self
+                .output
+                .push_str(&format!("\nRun saved to {}\n", path.display())),
+            Err(err) => self.show_error(format!("Could not save the run: {}", err)),
+        }
+    }
 
-                        // Code operations
-                        if ui
-                            .button("🔍 Find")
-                            .on_hover_text("Find/Replace (Ctrl+F)")
-                            .clicked()
-                        {
-                            self.show_find_replace = !self.show_find_replace;
-                        }
-                        if ui.button("▶️ Run").on_hover_text("Run Code (F5)").clicked() {
-                            self.active_tab = 1; // Switch to Output tab when running
-                            self.execute_code();
-                        }
-                        if ui
-                            .button("🗑️ Clear")
-                            .on_hover_text("Clear Output (Ctrl+Shift+C)")
-                            .clicked()
-                        {
-                            self.output = String::new();
-                            self.turtle_commands.clear();
-                            self.turtle_state = TurtleState {
-                                x: 0.0,
-                                y: 0.0,
-                                angle: 0.0,
-                                color: egui::Color32::BLACK,
-                            };
-                            self.turtle_zoom = 1.0;
-                            self.turtle_pan = egui::vec2(0.0, 0.0);
-                        }
+    /// Write the run that is waiting on INPUT or paused in the debugger,
+    /// with its output and drawing, to a session file
+    fn save_run_state_to(&mut self, path: &std::path::Path) -> Result<(), String> {
+        use time_warp_core::basic::snapshot::Session;
 
-                        ui.separator();
+        let Some(interpreter) = self.basic_interpreter.take() else {
+            return Err(
+                "Only a TW BASIC run that is waiting for input or paused in the debugger can be saved"
+                    .to_string(),
+            );
+        };
+        let session = Session {
+            source: self.included.code.clone(),
+            interpreter,
+            output: self.output.clone(),
+            graphics: self.drawn_commands.clone(),
+            prompt: self.waiting_for_input.then(|| self.input_prompt.clone()),
+        };
+        let written = std::fs::write(path, session.to_json().to_string());
+        self.basic_interpreter = Some(session.interpreter);
+        written.map_err(|err| err.to_string())
+    }
 
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            ui.add_space(8.0);
-                        });
-                    });
-                });
-            ui.add_space(2.0);
-        });
+    /// Ask for a session file and carry on the run saved in it
+    fn resume_run_state(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("Time Warp session", &["twsession"])
+            .pick_file()
+        else {
+            return;
+        };
+        if let Err(err) = self.resume_run_state_from(&path) {
+            self.show_error(format!("Could not resume the run: {}", err));
+        }
+    }
 
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.heading("🚀 Time Warp IDE");
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    // Status indicators
-                    if self.is_executing {
-                        ui.colored_label(egui::Color32::GREEN, "● Running");
-                    } else if self.waiting_for_input {
-                        ui.colored_label(egui::Color32::YELLOW, "● Waiting for Input");
-                    } else {
-                        ui.colored_label(egui::Color32::GRAY, "● Ready");
-                    }
+    /// Put the program, output, drawing and paused interpreter back as
+    /// they were saved. The run carries on from the answer box, or from the
+    /// Debug tab if it was saved in the debugger.
+    fn resume_run_state_from(&mut self, path: &std::path::Path) -> Result<(), String> {
+        use time_warp_core::basic::snapshot::Session;
+        use time_warp_core::json::Json;
+
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let session = Session::from_json(&Json::parse(&text)?)?;
+
+        self.cancel_running_program();
+        self.set_language(Language::Basic);
+        self.code = session.source;
+        self.included = Expanded::plain(&self.code);
+        self.clear_canvas();
+        self.process_graphics_commands(&session.graphics);
+        self.output = session.output;
+        let mut interpreter = session.interpreter;
+        interpreter.max_instructions = (self.execution_timeout_ms() * 1000) as usize;
+        self.set_up_files(&mut interpreter);
+        match session.prompt {
+            Some(prompt) => {
+                self.waiting_for_input = true;
+                self.input_prompt = prompt;
+                self.active_tab = 1;
+            }
+            None => {
+                self.debug_mode = true;
+                self.debug_state = DebugState::Paused;
+                self.current_debug_line = interpreter.current_source_line().map(|line| line as u32);
+                self.active_tab = 2;
+            }
+        }
+        self.basic_interpreter = Some(interpreter);
+        self.refresh_debug_view();
+        Ok(())
+    }
 
-                    ui.separator();
+    fn stop_debug_session(&mut self) {
+        self.debug_state = DebugState::Stopped;
+        self.current_debug_line = None;
+        self.basic_interpreter = None;
+        self.output.push_str("Debug session stopped.\n");
+    }
 
-                    // File info
-                    if let Some(path) = &self.last_file_path {
-                        ui.label(format!(
-                            "📄 {}",
-                            std::path::Path::new(path)
-                                .file_name()
-                                .unwrap_or(std::ffi::OsStr::new("untitled"))
-                                .to_string_lossy()
-                        ));
-                    } else {
-                        ui.label("📄 untitled");
-                    }
-                });
-            });
-        });
+    /// Resume the paused debug session with the given step granularity
+    fn step_debug(&mut self, mode: time_warp_core::basic::StepMode) {
+        if self.debug_state != DebugState::Paused {
+            return;
+        }
+        let breakpoints: Vec<usize> = self
+            .current_breakpoints()
+            .into_iter()
+            .filter_map(|line| self.included.expanded_line(line as usize))
+            .collect();
+        let Some(interpreter) = self.basic_interpreter.as_mut() else {
+            return;
+        };
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical(|ui| {
-                // Tab bar with better styling
-                egui::Frame::none()
-                    .fill(ui.style().visuals.faint_bg_color)
+        interpreter.set_breakpoints(breakpoints);
+        self.debug_state = DebugState::Running;
+        self.debug_step_mode = mode;
+        let started = std::time::Instant::now();
+        let result = interpreter.run(mode);
+        self.finish_debug_step(result, started.elapsed());
+    }
+
+    /// Show where a debug step (or the frame of one that is sleeping) got
+    /// to. A step that sleeps stays Running and carries on each frame.
+    pub(crate) fn finish_debug_step(
+        &mut self,
+        result: Result<
+            time_warp_core::basic::ExecutionResult,
+            time_warp_core::basic::InterpreterError,
+        >,
+        elapsed: std::time::Duration,
+    ) {
+        use time_warp_core::basic::ExecutionResult;
+
+        let Some(interpreter) = self.basic_interpreter.as_mut() else {
+            return;
+        };
+        let printed = interpreter.take_printer_output();
+        #[cfg(feature = "audio")]
+        let music = interpreter.take_music();
+
+        // Statements accumulate across steps; time only counts while actually running
+        let stats = self
+            .execution_stats
+            .get_or_insert_with(ExecutionStats::default);
+        stats.statements = interpreter.instruction_count();
+        stats.max_instructions = interpreter.max_instructions;
+        stats.elapsed += elapsed;
+        self.receive_printout(printed);
+        #[cfg(feature = "audio")]
+        self.play_music(music);
+
+        match result {
+            Ok(ExecutionResult::Break {
+                line,
+                partial_output,
+                partial_graphics,
+            }) => {
+                self.output.push_str(&partial_output);
+                self.process_graphics_commands(&partial_graphics);
+                // Inside an included file, the INCLUDE line is shown
+                self.current_debug_line = Some(self.included.main_line(line) as u32);
+                self.debug_state = DebugState::Paused;
+                self.refresh_debug_view();
+            }
+            Ok(ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                self.output.push_str(&output);
+                self.process_graphics_commands(&graphics_commands);
+                self.refresh_debug_view();
+                self.stop_debug_session();
+            }
+            Ok(ExecutionResult::NeedInput {
+                variable,
+                prompt,
+                partial_output,
+                partial_graphics,
+            }) => {
+                self.output.push_str(&partial_output);
+                self.output.push_str(&prompt);
+                self.process_graphics_commands(&partial_graphics);
+                self.waiting_for_input = true;
+                self.input_prompt = prompt;
+                self.current_input_var = variable;
+                self.debug_state = DebugState::Paused;
+            }
+            Ok(ExecutionResult::NeedPermission {
+                permission,
+                partial_output,
+                partial_graphics,
+            }) => {
+                self.output.push_str(&partial_output);
+                self.process_graphics_commands(&partial_graphics);
+                self.pending_permission = Some(permission);
+                self.debug_state = DebugState::Paused;
+            }
+            // Carries on each frame until it pauses or ends
+            Ok(ExecutionResult::Polling {
+                partial_output,
+                partial_graphics,
+            }) => {
+                self.output.push_str(&partial_output);
+                self.process_graphics_commands(&partial_graphics);
+                self.polling_input = true;
+                self.grab_keyboard = true;
+            }
+            Ok(ExecutionResult::Error(err)) => {
+                self.output.push_str(&format!("Error: {}\n", err));
+                self.stop_debug_session();
+            }
+            Err(err) => {
+                let message = self.run_error(&err);
+                self.output.push_str(&format!("{}\n", message));
+                self.stop_debug_session();
+            }
+        }
+    }
+
+    /// Copy variables and the GOSUB stack out of the paused interpreter
+    fn refresh_debug_view(&mut self) {
+        if let Some(interpreter) = &self.basic_interpreter {
+            self.debug_variables = interpreter.variable_snapshot().into_iter().collect();
+            self.debug_call_stack = interpreter
+                .call_stack_lines()
+                .into_iter()
+                .map(|line| format!("GOSUB from {}", self.included.locate_line(line)))
+                .collect();
+        }
+    }
+
+    fn current_breakpoints(&self) -> Vec<u32> {
+        self.breakpoints
+            .get(&self.document_name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn render_debug_editor(&mut self, ui: &mut egui::Ui) {
+        let filename = self.document_name();
+        let bookmarks = self.current_bookmarks();
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        let syntax_enabled = self.syntax_highlighting_enabled;
+        let current_debug_line = self.current_debug_line;
+        let palette = self.palette();
+        let comments = self.language.backend().comment_prefixes();
+        let keywords: Vec<String> = self
+            .get_language_keywords()
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.set_width(ui.available_width());
+
+            let lines: Vec<String> = self.code.lines().map(|s| s.to_string()).collect();
+            let breakpoints = self.breakpoints.entry(filename).or_default();
+            let cache = &mut self.highlight_cache;
+
+            for (line_idx, line) in lines.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    // Breakpoint column
+                    let line_number = (line_idx + 1) as u32;
+                    let has_breakpoint = breakpoints.contains(&line_number);
+
+                    let breakpoint_button =
+                        egui::Button::new(if has_breakpoint { "🔴" } else { "⚪" })
+                            .frame(false)
+                            .small();
+
+                    if ui
+                        .add(breakpoint_button)
+                        .on_hover_text(if has_breakpoint {
+                            "Click to remove breakpoint"
+                        } else {
+                            "Click to add breakpoint"
+                        })
+                        .clicked()
+                    {
+                        if has_breakpoint {
+                            breakpoints.retain(|&x| x != line_number);
+                        } else {
+                            breakpoints.push(line_number);
+                            breakpoints.sort();
+                        }
+                    }
+
+                    let (cell, _) =
+                        ui.allocate_exact_size(egui::vec2(8.0, row_height), egui::Sense::hover());
+                    if bookmarks.contains(&line_number) {
+                        editor::paint_bookmark(ui, cell, palette.bookmark);
+                    }
+
+                    // Line number
+                    ui.label(
+                        egui::RichText::new(format!("{:4}", line_number))
+                            .color(palette.line_number)
+                            .text_style(egui::TextStyle::Monospace),
+                    );
+
+                    // Current debug line indicator
+                    if Some(line_number) == current_debug_line {
+                        ui.label(egui::RichText::new("▶").color(palette.current_line));
+                    } else {
+                        ui.add_space(12.0);
+                    }
+
+                    // Line content with syntax highlighting
+                    if syntax_enabled {
+                        // Simple syntax highlighting for debug view
+                        let highlighted = cache.line(line, &keywords, comments, palette);
+                        for (text, color) in highlighted {
+                            ui.label(
+                                egui::RichText::new(text)
+                                    .color(*color)
+                                    .text_style(egui::TextStyle::Monospace),
+                            );
+                        }
+                    } else {
+                        ui.label(egui::RichText::new(line).text_style(egui::TextStyle::Monospace));
+                    }
+                });
+            }
+
+            // Handle empty last line
+            if self.code.ends_with('\n') || self.code.is_empty() {
+                ui.horizontal(|ui| {
+                    let line_number = (lines.len() + 1) as u32;
+                    let has_breakpoint = breakpoints.contains(&line_number);
+
+                    let breakpoint_button =
+                        egui::Button::new(if has_breakpoint { "🔴" } else { "⚪" })
+                            .frame(false)
+                            .small();
+
+                    if ui
+                        .add(breakpoint_button)
+                        .on_hover_text(if has_breakpoint {
+                            "Click to remove breakpoint"
+                        } else {
+                            "Click to add breakpoint"
+                        })
+                        .clicked()
+                    {
+                        if has_breakpoint {
+                            breakpoints.retain(|&x| x != line_number);
+                        } else {
+                            breakpoints.push(line_number);
+                            breakpoints.sort();
+                        }
+                    }
+                    ui.add_space(8.0);
+
+                    ui.label(
+                        egui::RichText::new(format!("{:4}", line_number))
+                            .color(palette.line_number)
+                            .text_style(egui::TextStyle::Monospace),
+                    );
+                    ui.add_space(12.0);
+                });
+            }
+        });
+    }
+
+    /// What the editor gutter marks beside lines after a run: the line,
+    /// its colour and what the colour means
+    pub(crate) fn run_marks(&self) -> Vec<(usize, egui::Color32, String)> {
+        #[cfg(feature = "profiler")]
+        if self.show_profile_heatmap && !self.profile_results.is_empty() {
+            return self.profile_marks();
+        }
+        match &self.coverage {
+            Some(coverage) if self.show_coverage => coverage.marks(self.palette()),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The turtle canvas, filling the rest of `ui`: drag to pan, scroll to
+    /// zoom, with the replay animation and the turtles drawn on top
+    fn render_turtle_canvas(&mut self, ui: &mut egui::Ui) {
+        let size = ui.available_size().max(TURTLE_CANVAS_SIZE);
+        self.resize_canvas(size);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+
+        // Handle pan, unless a running program has the mouse
+        if response.dragged() && !self.polling_input {
+            self.turtle_pan += response.drag_delta() / self.turtle_zoom;
+        }
+        // Wheel (or pinch) zoom centred on the cursor
+        if let Some(hover) = response.hover_pos() {
+            let factor = ui.input(|i| i.zoom_delta() * (i.scroll_delta.y * 0.002).exp());
+            if factor != 1.0 {
+                self.zoom_canvas_at(rect.center(), hover, factor);
+            }
+        }
+
+        ui.painter().rect_filled(rect, 0.0, self.canvas_background);
+        ui.painter()
+            .rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+
+        // Draw turtle lines with zoom and pan
+        let center = rect.center();
+        let (pan, zoom) = (self.turtle_pan, self.turtle_zoom);
+        match response.hover_pos() {
+            Some(hover) => self.track_canvas_mouse(ui, turtle::to_turtle(center, pan, zoom, hover)),
+            None => self.canvas_mouse.buttons = 0,
+        }
+        let to_screen = |p: egui::Pos2| turtle::to_screen(center, pan, zoom, p);
+        if self.show_grid {
+            turtle::paint_grid(&ui.painter_at(rect), rect, pan, zoom);
+        }
+        let dt = ui.input(|i| i.stable_dt);
+        let animating = self.advance_turtle_animation(dt);
+        if animating {
+            // Mid-replay: show what's drawn so far and where the pen is
+            let (shown, pen) = turtle::replay(&self.turtle_commands, self.turtle_progress);
+            turtle::paint(&ui.painter_at(rect), &shown, to_screen, zoom);
+            if let Some(pen) = pen {
+                ui.painter_at(rect).circle_filled(
+                    to_screen(pen),
+                    4.0,
+                    egui::Color32::from_rgb(0, 150, 0),
+                );
+            }
+            ui.ctx().request_repaint();
+        } else {
+            turtle::paint(&ui.painter_at(rect), &self.turtle_commands, to_screen, zoom);
+        }
+
+        // Draw each turtle as a triangle, labelled once there is more than one.
+        // The pen marker stands in for them until a replay finishes.
+        let label_turtles = self.turtles.len() > 1;
+        for (name, state) in self
+            .turtles
+            .iter()
+            .filter(|(_, state)| state.visible && !animating)
+        {
+            let turtle_x = center.x + (state.x + self.turtle_pan.x) * self.turtle_zoom;
+            let turtle_y = center.y + (state.y + self.turtle_pan.y) * self.turtle_zoom;
+
+            let size = 8.0 * self.turtle_zoom;
+            let angle_rad = state.angle.to_radians();
+            let points = [
+                egui::pos2(
+                    turtle_x + size * angle_rad.cos(),
+                    turtle_y + size * angle_rad.sin(),
+                ),
+                egui::pos2(
+                    turtle_x + size * (angle_rad + 2.0944).cos(),
+                    turtle_y + size * (angle_rad + 2.0944).sin(),
+                ),
+                egui::pos2(
+                    turtle_x + size * (angle_rad - 2.0944).cos(),
+                    turtle_y + size * (angle_rad - 2.0944).sin(),
+                ),
+            ];
+
+            ui.painter().add(egui::Shape::convex_polygon(
+                points.to_vec(),
+                state.color,
+                egui::Stroke::new(1.0, egui::Color32::BLACK),
+            ));
+            if label_turtles {
+                ui.painter().text(
+                    egui::pos2(turtle_x + size, turtle_y - size),
+                    egui::Align2::LEFT_BOTTOM,
+                    name,
+                    egui::FontId::proportional(11.0),
+                    egui::Color32::DARK_GRAY,
+                );
+            }
+        }
+
+        // Where the mouse is, in the coordinates SETXY uses
+        if let Some(hover) = response.hover_pos().filter(|_| self.show_grid) {
+            let at = turtle::to_turtle(center, pan, zoom, hover);
+            ui.painter().text(
+                rect.left_bottom() + egui::vec2(4.0, -4.0),
+                egui::Align2::LEFT_BOTTOM,
+                format!("x: {:.0}  y: {:.0}", at.x, at.y),
+                egui::FontId::monospace(12.0),
+                egui::Color32::from_rgb(0, 90, 160),
+            );
+        }
+    }
+
+    // Code completion methods
+    fn get_language_keywords(&self) -> Vec<&'static str> {
+        self.language.backend().keywords().to_vec()
+    }
+
+    fn get_completion_suggestions(&self, query: &str) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        let query_lower = query.to_lowercase();
+
+        // Add language keywords
+        let keywords = self.get_language_keywords();
+        for keyword in keywords {
+            if keyword.to_lowercase().starts_with(&query_lower) {
+                suggestions.push(keyword.to_string());
+            }
+        }
+
+        // Add variables from debug session
+        for var_name in self.debug_variables.keys() {
+            if var_name.to_lowercase().starts_with(&query_lower) {
+                suggestions.push(var_name.clone());
+            }
+        }
+
+        // Add TW BASIC functions and commands
+        let basic = self.language == Language::Basic;
+        let basic_functions = vec![
+            "ABS(", "ASC(", "CHR$(", "COS(", "EXP(", "INT(", "LEFT$(", "LEN(", "LOG(", "MID$(",
+            "RIGHT$(", "RND(", "SIN(", "SQR(", "STR$(", "TAN(", "VAL(",
+        ];
+
+        for func in basic_functions {
+            if basic && func.to_lowercase().starts_with(&query_lower) {
+                suggestions.push(func.to_string());
+            }
+        }
+
+        // Add BASIC commands that might be partially typed
+        let basic_commands = vec![
+            "PRINT",
+            "WRITELN",
+            "INPUT",
+            "READLN",
+            "LET",
+            "IF",
+            "THEN",
+            "ELSE",
+            "WHILE",
+            "DO",
+            "FOR",
+            "TO",
+            "STEP",
+            "NEXT",
+            "FORWARD",
+            "FD",
+            "BACK",
+            "BK",
+            "LEFT",
+            "LT",
+            "RIGHT",
+            "RT",
+            "PENUP",
+            "PU",
+            "PENDOWN",
+            "PD",
+            "SETPENCOLOR",
+            "SETPC",
+            "PENSIZE",
+            "FILL",
+            "ARC",
+            "CIRCLE",
+            "LABEL",
+            "LABELSIZE",
+            "SETBACKGROUND",
+            "SETBG",
+            "HIDETURTLE",
+            "SHOWTURTLE",
+            "HOME",
+            "SETXY",
+            "SETHEADING",
+            "SETH",
+            "CLEARSCREEN",
+            "TURTLE",
+            "WHILE",
+            "WEND",
+            "GOTO",
+            "GOSUB",
+            "RETURN",
+            "RESUME",
+            "ERROR",
+            "LPRINT",
+            "OPEN",
+            "CLOSE",
+            "WRITE",
+            "FIELD",
+            "LSET",
+            "RSET",
+            "GET",
+            "PUT",
+            "FILES",
+            "KILL",
+            "NAME",
+            "MKDIR",
+            "CHDIR",
+            "RMDIR",
+            "POKE",
+            "END",
+            "CLS",
+            "LOCATE",
+            "COLOR",
+            "BEEP",
+            "SLEEP",
+            "PLAY",
+            "RANDOMIZE",
+        ];
+
+        for cmd in basic_commands {
+            if basic && cmd.to_lowercase().starts_with(&query_lower) {
+                suggestions.push(cmd.to_string());
+            }
+        }
+
+        // Sort and deduplicate
+        suggestions.sort();
+        suggestions.dedup();
+
+        // Limit to top 10 suggestions
+        suggestions.truncate(10);
+
+        suggestions
+    }
+
+    fn render_code_editor(&mut self, ui: &mut egui::Ui) {
+        let response = self.code_text_edit(ui);
+
+        // Check if code changed and save undo state
+        if response.changed() && self.code != self.previous_code {
+            self.save_undo_state();
+            self.previous_code = self.code.clone();
+        }
+
+        // Handle undo/redo keyboard shortcuts
+        if ui.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Z) && !i.modifiers.shift) {
+            self.undo();
+        }
+        if ui.input(|i| {
+            (i.modifiers.ctrl && i.key_pressed(egui::Key::Y))
+                || (i.modifiers.ctrl && i.modifiers.shift && i.key_pressed(egui::Key::Z))
+        }) {
+            self.redo();
+        }
+
+        // Auto-completion triggers
+        if let Some(text) = ui.input(|i| {
+            i.events.iter().find_map(|e| match e {
+                egui::Event::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+        }) {
+            // Trigger completion after typing certain characters
+            if text.chars().any(|c| c == '.' || c == '(' || c == ' ') {
+                // Small delay to avoid triggering on every keystroke
+                self.trigger_completion();
+            }
+        }
+    }
+
+    fn trigger_completion(&mut self) {
+        // The word being typed at the caret
+        let word = editor::word_before(&self.code, self.selection.head);
+        let current_word: String = self
+            .code
+            .chars()
+            .skip(word.start)
+            .take(word.len())
+            .collect();
+
+        self.completion_query = current_word.clone();
+        self.completion_items = self.get_completion_suggestions(&current_word);
+        self.completion_selected = 0;
+        self.show_completion = self.settings.code_completion && !self.completion_items.is_empty();
+    }
+}
+
+/// One syntax tree node, open to start with so the whole shape shows
+fn show_syntax_node(ui: &mut egui::Ui, node: &time_warp_core::basic::tree::SyntaxNode, id: String) {
+    if node.children.is_empty() {
+        ui.monospace(&node.label);
+        return;
+    }
+    egui::CollapsingHeader::new(egui::RichText::new(&node.label).monospace())
+        .id_source(&id)
+        .default_open(true)
+        .show(ui, |ui| {
+            for (index, child) in node.children.iter().enumerate() {
+                show_syntax_node(ui, child, format!("{}.{}", id, index));
+            }
+        });
+}
+
+/// The IDE's look: the chosen theme, fonts sized from the chosen font size
+/// and roomier spacing. Set at startup and again when the settings change;
+/// the context keeps it for every frame and viewport.
+fn ide_style(settings: &settings::Settings) -> egui::Style {
+    let mut style = egui::Style::default();
+    let size = settings.font_size;
+
+    style.visuals = settings.theme.visuals();
+
+    // Set a more modern font
+    style.text_styles.insert(
+        egui::TextStyle::Heading,
+        egui::FontId::new(size + 6.0, egui::FontFamily::Proportional),
+    );
+    style.text_styles.insert(
+        egui::TextStyle::Body,
+        egui::FontId::new(size, egui::FontFamily::Proportional),
+    );
+    style.text_styles.insert(
+        egui::TextStyle::Button,
+        egui::FontId::new(size, egui::FontFamily::Proportional),
+    );
+    style.text_styles.insert(
+        egui::TextStyle::Monospace,
+        egui::FontId::new(settings.code_font_size, egui::FontFamily::Monospace),
+    );
+    style.spacing.item_spacing = egui::vec2(8.0, 4.0);
+    style.spacing.button_padding = egui::vec2(8.0, 4.0);
+    style
+}
+
+impl TimeWarpApp {
+    /// Create the app and apply the IDE style once, instead of on every frame
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut app = Self::default();
+        app.migrate_legacy_storage();
+        app.load_settings();
+        cc.egui_ctx.set_style(ide_style(&app.settings));
+        app.apply_code_font(&cc.egui_ctx);
+        app.restore_session();
+        app
+    }
+
+    /// Move files left in `~/.time_warp` by older versions to the platform locations
+    fn migrate_legacy_storage(&mut self) {
+        let Some(legacy) = storage::legacy_dir() else {
+            return;
+        };
+        match storage::migrate_legacy(&legacy, &self.storage) {
+            Ok(moved) => self.storage_migration_log = moved,
+            Err(err) => self.show_error(format!(
+                "Could not move files from {}: {}",
+                legacy.display(),
+                err
+            )),
+        }
+    }
+}
+
+impl TimeWarpApp {
+    /// Draw one complete workspace (menus, editor, output, dialogs) into `ctx`
+    fn show_workspace(&mut self, ctx: &egui::Context) {
+        // Handle keyboard shortcuts
+        self.handle_shortcuts(ctx);
+        self.handle_dropped_files(ctx);
+        self.poll_external_changes(ctx);
+        self.poll_watch_mode(ctx);
+        self.pump_program_input(ctx);
+        #[cfg(feature = "audio")]
+        self.poll_music(ctx);
+
+        egui::TopBottomPanel::top("menu_bar")
+            .min_height(40.0)
+            .show(ctx, |ui| {
+                ui.painter().rect_filled(
+                    ui.available_rect_before_wrap(),
+                    0.0,
+                    self.palette().menu_bar,
+                );
+                ui.add_space(6.0);
+                egui::menu::bar(ui, |ui| {
+                    // File menu
+                    ui.menu_button("📁 File", |ui| {
+                        if ui.button("📄 New File...").clicked() {
+                            self.open_new_file_dialog();
+                            ui.close_menu();
+                        }
+                        #[cfg(feature = "workspaces")]
+                        if ui.button("🪟 New Workspace Window").clicked() {
+                            self.new_workspace_requested = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("📂 Open File...").clicked() {
+                            self.open_file();
+                            ui.close_menu();
+                        }
+                        ui.menu_button("🕘 Recent", |ui| self.render_recent_files_menu(ui));
+                        ui.separator();
+                        if ui.button("📁 Open Project...").clicked() {
+                            self.open_project_dialog();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("📁 New Project...")
+                            .on_hover_text("Group this lesson's programs and data files")
+                            .clicked()
+                        {
+                            self.new_project_dialog();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(self.project.is_some(), egui::Button::new("Close Project"))
+                            .clicked()
+                        {
+                            self.project = None;
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("💾 Save").clicked() {
+                            self.save_current_file();
+                            ui.close_menu();
+                        }
+                        if ui.button("💾 Save As...").clicked() {
+                            if let Some(path) = FileDialog::new()
+                                .set_file_name(self.untitled_file_name())
+                                .save_file()
+                            {
+                                let location = path.display().to_string();
+                                if self.save_to_location(&location) {
+                                    self.remember_recent_file(&location);
+                                    self.output = format!("Saved to {}", path.display());
+                                }
+                            }
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.language == Language::Basic
+                                    && self.basic_interpreter.is_some(),
+                                egui::Button::new("⏸ Save Paused Run..."),
+                            )
+                            .on_hover_text("Save a run waiting for input or paused in the debugger, to carry on later")
+                            .clicked()
+                        {
+                            self.save_run_state();
+                            ui.close_menu();
+                        }
+                        if ui.button("▶ Resume Saved Run...").clicked() {
+                            self.resume_run_state();
+                            ui.close_menu();
+                        }
+                        #[cfg(feature = "webdav")]
+                        if ui.button("🌐 Class Share...").clicked() {
+                            self.show_class_share = true;
+                            ui.close_menu();
+                        }
+                        ui.menu_button("📤 Export", |ui| {
+                            if ui.button("🖼 Drawing as Image...").clicked() {
+                                self.export_canvas_image();
+                                ui.close_menu();
+                            }
+                            if ui
+                                .button("🎞 Record Animated GIF...")
+                                .on_hover_text("Save the turtle replay so it can be shared")
+                                .clicked()
+                            {
+                                self.export_animation();
+                                ui.close_menu();
+                            }
+                            ui.checkbox(&mut self.export_fit, "Fit drawing");
+                            ui.separator();
+                            if ui
+                                .button("🌐 Web Page...")
+                                .on_hover_text("One HTML file with the program, its output and its drawing replayed, for any browser")
+                                .clicked()
+                            {
+                                self.export_html();
+                                ui.close_menu();
+                            }
+                            if ui
+                                .button("📦 Standalone...")
+                                .on_hover_text("A copy of the program that runs full-screen without the IDE")
+                                .clicked()
+                            {
+                                self.export_standalone();
+                                ui.close_menu();
+                            }
+                        });
+                        ui.checkbox(&mut self.file_backends.usb_safe_saves, "USB-safe Saves")
+                            .on_hover_text(
+                                "Write to a temporary file first so unplugging mid-save can't corrupt a program",
+                            );
+                        ui.separator();
+                        if ui
+                            .add_enabled(
+                                self.last_file_path.is_some(),
+                                egui::Button::new("📝 Open in External Editor"),
+                            )
+                            .on_hover_text("Edit in another editor; saves there reload here")
+                            .clicked()
+                        {
+                            self.open_in_external_editor();
+                            ui.close_menu();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Editor:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.external_editor)
+                                    .desired_width(140.0),
+                            )
+                            .on_hover_text("Command used to open files, e.g. code or gedit");
+                        });
+                        ui.checkbox(&mut self.file_watcher.enabled, "Watch for External Changes");
+                        ui.separator();
+                        if ui.button("🚪 Exit").clicked() {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                            ui.close_menu();
+                        }
+                    });
+                    ui.menu_button("✏️ Edit", |ui| {
+                        if ui.button("🔍 Find...").clicked() {
+                            self.show_find_replace = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("🔄 Replace...").clicked() {
+                            self.show_find_replace = true;
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("↶ Undo").clicked() {
+                            self.undo();
+                            ui.close_menu();
+                        }
+                        if ui.button("↷ Redo").clicked() {
+                            self.redo();
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("📋 Copy").clicked() {
+                            self.copy_text(ctx);
+                            ui.close_menu();
+                        }
+                        if ui.button("✂️ Cut").clicked() {
+                            self.cut_text(ctx);
+                            ui.close_menu();
+                        }
+                        if ui.button("📄 Paste").clicked() {
+                            self.paste_text(ctx);
+                            ui.close_menu();
+                        }
+                        ui.menu_button("↕️ Lines", |ui| {
+                            for action in [
+                                keybindings::Action::DuplicateLine,
+                                keybindings::Action::DeleteLine,
+                                keybindings::Action::MoveLineUp,
+                                keybindings::Action::MoveLineDown,
+                            ] {
+                                let shortcut = self.settings.keybindings.label(action);
+                                if ui
+                                    .add(egui::Button::new(action.name()).shortcut_text(shortcut))
+                                    .clicked()
+                                {
+                                    self.run_action(action);
+                                    ui.close_menu();
+                                }
+                            }
+                            ui.separator();
+                            if ui
+                                .add(egui::Button::new("Toggle Comment").shortcut_text("Ctrl+/"))
+                                .clicked()
+                            {
+                                self.toggle_comment();
+                                ui.close_menu();
+                            }
+                        });
+                        ui.menu_button("🔖 Bookmarks", |ui| {
+                            for action in [
+                                keybindings::Action::ToggleBookmark,
+                                keybindings::Action::NextBookmark,
+                            ] {
+                                let shortcut = self.settings.keybindings.label(action);
+                                if ui
+                                    .add(egui::Button::new(action.name()).shortcut_text(shortcut))
+                                    .clicked()
+                                {
+                                    self.run_action(action);
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                        ui.separator();
+                        if ui.button("⚙️ Settings...").clicked() {
+                            self.show_settings = true;
+                            ui.close_menu();
+                        }
+                    });
+                    ui.menu_button("👁️ View", |ui| {
+                        if ui
+                            .selectable_label(self.show_line_numbers, "📏 Show Line Numbers")
+                            .clicked()
+                        {
+                            self.show_line_numbers = !self.show_line_numbers;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .selectable_label(self.split_document.is_some(), "Split View")
+                            .on_hover_text("A second pane beside the editor, scrolled separately")
+                            .clicked()
+                        {
+                            self.toggle_split_view();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.project.is_some(),
+                                egui::SelectableLabel::new(self.show_project_panel, "📁 Project Panel"),
+                            )
+                            .clicked()
+                        {
+                            self.show_project_panel = !self.show_project_panel;
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("Fold All").clicked() {
+                            self.fold_all(true);
+                            ui.close_menu();
+                        }
+                        if ui.button("Unfold All").clicked() {
+                            self.fold_all(false);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .selectable_label(
+                                self.syntax_highlighting_enabled,
+                                "🎨 Syntax Highlighting",
+                            )
+                            .clicked()
+                        {
+                            self.syntax_highlighting_enabled = !self.syntax_highlighting_enabled;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .selectable_label(self.settings.code_completion, "💡 Code Completion")
+                            .clicked()
+                        {
+                            self.settings.code_completion = !self.settings.code_completion;
+                            self.apply_settings(ctx);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .selectable_label(self.show_token_inspector, "🔤 Token Inspector")
+                            .on_hover_text("What the tokenizer makes of the program")
+                            .clicked()
+                        {
+                            self.show_token_inspector = !self.show_token_inspector;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .selectable_label(self.show_diagnostics, "⚠ Diagnostics")
+                            .on_hover_text(
+                                "Parse errors, unused variables, jumps to missing lines and \
+                                 code after END",
+                            )
+                            .clicked()
+                        {
+                            self.show_diagnostics = !self.show_diagnostics;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .selectable_label(self.show_printer, "🖨 Printer")
+                            .on_hover_text("What programs print with LPRINT")
+                            .clicked()
+                        {
+                            self.show_printer = !self.show_printer;
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        for theme in theme::Theme::ALL {
+                            if ui
+                                .selectable_label(self.settings.theme == theme, theme.label())
+                                .clicked()
+                            {
+                                self.settings.theme = theme;
+                                self.apply_settings(ctx);
+                                ui.close_menu();
+                            }
+                        }
+                        ui.separator();
+                        #[cfg(feature = "hints")]
+                        if ui
+                            .selectable_label(self.settings.hints_enabled, "💡 Learning Hints")
+                            .on_hover_text("Show a hint after the same error happens repeatedly")
+                            .clicked()
+                        {
+                            self.settings.hints_enabled = !self.settings.hints_enabled;
+                            if !self.settings.hints_enabled {
+                                self.active_hint = None;
+                            }
+                            self.apply_settings(ctx);
+                            ui.close_menu();
+                        }
+                        #[cfg(feature = "profiler")]
+                        {
+                            if ui
+                                .selectable_label(self.profiling_enabled, "⏱️ Profile Execution")
+                                .clicked()
+                            {
+                                self.profiling_enabled = !self.profiling_enabled;
+                                ui.close_menu();
+                            }
+                            if ui
+                                .add_enabled(
+                                    !self.profile_results.is_empty(),
+                                    egui::SelectableLabel::new(
+                                        self.show_profiler,
+                                        "📊 Profiler Results",
+                                    ),
+                                )
+                                .clicked()
+                            {
+                                self.show_profiler = !self.show_profiler;
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.menu_button("🏷️ Language", |ui| {
+                        for &language in Language::ALL {
+                            if ui
+                                .selectable_label(self.language == language, language.name())
+                                .clicked()
+                            {
+                                self.set_language(language);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.menu_button("❓ Help", |ui| {
+                        if ui.button("ℹ️ About").clicked() {
+                            self.show_about = true;
+                            ui.close_menu();
+                        }
+                        // Developer tools only show while Shift is held
+                        if ui.input(|i| i.modifiers.shift) && ui.button("🌳 Dump AST").clicked() {
+                            self.dump_ast();
+                            ui.close_menu();
+                        }
+                        if ui.button("💬 Test Prompt").clicked() {
+                            self.prompt_user("Enter some text for testing:", |input| {
+                                println!("User entered: {}", input);
+                                // In a real application, you would do something with the input here
+                            });
+                            ui.close_menu();
+                        }
+                    });
+                });
+                ui.add_space(6.0);
+            });
+
+        // Enhanced Toolbar
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.add_space(2.0);
+            egui::Frame::none()
+                .fill(ui.style().visuals.window_fill())
+                .stroke(ui.style().visuals.window_stroke())
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add_space(8.0);
+
+                        // File operations
+                        if ui
+                            .button("📄 New")
+                            .on_hover_text(format!(
+                                "New File ({})",
+                                self.settings
+                                    .keybindings
+                                    .label(keybindings::Action::NewFile)
+                            ))
+                            .clicked()
+                        {
+                            self.open_new_file_dialog();
+                        }
+                        if ui
+                            .button("📂 Open")
+                            .on_hover_text(format!(
+                                "Open File ({})",
+                                self.settings
+                                    .keybindings
+                                    .label(keybindings::Action::OpenFile)
+                            ))
+                            .clicked()
+                        {
+                            self.open_file();
+                        }
+                        if ui
+                            .button("💾 Save")
+                            .on_hover_text(format!(
+                                "Save File ({})",
+                                self.settings
+                                    .keybindings
+                                    .label(keybindings::Action::SaveFile)
+                            ))
+                            .clicked()
+                        {
+                            self.save_current_file();
+                        }
+
+                        ui.separator();
+
+                        // Edit operations
+                        if ui.button("↶ Undo").on_hover_text("Undo").clicked() {
+                            // Note: egui TextEdit doesn't have built-in undo, this is a placeholder
+                        }
+                        if ui.button("↷ Redo").on_hover_text("Redo").clicked() {
+                            // Note: egui TextEdit doesn't have built-in redo, this is a placeholder
+                        }
+                        if ui.button("📋 Copy").on_hover_text("Copy").clicked() {
+                            self.copy_text(ctx);
+                        }
+                        if ui.button("✂️ Cut").on_hover_text("Cut").clicked() {
+                            self.cut_text(ctx);
+                        }
+                        if ui.button("📄 Paste").on_hover_text("Paste").clicked() {
+                            self.paste_text(ctx);
+                        }
+
+                        ui.separator();
+
+                        // Code operations
+                        if ui
+                            .button("🔍 Find")
+                            .on_hover_text(format!(
+                                "Find/Replace ({})",
+                                self.settings.keybindings.label(keybindings::Action::Find)
+                            ))
+                            .clicked()
+                        {
+                            self.show_find_replace = !self.show_find_replace;
+                        }
+                        if ui
+                            .button("▶️ Run")
+                            .on_hover_text(format!(
+                                "Run Code ({})",
+                                self.settings.keybindings.label(keybindings::Action::Run)
+                            ))
+                            .clicked()
+                        {
+                            self.active_tab = 1; // Switch to Output tab when running
+                            self.execute_code();
+                        }
+                        if self.polling_input
+                            && ui
+                                .button("⏹ Stop")
+                                .on_hover_text("Stop the program while it sleeps or waits for keys or the mouse")
+                                .clicked()
+                        {
+                            self.stop_program();
+                        }
+                        if ui
+                            .button("🧪 Tests")
+                            .on_hover_text("Run the program and summarize its ASSERT statements")
+                            .clicked()
+                        {
+                            self.run_tests();
+                        }
+                        if ui
+                            .selectable_label(self.watch_mode, "👁️ Watch")
+                            .on_hover_text("Re-run the program every time the file is saved")
+                            .clicked()
+                        {
+                            self.watch_mode = !self.watch_mode;
+                            self.watch_run_due = None;
+                        }
+                        #[cfg(feature = "audio")]
+                        if ui
+                            .selectable_label(!self.settings.play_sound, "🔇 Mute")
+                            .on_hover_text("Silence BEEP, SOUND and PLAY")
+                            .clicked()
+                        {
+                            self.settings.play_sound = !self.settings.play_sound;
+                            self.apply_settings(ctx);
+                        }
+                        if ui
+                            .button("🗑️ Clear")
+                            .on_hover_text(format!(
+                                "Clear Output ({})",
+                                self.settings
+                                    .keybindings
+                                    .label(keybindings::Action::ClearOutput)
+                            ))
+                            .clicked()
+                        {
+                            self.output = String::new();
+                            self.clear_canvas();
+                            self.reset_canvas_view();
+                        }
+
+                        ui.separator();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.add_space(8.0);
+                        });
+                    });
+                });
+            ui.add_space(2.0);
+        });
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("🚀 Time Warp IDE");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // Status indicators
+                    if self.is_executing || self.polling_input {
+                        ui.colored_label(self.palette().success, "● Running");
+                        if self.polling_input {
+                            ui.spinner();
+                        }
+                    } else if self.waiting_for_input {
+                        ui.colored_label(self.palette().warning, "● Waiting for Input");
+                    } else {
+                        ui.colored_label(egui::Color32::GRAY, "● Ready");
+                    }
+
+                    ui.separator();
+
+                    // File info
+                    if let Some(path) = &self.last_file_path {
+                        let backend = self
+                            .file_backends
+                            .backend_for(path)
+                            .map(|backend| backend.name())
+                            .unwrap_or("Unavailable");
+                        ui.label(format!(
+                            "📄 {}",
+                            std::path::Path::new(path)
+                                .file_name()
+                                .unwrap_or(std::ffi::OsStr::new("untitled"))
+                                .to_string_lossy()
+                        ))
+                        .on_hover_text(format!("{} ({})", path, backend));
+                    } else {
+                        ui.label(format!("📄 {}", self.document_name()));
+                    }
+
+                    #[cfg(feature = "webdav")]
+                    if let Some(status) = self.share_transfer_status() {
+                        ui.separator();
+                        ui.spinner();
+                        ui.label(status);
+                    }
+                });
+            });
+        });
+
+        if self.show_project_panel && self.project.is_some() {
+            egui::SidePanel::left("project_panel")
+                .default_width(200.0)
+                .show(ctx, |ui| self.render_project_panel(ui));
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                // Tab bar with better styling
+                egui::Frame::none()
+                    .fill(ui.style().visuals.faint_bg_color)
                     .stroke(egui::Stroke::new(
                         1.0,
                         ui.style().visuals.window_stroke.color,
@@ -1552,2062 +3351,5125 @@ impl eframe::App for TimeWarpApp {
                         ui.horizontal(|ui| {
                             ui.add_space(8.0);
 
-                            // Tab buttons with better styling
-                            let tab_height = 32.0;
-                            if ui
-                                .add(
-                                    egui::Button::new("📝 Code Editor")
-                                        .fill(if self.active_tab == 0 {
-                                            ui.style().visuals.selection.bg_fill
-                                        } else {
-                                            egui::Color32::TRANSPARENT
-                                        })
-                                        .stroke(if self.active_tab == 0 {
-                                            egui::Stroke::new(
-                                                2.0,
-                                                ui.style().visuals.selection.stroke.color,
-                                            )
-                                        } else {
-                                            egui::Stroke::NONE
-                                        })
-                                        .rounding(egui::Rounding::same(4.0))
-                                        .min_size(egui::vec2(120.0, tab_height)),
-                                )
-                                .clicked()
-                            {
-                                self.active_tab = 0;
-                            }
+                            // Tab buttons with better styling
+                            let tab_height = 32.0;
+                            if ui
+                                .add(
+                                    egui::Button::new("📝 Code Editor")
+                                        .fill(if self.active_tab == 0 {
+                                            ui.style().visuals.selection.bg_fill
+                                        } else {
+                                            egui::Color32::TRANSPARENT
+                                        })
+                                        .stroke(if self.active_tab == 0 {
+                                            egui::Stroke::new(
+                                                2.0,
+                                                ui.style().visuals.selection.stroke.color,
+                                            )
+                                        } else {
+                                            egui::Stroke::NONE
+                                        })
+                                        .rounding(egui::Rounding::same(4.0))
+                                        .min_size(egui::vec2(120.0, tab_height)),
+                                )
+                                .clicked()
+                            {
+                                self.active_tab = 0;
+                            }
+
+                            if ui
+                                .add(
+                                    egui::Button::new("🖥️ Output & Graphics")
+                                        .fill(if self.active_tab == 1 {
+                                            ui.style().visuals.selection.bg_fill
+                                        } else {
+                                            egui::Color32::TRANSPARENT
+                                        })
+                                        .stroke(if self.active_tab == 1 {
+                                            egui::Stroke::new(
+                                                2.0,
+                                                ui.style().visuals.selection.stroke.color,
+                                            )
+                                        } else {
+                                            egui::Stroke::NONE
+                                        })
+                                        .rounding(egui::Rounding::same(4.0))
+                                        .min_size(egui::vec2(140.0, tab_height)),
+                                )
+                                .clicked()
+                            {
+                                self.active_tab = 1;
+                            }
+
+                            if ui
+                                .add(
+                                    egui::Button::new("🐛 Debug")
+                                        .fill(if self.active_tab == 2 {
+                                            ui.style().visuals.selection.bg_fill
+                                        } else {
+                                            egui::Color32::TRANSPARENT
+                                        })
+                                        .stroke(if self.active_tab == 2 {
+                                            egui::Stroke::new(
+                                                2.0,
+                                                ui.style().visuals.selection.stroke.color,
+                                            )
+                                        } else {
+                                            egui::Stroke::NONE
+                                        })
+                                        .rounding(egui::Rounding::same(4.0))
+                                        .min_size(egui::vec2(100.0, tab_height)),
+                                )
+                                .clicked()
+                            {
+                                self.active_tab = 2;
+                            }
+
+                            if ui
+                                .add(
+                                    egui::Button::new("🌳 Syntax Tree")
+                                        .fill(if self.active_tab == 3 {
+                                            ui.style().visuals.selection.bg_fill
+                                        } else {
+                                            egui::Color32::TRANSPARENT
+                                        })
+                                        .stroke(if self.active_tab == 3 {
+                                            egui::Stroke::new(
+                                                2.0,
+                                                ui.style().visuals.selection.stroke.color,
+                                            )
+                                        } else {
+                                            egui::Stroke::NONE
+                                        })
+                                        .rounding(egui::Rounding::same(4.0))
+                                        .min_size(egui::vec2(120.0, tab_height)),
+                                )
+                                .clicked()
+                            {
+                                self.active_tab = 3;
+                                self.refresh_syntax_tree();
+                            }
+
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.add_space(8.0);
+                                },
+                            );
+                        });
+                    });
+
+                ui.add_space(8.0);
+
+                // Main content area with better styling
+                egui::Frame::none()
+                    .fill(ui.style().visuals.panel_fill)
+                    .stroke(egui::Stroke::new(
+                        1.0,
+                        ui.style().visuals.window_stroke.color,
+                    ))
+                    .rounding(egui::Rounding::same(8.0))
+                    .inner_margin(egui::Margin::same(12.0))
+                    .show(ui, |ui| {
+                        match self.active_tab {
+                            0 => {
+                                // Code Editor Tab
+                                ui.vertical(|ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut self.show_line_numbers, "Line numbers");
+                                        ui.checkbox(&mut self.debug_mode, "Debug mode");
+                                        ui.separator();
+                                        if ui.button("🔍 Find/Replace").clicked() {
+                                            self.show_find_replace = !self.show_find_replace;
+                                        }
+                                        if ui.selectable_label(self.split_document.is_some(), "Split View").clicked() {
+                                            self.toggle_split_view();
+                                        }
+                                        #[cfg(feature = "profiler")]
+                                        if !self.profile_results.is_empty() {
+                                            ui.separator();
+                                            ui.checkbox(&mut self.show_profile_heatmap, "Profile heatmap");
+                                        }
+                                        if let Some(coverage) = &self.coverage {
+                                            let percent = coverage.percent();
+                                            ui.separator();
+                                            ui.checkbox(&mut self.show_coverage, format!("Coverage ({:.0}%)", percent));
+                                        }
+                                    });
+
+                                    self.render_document_tabs(ui);
+
+                                    if self.show_find_replace {
+                                        self.render_find_replace(ui);
+                                        ui.separator();
+                                    }
+
+                                    if self.show_diagnostics {
+                                        egui::TopBottomPanel::bottom("diagnostics_panel")
+                                            .resizable(true)
+                                            .show_inside(ui, |ui| self.render_diagnostics_panel(ui));
+                                    }
+
+                                    if self.split_document.is_some() {
+                                        egui::SidePanel::right("split_view_panel")
+                                            .resizable(true)
+                                            .default_width(ui.available_width() / 2.0)
+                                            .show_inside(ui, |ui| self.render_split_view(ui));
+                                    }
+
+                                    egui::ScrollArea::vertical().show(ui, |ui| {
+                                        if self.show_line_numbers && self.debug_mode {
+                                            // Custom editor with line numbers and breakpoints
+                                            self.render_debug_editor(ui);
+                                        } else {
+                                            // Completion keys are taken before the editor sees them, so
+                                            // Enter picks the suggestion instead of starting a new line
+                                            let open = self.show_completion && !self.completion_items.is_empty();
+                                            let (comment, trigger, hide, down, up, accept) = ui.input_mut(|i| {
+                                                (
+                                                    editor::consume_command_text(i, "/"),
+                                                    i.consume_key(egui::Modifiers::CTRL, egui::Key::Space),
+                                                    open && i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+                                                    open && i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                                                    open && i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                                                    open && i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                                                )
+                                            });
+                                            if comment {
+                                                self.toggle_comment();
+                                            } else if trigger {
+                                                self.trigger_completion();
+                                            } else if hide {
+                                                self.show_completion = false;
+                                            } else if down {
+                                                if self.completion_selected < self.completion_items.len().saturating_sub(1) {
+                                                    self.completion_selected += 1;
+                                                }
+                                            } else if up {
+                                                self.completion_selected = self.completion_selected.saturating_sub(1);
+                                            } else if accept {
+                                                if let Some(selected) = self.completion_items.get(self.completion_selected).cloned() {
+                                                    self.insert_completion(&selected);
+                                                }
+                                            }
+
+                                            self.render_code_editor(ui);
+
+                                            self.total_lines = self.code.lines().count().max(1);
+
+                                            // Show completion popup
+                                            if self.show_completion && !self.completion_items.is_empty() {
+                                                egui::Window::new("Code Completion")
+                                                    .collapsible(false)
+                                                    .resizable(false)
+                                                    .show(ui.ctx(), |ui| {
+                                                        egui::ScrollArea::vertical().show(ui, |ui| {
+                                                            let mut chosen = None;
+                                                            for (i, item) in self.completion_items.iter().enumerate() {
+                                                                let mut button = egui::Button::new(item);
+                                                                if i == self.completion_selected {
+                                                                    button = button.fill(egui::Color32::from_rgb(100, 150, 200));
+                                                                }
+                                                                if ui.add(button).clicked() {
+                                                                    chosen = Some(item.clone());
+                                                                }
+                                                            }
+                                                            if let Some(item) = chosen {
+                                                                self.insert_completion(&item);
+                                                            }
+                                                        });
+                                                    });
+                                            }
+                                        }
+                                    });
+                                });
+                            }
+                            1 => {
+                                // Output & Graphics Tab
+                                ui.vertical(|ui| {
+                                    if !self.canvas_maximized {
+                                        ui.label("Output:");
+                                    }
+
+                                    if !self.canvas_maximized {
+                                        ui.collapsing("📋 Scripted Input", |ui| {
+                                            ui.label("One answer per line. INPUT takes these in order and only asks once they run out.");
+                                            ui.add(
+                                                egui::TextEdit::multiline(&mut self.scripted_input)
+                                                    .font(egui::TextStyle::Monospace)
+                                                    .desired_width(f32::INFINITY)
+                                                    .desired_rows(3),
+                                            );
+                                        });
+                                    }
+
+                                    // Input prompt - show prominently at the top when needed
+                                    if self.waiting_for_input {
+                                        ui.separator();
+                                        ui.label("📝 Program Input Required");
+                                        ui.horizontal(|ui| {
+                                            ui.label(&self.input_prompt);
+                                            let response = ui.text_edit_singleline(&mut self.user_input);
+                                            if ui.button("🚀 Submit").clicked()
+                                                || (response.lost_focus()
+                                                    && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                                            {
+                                                self.submit_program_input();
+                                            }
+                                            if ui.button("❌ Cancel").clicked() {
+                                                self.output = format!("{}Input cancelled.", self.output);
+                                                self.waiting_for_input = false;
+                                                self.user_input.clear();
+                                                self.input_prompt.clear();
+                                                self.current_input_var.clear();
+                                                self.basic_interpreter = None;
+                                                self.waiting_program = None;
+                                            }
+                                        });
+                                        ui.separator();
+                                    }
+
+                                    if !self.canvas_maximized {
+                                        egui::ScrollArea::vertical()
+                                            .max_height(200.0)
+                                            .show(ui, |ui| {
+                                                let output = egui::TextEdit::multiline(&mut self.output)
+                                                    .font(egui::TextStyle::Monospace)
+                                                    .desired_width(f32::INFINITY)
+                                                    .show(ui);
+                                                // Clicking an error selects the line it happened on
+                                                if output.response.clicked() {
+                                                    let clicked = output.cursor_range.map(|range| {
+                                                        let index = range.primary.ccursor.index;
+                                                        let line = editor::line_and_column(&self.output, index).0;
+                                                        self.output.lines().nth(line - 1).unwrap_or("").to_string()
+                                                    });
+                                                    if let Some(line) = clicked.as_deref().and_then(diagnostics_panel::error_line) {
+                                                        self.pending_selection = Some(editor::line_selection(&self.code, line));
+                                                        self.active_tab = 0;
+                                                    }
+                                                }
+                                            });
+                                    }
+
+                                    // Turtle Graphics section
+
+                                    ui.separator();
+                                    ui.label("Turtle Graphics:");
+                                    ui.horizontal(|ui| {
+                                        let maximize = if self.canvas_maximized { "🗗 Restore" } else { "🗖 Maximize" };
+                                        if ui
+                                            .selectable_label(self.canvas_maximized, maximize)
+                                            .on_hover_text("Hide the output text and give the canvas the whole tab")
+                                            .clicked()
+                                        {
+                                            self.canvas_maximized = !self.canvas_maximized;
+                                        }
+                                        ui.separator();
+                                        ui.label("Zoom:");
+                                        ui.add(
+                                            egui::DragValue::new(&mut self.turtle_zoom)
+                                                .clamp_range(turtle::MIN_ZOOM..=turtle::MAX_ZOOM)
+                                                .speed(0.1),
+                                        );
+                                        if ui.button("🔍 Reset View").clicked() {
+                                            self.reset_canvas_view();
+                                        }
+                                        if ui
+                                            .button("🎯 Fit Drawing")
+                                            .on_hover_text("Zoom and pan so the whole drawing is in view")
+                                            .clicked()
+                                        {
+                                            self.fit_canvas_to_drawing();
+                                        }
+                                        ui.label("Background:");
+                                        ui.color_edit_button_srgba(&mut self.canvas_background)
+                                            .on_hover_text("Canvas colour; programs can change it with SETBACKGROUND");
+                                        let mut turtles_shown = self.turtles.values().all(|state| state.visible);
+                                        if ui
+                                            .checkbox(&mut turtles_shown, "🔺 Turtle")
+                                            .on_hover_text("Show the turtle cursors; programs can use HIDETURTLE and SHOWTURTLE")
+                                            .changed()
+                                        {
+                                            self.set_turtles_visible(turtles_shown);
+                                        }
+                                        ui.checkbox(&mut self.show_grid, "# Grid")
+                                            .on_hover_text("Show a coordinate grid, the axes and the position under the mouse");
+                                        ui.separator();
+                                        ui.checkbox(&mut self.turtle_animate, "🐢 Animate")
+                                            .on_hover_text("Watch the turtle draw; untick to show the drawing instantly");
+                                        ui.add_enabled(
+                                            self.turtle_animate,
+                                            egui::Slider::new(&mut self.turtle_speed, 1.0..=500.0)
+                                                .logarithmic(true)
+                                                .suffix(" lines/s"),
+                                        );
+                                        if ui.button("🔁 Replay").clicked() {
+                                            self.turtle_progress = 0.0;
+                                        }
+                                        if ui.button("⏭ Skip").clicked() {
+                                            self.turtle_progress =
+                                                turtle::stroke_count(&self.turtle_commands) as f32;
+                                        }
+                                        ui.separator();
+                                        ui.checkbox(&mut self.export_fit, "Fit drawing")
+                                            .on_hover_text("Export the whole drawing instead of the current zoom and pan");
+                                        if ui
+                                            .button("🖼 Export Image…")
+                                            .on_hover_text("Save the drawing as a PNG, or as an SVG for crisp printing")
+                                            .clicked()
+                                        {
+                                            self.export_canvas_image();
+                                        }
+                                    });
+                                    ui.add_space(4.0);
+
+                                    // The canvas fills the rest of the tab
+                                    self.render_turtle_canvas(ui);
+                                });
+                            }
+                            2 => {
+                                // Debug Tab
+                                ui.vertical(|ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut self.debug_mode, "Enable Debug Mode");
+                                        ui.separator();
+                                        ui.label("Debug State:");
+                                        match self.debug_state {
+                                            DebugState::Stopped => ui.colored_label(egui::Color32::GRAY, "⏹️ Stopped"),
+                                            DebugState::Running => ui.colored_label(self.palette().success, "▶️ Running"),
+                                            DebugState::Paused => ui.colored_label(self.palette().warning, "⏸️ Paused"),
+                                        }
+                                    });
+
+                                    ui.separator();
+
+                                    // Debug Controls
+                                    ui.horizontal(|ui| {
+                                        if ui.button("▶️ Start Debug").on_hover_text(format!("Start debugging session ({})", self.settings.keybindings.label(keybindings::Action::StartDebug))).clicked() && self.debug_mode {
+                                            self.start_debug_session();
+                                        }
+                                        if ui.button("⏯️ Continue").on_hover_text("Continue execution from paused state").clicked() && self.debug_mode && self.debug_state == DebugState::Paused {
+                                            self.step_debug(time_warp_core::basic::StepMode::Continue);
+                                        }
+                                        if ui.button("⏸️ Pause").on_hover_text("Pause execution").clicked() && self.debug_mode && self.debug_state == DebugState::Running {
+                                            self.debug_state = DebugState::Paused;
+                                        }
+                                        if ui.button("⏹️ Stop").on_hover_text("Stop debugging session").clicked() && self.debug_mode {
+                                            self.stop_debug_session();
+                                        }
+                                        if ui.button("⤵️ Step Over").on_hover_text(format!("Run the next line, treating GOSUB as one step ({})", self.settings.keybindings.label(keybindings::Action::StepOver))).clicked() && self.debug_mode && self.debug_state == DebugState::Paused {
+                                            self.step_debug(time_warp_core::basic::StepMode::StepOver);
+                                        }
+                                        if ui.button("⬇️ Step Into").on_hover_text(format!("Run the next line, following GOSUB into the subroutine ({})", self.settings.keybindings.label(keybindings::Action::StepInto))).clicked() && self.debug_mode && self.debug_state == DebugState::Paused {
+                                            self.step_debug(time_warp_core::basic::StepMode::StepInto);
+                                        }
+                                        if ui.button("⬆️ Step Out").on_hover_text(format!("Run until the current subroutine returns ({})", self.settings.keybindings.label(keybindings::Action::StepOut))).clicked() && self.debug_mode && self.debug_state == DebugState::Paused {
+                                            self.step_debug(time_warp_core::basic::StepMode::StepOut);
+                                        }
+                                        if ui.button("🔄 Reset").on_hover_text("Restart debug session").clicked() && self.debug_mode {
+                                            self.start_debug_session(); // Restart debug session
+                                        }
+                                    });
+
+                                    ui.separator();
+
+                                    // Breakpoints
+                                    ui.collapsing("Breakpoints", |ui| {
+                                        ui.label("Click on line numbers in the editor to toggle breakpoints");
+                                        let filename = self.document_name();
+
+                                        if let Some(breakpoints) = self.breakpoints.get(&filename) {
+                                            ui.label(format!("Breakpoints in {}: {:?}", filename, breakpoints));
+                                        } else {
+                                            ui.label(format!("No breakpoints in {}", filename));
+                                        }
+
+                                        if ui.button("Clear All Breakpoints").clicked() {
+                                            self.breakpoints.clear();
+                                        }
+                                    });
+
+                                    // Variables
+                                    ui.collapsing("Variables", |ui| {
+                                        ui.label("📊 Debug Variables:");
+                                        if self.debug_variables.is_empty() {
+                                            ui.label("  No debug variables");
+                                        } else {
+                                            for (name, value) in &self.debug_variables {
+                                                ui.label(format!("  {} = \"{}\"", name, value));
+                                            }
+                                        }
+
+                                        ui.separator();
+                                        ui.label("🔢 Program Variables:");
+                                        if self.variables.is_empty() {
+                                            ui.label("  No program variables");
+                                        } else {
+                                            for (name, value) in &self.variables {
+                                                ui.label(format!("  {} = \"{}\"", name, value));
+                                            }
+                                        }
+                                    });
+
+                                    // Call Stack
+                                    ui.collapsing("Call Stack", |ui| {
+                                        if self.debug_call_stack.is_empty() {
+                                            ui.label("Call stack is empty");
+                                        } else {
+                                            for (i, frame) in self.debug_call_stack.iter().enumerate() {
+                                                ui.label(format!("{}: {}", i, frame));
+                                            }
+                                        }
+                                    });
+
+                                    // Current Line
+                                    if let Some(line) = self.current_debug_line {
+                                        ui.separator();
+                                        ui.label(format!("Current Debug Line: {}", line));
+                                    }
+                                });
+                            }
+                            3 => {
+                                // Syntax Tree Tab
+                                ui.vertical(|ui| {
+                                    ui.horizontal(|ui| {
+                                        if ui.button("🔄 Refresh").clicked() {
+                                            self.refresh_syntax_tree();
+                                        }
+                                        ui.label("How the parser reads the program in the editor");
+                                    });
+                                    ui.separator();
+                                    egui::ScrollArea::vertical().show(ui, |ui| match &self.syntax_tree {
+                                        Ok(nodes) if nodes.is_empty() => {
+                                            ui.label("No statements yet.");
+                                        }
+                                        Ok(nodes) => {
+                                            for (index, node) in nodes.iter().enumerate() {
+                                                show_syntax_node(ui, node, index.to_string());
+                                            }
+                                        }
+                                        Err(message) => {
+                                            ui.colored_label(egui::Color32::RED, message);
+                                        }
+                                    });
+                                });
+                            }
+                            _ => {}
+                        }
+                    });
+            });
+        });
+
+        // General prompt handling - shown prominently when active
+        if self.general_prompt_active {
+            let mut open = true;
+            egui::Window::new("💬 Input Required")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(20.0);
+                        ui.label(&self.general_prompt_message);
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            ui.label("Input:");
+                            let response = ui.text_edit_singleline(&mut self.general_prompt_input);
+                            if ui.button("🚀 Submit").clicked()
+                                || (response.lost_focus()
+                                    && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                            {
+                                // Call the callback with the input
+                                if let Some(callback) = self.general_prompt_callback.take() {
+                                    callback(self.general_prompt_input.clone());
+                                }
+
+                                // Reset prompt state
+                                self.general_prompt_active = false;
+                                self.general_prompt_message.clear();
+                                self.general_prompt_input.clear();
+                            }
+                            if ui.button("❌ Cancel").clicked() {
+                                // Reset prompt state without calling callback
+                                self.general_prompt_active = false;
+                                self.general_prompt_message.clear();
+                                self.general_prompt_input.clear();
+                                self.general_prompt_callback = None;
+                            }
+                        });
+                    });
+                });
+
+            // If window was closed (user clicked X), treat as cancel
+            if !open {
+                self.general_prompt_active = false;
+                self.general_prompt_message.clear();
+                self.general_prompt_input.clear();
+                self.general_prompt_callback = None;
+            }
+        }
+
+        // Status Bar
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.add_space(2.0);
+            egui::Frame::none()
+                .fill(ui.style().visuals.window_fill())
+                .stroke(ui.style().visuals.window_stroke())
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add_space(8.0);
+
+                        // File and cursor information
+                        let line_count = self.code.lines().count();
+                        let char_count = self.code.chars().count();
+                        let selected = if self.selection.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" ({} selected)", self.selection.range().len())
+                        };
+                        ui.label(format!(
+                            "📏 Lines: {} | Chars: {} | Ln {}, Col {}{}",
+                            line_count, char_count, self.cursor_line, self.cursor_column, selected
+                        ));
+
+                        ui.separator();
+
+                        // Language Run executes the program as
+                        let mut chosen = self.language;
+                        egui::ComboBox::from_id_source("status_language")
+                            .selected_text(format!("🏷️ {}", self.language.name()))
+                            .show_ui(ui, |ui| {
+                                for &language in Language::ALL {
+                                    ui.selectable_value(&mut chosen, language, language.name());
+                                }
+                            });
+                        if chosen != self.language {
+                            self.set_language(chosen);
+                        }
+
+                        ui.separator();
+
+                        // Execution status
+                        if self.is_executing || self.polling_input {
+                            ui.colored_label(self.palette().success, "▶️ Running");
+                        } else if self.waiting_for_input {
+                            ui.colored_label(self.palette().warning, "⏸️ Waiting for Input");
+                        } else if self.general_prompt_active {
+                            ui.colored_label(egui::Color32::BLUE, "💬 Awaiting Response");
+                        } else {
+                            ui.colored_label(egui::Color32::GRAY, "⏹️ Ready");
+                        }
+
+                        ui.separator();
+
+                        if self.watch_mode {
+                            ui.colored_label(egui::Color32::from_rgb(0, 120, 200), "👁️ Watching")
+                                .on_hover_text("The program re-runs each time you save");
+                            ui.separator();
+                        }
+
+                        // Timeout setting
+                        ui.label(format!("⏰ Timeout: {}ms", self.execution_timeout_ms()));
+
+                        // Statements executed by the last run
+                        if let Some(stats) = &self.execution_stats {
+                            let percent = stats.percent_of_limit();
+                            let text = format!(
+                                "⚙️ {} statements | {:.1} ms | {:.1}% of limit",
+                                stats.statements,
+                                stats.elapsed.as_secs_f64() * 1000.0,
+                                percent.min(100.0)
+                            );
+                            let color = if percent >= 100.0 {
+                                egui::Color32::RED
+                            } else if percent >= 80.0 {
+                                egui::Color32::from_rgb(230, 140, 0)
+                            } else {
+                                ui.style().visuals.text_color()
+                            };
+                            ui.colored_label(color, text).on_hover_text(format!(
+                                "Programs stop after {} statements",
+                                stats.max_instructions
+                            ));
+                        }
+
+                        ui.separator();
+
+                        // Debug mode status
+                        if self.debug_mode {
+                            match self.debug_state {
+                                DebugState::Running => {
+                                    ui.colored_label(self.palette().success, "🐛 Debug: Running");
+                                }
+                                DebugState::Paused => {
+                                    ui.colored_label(self.palette().warning, "🐛 Debug: Paused");
+                                }
+                                DebugState::Stopped => {
+                                    ui.colored_label(egui::Color32::RED, "🐛 Debug: Stopped");
+                                }
+                            }
+                        } else {
+                            ui.colored_label(
+                                egui::Color32::GRAY,
+                                format!(
+                                    "🐛 Debug: Off ({} to toggle)",
+                                    self.settings
+                                        .keybindings
+                                        .label(keybindings::Action::ToggleDebugMode)
+                                ),
+                            );
+                        }
+
+                        ui.separator();
+
+                        // View options status
+                        if self.show_line_numbers {
+                            ui.label("📏 Line Numbers: ON");
+                        }
+                        if self.syntax_highlighting_enabled {
+                            ui.label("🎨 Syntax Highlighting: ON");
+                        }
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.add_space(8.0);
+                            ui.label("2.0.0");
+                        });
+                    });
+                });
+            ui.add_space(2.0);
+        });
+
+        // Hint card for repeated errors
+        #[cfg(feature = "hints")]
+        if self.active_hint.is_some() {
+            self.render_hint_card(ctx);
+        }
+
+        // Profiler results
+        #[cfg(feature = "profiler")]
+        if self.show_profiler && !self.profile_results.is_empty() {
+            self.render_profiler_window(ctx);
+        }
+
+        if self.show_token_inspector {
+            self.render_token_inspector(ctx);
+        }
+
+        if self.show_printer {
+            self.render_printer(ctx);
+        }
+
+        if self.show_settings {
+            self.render_settings(ctx);
+        }
+
+        // New File dialog
+        #[cfg(feature = "templates")]
+        if self.show_new_file_dialog {
+            self.render_new_file_dialog(ctx);
+        }
+
+        self.render_external_conflict(ctx);
+        self.render_close_document_prompt(ctx);
+        self.render_reload_prompt(ctx);
+        self.render_quit_prompt(ctx);
+        self.render_permission_prompt(ctx);
+        #[cfg(feature = "webdav")]
+        {
+            self.poll_share_transfer(ctx);
+            self.render_class_share(ctx);
+        }
+
+        // About dialog
+        if self.show_about {
+            egui::Window::new("About Time Warp IDE")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.heading("Time Warp IDE");
+                        ui.label("Version 2.0.0");
+                        ui.label("A modern, educational programming environment");
+                        ui.label("built in Rust using the egui framework.");
+                        ui.separator();
+                        ui.label("Exclusive TW BASIC development environment");
+                        ui.label("with interactive input and turtle graphics.");
+                        ui.separator();
+                    });
+
+                    ui.collapsing("🗄️ Storage", |ui| {
+                        egui::Grid::new("storage_paths")
+                            .num_columns(2)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (label, path) in self.storage.entries() {
+                                    ui.label(label);
+                                    ui.monospace(path.display().to_string());
+                                    ui.end_row();
+                                }
+                            });
+                        if !self.storage_migration_log.is_empty() {
+                            ui.separator();
+                            ui.label("Moved from the old ~/.time_warp folder:");
+                            for line in &self.storage_migration_log {
+                                ui.small(line);
+                            }
+                        }
+                    });
+
+                    ui.vertical_centered(|ui| {
+                        ui.separator();
+                        if ui.button("Close").clicked() {
+                            self.show_about = false;
+                        }
+                    });
+                });
+        }
+
+        // Error notification toast
+        if let Some(ref error_msg) = self.error_message {
+            let toast_duration = 3.0; // Show for 3 seconds
+            if self.error_timer < toast_duration {
+                self.error_timer += ctx.input(|i| i.unstable_dt).min(0.1) as f64; // Cap delta time
+
+                // Position toast at bottom center
+                let screen_rect = ctx.screen_rect();
+                let toast_width = 400.0;
+                let toast_height = 60.0;
+                let toast_pos = egui::pos2(
+                    screen_rect.center().x - toast_width / 2.0,
+                    screen_rect.bottom() - toast_height - 20.0,
+                );
+
+                let mut dismiss_clicked = false;
+                egui::Area::new("error_toast")
+                    .fixed_pos(toast_pos)
+                    .show(ctx, |ui| {
+                        egui::Frame::none()
+                            .fill(egui::Color32::from_rgb(220, 53, 69)) // Red background
+                            .stroke(egui::Stroke::new(2.0, egui::Color32::from_rgb(176, 42, 55)))
+                            .rounding(egui::Rounding::same(8.0))
+                            .shadow(egui::epaint::Shadow::small_dark())
+                            .show(ui, |ui| {
+                                ui.set_width(toast_width);
+                                ui.set_height(toast_height);
+                                ui.horizontal(|ui| {
+                                    ui.add_space(12.0);
+                                    ui.label(egui::RichText::new("❌").size(20.0));
+                                    ui.add_space(8.0);
+                                    ui.vertical(|ui| {
+                                        ui.add_space(8.0);
+                                        ui.label(
+                                            egui::RichText::new("Error")
+                                                .color(egui::Color32::WHITE)
+                                                .size(14.0),
+                                        );
+                                        ui.label(
+                                            egui::RichText::new(error_msg)
+                                                .color(egui::Color32::from_rgb(255, 235, 235))
+                                                .size(12.0),
+                                        );
+                                    });
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.add_space(8.0);
+                                            if ui.button("✕").clicked() {
+                                                dismiss_clicked = true;
+                                            }
+                                        },
+                                    );
+                                });
+                            });
+                    });
+
+                if dismiss_clicked {
+                    self.error_message = None;
+                    self.error_timer = 0.0;
+                }
+            } else {
+                // Auto-dismiss after timeout
+                self.error_message = None;
+                self.error_timer = 0.0;
+            }
+        }
+    }
+}
+
+impl eframe::App for TimeWarpApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.player {
+            self.show_player(ctx);
+            return;
+        }
+        self.confirm_quit(ctx);
+        self.update_window_title(ctx);
+        self.track_session(ctx);
+        self.show_workspace(ctx);
+        #[cfg(feature = "workspaces")]
+        self.show_secondary_workspaces(ctx);
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    // A copy made by Export → Standalone runs its program and nothing else
+    if let Some(bundle) = standalone::find_bundle() {
+        return standalone::run_player(bundle);
+    }
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size([1200.0, 800.0])
+        .with_title("Time Warp IDE")
+        .with_drag_and_drop(true);
+    // Open where the last session's window was
+    if let Some(window) = session::saved_window(&storage::StoragePaths::resolve()) {
+        viewport = viewport
+            .with_inner_size(window.size)
+            .with_maximized(window.maximized);
+        if let Some(position) = window.position {
+            viewport = viewport.with_position(position);
+        }
+    }
+    let options = eframe::NativeOptions {
+        viewport,
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Time Warp IDE",
+        options,
+        Box::new(|cc| Box::new(TimeWarpApp::new(cc))),
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)] // set up state the way the UI would
+mod tests {
+    use super::*;
+    use std::fs;
+    use time_warp_core::basic::DEFAULT_TURTLE;
+
+    /// A path in the temp directory that test runs going at once don't share
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_file_operations() {
+        // Test New File functionality
+        let mut app = TimeWarpApp::default();
+        app.code = "some code".to_string();
+        app.output = "some output".to_string();
+        app.last_file_path = Some("test.txt".to_string());
+
+        // Simulate New File
+        app.code.clear();
+        // File operations no longer set output messages
+        app.last_file_path = None;
+
+        assert_eq!(app.code, "");
+        // Output should remain unchanged for file operations
+        assert_eq!(app.output, "some output");
+        assert_eq!(app.last_file_path, None);
+    }
+
+    #[test]
+    fn test_save_operations() {
+        let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT \"TEST\"".to_string();
+        app.last_file_path = Some("test_save.twb".to_string());
+        app.output = "previous output".to_string(); // Set some initial output
+
+        // Simulate Save
+        if let Some(path) = &app.last_file_path {
+            fs::write(path, &app.code).unwrap();
+            // File operations no longer set output messages
+        }
+
+        // Verify file was saved
+        let content = fs::read_to_string("test_save.twb").unwrap();
+        assert_eq!(content, "10 PRINT \"TEST\"");
+        // Output should remain unchanged
+        assert_eq!(app.output, "previous output");
+
+        // Cleanup
+        fs::remove_file("test_save.twb").unwrap();
+    }
+
+    #[test]
+    fn test_view_operations() {
+        let mut app = TimeWarpApp::default();
+
+        // Test Show Line Numbers toggle
+        assert!(!app.show_line_numbers);
+        app.show_line_numbers = !app.show_line_numbers;
+        assert!(app.show_line_numbers);
+        app.show_line_numbers = !app.show_line_numbers;
+        assert!(!app.show_line_numbers);
+    }
+
+    #[test]
+    fn test_edit_operations() {
+        let mut app = TimeWarpApp::default();
+        app.code = "old text".to_string();
+
+        // Test Find/Replace
+        assert!(!app.show_find_replace);
+        app.show_find_replace = true;
+        assert!(app.show_find_replace);
+
+        // Test Replace All
+        app.find_text = "old".to_string();
+        app.replace_text = "new".to_string();
+        app.replace_all_matches();
+        assert_eq!(app.code, "new text");
+    }
+
+    #[test]
+    fn test_regex_find_replace() {
+        use editor::Selection;
+        use search::SearchOptions;
+
+        let code = "10 PRINT X\n20 print XY\n30 REM X";
+        let literal = SearchOptions::default();
+        let word = SearchOptions {
+            whole_word: true,
+            case_sensitive: true,
+            ..literal
+        };
+        assert_eq!(search::find_all(code, "print", literal).unwrap().len(), 2);
+        assert_eq!(
+            search::find_all(code, "X", word).unwrap(),
+            vec![9..10, 30..31]
+        );
+        // Literal mode doesn't treat . as a pattern
+        assert!(search::find_all(code, "1.", literal).unwrap().is_empty());
+
+        // Capture groups go into the replacement in regex mode only
+        let regex = SearchOptions {
+            regex: true,
+            ..literal
+        };
+        let (swapped, count) =
+            search::replace_all(code, r"^(\d+) (\w+)", "$2 at $1", regex).unwrap();
+        assert_eq!(swapped, "PRINT at 10 X\nprint at 20 XY\nREM at 30 X");
+        assert_eq!(count, 3);
+        let (literal_dollar, _) = search::replace_all("a", "a", "$1", literal).unwrap();
+        assert_eq!(literal_dollar, "$1");
+        assert!(search::replace_all(code, "(", "", regex).is_err());
+
+        // Find Next selects matches in turn and wraps round
+        let mut app = TimeWarpApp::default();
+        app.code = code.to_string();
+        app.find_text = "x".to_string();
+        app.search.whole_word = true;
+        app.find_next();
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection {
+                anchor: 9,
+                head: 10
+            })
+        );
+        app.selection = Selection {
+            anchor: 9,
+            head: 10,
+        };
+        app.find_next();
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection {
+                anchor: 30,
+                head: 31
+            })
+        );
+        app.selection = Selection::caret(31);
+        app.find_next();
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection {
+                anchor: 9,
+                head: 10
+            })
+        );
+
+        // Replace changes the selected match only, then moves on
+        app.selection = Selection {
+            anchor: 9,
+            head: 10,
+        };
+        app.replace_text = "Y".to_string();
+        app.replace_next();
+        assert_eq!(app.code, "10 PRINT Y\n20 print XY\n30 REM X");
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection {
+                anchor: 30,
+                head: 31
+            })
+        );
+
+        // Find All lists each match with its line, even two on one line
+        let results = search::find_results("a = 1\nb = a + a", "a", word).unwrap();
+        assert_eq!(
+            results.iter().map(|r| r.line).collect::<Vec<_>>(),
+            vec![1, 2, 2]
+        );
+        assert_eq!(results[2].context, "b = a + a");
+        assert_eq!(results[2].matched, 8..9);
+        assert_eq!(
+            results[2].selection,
+            Selection {
+                anchor: 14,
+                head: 15
+            }
+        );
+
+        // A bad pattern is reported instead of changing anything
+        app.search.regex = true;
+        app.find_text = "[".to_string();
+        app.replace_all_matches();
+        assert!(app.search_error.is_some());
+        assert_eq!(app.code, "10 PRINT Y\n20 print XY\n30 REM X");
+    }
+
+    #[test]
+    fn test_find_highlighting() {
+        use editor::Selection;
+
+        // Matches get a background without changing the text or its colours
+        let palette = theme::Theme::Light.palette();
+        let mut job = egui::text::LayoutJob::simple(
+            "AB AB".to_string(),
+            egui::FontId::monospace(12.0),
+            palette.text,
+            f32::INFINITY,
+        );
+        highlight::mark_ranges(&mut job, &[0..2, 3..5], palette.find_match);
+        let parts: Vec<_> = job
+            .sections
+            .iter()
+            .map(|section| (section.byte_range.clone(), section.format.background))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (0..2, palette.find_match),
+                (2..3, egui::Color32::TRANSPARENT),
+                (3..5, palette.find_match),
+            ]
+        );
+
+        // F3 and Shift+F3 step through the matches, wrapping at either end
+        let mut app = TimeWarpApp::default();
+        app.code = "X = X + X".to_string();
+        app.find_text = "X".to_string();
+        app.run_action(keybindings::Action::FindNext);
+        assert!(app.show_find_replace);
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection { anchor: 0, head: 1 })
+        );
+        app.selection = Selection { anchor: 0, head: 1 };
+        app.run_action(keybindings::Action::FindPrevious);
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection { anchor: 8, head: 9 })
+        );
+        app.selection = Selection { anchor: 8, head: 9 };
+        app.run_action(keybindings::Action::FindPrevious);
+        assert_eq!(
+            app.pending_selection,
+            Some(Selection { anchor: 4, head: 5 })
+        );
+
+        // The counter says which match is selected
+        let matches = search::find_all(&app.code, "X", app.search).unwrap();
+        assert_eq!(
+            search::match_counter(&app.code, &matches, Selection { anchor: 4, head: 5 }),
+            "2 of 3"
+        );
+        assert_eq!(
+            search::match_counter(&app.code, &matches, Selection::caret(2)),
+            "3 match(es)"
+        );
+    }
+
+    #[test]
+    fn test_help_operations() {
+        let mut app = TimeWarpApp::default();
+
+        // Test About dialog
+        assert!(!app.show_about);
+        app.show_about = true;
+        assert!(app.show_about);
+        app.show_about = false;
+        assert!(!app.show_about);
+    }
+
+    #[test]
+    fn test_menu_state_changes() {
+        let mut app = TimeWarpApp::default();
+
+        // Test all menu state changes
+        assert!(!app.show_find_replace);
+        assert!(!app.show_about);
+        assert!(!app.show_line_numbers);
+
+        // Simulate menu clicks
+        app.show_find_replace = true;
+        app.show_about = true;
+        app.show_line_numbers = true;
+
+        assert!(app.show_find_replace);
+        assert!(app.show_about);
+        assert!(app.show_line_numbers);
+    }
+
+    #[test]
+    fn test_tab_switching() {
+        let mut app = TimeWarpApp::default();
+
+        // Test tab switching
+        assert_eq!(app.active_tab, 0);
+        app.active_tab = 1;
+        assert_eq!(app.active_tab, 1);
+        app.active_tab = 0;
+        assert_eq!(app.active_tab, 0);
+    }
+
+    #[test]
+    fn test_keyboard_shortcuts() {
+        let mut app = TimeWarpApp::default();
+        let ctx = egui::Context::default();
+
+        // Test Ctrl+N (New File)
+        app.code = "existing code".to_string();
+        app.last_file_path = Some("file.txt".to_string());
+
+        // Simulate Ctrl+N key press
+        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::N)) {
+            app.code.clear();
+            app.output = "New file created.".to_string();
+        }
+
+        // Since we can't simulate key presses in unit tests, test the logic directly
+        app.code.clear();
+        app.output = "New file created.".to_string();
+        app.last_file_path = None;
+
+        assert_eq!(app.code, "");
+        assert_eq!(app.output, "New file created.");
+        assert_eq!(app.last_file_path, None);
+    }
+
+    #[test]
+    fn test_basic_program_execution() {
+        let mut app = TimeWarpApp::default();
+
+        // Test simple BASIC program execution
+        let basic_code = "10 PRINT \"Hello from Time_Warp!\"\n20 PRINT \"Testing output console...\"\n30 PRINT \"Count: 1\"\n40 PRINT \"Count: 2\"\n50 PRINT \"Count: 3\"\n60 PRINT \"Test complete!\"";
+        let result = app.execute_tw_basic(basic_code);
+
+        // Debug: print the actual result
+        println!("Actual result: {:?}", result);
+
+        // Verify the output contains expected strings
+        assert!(result.contains("Hello from Time_Warp!"));
+        assert!(result.contains("Testing output console..."));
+        assert!(result.contains("Count: 1"));
+        assert!(result.contains("Count: 2"));
+        assert!(result.contains("Count: 3"));
+        assert!(result.contains("Test complete!"));
+    }
+
+    #[test]
+    fn test_enhanced_basic_commands() {
+        let mut app = TimeWarpApp::default();
+
+        // Test WRITELN command (Pascal-style with newline)
+        let writeln_code = "WRITELN \"Hello with newline\"";
+        let result = app.execute_tw_basic(writeln_code);
+        println!("WRITELN result: {:?}", result);
+        assert!(result.contains("Hello with newline"));
+
+        // Test turtle graphics commands
+        let turtle_code = "FORWARD 50\nRIGHT 90\nBACK 25";
+        let result = app.execute_tw_basic(turtle_code);
+        println!("Turtle commands result: {:?}", result);
+        assert!(result.contains("Moved forward 50"));
+        assert!(result.contains("Turned right 90"));
+        assert!(result.contains("Moved back 25"));
+    }
+
+    #[test]
+    fn test_input_statement_parsing() {
+        // Test that INPUT statements with semicolon separators parse correctly
+        let input_code = "10 INPUT \"Name? \"; NAME$\n20 PRINT \"Hello \"; NAME$";
+
+        // This should not panic or return a parse error
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic(input_code);
+
+        // The execution should start (even if it waits for input)
+        // We just want to make sure it doesn't fail with a parse error
+        println!("INPUT parsing result: {:?}", result);
+        // If we get here without panicking, the parsing worked
+    }
+
+    #[test]
+    fn test_input_statement_execution() {
+        // Test that INPUT statements properly set waiting_for_input state
+        let input_code = "10 INPUT \"Name? \"; NAME$";
+
+        let mut app = TimeWarpApp::default();
+        let _result = app.execute_tw_basic(input_code);
+
+        // After executing an INPUT statement, the app should be waiting for input
+        assert!(
+            app.waiting_for_input,
+            "App should be waiting for input after INPUT statement"
+        );
+        assert_eq!(
+            app.input_prompt, "Name? ",
+            "Input prompt should be set correctly"
+        );
+        assert_eq!(
+            app.current_input_var, "NAME$",
+            "Current input variable should be set correctly"
+        );
+    }
+
+    #[test]
+    fn test_tab_function() {
+        let mut app = TimeWarpApp::default();
+
+        // Test TAB function in PRINT statements
+        let tab_code = "PRINT \"Hello\"; TAB(10); \"World\"";
+        let result = app.execute_tw_basic(tab_code);
+
+        println!("TAB result: {:?}", result);
+
+        // Verify TAB function produces spaces for positioning
+        assert!(result.contains("Hello"));
+        assert!(result.contains("World"));
+    }
+
+    #[test]
+    fn test_print_variable() {
+        let mut app = TimeWarpApp::default();
+
+        // Test PRINT with a variable
+        let print_code = "LET X = 42\nPRINT X";
+        let result = app.execute_tw_basic(print_code);
+
+        println!("PRINT variable result: {:?}", result);
+
+        // Should contain the variable value
+        assert!(result.contains("42"));
+    }
+
+    #[test]
+    fn test_print_variable_simple() {
+        let mut app = TimeWarpApp::default();
+
+        // Test PRINT with a variable (simple case)
+        let print_code = "PRINT X";
+        let result = app.execute_tw_basic(print_code);
+
+        println!("PRINT variable simple result: {:?}", result);
+
+        // Should not crash with parse error
+        assert!(!result.contains("Error: E1"));
+    }
+
+    #[test]
+    fn test_print_with_line_number() {
+        let mut app = TimeWarpApp::default();
+
+        // Test PRINT with line number (like user might enter)
+        let print_code = "10 PRINT X";
+        let result = app.execute_tw_basic(print_code);
+
+        println!("PRINT with line number result: {:?}", result);
+
+        // Should not crash with parse error
+        assert!(!result.contains("Error: E1"));
+        // Should contain the variable value
+        assert!(result.contains("0"));
+    }
+
+    #[test]
+    fn test_print_no_space() {
+        let mut app = TimeWarpApp::default();
+
+        // Test PRINTX (no space) - this should cause a parse error
+        let print_code = "PRINTX";
+        let result = app.execute_tw_basic(print_code);
+
+        println!("PRINT no space result: {:?}", result);
+
+        // This should contain a parse error
+        assert!(result.contains("Error: E1"));
+    }
 
-                            if ui
-                                .add(
-                                    egui::Button::new("🖥️ Output & Graphics")
-                                        .fill(if self.active_tab == 1 {
-                                            ui.style().visuals.selection.bg_fill
-                                        } else {
-                                            egui::Color32::TRANSPARENT
-                                        })
-                                        .stroke(if self.active_tab == 1 {
-                                            egui::Stroke::new(
-                                                2.0,
-                                                ui.style().visuals.selection.stroke.color,
-                                            )
-                                        } else {
-                                            egui::Stroke::NONE
-                                        })
-                                        .rounding(egui::Rounding::same(4.0))
-                                        .min_size(egui::vec2(140.0, tab_height)),
-                                )
-                                .clicked()
-                            {
-                                self.active_tab = 1;
-                            }
+    #[test]
+    fn test_print_lowercase() {
+        let mut app = TimeWarpApp::default();
 
-                            if ui
-                                .add(
-                                    egui::Button::new("🐛 Debug")
-                                        .fill(if self.active_tab == 2 {
-                                            ui.style().visuals.selection.bg_fill
-                                        } else {
-                                            egui::Color32::TRANSPARENT
-                                        })
-                                        .stroke(if self.active_tab == 2 {
-                                            egui::Stroke::new(
-                                                2.0,
-                                                ui.style().visuals.selection.stroke.color,
-                                            )
-                                        } else {
-                                            egui::Stroke::NONE
-                                        })
-                                        .rounding(egui::Rounding::same(4.0))
-                                        .min_size(egui::vec2(100.0, tab_height)),
-                                )
-                                .clicked()
-                            {
-                                self.active_tab = 2;
-                            }
+        // Test print x (lowercase) - should work since tokenizer uppercases
+        let print_code = "print x";
+        let result = app.execute_tw_basic(print_code);
 
-                            ui.with_layout(
-                                egui::Layout::right_to_left(egui::Align::Center),
-                                |ui| {
-                                    ui.add_space(8.0);
-                                },
-                            );
-                        });
-                    });
+        println!("PRINT lowercase result: {:?}", result);
 
-                ui.add_space(8.0);
+        // Should not crash with parse error
+        assert!(!result.contains("Error: E1"));
+        // Should contain the variable value
+        assert!(result.contains("0"));
+    }
 
-                // Main content area with better styling
-                egui::Frame::none()
-                    .fill(ui.style().visuals.panel_fill)
-                    .stroke(egui::Stroke::new(
-                        1.0,
-                        ui.style().visuals.window_stroke.color,
-                    ))
-                    .rounding(egui::Rounding::same(8.0))
-                    .inner_margin(egui::Margin::same(12.0))
-                    .show(ui, |ui| {
-                        match self.active_tab {
-                            0 => {
-                                // Code Editor Tab
-                                ui.vertical(|ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.checkbox(&mut self.show_line_numbers, "Line numbers");
-                                        ui.checkbox(&mut self.debug_mode, "Debug mode");
-                                        ui.separator();
-                                        if ui.button("🔍 Find/Replace").clicked() {
-                                            self.show_find_replace = !self.show_find_replace;
-                                        }
-                                    });
+    #[test]
+    fn test_let_and_print() {
+        let mut app = TimeWarpApp::default();
 
-                                    if self.show_find_replace {
-                                        ui.horizontal(|ui| {
-                                            ui.label("Find:");
-                                            ui.text_edit_singleline(&mut self.find_text);
-                                            ui.label("Replace:");
-                                            ui.text_edit_singleline(&mut self.replace_text);
-                                            if ui.button("Replace All").clicked() {
-                                                self.code = self
-                                                    .code
-                                                    .replace(&self.find_text, &self.replace_text);
-                                            }
-                                        });
-                                        ui.separator();
-                                    }
+        // Test LET X = 5 : PRINT X
+        let code = "LET X = 5 : PRINT X";
+        let result = app.execute_tw_basic(code);
 
-                                    egui::ScrollArea::vertical().show(ui, |ui| {
-                                        if self.show_line_numbers && self.debug_mode {
-                                            // Custom editor with line numbers and breakpoints
-                                            self.render_debug_editor(ui);
-                                        } else {
-                                            // Handle completion input before creating TextEdit to avoid borrowing conflicts
-                                            let input = ui.input(|i| i.clone());
-                                            let should_trigger_completion = input.modifiers.ctrl && input.key_pressed(egui::Key::Space);
-                                            let should_hide_completion = input.key_pressed(egui::Key::Escape);
-                                            let should_select_down = self.show_completion && input.key_pressed(egui::Key::ArrowDown);
-                                            let should_select_up = self.show_completion && input.key_pressed(egui::Key::ArrowUp);
-                                            let should_insert_completion = self.show_completion && input.key_pressed(egui::Key::Enter);
-
-                                            // Calculate all needed data before any mutable borrows
-                                            let (current_word, selected_item, insert_start, insert_end) = {
-                                                let cursor_pos = self.code.len();
-                                                let before_cursor = &self.code[..cursor_pos];
-                                                let words: Vec<&str> = before_cursor.split_whitespace().collect();
-                                                let current_word = words.last().copied().unwrap_or("");
-
-                                                let (selected_item, insert_start, insert_end) = if should_insert_completion {
-                                                    if let Some(selected) = self.completion_items.get(self.completion_selected) {
-                                                        let start_pos = cursor_pos - current_word.len();
-                                                        (Some(selected.clone()), start_pos, cursor_pos)
-                                                    } else {
-                                                        (None, 0, 0)
-                                                    }
-                                                } else {
-                                                    (None, 0, 0)
-                                                };
-
-                                                (current_word, selected_item, insert_start, insert_end)
-                                            };
-
-                                            // Now do all mutable operations
-                                            if should_trigger_completion {
-                                                // self.update_completion(current_word);
-                                                self.completion_query = current_word.to_string();
-                                                self.completion_items = self.get_completion_suggestions(&current_word);
-                                                self.completion_selected = 0;
-                                                self.show_completion = !self.completion_items.is_empty();
-                                            } else if should_hide_completion {
-                                                self.show_completion = false;
-                                            } else if should_select_down {
-                                                if self.completion_selected < self.completion_items.len().saturating_sub(1) {
-                                                    self.completion_selected += 1;
-                                                }
-                                            } else if should_select_up {
-                                                if self.completion_selected > 0 {
-                                                    self.completion_selected = self.completion_selected.saturating_sub(1);
-                                                }
-                                            } else if let Some(selected) = selected_item {
-                                                self.code.replace_range(insert_start..insert_end, &selected);
-                                                self.show_completion = false;
-                                            }
+        println!("LET and PRINT result: {:?}", result);
 
-                                            // Syntax-highlighted code editor
-                                            if self.syntax_highlighting_enabled {
-                                                self.render_syntax_highlighted_editor(ui);
-                                            } else {
-                                                ui.add(
-                                                    egui::TextEdit::multiline(&mut self.code)
-                                                        .font(egui::TextStyle::Monospace)
-                                                        .desired_width(f32::INFINITY)
-                                                        .desired_rows(20)
-                                                );
-                                            }
+        // Should not crash with parse error
+        assert!(!result.contains("Error: E1"));
+        // Should contain 5
+        assert!(result.contains("5"));
+    }
 
-                                            // Update line count (cursor position tracking needs different approach in egui)
-                                            self.total_lines = self.code.lines().count().max(1);
+    #[test]
+    fn test_print_multiple_vars_no_comma() {
+        let mut app = TimeWarpApp::default();
 
-                                            // Show completion popup
-                                            if self.show_completion && !self.completion_items.is_empty() {
-                                                egui::Window::new("Code Completion")
-                                                    .collapsible(false)
-                                                    .resizable(false)
-                                                    .show(ui.ctx(), |ui| {
-                                                        egui::ScrollArea::vertical().show(ui, |ui| {
-                                                            for (i, item) in self.completion_items.iter().enumerate() {
-                                                                let mut button = egui::Button::new(item);
-                                                                if i == self.completion_selected {
-                                                                    button = button.fill(egui::Color32::from_rgb(100, 150, 200));
-                                                                }
-                                                                if ui.add(button).clicked() {
-                                                                    let cursor_pos = self.code.len();
-                                                                    let before_cursor = &self.code[..cursor_pos];
-                                                                    let words: Vec<&str> = before_cursor.split_whitespace().collect();
-                                                                    let current_word = words.last().copied().unwrap_or("");
-                                                                    let start_pos = cursor_pos - current_word.len();
-                                                                    self.code.replace_range(start_pos..cursor_pos, item);
-                                                                    self.show_completion = false;
-                                                                }
-                                                            }
-                                                        });
-                                                    });
-                                            }
-                                        }
-                                    });
-                                });
-                            }
-                            1 => {
-                                // Output & Graphics Tab
-                                ui.vertical(|ui| {
-                                    ui.label("Output:");
+        // Test PRINT X Y (without comma) - should cause parse error
+        let print_code = "PRINT X Y";
+        let result = app.execute_tw_basic(print_code);
 
-                                    // Input prompt - show prominently at the top when needed
-                                    if self.waiting_for_input {
-                                        ui.separator();
-                                        ui.label("📝 Program Input Required");
-                                        ui.horizontal(|ui| {
-                                            ui.label(&self.input_prompt);
-                                            let response = ui.text_edit_singleline(&mut self.user_input);
-                                            if ui.button("🚀 Submit").clicked()
-                                                || (response.lost_focus()
-                                                    && ui.input(|i| i.key_pressed(egui::Key::Enter)))
-                                            {
-                                                // Store the input in the variable
-                                                self.variables
-                                                    .insert(self.current_input_var.clone(), self.user_input.clone());
-
-                                                // Provide input to the BASIC interpreter and continue execution
-                                                if let Some(ref mut interpreter) = self.basic_interpreter {
-                                                    interpreter.provide_input(&self.user_input);
-
-                                                    // Continue execution with the interpreter
-                                                    match interpreter.execute("") {
-                                                        // Empty string since interpreter has state
-                                                        Ok(result) => match result {
-                                                            crate::languages::basic::ExecutionResult::Complete {
-                                                                output,
-                                                                graphics_commands,
-                                                            } => {
-                                                                self.process_graphics_commands(&graphics_commands);
-                                                                self.output = output;
-                                                                self.basic_interpreter = None;
-                                                            }
-                                                            crate::languages::basic::ExecutionResult::NeedInput {
-                                                                variable,
-                                                                prompt,
-                                                                partial_output,
-                                                                partial_graphics,
-                                                            } => {
-                                                                self.process_graphics_commands(&partial_graphics);
-                                                                self.input_prompt = prompt.clone();
-                                                                self.current_input_var = variable;
-                                                                self.output = format!(
-                                                                    "{}{}{}",
-                                                                    self.output, partial_output, prompt
-                                                                );
-                                                                // Keep waiting for more input
-                                                            }
-                                                            crate::languages::basic::ExecutionResult::Error(err) => {
-                                                                self.output =
-                                                                    format!("{}Error: {:?}", self.output, err);
-                                                                self.basic_interpreter = None;
-                                                            }
-                                                        },
-                                                        Err(err) => {
-                                                            self.output = format!("{}Error: {:?}", self.output, err);
-                                                            self.basic_interpreter = None;
-                                                        }
-                                                    }
-                                                }
+        println!("PRINT multiple vars no comma result: {:?}", result);
 
-                                                // Continue execution
-                                                self.waiting_for_input = false;
-                                                self.user_input.clear();
-                                                self.input_prompt.clear();
-                                                self.current_input_var.clear();
-                                            }
-                                            if ui.button("❌ Cancel").clicked() {
-                                                self.output = format!("{}Input cancelled.", self.output);
-                                                self.waiting_for_input = false;
-                                                self.user_input.clear();
-                                                self.input_prompt.clear();
-                                                self.current_input_var.clear();
-                                                self.basic_interpreter = None;
-                                            }
-                                        });
-                                        ui.separator();
-                                    }
+        // This should cause a parse error
+        assert!(result.contains("Error: E1"));
+    }
+
+    #[test]
+    fn test_print_x_and_printx() {
+        let mut app = TimeWarpApp::default();
+
+        // Test PRINT X : PRINTX (what user entered)
+        let code = "PRINT X\nPRINTX";
+        let result = app.execute_tw_basic(code);
+
+        println!("PRINT X and PRINTX result: {:?}", result);
+
+        // Should have parse error for PRINTX with no expression
+        assert!(result.contains("Error: E1"));
+        assert!(result.contains("Unexpected token in expression"));
+    }
+
+    #[test]
+    fn test_letx_equals_five() {
+        let mut app = TimeWarpApp::default();
+
+        // Test LETX=5 (variable named LETX)
+        let code = "LETX=5\nPRINT LETX";
+        let result = app.execute_tw_basic(code);
+
+        println!("LETX=5 result: {:?}", result);
+
+        // Should work - LETX is a valid variable name
+        assert!(result.contains("5"));
+    }
+
+    #[test]
+    fn test_input_and_print() {
+        let mut app = TimeWarpApp::default();
+
+        // Test INPUT X : PRINT X
+        let code = "INPUT X\nPRINT X";
+        let result = app.execute_tw_basic(code);
+
+        println!("INPUT and PRINT result: {:?}", result);
+        println!("Waiting for input: {}", app.waiting_for_input);
+
+        // Should be waiting for input
+        assert!(app.waiting_for_input);
 
-                                    egui::ScrollArea::vertical()
-                                        .max_height(200.0)
-                                        .show(ui, |ui| {
-                                            ui.add(
-                                                egui::TextEdit::multiline(&mut self.output)
-                                                    .font(egui::TextStyle::Monospace)
-                                                    .desired_width(f32::INFINITY),
-                                            );
-                                        });
+        // Simulate providing input
+        if let Some(ref mut interpreter) = app.basic_interpreter {
+            let continue_result = interpreter.provide_input("42").unwrap();
+            match continue_result {
+                time_warp_core::basic::ExecutionResult::Complete { output, .. } => {
+                    app.output = output;
+                }
+                _ => panic!("Expected Complete"),
+            }
+        }
 
-                                    // Turtle Graphics section
+        println!("Final output: {:?}", app.output);
+        // Should contain the input echo and the PRINT output
+        assert!(app.output.contains("42"));
+        assert!(app.output == "42\n42\n");
+    }
 
-                                    ui.separator();
-                                    ui.label("Turtle Graphics:");
-                                    ui.horizontal(|ui| {
-                                        ui.label("Zoom:");
-                                        ui.add(
-                                            egui::DragValue::new(&mut self.turtle_zoom)
-                                                .clamp_range(0.1..=5.0)
-                                                .speed(0.1),
-                                        );
-                                        if ui.button("🔍 Reset View").clicked() {
-                                            self.turtle_zoom = 1.0;
-                                            self.turtle_pan = egui::vec2(0.0, 0.0);
-                                        }
-                                    });
-                                    ui.add_space(4.0);
+    #[test]
+    fn test_print_semicolon() {
+        let mut app = TimeWarpApp::default();
 
-                                    // Simple canvas for turtle graphics
-                                    let canvas_size = egui::vec2(400.0, 300.0);
-                                    let (rect, response) =
-                                        ui.allocate_exact_size(canvas_size, egui::Sense::drag());
+        // Test PRINT X; (should not add newline)
+        let code = "PRINT 42;";
+        let result = app.execute_tw_basic(code);
 
-                                    // Handle pan
-                                    if response.dragged() {
-                                        self.turtle_pan += response.drag_delta() / self.turtle_zoom;
-                                    }
+        println!("PRINT with semicolon result: {:?}", result);
 
-                                    ui.painter().rect_filled(rect, 0.0, egui::Color32::WHITE);
-                                    ui.painter().rect_stroke(
-                                        rect,
-                                        0.0,
-                                        egui::Stroke::new(1.0, egui::Color32::BLACK),
-                                    );
+        // Should not end with newline
+        assert!(!result.ends_with("\n"));
+        assert!(result == "42");
+    }
 
-                                    // Draw turtle lines with zoom and pan
-                                    for command in &self.turtle_commands {
-                                        if command.starts_with("LINE ") {
-                                            let parts: Vec<&str> =
-                                                command.split_whitespace().collect();
-                                            if parts.len() >= 5 {
-                                                if let (Ok(x1), Ok(y1), Ok(x2), Ok(y2)) = (
-                                                    parts[1].parse::<f32>(),
-                                                    parts[2].parse::<f32>(),
-                                                    parts[3].parse::<f32>(),
-                                                    parts[4].parse::<f32>(),
-                                                ) {
-                                                    let center = rect.center();
-                                                    let start = egui::pos2(
-                                                        center.x
-                                                            + (x1 + self.turtle_pan.x)
-                                                                * self.turtle_zoom,
-                                                        center.y
-                                                            + (y1 + self.turtle_pan.y)
-                                                                * self.turtle_zoom,
-                                                    );
-                                                    let end = egui::pos2(
-                                                        center.x
-                                                            + (x2 + self.turtle_pan.x)
-                                                                * self.turtle_zoom,
-                                                        center.y
-                                                            + (y2 + self.turtle_pan.y)
-                                                                * self.turtle_zoom,
-                                                    );
-                                                    ui.painter().line_segment(
-                                                        [start, end],
-                                                        egui::Stroke::new(
-                                                            2.0,
-                                                            egui::Color32::BLACK,
-                                                        ),
-                                                    );
-                                                }
-                                            }
-                                        }
-                                    }
+    #[test]
+    fn test_print_gw_basic_features() {
+        let mut app = TimeWarpApp::default();
 
-                                    // Draw turtle
-                                    let center = rect.center();
-                                    let turtle_x = center.x
-                                        + (self.turtle_state.x + self.turtle_pan.x)
-                                            * self.turtle_zoom;
-                                    let turtle_y = center.y
-                                        + (self.turtle_state.y + self.turtle_pan.y)
-                                            * self.turtle_zoom;
-
-                                    // Draw a simple triangle for the turtle
-                                    let size = 8.0 * self.turtle_zoom;
-                                    let angle_rad = self.turtle_state.angle.to_radians();
-                                    let points = [
-                                        egui::pos2(
-                                            turtle_x + size * angle_rad.cos(),
-                                            turtle_y + size * angle_rad.sin(),
-                                        ),
-                                        egui::pos2(
-                                            turtle_x + size * (angle_rad + 2.0944).cos(),
-                                            turtle_y + size * (angle_rad + 2.0944).sin(),
-                                        ),
-                                        egui::pos2(
-                                            turtle_x + size * (angle_rad - 2.0944).cos(),
-                                            turtle_y + size * (angle_rad - 2.0944).sin(),
-                                        ),
-                                    ];
-
-                                    ui.painter().add(egui::Shape::convex_polygon(
-                                        points.to_vec(),
-                                        self.turtle_state.color,
-                                        egui::Stroke::new(1.0, egui::Color32::BLACK),
-                                    ));
-                                });
-                            }
-                            2 => {
-                                // Debug Tab
-                                ui.vertical(|ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.checkbox(&mut self.debug_mode, "Enable Debug Mode");
-                                        ui.separator();
-                                        ui.label("Debug State:");
-                                        match self.debug_state {
-                                            DebugState::Stopped => ui.colored_label(egui::Color32::GRAY, "⏹️ Stopped"),
-                                            DebugState::Running => ui.colored_label(egui::Color32::GREEN, "▶️ Running"),
-                                            DebugState::Paused => ui.colored_label(egui::Color32::YELLOW, "⏸️ Paused"),
-                                        }
-                                    });
+        // Test comma tabulation (GW-BASIC style - every 14 characters)
+        let comma_code = "PRINT \"A\",\"B\",\"C\"";
+        let result1 = app.execute_tw_basic(comma_code);
+        println!("PRINT comma tabulation result: {:?}", result1);
+        // "A" should be followed by spaces to reach column 14, then "B" at column 15, etc.
 
-                                    ui.separator();
+        // Test TAB function
+        let tab_code = "PRINT \"HELLO\";TAB(15);\"WORLD\"";
+        let result2 = app.execute_tw_basic(tab_code);
+        println!("PRINT TAB function result: {:?}", result2);
+        // Should have "HELLO" followed by spaces to column 15, then "WORLD"
 
-                                    // Debug Controls
-                                    ui.horizontal(|ui| {
-                                        if ui.button("▶️ Start Debug").on_hover_text("Start debugging session (Ctrl+F5)").clicked() && self.debug_mode {
-                                            self.start_debug_session();
-                                        }
-                                        if ui.button("⏯️ Continue").on_hover_text("Continue execution from paused state").clicked() && self.debug_mode && self.debug_state == DebugState::Paused {
-                                            self.debug_state = DebugState::Running;
-                                        }
-                                        if ui.button("⏸️ Pause").on_hover_text("Pause execution (F11)").clicked() && self.debug_mode && self.debug_state == DebugState::Running {
-                                            self.debug_state = DebugState::Paused;
-                                        }
-                                        if ui.button("⏹️ Stop").on_hover_text("Stop debugging session").clicked() && self.debug_mode {
-                                            self.stop_debug_session();
-                                        }
-                                        if ui.button("⏭️ Step").on_hover_text("Step to next line (F10)").clicked() && self.debug_mode && self.debug_state == DebugState::Paused {
-                                            self.step_debug();
-                                        }
-                                        if ui.button("🔄 Reset").on_hover_text("Restart debug session").clicked() && self.debug_mode {
-                                            self.start_debug_session(); // Restart debug session
-                                        }
-                                    });
+        // Test SPC function
+        let spc_code = "PRINT \"TEST\";SPC(3);\"SPACES\"";
+        let result3 = app.execute_tw_basic(spc_code);
+        println!("PRINT SPC function result: {:?}", result3);
+        // Should have "TEST" followed by 3 spaces, then "SPACES"
 
-                                    ui.separator();
+        // Verify all contain expected content
+        assert!(result1.contains("A"));
+        assert!(result1.contains("B"));
+        assert!(result1.contains("C"));
+        assert!(result2.contains("HELLO"));
+        assert!(result2.contains("WORLD"));
+        assert!(result3.contains("TEST"));
+        assert!(result3.contains("SPACES"));
+    }
 
-                                    // Breakpoints
-                                    ui.collapsing("Breakpoints", |ui| {
-                                        ui.label("Click on line numbers in the editor to toggle breakpoints");
-                                        let filename = self.last_file_path.as_ref()
-                                            .and_then(|p| std::path::Path::new(p).file_name())
-                                            .and_then(|n| n.to_str())
-                                            .unwrap_or("untitled");
+    #[test]
+    fn test_def_fn_functions() {
+        let mut app = TimeWarpApp::default();
 
-                                        if let Some(breakpoints) = self.breakpoints.get(filename) {
-                                            ui.label(format!("Breakpoints in {}: {:?}", filename, breakpoints));
-                                        } else {
-                                            ui.label(format!("No breakpoints in {}", filename));
-                                        }
+        // Test DEF FN and calling user-defined functions
+        let def_code = "DEF FN SQUARE(X) = X * X\nPRINT FN SQUARE(5)";
+        let result = app.execute_tw_basic(def_code);
+        println!("DEF FN result: {:?}", result);
 
-                                        if ui.button("Clear All Breakpoints").clicked() {
-                                            self.breakpoints.clear();
-                                        }
-                                    });
+        // Should contain 25 (5 squared)
+        assert!(result.contains("25"));
+    }
 
-                                    // Variables
-                                    ui.collapsing("Variables", |ui| {
-                                        ui.label("📊 Debug Variables:");
-                                        if self.debug_variables.is_empty() {
-                                            ui.label("  No debug variables");
-                                        } else {
-                                            for (name, value) in &self.debug_variables {
-                                                ui.label(format!("  {} = \"{}\"", name, value));
-                                            }
-                                        }
+    #[test]
+    fn test_clear_command() {
+        let mut app = TimeWarpApp::default();
 
-                                        ui.separator();
-                                        ui.label("🔢 Program Variables:");
-                                        if self.variables.is_empty() {
-                                            ui.label("  No program variables");
-                                        } else {
-                                            for (name, value) in &self.variables {
-                                                ui.label(format!("  {} = \"{}\"", name, value));
-                                            }
-                                        }
-                                    });
+        // Set up some variables and functions
+        let setup_code = "LET X = 42\nDEF FN TEST(Y) = Y + 1\nDIM A(10)";
+        app.execute_tw_basic(setup_code);
 
-                                    // Call Stack
-                                    ui.collapsing("Call Stack", |ui| {
-                                        if self.debug_call_stack.is_empty() {
-                                            ui.label("Call stack is empty");
-                                        } else {
-                                            for (i, frame) in self.debug_call_stack.iter().enumerate() {
-                                                ui.label(format!("{}: {}", i, frame));
-                                            }
-                                        }
-                                    });
+        // Clear everything
+        let clear_code = "CLEAR";
+        let result = app.execute_tw_basic(clear_code);
+        println!("CLEAR result: {:?}", result);
 
-                                    // Current Line
-                                    if let Some(line) = self.current_debug_line {
-                                        ui.separator();
-                                        ui.label(format!("Current Debug Line: {}", line));
-                                    }
-                                });
-                            }
-                            _ => {}
-                        }
-                    });
-            });
-        });
+        // Should contain confirmation message
+        assert!(result.contains("cleared"));
+    }
 
-        // General prompt handling - shown prominently when active
-        if self.general_prompt_active {
-            let mut open = true;
-            egui::Window::new("💬 Input Required")
-                .open(&mut open)
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.vertical_centered(|ui| {
-                        ui.add_space(20.0);
-                        ui.label(&self.general_prompt_message);
-                        ui.add_space(10.0);
-                        ui.horizontal(|ui| {
-                            ui.label("Input:");
-                            let response = ui.text_edit_singleline(&mut self.general_prompt_input);
-                            if ui.button("🚀 Submit").clicked()
-                                || (response.lost_focus()
-                                    && ui.input(|i| i.key_pressed(egui::Key::Enter)))
-                            {
-                                // Call the callback with the input
-                                if let Some(callback) = self.general_prompt_callback.take() {
-                                    callback(self.general_prompt_input.clone());
-                                }
+    #[test]
+    fn test_for_loop_simple() {
+        let mut app = TimeWarpApp::default();
 
-                                // Reset prompt state
-                                self.general_prompt_active = false;
-                                self.general_prompt_message.clear();
-                                self.general_prompt_input.clear();
-                            }
-                            if ui.button("❌ Cancel").clicked() {
-                                // Reset prompt state without calling callback
-                                self.general_prompt_active = false;
-                                self.general_prompt_message.clear();
-                                self.general_prompt_input.clear();
-                                self.general_prompt_callback = None;
-                            }
-                        });
-                    });
-                });
+        // Test just FOR loop
+        let code = "for i=1 to 3\nprint i\nnext";
+        let result = app.execute_tw_basic(code);
 
-            // If window was closed (user clicked X), treat as cancel
-            if !open {
-                self.general_prompt_active = false;
-                self.general_prompt_message.clear();
-                self.general_prompt_input.clear();
-                self.general_prompt_callback = None;
-            }
-        }
+        // Should work and produce 1\n2\n3\n
+        assert!(result == "1\n2\n3\n");
+        assert!(!result.contains("timeout"));
+    }
 
-        // Status Bar
-        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
-            ui.add_space(2.0);
-            egui::Frame::none()
-                .fill(ui.style().visuals.window_fill())
-                .stroke(ui.style().visuals.window_stroke())
-                .show(ui, |ui| {
-                    ui.horizontal(|ui| {
-                        ui.add_space(8.0);
+    #[test]
+    fn test_for_loop_program() {
+        let mut app = TimeWarpApp::default();
+
+        // Test the user's program
+        let code = "10 cls\n20 print \"Hello\"\n30 for i=1 to 10\n40 print 1/i\n50 next\n60 end";
+        let result = app.execute_tw_basic(code);
+
+        // Should work and contain Hello and the divisions
+        assert!(result.contains("Hello"));
+        assert!(result.contains("0.1"));
+        assert!(!result.contains("timeout"));
+    }
 
-                        // File and cursor information
-                        let line_count = self.code.lines().count();
-                        let char_count = self.code.chars().count();
-                        ui.label(format!(
-                            "📏 Lines: {} | Chars: {} | Ln {}, Col {}",
-                            line_count, char_count, self.cursor_line, self.cursor_column
-                        ));
+    #[test]
+    fn test_forward_in_line_numbered_program() {
+        let mut app = TimeWarpApp::default();
 
-                        ui.separator();
+        // Test FORWARD in a line-numbered BASIC program
+        let code = "10 FORWARD 5\n20 END";
+        let result = app.execute_tw_basic(code);
+        println!("FORWARD test result: {:?}", result);
+        println!("Turtle commands after FORWARD: {:?}", app.turtle_commands);
+        println!(
+            "Turtle state: x={}, y={}, angle={}",
+            app.turtles[DEFAULT_TURTLE].x,
+            app.turtles[DEFAULT_TURTLE].y,
+            app.turtles[DEFAULT_TURTLE].angle
+        );
+        assert!(result.contains("Moved forward"));
+        assert!(!app.turtle_commands.is_empty());
+        // Should have moved 5 units from (0, 0) to (5, 0)
+        assert_eq!(app.turtles[DEFAULT_TURTLE].x, 5.0);
+        assert_eq!(app.turtles[DEFAULT_TURTLE].y, 0.0);
+    }
 
-                        // Language and encoding
-                        ui.label("🏷️ TW BASIC");
+    #[test]
+    fn test_forward_direct_command() {
+        let mut app = TimeWarpApp::default();
 
-                        ui.separator();
+        // Test FORWARD as a direct command (not line-numbered) with longer distance
+        let code = "FORWARD 50";
+        let result = app.execute_tw_basic(code);
+        println!("Direct FORWARD test result: {:?}", result);
+        println!(
+            "Turtle commands after direct FORWARD: {:?}",
+            app.turtle_commands
+        );
+        println!(
+            "Turtle state: x={}, y={}, angle={}",
+            app.turtles[DEFAULT_TURTLE].x,
+            app.turtles[DEFAULT_TURTLE].y,
+            app.turtles[DEFAULT_TURTLE].angle
+        );
+        assert!(result.contains("Moved forward"));
+        assert!(!app.turtle_commands.is_empty());
+        // Should have moved 50 units from (0, 0) to (50, 0)
+        assert_eq!(app.turtles[DEFAULT_TURTLE].x, 50.0);
+        assert_eq!(app.turtles[DEFAULT_TURTLE].y, 0.0);
+    }
 
-                        // Execution status
-                        if self.is_executing {
-                            ui.colored_label(egui::Color32::GREEN, "▶️ Running");
-                        } else if self.waiting_for_input {
-                            ui.colored_label(egui::Color32::YELLOW, "⏸️ Waiting for Input");
-                        } else if self.general_prompt_active {
-                            ui.colored_label(egui::Color32::BLUE, "💬 Awaiting Response");
-                        } else {
-                            ui.colored_label(egui::Color32::GRAY, "⏹️ Ready");
-                        }
+    // ===== GW BASIC COMMAND TESTS =====
 
-                        ui.separator();
+    #[test]
+    fn test_type_declaration_commands() {
+        println!("\n=== TESTING TYPE DECLARATION COMMANDS ===");
+        let mut app = TimeWarpApp::default();
 
-                        // Timeout setting
-                        ui.label(format!("⏰ Timeout: {}ms", self.execution_timeout_ms));
+        // Test DEFINT with range
+        let program = "10 DEFINT A-Z\n20 A = 3.14\n30 B = 5.9\n40 PRINT A, B";
+        let result = app.execute_tw_basic(program);
+        println!("DEFINT test output: {}", result);
+        assert!(
+            result.contains("3") && result.contains("5"),
+            "DEFINT should truncate decimals to integers"
+        );
 
-                        ui.separator();
+        // Test DEFSTR
+        let program = "10 DEFSTR S\n20 S = 123\n30 PRINT S";
+        let result = app.execute_tw_basic(program);
+        println!("DEFSTR test output: {}", result);
+        assert!(
+            result.contains("123"),
+            "DEFSTR should convert numbers to strings"
+        );
 
-                        // Debug mode status
-                        if self.debug_mode {
-                            match self.debug_state {
-                                DebugState::Running => {
-                                    ui.colored_label(egui::Color32::GREEN, "🐛 Debug: Running");
-                                }
-                                DebugState::Paused => {
-                                    ui.colored_label(egui::Color32::YELLOW, "🐛 Debug: Paused");
-                                }
-                                DebugState::Stopped => {
-                                    ui.colored_label(egui::Color32::RED, "🐛 Debug: Stopped");
-                                }
-                            }
-                        } else {
-                            ui.colored_label(egui::Color32::GRAY, "🐛 Debug: Off (F9 to toggle)");
-                        }
+        // Test DEFSNG (default behavior)
+        let program = "10 DEFSNG X\n20 X = 3.14159\n30 PRINT X";
+        let result = app.execute_tw_basic(program);
+        println!("DEFSNG test output: {}", result);
+        assert!(
+            result.contains("3.14159"),
+            "DEFSNG should preserve floating point precision"
+        );
 
-                        ui.separator();
+        // Test CLEAR resets type defaults
+        let program = "10 DEFINT A-Z\n20 CLEAR\n30 A = 3.14\n40 PRINT A";
+        let result = app.execute_tw_basic(program);
+        println!("CLEAR type defaults test output: {}", result);
+        assert!(
+            result.contains("3.14"),
+            "CLEAR should reset type defaults to single precision"
+        );
 
-                        // View options status
-                        if self.show_line_numbers {
-                            ui.label("📏 Line Numbers: ON");
-                        }
-                        if self.syntax_highlighting_enabled {
-                            ui.label("🎨 Syntax Highlighting: ON");
-                        }
+        println!("\n=== TYPE DECLARATION COMMANDS TEST PASSED ===");
+    }
 
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            ui.add_space(8.0);
-                            ui.label("2.0.0");
-                        });
-                    });
-                });
-            ui.add_space(2.0);
-        });
+    #[test]
+    fn test_system_functions() {
+        println!("\n=== TESTING SYSTEM FUNCTIONS ===");
+        let mut app = TimeWarpApp::default();
 
-        // About dialog
-        if self.show_about {
-            egui::Window::new("About Time Warp IDE")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.vertical_centered(|ui| {
-                        ui.heading("Time Warp IDE");
-                        ui.label("Version 2.0.0");
-                        ui.label("A modern, educational programming environment");
-                        ui.label("built in Rust using the egui framework.");
-                        ui.separator();
-                        ui.label("Exclusive TW BASIC development environment");
-                        ui.label("with interactive input and turtle graphics.");
-                        ui.separator();
-                        if ui.button("Close").clicked() {
-                            self.show_about = false;
-                        }
-                    });
-                });
-        }
+        // Test DATE$ function
+        let program = "PRINT DATE$";
+        let result = app.execute_tw_basic(program);
+        println!("DATE$ test output: {}", result);
+        // Should return a date string in MM-DD-YYYY format
+        assert!(result.contains("-"), "DATE$ should return formatted date");
 
-        // Error notification toast
-        if let Some(ref error_msg) = self.error_message {
-            let toast_duration = 3.0; // Show for 3 seconds
-            if self.error_timer < toast_duration {
-                self.error_timer += ctx.input(|i| i.unstable_dt).min(0.1) as f64; // Cap delta time
+        // Test TIME$ function
+        let program = "PRINT TIME$";
+        let result = app.execute_tw_basic(program);
+        println!("TIME$ test output: {}", result);
+        // Should return a time string in HH:MM:SS format
+        assert!(result.contains(":"), "TIME$ should return formatted time");
 
-                // Position toast at bottom center
-                let screen_rect = ctx.screen_rect();
-                let toast_width = 400.0;
-                let toast_height = 60.0;
-                let toast_pos = egui::pos2(
-                    screen_rect.center().x - toast_width / 2.0,
-                    screen_rect.bottom() - toast_height - 20.0,
-                );
+        // Test TIMER function
+        let program = "PRINT TIMER";
+        let result = app.execute_tw_basic(program);
+        println!("TIMER test output: {}", result);
+        // Should return a number (seconds since midnight)
+        assert!(
+            result
+                .chars()
+                .all(|c| c.is_numeric() || c == '.' || c == '\n'),
+            "TIMER should return a numeric value"
+        );
 
-                let mut dismiss_clicked = false;
-                egui::Area::new("error_toast")
-                    .fixed_pos(toast_pos)
-                    .show(ctx, |ui| {
-                        egui::Frame::none()
-                            .fill(egui::Color32::from_rgb(220, 53, 69)) // Red background
-                            .stroke(egui::Stroke::new(2.0, egui::Color32::from_rgb(176, 42, 55)))
-                            .rounding(egui::Rounding::same(8.0))
-                            .shadow(egui::epaint::Shadow::small_dark())
-                            .show(ui, |ui| {
-                                ui.set_width(toast_width);
-                                ui.set_height(toast_height);
-                                ui.horizontal(|ui| {
-                                    ui.add_space(12.0);
-                                    ui.label(egui::RichText::new("❌").size(20.0));
-                                    ui.add_space(8.0);
-                                    ui.vertical(|ui| {
-                                        ui.add_space(8.0);
-                                        ui.label(
-                                            egui::RichText::new("Error")
-                                                .color(egui::Color32::WHITE)
-                                                .size(14.0),
-                                        );
-                                        ui.label(
-                                            egui::RichText::new(error_msg)
-                                                .color(egui::Color32::from_rgb(255, 235, 235))
-                                                .size(12.0),
-                                        );
-                                    });
-                                    ui.with_layout(
-                                        egui::Layout::right_to_left(egui::Align::Center),
-                                        |ui| {
-                                            ui.add_space(8.0);
-                                            if ui.button("✕").clicked() {
-                                                dismiss_clicked = true;
-                                            }
-                                        },
-                                    );
-                                });
-                            });
-                    });
+        // Test ENVIRON$ with variable name
+        let program = "PRINT ENVIRON$(\"PATH\")";
+        let result = app.execute_tw_basic(program);
+        println!("ENVIRON$ test output: {}", result);
+        // Should return the PATH environment variable or empty string
+        // (We can't assert specific content since it depends on the environment)
 
-                if dismiss_clicked {
-                    self.error_message = None;
-                    self.error_timer = 0.0;
-                }
-            } else {
-                // Auto-dismiss after timeout
-                self.error_message = None;
-                self.error_timer = 0.0;
-            }
-        }
+        // Test ENVIRON$ with numeric index
+        let program = "PRINT ENVIRON$(1)";
+        let result = app.execute_tw_basic(program);
+        println!("ENVIRON$ numeric test output: {}", result);
+        // Should return the first environment variable in KEY=VALUE format
+
+        // Test INT(RND(1)*100) expression
+        let program = "PRINT INT(RND(1)*100)";
+        let result = app.execute_tw_basic(program);
+        println!("INT(RND(1)*100) test output: {}", result);
+        // Should return an integer between 0 and 99
+        let num_result: f64 = result.trim().parse().expect("Should parse as number");
+        assert!(
+            (0.0..100.0).contains(&num_result),
+            "INT(RND(1)*100) should return 0-99"
+        );
+
+        println!("\n=== SYSTEM FUNCTIONS TEST PASSED ===");
     }
-}
 
-fn main() -> eframe::Result<()> {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1200.0, 800.0])
-            .with_title("Time Warp IDE"),
-        ..Default::default()
-    };
+    #[test]
+    #[cfg(feature = "templates")]
+    fn test_new_file_templates() {
+        let builtin = templates::builtin_templates();
+        assert_eq!(
+            builtin.iter().any(|t| t.language == "Pascal"),
+            cfg!(feature = "pascal")
+        );
 
-    eframe::run_native(
-        "Time Warp IDE",
-        options,
-        Box::new(|_cc| Box::new(TimeWarpApp::default())),
-    )
-}
+        // Every non-interactive BASIC template runs cleanly
+        for template in builtin.iter().filter(|t| t.language == "TW BASIC") {
+            if template.content.contains("INPUT") {
+                continue;
+            }
+            let mut interpreter = time_warp_core::basic::Interpreter::new();
+            assert!(
+                interpreter.execute(&template.content).is_ok(),
+                "template '{}' failed to run",
+                template.name
+            );
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+        // Templates in the other languages run cleanly too
+        for template in builtin.iter().filter(|t| t.language != "TW BASIC") {
+            let language = Language::from_name(&template.language).unwrap();
+            let result = TimeWarpApp::default().execute_with(language, &template.content);
+            assert!(!result.contains("Error"), "{}: {}", template.name, result);
+        }
 
-    #[test]
-    fn test_file_operations() {
-        // Test New File functionality
+        // Creating from a template replaces the editor and forgets the old path
         let mut app = TimeWarpApp::default();
-        app.code = "some code".to_string();
-        app.output = "some output".to_string();
-        app.last_file_path = Some("test.txt".to_string());
-
-        // Simulate New File
-        app.code.clear();
-        // File operations no longer set output messages
-        app.last_file_path = None;
-
-        assert_eq!(app.code, "");
-        // Output should remain unchanged for file operations
-        assert_eq!(app.output, "some output");
+        app.code = "old code".to_string();
+        app.last_file_path = Some("old.twb".to_string());
+        let starter = builtin
+            .iter()
+            .find(|t| t.name == "Turtle art starter")
+            .unwrap();
+        app.create_file_from_template(starter);
+        assert_eq!(app.code, starter.content);
         assert_eq!(app.last_file_path, None);
+        assert_eq!(app.untitled_file_name(), "untitled.twb");
+        #[cfg(feature = "logo")]
+        {
+            let logo = builtin.iter().find(|t| t.language == "Logo").unwrap();
+            app.create_file_from_template(logo);
+            assert_eq!(app.language, Language::Logo);
+            assert_eq!(app.untitled_file_name(), "untitled.twl");
+        }
+
+        // User templates are picked up from a directory
+        let dir = temp_path("time_warp_template_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Spiral.twb"), "10 FORWARD 10\n").unwrap();
+        let user = templates::load_user_templates(&dir);
+        assert_eq!(user.len(), 1);
+        assert_eq!(user[0].name, "Spiral");
+        assert_eq!(user[0].language, "TW BASIC");
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(templates::load_user_templates(&dir).is_empty());
     }
 
     #[test]
-    fn test_save_operations() {
+    #[cfg(feature = "profiler")]
+    fn test_line_profiler() {
         let mut app = TimeWarpApp::default();
-        app.code = "10 PRINT \"TEST\"".to_string();
-        app.last_file_path = Some("test_save.twb".to_string());
-        app.output = "previous output".to_string(); // Set some initial output
-
-        // Simulate Save
-        if let Some(path) = &app.last_file_path {
-            fs::write(path, &app.code).unwrap();
-            // File operations no longer set output messages
-        }
-
-        // Verify file was saved
-        let content = fs::read_to_string("test_save.twb").unwrap();
-        assert_eq!(content, "10 PRINT \"TEST\"");
-        // Output should remain unchanged
-        assert_eq!(app.output, "previous output");
+        app.profiling_enabled = true;
+        app.execute_tw_basic("10 LET T = 0\n20 FOR I = 1 TO 5\n30 PRINT I\n40 NEXT I\n50 END");
 
-        // Cleanup
-        fs::remove_file("test_save.twb").unwrap();
+        let hits: HashMap<usize, u64> = app
+            .profile_results
+            .iter()
+            .map(|entry| (entry.line, entry.hits))
+            .collect();
+        assert_eq!(hits.get(&1), Some(&1));
+        assert_eq!(hits.get(&3), Some(&5));
+
+        // Hottest line first by default, ties broken by line number
+        assert_eq!(app.sorted_profile()[0].line, 3);
+        app.profile_sort = profiler::ProfileSort::Line;
+        app.profile_sort_descending = false;
+        let lines: Vec<usize> = app.sorted_profile().iter().map(|e| e.line).collect();
+        assert_eq!(lines[0], 1);
+
+        // The editor gutter colours each line that ran by its heat
+        let marks = app.run_marks();
+        let (_, color, hover) = marks.iter().find(|(line, _, _)| *line == 3).unwrap();
+        assert_eq!(*color, TimeWarpApp::heat_color(5, 5));
+        assert_eq!(hover, "Line 3 ran 5 times");
+        app.show_profile_heatmap = false;
+        assert!(app
+            .run_marks()
+            .iter()
+            .all(|(_, _, hover)| !hover.contains("ran")));
+
+        // Profiling is off by default
+        let mut app = TimeWarpApp::default();
+        app.execute_tw_basic("10 PRINT 1");
+        assert!(app.profile_results.is_empty());
     }
 
     #[test]
-    fn test_view_operations() {
+    #[cfg(feature = "workspaces")]
+    fn test_workspaces_are_independent() {
         let mut app = TimeWarpApp::default();
-
-        // Test Show Line Numbers toggle
-        assert_eq!(app.show_line_numbers, false);
-        app.show_line_numbers = !app.show_line_numbers;
-        assert_eq!(app.show_line_numbers, true);
-        app.show_line_numbers = !app.show_line_numbers;
-        assert_eq!(app.show_line_numbers, false);
+        app.open_workspace();
+        assert_eq!(app.workspaces.len(), 1);
+
+        app.code = "10 PRINT \"MAIN\"".to_string();
+        app.execute_code();
+        let workspace = &mut app.workspaces[0].app;
+        workspace.code = "10 PRINT \"SECOND\"".to_string();
+        workspace.execute_code();
+
+        assert!(app.output.contains("MAIN"));
+        assert!(!app.output.contains("SECOND"));
+        assert!(app.workspaces[0].app.output.contains("SECOND"));
+        assert!(!app.workspaces[0].app.output.contains("MAIN"));
+
+        // Each workspace gets its own window id and title
+        app.open_workspace();
+        assert_ne!(app.workspaces[0].viewport_id, app.workspaces[1].viewport_id);
+        assert_ne!(app.workspaces[0].title, app.workspaces[1].title);
     }
 
     #[test]
-    fn test_edit_operations() {
+    fn test_coverage_after_run() {
         let mut app = TimeWarpApp::default();
-        app.code = "old text".to_string();
+        assert!(app.coverage.is_none());
+
+        app.execute_tw_basic("10 PRINT \"A\"\n20 GOTO 40\n30 PRINT \"DEAD\"\n40 END");
+        let coverage = app.coverage.as_ref().unwrap();
+        assert!(coverage.executed.contains(&1));
+        assert!(coverage.executed.contains(&2));
+        assert!(!coverage.executed.contains(&3));
+        assert!(coverage.executable.contains(&3));
+        assert!(coverage.executed.contains(&4));
+        assert_eq!(coverage.percent(), 75.0);
+
+        // The editor gutter marks each statement line, red where the run never went
+        let palette = app.palette();
+        let marks: Vec<_> = app
+            .run_marks()
+            .into_iter()
+            .map(|(line, color, _)| (line, color))
+            .collect();
+        assert_eq!(
+            marks,
+            [
+                (1, palette.covered),
+                (2, palette.covered),
+                (3, palette.uncovered),
+                (4, palette.covered)
+            ]
+        );
+        app.show_coverage = false;
+        assert!(app.run_marks().is_empty());
+    }
 
-        // Test Find/Replace
-        assert_eq!(app.show_find_replace, false);
-        app.show_find_replace = true;
-        assert_eq!(app.show_find_replace, true);
+    #[test]
+    fn test_include_directive() {
+        use time_warp_core::basic::include;
 
-        // Test Replace All
-        app.find_text = "old".to_string();
-        app.replace_text = "new".to_string();
-        app.code = app.code.replace(&app.find_text, &app.replace_text);
-        assert_eq!(app.code, "new text");
+        assert_eq!(
+            include::include_target("'INCLUDE \"lib.twb\""),
+            Some("lib.twb")
+        );
+        assert_eq!(
+            include::include_target("20 REM $include: 'util.bi'"),
+            Some("util.bi")
+        );
+        assert_eq!(include::include_target("' include the lib"), None);
+        assert_eq!(include::include_target("PRINT \"'INCLUDE\""), None);
+
+        let root = std::env::temp_dir().join(format!("tw_include_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        let write = |name: &str, code: &str| std::fs::write(root.join(name), code).unwrap();
+        write("lib/greet.twb", "PRINT \"HELLO\"\n'$INCLUDE: 'more.twb'");
+        write("lib/more.twb", "PRINT \"MORE\"");
+        write("lib/broken.twb", "PRINT 1\nPRINT @");
+
+        // Included lines run where the INCLUDE was, found next to the file naming them
+        let mut app = TimeWarpApp::default();
+        app.last_file_path = Some(root.join("main.twb").display().to_string());
+        let output = app.execute_tw_basic("'INCLUDE \"lib/greet.twb\"\nPRINT \"MAIN\"");
+        assert_eq!(output, "HELLO\nMORE\nMAIN\n");
+        assert_eq!(app.included.main_line(2), 1);
+        assert_eq!(app.included.expanded_line(2), Some(3));
+        let coverage = app.coverage.as_ref().unwrap();
+        assert_eq!(coverage.executed, [2].into_iter().collect());
+
+        // An error in an included file names the file and its own line
+        let output = app.execute_tw_basic("PRINT 0\n'INCLUDE \"lib/broken.twb\"");
+        assert!(output.contains("line 2 of broken.twb"), "{}", output);
+        let output = app.execute_tw_basic("'INCLUDE \"lib/gone.twb\"");
+        assert!(output.contains("Could not INCLUDE lib/gone.twb at line 1 of main.twb"));
+        // Only where an error happened is mapped, not the lines it names
+        app.execute_tw_basic("PRINT 0\n'INCLUDE \"lib/broken.twb\"");
+        assert_eq!(
+            app.included
+                .locate_at_lines("There is no line 2 to jump to, at line 3"),
+            "There is no line 2 to jump to, at line 2 of broken.twb"
+        );
+
+        // A file that ends up including itself is stopped
+        write("a.twb", "'INCLUDE \"b.twb\"");
+        write("b.twb", "PRINT 1\n'INCLUDE \"a.twb\"");
+        app.last_file_path = Some(root.join("a.twb").display().to_string());
+        let output = app.execute_tw_basic("'INCLUDE \"b.twb\"");
+        assert!(output.contains("line 2 of b.twb"), "{}", output);
+        assert!(output.contains("a.twb → b.twb → a.twb"), "{}", output);
+        let _ = std::fs::remove_dir_all(&root);
     }
 
     #[test]
-    fn test_help_operations() {
+    #[cfg(feature = "hints")]
+    fn test_hint_after_repeated_errors() {
+        let mut app = TimeWarpApp::default();
+        let broken = "10 IF X > 1 PRINT \"BIG\"";
+
+        app.execute_tw_basic(broken);
+        app.execute_tw_basic(broken);
+        assert!(app.active_hint.is_none());
+        app.execute_tw_basic(broken);
+        let (card, count) = app.active_hint.expect("hint after third error");
+        assert_eq!(card.category, "expected_token");
+        assert_eq!(count, 3);
+
+        // Counts are per file
+        app.active_hint = None;
+        app.last_file_path = Some("other.twb".to_string());
+        app.execute_tw_basic(broken);
+        assert!(app.active_hint.is_none());
+
+        // Errors are grouped by their code, not by what their message says
+        assert_eq!(
+            hints::categorize(ErrorCode::DivisionByZero),
+            "division_by_zero"
+        );
+        assert_eq!(hints::categorize(ErrorCode::FileNotFound), "runtime");
+        assert_eq!(hints::categorize(ErrorCode::InvalidNumber), "syntax");
         let mut app = TimeWarpApp::default();
+        for _ in 0..3 {
+            app.execute_tw_basic("10 PRINT SIMILAR(\"A\")");
+        }
+        let (card, _) = app.active_hint.expect("hint after third error");
+        assert_eq!(card.category, "argument_count");
 
-        // Test About dialog
-        assert_eq!(app.show_about, false);
-        app.show_about = true;
-        assert_eq!(app.show_about, true);
-        app.show_about = false;
-        assert_eq!(app.show_about, false);
+        // The off switch stops tracking entirely
+        let mut app = TimeWarpApp::default();
+        app.settings.hints_enabled = false;
+        for _ in 0..5 {
+            app.execute_tw_basic(broken);
+        }
+        assert!(app.active_hint.is_none());
     }
 
     #[test]
-    fn test_menu_state_changes() {
+    fn test_execution_stats() {
         let mut app = TimeWarpApp::default();
+        assert!(app.execution_stats.is_none());
 
-        // Test all menu state changes
-        assert_eq!(app.show_find_replace, false);
-        assert_eq!(app.show_about, false);
-        assert_eq!(app.show_line_numbers, false);
-
-        // Simulate menu clicks
-        app.show_find_replace = true;
-        app.show_about = true;
-        app.show_line_numbers = true;
+        app.execute_tw_basic("10 FOR I = 1 TO 3\n20 PRINT I\n30 NEXT I");
+        let stats = app.execution_stats.clone().unwrap();
+        assert_eq!(stats.statements, 7);
+        assert_eq!(stats.max_instructions, 5_000_000);
+        assert!(stats.percent_of_limit() < 1.0);
 
-        assert_eq!(app.show_find_replace, true);
-        assert_eq!(app.show_about, true);
-        assert_eq!(app.show_line_numbers, true);
+        // A run that carries on after INPUT keeps counting
+        app.code = "10 INPUT A\n20 FOR I = 1 TO 3\n30 NEXT I".to_string();
+        app.execute_code();
+        assert!(app.waiting_for_input);
+        let waiting = app.execution_stats.clone().unwrap().statements;
+        app.user_input = "5".to_string();
+        app.submit_program_input();
+        assert!(!app.waiting_for_input);
+        assert_eq!(app.execution_stats.clone().unwrap().statements, waiting + 4);
     }
 
     #[test]
-    fn test_tab_switching() {
-        let mut app = TimeWarpApp::default();
-
-        // Test tab switching
-        assert_eq!(app.active_tab, 0);
-        app.active_tab = 1;
-        assert_eq!(app.active_tab, 1);
-        app.active_tab = 0;
-        assert_eq!(app.active_tab, 0);
+    fn test_ide_style() {
+        let mut settings = settings::Settings::default();
+        let style = ide_style(&settings);
+        assert!(!style.visuals.dark_mode);
+        assert_eq!(
+            style.text_styles.get(&egui::TextStyle::Heading),
+            Some(&egui::FontId::new(20.0, egui::FontFamily::Proportional))
+        );
+        assert_eq!(style.spacing.item_spacing, egui::vec2(8.0, 4.0));
+
+        settings.theme = theme::Theme::Dark;
+        settings.font_size = 18.0;
+        settings.code_font_size = 32.0;
+        let style = ide_style(&settings);
+        assert!(style.visuals.dark_mode);
+        // Code is sized on its own, so it can be made big for a projector
+        assert_eq!(
+            style.text_styles.get(&egui::TextStyle::Monospace),
+            Some(&egui::FontId::new(32.0, egui::FontFamily::Monospace))
+        );
+        assert_eq!(
+            style.text_styles.get(&egui::TextStyle::Body),
+            Some(&egui::FontId::new(18.0, egui::FontFamily::Proportional))
+        );
     }
 
     #[test]
-    fn test_keyboard_shortcuts() {
+    fn test_turtle_ops() {
+        use time_warp_core::basic::GraphicsCommand;
+
         let mut app = TimeWarpApp::default();
-        let ctx = egui::Context::default();
+        app.process_graphics_commands(&[
+            GraphicsCommand::Forward(10.0).into(),
+            GraphicsCommand::Right(90.0).into(),
+            GraphicsCommand::Move(10.0).into(),
+            GraphicsCommand::SetXY(0.0, 0.0).into(),
+        ]);
+
+        // Move draws nothing; SETXY uses both coordinates
+        assert_eq!(
+            app.turtle_commands,
+            vec![
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(0.0, 0.0),
+                    to: egui::pos2(10.0, 0.0),
+                },
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(10.0, 10.0),
+                    to: egui::pos2(0.0, 0.0),
+                },
+            ]
+        );
+        assert_eq!(
+            (app.turtles[DEFAULT_TURTLE].x, app.turtles[DEFAULT_TURTLE].y),
+            (0.0, 0.0)
+        );
+    }
 
-        // Test Ctrl+N (New File)
-        app.code = "existing code".to_string();
-        app.last_file_path = Some("file.txt".to_string());
+    #[test]
+    fn test_penup_moves_without_drawing() {
+        use time_warp_core::basic::{ExecutionResult, GraphicsCommand};
+
+        let graphics = |interp: &mut time_warp_core::basic::Interpreter, code: &str| match interp
+            .execute(code)
+        {
+            Ok(ExecutionResult::Complete {
+                graphics_commands, ..
+            }) => graphics_commands
+                .into_iter()
+                .map(|tagged| tagged.command)
+                .collect::<Vec<_>>(),
+            other => panic!("unexpected result: {:?}", other),
+        };
 
-        // Simulate Ctrl+N key press
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::N)) {
-            app.code.clear();
-            app.output = "New file created.".to_string();
-        }
+        let mut interp = time_warp_core::basic::Interpreter::new();
+        assert_eq!(
+            graphics(
+                &mut interp,
+                "PENUP\nFORWARD 10\nSETXY 0, 20\nPENDOWN\nBACK 5\nSETXY 30, 20"
+            ),
+            vec![
+                GraphicsCommand::Move(10.0),
+                GraphicsCommand::MoveTo(0.0, 20.0),
+                GraphicsCommand::Back(5.0),
+                GraphicsCommand::SetXY(30.0, 20.0),
+            ]
+        );
 
-        // Since we can't simulate key presses in unit tests, test the logic directly
-        app.code.clear();
-        app.output = "New file created.".to_string();
-        app.last_file_path = None;
+        // A new run starts with the pen down again
+        graphics(&mut interp, "PENUP");
+        assert_eq!(
+            graphics(&mut interp, "FORWARD 1"),
+            vec![GraphicsCommand::Forward(1.0)]
+        );
 
-        assert_eq!(app.code, "");
-        assert_eq!(app.output, "New file created.");
-        assert_eq!(app.last_file_path, None);
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic("PENUP\nFORWARD 50\nPENDOWN\nFORWARD 10");
+        assert!(!result.contains("Error"), "{}", result);
+        assert_eq!(
+            app.turtle_commands,
+            vec![turtle::TurtleOp::Line {
+                from: egui::pos2(50.0, 0.0),
+                to: egui::pos2(60.0, 0.0),
+            }]
+        );
     }
 
     #[test]
-    fn test_basic_program_execution() {
+    fn test_watch_mode_debounce_and_cancel() {
         let mut app = TimeWarpApp::default();
+        app.file_saved();
+        assert!(
+            app.watch_run_due.is_none(),
+            "saves only schedule runs in watch mode"
+        );
 
-        // Test simple BASIC program execution
-        let basic_code = "10 PRINT \"Hello from Time_Warp!\"\n20 PRINT \"Testing output console...\"\n30 PRINT \"Count: 1\"\n40 PRINT \"Count: 2\"\n50 PRINT \"Count: 3\"\n60 PRINT \"Test complete!\"";
-        let result = app.execute_tw_basic(basic_code);
+        app.watch_mode = true;
+        app.file_saved();
+        let first_due = app.watch_run_due.unwrap();
+        app.file_saved();
+        let due = app.watch_run_due.unwrap();
+        assert!(due >= first_due, "a second save pushes the run back");
 
-        // Debug: print the actual result
-        println!("Actual result: {:?}", result);
+        assert!(!app.take_due_watch_run(due - std::time::Duration::from_millis(1)));
+        assert!(app.take_due_watch_run(due));
+        assert!(
+            !app.take_due_watch_run(due),
+            "each save runs the program once"
+        );
 
-        // Verify the output contains expected strings
-        assert!(result.contains("Hello from Time_Warp!"));
-        assert!(result.contains("Testing output console..."));
-        assert!(result.contains("Count: 1"));
-        assert!(result.contains("Count: 2"));
-        assert!(result.contains("Count: 3"));
-        assert!(result.contains("Test complete!"));
+        // Turning watch mode off drops a pending run
+        app.file_saved();
+        app.watch_mode = false;
+        assert!(!app.take_due_watch_run(due + WATCH_DEBOUNCE * 2));
+        assert!(app.watch_run_due.is_none());
+
+        // A run stuck waiting for input is cancelled and the canvas starts over
+        app.code = "FORWARD 10\nINPUT A\nFORWARD 10".to_string();
+        app.execute_code();
+        assert!(app.waiting_for_input);
+        assert_eq!(app.turtle_commands.len(), 1);
+        app.rerun_for_watch();
+        assert!(app.waiting_for_input);
+        assert_eq!(app.turtle_commands.len(), 1);
+
+        app.code = "FORWARD 10".to_string();
+        app.rerun_for_watch();
+        assert!(!app.waiting_for_input);
+        assert!(app.basic_interpreter.is_none());
+        assert_eq!(app.turtle_commands.len(), 1);
     }
 
     #[test]
-    fn test_enhanced_basic_commands() {
+    fn test_external_file_changes() {
+        let dir = std::env::temp_dir().join(format!("tw_external_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("art.twb");
+        fs::write(&path, "FORWARD 10").unwrap();
+
         let mut app = TimeWarpApp::default();
+        app.code = "FORWARD 10".to_string();
+        app.last_file_path = Some(path.display().to_string());
+        app.file_watcher.track(&path, &app.code);
+        let start = std::time::Instant::now();
+        assert_eq!(app.file_watcher.check(&path, start), None);
+
+        // Another editor saves; bump the timestamp in case the filesystem is coarse
+        fs::write(&path, "FORWARD 20").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(
+            app.file_watcher.check(&path, start),
+            None,
+            "polls are throttled"
+        );
+        let later = start + file_watch::POLL_INTERVAL;
+        let external = app.file_watcher.check(&path, later);
+        assert_eq!(external.as_deref(), Some("FORWARD 20"));
+
+        // A clean buffer reloads without asking
+        app.external_file_changed(external.unwrap());
+        assert_eq!(app.code, "FORWARD 20");
+        assert!(app.external_conflict.is_none());
+
+        // Unsaved edits turn the change into a conflict
+        app.code = "FORWARD 30".to_string();
+        app.external_file_changed("FORWARD 40".to_string());
+        assert_eq!(app.code, "FORWARD 30");
+        assert_eq!(app.external_conflict.as_deref(), Some("FORWARD 40"));
+        app.reload_from_disk(app.external_conflict.clone().unwrap());
+        assert_eq!(app.code, "FORWARD 40");
+        assert!(!app.file_watcher.is_dirty(&app.code));
+
+        // Saving over a change made since the last look asks first, even
+        // with watching off; keeping the edits lets the next save go ahead
+        app.file_watcher.enabled = false;
+        fs::write(&path, "FORWARD 50").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+        app.code = "FORWARD 60".to_string();
+        app.save_current_file();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "FORWARD 50");
+        assert_eq!(app.external_conflict.as_deref(), Some("FORWARD 50"));
+        app.external_conflict = None;
+        app.save_current_file();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "FORWARD 60");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-        // Test WRITELN command (Pascal-style with newline)
-        let writeln_code = "WRITELN \"Hello with newline\"";
-        let result = app.execute_tw_basic(writeln_code);
-        println!("WRITELN result: {:?}", result);
-        assert!(result.contains("Hello with newline"));
+    #[test]
+    fn test_absolute_turtle_commands() {
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic(
+            "FORWARD 10\nPENSIZE 4\nCLEARSCREEN\nSETXY 5, 5\nRIGHT 45\nSETH 0\nFORWARD 10\nHOME",
+        );
+        assert!(!result.contains("Error"), "{}", result);
 
-        // Test turtle graphics commands
-        let turtle_code = "FORWARD 50\nRIGHT 90\nBACK 25";
-        let result = app.execute_tw_basic(turtle_code);
-        println!("Turtle commands result: {:?}", result);
-        assert!(result.contains("Moved forward 50"));
-        assert!(result.contains("Turned right 90"));
-        assert!(result.contains("Moved back 25"));
+        // Only what was drawn after CLEARSCREEN is left, still with the wide pen
+        assert_eq!(
+            app.turtle_commands,
+            vec![
+                turtle::TurtleOp::PenWidth(4.0),
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(0.0, 0.0),
+                    to: egui::pos2(5.0, 5.0),
+                },
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(5.0, 5.0),
+                    to: egui::pos2(15.0, 5.0),
+                },
+            ]
+        );
+        assert_eq!(
+            (
+                app.turtles[DEFAULT_TURTLE].x,
+                app.turtles[DEFAULT_TURTLE].y,
+                app.turtles[DEFAULT_TURTLE].angle
+            ),
+            (0.0, 0.0, 0.0)
+        );
     }
 
     #[test]
-    fn test_input_statement_parsing() {
-        // Test that INPUT statements with semicolon separators parse correctly
-        let input_code = "10 INPUT \"Name? \"; NAME$\n20 PRINT \"Hello \"; NAME$";
+    fn test_file_backends() {
+        let dir = std::env::temp_dir().join(format!("tw_backends_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("usb.twb").display().to_string();
 
-        // This should not panic or return a parse error
         let mut app = TimeWarpApp::default();
-        let result = app.execute_tw_basic(input_code);
+        app.code = "10 PRINT \"SAFE\"".to_string();
+        assert!(app.save_to_location(&path));
+        app.code = "10 PRINT \"AGAIN\"".to_string();
+        assert!(app.save_to_location(&path));
+        // USB-safe saves leave no temporary file behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
 
-        // The execution should start (even if it waits for input)
-        // We just want to make sure it doesn't fail with a parse error
-        println!("INPUT parsing result: {:?}", result);
-        // If we get here without panicking, the parsing worked
-        assert!(true); // Just verify we don't crash
+        app.code.clear();
+        assert!(app.open_location(&path));
+        assert_eq!(app.code, "10 PRINT \"AGAIN\"");
+
+        // Class shares need the network permission first, and builds
+        // without the webdav feature refuse them outright
+        let url = "http://127.0.0.1:9/class/hello.twb";
+        assert!(!app.open_location(url));
+        let refusal = if cfg!(feature = "webdav") {
+            "network shares are turned off"
+        } else {
+            "no class share support"
+        };
+        assert!(app.error_message.take().unwrap().contains(refusal));
+        assert_eq!(app.last_file_path.as_deref(), Some(path.as_str()));
+
+        // Opening a file switches to the language its extension names
+        for (name, language) in [
+            ("family.tpr", "Prolog"),
+            ("quiz.plt", "PILOT"),
+            ("grades.twp", "Pascal"),
+            ("spiral.logo", "Logo"),
+        ] {
+            let Some(language) = Language::from_name(language) else {
+                continue; // not in this build
+            };
+            let other = dir.join(name).display().to_string();
+            assert!(app.save_to_location(&other));
+            app.language = Language::Basic;
+            assert!(app.open_location(&other));
+            assert_eq!(app.language, language, "{}", name);
+        }
+        // An unknown extension leaves the language alone
+        let language = app.language;
+        let notes = dir.join("notes.txt").display().to_string();
+        assert!(app.save_to_location(&notes));
+        assert!(app.open_location(&notes));
+        assert_eq!(app.language, language);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_input_statement_execution() {
-        // Test that INPUT statements properly set waiting_for_input state
-        let input_code = "10 INPUT \"Name? \"; NAME$";
+    #[cfg(feature = "webdav")]
+    fn test_class_share_transfers() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        // A share that answers one GET in chunks and then one PUT
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in [
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                 6\r\n10 PRI\r\n8\r\nNT \"HI\"\n\r\n0\r\n\r\n",
+                "HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n",
+            ] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        line.to_ascii_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(str::to_string)
+                    })
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                requests.push((head, String::from_utf8(body).unwrap()));
+            }
+            requests
+        });
 
+        let ctx = egui::Context::default();
         let mut app = TimeWarpApp::default();
-        let _result = app.execute_tw_basic(input_code);
+        app.file_backends.network_allowed = true;
+        let url = format!("http://127.0.0.1:{}/class/hello.twb", port);
+        let finish = |app: &mut TimeWarpApp| {
+            while app.share_transfer.is_some() {
+                app.poll_share_transfer(&ctx);
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        };
 
-        // After executing an INPUT statement, the app should be waiting for input
-        assert!(
-            app.waiting_for_input,
-            "App should be waiting for input after INPUT statement"
-        );
-        assert_eq!(
-            app.input_prompt, "Name? ",
-            "Input prompt should be set correctly"
+        // The open happens in the background and lands once it arrives
+        assert!(app.open_location(&url));
+        assert!(app.share_transfer_status().unwrap().starts_with("Opening"));
+        finish(&mut app);
+        assert_eq!(app.error_message, None);
+        assert_eq!(app.code, "10 PRINT \"HI\"\n");
+        assert_eq!(app.last_file_path.as_deref(), Some(url.as_str()));
+
+        app.code = "10 PRINT \"BYE\"".to_string();
+        assert!(app.save_to_location(&url));
+        finish(&mut app);
+        assert_eq!(app.error_message, None);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].0.starts_with("GET /class/hello.twb HTTP/1.1"));
+        assert!(requests[0].0.contains(&format!("127.0.0.1:{}", port)));
+        assert!(requests[1].0.starts_with("PUT /class/hello.twb HTTP/1.1"));
+        assert_eq!(requests[1].1, "10 PRINT \"BYE\"");
+
+        // A password is never sent over plain http
+        app.file_backends.webdav_username = "pupil".to_string();
+        assert!(app.open_location(&url));
+        finish(&mut app);
+        assert!(app
+            .error_message
+            .take()
+            .unwrap()
+            .contains("only sent to https://"));
+    }
+
+    #[test]
+    fn test_named_turtles() {
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic(
+            "SETPC 4\nFORWARD 10\nTURTLE \"A\"\nPENUP\nSETXY 0, 20\nPENDOWN\nFORWARD 5\nLEFT 45\nTURTLE \"main\"\nFORWARD 10",
         );
+        assert!(!result.contains("Error"), "{}", result);
+
+        // Each turtle keeps its own position, heading, pen and colour
+        let a = &app.turtles["A"];
+        assert_eq!((a.x, a.y, a.angle), (5.0, 20.0, -45.0));
+        assert_eq!(a.color, turtle::DEFAULT_PEN_COLOR);
+        let main = &app.turtles[DEFAULT_TURTLE];
+        assert_eq!((main.x, main.y, main.angle), (20.0, 0.0, 0.0));
+        assert_eq!(main.color, egui::Color32::from_rgb(220, 0, 0));
+
+        // The canvas pen switches whenever the drawing turtle does
+        let red = egui::Color32::from_rgb(220, 0, 0);
         assert_eq!(
-            app.current_input_var, "NAME$",
-            "Current input variable should be set correctly"
+            app.turtle_commands,
+            vec![
+                turtle::TurtleOp::ColorChange(red),
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(0.0, 0.0),
+                    to: egui::pos2(10.0, 0.0),
+                },
+                turtle::TurtleOp::ColorChange(turtle::DEFAULT_PEN_COLOR),
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(0.0, 20.0),
+                    to: egui::pos2(5.0, 20.0),
+                },
+                turtle::TurtleOp::ColorChange(red),
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(10.0, 0.0),
+                    to: egui::pos2(20.0, 0.0),
+                },
+            ]
         );
+
+        // Back to one turtle after clearing
+        app.clear_canvas();
+        assert_eq!(app.turtles.len(), 1);
     }
 
     #[test]
-    fn test_tab_function() {
+    fn test_turtle_animation() {
         let mut app = TimeWarpApp::default();
+        app.execute_tw_basic("SETPC 4\nFORWARD 10\nRIGHT 90\nPENSIZE 3\nFORWARD 10");
+        assert_eq!(turtle::stroke_count(&app.turtle_commands), 2);
+
+        // Half a second at 3 lines/s is one and a half lines
+        app.turtle_speed = 3.0;
+        assert!(app.advance_turtle_animation(0.05));
+        for _ in 0..9 {
+            app.advance_turtle_animation(0.05);
+        }
+        assert!((app.turtle_progress - 1.5).abs() < 1e-4);
+        let (shown, pen) = turtle::replay(&app.turtle_commands, 1.5);
+        assert_eq!(shown.len(), 4); // colour, first line, width, half of the second
+        assert_eq!(
+            shown[1],
+            turtle::TurtleOp::Line {
+                from: egui::pos2(0.0, 0.0),
+                to: egui::pos2(10.0, 0.0),
+            }
+        );
+        let tip = pen.unwrap();
+        assert!((tip.x - 10.0).abs() < 1e-4 && (tip.y - 5.0).abs() < 1e-4);
+
+        // The replay stops at the end, and the instant option skips it
+        while app.advance_turtle_animation(1.0) {}
+        assert_eq!(app.turtle_progress, 2.0);
+        app.turtle_progress = 0.0;
+        app.turtle_animate = false;
+        assert!(!app.advance_turtle_animation(0.0));
+        assert_eq!(app.turtle_progress, 2.0);
+        assert_eq!(
+            turtle::replay(&app.turtle_commands, 2.0).0,
+            app.turtle_commands
+        );
+    }
 
-        // Test TAB function in PRINT statements
-        let tab_code = "PRINT \"Hello\"; TAB(10); \"World\"";
-        let result = app.execute_tw_basic(tab_code);
-
-        println!("TAB result: {:?}", result);
+    #[test]
+    fn test_answer_matching() {
+        use time_warp_core::basic::matching::{match_answer, similarity, soundex};
+
+        assert_eq!(match_answer("colour", "COLOR,COLOUR"), 1);
+        assert_eq!(match_answer("The answer is Paris!", "PARIS"), 1);
+        assert_eq!(match_answer("prais", "PARIS"), 1);
+        assert_eq!(match_answer("Parisian", "PARIS"), 0);
+        assert_eq!(match_answer("13", "12"), 0);
+        assert_eq!(match_answer("yeah", "yes,yeah|yep"), 2);
+        assert_eq!(match_answer("cat", "CAR"), 0);
+
+        assert_eq!(soundex("Robert"), "R163");
+        assert_eq!(soundex("Ashcraft"), "A261");
+        assert_eq!(soundex("Tymczak"), "T522");
+        assert_eq!(soundex("123"), "");
+        assert_eq!(similarity("CAT", "cart"), 75.0);
+        assert_eq!(similarity("Paris!", "paris"), 100.0);
 
-        // Verify TAB function produces spaces for positioning
-        assert!(result.contains("Hello"));
-        assert!(result.contains("World"));
+        let mut app = TimeWarpApp::default();
+        let output = app.execute_tw_basic(
+            "IF MATCH(\"colour\", \"COLOR|COLOUR\") THEN PRINT \"YES\"\nPRINT SOUNDEX$(\"Pfister\")\nPRINT SIMILAR(\"CAT\", \"CART\")",
+        );
+        assert!(output.contains("YES"));
+        assert!(output.contains("P236"));
+        assert!(output.contains("75"));
     }
 
     #[test]
-    fn test_print_variable() {
+    fn test_export_canvas_png() {
         let mut app = TimeWarpApp::default();
+        app.execute_tw_basic("SETPC 4\nPENSIZE 4\nFORWARD 100");
+        let red = egui::Color32::from_rgb(220, 0, 0);
+        let drawn = turtle::bounds(&app.turtle_commands).unwrap();
+        assert_eq!(drawn.height().max(drawn.width()), 100.0);
+
+        // The current view is the canvas size, with the drawing where it is on screen
+        let middle = drawn.center();
+        let image = app.canvas_image();
+        assert_eq!((image.width, image.height), (400, 300));
+        assert_eq!(
+            image.pixel((200.0 + middle.x) as u32, (150.0 + middle.y) as u32),
+            red
+        );
+        assert_eq!(image.pixel(5, 5), egui::Color32::WHITE);
+        app.turtle_pan = egui::vec2(150.0, 0.0);
+        let panned = app.canvas_image();
+        assert_eq!(
+            panned.pixel((200.0 + middle.x) as u32, (150.0 + middle.y) as u32),
+            egui::Color32::WHITE
+        );
 
-        // Test PRINT with a variable
-        let print_code = "LET X = 42\nPRINT X";
-        let result = app.execute_tw_basic(print_code);
-
-        println!("PRINT variable result: {:?}", result);
-
-        // Should contain the variable value
-        assert!(result.contains("42"));
+        // Fitting crops to the drawing's shape, whatever the view
+        app.export_fit = true;
+        let fitted = app.canvas_image();
+        assert!(fitted.width <= FIT_EXPORT_SIZE.x as u32);
+        assert!(fitted.height <= FIT_EXPORT_SIZE.y as u32);
+        assert_eq!(fitted.pixel(fitted.width / 2, fitted.height / 2), red);
+
+        let dir = std::env::temp_dir().join(format!("tw_export_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("drawing.png");
+        canvas_export::save_png(&fitted, &path).unwrap();
+        let png = fs::read(&path).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(
+            u32::from_be_bytes(png[16..20].try_into().unwrap()),
+            fitted.width
+        );
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_print_variable_simple() {
+    fn test_export_canvas_svg() {
         let mut app = TimeWarpApp::default();
-
-        // Test PRINT with a variable (simple case)
-        let print_code = "PRINT X";
-        let result = app.execute_tw_basic(print_code);
-
-        println!("PRINT variable simple result: {:?}", result);
-
-        // Should not crash with parse error
-        assert!(!result.contains("ParseError"));
+        app.execute_tw_basic("SETPC 4\nPENSIZE 3\nSETXY 100, 0\nSETPC 1\nSETXY 100, 50");
+
+        let dir = std::env::temp_dir().join(format!("tw_svg_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("art.svg");
+        app.write_canvas_export(&path).unwrap();
+        let svg = fs::read_to_string(&path).unwrap();
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\" height=\"300\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        // One element per segment, in its pen colour and width, in canvas pixels
+        assert_eq!(svg.matches("<line ").count(), 2);
+        assert!(svg.contains(
+            "<line x1=\"200\" y1=\"150\" x2=\"300\" y2=\"150\" stroke=\"#dc0000\" stroke-width=\"3\"/>"
+        ));
+        assert!(svg.contains("x2=\"300\" y2=\"200\" stroke=\"#0000ff\""));
+
+        // Fitting uses the same framing as the PNG export
+        app.export_fit = true;
+        let svg = canvas_export::to_svg(&app.turtle_commands, &app.export_frame());
+        let frame = app.export_frame();
+        assert_eq!(
+            (frame.width, frame.height),
+            (app.canvas_image().width, app.canvas_image().height)
+        );
+        assert!(svg.contains(&format!(
+            "width=\"{}\" height=\"{}\"",
+            frame.width, frame.height
+        )));
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_tokenize_input_x() {
-        use crate::languages::basic::Tokenizer;
-
-        let mut tokenizer = Tokenizer::new("INPUT X");
-        let tokens = tokenizer.tokenize().unwrap();
+    fn test_record_animated_gif() {
+        let mut app = TimeWarpApp::default();
+        app.execute_tw_basic("SETPC 4\nSETXY 100, 0\nSETXY 100, 50\nSETXY 0, 50\nSETXY 0, 0");
+        app.turtle_speed = 2.0;
+
+        let gif = app.animation_gif();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([gif[6], gif[7]]), 400);
+        assert_eq!(u16::from_le_bytes([gif[8], gif[9]]), 300);
+        assert!(gif.windows(11).any(|w| w == b"NETSCAPE2.0"));
+        assert_eq!(gif.last(), Some(&0x3b));
+        // Four strokes at two a second: half a second each, then a pause at the end
+        let delays: Vec<u16> = gif
+            .windows(8)
+            .filter(|w| w[..4] == [0x21, 0xf9, 4, 0x04])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]))
+            .collect();
+        assert_eq!(delays, vec![50, 50, 50, 50 + GIF_FINAL_HOLD]);
+
+        // Each frame adds a stroke; the last is the finished drawing
+        let frame = app.export_frame();
+        let frames = canvas_export::replay_frames(&app.turtle_commands, &frame, 4);
+        let red = egui::Color32::from_rgb(220, 0, 0);
+        assert_eq!(frames[0].pixel(250, 150), red);
+        assert_eq!(frames[0].pixel(300, 175), egui::Color32::WHITE);
+        assert_eq!(frames[1].pixel(300, 175), red);
+        assert_eq!(
+            frames[3].pixels,
+            canvas_export::render(&app.turtle_commands, &frame).pixels
+        );
+    }
 
-        println!("Tokens for 'INPUT X': {:?}", tokens);
+    #[test]
+    fn test_canvas_grid_and_coordinates() {
+        // Grid steps are round numbers at least 25 pixels apart
+        assert_eq!(turtle::grid_spacing(1.0), 50.0);
+        assert_eq!(turtle::grid_spacing(2.0), 20.0);
+        assert_eq!(turtle::grid_spacing(5.0), 5.0);
+        assert_eq!(turtle::grid_spacing(0.1), 500.0);
+        assert_eq!(
+            turtle::grid_positions(-120.0, 75.0, 50.0),
+            vec![-100.0, -50.0, 0.0, 50.0]
+        );
 
-        // Should have INPUT, identifier X, EOF
-        assert!(tokens.len() >= 3);
+        // The readout reports the point SETXY would move the turtle to
+        let mut app = TimeWarpApp::default();
+        app.execute_tw_basic("SETXY 40, -30");
+        let turtle = &app.turtles[DEFAULT_TURTLE];
+        let target = egui::pos2(turtle.x, turtle.y);
+        let center = egui::pos2(200.0, 150.0);
+        let (pan, zoom) = (egui::vec2(10.0, -5.0), 2.0);
+        let screen = turtle::to_screen(center, pan, zoom, target);
+        assert_eq!(screen, egui::pos2(300.0, 80.0));
+        assert_eq!(
+            turtle::to_turtle(center, pan, zoom, screen),
+            egui::pos2(40.0, -30.0)
+        );
     }
 
     #[test]
-    fn test_parse_input_x() {
-        use crate::languages::basic::{Parser, Tokenizer};
-
-        let mut tokenizer = Tokenizer::new("INPUT X");
-        let tokens = tokenizer.tokenize().unwrap();
-        let mut parser = Parser::new(tokens);
-        let program = parser.parse_program().unwrap();
+    fn test_canvas_zoom_and_fit() {
+        // Zooming keeps the turtle point under the mouse where it was
+        let center = egui::pos2(200.0, 150.0);
+        let mouse = egui::pos2(260.0, 90.0);
+        let (pan, zoom) = (egui::vec2(15.0, -10.0), 1.0);
+        let under_mouse = turtle::to_turtle(center, pan, zoom, mouse);
+        let new_pan = turtle::zoom_about(center, pan, zoom, 2.5, mouse);
+        let moved = turtle::to_screen(center, new_pan, 2.5, under_mouse);
+        assert!((moved - mouse).length() < 1e-3);
 
-        println!("Parsed program for 'INPUT X': {:?}", program);
+        let mut app = TimeWarpApp::default();
+        app.zoom_canvas_at(center, mouse, 100.0);
+        assert_eq!(app.turtle_zoom, turtle::MAX_ZOOM);
+
+        // Fit Drawing centres the drawing and scales it to the canvas
+        app.execute_tw_basic("PENDOWN\nSETXY 100, 0\nSETXY 100, 50");
+        app.fit_canvas_to_drawing();
+        assert_eq!(app.turtle_pan, egui::vec2(-50.0, -25.0));
+        assert!(app.turtle_zoom > 1.0 && app.turtle_zoom <= turtle::MAX_ZOOM);
+
+        // With nothing drawn it falls back to the default view
+        app.clear_canvas();
+        app.fit_canvas_to_drawing();
+        assert_eq!((app.turtle_pan, app.turtle_zoom), (egui::Vec2::ZERO, 1.0));
+    }
 
-        // Should have one statement
-        assert_eq!(program.statements.len(), 1);
+    #[test]
+    fn test_canvas_resize_keeps_scale() {
+        let mut app = TimeWarpApp::default();
+        app.turtle_zoom = 1.5;
+
+        // Doubling the canvas doubles the zoom, so the same area stays in view
+        app.resize_canvas(egui::vec2(1000.0, 600.0));
+        assert_eq!(app.canvas_size, egui::vec2(1000.0, 600.0));
+        assert_eq!(app.turtle_zoom, 3.0);
+        app.resize_canvas(TURTLE_CANVAS_SIZE);
+        assert_eq!(app.turtle_zoom, 1.5);
+
+        // Exports follow the canvas size
+        app.resize_canvas(egui::vec2(800.0, 600.0));
+        let frame = app.export_frame();
+        assert_eq!((frame.width, frame.height), (800, 600));
+
+        // Reset shows the classic 400x300 area scaled to the canvas
+        app.turtle_pan = egui::vec2(30.0, 40.0);
+        app.reset_canvas_view();
+        assert_eq!((app.turtle_pan, app.turtle_zoom), (egui::Vec2::ZERO, 2.0));
     }
 
     #[test]
-    fn test_parse_print_semicolon() {
-        use crate::languages::basic::{Parser, Tokenizer};
+    fn test_fill_closed_shape() {
+        let square = "FORWARD 100\nRIGHT 90\nFORWARD 100\nRIGHT 90\nFORWARD 100\nRIGHT 90\nFORWARD 100\nPENUP\n";
 
-        let mut tokenizer = Tokenizer::new("PRINT 42;");
-        let tokens = tokenizer.tokenize().unwrap();
-        let mut parser = Parser::new(tokens);
-        let program = parser.parse_program().unwrap();
+        // Inside the square, FILL floods it with the pen colour
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic(&format!(
+            "{}RIGHT 135\nFORWARD 20\nSETPENCOLOR 4\nFILL",
+            square
+        ));
+        assert!(!result.contains("Error"), "{}", result);
+        let Some(turtle::TurtleOp::Fill { color, spans, .. }) = app.turtle_commands.last() else {
+            panic!("no fill in {:?}", app.turtle_commands);
+        };
+        assert_eq!(*color, app.turtles[DEFAULT_TURTLE].color);
+        let area: f32 = spans.iter().map(|span| span.width() * span.height()).sum();
+        assert!(area > 95.0 * 95.0 && area <= 100.0 * 100.0, "{}", area);
 
-        println!("Parsed program for 'PRINT 42;': {:?}", program);
+        // The fill is part of exports and counts as one replay step
+        let image = app.canvas_image();
+        let middle = app.export_frame().to_pixel(egui::pos2(50.0, 50.0));
+        assert_eq!(
+            image.pixel(middle.x as u32, middle.y as u32),
+            egui::Color32::from_rgb(220, 0, 0)
+        );
+        assert_eq!(turtle::stroke_count(&app.turtle_commands), 5);
 
-        // Should have one statement
-        assert_eq!(program.statements.len(), 1);
+        // Outside a closed shape there is nothing to fill
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic(&format!("{}SETXY 150, 50\nFILL", square));
+        assert!(!result.contains("Error"), "{}", result);
+        assert!(!app
+            .turtle_commands
+            .iter()
+            .any(|op| matches!(op, turtle::TurtleOp::Fill { .. })));
     }
 
     #[test]
-    fn test_print_with_line_number() {
+    fn test_arc_and_circle() {
         let mut app = TimeWarpApp::default();
+        let result =
+            app.execute_tw_basic("RIGHT 90\nARC 50, 90\nPENUP\nCIRCLE 10\nPENDOWN\nCIRCLE 20");
+        assert!(!result.contains("Error"), "{}", result);
 
-        // Test PRINT with line number (like user might enter)
-        let print_code = "10 PRINT X";
-        let result = app.execute_tw_basic(print_code);
+        // Both are centred on the turtle and start straight ahead of it
+        let center = egui::pos2(0.0, 0.0);
+        assert_eq!(
+            app.turtle_commands,
+            vec![
+                turtle::TurtleOp::Arc {
+                    center,
+                    radius: 50.0,
+                    start_angle: 90.0,
+                    sweep: 90.0,
+                },
+                turtle::TurtleOp::Arc {
+                    center,
+                    radius: 20.0,
+                    start_angle: 90.0,
+                    sweep: 360.0,
+                },
+            ]
+        );
+        let points = turtle::arc_points(center, 50.0, 90.0, 90.0);
+        assert!((points[0] - egui::pos2(0.0, 50.0)).length() < 1e-3);
+        assert!((points[points.len() - 1] - egui::pos2(-50.0, 0.0)).length() < 1e-3);
 
-        println!("PRINT with line number result: {:?}", result);
+        // The turtle doesn't move
+        let turtle = &app.turtles[DEFAULT_TURTLE];
+        assert_eq!((turtle.x, turtle.y, turtle.angle), (0.0, 0.0, 90.0));
 
-        // Should not crash with parse error
-        assert!(!result.contains("ParseError"));
-        // Should contain the variable value
-        assert!(result.contains("0"));
+        assert!(app.execute_tw_basic("CIRCLE 0").contains("Error"));
     }
 
     #[test]
-    fn test_print_no_space() {
+    fn test_label_text() {
         let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic("LABELSIZE 20\nRIGHT 90\nLABEL \"Hi\"");
+        assert!(!result.contains("Error"), "{}", result);
 
-        // Test PRINTX (no space) - this should cause a parse error
-        let print_code = "PRINTX";
-        let result = app.execute_tw_basic(print_code);
-
-        println!("PRINT no space result: {:?}", result);
+        // Written at the turtle, along its heading, at the chosen size
+        assert_eq!(
+            app.turtle_commands.last(),
+            Some(&turtle::TurtleOp::Text {
+                at: egui::pos2(0.0, 0.0),
+                text: "Hi".to_string(),
+                angle: 90.0,
+                size: 20.0,
+            })
+        );
+        let svg = canvas_export::to_svg(&app.turtle_commands, &app.export_frame());
+        assert!(svg.contains("transform=\"rotate(90 200 150)\""), "{}", svg);
+        assert!(svg.contains("font-size=\"20\""), "{}", svg);
 
-        // This should contain a parse error
-        assert!(result.contains("ParseError"));
+        assert!(app.execute_tw_basic("LABELSIZE -1").contains("Error"));
     }
 
     #[test]
-    fn test_print_lowercase() {
+    fn test_canvas_background() {
         let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic("SETBACKGROUND 1\nFORWARD 50\nCLEARSCREEN");
+        assert!(!result.contains("Error"), "{}", result);
 
-        // Test print x (lowercase) - should work since tokenizer uppercases
-        let print_code = "print x";
-        let result = app.execute_tw_basic(print_code);
-
-        println!("PRINT lowercase result: {:?}", result);
+        // Clearing the screen clears to the new colour
+        let blue = egui::Color32::from_rgb(0, 0, 255);
+        assert_eq!(app.canvas_background, blue);
+        assert_eq!(app.canvas_image().pixel(5, 5), blue);
+        let svg = canvas_export::to_svg(&app.turtle_commands, &app.export_frame());
+        assert!(svg.contains("fill=\"#0000ff\""), "{}", svg);
 
-        // Should not crash with parse error
-        assert!(!result.contains("ParseError"));
-        // Should contain the variable value
-        assert!(result.contains("0"));
+        app.execute_tw_basic("SETBG 255, 255, 200");
+        assert_eq!(
+            app.canvas_background,
+            egui::Color32::from_rgb(255, 255, 200)
+        );
+        assert!(app.execute_tw_basic("SETBG 1, 2").contains("Error"));
     }
 
     #[test]
-    fn test_let_and_print() {
+    fn test_hide_and_show_turtle() {
         let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic("FORWARD 20\nHIDETURTLE\nFORWARD 20");
+        assert!(!result.contains("Error"), "{}", result);
 
-        // Test LET X = 5 : PRINT X
-        let code = "LET X = 5 : PRINT X";
-        let result = app.execute_tw_basic(code);
+        // Hidden turtles keep drawing
+        assert!(!app.turtles[DEFAULT_TURTLE].visible);
+        assert_eq!(turtle::stroke_count(&app.turtle_commands), 2);
 
-        println!("LET and PRINT result: {:?}", result);
+        app.execute_tw_basic("SHOWTURTLE");
+        assert!(app.turtles[DEFAULT_TURTLE].visible);
 
-        // Should not crash with parse error
-        assert!(!result.contains("ParseError"));
-        // Should contain 5
-        assert!(result.contains("5"));
+        // The toolbar toggle covers every turtle
+        app.execute_tw_basic("TURTLE \"B\"\nFORWARD 5");
+        app.set_turtles_visible(false);
+        assert!(app.turtles.values().all(|state| !state.visible));
     }
 
     #[test]
-    fn test_print_multiple_vars_no_comma() {
+    #[cfg(feature = "logo")]
+    fn test_logo_procedures_and_repeat() {
         let mut app = TimeWarpApp::default();
-
-        // Test PRINT X Y (without comma) - should cause parse error
-        let print_code = "PRINT X Y";
-        let result = app.execute_tw_basic(print_code);
-
-        println!("PRINT multiple vars no comma result: {:?}", result);
-
-        // This should cause a parse error
-        assert!(result.contains("ParseError"));
+        app.language = Language::Logo;
+        app.code = "TO square :size\n\
+                    \x20 REPEAT 4 [FD :size RT 90]\n\
+                    END\n\
+                    TO double :n\n\
+                    \x20 OUTPUT :n * 2\n\
+                    END\n\
+                    square 50\n\
+                    PRINT [four sides]\n\
+                    PRINT double 21\n\
+                    PRINT heading\n"
+            .to_string();
+        app.execute_code();
+        assert_eq!(app.output, "four sides\n42\n0\n");
+
+        // Logo starts facing up, so the first side runs up the canvas
+        assert_eq!(turtle::stroke_count(&app.turtle_commands), 4);
+        match app.turtle_commands.first() {
+            Some(turtle::TurtleOp::Line { to, .. }) => {
+                assert!(to.x.abs() < 0.01 && (to.y + 50.0).abs() < 0.01, "{:?}", to);
+            }
+            other => panic!("expected a line, got {:?}", other),
+        }
+        let state = &app.turtles[DEFAULT_TURTLE];
+        assert!(state.x.abs() < 0.01 && state.y.abs() < 0.01);
+
+        // Output before an error is kept
+        let result = app.execute_with(Language::Logo, "PRINT \"hi\nFD");
+        assert!(result.starts_with("hi\nError"), "{}", result);
+        assert!(result.contains("Not enough inputs to FD"), "{}", result);
+        let result = app.execute_with(Language::Logo, "JUMP 10");
+        assert!(result.contains("I don't know how to JUMP"), "{}", result);
+
+        // Opening a Logo file switches the language
+        assert_eq!(Language::from_extension("twl"), Some(Language::Logo));
+        assert_eq!(Language::from_extension("twb"), Some(Language::Basic));
     }
 
     #[test]
-    fn test_print_x_and_printx() {
+    #[cfg(feature = "pilot")]
+    fn test_pilot_quiz() {
         let mut app = TimeWarpApp::default();
+        app.language = Language::Pilot;
+        app.code = "C:#TRIES = 0\n\
+                    *ASK\n\
+                    T:Capital of France?\n\
+                    A:$ANSWER\n\
+                    C:#TRIES = #TRIES + 1\n\
+                    M:PARIS\n\
+                    TN:No, try again.\n\
+                    JN:*ASK\n\
+                    U:*PRAISE\n\
+                    T:You said $ANSWER after #TRIES tries.\n\
+                    E:\n\
+                    *PRAISE\n\
+                    T:Correct!\n\
+                    E:\n"
+            .to_string();
+        app.execute_code();
+        assert!(app.waiting_for_input);
+        assert_eq!(app.output, "Capital of France?\n> ");
 
-        // Test PRINT X : PRINTX (what user entered)
-        let code = "PRINT X\nPRINTX";
-        let result = app.execute_tw_basic(code);
-
-        println!("PRINT X and PRINTX result: {:?}", result);
+        // Each A: waits again until M: finds a match (typos forgiven)
+        app.user_input = "London".to_string();
+        app.submit_program_input();
+        assert!(app.waiting_for_input);
+        app.user_input = "I think Pariss".to_string();
+        app.submit_program_input();
+        assert!(!app.waiting_for_input);
+        assert!(app.waiting_program.is_none());
+        assert_eq!(
+            app.output,
+            "Capital of France?\n> London\nNo, try again.\nCapital of France?\n\
+             > I think Pariss\nCorrect!\nYou said I think Pariss after 2 tries.\n"
+        );
 
-        // Should have parse error for PRINTX with no expression
-        assert!(result.contains("ParseError"));
-        assert!(result.contains("Unexpected token in expression"));
+        let result = app.execute_with(Language::Pilot, "J:*NOWHERE");
+        assert!(result.contains("no label *NOWHERE"), "{}", result);
+        let result = app.execute_with(Language::Pilot, "X:hello");
+        assert!(result.contains("not a PILOT statement"), "{}", result);
     }
 
     #[test]
-    fn test_letx_equals_five() {
+    #[cfg(feature = "pascal")]
+    fn test_pascal_procedures_and_readln() {
         let mut app = TimeWarpApp::default();
+        app.language = Language::Pascal;
+        app.code = "program Scores;\n\
+                    type Pupil = record name: string; score: integer; end;\n\
+                    var pupils: array[1..2] of Pupil;\n\
+                    \x20   i: integer;\n\
+                    procedure Bump(var n: integer; by: integer);\n\
+                    begin n := n + by end;\n\
+                    function Average: real;\n\
+                    var total, i: integer;\n\
+                    begin\n\
+                    \x20 total := 0;\n\
+                    \x20 for i := 1 to 2 do total := total + pupils[i].score;\n\
+                    \x20 Average := total / 2\n\
+                    end;\n\
+                    begin\n\
+                    \x20 for i := 1 to 2 do\n\
+                    \x20 begin\n\
+                    \x20   write('Name? ');\n\
+                    \x20   readln(pupils[i].name);\n\
+                    \x20   pupils[i].score := 10 * i;\n\
+                    \x20 end;\n\
+                    \x20 Bump(pupils[2].score, 5);\n\
+                    \x20 writeln(pupils[2].name, ' has ', pupils[2].score);\n\
+                    \x20 writeln('Average: ', Average:0:1)\n\
+                    end.\n"
+            .to_string();
+        app.execute_code();
+        assert!(app.waiting_for_input);
+        assert_eq!(app.output, "Name? ");
 
-        // Test LETX=5 (variable named LETX)
-        let code = "LETX=5\nPRINT LETX";
-        let result = app.execute_tw_basic(code);
-
-        println!("LETX=5 result: {:?}", result);
+        // Each readln waits again; earlier answers are kept
+        app.user_input = "Ada".to_string();
+        app.submit_program_input();
+        assert!(app.waiting_for_input);
+        assert_eq!(app.output, "Name? Ada\nName? ");
+        app.user_input = "Alan".to_string();
+        app.submit_program_input();
+        assert!(!app.waiting_for_input);
+        assert!(app.waiting_program.is_none());
+        assert_eq!(
+            app.output,
+            "Name? Ada\nName? Alan\nAlan has 25\nAverage: 17.5\n"
+        );
 
-        // Should work - LETX is a valid variable name
-        assert!(result.contains("5"));
+        // Output before an error is kept alongside it
+        let result = app.execute_with(
+            Language::Pascal,
+            "var i: integer;\nbegin\n  writeln('start');\n  i := 2.5\nend.",
+        );
+        assert!(result.starts_with("start\nError:"), "{}", result);
+        assert!(result.contains("Line 4"), "{}", result);
+        let result = app.execute_with(Language::Pascal, "begin\n  writeln('no end')\n");
+        assert!(result.contains("Error: E1"), "{}", result);
     }
 
     #[test]
-    fn test_input_and_print() {
+    #[cfg(feature = "prolog")]
+    fn test_language_selector_and_prolog() {
         let mut app = TimeWarpApp::default();
+        app.set_language(Language::Prolog);
+        assert_eq!(app.language, Language::Prolog);
+        assert_eq!(app.new_file_extension, "tpr");
+        assert!(app.get_language_keywords().contains(&"findall"));
+
+        app.code = "likes(mary, wine).\n\
+                    likes(john, X) :- likes(X, wine).\n\
+                    ?- likes(john, Who).\n"
+            .to_string();
+        app.execute_code();
+        assert_eq!(app.output, "?- likes(john, Who).\nWho = mary\n");
+
+        // A saved file keeps its own extension
+        app.last_file_path = Some("scores.twb".to_string());
+        app.set_language(Language::Basic);
+        assert_eq!(app.new_file_extension, "tpr");
+    }
 
-        // Test INPUT X : PRINT X
-        let code = "INPUT X\nPRINT X";
-        let result = app.execute_tw_basic(code);
-
-        println!("INPUT and PRINT result: {:?}", result);
-        println!("Waiting for input: {}", app.waiting_for_input);
+    #[test]
+    fn test_language_backends() {
+        for &language in Language::ALL {
+            let backend = language.backend();
+            assert!(!backend.keywords().is_empty(), "{}", language.name());
+            assert_eq!(
+                Language::from_extension(language.extension()),
+                Some(language)
+            );
+        }
 
-        // Should be waiting for input
-        assert!(app.waiting_for_input);
+        // Comments come from the backend of the language being edited
+        #[cfg(feature = "prolog")]
+        {
+            let comments = Language::Prolog.backend().comment_prefixes();
+            let palette = theme::Theme::Light.palette();
+            let highlighted = highlight::highlight_line("% note", &[], comments, palette);
+            assert_eq!(highlighted[0].1, egui::Color32::from_rgb(0, 128, 0));
+        }
 
-        // Simulate providing input
-        if let Some(ref mut interpreter) = app.basic_interpreter {
-            interpreter.provide_input("42");
-            let continue_result = interpreter.execute("").unwrap();
-            match continue_result {
-                crate::languages::basic::ExecutionResult::Complete { output, .. } => {
-                    app.output = output;
-                }
-                _ => panic!("Expected Complete"),
-            }
+        // A paused run carries on through the same backend
+        let mut app = TimeWarpApp::default();
+        #[cfg(feature = "pilot")]
+        {
+            app.language = Language::Pilot;
+            app.code = "T:Name?\nA:$NAME\nT:Hi $NAME\n".to_string();
+            app.execute_code();
+            assert!(app.waiting_for_input);
+            app.user_input = "Sam".to_string();
+            app.submit_program_input();
+            assert!(app.output.ends_with("Hi Sam\n"), "{}", app.output);
         }
 
-        println!("Final output: {:?}", app.output);
-        // Should contain the input echo and the PRINT output
-        assert!(app.output.contains("42"));
-        assert!(app.output == "42\n42\n");
+        // BASIC's INPUT waits the same way
+        app.language = Language::Basic;
+        app.code = "10 INPUT \"Age\"; A\n20 PRINT A + 1\n".to_string();
+        app.execute_code();
+        assert!(app.waiting_for_input);
+        assert_eq!(app.output, "Age");
+        app.user_input = "41".to_string();
+        app.submit_program_input();
+        assert!(!app.waiting_for_input);
+        assert_eq!(app.output, "Age41\n42\n");
     }
 
     #[test]
-    fn test_print_semicolon() {
-        let mut app = TimeWarpApp::default();
-
-        // Test PRINT X; (should not add newline)
-        let code = "PRINT 42;";
-        let result = app.execute_tw_basic(code);
+    fn test_assert_and_run_tests() {
+        use time_warp_core::basic::Interpreter;
 
-        println!("PRINT with semicolon result: {:?}", result);
+        // A failed ASSERT is reported and recorded, and the program carries on
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute(
+                "10 X = 0.1\n20 ASSERT X = 0.1\n30 ASSERT X > 1, \"too small\"\n40 PRINT \"end\"\n",
+            )
+            .unwrap();
+        match result {
+            time_warp_core::basic::ExecutionResult::Complete { output, .. } => {
+                assert_eq!(output, "ASSERT failed at line 3: too small\nend\n")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let results = interpreter.assertion_results();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].passed && !results[1].passed);
+        assert_eq!(results[1].line, 3);
 
-        // Should not end with newline
-        assert!(!result.ends_with("\n"));
-        assert!(result == "42");
+        let mut app = TimeWarpApp::default();
+        app.code = "10 ASSERT 1 + 1 = 2\n20 ASSERT 2 * 2 = 5, \"times\"\n".to_string();
+        app.run_tests();
+        assert!(app
+            .output
+            .contains("🧪 Tests: 1 passed, 1 failed\n  ✗ line 2: times\n"));
+        assert!(!app.testing);
+
+        // A program that asks for input is summarized once it finishes
+        app.code = "10 INPUT A\n20 ASSERT A = 4, \"four\"\n".to_string();
+        app.run_tests();
+        assert!(app.waiting_for_input);
+        assert!(!app.output.contains("Tests"));
+        app.user_input = "4".to_string();
+        app.submit_program_input();
+        assert!(app.output.ends_with("🧪 Tests: 1 passed, 0 failed\n"));
+
+        // A plain run doesn't add a summary
+        app.execute_code();
+        assert!(!app.output.contains("Tests"));
+
+        #[cfg(feature = "logo")]
+        {
+            app.language = Language::Logo;
+            app.run_tests();
+            assert!(app.output.contains("TW BASIC"));
+        }
     }
 
     #[test]
-    fn test_print_gw_basic_features() {
-        let mut app = TimeWarpApp::default();
+    fn test_scripted_input() {
+        use time_warp_core::basic::{ExecutionResult, Interpreter};
 
-        // Test comma tabulation (GW-BASIC style - every 14 characters)
-        let comma_code = "PRINT \"A\",\"B\",\"C\"";
-        let result1 = app.execute_tw_basic(comma_code);
-        println!("PRINT comma tabulation result: {:?}", result1);
-        // "A" should be followed by spaces to reach column 14, then "B" at column 15, etc.
+        // Queued answers are used in order and echoed after their prompts
+        let mut interpreter = Interpreter::new();
+        interpreter.queue_input(["Ada".to_string(), "36".to_string()]);
+        let code = "10 INPUT \"Name? \"; N$\n20 INPUT \"Age? \"; A\n30 PRINT N$; A + 1\n";
+        match interpreter.execute(code).unwrap() {
+            ExecutionResult::Complete { output, .. } => {
+                assert_eq!(output, "Name? Ada\nAge? 36\nAda37\n")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
 
-        // Test TAB function
-        let tab_code = "PRINT \"HELLO\";TAB(15);\"WORLD\"";
-        let result2 = app.execute_tw_basic(tab_code);
-        println!("PRINT TAB function result: {:?}", result2);
-        // Should have "HELLO" followed by spaces to column 15, then "WORLD"
+        // Once the answers run out the program waits as usual
+        let mut interpreter = Interpreter::new();
+        interpreter.queue_input(["1".to_string()]);
+        match interpreter
+            .execute("10 INPUT A\n20 INPUT B\n30 PRINT A + B\n")
+            .unwrap()
+        {
+            ExecutionResult::NeedInput { partial_output, .. } => {
+                assert_eq!(partial_output, "? 1\n")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
 
-        // Test SPC function
-        let spc_code = "PRINT \"TEST\";SPC(3);\"SPACES\"";
-        let result3 = app.execute_tw_basic(spc_code);
-        println!("PRINT SPC function result: {:?}", result3);
-        // Should have "TEST" followed by 3 spaces, then "SPACES"
+        // Every language that asks for input takes scripted answers
+        for (name, code) in [
+            ("PILOT", "T:Name?\nA:$NAME\nT:Hi $NAME\n"),
+            (
+                "Pascal",
+                "program Hi;\nvar name: string;\nbegin\n  readln(name);\n  writeln('Hi ', name)\nend.\n",
+            ),
+        ] {
+            let Some(language) = Language::from_name(name) else {
+                continue; // not in this build
+            };
+            let mut backend = language.backend();
+            backend.queue_input(vec!["Ada".to_string()]);
+            match backend.execute(code, 10_000).unwrap() {
+                ExecutionResult::Complete { output, .. } => {
+                    assert!(output.contains("Hi Ada"), "{}: {}", language.name(), output)
+                }
+                other => panic!("{}: unexpected result: {:?}", language.name(), other),
+            }
+        }
 
-        // Verify all contain expected content
-        assert!(result1.contains("A"));
-        assert!(result1.contains("B"));
-        assert!(result1.contains("C"));
-        assert!(result2.contains("HELLO"));
-        assert!(result2.contains("WORLD"));
-        assert!(result3.contains("TEST"));
-        assert!(result3.contains("SPACES"));
+        // The IDE field feeds runs one answer per line
+        let mut app = TimeWarpApp::default();
+        app.scripted_input = "3\n4\n".to_string();
+        app.code = "10 INPUT A\n20 INPUT B\n30 PRINT A * B\n".to_string();
+        app.execute_code();
+        assert!(!app.waiting_for_input);
+        assert!(app.output.contains("12"));
     }
 
     #[test]
-    fn test_def_fn_functions() {
+    fn test_dump_ast() {
+        // Dump AST shows the program's syntax tree as JSON
         let mut app = TimeWarpApp::default();
+        app.code = "10 GOTO 10\n".to_string();
+        app.dump_ast();
+        assert!(app.output.contains("\"type\": \"Goto\""));
+    }
 
-        // Test DEF FN and calling user-defined functions
-        let def_code = "DEF FN SQUARE(X) = X * X\nPRINT FN SQUARE(5)";
-        let result = app.execute_tw_basic(def_code);
-        println!("DEF FN result: {:?}", result);
+    #[test]
+    fn test_syntax_tree() {
+        use time_warp_core::basic::tree::{parse_to_tree, SyntaxNode};
+
+        let labels = |node: &SyntaxNode| -> Vec<String> {
+            node.children
+                .iter()
+                .map(|child| child.label.clone())
+                .collect()
+        };
+        let tree = parse_to_tree("PRINT 1+2*3\nIF X > -1 THEN GOTO 1\n").unwrap();
+        assert_eq!(tree.len(), 2);
+        let print = &tree[0];
+        assert_eq!(print.label, "line 1: PRINT");
+        let sum = &print.children[0];
+        assert_eq!(sum.label, "+ — 1 + (2 * 3)");
+        assert_eq!(labels(sum), vec!["1", "* — 2 * 3"]);
+        assert_eq!(labels(&sum.children[1]), vec!["2", "3"]);
+
+        let branch = &tree[1];
+        assert_eq!(branch.label, "line 2: IF");
+        assert_eq!(labels(branch), vec!["condition: > — X > (-1)", "THEN"]);
+        assert_eq!(labels(&branch.children[1]), vec!["GOTO"]);
 
-        // Should contain 25 (5 squared)
-        assert!(result.contains("25"));
+        let mut app = TimeWarpApp::default();
+        app.code = "10 LET A = (1 + 2) * 3\n".to_string();
+        app.refresh_syntax_tree();
+        let tree = app.syntax_tree.clone().unwrap();
+        assert_eq!(tree[0].children[0].label, "* — (1 + 2) * 3");
+
+        app.code = "10 PRINT (".to_string();
+        app.refresh_syntax_tree();
+        assert!(app.syntax_tree.is_err());
     }
 
     #[test]
-    fn test_clear_command() {
+    fn test_token_inspector() {
+        use time_warp_core::basic::Tokenizer;
+
+        let (tokens, error) = Tokenizer::new("10 PRINT \"Hi\"; X+1\nEND").tokenize_spanned();
+        assert!(error.is_none());
+        let summary: Vec<(String, &str, usize, usize)> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.kind(),
+                    token.lexeme.as_str(),
+                    token.line,
+                    token.column,
+                )
+            })
+            .collect();
+        assert_eq!(summary[0], ("Number".to_string(), "10", 1, 1));
+        assert_eq!(summary[1], ("Print".to_string(), "PRINT", 1, 4));
+        assert_eq!(summary[2], ("String".to_string(), "\"Hi\"", 1, 10));
+        assert_eq!(summary[5], ("Plus".to_string(), "+", 1, 17));
+        assert_eq!(summary[7], ("Eol".to_string(), "\n", 1, 19));
+        assert_eq!(summary[8], ("End".to_string(), "END", 2, 1));
+
+        // Tokens before an unexpected character are still shown
         let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT 1 @ 2\n".to_string();
+        let (tokens, error) = app.inspect_tokens();
+        assert_eq!(tokens.len(), 3);
+        let error = error.unwrap();
+        assert_eq!(error.message(), "Unexpected character '@'");
+        assert_eq!(
+            format!("{:?}", error.position().unwrap()),
+            "line 1, column 12"
+        );
+    }
 
-        // Set up some variables and functions
-        let setup_code = "LET X = 42\nDEF FN TEST(Y) = Y + 1\nDIM A(10)";
-        app.execute_tw_basic(setup_code);
+    #[test]
+    fn test_save_and_resume_run() {
+        use time_warp_core::basic::snapshot::Session;
+        use time_warp_core::basic::{ExecutionResult, Interpreter};
+        use time_warp_core::json::Json;
 
-        // Clear everything
-        let clear_code = "CLEAR";
-        let result = app.execute_tw_basic(clear_code);
-        println!("CLEAR result: {:?}", result);
+        let code = "10 DIM A(3)\n20 DEF FN SQ(X) = X * X\n30 FOR I = 1 TO 2\n40 S = S + FN SQ(I + 1)\n50 FORWARD 10\n60 NEXT I\n70 GOSUB 100\n80 PRINT S, N, T$\n90 END\n100 T$ = \"sub\"\n110 INPUT \"N? \"; N\n120 RETURN\n";
 
-        // Should contain confirmation message
-        assert!(result.contains("cleared"));
+        // The interpreter's state survives a round trip through JSON
+        let mut interpreter = Interpreter::new();
+        let ExecutionResult::NeedInput {
+            partial_graphics, ..
+        } = interpreter.execute(code).unwrap()
+        else {
+            panic!("expected INPUT");
+        };
+        let session = Session {
+            source: code.to_string(),
+            interpreter,
+            output: "N? ".to_string(),
+            graphics: partial_graphics,
+            prompt: Some("N? ".to_string()),
+        };
+        let saved = session.to_json().to_string();
+        let mut restored = Session::from_json(&Json::parse(&saved).unwrap()).unwrap();
+        assert_eq!(restored.graphics.len(), 2);
+        assert_eq!(
+            restored.interpreter.save_state(),
+            session.interpreter.save_state()
+        );
+        match restored.interpreter.provide_input("7").unwrap() {
+            ExecutionResult::Complete { output, .. } => assert_eq!(output, "7\n13\t7\tsub\n"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(Session::from_json(&Json::parse("{}").unwrap()).is_err());
+
+        // The IDE saves a run waiting on INPUT and picks it up in a new window
+        let path =
+            std::env::temp_dir().join(format!("tw_session_{}.twsession", std::process::id()));
+        let mut app = TimeWarpApp::default();
+        assert!(app.save_run_state_to(&path).is_err());
+        app.code = code.to_string();
+        app.execute_code();
+        assert!(app.waiting_for_input);
+        app.save_run_state_to(&path).unwrap();
+
+        let mut resumed = TimeWarpApp::default();
+        resumed.resume_run_state_from(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(resumed.code, code);
+        assert!(resumed.waiting_for_input);
+        assert_eq!(resumed.input_prompt, "N? ");
+        assert_eq!(resumed.turtle_commands.len(), app.turtle_commands.len());
+        assert_eq!(resumed.turtles["MAIN"].y, app.turtles["MAIN"].y);
+        resumed.user_input = "3".to_string();
+        resumed.submit_program_input();
+        assert!(
+            resumed.output.ends_with("13\t3\tsub\n"),
+            "{}",
+            resumed.output
+        );
     }
 
     #[test]
-    fn test_for_loop_simple() {
-        let mut app = TimeWarpApp::default();
+    #[cfg(feature = "logo")]
+    fn test_session_restore() {
+        let root = std::env::temp_dir().join(format!("tw_last_session_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let storage = storage::StoragePaths::under(&root);
 
-        // Test just FOR loop
-        let code = "for i=1 to 3\nprint i\nnext";
-        let result = app.execute_tw_basic(code);
+        let mut app = TimeWarpApp::default();
+        app.storage = storage.clone();
+        app.set_language(Language::Logo);
+        app.code = "FORWARD 50 ; not saved yet\n".to_string();
+        app.active_tab = 1;
+        app.breakpoints.insert("<untitled>".to_string(), vec![3, 7]);
+        app.turtle_zoom = 2.5;
+        app.turtle_pan = egui::vec2(-40.0, 12.0);
+        app.window_geometry = Some(session::WindowGeometry {
+            position: Some(egui::pos2(30.0, 60.0)),
+            size: egui::vec2(1024.0, 700.0),
+            maximized: false,
+        });
+        app.save_session().unwrap();
+        assert!(session::session_file(&storage).is_file());
+
+        let mut restored = TimeWarpApp::default();
+        restored.storage = storage.clone();
+        restored.restore_session();
+        assert_eq!(restored.language, Language::Logo);
+        assert_eq!(restored.code, "FORWARD 50 ; not saved yet\n");
+        assert_eq!(restored.active_tab, 1);
+        assert_eq!(restored.breakpoints["<untitled>"], vec![3, 7]);
+        assert_eq!(restored.turtle_zoom, 2.5);
+        assert_eq!(restored.turtle_pan, egui::vec2(-40.0, 12.0));
+        assert_eq!(session::saved_window(&storage), app.window_geometry);
+
+        // Nothing changed, so nothing is rewritten
+        std::fs::remove_file(session::session_file(&storage)).unwrap();
+        restored.window_geometry = app.window_geometry;
+        restored.save_session().unwrap();
+        assert!(!session::session_file(&storage).exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
 
-        // Should work and produce 1\n2\n3\n
-        assert!(result == "1\n2\n3\n");
-        assert!(!result.contains("timeout"));
+    #[test]
+    #[cfg(feature = "logo")]
+    fn test_split_view() {
+        let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT 1".to_string();
+        app.new_document();
+        app.code = "FD 10".to_string();
+        app.language = Language::Logo;
+        app.new_document();
+
+        // The split starts on the file being edited
+        app.toggle_split_view();
+        assert_eq!(app.split_document, Some(2));
+        app.toggle_split_view();
+        assert_eq!(app.split_document, None);
+
+        // Swapping edits the split tab and shows the old one beside it
+        app.split_document = Some(1);
+        app.swap_split_view();
+        assert_eq!((app.active_document, app.split_document), (1, Some(2)));
+        assert_eq!((app.code.as_str(), app.language), ("FD 10", Language::Logo));
+
+        // Closing tabs keeps the split on the same file, or ends it
+        app.close_document(0);
+        assert_eq!((app.active_document, app.split_document), (0, Some(1)));
+        app.close_document(1);
+        assert_eq!(app.split_document, None);
     }
 
     #[test]
-    fn test_for_loop_program() {
+    #[cfg(feature = "logo")]
+    fn test_editor_tabs() {
+        let root = std::env::temp_dir().join(format!("tw_editor_tabs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let first = root.join("first.twb").display().to_string();
+        let second = root.join("square.logo").display().to_string();
+        std::fs::write(&first, "10 PRINT 1").unwrap();
+        std::fs::write(&second, "FD 10").unwrap();
+
+        // The empty program at startup is reused; the next file gets a tab
         let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        assert!(app.open_location(&first));
+        assert_eq!(app.documents.len(), 1);
+        app.code.push_str(": PRINT 2");
+        app.save_undo_state();
+        app.breakpoints.insert(app.document_name(), vec![1]);
+        assert!(app.open_location(&second));
+        assert_eq!((app.documents.len(), app.active_document), (2, 1));
+        assert_eq!((app.code.as_str(), app.language), ("FD 10", Language::Logo));
+        assert!(app.undo_history.is_empty());
+        assert!(app.current_breakpoints().is_empty());
+        assert_eq!(
+            app.document_tabs(),
+            vec![
+                ("first.twb".to_string(), true),
+                ("square.logo".to_string(), false)
+            ]
+        );
 
-        // Test the user's program
-        let code = "10 cls\n20 print \"Hello\"\n30 for i=1 to 10\n40 print 1/i\n50 next\n60 end";
-        let result = app.execute_tw_basic(code);
-
-        // Should work and contain Hello and the divisions
-        assert!(result.contains("Hello"));
-        assert!(result.contains("0.1"));
-        assert!(!result.contains("timeout"));
+        // Each tab keeps its own buffer, undo history and breakpoints
+        app.switch_document(0);
+        assert_eq!(app.code, "10 PRINT 1: PRINT 2");
+        assert_eq!(app.language, Language::Basic);
+        assert_eq!(app.undo_history.last(), Some(&app.code));
+        assert_eq!(app.current_breakpoints(), vec![1]);
+
+        // Opening a file that is already open goes to its tab
+        assert!(app.open_location(&second));
+        assert_eq!((app.documents.len(), app.active_document), (2, 1));
+
+        // New tabs are numbered so their breakpoints stay apart
+        app.new_document();
+        assert_eq!(app.document_name(), "untitled");
+        app.new_document();
+        assert_eq!(app.document_name(), "untitled 2");
+        assert_eq!(app.documents.len(), 4);
+
+        // Closing the active tab moves to the one that took its place
+        app.close_document(1);
+        assert_eq!(app.documents.len(), 3);
+        assert_eq!(app.document_name(), "untitled 2");
+        app.close_document(2);
+        assert_eq!(app.document_name(), "untitled");
+
+        // Every tab comes back in the next session
+        app.code = "PRINT \"NEW\"".to_string();
+        app.save_session().unwrap();
+        let mut restored = TimeWarpApp::default();
+        restored.storage = app.storage.clone();
+        restored.restore_session();
+        assert_eq!(restored.documents.len(), 2);
+        assert_eq!(restored.active_document, 1);
+        assert_eq!(restored.code, "PRINT \"NEW\"");
+        restored.switch_document(0);
+        assert_eq!(restored.last_file_path.as_deref(), Some(first.as_str()));
+        assert_eq!(restored.code, "10 PRINT 1: PRINT 2");
+        assert!(restored.is_dirty());
+
+        // Closing the last tab leaves an empty program
+        restored.close_document(1);
+        restored.close_document(0);
+        assert_eq!(restored.documents.len(), 1);
+        assert!(restored.is_blank_document());
+
+        let _ = std::fs::remove_dir_all(&root);
     }
 
     #[test]
-    fn test_forward_in_line_numbered_program() {
-        let mut app = TimeWarpApp::default();
+    fn test_unsaved_changes() {
+        let root = std::env::temp_dir().join(format!("tw_unsaved_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let first = root.join("first.twb").display().to_string();
+        let second = root.join("second.twb").display().to_string();
+        std::fs::write(&first, "10 PRINT 1").unwrap();
+        std::fs::write(&second, "10 PRINT 2").unwrap();
 
-        // Test FORWARD in a line-numbered BASIC program
-        let code = "10 FORWARD 5\n20 END";
-        let result = app.execute_tw_basic(code);
-        println!("FORWARD test result: {:?}", result);
-        println!("Turtle commands after FORWARD: {:?}", app.turtle_commands);
-        println!(
-            "Turtle state: x={}, y={}, angle={}",
-            app.turtle_state.x, app.turtle_state.y, app.turtle_state.angle
+        let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        let ctx = egui::Context::default();
+        assert!(app.open_location(&first));
+        assert!(app.open_location(&second));
+        app.update_window_title(&ctx);
+        assert_eq!(app.window_title, "second.twb — Time Warp IDE");
+        assert!(app.unsaved_documents().is_empty());
+
+        // Each tab tracks its own edits; the title marks the one in view
+        app.code.push_str(": PRINT 3");
+        app.switch_document(0);
+        app.code.push_str(": PRINT 4");
+        app.update_window_title(&ctx);
+        assert_eq!(app.window_title, "● first.twb — Time Warp IDE");
+        assert_eq!(app.unsaved_documents(), vec!["first.twb", "second.twb"]);
+
+        // Save All writes every edited tab and stays on the one in view
+        assert!(app.save_all_documents());
+        assert_eq!(app.active_document, 0);
+        assert_eq!(
+            std::fs::read_to_string(&first).unwrap(),
+            "10 PRINT 1: PRINT 4"
         );
-        assert!(result.contains("Moved forward"));
-        assert!(!app.turtle_commands.is_empty());
-        // Should have moved 5 units from (0, 0) to (5, 0)
-        assert_eq!(app.turtle_state.x, 5.0);
-        assert_eq!(app.turtle_state.y, 0.0);
+        assert_eq!(
+            std::fs::read_to_string(&second).unwrap(),
+            "10 PRINT 2: PRINT 3"
+        );
+        assert!(app.unsaved_documents().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
     }
 
     #[test]
-    fn test_forward_direct_command() {
+    #[cfg(feature = "pascal")]
+    fn test_dropped_files() {
+        let root = std::env::temp_dir().join(format!("tw_dropped_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let lesson = root.join("lesson.twp").display().to_string();
+        std::fs::write(&lesson, "begin\nend.").unwrap();
+
+        // A dropped file opens in its own tab and goes on the Recent list
         let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        app.code = "10 PRINT 1".to_string();
+        app.save_undo_state();
+        app.open_dropped_file(&lesson);
+        assert_eq!((app.documents.len(), app.language), (2, Language::Pascal));
+        assert_eq!(app.settings.recent_files, vec![lesson.clone()]);
+
+        // Dropping it again over unsaved edits asks first
+        app.code.push_str("\n{ mine }");
+        app.open_dropped_file(&lesson);
+        assert_eq!(app.reloading_document.as_deref(), Some(lesson.as_str()));
+        assert!(app.code.ends_with("{ mine }"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
 
-        // Test FORWARD as a direct command (not line-numbered) with longer distance
-        let code = "FORWARD 50";
-        let result = app.execute_tw_basic(code);
-        println!("Direct FORWARD test result: {:?}", result);
-        println!(
-            "Turtle commands after direct FORWARD: {:?}",
-            app.turtle_commands
-        );
-        println!(
-            "Turtle state: x={}, y={}, angle={}",
-            app.turtle_state.x, app.turtle_state.y, app.turtle_state.angle
-        );
-        assert!(result.contains("Moved forward"));
-        assert!(!app.turtle_commands.is_empty());
-        // Should have moved 50 units from (0, 0) to (50, 0)
-        assert_eq!(app.turtle_state.x, 50.0);
-        assert_eq!(app.turtle_state.y, 0.0);
+    #[test]
+    fn test_recent_files() {
+        let root = std::env::temp_dir().join(format!("tw_recent_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Newest first, each file once, and no more than ten
+        let mut recent = Vec::new();
+        for number in 0..12 {
+            recent_files::add_recent(&mut recent, &format!("lesson{}.twb", number));
+        }
+        recent_files::add_recent(&mut recent, "lesson5.twb");
+        assert_eq!(recent.len(), recent_files::MAX_RECENT_FILES);
+        assert_eq!(recent[..3], ["lesson5.twb", "lesson11.twb", "lesson10.twb"]);
+
+        // The list is kept in the settings file
+        let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        let lesson = root.join("lesson.twb").display().to_string();
+        let gone = root.join("gone.twb").display().to_string();
+        std::fs::write(&lesson, "10 PRINT 1").unwrap();
+        app.remember_recent_file(&gone);
+        app.remember_recent_file(&lesson);
+        let saved = settings::Settings::load(&app.storage.settings_file).unwrap();
+        assert_eq!(saved.recent_files, vec![lesson.clone(), gone.clone()]);
+
+        // Opening one from the list; a file that has gone is dropped from it
+        app.open_recent_file(&gone);
+        assert!(app.error_message.take().is_some());
+        app.open_recent_file(&lesson);
+        assert_eq!(app.code, "10 PRINT 1");
+        assert_eq!(app.settings.recent_files, vec![lesson]);
+        let _ = std::fs::remove_dir_all(&root);
     }
 
-    // ===== GW BASIC COMMAND TESTS =====
+    #[test]
+    fn test_html_export() {
+        // A text-only run: its output and the program, escaped, and no replay
+        let mut app = TimeWarpApp::default();
+        app.code = "PRINT \"1 < 2 & 3 > 2\"".to_string();
+        app.execute_code();
+        let page = app.html_page();
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<title>untitled</title>"));
+        assert!(page.contains("<pre id=\"output\">1 &lt; 2 &amp; 3 &gt; 2\n</pre>"));
+        assert!(page.contains("PRINT &quot;1 &lt; 2"));
+        assert!(!page.contains("<svg") && !page.contains("replay()"));
+
+        // A drawing is inline SVG, replayed at the canvas animation speed
+        app.code = "FORWARD 50\nRIGHT 90\nFORWARD 50".to_string();
+        app.turtle_speed = 4.0;
+        app.execute_code();
+        let page = app.html_page();
+        assert_eq!(page.matches("<line ").count(), 2);
+        assert!(page.contains("<script>") && page.contains("}, 250);"));
+        assert!(page.trim_end().ends_with("</html>"));
+
+        // With the web runtime, Run runs the program again in the browser
+        let mut page = html_export::Page {
+            title: "square",
+            language: "Logo",
+            code: "FORWARD 50",
+            output: "",
+            svg: None,
+            strokes_per_second: 4.0,
+            runtime: Some(b"\0asm"),
+        };
+        let html = html_export::to_html(&page);
+        assert!(html.contains("<button id=\"run\" data-language=\"Logo\" data-delay=\"250\">"));
+        assert!(html.contains("<div id=\"drawing\"></div>"));
+        assert!(html.contains("<pre id=\"output\"></pre>"));
+        assert!(html.contains("<script id=\"runtime\" type=\"application/wasm\">AGFzbQ==</script>"));
+        assert!(html.contains("<script type=\"module\">"));
+        page.runtime = None;
+        let html = html_export::to_html(&page);
+        assert!(!html.contains("id=\"run\"") && !html.contains("<script"));
+        if let Some(runtime) = html_export::WEB_RUNTIME {
+            assert!(runtime.starts_with(b"\0asm"));
+        }
+    }
 
     #[test]
-    fn test_file_io_commands() {
-        use crate::languages::basic::Interpreter;
+    fn test_qbasic_dialect() {
+        use time_warp_core::basic::dialect::Dialect;
+
+        let program = "' Count, choose and greet
+DECLARE SUB Greet (who$)
+total = 0
+n = 1
+DO WHILE n <= 3
+    total = total + n
+    n = n + 1
+LOOP
+IF total = 6 THEN
+    PRINT \"six\"
+ELSEIF total = 7 THEN
+    PRINT \"seven\"
+ELSE
+    PRINT \"other\"
+END IF
+k = 0
+DO
+    k = k + 1
+    IF k = 2 THEN
+        EXIT DO
+    END IF
+LOOP
+WHILE k < 5
+    k = k + 1
+WEND
+CALL Greet(\"Ada\")
+Greet \"Bob\"
+PRINT k
+END
+
+SUB Greet (who$)
+    PRINT \"Hi \"; who$
+END SUB
+";
+        // TW BASIC can't read it; QBasic runs it
+        let mut app = TimeWarpApp::default();
+        app.code = program.to_string();
+        app.execute_code();
+        assert!(app.output.contains("Error"), "{}", app.output);
+        app.settings.basic_dialect = Dialect::QBasic;
+        app.execute_code();
+        assert_eq!(app.output, "six\nHi Ada\nHi Bob\n5\n");
+
+        // Statements stay on the lines they were written on
+        let coverage = app.coverage.clone().unwrap();
+        assert!(coverage.executed.contains(&10) && !coverage.executed.contains(&12));
+        assert!(coverage.executable.contains(&32));
+    }
 
-        println!("=== TESTING FILE I/O COMMANDS ===");
+    #[test]
+    fn test_retro_dialects() {
+        use time_warp_core::basic::dialect::Dialect;
+
+        let run = |dialect: Dialect, code: &str| {
+            let mut app = TimeWarpApp::default();
+            app.settings.basic_dialect = dialect;
+            app.code = code.to_string();
+            app.execute_code();
+            app.output
+        };
 
-        // Test OPEN command
-        println!("\n--- Testing OPEN command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("OPEN \"test.txt\" FOR OUTPUT AS #1");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("OPEN result: {}", output);
-                assert!(output.contains("File opened") || output.is_empty()); // May be empty if not fully implemented
-            }
-            _ => println!("OPEN command executed (may not be fully implemented yet)"),
-        }
+        // Applesoft: HOME clears the screen, turtle words are plain names,
+        // commas move to 16-column zones and integers have two bytes
+        let output = run(
+            Dialect::Applesoft,
+            "10 PRINT \"GONE\"\n20 HOME\n30 LABEL = 5\n40 PRINT LABEL\n50 PRINT \"A\",\"B\"\n60 A% = -32768",
+        );
+        assert_eq!(output, "5\nA               B\n");
+        let output = run(Dialect::Applesoft, "10 A% = 40000");
+        assert!(output.contains("ILLEGAL QUANTITY"), "{}", output);
+
+        // Commodore 64: PETSCII CHR$, padded numbers, 10-column zones and a
+        // 40-column screen
+        let output = run(
+            Dialect::Commodore64,
+            "10 PRINT \"GONE\"\n20 PRINT CHR$(147);\"HELLO\";CHR$(5)\n30 PRINT 7;-2\n\
+             40 PRINT \"A\",\"B\"\n50 PRINT \"1234567890123456789012345678901234567890AB\"",
+        );
+        assert_eq!(
+            output,
+            "HELLO\n 7 -2 \nA         B\n1234567890123456789012345678901234567890\nAB\n"
+        );
 
-        // Test CLOSE command
-        println!("\n--- Testing CLOSE command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("CLOSE #1");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("CLOSE result: {}", output);
-            }
-            _ => println!("CLOSE command executed"),
-        }
+        // TW BASIC keeps its turtle words, plain CHR$ and CLS
+        let output = run(Dialect::TwBasic, "PRINT \"GONE\"\nCLS\nPRINT CHR$(65)");
+        assert_eq!(output, "A\n");
+        assert!(run(Dialect::TwBasic, "LABEL = 5").contains("Error"));
+    }
 
-        // Test PRINT# command
-        println!("\n--- Testing PRINT# command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("PRINT #1, \"Hello World\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("PRINT# result: {}", output);
-            }
-            _ => println!("PRINT# command executed"),
-        }
+    #[test]
+    fn test_strict_mode() {
+        use time_warp_core::basic::strict::{Severity, Strictness};
+        use time_warp_core::basic::Interpreter;
+
+        let run = |strictness: Strictness, code: &str| {
+            let mut app = TimeWarpApp::default();
+            app.settings.basic_strictness = strictness;
+            app.code = code.to_string();
+            app.execute_code();
+            (app.output, app.diagnostics)
+        };
 
-        // Test INPUT# command
-        println!("\n--- Testing INPUT# command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("INPUT #1, A$");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("INPUT# result: {}", output);
-            }
-            _ => println!("INPUT# command executed"),
-        }
+        // Permissive by default: unset variables are 0 and text counts as a number
+        let code = "A = B + 1\nPRINT A\nPRINT \"5\" * 2";
+        assert_eq!(
+            run(Strictness::Permissive, code),
+            ("1\n10\n".to_string(), vec![])
+        );
 
-        // Test KILL command
-        println!("\n--- Testing KILL command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("KILL \"test.txt\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("KILL result: {}", output);
-            }
-            _ => println!("KILL command executed"),
-        }
+        // Strict refuses to start a program that reads a variable never set
+        let (output, diagnostics) = run(Strictness::Strict, "PRINT 1\nA = TOTL + 1");
+        assert!(
+            output.contains("TOTL is used but never given a value at line 2"),
+            "{}",
+            output
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].severity),
+            (2, Severity::Error)
+        );
 
-        // Test NAME command
-        println!("\n--- Testing NAME command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("NAME \"old.txt\" AS \"new.txt\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("NAME result: {}", output);
-            }
-            _ => println!("NAME command executed"),
+        // ... and mixing text and numbers, unless VAL and STR$ say so
+        for code in ["PRINT \"5\" * 2", "A$ = 5", "A = \"5\""] {
+            let (output, _) = run(Strictness::Strict, code);
+            assert!(
+                output.contains("Strict mode doesn't"),
+                "{}: {}",
+                code,
+                output
+            );
         }
+        let (output, _) = run(
+            Strictness::Strict,
+            "PRINT VAL(\"5 apples\") * 2\nA$ = STR$(5)\nPRINT A$",
+        );
+        assert_eq!(output, "10\n 5\n");
 
-        // Test FILES command
-        println!("\n--- Testing FILES command ---");
+        // Warnings don't stop the program
         let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("FILES");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("FILES result: {}", output);
-            }
-            _ => println!("FILES command executed"),
-        }
-
-        println!("\n=== FILE I/O COMMANDS TEST COMPLETE ===");
+        interpreter.set_strictness(Strictness::Strict);
+        interpreter
+            .load(
+                "10 N = 1\n20 N$ = \"one\"\n30 IF 1 THEN PRINT N\n\
+                 40 FOR I = 1 TO 2 STEP 0\n50 NEXT I\n60 DEF FN SQ(X) = X * X",
+            )
+            .unwrap();
+        let found: Vec<(usize, Severity)> = interpreter
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.severity))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (2, Severity::Warning),
+                (3, Severity::Warning),
+                (4, Severity::Warning)
+            ]
+        );
     }
 
     #[test]
-    fn test_graphics_commands() {
-        use crate::languages::basic::Interpreter;
-
-        println!("=== TESTING GRAPHICS COMMANDS ===");
+    fn test_diagnostics() {
+        use time_warp_core::basic::strict::Severity;
 
-        // Test LINE command
-        println!("\n--- Testing LINE command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("LINE (10, 10)-(100, 100)");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("LINE result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty());
-            }
-            _ => println!("LINE command executed"),
-        }
+        let mut app = TimeWarpApp::default();
+        app.code = "10 TOTAL = 0\n20 UNUSED = 5\n30 GOSUB 100\n40 GOTO 999\n50 END\n\
+                    60 PRINT \"NEVER\"\n70 PRINT \"NOR THIS\"\n100 PRINT TOTAL\n110 RETURN"
+            .to_string();
+        let found = app.check_program();
+        let summary: Vec<(usize, Severity)> = found
+            .iter()
+            .map(|found| (found.line, found.severity))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (2, Severity::Warning),
+                (4, Severity::Error),
+                (6, Severity::Warning),
+                (7, Severity::Warning)
+            ]
+        );
+        assert_eq!(
+            found[0].message,
+            "UNUSED is given a value that is never used"
+        );
+        assert_eq!(found[1].message, "There is no line 999 to jump to");
+        assert!(found[2].message.contains("after END"));
+
+        // Clicking a finding selects its whole line
+        let selection = editor::line_selection(&app.code, 2);
+        assert_eq!(editor::selected_text(&app.code, selection), "20 UNUSED = 5");
+
+        // Every line that doesn't parse is listed, not only the first
+        app.code = "10 PRINT (\n20 PRINT 1\n30 X = \n40 PRINT 2".to_string();
+        let lines: Vec<usize> = app.check_program().iter().map(|found| found.line).collect();
+        assert_eq!(lines, [1, 3]);
+
+        // A run that fails to parse opens the panel
+        assert!(!app.show_diagnostics);
+        app.execute_code();
+        assert!(app.show_diagnostics);
+    }
 
-        // Test CIRCLE command
-        println!("\n--- Testing CIRCLE command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("CIRCLE (200, 200), 50");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("CIRCLE result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty());
-            }
-            _ => println!("CIRCLE command executed"),
-        }
+    #[test]
+    fn test_error_positions() {
+        use time_warp_core::basic::dialect::Dialect;
+        use time_warp_core::basic::{Interpreter, InterpreterError, Position};
 
-        // Test PSET command
-        println!("\n--- Testing PSET command ---");
+        // A parse error says where the token it stopped at is
         let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("PSET (150, 150)");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("PSET result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-            }
-            _ => println!("PSET command executed"),
-        }
+        let err = interpreter
+            .load("10 PRINT 1\n20 IF X > 1 PRINT 2")
+            .unwrap_err();
+        assert_eq!(
+            err.position(),
+            Some(Position {
+                line: 2,
+                column: 13
+            })
+        );
+        assert!(matches!(err.inner(), InterpreterError::ParseError { .. }));
+        assert_eq!(err.message(), "Expected Then, found Some(Print)");
 
-        // Test PRESET command
-        println!("\n--- Testing PRESET command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("PRESET (150, 150)");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("PRESET result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-            }
-            _ => println!("PRESET command executed"),
-        }
+        // and so does one from the tokenizer, at the start of its token
+        let err = interpreter.load("10 PRINT \"OPEN").unwrap_err();
+        assert_eq!(
+            err.position(),
+            Some(Position {
+                line: 1,
+                column: 10
+            })
+        );
 
-        // Test PAINT command
-        println!("\n--- Testing PAINT command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("PAINT (100, 100)");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("PAINT result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-            }
-            _ => println!("PAINT command executed"),
-        }
+        // The Output tab names the place
+        let mut app = TimeWarpApp::default();
+        let result = app.execute_tw_basic("10 PRINT 1\n20 IF X > 1 PRINT 2");
+        assert!(result.contains("Error: E1"), "{}", result);
+        assert!(result.contains("line 2, column 13"), "{}", result);
 
-        // Test DRAW command
-        println!("\n--- Testing DRAW command ---");
+        // A QBasic program's lines are rewritten, so only the line is kept
         let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("DRAW \"U10 D10 L10 R10\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("DRAW result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-            }
-            _ => println!("DRAW command executed"),
-        }
-
-        println!("\n=== GRAPHICS COMMANDS TEST COMPLETE ===");
+        interpreter.set_dialect(Dialect::QBasic);
+        let err = interpreter.load("PRINT 1\nIF X > 1 PRINT 2").unwrap_err();
+        assert_eq!(err.position(), None);
+        assert_eq!(err.message(), "Expected Then, found Some(Print) at line 2");
     }
 
     #[test]
-    fn test_sound_commands() {
-        use crate::languages::basic::Interpreter;
-
-        println!("=== TESTING SOUND COMMANDS ===");
-
-        // Test BEEP command
-        println!("\n--- Testing BEEP command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("BEEP");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("BEEP result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty()); // Should generate a sound command
-            }
-            _ => println!("BEEP command executed"),
-        }
+    fn test_runtime_error_lines() {
+        let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT 1\n20 X = 0\n30 PRINT 2 : PRINT 1 / X".to_string();
+        let result = app.execute_tw_basic(&app.code.clone());
+        let error = result.lines().last().unwrap();
+        assert_eq!(
+            error,
+            "Error: E201 Division by zero (line 3, column 14) in 30"
+        );
 
-        // Test SOUND command
-        println!("\n--- Testing SOUND command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("SOUND 440, 1000");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("SOUND result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty()); // Should generate a sound command
-            }
-            _ => println!("SOUND command executed"),
-        }
+        // Clicking it in the Output tab selects line 3
+        let line = diagnostics_panel::error_line(error).unwrap();
+        assert_eq!(line, 3);
+        let selection = editor::line_selection(&app.code, line);
+        assert_eq!(
+            editor::selected_text(&app.code, selection),
+            "30 PRINT 2 : PRINT 1 / X"
+        );
 
-        println!("\n=== SOUND COMMANDS TEST COMPLETE ===");
+        // Other output, and lines of included files, aren't taken for places
+        assert_eq!(diagnostics_panel::error_line("PRINTed line 3"), None);
+        assert_eq!(
+            diagnostics_panel::error_line(
+                "Error: E201 Division by zero (line 2 of lib.twb, column 4)"
+            ),
+            None
+        );
+        assert_eq!(
+            diagnostics_panel::error_line(
+                "Error: ParseError { code: UnsetVariable, message: \"X is used but never given a value at line 4\" }"
+            ),
+            Some(4)
+        );
     }
 
     #[test]
-    fn test_screen_control_commands() {
-        use crate::languages::basic::Interpreter;
+    fn test_error_squiggles() {
+        let mut app = TimeWarpApp::default();
+        // "π" is two bytes, so the spans after it are counted in bytes
+        app.code = "10 PRINT \"π\"\n20 IF X > 1 PRINT 2\n30 PRINT @\n40 PRINT (".to_string();
+        let underlined = |app: &TimeWarpApp| -> Vec<(usize, String)> {
+            app.diagnostics
+                .iter()
+                .map(|found| {
+                    let span = found.span.clone().expect("a parse error has a span");
+                    let characters = editor::span_characters(&app.code, found.line, &span);
+                    let text: String = app
+                        .code
+                        .chars()
+                        .skip(characters.start)
+                        .take(characters.len())
+                        .collect();
+                    (found.line, text)
+                })
+                .collect()
+        };
 
-        println!("=== TESTING SCREEN CONTROL COMMANDS ===");
+        // A run that fails to parse underlines each mistake
+        app.execute_code();
+        assert_eq!(
+            underlined(&app),
+            [
+                (2, "PRINT".to_string()),
+                (3, "@".to_string()),
+                (4, "(".to_string())
+            ]
+        );
 
-        // Test LOCATE command
-        println!("\n--- Testing LOCATE command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("LOCATE 10, 20");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("LOCATE result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty()); // Should generate a locate command
-            }
-            _ => println!("LOCATE command executed"),
-        }
+        // Spans count bytes of their own line
+        app.code = "10 A$ = \"é\" : PRINT A$ +".to_string();
+        app.diagnostics.clear();
+        app.execute_code();
+        assert_eq!(underlined(&app), [(1, "+".to_string())]);
 
-        // Test SCREEN command
-        println!("\n--- Testing SCREEN command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("SCREEN 1");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("SCREEN result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty()); // Should generate a screen command
-            }
-            _ => println!("SCREEN command executed"),
-        }
+        // A run that parses clears them
+        app.code = "10 PRINT 1".to_string();
+        app.execute_code();
+        assert!(app.diagnostics.is_empty());
+    }
 
-        // Test WIDTH command
-        println!("\n--- Testing WIDTH command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("WIDTH 80");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("WIDTH result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty()); // Should generate a width command
-            }
-            _ => println!("WIDTH command executed"),
-        }
+    #[test]
+    fn test_tokenized_basic_import() {
+        // File → Open reads a GW-BASIC binary as source; saving writes plain text
+        let program = [0xFF, 1, 1, 10, 0, 0x91, b' ', 0x12, 0, 0, 0];
+        let path = temp_path("tw_tokenized").with_extension("bas");
+        fs::write(&path, program).unwrap();
+        let mut app = TimeWarpApp::default();
+        assert!(app.open_location(&path.display().to_string()));
+        assert_eq!(app.code, "10 PRINT 1\n");
+        assert_eq!(app.language, Language::Basic);
+        let _ = fs::remove_file(&path);
+    }
 
-        // Test COLOR command
-        println!("\n--- Testing COLOR command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("COLOR 1, 2");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("COLOR result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-                assert!(!graphics_commands.is_empty()); // Should generate color commands
-            }
-            _ => println!("COLOR command executed"),
-        }
+    #[test]
+    fn test_standalone_export() {
+        let root = std::env::temp_dir().join(format!("tw_standalone_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("shapes.twb"), "FORWARD 50").unwrap();
 
-        // Test PALETTE command
-        println!("\n--- Testing PALETTE command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("PALETTE 0, 65535");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("PALETTE result: {}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
-            }
-            _ => println!("PALETTE command executed"),
-        }
+        // The bundle carries the program with its INCLUDE lines filled in
+        let mut app = TimeWarpApp::default();
+        app.last_file_path = Some(root.join("game.twb").display().to_string());
+        app.code = "'INCLUDE \"shapes.twb\"\nPRINT \"DONE\"".to_string();
+        let bundle = app.standalone_bundle().unwrap();
+        assert_eq!(bundle.name, "game");
+        assert_eq!(bundle.code, "FORWARD 50\nPRINT \"DONE\"\n");
+        assert_eq!(
+            standalone::Bundle::from_json(&bundle.to_json()),
+            Ok(bundle.clone())
+        );
+        assert!(standalone::Bundle::from_json(&time_warp_core::json::Json::Null).is_err());
 
-        println!("\n=== SCREEN CONTROL COMMANDS TEST COMPLETE ===");
+        // The folder holds a copy of the executable and the bundle beside it
+        let exe = root.join("time-warp-ide");
+        std::fs::write(&exe, "binary").unwrap();
+        let copy = standalone::write_bundle(&root.join("game"), &exe, &bundle).unwrap();
+        assert_eq!(
+            copy,
+            root.join("game")
+                .join(format!("game{}", std::env::consts::EXE_SUFFIX))
+        );
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "binary");
+        let saved =
+            std::fs::read_to_string(root.join("game").join(standalone::BUNDLE_FILE)).unwrap();
+        let saved = time_warp_core::json::Json::parse(&saved).unwrap();
+        assert_eq!(standalone::Bundle::from_json(&saved), Ok(bundle.clone()));
+
+        // A player runs the program as soon as it starts
+        let player = TimeWarpApp::player(&bundle);
+        assert!(player.player);
+        assert!(player.output.ends_with("DONE\n"), "{}", player.output);
+        assert!(!player.turtle_commands.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
     }
 
     #[test]
-    fn test_error_handling_commands() {
-        use crate::languages::basic::Interpreter;
-
-        println!("=== TESTING ERROR HANDLING COMMANDS ===");
+    #[cfg(all(feature = "logo", feature = "pilot"))]
+    fn test_projects() {
+        let root = std::env::temp_dir().join(format!("tw_project_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("data")).unwrap();
+
+        // Members are listed relative to the project, programs apart from data
+        let path = root.join("turtles.twproj");
+        let mut project = projects::Project::new(&path);
+        assert_eq!(project.name, "turtles");
+        project.add_member(&root.join("square.logo"));
+        project.add_member(&root.join("data").join("sizes.txt"));
+        project.add_member(&root.join("square.logo"));
+        project.add_member(&root.join("notes"));
+        assert_eq!(project.sources, vec!["square.logo"]);
+        assert_eq!(project.data, vec!["data/sizes.txt", "notes"]);
+        assert_eq!(
+            project.member_path("data/sizes.txt"),
+            root.join("data").join("sizes.txt")
+        );
 
-        // Test ON ERROR command
-        println!("\n--- Testing ON ERROR command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("ON ERROR GOTO 100");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("ON ERROR result: {}", output);
-            }
-            _ => println!("ON ERROR command executed"),
+        // Settings survive the file; a time limit out of range is ignored
+        project.language = Some(Language::Pilot);
+        project.timeout_ms = Some(2000);
+        project.save().unwrap();
+        assert_eq!(projects::Project::load(&path).unwrap(), project);
+        let mut json = project.to_json();
+        if let time_warp_core::json::Json::Object(fields) = &mut json {
+            fields.retain(|(key, _)| key != "settings");
+            fields.push((
+                "settings".to_string(),
+                time_warp_core::json::Json::parse(r#"{"timeout_ms": 5}"#).unwrap(),
+            ));
         }
+        let loaded = projects::Project::from_json(&path, &json);
+        assert_eq!((loaded.language, loaded.timeout_ms), (None, None));
+        assert!(projects::is_project_file("lessons/Turtles.TWPROJ"));
+        assert!(!projects::is_project_file("square.logo"));
+
+        // Opening the project opens its first program and applies its limit
+        std::fs::write(root.join("square.logo"), "REPEAT 4 [FD 50 RT 90]").unwrap();
+        std::fs::write(root.join("notes"), "T:Hello").unwrap();
+        let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        app.open_project(&path);
+        assert_eq!(app.code, "REPEAT 4 [FD 50 RT 90]");
+        assert_eq!(app.language, Language::Logo);
+        assert_eq!(app.execution_timeout_ms(), 2000);
+        assert!(app.show_project_panel);
+
+        // A member with no language of its own takes the project's
+        app.open_project_member(&root.join("notes"));
+        assert_eq!(app.documents.len(), 2);
+        assert_eq!(app.language, Language::Pilot);
+
+        // The session lists the project again without reopening its files
+        let session = app.session_json();
+        let mut restored = TimeWarpApp::default();
+        restored.apply_session(&session);
+        assert_eq!(restored.project, Some(project));
+        app.project = None;
+        assert_eq!(
+            app.execution_timeout_ms(),
+            app.settings.execution_timeout_ms
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
 
-        // Test RESUME command
-        println!("\n--- Testing RESUME command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("RESUME");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("RESUME result: {}", output);
-            }
-            _ => println!("RESUME command executed"),
-        }
+    #[test]
+    fn test_settings() {
+        use settings::Settings;
+        use theme::Theme;
+        use time_warp_core::basic::dialect::Dialect;
+        use time_warp_core::basic::strict::Strictness;
+        use time_warp_core::json::Json;
+
+        let root = std::env::temp_dir().join(format!("tw_settings_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let file = storage::StoragePaths::under(&root).settings_file;
+
+        // Nothing saved yet
+        assert_eq!(Settings::load(&file).unwrap(), Settings::default());
+
+        let chosen = Settings {
+            font_size: 18.0,
+            code_font_size: 40.0,
+            code_font: Some(root.join("fonts").join("Mono.ttf")),
+            theme: Theme::Dark,
+            execution_timeout_ms: 12_000,
+            tab_width: 2,
+            autosave_secs: 0,
+            code_completion: true,
+            basic_dialect: Dialect::QBasic,
+            basic_strictness: Strictness::Strict,
+            keybindings: Default::default(),
+            recent_files: vec!["/lessons/week1.twb".to_string()],
+            printer_file: Some(root.join("printout.txt")),
+            ask_permission: false,
+            play_sound: false,
+            hints_enabled: false,
+            program_permissions: [(
+                "SCORES".to_string(),
+                [(time_warp_core::basic::Permission::DeleteFiles, false)].into(),
+            )]
+            .into(),
+        };
+        chosen.save(&file).unwrap();
+        assert_eq!(Settings::load(&file).unwrap(), chosen);
+
+        // Missing and out-of-range fields keep their defaults
+        let partial =
+            Json::parse(r#"{"font_size": 99, "theme": "Dark", "autosave_secs": 30}"#).unwrap();
+        let loaded = Settings::from_json(&partial);
+        assert_eq!(loaded.font_size, 14.0);
+        assert_eq!(loaded.theme, Theme::Dark);
+        assert_eq!(loaded.autosave_secs, 30);
+        assert_eq!(loaded.tab_width, 4);
+        assert_eq!(loaded.code_font, None);
+
+        std::fs::write(&file, "{ not json").unwrap();
+        assert!(Settings::load(&file).is_err());
+
+        // The timeout setting sets the instruction limit
+        let mut app = TimeWarpApp::default();
+        app.settings.execution_timeout_ms = 1;
+        app.execute_tw_basic("10 GOTO 10");
+        assert_eq!(app.execution_stats.as_ref().unwrap().max_instructions, 1000);
 
-        // Test RESUME with line number
-        println!("\n--- Testing RESUME NEXT command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("RESUME NEXT");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("RESUME NEXT result: {}", output);
-            }
-            _ => println!("RESUME NEXT command executed"),
-        }
+        // Tabs typed in the editor become spaces, keeping the cursor in place
+        let mut text = "IF X\tTHEN\n\tPRINT".to_string();
+        let cursor = settings::expand_tabs(&mut text, 4, 12);
+        assert_eq!(text, "IF X    THEN\n    PRINT");
+        assert_eq!(cursor, 18);
 
-        println!("\n=== ERROR HANDLING COMMANDS TEST COMPLETE ===");
+        let _ = std::fs::remove_dir_all(&root);
     }
 
     #[test]
-    fn test_control_flow_commands() {
-        use crate::languages::basic::Interpreter;
-
-        println!("=== TESTING CONTROL FLOW COMMANDS ===");
-
-        // Test WHILE/WEND loop
-        println!("\n--- Testing WHILE/WEND loop ---");
-        let mut interpreter = Interpreter::new();
-        let program = r#"
-        LET X = 1
-        WHILE X <= 3
-        PRINT "Count: "; X
-        LET X = X + 1
-        WEND
-        PRINT "Loop finished"
-        "#;
-        let result = interpreter.execute(program);
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("WHILE/WEND result:\n{}", output);
-                assert!(output.contains("Count: 1"));
-                assert!(output.contains("Count: 2"));
-                assert!(output.contains("Count: 3"));
-                assert!(output.contains("Loop finished"));
-            }
-            _ => println!("WHILE/WEND loop executed"),
-        }
+    fn test_bookmarks() {
+        let root = std::env::temp_dir().join(format!("tw_bookmarks_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        app.code = "10 GOSUB 100\n20 END\n100 PRINT 1\n110 RETURN".to_string();
+
+        // Ctrl+F2 marks the caret's line, and again takes the mark off
+        app.selection = editor::Selection::caret(editor::line_start(&app.code, 2));
+        app.run_action(keybindings::Action::ToggleBookmark);
+        app.selection = editor::Selection::caret(0);
+        app.run_action(keybindings::Action::ToggleBookmark);
+        assert_eq!(app.current_bookmarks(), vec![1, 3]);
+
+        // F2 goes down through them and back round to the top
+        app.run_action(keybindings::Action::NextBookmark);
+        assert_eq!(app.pending_selection, Some(editor::Selection::caret(20)));
+        app.selection = app.pending_selection.take().unwrap();
+        app.run_action(keybindings::Action::NextBookmark);
+        assert_eq!(app.pending_selection, Some(editor::Selection::caret(0)));
+        app.selection = app.pending_selection.take().unwrap();
+        app.run_action(keybindings::Action::ToggleBookmark);
+        assert_eq!(app.current_bookmarks(), vec![3]);
+
+        // Each tab has its own, and they come back with the session
+        app.new_document();
+        assert!(app.current_bookmarks().is_empty());
+        app.save_session().unwrap();
+        let mut restored = TimeWarpApp::default();
+        restored.storage = app.storage.clone();
+        restored.restore_session();
+        restored.switch_document(0);
+        assert_eq!(restored.current_bookmarks(), vec![3]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
 
-        // Test SELECT CASE
-        println!("\n--- Testing SELECT CASE ---");
-        let mut interpreter = Interpreter::new();
-        let program = r#"
-        LET GRADE = 85
-        SELECT CASE GRADE
-        CASE 90 TO 100
-        PRINT "A"
-        CASE 80 TO 89
-        PRINT "B"
-        CASE 70 TO 79
-        PRINT "C"
-        CASE ELSE
-        PRINT "F"
-        END SELECT
-        "#;
-        let result = interpreter.execute(program);
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("SELECT CASE result:\n{}", output);
-                assert!(output.contains("B"));
-            }
-            _ => println!("SELECT CASE executed"),
-        }
+    #[test]
+    #[cfg(feature = "pascal")]
+    fn test_code_folding() {
+        let basic = Language::Basic.backend();
+        let program = "10 GOSUB 100\n20 FOR I = 1 TO 3\n30 PRINT I\n40 NEXT I\n50 END\n\
+                       100 PRINT \"COUNTING\"\n110 WHILE X < 3\n120 X = X + 1\n130 WEND\n140 RETURN";
+        // Loops, and the subroutine from its first line down to RETURN
+        assert_eq!(
+            editor::fold_regions(program, &*basic),
+            vec![1..4, 5..10, 6..9]
+        );
+        // A loop on one line has nothing to fold
+        assert!(editor::fold_regions("10 FOR I = 1 TO 3: PRINT I: NEXT I", &*basic).is_empty());
+
+        let pascal = Language::Pascal.backend();
+        let program = "program Test;\nbegin\n  if x then\n  begin\n    writeln(1)\n  end\nend.";
+        assert_eq!(editor::fold_regions(program, &*pascal), vec![1..7, 3..6]);
+
+        // Folded lines are squashed to nothing but stay in the text
+        let font_id = egui::FontId::monospace(12.0);
+        let mut job = egui::text::LayoutJob::simple(
+            "a\nb\nc\nd".to_string(),
+            font_id,
+            egui::Color32::WHITE,
+            f32::INFINITY,
+        );
+        highlight::hide_lines(&mut job, "a\nb\nc\nd", &[1..2, 3..4]);
+        let hidden: Vec<_> = job
+            .sections
+            .iter()
+            .filter(|section| section.format.line_height == Some(0.0))
+            .map(|section| section.byte_range.clone())
+            .collect();
+        assert_eq!(hidden, vec![1..3, 5..7]);
+        assert_eq!(job.text, "a\nb\nc\nd");
 
-        println!("\n=== CONTROL FLOW COMMANDS TEST COMPLETE ===");
+        // Fold All folds every block, and each tab keeps its own folds
+        let mut app = TimeWarpApp::default();
+        app.language = Language::Pascal;
+        app.code = program.to_string();
+        app.fold_all(true);
+        assert_eq!(app.folded_lines, BTreeSet::from([1, 3]));
+        let regions = editor::fold_regions(&app.code, &*pascal);
+        assert_eq!(
+            editor::hidden_lines(&regions, &app.folded_lines),
+            vec![2..7, 4..6]
+        );
+        app.new_document();
+        assert!(app.folded_lines.is_empty());
+        app.switch_document(0);
+        assert_eq!(app.folded_lines, BTreeSet::from([1, 3]));
+        app.fold_all(false);
+        assert!(app.folded_lines.is_empty());
     }
 
     #[test]
-    fn test_system_commands() {
-        use crate::languages::basic::Interpreter;
+    #[cfg(feature = "pascal")]
+    fn test_brackets_and_indent() {
+        let basic = Language::Basic.backend();
+        let pascal = Language::Pascal.backend();
 
-        println!("=== TESTING SYSTEM COMMANDS ===");
+        // The caret may be on a bracket or just after one
+        let code = "PRINT (A + (B))";
+        assert_eq!(
+            editor::matching_bracket(code, 6, basic.brackets()),
+            Some((6, 14))
+        );
+        assert_eq!(
+            editor::matching_bracket(code, 15, basic.brackets()),
+            Some((14, 6))
+        );
+        assert_eq!(
+            editor::matching_bracket(code, 11, basic.brackets()),
+            Some((11, 13))
+        );
+        assert_eq!(editor::matching_bracket(code, 2, basic.brackets()), None);
+        assert_eq!(editor::matching_bracket("(()", 0, basic.brackets()), None);
+
+        // Enter after FOR indents; Enter after NEXT moves NEXT back out
+        let mut code = "10 FOR I = 1 TO 3\n".to_string();
+        let caret = editor::auto_indent(&mut code, 18, &*basic, 2);
+        assert_eq!((code.as_str(), caret), ("10 FOR I = 1 TO 3\n  ", 20));
+        code.push_str("20 PRINT I\n");
+        let caret = editor::auto_indent(&mut code, 31, &*basic, 2);
+        assert_eq!(caret, 33);
+        code.push_str("30 NEXT I\n");
+        let caret = editor::auto_indent(&mut code, 43, &*basic, 2);
+        assert_eq!(code, "10 FOR I = 1 TO 3\n  20 PRINT I\n30 NEXT I\n");
+        assert_eq!(caret, 41);
+
+        // Words in strings and comments don't count
+        let mut code = "PRINT \"FOR\" ' WHILE\n".to_string();
+        assert_eq!(editor::auto_indent(&mut code, 20, &*basic, 4), 20);
+
+        // Pascal's blocks are begin and end, in any case
+        let mut code = "begin\n  writeln('hi');\n  end;\n".to_string();
+        let caret = editor::auto_indent(&mut code, 30, &*pascal, 2);
+        assert_eq!(code, "begin\n  writeln('hi');\nend;\n");
+        assert_eq!(caret, 28);
+        let mut code = "  end else begin\n".to_string();
+        editor::auto_indent(&mut code, 17, &*pascal, 2);
+        assert_eq!(code, "  end else begin\n    ");
+    }
 
-        // Test SYSTEM command
-        println!("\n--- Testing SYSTEM command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("SYSTEM");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("SYSTEM result: {}", output);
+    #[test]
+    fn test_toggle_comment() {
+        use editor::{toggle_line_comments, Selection};
+        let basic = Language::Basic.backend().line_comments();
+
+        // REM goes after the line number so the line keeps its place
+        let mut code = "10 PRINT 1\n\n  20 GOTO 10".to_string();
+        let selection = toggle_line_comments(
+            &mut code,
+            Selection {
+                anchor: 3,
+                head: 15,
+            },
+            basic,
+        );
+        assert_eq!(code, "10 REM PRINT 1\n\n  20 REM GOTO 10");
+        assert_eq!(
+            selection,
+            Selection {
+                anchor: 7,
+                head: 19
             }
-            _ => println!("SYSTEM command executed"),
-        }
-
-        // Test CHDIR command
-        println!("\n--- Testing CHDIR command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("CHDIR \"/tmp\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("CHDIR result: {}", output);
+        );
+        let selection = toggle_line_comments(&mut code, selection, basic);
+        assert_eq!(code, "10 PRINT 1\n\n  20 GOTO 10");
+        assert_eq!(
+            selection,
+            Selection {
+                anchor: 3,
+                head: 15
             }
-            _ => println!("CHDIR command executed"),
-        }
+        );
 
-        // Test MKDIR command
-        println!("\n--- Testing MKDIR command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("MKDIR \"testdir\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("MKDIR result: {}", output);
-            }
-            _ => println!("MKDIR command executed"),
+        // ' comments come off too; a mix of commented and plain lines gets
+        // commented throughout
+        let mut code = "' note\nrem old".to_string();
+        toggle_line_comments(&mut code, Selection { anchor: 0, head: 9 }, basic);
+        assert_eq!(code, "note\nold");
+        let mut code = "REM a\nb".to_string();
+        toggle_line_comments(&mut code, Selection { anchor: 0, head: 7 }, basic);
+        assert_eq!(code, "REM REM a\nREM b");
+
+        // Other languages use their own line comment
+        #[cfg(feature = "pascal")]
+        {
+            let pascal = Language::Pascal.backend().line_comments();
+            let mut code = "  writeln(1);".to_string();
+            let caret = toggle_line_comments(&mut code, Selection::caret(5), pascal);
+            assert_eq!(code, "  // writeln(1);");
+            assert_eq!(caret, Selection::caret(8));
         }
-
-        // Test RMDIR command
-        println!("\n--- Testing RMDIR command ---");
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute("RMDIR \"testdir\"");
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("RMDIR result: {}", output);
-            }
-            _ => println!("RMDIR command executed"),
+        #[cfg(feature = "prolog")]
+        {
+            let prolog = Language::Prolog.backend().line_comments();
+            let mut code = "likes(a, b).".to_string();
+            toggle_line_comments(&mut code, Selection::caret(0), prolog);
+            assert_eq!(code, "% likes(a, b).");
         }
 
-        println!("\n=== SYSTEM COMMANDS TEST COMPLETE ===");
+        // The app's version is recorded for undo
+        let mut app = TimeWarpApp::default();
+        app.code = "PRINT 1".to_string();
+        app.toggle_comment();
+        assert_eq!(app.code, "REM PRINT 1");
+        assert_eq!(app.undo_history.last(), Some(&app.code));
     }
 
     #[test]
-    fn test_array_commands() {
-        use crate::languages::basic::Interpreter;
+    fn test_editor_cursor() {
+        use editor::Selection;
+
+        // Positions are characters, so accented text doesn't throw them off
+        let code = "10 PRINT \"é\"\n20 GOTO 10";
+        assert_eq!(editor::line_and_column(code, 0), (1, 1));
+        assert_eq!(editor::line_and_column(code, 12), (1, 13));
+        assert_eq!(editor::line_and_column(code, 13), (2, 1));
+        assert_eq!(editor::line_and_column(code, 99), (2, 11));
+        assert_eq!(Selection { anchor: 9, head: 3 }.range(), 3..9);
+
+        // Completion replaces the word at the caret, not the end of the code
+        let mut app = TimeWarpApp::default();
+        app.settings.code_completion = true;
+        app.code = "10 PRI\n20 END".to_string();
+        app.selection = Selection::caret(6);
+        app.trigger_completion();
+        assert_eq!(app.completion_query, "PRI");
+        assert!(app.show_completion);
+        app.insert_completion("PRINT");
+        assert_eq!(app.code, "10 PRINT\n20 END");
+        assert_eq!(app.pending_selection, Some(Selection::caret(8)));
+        assert!(!app.show_completion);
+        assert_eq!(editor::word_before("X=LE", 4), 2..4);
+
+        // Line operations work on every line the selection touches
+        let mut code = "a\nbb\nc".to_string();
+        let copy = editor::duplicate_lines(&mut code, Selection { anchor: 0, head: 3 });
+        assert_eq!(code, "a\nbb\na\nbb\nc");
+        assert_eq!(copy, Selection { anchor: 5, head: 8 });
+
+        let mut code = "a\nbb\nc".to_string();
+        let moved = editor::move_lines(&mut code, Selection::caret(3), true);
+        assert_eq!(code, "bb\na\nc");
+        assert_eq!(moved, Selection::caret(1));
+        let moved = editor::move_lines(&mut code, moved, false);
+        assert_eq!(code, "a\nbb\nc");
+        assert_eq!(moved, Selection::caret(3));
+        // The last line can't go further down
+        assert_eq!(
+            editor::move_lines(&mut code, Selection::caret(6), false),
+            Selection::caret(6)
+        );
+        assert_eq!(code, "a\nbb\nc");
+
+        // A selection ending at the start of a line leaves that line alone
+        let deleted = editor::delete_lines(&mut code, Selection { anchor: 0, head: 2 });
+        assert_eq!(code, "bb\nc");
+        assert_eq!(deleted, Selection::caret(0));
+
+        // Through the shortcuts, each recorded for undo like typing is
+        app.code = "10 A\n20 B".to_string();
+        app.selection = Selection::caret(0);
+        app.run_action(keybindings::Action::MoveLineDown);
+        assert_eq!(app.code, "20 B\n10 A");
+        app.run_action(keybindings::Action::DeleteLine);
+        // The caret followed the moved line, so that is the one deleted
+        assert_eq!(app.code, "20 B");
+        assert_eq!(app.undo_history, ["20 B\n10 A", "20 B"]);
+    }
 
-        println!("=== TESTING ARRAY COMMANDS ===");
+    #[test]
+    fn test_clipboard_uses_selection() {
+        use editor::Selection;
 
-        // Test OPTION BASE
-        println!("\n--- Testing OPTION BASE command ---");
-        let mut interpreter = Interpreter::new();
-        let program = r#"
-        OPTION BASE 1
-        DIM A(5)
-        LET A(1) = 10
-        PRINT "Array base is 1, A(1) = "; A(1)
-        "#;
-        let result = interpreter.execute(program);
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("OPTION BASE result:\n{}", output);
-                assert!(output.contains("Array base is 1"));
-            }
-            _ => println!("OPTION BASE executed"),
-        }
+        let ctx = egui::Context::default();
+        let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT \"A\"\n20 END".to_string();
+
+        // Copy takes only the selection, whichever way it was dragged
+        app.selection = Selection { anchor: 8, head: 3 };
+        app.copy_text(&ctx);
+        assert_eq!(app.clipboard_content, "PRINT");
+        assert_eq!(app.code, "10 PRINT \"A\"\n20 END");
+
+        // Nothing selected: nothing copied or cut
+        app.selection = Selection::caret(0);
+        app.cut_text(&ctx);
+        assert_eq!(app.clipboard_content, "PRINT");
+        assert_eq!(app.code, "10 PRINT \"A\"\n20 END");
+
+        // Cut removes just the selection and leaves the caret there
+        app.selection = Selection {
+            anchor: 12,
+            head: 19,
+        };
+        app.cut_text(&ctx);
+        assert_eq!(app.clipboard_content, "\n20 END");
+        assert_eq!(app.code, "10 PRINT \"A\"");
+        assert_eq!(app.selection, Selection::caret(12));
+
+        // Paste goes in at the caret, replacing any selection
+        app.selection = Selection::caret(0);
+        app.paste_text(&ctx);
+        assert_eq!(app.code, "\n20 END10 PRINT \"A\"");
+        assert_eq!(app.selection, Selection::caret(7));
+        app.selection = Selection { anchor: 0, head: 7 };
+        app.clipboard_content = "5 CLS".to_string();
+        app.paste_text(&ctx);
+        assert_eq!(app.code, "5 CLS10 PRINT \"A\"");
+        assert_eq!(app.undo_history.last(), Some(&app.code));
+    }
 
-        // Test ERASE command
-        println!("\n--- Testing ERASE command ---");
-        let mut interpreter = Interpreter::new();
-        let program = r#"
-        DIM B(10)
-        LET B(0) = 42
-        PRINT "Before ERASE: B(0) = "; B(0)
-        ERASE B
-        "#;
-        let result = interpreter.execute(program);
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete { output, .. }) => {
-                println!("ERASE result:\n{}", output);
-            }
-            _ => println!("ERASE command executed"),
+    #[test]
+    fn test_highlighting_in_editor() {
+        let palette = theme::Theme::Light.palette();
+        let keywords: Vec<String> = ["PRINT", "FOR", "TO", "NEXT", "LEFT$"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        let comments = Language::Basic.backend().comment_prefixes();
+
+        // The coloured pieces always rebuild the line exactly, so the caret
+        // lines up with what is drawn
+        for line in [
+            "10 FOR I=1 TO 10: PRINT LEFT$(A$, 2) ' ünïcode",
+            "20 PRINT \"unterminated",
+            "30 REM",
+            "   ",
+            "",
+            "40 X>=Y<>Z",
+        ] {
+            let pieces = highlight::highlight_line(line, &keywords, comments, palette);
+            let rebuilt: String = pieces.iter().map(|(text, _)| text.as_str()).collect();
+            assert_eq!(rebuilt, line);
         }
 
-        println!("\n=== ARRAY COMMANDS TEST COMPLETE ===");
+        let pieces =
+            highlight::highlight_line("10 PRINT LEFT$(A$, 2) ' note", &keywords, comments, palette);
+        assert_eq!(pieces[0], ("10".to_string(), palette.number));
+        assert!(pieces.contains(&("LEFT$".to_string(), palette.keyword)));
+        // Neighbouring plain text is one piece
+        assert!(pieces.contains(&("A$, ".to_string(), palette.text)));
+        assert_eq!(
+            pieces.last().unwrap(),
+            &("' note".to_string(), palette.comment)
+        );
+        // Keywords inside longer names stay plain
+        let pieces = highlight::highlight_line("PRINTER = 1", &keywords, comments, palette);
+        assert_eq!(pieces[0], ("PRINTER ".to_string(), palette.text));
+        assert_eq!(
+            highlight::highlight_line("30 REM", &keywords, comments, palette)[2],
+            ("REM".to_string(), palette.comment)
+        );
+
+        // The layouter sees the same text as the editor, newlines included
+        let code = "10 PRINT 1\n\n20 END\n";
+        let mut cache = highlight::HighlightCache::default();
+        let font = egui::FontId::monospace(12.0);
+        let job =
+            highlight::layout_job(code, &mut cache, &keywords, comments, palette, font.clone());
+        assert_eq!(job.text, code);
+        // "10 PRINT 1", "" and "20 END"; the second blank line is the same as the first
+        assert_eq!(cache.misses, 3);
+
+        // Editing one line highlights only that line again
+        let edited = "10 PRINT 2\n\n20 END\n";
+        let job = highlight::layout_job(
+            edited,
+            &mut cache,
+            &keywords,
+            comments,
+            palette,
+            font.clone(),
+        );
+        assert_eq!(job.text, edited);
+        assert_eq!(cache.misses, 4);
+        let again = highlight::layout_job(
+            edited,
+            &mut cache,
+            &keywords,
+            comments,
+            palette,
+            font.clone(),
+        );
+        assert_eq!(again.sections, job.sections);
+        assert_eq!(cache.misses, 4);
+
+        // A new theme colours every line afresh
+        let dark = theme::Theme::Dark.palette();
+        highlight::layout_job(edited, &mut cache, &keywords, comments, dark, font);
+        assert_eq!(cache.misses, 7);
+        assert_eq!(
+            cache.line("20 END", &keywords, comments, dark)[0].1,
+            dark.number
+        );
     }
 
     #[test]
-    fn test_comprehensive_gw_basic_program() {
-        use crate::languages::basic::Interpreter;
+    fn test_code_fonts() {
+        let root = std::env::temp_dir().join(format!("tw_fonts_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("truetype")).unwrap();
+        std::fs::write(root.join("truetype").join("Mono.ttf"), b"ttf").unwrap();
+        std::fs::write(root.join("Andale.OTF"), b"otf").unwrap();
+        std::fs::write(root.join("readme.txt"), b"").unwrap();
+
+        // Subfolders are searched and only font files are listed, by name
+        let found = fonts::scan(&[root.clone(), root.join("missing")]);
+        let names: Vec<&str> = found.iter().map(|font| font.name.as_str()).collect();
+        assert_eq!(names, ["Andale", "Mono"]);
+        assert_eq!(found[1].path, root.join("truetype").join("Mono.ttf"));
+
+        // The chosen font goes first in the monospace family
+        let definitions = fonts::definitions(Some(&found[1].path)).unwrap();
+        assert_eq!(
+            definitions.families[&egui::FontFamily::Monospace][0],
+            "code_font"
+        );
+        let built_in = fonts::definitions(None).unwrap();
+        assert_eq!(built_in, egui::FontDefinitions::default());
+        assert!(fonts::definitions(Some(&root.join("gone.ttf"))).is_err());
 
-        println!("=== TESTING COMPREHENSIVE GW BASIC PROGRAM ===");
+        let _ = std::fs::remove_dir_all(&root);
+    }
 
-        // Create a comprehensive program using multiple GW BASIC features
-        let program = r#"
-        PRINT "Hello World"
-        LET GRADE = 85
-        SELECT CASE GRADE
-        CASE 80 TO 89
-        PRINT "Grade: B"
-        END SELECT
-        "#;
+    #[test]
+    fn test_keybindings() {
+        use keybindings::{Action, Keybindings, Shortcut};
+        use time_warp_core::json::Json;
+
+        let bindings = Keybindings::default();
+        assert!(bindings.conflicts().is_empty());
+        assert_eq!(bindings.label(Action::ClearOutput), "Ctrl+Shift+C");
+        for action in Action::ALL {
+            let shortcut = bindings.get(action).unwrap();
+            assert_eq!(Shortcut::parse(&shortcut.to_string()), Some(shortcut));
+        }
 
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(program);
+        // Modifiers must match exactly, so Ctrl+F5 doesn't also run
+        let run = bindings.get(Action::Run).unwrap();
+        assert!(run.matches(egui::Key::F5, egui::Modifiers::NONE));
+        assert!(!run.matches(egui::Key::F5, egui::Modifiers::CTRL));
 
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("COMPREHENSIVE PROGRAM OUTPUT:\n{}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
+        // Clashes with other actions and with the editor's own shortcuts
+        let ctrl = Shortcut::ctrl;
+        assert_eq!(
+            bindings.conflict(Action::Run, ctrl(egui::Key::S)),
+            Some("Save File")
+        );
+        assert_eq!(
+            bindings.conflict(Action::Run, ctrl(egui::Key::Z)),
+            Some("Undo")
+        );
+        assert_eq!(bindings.conflict(Action::Run, run), None);
+
+        // Remapped and removed shortcuts survive the settings file
+        let mut settings = settings::Settings::default();
+        settings
+            .keybindings
+            .set(Action::Run, Some(ctrl(egui::Key::Space)));
+        settings.keybindings.set(Action::Replace, None);
+        let saved = settings.to_json().to_string();
+        let loaded = settings::Settings::from_json(&Json::parse(&saved).unwrap());
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.keybindings.label(Action::Replace), "unbound");
+        assert_eq!(
+            loaded.keybindings.conflicts(),
+            vec![(Action::Run, "Code Completion")]
+        );
 
-                // Verify key outputs
-                assert!(output.contains("Hello World"));
-                assert!(output.contains("Grade: B"));
+        // A shortcut that doesn't parse keeps the default
+        let edited = Json::parse(r#"{"run": "Ctrl+Nope", "find": "Alt+F"}"#).unwrap();
+        let edited = Keybindings::from_json(&edited);
+        assert_eq!(edited.label(Action::Run), "F5");
+        assert_eq!(edited.label(Action::Find), "Alt+F");
 
-                println!("\n=== COMPREHENSIVE TEST PASSED ===");
-            }
-            Err(e) => {
-                println!("COMPREHENSIVE PROGRAM FAILED: {:?}", e);
-                panic!("Comprehensive test failed");
-            }
-            _ => {
-                println!("COMPREHENSIVE PROGRAM - Unexpected result type");
-            }
-        }
+        // Actions do what the old fixed shortcuts did
+        let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT \"hi\"".to_string();
+        app.run_action(Action::Run);
+        assert_eq!(app.active_tab, 1);
+        assert!(app.output.contains("hi"));
+        app.run_action(Action::ClearOutput);
+        assert!(app.output.is_empty());
+        app.run_action(Action::ToggleDebugMode);
+        assert!(app.debug_mode);
     }
 
     #[test]
-    fn test_comprehensive_demo_program() {
-        use crate::languages::basic::Interpreter;
-
-        println!("\n=== TESTING COMPREHENSIVE DEMO PROGRAM ===");
+    fn test_theme_palettes() {
+        use theme::Theme;
+
+        for theme in Theme::ALL {
+            assert_eq!(Theme::from_name(theme.name()), Some(theme));
+            let visuals = theme.visuals();
+            let palette = theme.palette();
+            // Code colours must stand out from the editor background
+            let background = visuals.extreme_bg_color;
+            let brightness = |c: egui::Color32| c.r() as u32 + c.g() as u32 + c.b() as u32;
+            for color in [
+                palette.text,
+                palette.keyword,
+                palette.string,
+                palette.comment,
+            ] {
+                let contrast = brightness(color).abs_diff(brightness(background));
+                assert!(contrast > 150, "{:?} {:?}", theme, color);
+            }
+        }
+        assert!(Theme::Dark.visuals().dark_mode);
 
-        let program = r#"
-10 PRINT "TW BASIC Comprehensive Demonstration Program"
-20 PRINT "============================================"
-30 LET SCORE = 0
-40 PRINT "SCORE ="; SCORE
-50 PRINT "Program completed successfully!"
-"#;
+        // The highlighter paints with the palette it is given
+        let dark = Theme::Dark.palette();
+        let highlighted =
+            highlight::highlight_line("PRINT \"hi\"", &["PRINT".to_string()], &["REM"], dark);
+        assert_eq!(highlighted[0], ("PRINT".to_string(), dark.keyword));
+        assert!(highlighted.contains(&("\"hi\"".to_string(), dark.string)));
 
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(program);
+        // Switching theme is remembered with the other settings
+        let mut app = TimeWarpApp::default();
+        app.settings.theme = Theme::Dark;
+        assert_eq!(app.palette(), dark);
+        let saved = app.settings.to_json().to_string();
+        let loaded =
+            settings::Settings::from_json(&time_warp_core::json::Json::parse(&saved).unwrap());
+        assert_eq!(loaded.theme, Theme::Dark);
+    }
 
-        match result {
-            Ok(crate::languages::basic::ExecutionResult::Complete {
-                output,
-                graphics_commands,
-            }) => {
-                println!("COMPREHENSIVE DEMO OUTPUT:\n{}", output);
-                println!("Graphics commands generated: {}", graphics_commands.len());
+    #[test]
+    fn test_pen_color_and_size() {
+        let mut app = TimeWarpApp::default();
+        let result =
+            app.execute_tw_basic("SETPENCOLOR 4\nPENSIZE 5\nFORWARD 10\nSETPC 0, 128, 255");
+        assert!(!result.contains("Error"), "{}", result);
 
-                // Verify comprehensive functionality
-                assert!(output.contains("TW BASIC Comprehensive Demonstration Program"));
-                assert!(output.contains("SCORE =0"));
-                assert!(output.contains("Program completed successfully"));
+        assert_eq!(
+            app.turtle_commands[..3],
+            [
+                turtle::TurtleOp::ColorChange(egui::Color32::from_rgb(220, 0, 0)),
+                turtle::TurtleOp::PenWidth(5.0),
+                turtle::TurtleOp::Line {
+                    from: egui::pos2(0.0, 0.0),
+                    to: egui::pos2(10.0, 0.0),
+                },
+            ]
+        );
+        assert_eq!(
+            app.turtles[DEFAULT_TURTLE].color,
+            egui::Color32::from_rgb(0, 128, 255)
+        );
+        assert_eq!(app.turtles[DEFAULT_TURTLE].pen_width, 5.0);
 
-                println!("\n=== COMPREHENSIVE DEMO TEST PASSED ===");
-            }
-            Err(e) => {
-                println!("COMPREHENSIVE DEMO FAILED: {:?}", e);
-                panic!("Comprehensive demo test failed");
-            }
-            _ => {
-                println!("COMPREHENSIVE DEMO - Unexpected result type");
-            }
-        }
+        let result = app.execute_tw_basic("SETPENCOLOR 99");
+        assert!(result.contains("Error"));
     }
 
     #[test]
-    fn test_type_declaration_commands() {
-        println!("\n=== TESTING TYPE DECLARATION COMMANDS ===");
+    fn test_on_error() {
+        // The handler after END is reached, as far as the checker can tell
         let mut app = TimeWarpApp::default();
+        app.code = "10 ON ERROR GOTO 100\n20 X = 0\n30 PRINT 10 / X\n40 PRINT \"AFTER\"\n50 END\n\
+                    100 PRINT \"TRAPPED\"\n110 X = 2\n120 RESUME NEXT"
+            .to_string();
+        assert!(app.check_program().is_empty());
+    }
 
-        // Test DEFINT with range
-        let program = "10 DEFINT A-Z\n20 A = 3.14\n30 B = 5.9\n40 PRINT A, B";
+    #[test]
+    fn test_lprint() {
+        let mut app = TimeWarpApp::default();
+        let program = "10 PRINT \"REPORT\"\n20 LPRINT \"NAME\", \"SCORE\"\n\
+                       30 LPRINT USING \"\\      \\###\"; \"ADA\"; 95";
         let result = app.execute_tw_basic(program);
-        println!("DEFINT test output: {}", result);
-        assert!(
-            result.contains("3") && result.contains("5"),
-            "DEFINT should truncate decimals to integers"
+        assert_eq!(result, "REPORT\n");
+        assert_eq!(app.printout, "NAME          SCORE\nADA      95\n");
+        assert!(app.show_printer);
+
+        // With a printer file, each run's pages are added to its end
+        let path = temp_path("time_warp_printer_test").with_extension("txt");
+        let _ = fs::remove_file(&path);
+        app.settings.printer_file = Some(path.clone());
+        app.execute_tw_basic(program);
+        app.execute_tw_basic(program);
+        let printed = fs::read_to_string(&path).unwrap();
+        assert_eq!(printed.matches("ADA      95").count(), 2);
+        fs::remove_file(&path).unwrap();
+
+        // PRINT USING lays values out the same way on the screen
+        let using = |format: &str, values: &str| {
+            TimeWarpApp::default()
+                .execute_tw_basic(&format!("10 PRINT USING \"{}\"; {}", format, values))
+        };
+        assert_eq!(using("###.##", "3.14159"), "  3.14\n");
+        assert_eq!(using("$$#,###.##", "1234.5"), " $1,234.50\n");
+        assert_eq!(using("**##.#", "5.26"), "***5.3\n");
+        assert_eq!(using("##.##-", "-5"), " 5.00-\n");
+        assert_eq!(using("+##", "7"), " +7\n");
+        assert_eq!(using("##", "123"), "%123\n");
+        assert_eq!(using("##.##^^^^", "1234.5"), " 1.23E+03\n");
+        assert_eq!(
+            using("!-\\  \\-&", "\"HELLO\"; \"WORLD\"; \"!\""),
+            "H-WORL-!\n"
         );
+        assert_eq!(using("## ", "1; 2; 3"), " 1  2  3 \n");
+        assert!(using("NO FIELDS", "1").contains("Error"));
+        assert!(using("##", "\"TEXT\"").contains("E203"));
+    }
 
-        // Test DEFSTR
-        let program = "10 DEFSTR S\n20 S = 123\n30 PRINT S";
-        let result = app.execute_tw_basic(program);
-        println!("DEFSTR test output: {}", result);
-        assert!(
-            result.contains("123"),
-            "DEFSTR should convert numbers to strings"
+    #[test]
+    fn test_file_io() {
+        let root = std::env::temp_dir().join(format!("tw_file_io_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+        app.settings.ask_permission = false;
+
+        // Each program keeps its files in a folder of its own
+        let result = app.execute_tw_basic(
+            "10 OPEN \"SCORES.TXT\" FOR OUTPUT AS #1\n20 PRINT #1, \"ADA\"; 95\n30 CLOSE",
         );
+        assert_eq!(result, "");
+        let saved = fs::read_to_string(app.files_dir().join("SCORES.TXT")).unwrap();
+        assert_eq!(saved, "ADA95\n");
+
+        // Programs with the same name in different folders don't share one
+        app.last_file_path = Some(root.join("a").join("game.twb").display().to_string());
+        let first = app.files_dir();
+        app.last_file_path = Some(root.join("b").join("game.twb").display().to_string());
+        assert_ne!(app.files_dir(), first);
+        assert!(app.program_name().starts_with("game-"));
+        fs::remove_dir_all(&root).unwrap();
+    }
 
-        // Test DEFSNG (default behavior)
-        let program = "10 DEFSNG X\n20 X = 3.14159\n30 PRINT X";
-        let result = app.execute_tw_basic(program);
-        println!("DEFSNG test output: {}", result);
-        assert!(
-            result.contains("3.14159"),
-            "DEFSNG should preserve floating point precision"
+    #[test]
+    fn test_file_permissions() {
+        use time_warp_core::basic::Permission;
+
+        let root = std::env::temp_dir().join(format!("tw_permissions_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mut app = TimeWarpApp::default();
+        app.storage = storage::StoragePaths::under(&root);
+
+        // The run waits before its first write, then asks no more
+        let program = "10 PRINT \"START\"\n\
+                       20 OPEN \"A.TXT\" FOR OUTPUT AS #1: PRINT #1, 1: CLOSE\n\
+                       30 OPEN \"B.TXT\" FOR APPEND AS #1: CLOSE\n\
+                       40 PRINT \"WROTE\"";
+        assert_eq!(app.execute_tw_basic(program), "START\n");
+        assert_eq!(app.pending_permission, Some(Permission::WriteFiles));
+        assert!(!app.files_dir().join("A.TXT").exists());
+        app.answer_permission(true);
+        assert_eq!(app.pending_permission, None);
+        assert_eq!(app.output, "START\nWROTE\n");
+        assert!(app.files_dir().join("B.TXT").is_file());
+
+        // Reading needs no permission; a refusal is an error ON ERROR can catch
+        assert_eq!(
+            app.execute_tw_basic("10 OPEN \"A.TXT\" FOR INPUT AS #1: INPUT #1, A: PRINT A"),
+            "1\n"
+        );
+        app.execute_tw_basic("10 ON ERROR GOTO 100\n20 KILL \"A.TXT\"\n30 END\n100 PRINT ERR");
+        assert_eq!(app.pending_permission, Some(Permission::DeleteFiles));
+        app.answer_permission(false);
+        assert_eq!(app.output, "70\n");
+        assert!(app.files_dir().join("A.TXT").exists());
+
+        // A remembered answer is kept for the program, in the settings file
+        app.execute_tw_basic("10 KILL \"A.TXT\"");
+        app.remember_permission = true;
+        app.answer_permission(true);
+        assert!(!app.files_dir().join("A.TXT").exists());
+        let saved = settings::Settings::load(&app.storage.settings_file).unwrap();
+        assert!(saved.program_permissions[&app.program_name()][&Permission::DeleteFiles]);
+        assert_eq!(
+            app.execute_tw_basic("10 KILL \"B.TXT\": PRINT \"GONE\""),
+            "GONE\n"
         );
+        app.execute_tw_basic("10 MKDIR \"OLD\"");
+        assert_eq!(app.pending_permission, Some(Permission::WriteFiles));
 
-        // Test CLEAR resets type defaults
-        let program = "10 DEFINT A-Z\n20 CLEAR\n30 A = 3.14\n40 PRINT A";
-        let result = app.execute_tw_basic(program);
-        println!("CLEAR type defaults test output: {}", result);
-        assert!(
-            result.contains("3.14"),
-            "CLEAR should reset type defaults to single precision"
+        // Without asking, programs go ahead
+        app.cancel_running_program();
+        app.settings.ask_permission = false;
+        assert_eq!(
+            app.execute_tw_basic("10 MKDIR \"OLD\": RMDIR \"OLD\": PRINT \"OK\""),
+            "OK\n"
         );
+        fs::remove_dir_all(&root).unwrap();
+    }
 
-        println!("\n=== TYPE DECLARATION COMMANDS TEST PASSED ===");
+    #[test]
+    fn test_poke_background() {
+        // A Commodore 64's background colour sets the canvas's
+        let mut app = TimeWarpApp::default();
+        assert_eq!(
+            app.execute_tw_basic("10 POKE 53281, 6: PRINT PEEK(53281)"),
+            "6\n"
+        );
+        assert_eq!(app.canvas_background, egui::Color32::from_rgb(0, 0, 170));
     }
 
     #[test]
-    fn test_system_functions() {
-        println!("\n=== TESTING SYSTEM FUNCTIONS ===");
+    fn test_inkey() {
+        use time_warp_core::basic::{keyboard, ExecutionResult, Interpreter, StepMode};
+
+        // INKEY$ reads typed keys in order, and "" at once when there are none
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 PRINT \"[\" + INKEY$ + \"]\"\n20 PRINT INKEY$ = CHR$(0) + \"H\"\n30 PRINT \"[\" + INKEY$ + \"]\"")
+            .unwrap();
+        interpreter.press_key("a");
+        interpreter.press_key(keyboard::UP);
+        match interpreter.run(StepMode::Continue) {
+            Ok(ExecutionResult::Complete { output, .. }) => assert_eq!(output, "[a]\n-1\n[]\n"),
+            other => panic!("expected the run to finish, got {:?}", other),
+        }
+        assert_eq!(keyboard::function_key(1).as_deref(), Some("\0;"));
+
+        // A program waiting for a key runs on between frames until one comes
         let mut app = TimeWarpApp::default();
+        app.code = "10 PRINT \"PRESS A KEY\"\n\
+                    20 K$ = INKEY$: IF K$ = \"\" THEN GOTO 20\n\
+                    30 CLS: PRINT \"GOT \" + K$"
+            .to_string();
+        app.execute_code();
+        assert!(app.polling_input);
+        assert_eq!(app.output, "PRESS A KEY\n");
+        let ctx = egui::Context::default();
+        app.pump_program_input(&ctx);
+        assert!(app.polling_input);
+        app.basic_interpreter.as_mut().unwrap().press_key("q");
+        app.pump_program_input(&ctx);
+        assert!(!app.polling_input);
+        assert!(app.basic_interpreter.is_none());
+        // CLS clears what earlier frames printed
+        assert_eq!(app.output, "GOT q\n");
+
+        // Waiting doesn't run into the time limit, and Stop ends it
+        app.settings.execution_timeout_ms = 1;
+        app.code = "10 IF INKEY$ = \"\" THEN GOTO 10".to_string();
+        app.execute_code();
+        for _ in 0..3 {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            app.pump_program_input(&ctx);
+        }
+        assert!(app.polling_input);
+        app.stop_program();
+        assert!(!app.polling_input);
+        assert!(app.basic_interpreter.is_none());
+        assert!(app.output.ends_with("Stopped.\n"));
+    }
 
-        // Test DATE$ function
-        let program = "PRINT DATE$";
-        let result = app.execute_tw_basic(program);
-        println!("DATE$ test output: {}", result);
-        // Should return a date string in MM-DD-YYYY format
-        assert!(result.contains("-"), "DATE$ should return formatted date");
+    #[test]
+    fn test_mouse_input() {
+        use time_warp_core::basic::pump::{Mouse, LEFT_BUTTON, RIGHT_BUTTON};
 
-        // Test TIME$ function
-        let program = "PRINT TIME$";
-        let result = app.execute_tw_basic(program);
-        println!("TIME$ test output: {}", result);
-        // Should return a time string in HH:MM:SS format
-        assert!(result.contains(":"), "TIME$ should return formatted time");
+        let mut app = TimeWarpApp::default();
+        let ctx = egui::Context::default();
+        let mut interpreter = time_warp_core::basic::Interpreter::new();
+        interpreter.set_mouse(Mouse {
+            x: 12.4,
+            y: -30.0,
+            buttons: LEFT_BUTTON | RIGHT_BUTTON,
+        });
+        match interpreter.execute("10 PRINT MOUSEX; \",\"; MOUSEY; \",\"; MOUSEB") {
+            Ok(time_warp_core::basic::ExecutionResult::Complete { output, .. }) => {
+                assert_eq!(output, "12,-30,3\n")
+            }
+            other => panic!("expected the run to finish, got {:?}", other),
+        }
 
-        // Test TIMER function
-        let program = "PRINT TIMER";
-        let result = app.execute_tw_basic(program);
-        println!("TIMER test output: {}", result);
-        // Should return a number (seconds since midnight)
-        assert!(
-            result
-                .chars()
-                .all(|c| c.is_numeric() || c == '.' || c == '\n'),
-            "TIMER should return a numeric value"
-        );
+        // Click to draw: the program waits for a button, then goes where the mouse is
+        app.code = "10 IF MOUSEB = 0 THEN GOTO 10\n\
+                    20 SETXY MOUSEX, MOUSEY\n\
+                    30 PRINT MOUSEX; \",\"; MOUSEY"
+            .to_string();
+        app.execute_code();
+        assert!(app.polling_input);
+        app.pump_program_input(&ctx);
+        assert!(app.polling_input);
+        app.canvas_mouse = Mouse {
+            x: 50.0,
+            y: 20.0,
+            buttons: LEFT_BUTTON,
+        };
+        app.pump_program_input(&ctx);
+        assert!(!app.polling_input);
+        assert_eq!(app.output, "Moved to (50, 20)\n50,20\n");
+        let turtle = &app.turtles[DEFAULT_TURTLE];
+        assert_eq!((turtle.x, turtle.y), (50.0, 20.0));
+
+        // A click over the canvas sets off ON MOUSE GOSUB
+        app.code = "10 ON MOUSE GOSUB 100\n20 MOUSE ON\n\
+                    30 IF INKEY$ = \"\" THEN GOTO 30\n\
+                    100 PRINT \"CLICK\": END"
+            .to_string();
+        app.execute_code();
+        app.pump_program_input(&ctx);
+        assert!(app.polling_input);
+        app.canvas_clicked = true;
+        app.pump_program_input(&ctx);
+        assert!(!app.polling_input);
+        assert_eq!(app.output, "CLICK\n");
+    }
 
-        // Test ENVIRON$ with variable name
-        let program = "PRINT ENVIRON$(\"PATH\")";
-        let result = app.execute_tw_basic(program);
-        println!("ENVIRON$ test output: {}", result);
-        // Should return the PATH environment variable or empty string
-        // (We can't assert specific content since it depends on the environment)
+    #[test]
+    fn test_key_and_timer_traps_in_the_ide() {
+        let mut app = TimeWarpApp::default();
+        // Loops waiting for their traps don't run into the time limit
+        app.settings.execution_timeout_ms = 1;
+        let ctx = egui::Context::default();
 
-        // Test ENVIRON$ with numeric index
-        let program = "PRINT ENVIRON$(1)";
-        let result = app.execute_tw_basic(program);
-        println!("ENVIRON$ numeric test output: {}", result);
-        // Should return the first environment variable in KEY=VALUE format
+        // ON TIMER ticks while the program waits between frames
+        app.code = "10 ON TIMER(0.05) GOSUB 100: TIMER ON\n20 GOTO 20\n\
+                    100 T = T + 1: PRINT \"TICK\"; T\n110 IF T = 2 THEN GOTO 130\n120 RETURN\n130 END"
+            .to_string();
+        app.execute_code();
+        for _ in 0..100 {
+            if !app.polling_input {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            app.pump_program_input(&ctx);
+        }
+        assert_eq!(app.output, "TICK1\nTICK2\n");
+
+        // The cursor keys and F-keys set off ON KEY
+        app.code =
+            "10 ON KEY(11) GOSUB 100: KEY(11) ON\n20 GOTO 20\n100 PRINT \"UP\": END".to_string();
+        app.execute_code();
+        assert!(app.polling_input);
+        let press = |key| egui::Event::Key {
+            key,
+            pressed: true,
+            repeat: false,
+            modifiers: egui::Modifiers::NONE,
+        };
+        ctx.begin_frame(egui::RawInput {
+            events: vec![press(egui::Key::ArrowDown), press(egui::Key::ArrowUp)],
+            ..Default::default()
+        });
+        app.pump_program_input(&ctx);
+        let _ = ctx.end_frame();
+        assert!(!app.polling_input);
+        assert_eq!(app.output, "UP\n");
+    }
 
-        // Test INT(RND(1)*100) expression
-        let program = "PRINT INT(RND(1)*100)";
-        let result = app.execute_tw_basic(program);
-        println!("INT(RND(1)*100) test output: {}", result);
-        // Should return an integer between 0 and 99
-        let num_result: f64 = result.trim().parse().expect("Should parse as number");
-        assert!(
-            num_result >= 0.0 && num_result < 100.0,
-            "INT(RND(1)*100) should return 0-99"
-        );
+    #[test]
+    fn test_sleep_and_wait() {
+        use std::time::{Duration, Instant};
+
+        // Outside the IDE nothing waits; the pause counts against the
+        // instruction limit, so a long one ends the run
+        let mut interpreter = time_warp_core::basic::Interpreter::new();
+        interpreter.max_instructions = 5_000_000;
+        let started = Instant::now();
+        assert!(interpreter
+            .execute("10 WAIT 6: SLEEP 0.05: PRINT \"DONE\"")
+            .is_ok());
+        let error = format!("{:?}", interpreter.execute("10 SLEEP 60: PRINT \"LATE\""));
+        assert!(error.contains("Execution timeout"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let error = format!("{:?}", interpreter.execute("10 SLEEP -1"));
+        assert!(error.contains("SLEEP takes 0 or more"), "{}", error);
+
+        // In the IDE the run hands back each frame until the time is up
+        let mut app = TimeWarpApp::default();
+        let ctx = egui::Context::default();
+        app.code = "10 PRINT \"A\": SLEEP 0.1: PRINT \"B\"".to_string();
+        app.execute_code();
+        assert!(app.polling_input);
+        app.pump_program_input(&ctx);
+        assert!(app.polling_input);
+        assert_eq!(app.output, "A\n");
+        std::thread::sleep(Duration::from_millis(120));
+        app.pump_program_input(&ctx);
+        assert!(!app.polling_input);
+        assert_eq!(app.output, "A\nB\n");
+
+        // So does a debug step over a SLEEP, which then lands on the next line
+        app.code = "10 SLEEP 0.1\n20 PRINT \"B\"".to_string();
+        app.start_debug_session();
+        assert_eq!(app.current_debug_line, Some(1));
+        let started = Instant::now();
+        app.step_debug(time_warp_core::basic::StepMode::StepOver);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(app.polling_input);
+        assert!(app.debug_state == DebugState::Running);
+        std::thread::sleep(Duration::from_millis(120));
+        app.pump_program_input(&ctx);
+        assert!(app.debug_state == DebugState::Paused);
+        assert_eq!(app.current_debug_line, Some(2));
+        app.stop_program();
+
+        // SLEEP alone waits for a key, which INKEY$ then reads
+        app.code = "10 SLEEP\n20 PRINT INKEY$".to_string();
+        app.execute_code();
+        app.pump_program_input(&ctx);
+        assert!(app.polling_input);
+        app.basic_interpreter.as_mut().unwrap().press_key("x");
+        app.pump_program_input(&ctx);
+        assert_eq!(app.output, "x\n");
+
+        // Stop works while a program sleeps
+        app.code = "10 SLEEP 100".to_string();
+        app.execute_code();
+        assert!(app.polling_input);
+        app.stop_program();
+        assert!(!app.polling_input);
+    }
 
-        println!("\n=== SYSTEM FUNCTIONS TEST PASSED ===");
+    #[test]
+    fn test_play_music() {
+        use std::time::Duration;
+        use time_warp_core::basic::music;
+
+        // Notes are queued for whatever drives the interpreter to play
+        let mut interpreter = time_warp_core::basic::Interpreter::new();
+        assert!(interpreter
+            .execute("10 PLAY \"T120 L4 C D E8 F#8\": PLAY \"P4 MS O3 A.\"")
+            .is_ok());
+        let notes = interpreter.take_music();
+        assert_eq!(notes.len(), 6);
+        assert!((notes[0].frequency.unwrap() - 523.25).abs() < 0.01);
+        assert_eq!(notes[0].length, Duration::from_millis(500));
+        assert_eq!(notes[0].sounding, Duration::from_micros(437_500));
+        assert_eq!(notes[2].length, Duration::from_millis(250));
+        assert!((notes[3].frequency.unwrap() - 739.99).abs() < 0.01);
+        assert_eq!(notes[4].frequency, None);
+        assert_eq!(notes[5].frequency, Some(440.0));
+        assert_eq!(notes[5].length, Duration::from_millis(750));
+        assert_eq!(notes[5].sounding, Duration::from_micros(562_500));
+        assert!(interpreter.take_music().is_empty());
+        let error = format!("{:?}", interpreter.execute("10 PLAY \"O9 C\""));
+        assert!(error.contains("octave takes 0 to 6, not 9"), "{}", error);
+
+        let wav = music::to_wav(&notes[..1]);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 2 * music::SAMPLE_RATE as usize / 2);
+
+        // The IDE writes each batch to its own private folder, and forgets it on stop
+        #[cfg(feature = "audio")]
+        {
+            let mut player = audio::MusicPlayer::default();
+            player.queue(&notes[..1]).unwrap();
+            player.queue(&notes[1..2]).unwrap();
+            assert!(player.busy());
+            player.stop();
+            assert!(!player.busy());
+        }
+
+        // In the IDE the program waits for the music before carrying on
+        let mut app = TimeWarpApp::default();
+        app.settings.play_sound = false;
+        let ctx = egui::Context::default();
+        app.code = "10 PLAY \"T240 C\": PRINT \"DONE\"".to_string();
+        app.execute_code();
+        assert!(app.polling_input);
+        assert_eq!(app.output, "");
+        std::thread::sleep(Duration::from_millis(270));
+        app.pump_program_input(&ctx);
+        assert_eq!(app.output, "DONE\n");
+
+        // MB plays in the background, and ON PLAY fires as the notes run low
+        app.code = "10 ON PLAY(2) GOSUB 100: PLAY ON\n\
+                    20 PLAY \"MB T255 L16 C C\": PRINT \"ON\"\n\
+                    30 IF F = 0 THEN GOTO 30\n\
+                    40 PRINT \"LOW\": END\n\
+                    100 F = 1: RETURN"
+            .to_string();
+        app.execute_code();
+        for _ in 0..20 {
+            if !app.polling_input {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+            app.pump_program_input(&ctx);
+        }
+        assert!(!app.polling_input);
+        assert_eq!(app.output, "ON\nLOW\n");
+    }
+
+    #[test]
+    fn test_legacy_storage_migration() {
+        let root = temp_path("time_warp_storage_test");
+        let _ = fs::remove_dir_all(&root);
+        let legacy = root.join("legacy");
+        fs::create_dir_all(legacy.join("templates")).unwrap();
+        fs::write(legacy.join("templates").join("Maze.twb"), "10 END").unwrap();
+        fs::write(legacy.join("settings.json"), "{}").unwrap();
+
+        let paths = storage::StoragePaths::under(&root.join("new"));
+        let moved = storage::migrate_legacy(&legacy, &paths).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert!(paths.templates_dir.join("Maze.twb").is_file());
+        assert!(paths.settings_file.is_file());
+        assert!(!legacy.exists());
+
+        // Running again is harmless
+        assert!(storage::migrate_legacy(&legacy, &paths).unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
     }
 }
@@ -0,0 +1,120 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::collections::BTreeMap;
+use time_warp_core::basic::{Interpreter, Permission};
+use time_warp_core::json::Json;
+
+/// Allow or Deny answers kept with "Remember for this program", by the
+/// program's name and where it is saved (see `program_name`)
+pub(crate) type ProgramPermissions = BTreeMap<String, BTreeMap<Permission, bool>>;
+
+pub(crate) fn to_json(remembered: &ProgramPermissions) -> Json {
+    Json::Object(
+        remembered
+            .iter()
+            .map(|(program, answers)| {
+                let answers = answers
+                    .iter()
+                    .map(|(permission, allowed)| {
+                        (permission.name().to_string(), Json::Bool(*allowed))
+                    })
+                    .collect();
+                (program.clone(), Json::Object(answers))
+            })
+            .collect(),
+    )
+}
+
+/// Answers for permissions this version doesn't know are dropped
+pub(crate) fn from_json(json: &Json) -> ProgramPermissions {
+    let Json::Object(programs) = json else {
+        return ProgramPermissions::new();
+    };
+    programs
+        .iter()
+        .filter_map(|(program, answers)| {
+            let Json::Object(answers) = answers else {
+                return None;
+            };
+            let answers: BTreeMap<Permission, bool> = answers
+                .iter()
+                .filter_map(|(name, allowed)| match allowed {
+                    Json::Bool(allowed) => Some((Permission::from_name(name)?, *allowed)),
+                    _ => None,
+                })
+                .collect();
+            (!answers.is_empty()).then(|| (program.clone(), answers))
+        })
+        .collect()
+}
+
+impl TimeWarpApp {
+    /// Give a run the program's sandbox folder, and have it ask before
+    /// changing files unless it has a remembered answer
+    pub(crate) fn set_up_files(&self, interpreter: &mut Interpreter) {
+        interpreter.set_files_dir(Some(self.files_dir()));
+        interpreter.set_asking_permission(self.settings.ask_permission);
+        if let Some(answers) = self.settings.program_permissions.get(&self.program_name()) {
+            for (&permission, &allowed) in answers {
+                interpreter.set_permission(permission, allowed);
+            }
+        }
+    }
+
+    /// Tell the waiting run whether it may go ahead, remembering the answer
+    /// for the program when asked to, and carry on
+    pub(crate) fn answer_permission(&mut self, allowed: bool) {
+        let Some(permission) = self.pending_permission.take() else {
+            return;
+        };
+        if std::mem::take(&mut self.remember_permission) {
+            self.settings
+                .program_permissions
+                .entry(self.program_name())
+                .or_default()
+                .insert(permission, allowed);
+            if let Err(err) = self.settings.save(&self.storage.settings_file) {
+                self.show_error(format!("Could not save settings: {}", err));
+            }
+        }
+        if let Some(interpreter) = self.basic_interpreter.as_mut() {
+            let started = std::time::Instant::now();
+            let result = interpreter.answer_permission(permission, allowed);
+            self.continue_basic_run(result, started.elapsed());
+        }
+        self.finish_test_run();
+    }
+
+    pub(crate) fn render_permission_prompt(&mut self, ctx: &egui::Context) {
+        let Some(permission) = self.pending_permission else {
+            return;
+        };
+        let (mut allow, mut deny) = (false, false);
+        egui::Window::new("🔒 Permission")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "This program wants to {} — allow?",
+                    permission.name()
+                ));
+                ui.weak(format!(
+                    "Only its own folder, {}",
+                    self.files_dir().display()
+                ));
+                ui.add_space(6.0);
+                ui.checkbox(&mut self.remember_permission, "Remember for this program");
+                ui.horizontal(|ui| {
+                    allow = ui.button("✅ Allow").clicked();
+                    deny = ui
+                        .button("🚫 Deny")
+                        .on_hover_text("The program stops with \"Permission denied\"")
+                        .clicked();
+                });
+            });
+        if allow || deny {
+            self.answer_permission(allow);
+        }
+    }
+}
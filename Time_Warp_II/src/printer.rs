@@ -0,0 +1,89 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+use rfd::FileDialog;
+use std::io::Write;
+use std::path::Path;
+
+/// Add `text` to the end of the file at `path`, creating it if need be
+pub(crate) fn append_to_file(path: &Path, text: &str) -> std::io::Result<()> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())
+}
+
+impl TimeWarpApp {
+    /// Take what the program LPRINTed: it is shown in the Printer window and,
+    /// when a printer file is set, added to the end of that file
+    pub(crate) fn receive_printout(&mut self, printed: String) {
+        if printed.is_empty() {
+            return;
+        }
+        if let Some(path) = self.settings.printer_file.clone() {
+            if let Err(err) = append_to_file(&path, &printed) {
+                self.show_error(format!("Could not print to {}: {}", path.display(), err));
+            }
+        }
+        self.printout.push_str(&printed);
+        self.show_printer = true;
+    }
+
+    pub(crate) fn render_printer(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_printer;
+
+        egui::Window::new("🖨 Printer")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    match self.settings.printer_file.clone() {
+                        Some(path) => {
+                            ui.label(format!("Also printing to {}", path.display()));
+                            if ui.button("Stop").clicked() {
+                                self.settings.printer_file = None;
+                                self.apply_settings(ctx);
+                            }
+                        }
+                        None => {
+                            if ui
+                                .button("📄 Print to File…")
+                                .on_hover_text("Add what programs LPRINT to the end of a text file")
+                                .clicked()
+                            {
+                                if let Some(path) = FileDialog::new()
+                                    .add_filter("Text", &["txt"])
+                                    .set_file_name("printout.txt")
+                                    .save_file()
+                                {
+                                    self.settings.printer_file = Some(path);
+                                    self.apply_settings(ctx);
+                                }
+                            }
+                        }
+                    }
+                    if ui.button("🗑 Clear").clicked() {
+                        self.printout.clear();
+                    }
+                });
+                ui.separator();
+
+                if self.printout.is_empty() {
+                    ui.label("Nothing printed yet. LPRINT and LPRINT USING print here.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::Label::new(egui::RichText::new(&self.printout).monospace())
+                                .wrap(false),
+                        );
+                    });
+            });
+
+        self.show_printer = open;
+    }
+}
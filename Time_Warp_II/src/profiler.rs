@@ -0,0 +1,149 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+use time_warp_core::basic::LineProfile;
+
+/// Column the profiler table is sorted by
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ProfileSort {
+    Line,
+    Hits,
+    TotalTime,
+    AverageTime,
+}
+
+impl TimeWarpApp {
+    /// Profile entries ordered by the column the user picked in the profiler table
+    pub(crate) fn sorted_profile(&self) -> Vec<LineProfile> {
+        let mut entries = self.profile_results.clone();
+        entries.sort_by(|a, b| {
+            let ordering = match self.profile_sort {
+                ProfileSort::Line => a.line.cmp(&b.line),
+                ProfileSort::Hits => a.hits.cmp(&b.hits),
+                ProfileSort::TotalTime => a.total_time.cmp(&b.total_time),
+                ProfileSort::AverageTime => a.average_time().cmp(&b.average_time()),
+            };
+            let ordering = if self.profile_sort_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            ordering.then(a.line.cmp(&b.line))
+        });
+        entries
+    }
+
+    /// Gutter colour for a line, from pale yellow (rarely run) to red (hottest line)
+    pub(crate) fn heat_color(hits: u64, max_hits: u64) -> egui::Color32 {
+        if hits == 0 || max_hits == 0 {
+            return egui::Color32::TRANSPARENT;
+        }
+        let heat = (hits as f32 / max_hits as f32).clamp(0.0, 1.0);
+        egui::Color32::from_rgb(
+            255,
+            (230.0 - 190.0 * heat) as u8,
+            (150.0 - 130.0 * heat) as u8,
+        )
+    }
+
+    /// Editor gutter marks colouring each line by how often the last
+    /// profiled run ran it
+    pub(crate) fn profile_marks(&self) -> Vec<(usize, egui::Color32, String)> {
+        let max_hits = self
+            .profile_results
+            .iter()
+            .map(|entry| entry.hits)
+            .max()
+            .unwrap_or(0);
+        self.profile_results
+            .iter()
+            .filter(|entry| entry.hits > 0)
+            .map(|entry| {
+                (
+                    entry.line,
+                    Self::heat_color(entry.hits, max_hits),
+                    format!("Line {} ran {} times", entry.line, entry.hits),
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn render_profiler_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_profiler;
+        let entries = self.sorted_profile();
+        let source_lines: Vec<&str> = self.code.lines().collect();
+        let mut clicked_sort = None;
+
+        egui::Window::new("📊 Profiler")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                let total_hits: u64 = entries.iter().map(|e| e.hits).sum();
+                ui.label(format!(
+                    "{} lines executed, {} statements in total",
+                    entries.len(),
+                    total_hits
+                ));
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("profiler_table")
+                            .striped(true)
+                            .num_columns(5)
+                            .show(ui, |ui| {
+                                for (label, sort) in [
+                                    ("Line", ProfileSort::Line),
+                                    ("Hits", ProfileSort::Hits),
+                                    ("Total (ms)", ProfileSort::TotalTime),
+                                    ("Avg (µs)", ProfileSort::AverageTime),
+                                ] {
+                                    let arrow = if self.profile_sort != sort {
+                                        ""
+                                    } else if self.profile_sort_descending {
+                                        " ⏷"
+                                    } else {
+                                        " ⏶"
+                                    };
+                                    if ui.button(format!("{}{}", label, arrow)).clicked() {
+                                        clicked_sort = Some(sort);
+                                    }
+                                }
+                                ui.strong("Code");
+                                ui.end_row();
+
+                                for entry in &entries {
+                                    ui.monospace(entry.line.to_string());
+                                    ui.monospace(entry.hits.to_string());
+                                    ui.monospace(format!(
+                                        "{:.3}",
+                                        entry.total_time.as_secs_f64() * 1000.0
+                                    ));
+                                    ui.monospace(format!(
+                                        "{:.1}",
+                                        entry.average_time().as_secs_f64() * 1_000_000.0
+                                    ));
+                                    ui.monospace(
+                                        source_lines
+                                            .get(entry.line.saturating_sub(1))
+                                            .map(|l| l.trim())
+                                            .unwrap_or(""),
+                                    );
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        if let Some(sort) = clicked_sort {
+            if self.profile_sort == sort {
+                self.profile_sort_descending = !self.profile_sort_descending;
+            } else {
+                self.profile_sort = sort;
+                self.profile_sort_descending = sort != ProfileSort::Line;
+            }
+        }
+        self.show_profiler = open;
+    }
+}
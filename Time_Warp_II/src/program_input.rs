@@ -0,0 +1,178 @@
+use crate::{DebugState, TimeWarpApp};
+use eframe::egui;
+use time_warp_core::basic::pump::{Mouse, LEFT_BUTTON, MIDDLE_BUTTON, RIGHT_BUTTON};
+use time_warp_core::basic::{keyboard, EventSource, StepMode};
+
+/// F1 to F10, which are KEY(1) to KEY(10) for ON KEY
+const FUNCTION_KEYS: [egui::Key; 10] = [
+    egui::Key::F1,
+    egui::Key::F2,
+    egui::Key::F3,
+    egui::Key::F4,
+    egui::Key::F5,
+    egui::Key::F6,
+    egui::Key::F7,
+    egui::Key::F8,
+    egui::Key::F9,
+    egui::Key::F10,
+];
+
+/// The key `event` presses, unless Ctrl, Alt or the like is held for one
+/// of the IDE's shortcuts
+fn plain_key(event: &egui::Event) -> Option<egui::Key> {
+    match event {
+        egui::Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+            ..
+        } if !modifiers.any() => Some(*key),
+        _ => None,
+    }
+}
+
+/// The KEY(n) that ON KEY traps for `key`: F1 to F10 are 1 to 10, and the
+/// cursor keys up, left, right and down are 11 to 14
+fn trapped_key(key: egui::Key) -> Option<EventSource> {
+    let number = match key {
+        egui::Key::ArrowUp => 11,
+        egui::Key::ArrowLeft => 12,
+        egui::Key::ArrowRight => 13,
+        egui::Key::ArrowDown => 14,
+        _ => FUNCTION_KEYS.iter().position(|&f| f == key)? as u8 + 1,
+    };
+    Some(EventSource::Key(number))
+}
+
+/// What INKEY$ reads for `event`, if it is a key the program should see
+fn program_key(event: &egui::Event) -> Option<String> {
+    let (key, modifiers) = match event {
+        egui::Event::Text(text) => return Some(text.clone()),
+        egui::Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+            ..
+        } => (*key, *modifiers),
+        _ => return None,
+    };
+    let code = match key {
+        egui::Key::Enter => "\r",
+        egui::Key::Escape => "\u{1b}",
+        egui::Key::Backspace => "\u{8}",
+        egui::Key::Tab => "\t",
+        egui::Key::ArrowUp => keyboard::UP,
+        egui::Key::ArrowDown => keyboard::DOWN,
+        egui::Key::ArrowLeft => keyboard::LEFT,
+        egui::Key::ArrowRight => keyboard::RIGHT,
+        egui::Key::Home => keyboard::HOME,
+        egui::Key::End => keyboard::END,
+        egui::Key::PageUp => keyboard::PAGE_UP,
+        egui::Key::PageDown => keyboard::PAGE_DOWN,
+        egui::Key::Insert => keyboard::INSERT,
+        egui::Key::Delete => keyboard::DELETE,
+        _ => {
+            let function = FUNCTION_KEYS.iter().position(|&f| f == key)?;
+            // Ctrl or Alt with F-keys is left to the IDE's shortcuts
+            if modifiers.any() {
+                return None;
+            }
+            return keyboard::function_key(function as u8 + 1);
+        }
+    };
+    Some(code.to_string())
+}
+
+impl TimeWarpApp {
+    /// Pass the keys typed and what the mouse did since the last frame to a
+    /// run waiting on them, then let it carry on until it waits again or
+    /// finishes. F-keys and cursor keys set off ON KEY GOSUB, and a click
+    /// over the canvas ON MOUSE GOSUB.
+    pub(crate) fn pump_program_input(&mut self, ctx: &egui::Context) {
+        if !self.polling_input {
+            return;
+        }
+        // Keys typed in the editor stay there; the run takes the keyboard as it starts
+        if std::mem::take(&mut self.grab_keyboard) {
+            ctx.memory_mut(|memory| memory.stop_text_input());
+        }
+        let (keys, trapped): (Vec<String>, Vec<EventSource>) = if ctx.wants_keyboard_input() {
+            Default::default()
+        } else {
+            ctx.input(|input| {
+                let events = &input.events;
+                (
+                    events.iter().filter_map(program_key).collect(),
+                    events
+                        .iter()
+                        .filter_map(plain_key)
+                        .filter_map(trapped_key)
+                        .collect(),
+                )
+            })
+        };
+        self.polling_input = false;
+        let Some(interpreter) = self.basic_interpreter.as_mut() else {
+            return;
+        };
+        for key in &keys {
+            interpreter.press_key(key);
+        }
+        for source in trapped {
+            interpreter.raise_event(source);
+        }
+        interpreter.set_mouse(self.canvas_mouse);
+        if std::mem::take(&mut self.canvas_clicked) {
+            interpreter.raise_event(EventSource::Mouse);
+        }
+        // A debug step that slept carries on as the same step
+        let stepping = self.debug_state == DebugState::Running;
+        let mode = if stepping {
+            self.debug_step_mode
+        } else {
+            StepMode::Continue
+        };
+        let started = std::time::Instant::now();
+        let result = interpreter.run(mode);
+        if stepping {
+            self.finish_debug_step(result, started.elapsed());
+        } else {
+            self.continue_basic_run(result, started.elapsed());
+            self.finish_test_run();
+        }
+        if self.polling_input {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Note where the mouse is over the canvas, `at` in turtle coordinates,
+    /// and which buttons are down
+    pub(crate) fn track_canvas_mouse(&mut self, ui: &egui::Ui, at: egui::Pos2) {
+        let (buttons, pressed) = ui.input(|input| {
+            let pointer = &input.pointer;
+            let buttons = [
+                (egui::PointerButton::Primary, LEFT_BUTTON),
+                (egui::PointerButton::Secondary, RIGHT_BUTTON),
+                (egui::PointerButton::Middle, MIDDLE_BUTTON),
+            ]
+            .iter()
+            .filter(|(button, _)| pointer.button_down(*button))
+            .fold(0, |held, (_, bit)| held | bit);
+            (buttons, pointer.any_pressed())
+        });
+        self.canvas_mouse = Mouse {
+            x: at.x as f64,
+            y: at.y as f64,
+            buttons,
+        };
+        self.canvas_clicked |= pressed && self.polling_input;
+    }
+
+    /// ⏹ Stop: end a run that is sleeping or waiting on the keyboard or
+    /// mouse, keeping what it printed
+    pub(crate) fn stop_program(&mut self) {
+        self.cancel_running_program();
+        self.testing = false;
+        self.output.push_str("\nStopped.\n");
+    }
+}
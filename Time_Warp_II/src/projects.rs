@@ -0,0 +1,343 @@
+use crate::settings::TIMEOUTS_MS;
+use crate::TimeWarpApp;
+use eframe::egui;
+use rfd::FileDialog;
+use std::path::{Path, PathBuf};
+use time_warp_core::json::Json;
+use time_warp_core::Language;
+
+/// Extension of project files
+pub(crate) const PROJECT_EXTENSION: &str = "twproj";
+
+/// Whether `location` names a project file rather than a program
+pub(crate) fn is_project_file(location: &str) -> bool {
+    Path::new(location)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(PROJECT_EXTENSION))
+}
+
+/// A lesson made of several files: the programs, the data files they read,
+/// and settings that apply while it is open. Members are stored relative to
+/// the project file so a project folder can be copied to another machine.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Project {
+    pub path: PathBuf,
+    pub name: String,
+    pub sources: Vec<String>,
+    pub data: Vec<String>,
+    pub language: Option<Language>, // for members whose extension names none
+    pub timeout_ms: Option<u64>,    // in place of the one in Settings
+}
+
+impl Project {
+    /// An empty project to be saved at `path`, named after the file
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            name: path.file_stem().map_or_else(
+                || "Project".to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            ),
+            sources: Vec::new(),
+            data: Vec::new(),
+            language: None,
+            timeout_ms: None,
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let list =
+            |files: &[String]| Json::Array(files.iter().map(|file| file.as_str().into()).collect());
+        Json::object([
+            ("name", self.name.as_str().into()),
+            ("sources", list(&self.sources)),
+            ("data", list(&self.data)),
+            (
+                "settings",
+                Json::object([
+                    (
+                        "language",
+                        self.language
+                            .map_or(Json::Null, |language| language.name().into()),
+                    ),
+                    (
+                        "timeout_ms",
+                        self.timeout_ms
+                            .map_or(Json::Null, |ms| (ms as usize).into()),
+                    ),
+                ]),
+            ),
+        ])
+    }
+
+    /// Read a project; settings that are missing or out of range are left unset
+    pub fn from_json(path: &Path, json: &Json) -> Self {
+        let list = |key: &str| match json.get(key) {
+            Some(Json::Array(files)) => files
+                .iter()
+                .filter_map(Json::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let defaults = Self::new(path);
+        Self {
+            name: json
+                .get("name")
+                .and_then(Json::as_str)
+                .map_or(defaults.name, str::to_string),
+            sources: list("sources"),
+            data: list("data"),
+            language: json
+                .at(&["settings", "language"])
+                .and_then(Json::as_str)
+                .and_then(Language::from_name),
+            timeout_ms: json
+                .at(&["settings", "timeout_ms"])
+                .and_then(Json::as_usize)
+                .map(|ms| ms as u64)
+                .filter(|ms| TIMEOUTS_MS.contains(ms)),
+            ..defaults
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Ok(Self::from_json(path, &Json::parse(&text)?))
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::write(&self.path, self.to_json().pretty())
+    }
+
+    fn folder(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// Where a member listed as `file` is on disk
+    pub fn member_path(&self, file: &str) -> PathBuf {
+        self.folder().join(file)
+    }
+
+    /// How `path` is listed in the project: relative to its folder when
+    /// inside it, otherwise in full
+    pub fn member_name(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(self.folder()).unwrap_or(path);
+        relative.to_string_lossy().replace('\\', "/")
+    }
+
+    /// Add `path` to the programs, or to the data files if it isn't in a
+    /// language the IDE knows. Adding a member twice does nothing.
+    pub fn add_member(&mut self, path: &Path) {
+        let name = self.member_name(path);
+        let is_source = path.extension().is_some_and(|extension| {
+            Language::from_extension(&extension.to_string_lossy()).is_some()
+        });
+        let list = if is_source {
+            &mut self.sources
+        } else {
+            &mut self.data
+        };
+        if !list.contains(&name) {
+            list.push(name);
+        }
+    }
+}
+
+impl TimeWarpApp {
+    /// The run time limit, from the open project if it sets one
+    pub(crate) fn execution_timeout_ms(&self) -> u64 {
+        self.project
+            .as_ref()
+            .and_then(|project| project.timeout_ms)
+            .unwrap_or(self.settings.execution_timeout_ms)
+    }
+
+    /// Ask for a project file and open it
+    pub(crate) fn open_project_dialog(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("Time Warp Projects", &[PROJECT_EXTENSION])
+            .pick_file()
+        {
+            self.open_project(&path);
+        }
+    }
+
+    /// Make `path` the open project, show its files and open its first program
+    pub(crate) fn open_project(&mut self, path: &Path) {
+        match Project::load(path) {
+            Ok(project) => {
+                let main = project
+                    .sources
+                    .first()
+                    .map(|file| project.member_path(file));
+                self.project = Some(project);
+                self.show_project_panel = true;
+                self.remember_recent_file(&path.display().to_string());
+                if let Some(main) = main {
+                    self.open_project_member(&main);
+                }
+            }
+            Err(err) => self.show_error(format!("Could not open {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Start a project file, with the file being edited as its first program
+    pub(crate) fn new_project_dialog(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("Time Warp Projects", &[PROJECT_EXTENSION])
+            .set_file_name(format!("lesson.{}", PROJECT_EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+        let mut project = Project::new(&path);
+        if let Some(file) = &self.last_file_path {
+            project.add_member(Path::new(file));
+        }
+        self.project = Some(project);
+        self.show_project_panel = true;
+        self.save_project();
+        self.remember_recent_file(&path.display().to_string());
+    }
+
+    /// Write the open project back to its file
+    pub(crate) fn save_project(&mut self) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        if let Err(err) = project.save() {
+            let message = format!("Could not save {}: {}", project.path.display(), err);
+            self.show_error(message);
+        }
+    }
+
+    /// Open one of the project's files in a tab. The project's language is
+    /// used when the file's extension doesn't name one.
+    pub(crate) fn open_project_member(&mut self, path: &Path) {
+        let location = path.display().to_string();
+        if !self.open_location(&location) {
+            return;
+        }
+        let named = path.extension().is_some_and(|extension| {
+            Language::from_extension(&extension.to_string_lossy()).is_some()
+        });
+        if let (false, Some(language)) = (named, self.project.as_ref().and_then(|p| p.language)) {
+            self.set_language(language);
+        }
+    }
+
+    /// The project tree: its programs and data files, each opened by a click
+    pub(crate) fn render_project_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(project) = self.project.clone() else {
+            return;
+        };
+        let mut open = None;
+        let mut remove = None;
+        ui.horizontal(|ui| {
+            ui.strong(format!("📁 {}", project.name));
+            if ui
+                .small_button("✖")
+                .on_hover_text("Close the project")
+                .clicked()
+            {
+                self.project = None;
+            }
+        });
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .id_source("project_tree")
+            .show(ui, |ui| {
+                for (heading, files) in [
+                    ("Programs", &project.sources),
+                    ("Data Files", &project.data),
+                ] {
+                    egui::CollapsingHeader::new(heading)
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            if files.is_empty() {
+                                ui.weak("None yet");
+                            }
+                            for file in files {
+                                ui.horizontal(|ui| {
+                                    let path = project.member_path(file);
+                                    let current = self.last_file_path.as_deref()
+                                        == Some(&path.display().to_string());
+                                    if ui.selectable_label(current, file).clicked() {
+                                        open = Some(path);
+                                    }
+                                    if ui
+                                        .small_button("−")
+                                        .on_hover_text("Take out of the project")
+                                        .clicked()
+                                    {
+                                        remove = Some(file.clone());
+                                    }
+                                });
+                            }
+                        });
+                }
+            });
+        ui.separator();
+        let current = self.last_file_path.clone();
+        if ui
+            .add_enabled(current.is_some(), egui::Button::new("➕ Add Current File"))
+            .on_hover_text("Save a new program first")
+            .clicked()
+        {
+            if let (Some(project), Some(file)) = (&mut self.project, current) {
+                project.add_member(Path::new(&file));
+            }
+            self.save_project();
+        }
+        if ui.button("➕ Add Files...").clicked() {
+            if let Some(paths) = FileDialog::new()
+                .set_directory(project.folder())
+                .pick_files()
+            {
+                if let Some(project) = &mut self.project {
+                    for path in paths {
+                        project.add_member(&path);
+                    }
+                }
+                self.save_project();
+            }
+        }
+        egui::CollapsingHeader::new("⚙ Project Settings").show(ui, |ui| {
+            let mut settings = (project.language, project.timeout_ms);
+            egui::ComboBox::from_label("Language")
+                .selected_text(settings.0.map_or("From extension", Language::name))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut settings.0, None, "From extension");
+                    for &language in Language::ALL {
+                        ui.selectable_value(&mut settings.0, Some(language), language.name());
+                    }
+                });
+            let mut own_timeout = settings.1.is_some();
+            ui.checkbox(&mut own_timeout, "Own time limit");
+            if own_timeout {
+                let mut ms = settings.1.unwrap_or(self.settings.execution_timeout_ms);
+                ui.add(egui::Slider::new(&mut ms, TIMEOUTS_MS).suffix(" ms"));
+                settings.1 = Some(ms);
+            } else {
+                settings.1 = None;
+            }
+            if settings != (project.language, project.timeout_ms) {
+                if let Some(project) = &mut self.project {
+                    (project.language, project.timeout_ms) = settings;
+                }
+                self.save_project();
+            }
+        });
+        if let Some(file) = remove {
+            if let Some(project) = &mut self.project {
+                project.sources.retain(|member| *member != file);
+                project.data.retain(|member| *member != file);
+            }
+            self.save_project();
+        }
+        if let Some(path) = open {
+            self.open_project_member(&path);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::path::Path;
+
+/// How many files File → Recent remembers
+pub(crate) const MAX_RECENT_FILES: usize = 10;
+
+/// Put `location` at the top of `recent`, dropping an older entry for it
+/// and anything past the limit
+pub(crate) fn add_recent(recent: &mut Vec<String>, location: &str) {
+    recent.retain(|file| file != location);
+    recent.insert(0, location.to_string());
+    recent.truncate(MAX_RECENT_FILES);
+}
+
+impl TimeWarpApp {
+    /// Remember a file the user opened or saved, in the settings file so the
+    /// list is there next time the IDE starts
+    pub(crate) fn remember_recent_file(&mut self, location: &str) {
+        add_recent(&mut self.settings.recent_files, location);
+        self.save_recent_files();
+    }
+
+    fn save_recent_files(&mut self) {
+        if let Err(err) = self.settings.save(&self.storage.settings_file) {
+            self.show_error(format!("Could not save settings: {}", err));
+        }
+    }
+
+    /// Open a file from the Recent list. One that has gone is taken off it.
+    pub(crate) fn open_recent_file(&mut self, location: &str) {
+        if crate::projects::is_project_file(location) && Path::new(location).exists() {
+            self.open_project(Path::new(location));
+        } else if self.open_location(location) {
+            self.remember_recent_file(location);
+        } else if !location.contains("://") && !Path::new(location).exists() {
+            self.settings.recent_files.retain(|file| file != location);
+            self.save_recent_files();
+        }
+    }
+
+    /// File → Recent: the last files opened, newest first
+    pub(crate) fn render_recent_files_menu(&mut self, ui: &mut egui::Ui) {
+        if self.settings.recent_files.is_empty() {
+            ui.weak("No recent files");
+            return;
+        }
+        let mut chosen = None;
+        for location in &self.settings.recent_files {
+            let name = Path::new(location)
+                .file_name()
+                .map_or(location.clone(), |name| name.to_string_lossy().into_owned());
+            if ui.button(name).on_hover_text(location).clicked() {
+                chosen = Some(location.clone());
+            }
+        }
+        ui.separator();
+        if ui.button("Clear Recent Files").clicked() {
+            self.settings.recent_files.clear();
+            self.save_recent_files();
+            ui.close_menu();
+        }
+        if let Some(location) = chosen {
+            self.open_recent_file(&location);
+            ui.close_menu();
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use crate::editor::{self, Selection};
+use crate::keybindings::Action;
+use crate::TimeWarpApp;
+use eframe::egui;
+use regex::{NoExpand, Regex, RegexBuilder};
+use std::ops::Range;
+
+/// How the Find box text is matched against the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct SearchOptions {
+    pub regex: bool, // otherwise the text is found literally
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+/// The Find box text as a regular expression, or why it isn't one
+pub(crate) fn pattern(find: &str, options: SearchOptions) -> Result<Regex, String> {
+    let mut source = if options.regex {
+        find.to_string()
+    } else {
+        regex::escape(find)
+    };
+    if options.whole_word {
+        source = format!(r"\b(?:{})\b", source);
+    }
+    RegexBuilder::new(&source)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+/// Byte ranges of every non-empty match in `text`
+pub(crate) fn find_all(
+    text: &str,
+    find: &str,
+    options: SearchOptions,
+) -> Result<Vec<Range<usize>>, String> {
+    if find.is_empty() {
+        return Ok(Vec::new());
+    }
+    let pattern = pattern(find, options)?;
+    Ok(pattern
+        .find_iter(text)
+        .map(|found| found.range())
+        .filter(|range| !range.is_empty())
+        .collect())
+}
+
+/// What a match is replaced with: `$1`, `${name}` and `$$` are expanded in
+/// regex mode and taken literally otherwise
+fn replacement(pattern: &Regex, matched: &str, replace: &str, options: SearchOptions) -> String {
+    match (options.regex, pattern.captures(matched)) {
+        (true, Some(captures)) => {
+            let mut expanded = String::new();
+            captures.expand(replace, &mut expanded);
+            expanded
+        }
+        _ => replace.to_string(),
+    }
+}
+
+/// `text` with every match replaced, and how many there were
+pub(crate) fn replace_all(
+    text: &str,
+    find: &str,
+    replace: &str,
+    options: SearchOptions,
+) -> Result<(String, usize), String> {
+    if find.is_empty() {
+        return Ok((text.to_string(), 0));
+    }
+    let pattern = pattern(find, options)?;
+    let count = pattern.find_iter(text).count();
+    let replaced = if options.regex {
+        pattern.replace_all(text, replace)
+    } else {
+        pattern.replace_all(text, NoExpand(replace))
+    };
+    Ok((replaced.into_owned(), count))
+}
+
+/// The characters a byte range of `text` covers, as a selection
+fn to_selection(text: &str, range: &Range<usize>) -> Selection {
+    Selection {
+        anchor: text[..range.start].chars().count(),
+        head: text[..range.end].chars().count(),
+    }
+}
+
+/// The first match starting at or after character `from`, wrapping round to
+/// the top, as a selection
+pub(crate) fn find_next(
+    text: &str,
+    find: &str,
+    options: SearchOptions,
+    from: usize,
+) -> Result<Option<Selection>, String> {
+    let matches = find_all(text, find, options)?;
+    let from = editor::byte_index(text, from);
+    let found = matches
+        .iter()
+        .find(|range| range.start >= from)
+        .or(matches.first());
+    Ok(found.map(|range| to_selection(text, range)))
+}
+
+/// The last match ending at or before character `before`, wrapping round to
+/// the bottom
+pub(crate) fn find_previous(
+    text: &str,
+    find: &str,
+    options: SearchOptions,
+    before: usize,
+) -> Result<Option<Selection>, String> {
+    let matches = find_all(text, find, options)?;
+    let before = editor::byte_index(text, before);
+    let found = matches
+        .iter()
+        .rev()
+        .find(|range| range.end <= before)
+        .or(matches.last());
+    Ok(found.map(|range| to_selection(text, range)))
+}
+
+/// "3 of 17" when the selection is the third of 17 matches, otherwise
+/// just how many there are
+pub(crate) fn match_counter(text: &str, matches: &[Range<usize>], selection: Selection) -> String {
+    match matches
+        .iter()
+        .position(|range| to_selection(text, range).range() == selection.range())
+    {
+        Some(index) => format!("{} of {}", index + 1, matches.len()),
+        None => format!("{} match(es)", matches.len()),
+    }
+}
+
+/// One match listed by Find All
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FindResult {
+    pub line: usize,           // 1-based
+    pub context: String,       // the whole line the match is on
+    pub matched: Range<usize>, // byte range of the match in `context`
+    pub selection: Selection,  // the match in the code
+}
+
+/// Every match in `text` with the line it is on
+pub(crate) fn find_results(
+    text: &str,
+    find: &str,
+    options: SearchOptions,
+) -> Result<Vec<FindResult>, String> {
+    Ok(find_all(text, find, options)?
+        .into_iter()
+        .map(|range| {
+            let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[range.start..]
+                .find('\n')
+                .map_or(text.len(), |i| range.start + i);
+            let start = text[..range.start].chars().count();
+            FindResult {
+                line: text[..range.start].matches('\n').count() + 1,
+                context: text[line_start..line_end].to_string(),
+                // A match running over a newline is shown up to the line end
+                matched: range.start - line_start..range.end.min(line_end) - line_start,
+                selection: Selection {
+                    anchor: start,
+                    head: start + text[range].chars().count(),
+                },
+            }
+        })
+        .collect())
+}
+
+impl TimeWarpApp {
+    /// Report a bad regular expression under the Find box, or clear it
+    fn search_result<T>(&mut self, result: Result<T, String>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.search_error = None;
+                Some(value)
+            }
+            Err(err) => {
+                self.search_error = Some(err);
+                None
+            }
+        }
+    }
+
+    /// Select the next match after the caret
+    pub(crate) fn find_next(&mut self) {
+        let range = self.selection.range();
+        // Start past a match that is already selected so it moves on
+        let from = if range.is_empty() {
+            range.start
+        } else {
+            range.end
+        };
+        let found = find_next(&self.code, &self.find_text, self.search, from);
+        if let Some(Some(selection)) = self.search_result(found) {
+            self.pending_selection = Some(selection);
+        }
+    }
+
+    /// Select the match before the caret
+    pub(crate) fn find_previous(&mut self) {
+        let found = find_previous(
+            &self.code,
+            &self.find_text,
+            self.search,
+            self.selection.range().start,
+        );
+        if let Some(Some(selection)) = self.search_result(found) {
+            self.pending_selection = Some(selection);
+        }
+    }
+
+    /// Replace the selected match, then select the next one
+    pub(crate) fn replace_next(&mut self) {
+        let range = self.selection.range();
+        let selected = editor::selected_text(&self.code, self.selection);
+        let Some(pattern) = self.search_result(pattern(&self.find_text, self.search)) else {
+            return;
+        };
+        let whole_match = pattern
+            .find(&selected)
+            .is_some_and(|found| found.range() == (0..selected.len()) && !selected.is_empty());
+        if whole_match {
+            let with = replacement(&pattern, &selected, &self.replace_text, self.search);
+            self.edit_code(|code, _| Selection::caret(editor::replace(code, range.clone(), &with)));
+        }
+        self.find_next();
+    }
+
+    /// Replace every match, as one step for undo
+    pub(crate) fn replace_all_matches(&mut self) {
+        let replaced = replace_all(&self.code, &self.find_text, &self.replace_text, self.search);
+        if let Some((code, count)) = self.search_result(replaced) {
+            if count > 0 {
+                self.edit_code(|old, _| {
+                    *old = code;
+                    Selection::caret(0)
+                });
+            }
+        }
+    }
+
+    /// The Find/Replace bar above the editor
+    pub(crate) fn render_find_replace(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Find:");
+            let find = ui.text_edit_singleline(&mut self.find_text);
+            if find.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.find_next();
+            }
+            ui.checkbox(&mut self.search.case_sensitive, "Aa")
+                .on_hover_text("Match case");
+            ui.checkbox(&mut self.search.whole_word, "Word")
+                .on_hover_text("Whole words only");
+            ui.checkbox(&mut self.search.regex, ".*")
+                .on_hover_text("Regular expression; Replace can use $1, ${name}");
+            let shortcut = |action| self.settings.keybindings.label(action);
+            let (previous, next) = (shortcut(Action::FindPrevious), shortcut(Action::FindNext));
+            if ui
+                .button("⬆")
+                .on_hover_text(format!("Find Previous ({})", previous))
+                .clicked()
+            {
+                self.find_previous();
+            }
+            if ui.button("Find Next").on_hover_text(next).clicked() {
+                self.find_next();
+            }
+            if ui.button("Find All").clicked() {
+                self.show_find_results = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Replace:");
+            ui.text_edit_singleline(&mut self.replace_text);
+            if ui.button("Replace").clicked() {
+                self.replace_next();
+            }
+            if ui.button("Replace All").clicked() {
+                self.replace_all_matches();
+            }
+        });
+        match find_all(&self.code, &self.find_text, self.search) {
+            Ok(matches) if !self.find_text.is_empty() => {
+                self.search_error = None;
+                ui.label(match_counter(&self.code, &matches, self.selection));
+            }
+            Ok(_) => self.search_error = None,
+            Err(err) => self.search_error = Some(err),
+        }
+        if let Some(err) = &self.search_error {
+            ui.colored_label(egui::Color32::RED, err);
+        }
+        if self.show_find_results {
+            self.render_find_results(ui);
+        }
+    }
+
+    /// The Find All list. It is worked out afresh each frame so it follows
+    /// edits; clicking a match selects it in the editor.
+    fn render_find_results(&mut self, ui: &mut egui::Ui) {
+        let Ok(results) = find_results(&self.code, &self.find_text, self.search) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.strong(format!("Find All: {} result(s)", results.len()));
+            if ui.small_button("✖").on_hover_text("Close").clicked() {
+                self.show_find_results = false;
+            }
+        });
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let text = ui.visuals().text_color();
+        let marked = egui::text::TextFormat {
+            background: ui.visuals().selection.bg_fill,
+            ..egui::text::TextFormat::simple(font_id.clone(), text)
+        };
+        let plain = egui::text::TextFormat::simple(font_id, text);
+        egui::ScrollArea::vertical()
+            .id_source("find_results")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for result in &results {
+                    let mut job = egui::text::LayoutJob::default();
+                    let context = &result.context;
+                    job.append(&format!("{:>4}: ", result.line), 0.0, plain.clone());
+                    job.append(&context[..result.matched.start], 0.0, plain.clone());
+                    job.append(&context[result.matched.clone()], 0.0, marked.clone());
+                    job.append(&context[result.matched.end..], 0.0, plain.clone());
+                    if ui
+                        .add(egui::Label::new(job).sense(egui::Sense::click()))
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.pending_selection = Some(result.selection);
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,265 @@
+use crate::storage::StoragePaths;
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use time_warp_core::json::Json;
+use time_warp_core::Language;
+
+/// Where the main window was and how big, in screen points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct WindowGeometry {
+    pub position: Option<egui::Pos2>,
+    pub size: egui::Vec2,
+    pub maximized: bool,
+}
+
+/// The file the IDE keeps its last session in
+pub(crate) fn session_file(storage: &StoragePaths) -> PathBuf {
+    storage.sessions_dir.join("last_session.json")
+}
+
+/// The window geometry saved with the last session, so `main` can open the
+/// window there before the app exists
+pub(crate) fn saved_window(storage: &StoragePaths) -> Option<WindowGeometry> {
+    let text = std::fs::read_to_string(session_file(storage)).ok()?;
+    let window = Json::parse(&text).ok()?.get("window")?.clone();
+    let number = |key: &str| window.get(key).and_then(Json::as_f64).map(|n| n as f32);
+    let size = egui::vec2(number("width")?, number("height")?);
+    if size.x < 200.0 || size.y < 150.0 {
+        return None;
+    }
+    Some(WindowGeometry {
+        position: number("x").zip(number("y")).map(|(x, y)| egui::pos2(x, y)),
+        size,
+        maximized: matches!(window.get("maximized"), Some(Json::Bool(true))),
+    })
+}
+
+/// Line numbers kept per file, such as breakpoints, as a JSON object
+fn file_lines_json(files: &HashMap<String, Vec<u32>>) -> Json {
+    let mut files: Vec<(&String, &Vec<u32>)> = files.iter().collect();
+    files.sort();
+    Json::Object(
+        files
+            .into_iter()
+            .filter(|(_, lines)| !lines.is_empty())
+            .map(|(file, lines)| {
+                let lines = lines.iter().map(|&line| (line as usize).into()).collect();
+                (file.clone(), Json::Array(lines))
+            })
+            .collect(),
+    )
+}
+
+/// Read back what `file_lines_json` wrote
+fn file_lines(files: &[(String, Json)]) -> HashMap<String, Vec<u32>> {
+    files
+        .iter()
+        .filter_map(|(file, lines)| match lines {
+            Json::Array(lines) => Some((
+                file.clone(),
+                lines
+                    .iter()
+                    .filter_map(Json::as_usize)
+                    .map(|line| line as u32)
+                    .collect(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+impl TimeWarpApp {
+    /// Open files and their buffers, tab, breakpoints, bookmarks, project,
+    /// canvas view and window
+    pub(crate) fn session_json(&self) -> Json {
+        let window = match self.window_geometry {
+            Some(geometry) => {
+                let mut fields = vec![
+                    ("width".to_string(), Json::Number(geometry.size.x as f64)),
+                    ("height".to_string(), Json::Number(geometry.size.y as f64)),
+                    ("maximized".to_string(), Json::Bool(geometry.maximized)),
+                ];
+                if let Some(position) = geometry.position {
+                    fields.push(("x".to_string(), Json::Number(position.x as f64)));
+                    fields.push(("y".to_string(), Json::Number(position.y as f64)));
+                }
+                Json::Object(fields)
+            }
+            None => Json::Null,
+        };
+
+        let documents = self
+            .document_sessions()
+            .into_iter()
+            .map(|(path, code, language)| {
+                Json::object([
+                    ("file", path.map(Json::from).unwrap_or(Json::Null)),
+                    ("code", code.into()),
+                    ("language", language.name().into()),
+                ])
+            })
+            .collect();
+
+        Json::object([
+            ("documents", Json::Array(documents)),
+            ("active_document", self.active_document.into()),
+            ("active_tab", self.active_tab.into()),
+            ("breakpoints", file_lines_json(&self.breakpoints)),
+            ("bookmarks", file_lines_json(&self.bookmarks)),
+            (
+                "project",
+                self.project.as_ref().map_or(Json::Null, |project| {
+                    project.path.display().to_string().into()
+                }),
+            ),
+            ("zoom", Json::Number(self.turtle_zoom as f64)),
+            (
+                "pan",
+                Json::Array(vec![
+                    Json::Number(self.turtle_pan.x as f64),
+                    Json::Number(self.turtle_pan.y as f64),
+                ]),
+            ),
+            ("window", window),
+        ])
+    }
+
+    /// Put the IDE back the way `session_json` found it. The window itself
+    /// is placed by `main` from `saved_window`.
+    pub(crate) fn apply_session(&mut self, session: &Json) {
+        match session.get("documents") {
+            Some(Json::Array(documents)) if !documents.is_empty() => {
+                for (index, document) in documents.iter().enumerate() {
+                    if index > 0 {
+                        self.new_document();
+                    }
+                    self.apply_document_session(document);
+                }
+                let active = session.get("active_document").and_then(Json::as_usize);
+                self.switch_document(active.unwrap_or(0).min(documents.len() - 1));
+            }
+            // Sessions from before editor tabs held the one open file at the top
+            _ => self.apply_document_session(session),
+        }
+        if let Some(tab) = session.get("active_tab").and_then(Json::as_usize) {
+            self.active_tab = tab.min(3);
+        }
+        if let Some(Json::Object(files)) = session.get("breakpoints") {
+            self.breakpoints = file_lines(files);
+        }
+        if let Some(Json::Object(files)) = session.get("bookmarks") {
+            self.bookmarks = file_lines(files);
+        }
+        // The project's files are already open as tabs, so it is only listed
+        if let Some(path) = session.get("project").and_then(Json::as_str) {
+            if let Ok(project) = crate::projects::Project::load(std::path::Path::new(path)) {
+                self.project = Some(project);
+                self.show_project_panel = true;
+            }
+        }
+        if let Some(zoom) = session.get("zoom").and_then(Json::as_f64) {
+            self.turtle_zoom =
+                (zoom as f32).clamp(crate::turtle::MIN_ZOOM, crate::turtle::MAX_ZOOM);
+        }
+        if let Some(Json::Array(pan)) = session.get("pan") {
+            if let [Json::Number(x), Json::Number(y)] = pan.as_slice() {
+                self.turtle_pan = egui::vec2(*x as f32, *y as f32);
+            }
+        }
+    }
+
+    /// Reopen one editor tab's file and put its saved buffer back
+    fn apply_document_session(&mut self, document: &Json) {
+        if let Some(file) = document.get("file").and_then(Json::as_str) {
+            // The file is opened so later saves and change checks use it; the
+            // saved buffer then goes on top, keeping any unsaved edits
+            if Path::new(file).is_file() {
+                self.open_location(file);
+            }
+        }
+        if let Some(code) = document.get("code").and_then(Json::as_str) {
+            self.code = code.to_string();
+        }
+        if let Some(language) = document
+            .get("language")
+            .and_then(Json::as_str)
+            .and_then(Language::from_name)
+        {
+            self.set_language(language);
+        }
+    }
+
+    /// Pick up where the last session left off, if one was saved
+    pub(crate) fn restore_session(&mut self) {
+        let Ok(text) = std::fs::read_to_string(session_file(&self.storage)) else {
+            return;
+        };
+        match Json::parse(&text) {
+            Ok(session) => {
+                self.apply_session(&session);
+                self.saved_session = text;
+            }
+            Err(err) => self.show_error(format!("Could not restore the last session: {}", err)),
+        }
+    }
+
+    /// Write the session if it changed since it was last written
+    pub(crate) fn save_session(&mut self) -> std::io::Result<()> {
+        let text = self.session_json().to_string();
+        if text == self.saved_session {
+            return Ok(());
+        }
+        let path = session_file(&self.storage);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, &text)?;
+        self.saved_session = text;
+        Ok(())
+    }
+
+    /// Note the main window's geometry and save the session when the window
+    /// is closed and, unless autosave is off, every `autosave_secs`, so a
+    /// machine switched off at the wall loses at most that much
+    pub(crate) fn track_session(&mut self, ctx: &egui::Context) {
+        let (outer, inner, maximized, closing) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.outer_rect,
+                viewport.inner_rect,
+                viewport.maximized.unwrap_or(false),
+                viewport.close_requested(),
+            )
+        });
+        if let Some(inner) = inner {
+            // A maximized window keeps the size it had before, for un-maximizing
+            let previous = self.window_geometry;
+            self.window_geometry = Some(match previous {
+                Some(previous) if maximized => WindowGeometry {
+                    maximized,
+                    ..previous
+                },
+                _ => WindowGeometry {
+                    position: outer.map(|outer| outer.min),
+                    size: inner.size(),
+                    maximized,
+                },
+            });
+        }
+
+        let interval = Duration::from_secs(self.settings.autosave_secs);
+        let due = !interval.is_zero() && self.session_checked.elapsed() >= interval;
+        if !interval.is_zero() {
+            ctx.request_repaint_after(interval);
+        }
+        if closing || due {
+            self.session_checked = Instant::now();
+            if let Err(err) = self.save_session() {
+                self.show_error(format!("Could not save the session: {}", err));
+            }
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use crate::keybindings::Keybindings;
+use crate::permissions::{self, ProgramPermissions};
+use crate::theme::Theme;
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use time_warp_core::basic::dialect::Dialect;
+use time_warp_core::basic::strict::Strictness;
+use time_warp_core::json::Json;
+
+pub(crate) const FONT_SIZES: RangeInclusive<f32> = 10.0..=24.0;
+pub(crate) const CODE_FONT_SIZES: RangeInclusive<f32> = 8.0..=48.0; // big enough for a projector
+pub(crate) const TAB_WIDTHS: RangeInclusive<usize> = 1..=8;
+pub(crate) const TIMEOUTS_MS: RangeInclusive<u64> = 500..=60_000;
+pub(crate) const AUTOSAVE_SECS: RangeInclusive<u64> = 0..=300;
+
+/// Preferences chosen in the Settings window and kept in
+/// `StoragePaths::settings_file` between runs
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Settings {
+    pub font_size: f32,             // body text; headings are sized from it
+    pub code_font_size: f32,        // editor, output console and debug views
+    pub code_font: Option<PathBuf>, // an installed font file; None = egui's built-in
+    pub theme: Theme,
+    pub execution_timeout_ms: u64,
+    pub tab_width: usize,   // spaces typed for the Tab key
+    pub autosave_secs: u64, // how often the session is written; 0 = only on exit
+    pub code_completion: bool,
+    pub basic_dialect: Dialect, // how TW BASIC programs are read
+    pub basic_strictness: Strictness,
+    pub keybindings: Keybindings,
+    pub recent_files: Vec<String>, // newest first, for File → Recent
+    pub printer_file: Option<PathBuf>, // LPRINT output is also added to this file
+    pub ask_permission: bool,      // ask before a program writes or deletes files
+    pub play_sound: bool,          // BEEP, SOUND and PLAY are heard, not just timed
+    pub hints_enabled: bool,       // a hint card follows the same error repeated
+    pub program_permissions: ProgramPermissions, // answers remembered for each program
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            font_size: 14.0,
+            code_font_size: 12.0,
+            code_font: None,
+            theme: Theme::Light,
+            execution_timeout_ms: 5000, // 5 seconds
+            tab_width: 4,
+            autosave_secs: 10,
+            code_completion: false,
+            basic_dialect: Dialect::TwBasic,
+            basic_strictness: Strictness::Permissive,
+            keybindings: Keybindings::default(),
+            recent_files: Vec::new(),
+            printer_file: None,
+            ask_permission: true,
+            play_sound: true,
+            hints_enabled: true,
+            program_permissions: ProgramPermissions::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("font_size", Json::Number(self.font_size as f64)),
+            ("code_font_size", Json::Number(self.code_font_size as f64)),
+            (
+                "code_font",
+                match &self.code_font {
+                    Some(path) => path.display().to_string().into(),
+                    None => Json::Null,
+                },
+            ),
+            ("theme", self.theme.name().into()),
+            (
+                "execution_timeout_ms",
+                (self.execution_timeout_ms as usize).into(),
+            ),
+            ("tab_width", self.tab_width.into()),
+            ("autosave_secs", (self.autosave_secs as usize).into()),
+            ("code_completion", Json::Bool(self.code_completion)),
+            ("basic_dialect", self.basic_dialect.name().into()),
+            ("basic_strictness", self.basic_strictness.name().into()),
+            ("keybindings", self.keybindings.to_json()),
+            (
+                "recent_files",
+                Json::Array(
+                    self.recent_files
+                        .iter()
+                        .map(|file| file.as_str().into())
+                        .collect(),
+                ),
+            ),
+            (
+                "printer_file",
+                match &self.printer_file {
+                    Some(path) => path.display().to_string().into(),
+                    None => Json::Null,
+                },
+            ),
+            ("ask_permission", Json::Bool(self.ask_permission)),
+            ("play_sound", Json::Bool(self.play_sound)),
+            ("hints_enabled", Json::Bool(self.hints_enabled)),
+            (
+                "program_permissions",
+                permissions::to_json(&self.program_permissions),
+            ),
+        ])
+    }
+
+    /// Fields that are missing or out of range keep their defaults, so
+    /// settings from older versions and hand-edited files still load
+    pub fn from_json(json: &Json) -> Self {
+        let defaults = Self::default();
+        let number = |key: &str| json.get(key).and_then(Json::as_f64);
+        let whole = |key: &str| json.get(key).and_then(Json::as_usize);
+        Self {
+            font_size: number("font_size")
+                .map(|size| size as f32)
+                .filter(|size| FONT_SIZES.contains(size))
+                .unwrap_or(defaults.font_size),
+            code_font_size: number("code_font_size")
+                .map(|size| size as f32)
+                .filter(|size| CODE_FONT_SIZES.contains(size))
+                .unwrap_or(defaults.code_font_size),
+            code_font: json
+                .get("code_font")
+                .and_then(Json::as_str)
+                .map(PathBuf::from),
+            theme: json
+                .get("theme")
+                .and_then(Json::as_str)
+                .and_then(Theme::from_name)
+                .unwrap_or(defaults.theme),
+            execution_timeout_ms: whole("execution_timeout_ms")
+                .map(|ms| ms as u64)
+                .filter(|ms| TIMEOUTS_MS.contains(ms))
+                .unwrap_or(defaults.execution_timeout_ms),
+            tab_width: whole("tab_width")
+                .filter(|width| TAB_WIDTHS.contains(width))
+                .unwrap_or(defaults.tab_width),
+            autosave_secs: whole("autosave_secs")
+                .map(|secs| secs as u64)
+                .filter(|secs| AUTOSAVE_SECS.contains(secs))
+                .unwrap_or(defaults.autosave_secs),
+            code_completion: match json.get("code_completion") {
+                Some(Json::Bool(enabled)) => *enabled,
+                _ => defaults.code_completion,
+            },
+            basic_dialect: json
+                .get("basic_dialect")
+                .and_then(Json::as_str)
+                .and_then(Dialect::from_name)
+                .unwrap_or(defaults.basic_dialect),
+            basic_strictness: json
+                .get("basic_strictness")
+                .and_then(Json::as_str)
+                .and_then(Strictness::from_name)
+                .unwrap_or(defaults.basic_strictness),
+            keybindings: json
+                .get("keybindings")
+                .map(Keybindings::from_json)
+                .unwrap_or(defaults.keybindings),
+            recent_files: match json.get("recent_files") {
+                Some(Json::Array(files)) => files
+                    .iter()
+                    .filter_map(Json::as_str)
+                    .take(crate::recent_files::MAX_RECENT_FILES)
+                    .map(str::to_string)
+                    .collect(),
+                _ => defaults.recent_files,
+            },
+            printer_file: json
+                .get("printer_file")
+                .and_then(Json::as_str)
+                .map(PathBuf::from),
+            ask_permission: match json.get("ask_permission") {
+                Some(Json::Bool(asking)) => *asking,
+                _ => defaults.ask_permission,
+            },
+            play_sound: match json.get("play_sound") {
+                Some(Json::Bool(playing)) => *playing,
+                _ => defaults.play_sound,
+            },
+            hints_enabled: match json.get("hints_enabled") {
+                Some(Json::Bool(enabled)) => *enabled,
+                _ => defaults.hints_enabled,
+            },
+            program_permissions: json
+                .get("program_permissions")
+                .map(permissions::from_json)
+                .unwrap_or(defaults.program_permissions),
+        }
+    }
+
+    /// The saved settings, or the defaults when nothing has been saved yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Self::from_json(&Json::parse(&text)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_json().pretty())
+    }
+}
+
+/// Replace each tab in `text` with `width` spaces. `cursor` is a character
+/// index into `text`; the same position in the new text is returned.
+pub(crate) fn expand_tabs(text: &mut String, width: usize, cursor: usize) -> usize {
+    let tabs_before = text.chars().take(cursor).filter(|&c| c == '\t').count();
+    *text = text.replace('\t', &" ".repeat(width));
+    cursor + tabs_before * (width.saturating_sub(1))
+}
+
+impl TimeWarpApp {
+    fn render_code_font_choice(&mut self, ui: &mut egui::Ui) {
+        let selected = match &self.settings.code_font {
+            Some(path) => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            None => "Built-in monospace".to_string(),
+        };
+        egui::ComboBox::from_id_source("code_font")
+            .selected_text(selected)
+            .width(220.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.settings.code_font, None, "Built-in monospace");
+                let fonts = self.installed_fonts().to_vec();
+                for font in fonts {
+                    ui.selectable_value(&mut self.settings.code_font, Some(font.path), font.name);
+                }
+            });
+    }
+
+    /// Load the settings file, reporting a damaged one and using defaults
+    pub(crate) fn load_settings(&mut self) {
+        match Settings::load(&self.storage.settings_file) {
+            Ok(settings) => self.settings = settings,
+            Err(err) => self.show_error(format!("Could not read settings: {}", err)),
+        }
+    }
+
+    /// Save the settings and restyle the IDE to match them
+    pub(crate) fn apply_settings(&mut self, ctx: &egui::Context) {
+        ctx.set_style(crate::ide_style(&self.settings));
+        self.apply_code_font(ctx);
+        if let Err(err) = self.settings.save(&self.storage.settings_file) {
+            self.show_error(format!("Could not save settings: {}", err));
+        }
+    }
+
+    pub(crate) fn render_settings(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        let before = self.settings.clone();
+
+        egui::Window::new("⚙️ Settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings_grid")
+                    .num_columns(2)
+                    .spacing([16.0, 8.0])
+                    .show(ui, |ui| {
+                        ui.label("Font size");
+                        ui.add(
+                            egui::Slider::new(&mut self.settings.font_size, FONT_SIZES)
+                                .step_by(1.0),
+                        );
+                        ui.end_row();
+
+                        ui.label("Code font size");
+                        ui.add(
+                            egui::Slider::new(&mut self.settings.code_font_size, CODE_FONT_SIZES)
+                                .step_by(1.0),
+                        )
+                        .on_hover_text("Editor, output console and debug views");
+                        ui.end_row();
+
+                        ui.label("Code font");
+                        self.render_code_font_choice(ui);
+                        ui.end_row();
+
+                        ui.label("Theme");
+                        ui.horizontal(|ui| {
+                            for theme in Theme::ALL {
+                                ui.radio_value(&mut self.settings.theme, theme, theme.label());
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Run timeout");
+                        ui.add(
+                            egui::Slider::new(&mut self.settings.execution_timeout_ms, TIMEOUTS_MS)
+                                .logarithmic(true)
+                                .suffix(" ms"),
+                        )
+                        .on_hover_text("Programs stop after about 1000 statements per millisecond");
+                        ui.end_row();
+
+                        ui.label("Tab width");
+                        ui.add(
+                            egui::Slider::new(&mut self.settings.tab_width, TAB_WIDTHS)
+                                .suffix(" spaces"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Autosave every");
+                        ui.add(
+                            egui::Slider::new(&mut self.settings.autosave_secs, AUTOSAVE_SECS)
+                                .suffix(" s"),
+                        )
+                        .on_hover_text(
+                            "How often the open file and session are saved; 0 saves only on exit",
+                        );
+                        ui.end_row();
+
+                        ui.label("Code completion");
+                        ui.checkbox(
+                            &mut self.settings.code_completion,
+                            "Suggest keywords (Ctrl+Space)",
+                        );
+                        ui.end_row();
+
+                        ui.label("BASIC dialect");
+                        egui::ComboBox::from_id_source("basic_dialect")
+                            .selected_text(self.settings.basic_dialect.name())
+                            .show_ui(ui, |ui| {
+                                for dialect in Dialect::ALL {
+                                    ui.selectable_value(
+                                        &mut self.settings.basic_dialect,
+                                        dialect,
+                                        dialect.name(),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text(
+                                "QBasic reads programs without line numbers, with block IF, \
+                                 DO...LOOP, WHILE...WEND and SUB...END SUB. Applesoft and \
+                                 Commodore 64 run 8-bit listings on a 40-column screen.",
+                            );
+                        ui.end_row();
+
+                        ui.label("BASIC checking");
+                        egui::ComboBox::from_id_source("basic_strictness")
+                            .selected_text(self.settings.basic_strictness.name())
+                            .show_ui(ui, |ui| {
+                                for strictness in Strictness::ALL {
+                                    ui.selectable_value(
+                                        &mut self.settings.basic_strictness,
+                                        strictness,
+                                        strictness.name(),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text(
+                                "Strict stops programs that read a variable never given a \
+                                 value or mix up text and numbers, and warns about code that \
+                                 probably doesn't do what it says. Problems are marked in the \
+                                 editor's gutter.",
+                            );
+                        ui.end_row();
+
+                        ui.label("Program files");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(
+                                &mut self.settings.ask_permission,
+                                "Ask before programs change files",
+                            )
+                            .on_hover_text(
+                                "A program waits for Allow before it first writes or deletes \
+                                 files in its sandbox folder",
+                            );
+                            if ui
+                                .add_enabled(
+                                    !self.settings.program_permissions.is_empty(),
+                                    egui::Button::new("Forget Answers"),
+                                )
+                                .on_hover_text("Ask again for programs whose answer was remembered")
+                                .clicked()
+                            {
+                                self.settings.program_permissions.clear();
+                            }
+                        });
+                        ui.end_row();
+
+                        #[cfg(feature = "audio")]
+                        {
+                            ui.label("Sound");
+                            ui.checkbox(&mut self.settings.play_sound, "Play BEEP, SOUND and PLAY")
+                                .on_hover_text(
+                                    "Programs still take as long as their music when this is off; \
+                                 🔇 Mute on the toolbar does the same",
+                                );
+                            ui.end_row();
+                        }
+
+                        #[cfg(feature = "hints")]
+                        {
+                            ui.label("Hints");
+                            ui.checkbox(
+                                &mut self.settings.hints_enabled,
+                                "Show a hint after the same error happens repeatedly",
+                            );
+                            ui.end_row();
+                        }
+                    });
+
+                egui::CollapsingHeader::new("⌨ Keyboard Shortcuts")
+                    .show(ui, |ui| self.render_keybindings(ui));
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("↺ Restore Defaults").clicked() {
+                        // The Recent list isn't a preference, so it stays
+                        self.settings = Settings {
+                            recent_files: std::mem::take(&mut self.settings.recent_files),
+                            ..Settings::default()
+                        };
+                    }
+                    ui.weak(self.storage.settings_file.display().to_string());
+                });
+            });
+
+        self.show_settings = open;
+        if !open {
+            self.recording_shortcut = None;
+        }
+        if self.settings != before {
+            self.apply_settings(ctx);
+        }
+    }
+}
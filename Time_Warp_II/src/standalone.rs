@@ -0,0 +1,185 @@
+use crate::{ide_style, TimeWarpApp};
+use eframe::egui;
+use rfd::FileDialog;
+use std::path::{Path, PathBuf};
+use time_warp_core::json::Json;
+use time_warp_core::Language;
+
+/// The program a standalone copy runs, kept next to its executable
+pub(crate) const BUNDLE_FILE: &str = "bundle.json";
+
+/// A program exported to run on its own, without the IDE around it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Bundle {
+    pub name: String, // the window title
+    pub language: Language,
+    pub code: String, // with any INCLUDE lines already filled in
+}
+
+impl Bundle {
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("name", self.name.as_str().into()),
+            ("language", self.language.name().into()),
+            ("code", self.code.as_str().into()),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Self, String> {
+        let field = |key: &str| {
+            json.get(key)
+                .and_then(Json::as_str)
+                .ok_or_else(|| format!("{} is missing \"{}\"", BUNDLE_FILE, key))
+        };
+        Ok(Self {
+            name: field("name")?.to_string(),
+            language: Language::from_name(field("language")?)
+                .ok_or_else(|| format!("{} names an unknown language", BUNDLE_FILE))?,
+            code: field("code")?.to_string(),
+        })
+    }
+}
+
+/// The bundle beside the running executable, when this is a standalone copy
+pub(crate) fn find_bundle() -> Option<Bundle> {
+    let exe = std::env::current_exe().ok()?;
+    let text = std::fs::read_to_string(exe.parent()?.join(BUNDLE_FILE)).ok()?;
+    Bundle::from_json(&Json::parse(&text).ok()?).ok()
+}
+
+/// Make `folder` a standalone copy of `bundle`: a copy of the IDE's
+/// executable `exe`, named after the program, with the bundle beside it.
+/// Returns the path of the copied executable.
+pub(crate) fn write_bundle(folder: &Path, exe: &Path, bundle: &Bundle) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(folder)?;
+    let copy = folder.join(format!("{}{}", bundle.name, std::env::consts::EXE_SUFFIX));
+    std::fs::copy(exe, &copy)?;
+    std::fs::write(folder.join(BUNDLE_FILE), bundle.to_json().pretty())?;
+    Ok(copy)
+}
+
+/// Run a standalone copy: the program full-screen, with no IDE around it
+pub(crate) fn run_player(bundle: Bundle) -> eframe::Result<()> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title(bundle.name.clone())
+            .with_fullscreen(true),
+        ..Default::default()
+    };
+    eframe::run_native(
+        &bundle.name.clone(),
+        options,
+        Box::new(move |cc| {
+            let mut app = TimeWarpApp::player(&bundle);
+            cc.egui_ctx.set_style(ide_style(&app.settings));
+            app.apply_code_font(&cc.egui_ctx);
+            Box::new(app)
+        }),
+    )
+}
+
+impl TimeWarpApp {
+    /// An app that only plays `bundle`, started as soon as it is made
+    pub(crate) fn player(bundle: &Bundle) -> Self {
+        let mut app = Self {
+            player: true,
+            code: bundle.code.clone(),
+            ..Self::default()
+        };
+        app.set_language(bundle.language);
+        app.execute_code();
+        app
+    }
+
+    /// The current program as a bundle named after its file
+    pub(crate) fn standalone_bundle(&self) -> Result<Bundle, String> {
+        let name = self.document_name();
+        let code = match self.language {
+            Language::Basic => {
+                self.expand_includes(&self.code)
+                    .map_err(|err| err.to_string())?
+                    .code
+            }
+            _ => self.code.clone(),
+        };
+        Ok(Bundle {
+            name: Path::new(&name)
+                .file_stem()
+                .map_or(name.clone(), |stem| stem.to_string_lossy().into_owned()),
+            language: self.language,
+            code,
+        })
+    }
+
+    /// File → Export → Standalone: ask for a folder and put a copy of the
+    /// program there that runs by itself
+    pub(crate) fn export_standalone(&mut self) {
+        let bundle = match self.standalone_bundle() {
+            Ok(bundle) => bundle,
+            Err(err) => return self.show_error(format!("Could not export: {}", err)),
+        };
+        let Some(folder) = FileDialog::new()
+            .set_title("Folder to put the standalone program in")
+            .pick_folder()
+        else {
+            return;
+        };
+        let written = std::env::current_exe()
+            .and_then(|exe| write_bundle(&folder.join(&bundle.name), &exe, &bundle));
+        match written {
+            Ok(copy) => self.output.push_str(&format!(
+                "Standalone program saved to {}; copy its folder to share it\n",
+                copy.display()
+            )),
+            Err(err) => self.show_error(format!("Could not export: {}", err)),
+        }
+    }
+
+    /// A standalone copy's window: the program's output and drawing, an
+    /// answer box when it asks for input, and Esc to quit
+    pub(crate) fn show_player(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        self.render_permission_prompt(ctx);
+        self.pump_program_input(ctx);
+        #[cfg(feature = "audio")]
+        self.poll_music(ctx);
+        // Text-only programs get the whole screen for their output
+        if self.turtle_commands.is_empty() {
+            egui::CentralPanel::default().show(ctx, |ui| self.render_player_output(ui));
+        } else {
+            egui::TopBottomPanel::bottom("player_output")
+                .resizable(true)
+                .default_height(160.0)
+                .show(ctx, |ui| self.render_player_output(ui));
+            egui::CentralPanel::default().show(ctx, |ui| self.render_turtle_canvas(ui));
+        }
+    }
+
+    fn render_player_output(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let running = self.run_unfinished();
+            if ui
+                .add_enabled(!running, egui::Button::new("🔄 Run Again"))
+                .clicked()
+            {
+                self.clear_canvas();
+                self.execute_code();
+            }
+            ui.weak("Press Esc to quit");
+        });
+        if self.waiting_for_input {
+            let answer = ui.text_edit_singleline(&mut self.user_input);
+            answer.request_focus();
+            if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.submit_program_input();
+            }
+        }
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                ui.add(egui::Label::new(egui::RichText::new(&self.output).monospace()).wrap(true));
+            });
+    }
+}
@@ -0,0 +1,140 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Every place the IDE keeps state on disk. Resolved once at startup from the
+/// platform conventions (XDG on Linux, Application Support on macOS, AppData on
+/// Windows) so that nothing else hard-codes a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoragePaths {
+    pub settings_file: PathBuf,
+    pub sessions_dir: PathBuf,
+    pub autosave_dir: PathBuf,
+    pub sandbox_dir: PathBuf,
+    pub templates_dir: PathBuf,
+    pub log_dir: PathBuf,
+}
+
+impl StoragePaths {
+    /// Platform directories for the IDE, or `None` if no home directory is known
+    pub fn platform() -> Option<Self> {
+        let dirs = directories::ProjectDirs::from("org", "TimeWarp", "Time Warp IDE")?;
+        let state_dir = dirs.state_dir().unwrap_or_else(|| dirs.data_local_dir());
+        Some(Self {
+            settings_file: dirs.config_dir().join("settings.json"),
+            sessions_dir: dirs.data_dir().join("sessions"),
+            autosave_dir: dirs.data_local_dir().join("autosave"),
+            sandbox_dir: dirs.data_local_dir().join("sandbox"),
+            templates_dir: dirs.data_dir().join("templates"),
+            log_dir: state_dir.join("logs"),
+        })
+    }
+
+    /// All locations under a single root, for portable installs and tests
+    pub fn under(root: &Path) -> Self {
+        Self {
+            settings_file: root.join("config").join("settings.json"),
+            sessions_dir: root.join("data").join("sessions"),
+            autosave_dir: root.join("data").join("autosave"),
+            sandbox_dir: root.join("data").join("sandbox"),
+            templates_dir: root.join("data").join("templates"),
+            log_dir: root.join("logs"),
+        }
+    }
+
+    /// Platform directories, falling back to a folder next to the working
+    /// directory when the platform gives us nothing
+    pub fn resolve() -> Self {
+        Self::platform().unwrap_or_else(|| Self::under(Path::new(".time_warp")))
+    }
+
+    /// Labelled locations for the About/Storage panel
+    pub fn entries(&self) -> Vec<(&'static str, &Path)> {
+        vec![
+            ("Settings", self.settings_file.as_path()),
+            ("Sessions", self.sessions_dir.as_path()),
+            ("Autosaves", self.autosave_dir.as_path()),
+            ("Sandboxes", self.sandbox_dir.as_path()),
+            ("Templates", self.templates_dir.as_path()),
+            ("Logs", self.log_dir.as_path()),
+        ]
+    }
+}
+
+/// Where older versions kept their files (`~/.time_warp`)
+pub fn legacy_dir() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".time_warp"))
+}
+
+/// Move anything found in the legacy directory into its new home. Files that
+/// already exist at the destination are left alone in the legacy folder.
+/// Returns a description of each item that was moved.
+pub fn migrate_legacy(legacy: &Path, paths: &StoragePaths) -> io::Result<Vec<String>> {
+    let mut moved = Vec::new();
+    if !legacy.is_dir() {
+        return Ok(moved);
+    }
+
+    let targets: [(&str, &Path); 5] = [
+        ("templates", &paths.templates_dir),
+        ("sessions", &paths.sessions_dir),
+        ("autosave", &paths.autosave_dir),
+        ("sandbox", &paths.sandbox_dir),
+        ("logs", &paths.log_dir),
+    ];
+    for (name, target) in targets {
+        let source = legacy.join(name);
+        if !source.is_dir() {
+            continue;
+        }
+        std::fs::create_dir_all(target)?;
+        for entry in std::fs::read_dir(&source)? {
+            let entry = entry?;
+            let destination = target.join(entry.file_name());
+            if destination.exists() {
+                continue;
+            }
+            move_path(&entry.path(), &destination)?;
+            moved.push(format!(
+                "{} -> {}",
+                entry.path().display(),
+                destination.display()
+            ));
+        }
+        // Only succeeds once the folder is empty
+        let _ = std::fs::remove_dir(&source);
+    }
+
+    let settings = legacy.join("settings.json");
+    if settings.is_file() && !paths.settings_file.exists() {
+        if let Some(parent) = paths.settings_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_path(&settings, &paths.settings_file)?;
+        moved.push(format!(
+            "{} -> {}",
+            settings.display(),
+            paths.settings_file.display()
+        ));
+    }
+
+    let _ = std::fs::remove_dir(legacy);
+    Ok(moved)
+}
+
+/// Rename, falling back to copy-and-delete across filesystems
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        std::fs::remove_dir(from)
+    } else {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use time_warp_core::Language;
+
+/// A starter program offered by the New File dialog
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramTemplate {
+    pub name: String,
+    pub language: String,
+    pub extension: String,
+    pub content: String,
+}
+
+impl ProgramTemplate {
+    fn new(name: &str, language: &str, extension: &str, content: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            language: language.to_string(),
+            extension: extension.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+/// Templates that ship with the IDE, for the languages this build has
+pub fn builtin_templates() -> Vec<ProgramTemplate> {
+    let mut templates = vec![
+        ProgramTemplate::new("Empty program", "TW BASIC", "twb", ""),
+        ProgramTemplate::new(
+            "Numbered BASIC skeleton",
+            "TW BASIC",
+            "twb",
+            "10 PRINT \"HELLO\"\n\
+             20 LET X = 1\n\
+             30 PRINT \"X = \"; X\n\
+             40 END\n",
+        ),
+        ProgramTemplate::new(
+            "Turtle art starter",
+            "TW BASIC",
+            "twb",
+            "10 FOR I = 1 TO 36\n\
+             20 FORWARD 100\n\
+             30 RIGHT 170\n\
+             40 NEXT I\n\
+             50 END\n",
+        ),
+        ProgramTemplate::new(
+            "Quiz game starter",
+            "TW BASIC",
+            "twb",
+            "10 LET SCORE = 0\n\
+             20 PRINT \"WHAT IS 6 * 7\"\n\
+             30 INPUT A\n\
+             40 IF A = 42 THEN LET SCORE = SCORE + 1\n\
+             50 PRINT \"WHAT IS 12 + 30\"\n\
+             60 INPUT B\n\
+             70 IF B = 42 THEN LET SCORE = SCORE + 1\n\
+             80 PRINT \"YOUR SCORE: \"; SCORE; \" OUT OF 2\"\n\
+             90 END\n",
+        ),
+        ProgramTemplate::new(
+            "Logo square spiral",
+            "Logo",
+            "twl",
+            "; Each side is a little longer than the last\n\
+             TO spiral :size\n\
+             \x20 IF :size > 150 [STOP]\n\
+             \x20 FD :size RT 90\n\
+             \x20 spiral :size + 5\n\
+             END\n\
+             \n\
+             REPEAT 4 [FD 20 RT 90]\n\
+             spiral 10\n",
+        ),
+        ProgramTemplate::new(
+            "PILOT quiz starter",
+            "PILOT",
+            "plt",
+            "R:Ask until the answer matches\n\
+             T:What is your name?\n\
+             A:$NAME\n\
+             *QUESTION\n\
+             T:What is the largest planet, $NAME?\n\
+             A:\n\
+             M:JUPITER\n\
+             TY:Well done!\n\
+             TN:Not quite - try again.\n\
+             JN:*QUESTION\n\
+             E:\n",
+        ),
+        ProgramTemplate::new(
+            "Hello world",
+            "Pascal",
+            "twp",
+            "program HelloWorld;\n\
+             begin\n\
+             \x20 writeln('Hello, World!');\n\
+             end.\n",
+        ),
+        ProgramTemplate::new(
+            "Prolog family tree",
+            "Prolog",
+            "tpr",
+            "% Facts, a rule, then questions about them\n\
+             parent(tom, bob).\n\
+             parent(bob, ann).\n\
+             parent(bob, pat).\n\
+             \n\
+             grandparent(X, Z) :- parent(X, Y), parent(Y, Z).\n\
+             \n\
+             ?- grandparent(tom, Who).\n\
+             ?- parent(bob, ann).\n",
+        ),
+    ];
+    templates.retain(|template| Language::from_name(&template.language).is_some());
+    templates
+}
+
+/// Map a file extension to the language name shown in the dialog
+pub fn language_for_extension(extension: &str) -> &'static str {
+    Language::from_extension(extension).map_or("Text", Language::name)
+}
+
+/// Load every readable file in `dir` as a template, sorted by name.
+/// A missing directory simply yields no templates.
+pub fn load_user_templates(dir: &Path) -> Vec<ProgramTemplate> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut templates: Vec<ProgramTemplate> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let content = std::fs::read_to_string(&path).ok()?;
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "txt".to_string());
+            Some(ProgramTemplate {
+                name,
+                language: language_for_extension(&extension).to_string(),
+                extension,
+                content,
+            })
+        })
+        .collect();
+
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+/// Built-in templates followed by any found in the user templates directory
+pub fn all_templates(user_dir: &Path) -> Vec<ProgramTemplate> {
+    let mut templates = builtin_templates();
+    templates.extend(load_user_templates(user_dir));
+    templates
+}
@@ -0,0 +1,125 @@
+use eframe::egui;
+use egui::Color32;
+
+/// The IDE's colour schemes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Theme {
+    Light,
+    Dark,
+}
+
+/// Colours the IDE paints itself, on top of egui's visuals. Each theme
+/// has its own so code stays readable on either background.
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub(crate) struct Palette {
+    pub text: Color32,
+    pub keyword: Color32,
+    pub comment: Color32,
+    pub string: Color32,
+    pub number: Color32,
+    pub operator: Color32,
+    pub bracket: Color32,
+    pub line_number: Color32,
+    pub current_line: Color32, // debugger's ▶ marker
+    pub covered: Color32,      // coverage gutter cells, labelled in black
+    pub uncovered: Color32,
+    pub menu_bar: Color32,
+    pub success: Color32,    // running, passed
+    pub warning: Color32,    // paused, waiting for input
+    pub find_match: Color32, // behind each match while Find/Replace is open
+    pub bookmark: Color32,   // gutter ribbon on bookmarked lines
+    pub error: Color32,      // gutter marks for strict mode's errors
+}
+
+const LIGHT: Palette = Palette {
+    text: Color32::BLACK,
+    keyword: Color32::from_rgb(0, 0, 255),
+    comment: Color32::from_rgb(0, 128, 0),
+    string: Color32::from_rgb(163, 21, 21),
+    number: Color32::from_rgb(0, 128, 128),
+    operator: Color32::from_rgb(128, 64, 0),
+    bracket: Color32::from_rgb(128, 0, 128),
+    line_number: Color32::from_rgb(100, 100, 100),
+    current_line: Color32::from_rgb(200, 120, 0),
+    covered: Color32::from_rgb(170, 225, 170),
+    uncovered: Color32::from_rgb(240, 160, 160),
+    menu_bar: Color32::from_rgb(220, 220, 220),
+    success: Color32::from_rgb(0, 140, 0),
+    warning: Color32::from_rgb(190, 120, 0),
+    find_match: Color32::from_rgb(255, 225, 120),
+    bookmark: Color32::from_rgb(60, 120, 220),
+    error: Color32::from_rgb(210, 30, 30),
+};
+
+const DARK: Palette = Palette {
+    text: Color32::from_rgb(212, 212, 212),
+    keyword: Color32::from_rgb(86, 156, 214),
+    comment: Color32::from_rgb(106, 153, 85),
+    string: Color32::from_rgb(206, 145, 120),
+    number: Color32::from_rgb(181, 206, 168),
+    operator: Color32::from_rgb(215, 186, 125),
+    bracket: Color32::from_rgb(197, 134, 192),
+    line_number: Color32::from_rgb(133, 133, 133),
+    current_line: Color32::YELLOW,
+    covered: Color32::from_rgb(120, 190, 120),
+    uncovered: Color32::from_rgb(210, 120, 120),
+    menu_bar: Color32::from_rgb(45, 45, 48),
+    success: Color32::GREEN,
+    warning: Color32::YELLOW,
+    find_match: Color32::from_rgb(100, 80, 30),
+    bookmark: Color32::from_rgb(90, 150, 255),
+    error: Color32::from_rgb(240, 80, 80),
+};
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Light, Theme::Dark];
+
+    /// Key in the settings file
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            _ => None,
+        }
+    }
+
+    /// Shown in the View menu and Settings
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Light => "☀ Light",
+            Theme::Dark => "🌙 Dark",
+        }
+    }
+
+    pub fn palette(self) -> &'static Palette {
+        match self {
+            Theme::Light => &LIGHT,
+            Theme::Dark => &DARK,
+        }
+    }
+
+    pub fn visuals(self) -> egui::Visuals {
+        match self {
+            Theme::Light => {
+                // Light visuals with softer panel and widget fills
+                let mut visuals = egui::Visuals::light();
+                visuals.window_fill = Color32::from_rgb(250, 250, 252);
+                visuals.panel_fill = Color32::from_rgb(255, 255, 255);
+                visuals.faint_bg_color = Color32::from_rgb(248, 248, 250);
+                visuals.widgets.noninteractive.bg_fill = Color32::from_rgb(252, 252, 254);
+                visuals.widgets.inactive.bg_fill = Color32::from_rgb(255, 255, 255);
+                visuals.widgets.hovered.bg_fill = Color32::from_rgb(240, 245, 255);
+                visuals.widgets.active.bg_fill = Color32::from_rgb(230, 240, 255);
+                visuals
+            }
+            Theme::Dark => egui::Visuals::dark(),
+        }
+    }
+}
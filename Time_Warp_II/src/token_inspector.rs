@@ -0,0 +1,63 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+use time_warp_core::basic::{InterpreterError, SpannedToken, Tokenizer};
+use time_warp_core::Language;
+
+impl TimeWarpApp {
+    /// What the tokenizer makes of the editor, up to the first error
+    pub(crate) fn inspect_tokens(&self) -> (Vec<SpannedToken>, Option<InterpreterError>) {
+        Tokenizer::new(&self.code).tokenize_spanned()
+    }
+
+    pub(crate) fn render_token_inspector(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_token_inspector;
+
+        egui::Window::new("🔤 Token Inspector")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                if self.language != Language::Basic {
+                    ui.label(format!(
+                        "The token inspector shows TW BASIC programs, not {}.",
+                        self.language.name()
+                    ));
+                    return;
+                }
+                let (tokens, error) = self.inspect_tokens();
+                ui.label(format!("{} tokens", tokens.len()));
+                if let Some(err) = &error {
+                    let stopped = match err.position() {
+                        Some(position) => format!("Stopped at {:?}: {}", position, err.message()),
+                        None => format!("Stopped: {}", err.message()),
+                    };
+                    ui.colored_label(egui::Color32::RED, stopped);
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("token_table")
+                            .striped(true)
+                            .num_columns(3)
+                            .show(ui, |ui| {
+                                ui.strong("Line:Col");
+                                ui.strong("Kind");
+                                ui.strong("Lexeme");
+                                ui.end_row();
+
+                                for token in &tokens {
+                                    ui.monospace(format!("{}:{}", token.line, token.column));
+                                    ui.monospace(token.kind());
+                                    // Escaped, so a newline token shows as \n
+                                    ui.monospace(format!("{:?}", token.lexeme));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        self.show_token_inspector = open;
+    }
+}
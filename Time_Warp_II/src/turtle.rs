@@ -0,0 +1,349 @@
+use eframe::egui;
+
+/// One drawing operation on the turtle canvas, in turtle coordinates
+/// (origin at the canvas centre, y pointing down, angles in degrees)
+#[derive(Debug, Clone, PartialEq)]
+pub enum TurtleOp {
+    Line {
+        from: egui::Pos2,
+        to: egui::Pos2,
+    },
+    Arc {
+        center: egui::Pos2,
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+    /// A LABEL: `text` starting at `at` and running along `angle` (the
+    /// turtle's heading), `size` units tall
+    Text {
+        at: egui::Pos2,
+        text: String,
+        angle: f32,
+        size: f32,
+    },
+    /// A FILL: the enclosed area around `seed`, as rectangles that tile it
+    Fill {
+        seed: egui::Pos2,
+        color: egui::Color32,
+        spans: Vec<egui::Rect>,
+    },
+    ColorChange(egui::Color32),
+    PenWidth(f32),
+}
+
+/// Pen used for ops until a ColorChange or PenWidth says otherwise
+pub const DEFAULT_PEN_COLOR: egui::Color32 = egui::Color32::BLACK;
+pub const DEFAULT_PEN_WIDTH: f32 = 2.0;
+
+/// Canvas colour until SETBACKGROUND or the picker changes it
+pub const DEFAULT_BACKGROUND: egui::Color32 = egui::Color32::WHITE;
+
+/// Height of LABEL text until LABELSIZE changes it
+pub const DEFAULT_LABEL_SIZE: f32 = 14.0;
+
+/// Paint `ops` in order. `to_screen` maps turtle coordinates to screen
+/// positions and `zoom` scales text to match.
+pub fn paint(
+    painter: &egui::Painter,
+    ops: &[TurtleOp],
+    to_screen: impl Fn(egui::Pos2) -> egui::Pos2,
+    zoom: f32,
+) {
+    let mut color = DEFAULT_PEN_COLOR;
+    let mut width = DEFAULT_PEN_WIDTH;
+
+    for op in ops {
+        match op {
+            TurtleOp::Line { from, to } => {
+                painter.line_segment(
+                    [to_screen(*from), to_screen(*to)],
+                    egui::Stroke::new(width, color),
+                );
+            }
+            TurtleOp::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => {
+                let points: Vec<egui::Pos2> = arc_points(*center, *radius, *start_angle, *sweep)
+                    .into_iter()
+                    .map(&to_screen)
+                    .collect();
+                painter.add(egui::Shape::line(points, egui::Stroke::new(width, color)));
+            }
+            TurtleOp::Text {
+                at,
+                text,
+                angle,
+                size,
+            } => {
+                let galley = painter.layout_no_wrap(
+                    text.clone(),
+                    egui::FontId::proportional(size * zoom),
+                    color,
+                );
+                // Text shapes turn about their top-left corner; start from the
+                // corner that puts the bottom-left of the text at the turtle
+                let angle = angle.to_radians();
+                let height = galley.size().y;
+                let corner =
+                    to_screen(*at) + egui::vec2(height * angle.sin(), -height * angle.cos());
+                let mut shape = egui::epaint::TextShape::new(corner, galley);
+                shape.angle = angle;
+                painter.add(shape);
+            }
+            TurtleOp::Fill { color, spans, .. } => {
+                for span in spans {
+                    let rect = egui::Rect::from_two_pos(to_screen(span.min), to_screen(span.max));
+                    painter.rect_filled(rect, 0.0, *color);
+                }
+            }
+            TurtleOp::ColorChange(new_color) => color = *new_color,
+            TurtleOp::PenWidth(new_width) => width = *new_width,
+        }
+    }
+}
+
+/// An arc approximated by short segments, each at most 5 degrees and about
+/// 4 units long, so large circles stay round when zoomed in
+pub fn arc_points(
+    center: egui::Pos2,
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+) -> Vec<egui::Pos2> {
+    let by_angle = sweep.abs() / 5.0;
+    let by_length = sweep.abs().to_radians() * radius.abs() / 4.0;
+    let steps = (by_angle.max(by_length).ceil() as usize).clamp(1, 1000);
+    (0..=steps)
+        .map(|i| {
+            let angle = (start_angle + sweep * i as f32 / steps as f32).to_radians();
+            egui::pos2(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+/// Smallest rectangle holding every line, arc and text anchor in `ops`, or
+/// `None` when nothing has been drawn
+pub fn bounds(ops: &[TurtleOp]) -> Option<egui::Rect> {
+    let mut points = Vec::new();
+    for op in ops {
+        match op {
+            TurtleOp::Line { from, to } => points.extend([*from, *to]),
+            TurtleOp::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => points.extend(arc_points(*center, *radius, *start_angle, *sweep)),
+            TurtleOp::Text { at, .. } => points.push(*at),
+            TurtleOp::Fill { spans, .. } => {
+                points.extend(spans.iter().flat_map(|span| [span.min, span.max]))
+            }
+            TurtleOp::ColorChange(_) | TurtleOp::PenWidth(_) => {}
+        }
+    }
+    let first = *points.first()?;
+    Some(
+        points
+            .iter()
+            .fold(egui::Rect::from_min_max(first, first), |rect, point| {
+                rect.union(egui::Rect::from_min_max(*point, *point))
+            }),
+    )
+}
+
+/// Screen position of a turtle-coordinate point on a canvas whose origin
+/// sits at `center`, shifted by `pan` and scaled by `zoom`
+pub fn to_screen(center: egui::Pos2, pan: egui::Vec2, zoom: f32, point: egui::Pos2) -> egui::Pos2 {
+    center + (point.to_vec2() + pan) * zoom
+}
+
+/// Inverse of `to_screen`: the turtle coordinates under a screen position
+pub fn to_turtle(center: egui::Pos2, pan: egui::Vec2, zoom: f32, screen: egui::Pos2) -> egui::Pos2 {
+    egui::Pos2::ZERO + (screen - center) / zoom - pan
+}
+
+/// Zoom levels the canvas allows
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 5.0;
+
+/// Pan that keeps the turtle point under `anchor` (a screen position) in
+/// place while the zoom changes, so zooming follows the mouse
+pub fn zoom_about(
+    center: egui::Pos2,
+    pan: egui::Vec2,
+    zoom: f32,
+    new_zoom: f32,
+    anchor: egui::Pos2,
+) -> egui::Vec2 {
+    let fixed = to_turtle(center, pan, zoom, anchor);
+    (anchor - center) / new_zoom - fixed.to_vec2()
+}
+
+/// Pan and zoom that frame everything in `ops` on a canvas of `size`,
+/// leaving `margin` pixels around it. `None` when nothing has been drawn.
+pub fn fit_view(ops: &[TurtleOp], size: egui::Vec2, margin: f32) -> Option<(egui::Vec2, f32)> {
+    let bounds = bounds(ops)?;
+    let room = size - egui::vec2(margin, margin) * 2.0;
+    // A straight line has no width or height; don't let it divide by zero
+    let drawn = bounds.size().max(egui::vec2(1.0, 1.0));
+    let zoom = (room.x / drawn.x)
+        .min(room.y / drawn.y)
+        .clamp(MIN_ZOOM, MAX_ZOOM);
+    Some((-bounds.center().to_vec2(), zoom))
+}
+
+/// Grid lines are kept at least this many pixels apart
+const MIN_GRID_PIXELS: f32 = 25.0;
+
+/// Turtle units between grid lines: the smallest 1, 2 or 5 times a power of
+/// ten that keeps the lines readable at `zoom`
+pub fn grid_spacing(zoom: f32) -> f32 {
+    if zoom <= 0.0 {
+        return 100.0;
+    }
+    let mut power = 1.0;
+    loop {
+        for step in [1.0, 2.0, 5.0] {
+            if power * step * zoom >= MIN_GRID_PIXELS {
+                return power * step;
+            }
+        }
+        power *= 10.0;
+    }
+}
+
+/// Multiples of `spacing` between `min` and `max`
+pub fn grid_positions(min: f32, max: f32, spacing: f32) -> Vec<f32> {
+    let first = (min / spacing).ceil() as i64;
+    let last = (max / spacing).floor() as i64;
+    (first..=last).map(|i| i as f32 * spacing).collect()
+}
+
+/// Draw a labelled coordinate grid and the origin axes under the drawing
+pub fn paint_grid(painter: &egui::Painter, rect: egui::Rect, pan: egui::Vec2, zoom: f32) {
+    let center = rect.center();
+    let top_left = to_turtle(center, pan, zoom, rect.min);
+    let bottom_right = to_turtle(center, pan, zoom, rect.max);
+    let spacing = grid_spacing(zoom);
+    let grid = egui::Stroke::new(1.0, egui::Color32::from_gray(225));
+    let axis = egui::Stroke::new(1.5, egui::Color32::from_gray(120));
+    let label_font = egui::FontId::monospace(9.0);
+    let label_color = egui::Color32::from_gray(140);
+
+    for x in grid_positions(top_left.x, bottom_right.x, spacing) {
+        let screen_x = to_screen(center, pan, zoom, egui::pos2(x, 0.0)).x;
+        let stroke = if x == 0.0 { axis } else { grid };
+        painter.vline(screen_x, rect.y_range(), stroke);
+        painter.text(
+            egui::pos2(screen_x + 2.0, rect.top() + 1.0),
+            egui::Align2::LEFT_TOP,
+            format!("{}", x),
+            label_font.clone(),
+            label_color,
+        );
+    }
+    for y in grid_positions(top_left.y, bottom_right.y, spacing) {
+        let screen_y = to_screen(center, pan, zoom, egui::pos2(0.0, y)).y;
+        let stroke = if y == 0.0 { axis } else { grid };
+        painter.hline(rect.x_range(), screen_y, stroke);
+        if y != 0.0 {
+            painter.text(
+                egui::pos2(rect.left() + 2.0, screen_y - 1.0),
+                egui::Align2::LEFT_BOTTOM,
+                format!("{}", y),
+                label_font.clone(),
+                label_color,
+            );
+        }
+    }
+}
+
+/// Lines per second when the drawing is replayed, until the slider moves
+pub const DEFAULT_ANIMATION_SPEED: f32 = 20.0;
+
+/// Ops that take time to replay; colour and width changes happen instantly.
+/// Fills and labels count as one stroke each and appear all at once.
+fn is_stroke(op: &TurtleOp) -> bool {
+    matches!(
+        op,
+        TurtleOp::Line { .. }
+            | TurtleOp::Arc { .. }
+            | TurtleOp::Fill { .. }
+            | TurtleOp::Text { .. }
+    )
+}
+
+/// Number of strokes an animated replay of `ops` has to draw
+pub fn stroke_count(ops: &[TurtleOp]) -> usize {
+    ops.iter().filter(|op| is_stroke(op)).count()
+}
+
+/// What is on the canvas `progress` strokes into a replay: every op before
+/// the current stroke, plus that stroke drawn part of the way. Also returns
+/// where the pen is, so it can be shown while the replay runs.
+pub fn replay(ops: &[TurtleOp], progress: f32) -> (Vec<TurtleOp>, Option<egui::Pos2>) {
+    let mut shown = Vec::new();
+    let mut pen = None;
+    let mut remaining = progress.max(0.0);
+
+    for op in ops {
+        if !is_stroke(op) {
+            shown.push(op.clone());
+            continue;
+        }
+        if remaining <= 0.0 {
+            break;
+        }
+        let fraction = remaining.min(1.0);
+        remaining -= fraction;
+        let partial = match op {
+            TurtleOp::Line { from, to } => TurtleOp::Line {
+                from: *from,
+                to: *from + (*to - *from) * fraction,
+            },
+            TurtleOp::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => TurtleOp::Arc {
+                center: *center,
+                radius: *radius,
+                start_angle: *start_angle,
+                sweep: sweep * fraction,
+            },
+            TurtleOp::Fill { .. } | TurtleOp::Text { .. } => op.clone(),
+            _ => unreachable!("only strokes reach here"),
+        };
+        pen = Some(stroke_end(&partial));
+        // A finished stroke is shown exactly as recorded
+        shown.push(if fraction < 1.0 { partial } else { op.clone() });
+    }
+    (shown, pen)
+}
+
+/// Where the pen finishes a stroke
+fn stroke_end(op: &TurtleOp) -> egui::Pos2 {
+    match op {
+        TurtleOp::Arc {
+            center,
+            radius,
+            start_angle,
+            sweep,
+        } => {
+            let end = (start_angle + sweep).to_radians();
+            egui::pos2(center.x + radius * end.cos(), center.y + radius * end.sin())
+        }
+        TurtleOp::Line { to, .. } => *to,
+        TurtleOp::Text { at, .. } => *at,
+        TurtleOp::Fill { seed, .. } => *seed,
+        TurtleOp::ColorChange(_) | TurtleOp::PenWidth(_) => egui::Pos2::ZERO,
+    }
+}
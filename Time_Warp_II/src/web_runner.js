@@ -0,0 +1,358 @@
+// Runs the program on an exported page in the browser. The interpreters,
+// built for WebAssembly (time_warp_web, wasm32-wasip1), sit base64-encoded
+// in the "runtime" script tag. The few system calls they make are answered
+// here, and the turtle commands they write out are drawn the way the IDE's
+// canvas draws them. It loads as a module, so its names stay clear of the
+// replay script's.
+
+const ENOSYS = 52; // every system call not answered below
+const EBADF = 8;
+const SIZE = { width: 1200, height: 900 };
+const MARGIN = 20;
+
+class Exit {
+  constructor(code) {
+    this.code = code;
+  }
+}
+
+// Run the compiled runtime with `args`; `ask` answers INPUT from what has
+// been printed so far, or returns null when there is no answer
+async function run(module, args, ask) {
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+  const argv = args.map((arg) => encoder.encode(arg + "\0"));
+  let memory;
+  let stdout = "";
+  let pending = new Uint8Array(0); // an answer not read yet
+  let eof = false;
+  const view = () => new DataView(memory.buffer);
+  const bytesAt = (pointer, length) => new Uint8Array(memory.buffer, pointer, length);
+  const iovecs = (pointer, count) =>
+    Array.from({ length: count }, (_, i) =>
+      bytesAt(view().getUint32(pointer + 8 * i, true), view().getUint32(pointer + 8 * i + 4, true))
+    );
+  const printed = () =>
+    events(stdout)
+      .map((event) => event.print ?? "")
+      .join("");
+
+  const system = {
+    args_sizes_get(count, size) {
+      view().setUint32(count, argv.length, true);
+      view().setUint32(size, argv.reduce((sum, arg) => sum + arg.length, 0), true);
+      return 0;
+    },
+    args_get(pointers, buffer) {
+      argv.forEach((arg, i) => {
+        view().setUint32(pointers + 4 * i, buffer, true);
+        bytesAt(buffer, arg.length).set(arg);
+        buffer += arg.length;
+      });
+      return 0;
+    },
+    environ_sizes_get(count, size) {
+      view().setUint32(count, 0, true);
+      view().setUint32(size, 0, true);
+      return 0;
+    },
+    environ_get: () => 0,
+    clock_time_get(id, precision, time) {
+      const nanoseconds = id === 0 ? Date.now() * 1e6 : performance.now() * 1e6;
+      view().setBigUint64(time, BigInt(Math.round(nanoseconds)), true);
+      return 0;
+    },
+    random_get(buffer, length) {
+      for (let at = 0; at < length; at += 65536) {
+        crypto.getRandomValues(bytesAt(buffer + at, Math.min(65536, length - at)));
+      }
+      return 0;
+    },
+    fd_write(fd, pointer, count, written) {
+      let total = 0;
+      for (const bytes of iovecs(pointer, count)) {
+        if (fd === 1) {
+          stdout += decoder.decode(bytes, { stream: true });
+        }
+        total += bytes.length;
+      }
+      view().setUint32(written, total, true);
+      return 0;
+    },
+    fd_read(fd, pointer, count, read) {
+      if (fd !== 0) {
+        return EBADF;
+      }
+      if (pending.length === 0 && !eof) {
+        const answer = ask(printed());
+        if (answer === null) {
+          eof = true;
+        } else {
+          pending = encoder.encode(answer + "\n");
+        }
+      }
+      let total = 0;
+      for (const bytes of iovecs(pointer, count)) {
+        const length = Math.min(bytes.length, pending.length);
+        bytes.set(pending.subarray(0, length));
+        pending = pending.subarray(length);
+        total += length;
+      }
+      view().setUint32(read, total, true);
+      return 0;
+    },
+    // SLEEP and WAIT end at once: a page can't pause without freezing
+    poll_oneoff(subscriptions, out, count, ready) {
+      for (let i = 0; i < count; i++) {
+        const event = out + 32 * i;
+        bytesAt(event, 32).fill(0);
+        bytesAt(event, 8).set(bytesAt(subscriptions + 48 * i, 8));
+        view().setUint8(event + 10, view().getUint8(subscriptions + 48 * i + 8));
+      }
+      view().setUint32(ready, count, true);
+      return 0;
+    },
+    fd_prestat_get: () => EBADF, // no folders, so no files to OPEN
+    proc_exit(code) {
+      throw new Exit(code);
+    },
+  };
+
+  const imports = {};
+  for (const { module: name, name: call } of WebAssembly.Module.imports(module)) {
+    imports[name] ??= {};
+    imports[name][call] = system[call] ?? (() => ENOSYS);
+  }
+  const instance = await WebAssembly.instantiate(module, imports);
+  memory = instance.exports.memory;
+  try {
+    instance.exports._start();
+  } catch (err) {
+    if (!(err instanceof Exit)) {
+      throw err;
+    }
+  }
+  return events(stdout);
+}
+
+function events(stdout) {
+  return stdout
+    .split("\n")
+    .filter((line) => line)
+    .map((line) => JSON.parse(line));
+}
+
+const css = (rgb) => `rgb(${rgb.join(",")})`;
+
+// Points along an arc, as turtle::arc_points works them out
+function arcPoints(center, radius, start, sweep) {
+  const byAngle = Math.abs(sweep) / 5;
+  const byLength = (Math.abs(sweep) * Math.PI * Math.abs(radius)) / 180 / 4;
+  const steps = Math.min(Math.max(Math.ceil(Math.max(byAngle, byLength)), 1), 1000);
+  return Array.from({ length: steps + 1 }, (_, i) => {
+    const angle = ((start + (sweep * i) / steps) * Math.PI) / 180;
+    return { x: center.x + radius * Math.cos(angle), y: center.y + radius * Math.sin(angle) };
+  });
+}
+
+// What the turtles drew, in turtle coordinates (y down, angles in degrees
+// clockwise from the x axis), as the IDE's canvas works it out
+function strokes(turtleEvents) {
+  const turtles = new Map();
+  let shapes = [];
+  let background = [255, 255, 255];
+  for (const { turtle: name, command, args } of turtleEvents) {
+    if (!turtles.has(name)) {
+      turtles.set(name, { x: 0, y: 0, angle: 0, color: [0, 0, 0], width: 2, labelSize: 14 });
+    }
+    const turtle = turtles.get(name);
+    const at = { x: turtle.x, y: turtle.y };
+    const pen = { color: turtle.color, width: turtle.width };
+    const move = (distance, draw) => {
+      const angle = (turtle.angle * Math.PI) / 180;
+      turtle.x += distance * Math.cos(angle);
+      turtle.y += distance * Math.sin(angle);
+      if (draw) {
+        shapes.push({ ...pen, points: [at, { x: turtle.x, y: turtle.y }] });
+      }
+    };
+    switch (command) {
+      case "FORWARD":
+        move(args[0], true);
+        break;
+      case "BACK":
+        move(-args[0], true);
+        break;
+      case "MOVE":
+        move(args[0], false);
+        break;
+      case "RIGHT":
+        turtle.angle = (turtle.angle + args[0]) % 360;
+        break;
+      case "LEFT":
+        turtle.angle = (turtle.angle - args[0]) % 360;
+        break;
+      case "HOME":
+        Object.assign(turtle, { x: 0, y: 0, angle: 0 });
+        break;
+      case "SETXY":
+        shapes.push({ ...pen, points: [at, { x: args[0], y: args[1] }] });
+        Object.assign(turtle, { x: args[0], y: args[1] });
+        break;
+      case "MOVETO":
+        Object.assign(turtle, { x: args[0], y: args[1] });
+        break;
+      case "SETHEADING":
+        turtle.angle = args[0] % 360;
+        break;
+      case "CLEARSCREEN":
+        shapes = [];
+        for (const other of turtles.values()) {
+          Object.assign(other, { x: 0, y: 0, angle: 0 });
+        }
+        break;
+      case "SETPENCOLOR":
+        turtle.color = args;
+        break;
+      case "SETPENSIZE":
+        turtle.width = args[0];
+        break;
+      case "ARC":
+        shapes.push({ ...pen, points: arcPoints(at, args[0], turtle.angle, args[1]) });
+        break;
+      case "LABEL":
+        shapes.push({ ...pen, text: args[0], at, angle: turtle.angle, size: turtle.labelSize });
+        break;
+      case "SETLABELSIZE":
+        turtle.labelSize = args[0];
+        break;
+      case "SETBACKGROUND":
+        background = args;
+        break;
+      case "FILL":
+        shapes.push({ ...pen, fill: at });
+        break;
+    }
+  }
+  return { shapes, background };
+}
+
+// Fill the area of one colour around `seed` (canvas pixels), as FILL does
+function floodFill(context, seed, color) {
+  const { width, height } = context.canvas;
+  const x = Math.round(seed.x);
+  const y = Math.round(seed.y);
+  if (x < 0 || y < 0 || x >= width || y >= height) {
+    return;
+  }
+  const image = context.getImageData(0, 0, width, height);
+  const pixels = new Uint32Array(image.data.buffer);
+  const paint = new Uint32Array(new Uint8ClampedArray([...color, 255]).buffer)[0];
+  const target = pixels[y * width + x];
+  if (target === paint) {
+    return;
+  }
+  const stack = [y * width + x];
+  while (stack.length > 0) {
+    const i = stack.pop();
+    if (pixels[i] !== target) {
+      continue;
+    }
+    pixels[i] = paint;
+    if (i % width > 0) stack.push(i - 1);
+    if (i % width < width - 1) stack.push(i + 1);
+    if (i >= width) stack.push(i - width);
+    if (i < pixels.length - width) stack.push(i + width);
+  }
+  context.putImageData(image, 0, 0);
+}
+
+// Draw `shapes` one at a time, every `delay` milliseconds, with the whole
+// drawing in view
+let timer;
+function draw(canvas, { shapes, background }, delay) {
+  const points = shapes.flatMap((shape) => shape.points ?? [shape.at ?? shape.fill]);
+  const xs = points.map((point) => point.x);
+  const ys = points.map((point) => point.y);
+  const [left, top] = [Math.min(...xs), Math.min(...ys)];
+  const [right, bottom] = [Math.max(...xs), Math.max(...ys)];
+  const zoom = Math.min(
+    Math.max(
+      Math.min(
+        (SIZE.width - 2 * MARGIN) / Math.max(right - left, 1),
+        (SIZE.height - 2 * MARGIN) / Math.max(bottom - top, 1)
+      ),
+      0.1
+    ),
+    5
+  );
+  const screen = (point) => ({
+    x: SIZE.width / 2 + (point.x - (left + right) / 2) * zoom,
+    y: SIZE.height / 2 + (point.y - (top + bottom) / 2) * zoom,
+  });
+
+  const context = canvas.getContext("2d", { willReadFrequently: true });
+  context.fillStyle = css(background);
+  context.fillRect(0, 0, SIZE.width, SIZE.height);
+  context.lineCap = "round";
+  context.lineJoin = "round";
+  const paint = (shape) => {
+    context.strokeStyle = context.fillStyle = css(shape.color);
+    context.lineWidth = shape.width;
+    if (shape.points) {
+      context.beginPath();
+      shape.points.map(screen).forEach(({ x, y }, i) => (i ? context.lineTo(x, y) : context.moveTo(x, y)));
+      context.stroke();
+    } else if (shape.text !== undefined) {
+      const { x, y } = screen(shape.at);
+      context.save();
+      context.translate(x, y);
+      context.rotate((shape.angle * Math.PI) / 180);
+      context.font = `${shape.size * zoom}px sans-serif`;
+      context.textBaseline = "bottom";
+      context.fillText(shape.text, 0, 0);
+      context.restore();
+    } else {
+      floodFill(context, screen(shape.fill), shape.color);
+    }
+  };
+
+  clearInterval(timer);
+  let next = 0;
+  timer = setInterval(() => {
+    if (next < shapes.length) paint(shapes[next++]);
+    else clearInterval(timer);
+  }, delay);
+}
+
+const button = document.getElementById("run");
+const output = document.getElementById("output");
+let compiled;
+button.addEventListener("click", async () => {
+  button.disabled = true;
+  try {
+    const base64 = document.getElementById("runtime").textContent.trim();
+    compiled ??= await WebAssembly.compile(Uint8Array.from(atob(base64), (c) => c.charCodeAt(0)));
+    const program = document.getElementById("program").textContent;
+    const ran = await run(compiled, ["time_warp_web", button.dataset.language, program], (printed) =>
+      prompt(printed.split("\n").slice(-10).join("\n"))
+    );
+    output.textContent = ran
+      .map((event) => event.print ?? (event.error ? `\n${event.error}\n` : ""))
+      .join("");
+    const drawing = strokes(ran.filter((event) => event.command));
+    if (drawing.shapes.length > 0) {
+      let canvas = document.querySelector("#drawing canvas");
+      if (!canvas) {
+        canvas = Object.assign(document.createElement("canvas"), SIZE);
+        document.getElementById("drawing").replaceChildren(canvas);
+        document.getElementById("replay")?.remove();
+      }
+      draw(canvas, drawing, Number(button.dataset.delay));
+    }
+  } catch (err) {
+    output.textContent = `Could not run the program in this browser: ${err}`;
+  } finally {
+    button.disabled = false;
+  }
+});
@@ -0,0 +1,250 @@
+//! Class Share: programs on a WebDAV share (GET to open, PUT to save), e.g.
+//! a school's class drive. Transfers run on their own thread so a slow or
+//! unreachable server never freezes the window; each frame checks whether
+//! the one in flight has finished.
+
+use crate::file_backends::{self, FileBackend};
+use crate::html_export::base64_encode;
+use crate::TimeWarpApp;
+use eframe::egui;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+/// How long a share may take to answer before the transfer gives up
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a frame is asked for while a transfer is in flight
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Files on a WebDAV share, over `http://` or `https://`. A password is
+/// only ever sent over `https://`.
+pub struct WebDavBackend {
+    pub username: String,
+    pub password: String,
+}
+
+impl WebDavBackend {
+    fn request(&self, method: &str, url: &str) -> io::Result<ureq::Request> {
+        if !file_backends::is_remote(url) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "expected an http:// or https:// address",
+            ));
+        }
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let request = agent.request(method, url);
+        if self.username.is_empty() {
+            return Ok(request);
+        }
+        if !url.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "a password is only sent to https:// shares; use an https:// address \
+                 or clear the user name",
+            ));
+        }
+        let credentials = format!("{}:{}", self.username, self.password);
+        Ok(request.set(
+            "Authorization",
+            &format!("Basic {}", base64_encode(credentials.as_bytes())),
+        ))
+    }
+}
+
+impl FileBackend for WebDavBackend {
+    fn name(&self) -> &'static str {
+        "WebDAV"
+    }
+
+    fn read(&self, location: &str) -> io::Result<String> {
+        let response = self.request("GET", location)?.call().map_err(http_error)?;
+        let mut bytes = Vec::new();
+        response.into_reader().read_to_end(&mut bytes)?;
+        file_backends::decode_program(bytes)
+    }
+
+    fn write(&self, location: &str, content: &str) -> io::Result<()> {
+        self.request("PUT", location)?
+            .send_bytes(content.as_bytes())
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+fn http_error(err: ureq::Error) -> io::Error {
+    match err {
+        ureq::Error::Status(status, _) => {
+            let kind = match status {
+                401 | 403 => io::ErrorKind::PermissionDenied,
+                404 => io::ErrorKind::NotFound,
+                _ => io::ErrorKind::Other,
+            };
+            io::Error::new(kind, format!("server answered HTTP {}", status))
+        }
+        ureq::Error::Transport(transport) => io::Error::other(transport.to_string()),
+    }
+}
+
+/// An open or save running on its own thread. An open hands back the
+/// program's text; a save hands back nothing.
+pub(crate) struct Transfer {
+    location: String,
+    saving: bool,
+    result: Receiver<io::Result<String>>,
+}
+
+impl Transfer {
+    fn start(
+        location: &str,
+        saving: bool,
+        work: impl FnOnce() -> io::Result<String> + Send + 'static,
+    ) -> Self {
+        let (sender, result) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(work());
+        });
+        Self {
+            location: location.to_string(),
+            saving,
+            result,
+        }
+    }
+
+    /// The outcome once the transfer has finished
+    fn finished(&self) -> Option<io::Result<String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(io::Error::other("the transfer stopped unexpectedly")))
+            }
+        }
+    }
+}
+
+impl TimeWarpApp {
+    /// Start opening or saving a share URL. False when it couldn't start,
+    /// after saying why.
+    pub(crate) fn start_share_transfer(&mut self, location: &str, content: Option<String>) -> bool {
+        let verb = if content.is_some() { "save" } else { "open" };
+        if self.share_transfer.is_some() {
+            self.show_error(format!(
+                "Could not {} {}: another class share transfer is still running",
+                verb, location
+            ));
+            return false;
+        }
+        let backend = match self.file_backends.backend_for(location) {
+            Ok(backend) => backend,
+            Err(err) => {
+                self.show_error(format!("Could not {} {}: {}", verb, location, err));
+                return false;
+            }
+        };
+        let url = location.to_string();
+        self.share_transfer = Some(match content {
+            Some(content) => Transfer::start(location, true, move || {
+                backend.write(&url, &content).map(|()| String::new())
+            }),
+            None => Transfer::start(location, false, move || backend.read(&url)),
+        });
+        true
+    }
+
+    /// Where the transfer in flight is going, for the status bar
+    pub(crate) fn share_transfer_status(&self) -> Option<String> {
+        self.share_transfer.as_ref().map(|transfer| {
+            let verb = if transfer.saving { "Saving" } else { "Opening" };
+            format!("{} {}", verb, transfer.location)
+        })
+    }
+
+    /// Finish the transfer in flight once its thread is done
+    pub(crate) fn poll_share_transfer(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.share_transfer.as_ref().and_then(Transfer::finished) else {
+            if self.share_transfer.is_some() {
+                ctx.request_repaint_after(POLL_INTERVAL);
+            }
+            return;
+        };
+        let Some(transfer) = self.share_transfer.take() else {
+            return;
+        };
+        match (transfer.saving, result) {
+            (false, Ok(content)) => self.show_opened(&transfer.location, content),
+            (true, Ok(_)) => {
+                self.last_file_path = Some(transfer.location);
+                self.file_saved();
+            }
+            (saving, Err(err)) => self.show_error(format!(
+                "Could not {} {}: {}",
+                if saving { "save" } else { "open" },
+                transfer.location,
+                err
+            )),
+        }
+    }
+
+    /// Open or save programs on a WebDAV class share
+    pub(crate) fn render_class_share(&mut self, ctx: &egui::Context) {
+        if !self.show_class_share {
+            return;
+        }
+        let mut open = true;
+        let mut action = None;
+        let idle = self.share_transfer.is_none();
+
+        egui::Window::new("🌐 Class Share")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.checkbox(
+                    &mut self.file_backends.network_allowed,
+                    "Allow opening and saving over the network",
+                )
+                .on_hover_text("Programs stay on this computer unless this is ticked");
+                ui.add_space(4.0);
+                ui.add_enabled_ui(self.file_backends.network_allowed, |ui| {
+                    egui::Grid::new("class_share_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("File address:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.class_share_url)
+                                    .hint_text("https://server/class/program.twb")
+                                    .desired_width(280.0),
+                            );
+                            ui.end_row();
+                            ui.label("User name:");
+                            ui.text_edit_singleline(&mut self.file_backends.webdav_username);
+                            ui.end_row();
+                            ui.label("Password:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.file_backends.webdav_password)
+                                    .password(true),
+                            )
+                            .on_hover_text("Only sent to https:// addresses");
+                            ui.end_row();
+                        });
+                    ui.add_enabled_ui(idle, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.button("📂 Open from Share").clicked() {
+                                action = Some(None);
+                            }
+                            if ui.button("💾 Save to Share").clicked() {
+                                action = Some(Some(self.code.clone()));
+                            }
+                        });
+                    });
+                });
+            });
+
+        let url = self.class_share_url.trim().to_string();
+        let started = match action {
+            Some(content) => self.start_share_transfer(&url, content),
+            None => false,
+        };
+        self.show_class_share = open && !started;
+    }
+}
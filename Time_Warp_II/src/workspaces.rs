@@ -0,0 +1,68 @@
+use crate::TimeWarpApp;
+use eframe::egui;
+
+/// An extra IDE window whose state is completely separate from the main one
+pub(crate) struct Workspace {
+    pub(crate) viewport_id: egui::ViewportId,
+    pub(crate) title: String,
+    pub(crate) app: TimeWarpApp,
+    pub(crate) open: bool,
+}
+
+impl TimeWarpApp {
+    /// Open another workspace with its own editor, interpreter session and
+    /// output, starting from this window's settings
+    pub(crate) fn open_workspace(&mut self) {
+        let number = self.next_workspace_number;
+        self.next_workspace_number += 1;
+        self.workspaces.push(Workspace {
+            viewport_id: egui::ViewportId::from_hash_of(("workspace", number)),
+            title: format!("Time Warp IDE - Workspace {}", number),
+            app: TimeWarpApp {
+                settings: self.settings.clone(),
+                ..TimeWarpApp::default()
+            },
+            open: true,
+        });
+    }
+
+    /// Show every extra workspace in its own native window
+    pub(crate) fn show_secondary_workspaces(&mut self, ctx: &egui::Context) {
+        let mut spawn_requested = std::mem::take(&mut self.new_workspace_requested);
+
+        for workspace in &mut self.workspaces {
+            let builder = egui::ViewportBuilder::default()
+                .with_title(workspace.title.clone())
+                .with_inner_size([1000.0, 700.0]);
+
+            ctx.show_viewport_immediate(workspace.viewport_id, builder, |ctx, class| {
+                if matches!(class, egui::ViewportClass::Embedded) {
+                    // The backend can't open native windows, so don't draw a second
+                    // full IDE over the first one
+                    egui::Window::new(workspace.title.as_str())
+                        .open(&mut workspace.open)
+                        .show(ctx, |ui| {
+                            ui.label("Extra workspaces need a backend with multi-window support.");
+                        });
+                    return;
+                }
+
+                workspace.app.confirm_quit(ctx);
+                workspace.app.show_workspace(ctx);
+                let app = &workspace.app;
+                let unsaved = !app.quit_confirmed && !app.unsaved_documents().is_empty();
+                if ctx.input(|i| i.viewport().close_requested()) && !unsaved {
+                    workspace.open = false;
+                }
+            });
+
+            // Workspaces are kept flat: a request from a child opens a sibling window
+            spawn_requested |= std::mem::take(&mut workspace.app.new_workspace_requested);
+        }
+
+        self.workspaces.retain(|workspace| workspace.open);
+        if spawn_requested {
+            self.open_workspace();
+        }
+    }
+}
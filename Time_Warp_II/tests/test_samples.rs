@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::fs;
 
 #[derive(Clone)]
+#[allow(dead_code)] // mirrors the IDE's turtle, colour included
 struct TurtleState {
     x: f32,
     y: f32,
@@ -9,6 +10,7 @@ struct TurtleState {
     color: String,
 }
 
+#[allow(dead_code)] // Input is part of the shape even though no sample asks
 enum CommandResult {
     Output(String),
     Goto(u32),
@@ -152,7 +154,7 @@ impl TestApp {
         while let Some(ch) = chars.next() {
             if ch == '"' {
                 // Handle quoted strings
-                while let Some(ch) = chars.next() {
+                for ch in chars.by_ref() {
                     if ch == '"' {
                         break;
                     }
@@ -188,7 +190,7 @@ impl TestApp {
             // Simple expression evaluation
             let value = if expr.starts_with('"') && expr.ends_with('"') {
                 expr[1..expr.len() - 1].to_string()
-            } else if let Ok(_) = expr.parse::<i32>() {
+            } else if expr.parse::<i32>().is_ok() {
                 expr.to_string()
             } else {
                 // Check if it's a variable reference
@@ -206,16 +208,14 @@ impl TestApp {
         if let Some((cond, then_part)) = condition.split_once(" THEN ") {
             let then_part = then_part.trim();
 
-            if self.evaluate_condition(cond.trim()) {
-                if then_part.starts_with("GOTO ") {
-                    if let Some(line_num_str) = then_part.strip_prefix("GOTO ") {
-                        if let Ok(line_num) = line_num_str.trim().parse::<u32>() {
-                            return CommandResult::Goto(line_num);
-                        }
+            if self.evaluate_condition(cond.trim()) && then_part.starts_with("GOTO ") {
+                if let Some(line_num_str) = then_part.strip_prefix("GOTO ") {
+                    if let Ok(line_num) = line_num_str.trim().parse::<u32>() {
+                        return CommandResult::Goto(line_num);
                     }
                 }
-                // Could handle other THEN actions here
             }
+            // Could handle other THEN actions here
         }
         CommandResult::Continue
     }
@@ -223,10 +223,9 @@ impl TestApp {
     fn evaluate_condition(&self, condition: &str) -> bool {
         // Simple condition evaluation
         if let Some((left, op_right)) = condition.split_once('=') {
-            let (op, right) = if op_right.starts_with('=') {
-                ("==", &op_right[1..])
-            } else {
-                ("=", op_right)
+            let (op, right) = match op_right.strip_prefix('=') {
+                Some(rest) => ("==", rest),
+                None => ("=", op_right),
             };
 
             let left_val = self.get_value(left.trim());
@@ -249,8 +248,6 @@ impl TestApp {
     fn get_value(&self, expr: &str) -> String {
         if let Some(value) = self.variables.get(expr) {
             value.clone()
-        } else if let Ok(_) = expr.parse::<i32>() {
-            expr.to_string()
         } else {
             expr.to_string()
         }
@@ -353,6 +350,5 @@ mod modular_basic_tests {
         // Placeholder test - the modular interpreter is implemented
         // and compiles successfully. Full integration testing would
         // require making modules public or creating a lib crate.
-        assert!(true);
     }
 }
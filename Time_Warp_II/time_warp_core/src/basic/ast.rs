@@ -0,0 +1,1068 @@
+use crate::basic::codes::ErrorCode;
+use crate::basic::events::{EventSource, EventTable};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Variable type declarations
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableType {
+    Integer, // %
+    Single,  // ! or default
+    Double,  // #
+    String,  // $
+}
+
+/// Runtime value types
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i32),
+    Single(f32),
+    Double(f64),
+    String(String),
+    Number(f64), // Legacy support
+}
+
+/// Variable information with type tracking
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableInfo {
+    pub value: Value,
+    pub declared_type: VariableType,
+}
+
+/// Token types for lexical analysis
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // Keywords
+    Let,
+    Print,
+    Lprint,
+    Using,
+    Input,
+    If,
+    Then,
+    Else,
+    End,
+    Stop,
+    Assert,
+    Randomize,
+    For,
+    To,
+    Step,
+    Next,
+    Goto,
+    Gosub,
+    Return,
+    Rem,
+    Dim,
+    Def,
+    Fn,
+    Clear,
+    Cls,
+    Writeln,
+    Printx,
+    Defint,
+    Defsng,
+    Defstr,
+    Defdbl,
+    Select,
+    Case,
+    On,
+    Off,
+    Error,
+    Resume,
+
+    // Files
+    Open,
+    Close,
+    Write,
+    Line,
+    As,
+    Output,
+    Append,
+    Random,
+    Field,
+    Lset,
+    Rset,
+    Get,
+    Put,
+    Files,
+    Kill,
+    Name,
+    Mkdir,
+    Chdir,
+    Rmdir,
+
+    // Memory
+    Poke,
+
+    // Pauses
+    Sleep,
+    Wait,
+
+    // Sound
+    Beep,
+    Sound,
+
+    // Event traps
+    Key,
+    Play,
+    Mouse,
+
+    // Turtle graphics
+    Forward,
+    Back,
+    TurnLeft,
+    TurnRight,
+    Penup,
+    Pendown,
+    Home,
+    Setxy,
+    SetHeading,
+    ClearScreen,
+    Turtle,
+    Turn,
+    SetPenColor,
+    PenSize,
+    Fill,
+    Arc,
+    Circle,
+    Label,
+    LabelSize,
+    SetBackground,
+    HideTurtle,
+    ShowTurtle,
+
+    // Operators
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
+
+    // Functions
+    Sin,
+    Cos,
+    Tan,
+    Sqr,
+    Abs,
+    Int,
+    Rnd,
+    Len,
+    Mid,
+    Left,
+    Right,
+    Chr,
+    Asc,
+    Val,
+    Str,
+    Tab,
+    Spc,
+
+    // System functions
+    Date,
+    Time,
+    Timer,
+    Environ,
+    Err,
+    Erl,
+
+    // Literals
+    Number(f64),
+    String(String),
+    Identifier(String),
+
+    // Punctuation
+    LParen,
+    RParen,
+    Comma,
+    Semicolon,
+    Colon,
+    Hash, // # before a file number
+
+    // Special
+    Eol,
+    Eof,
+}
+
+impl Token {
+    /// Whether this is one of the turtle-graphics words
+    pub fn is_turtle_keyword(&self) -> bool {
+        matches!(
+            self,
+            Token::Forward
+                | Token::Back
+                | Token::TurnLeft
+                | Token::TurnRight
+                | Token::Penup
+                | Token::Pendown
+                | Token::Home
+                | Token::Setxy
+                | Token::SetHeading
+                | Token::ClearScreen
+                | Token::Turtle
+                | Token::Turn
+                | Token::SetPenColor
+                | Token::PenSize
+                | Token::Fill
+                | Token::Arc
+                | Token::Circle
+                | Token::Label
+                | Token::LabelSize
+                | Token::SetBackground
+                | Token::HideTurtle
+                | Token::ShowTurtle
+        )
+    }
+}
+
+/// Abstract Syntax Tree node types
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    String(String),
+    Variable(String),
+    BinaryOp {
+        left: Box<Expression>,
+        operator: BinaryOperator,
+        right: Box<Expression>,
+    },
+    UnaryOp {
+        operator: UnaryOperator,
+        operand: Box<Expression>,
+    },
+    FunctionCall {
+        name: String,
+        arguments: Vec<Expression>,
+    },
+    ArrayAccess {
+        name: String,
+        index: Box<Expression>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+/// Written back as BASIC. Every operation inside another is bracketed,
+/// so `1+2*3` shows as `1 + (2 * 3)`: the grouping the parser chose.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |f: &mut fmt::Formatter<'_>, expression: &Expression| match expression {
+            Expression::BinaryOp { .. } | Expression::UnaryOp { .. } => {
+                write!(f, "({})", expression)
+            }
+            _ => write!(f, "{}", expression),
+        };
+        match self {
+            Expression::Number(value) => write!(f, "{}", value),
+            Expression::String(text) => write!(f, "\"{}\"", text),
+            Expression::Variable(name) => write!(f, "{}", name),
+            Expression::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                operand(f, left)?;
+                write!(f, " {} ", operator)?;
+                operand(f, right)
+            }
+            Expression::UnaryOp {
+                operator: UnaryOperator::Negate,
+                operand: inner,
+            } => {
+                write!(f, "-")?;
+                operand(f, inner)
+            }
+            Expression::UnaryOp {
+                operator: UnaryOperator::Not,
+                operand: inner,
+            } => {
+                write!(f, "NOT ")?;
+                operand(f, inner)
+            }
+            Expression::FunctionCall { name, arguments } => {
+                write!(f, "{}(", name)?;
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expression::ArrayAccess { name, index } => write!(f, "{}({})", name, index),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "MOD",
+            BinaryOperator::Power => "^",
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "<>",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOperator::Negate => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "NOT"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Let {
+        variable: String,
+        expression: Expression,
+    },
+    Print {
+        channel: PrintChannel,
+        using: Option<Expression>, // PRINT USING format
+        expressions: Vec<Expression>,
+        separators: Vec<PrintSeparator>,
+    },
+    Input {
+        prompt: Option<String>,
+        variable: String,
+    },
+    If {
+        condition: Expression,
+        then_branch: Vec<Statement>,
+        else_branch: Option<Vec<Statement>>,
+    },
+    For {
+        variable: String,
+        start: Expression,
+        end: Expression,
+        step: Option<Expression>,
+        body: Vec<Statement>,
+    },
+    Next {
+        variable: Option<String>,
+    },
+    Goto {
+        line: Expression,
+    },
+    Gosub {
+        line: Expression,
+    },
+    Return,
+    End,
+    Stop,
+    Assert {
+        condition: Expression,
+        message: Option<Expression>,
+    },
+    Randomize {
+        seed: Option<Expression>, // None or TIMER seeds from the clock
+    },
+    Rem(String),
+    Dim {
+        arrays: Vec<(String, Vec<Expression>)>,
+    },
+    Def {
+        name: String,
+        parameters: Vec<String>,
+        body: Expression,
+    },
+    Clear,
+    Cls, // clear the text output
+    Writeln {
+        expression: Expression,
+    },
+    Printx {
+        expression: Expression,
+    },
+    Select {
+        expression: Expression,
+        cases: Vec<SelectCase>,
+    },
+    Forward {
+        distance: Expression,
+    },
+    Back {
+        distance: Expression,
+    },
+    TurnLeft {
+        angle: Expression,
+    },
+    TurnRight {
+        angle: Expression,
+    },
+    Penup,
+    Pendown,
+    Home,
+    Setxy {
+        x: Expression,
+        y: Expression,
+    },
+    SetHeading {
+        angle: Expression,
+    },
+    ClearScreen,
+    Turtle {
+        name: Expression,
+    },
+    Turn {
+        angle: Expression,
+    },
+    SetPenColor {
+        components: Vec<Expression>, // palette index, or red, green, blue
+    },
+    PenSize {
+        size: Expression,
+    },
+    SetBackground {
+        components: Vec<Expression>, // palette index, or red, green, blue
+    },
+    Fill,
+    HideTurtle,
+    ShowTurtle,
+    Arc {
+        radius: Expression,
+        angle: Expression,
+    },
+    Circle {
+        radius: Expression,
+    },
+    Label {
+        text: Expression,
+    },
+    LabelSize {
+        size: Expression,
+    },
+    DefInt {
+        ranges: Vec<String>, // e.g., "A-C", "X"
+    },
+    DefSng {
+        ranges: Vec<String>,
+    },
+    DefDbl {
+        ranges: Vec<String>,
+    },
+    DefStr {
+        ranges: Vec<String>,
+    },
+    OnEvent {
+        event: EventKind,
+        argument: Option<Expression>, // key number, timer seconds or play threshold
+        line: Expression,             // handler line; 0 removes the handler
+    },
+    EventControl {
+        event: EventKind,
+        argument: Option<Expression>, // key number; KEY(0) means every key
+        state: TrapState,
+    },
+    OnError {
+        line: Expression, // handler line; 0 lets errors stop the program again
+    },
+    Resume {
+        target: ResumeTarget,
+    },
+    Error {
+        number: Expression, // the GW-BASIC error number ERR reads back
+    },
+    Open {
+        file: Expression,
+        mode: FileMode,
+        number: Expression,
+        length: Option<Expression>, // LEN=, a RANDOM file's record length
+    },
+    Close {
+        numbers: Vec<Expression>, // none closes every file
+    },
+    Write {
+        file: Option<Expression>, // None writes to the screen
+        expressions: Vec<Expression>,
+    },
+    FileInput {
+        number: Expression,
+        variables: Vec<String>,
+    },
+    LineInput {
+        number: Expression,
+        variable: String,
+    },
+    Field {
+        number: Expression,
+        fields: Vec<(Expression, String)>, // width AS variable
+    },
+    SetField {
+        justify: Justify, // LSET or RSET
+        variable: String,
+        expression: Expression,
+    },
+    Get {
+        number: Expression,
+        record: Option<Expression>, // None reads the next record
+    },
+    Put {
+        number: Expression,
+        record: Option<Expression>, // None writes the next record
+    },
+    Files {
+        pattern: Option<Expression>, // None lists the current folder
+    },
+    Kill {
+        pattern: Expression,
+    },
+    Name {
+        from: Expression,
+        to: Expression,
+    },
+    Folder {
+        action: FolderAction,
+        dir: Expression,
+    },
+    DefSeg {
+        segment: Option<Expression>, // None goes back to segment 0
+    },
+    Poke {
+        address: Expression,
+        value: Expression,
+    },
+    Sleep {
+        seconds: Option<Expression>, // None or 0 sleeps until a key is pressed
+    },
+    Wait {
+        ticks: Expression, // sixtieths of a second, as in Logo
+    },
+    Play {
+        music: Expression, // in the music macro language
+    },
+    Beep,
+    Sound {
+        frequency: Expression, // in hertz
+        ticks: Expression,     // 18.2 to a second, as in GW-BASIC
+    },
+}
+
+/// What OPEN opens a file for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Input,  // reading from the start
+    Output, // writing, emptying it first
+    Append, // writing after what is there
+    Random, // records read and written by number with GET and PUT
+}
+
+/// What MKDIR, CHDIR or RMDIR does with a folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FolderAction {
+    Make,   // MKDIR
+    Change, // CHDIR
+    Remove, // RMDIR
+}
+
+/// Which end of a field LSET or RSET puts text against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Justify {
+    Left,  // LSET
+    Right, // RSET
+}
+
+/// What a program has to be allowed to do when the IDE asks first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    WriteFiles,  // OPEN to write, NAME and MKDIR
+    DeleteFiles, // KILL and RMDIR
+}
+
+impl Permission {
+    pub const ALL: [Permission; 2] = [Permission::WriteFiles, Permission::DeleteFiles];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Permission::WriteFiles => "write files",
+            Permission::DeleteFiles => "delete files",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|permission| permission.name() == name)
+    }
+}
+
+/// Where RESUME carries on after an error handler
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResumeTarget {
+    Retry,            // RESUME or RESUME 0: the statement that failed
+    Next,             // RESUME NEXT: the statement after it
+    Line(Expression), // RESUME n
+}
+
+/// A runtime error caught by ON ERROR GOTO
+#[derive(Debug, Clone)]
+pub struct TrappedError {
+    pub error: InterpreterError,
+    pub number: u16,      // its GW-BASIC error number, as ERR reads it
+    pub statement: usize, // index of the statement that failed
+    pub line: usize,      // its BASIC line number, 0 if it has none
+}
+
+/// Kind of event a program can trap with ON ... GOSUB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Key,
+    Timer,
+    Play,
+    Mouse,
+}
+
+/// KEY(n) ON / OFF / STOP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapState {
+    On,      // events run the handler at the next statement boundary
+    Off,     // events are ignored
+    Stopped, // events are remembered and run once the trap is turned on
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectCase {
+    pub value: Option<Expression>, // None for CASE ELSE
+    pub statements: Vec<Statement>,
+}
+
+/// Where PRINT's output goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintChannel {
+    Screen,
+    Printer,          // LPRINT
+    File(Expression), // PRINT #n
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintSeparator {
+    Comma,
+    Semicolon,
+    None,
+}
+
+/// Complete BASIC program AST
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    pub line_numbers: HashMap<usize, usize>, // line_number -> statement_index
+    pub statement_lines: Vec<usize>,         // statement_index -> source line (1-based)
+    pub statement_columns: Vec<usize>,       // statement_index -> column it starts at (1-based)
+}
+
+/// User-defined function definition
+#[derive(Debug, Clone)]
+pub struct FunctionDefinition {
+    pub parameters: Vec<String>,
+    pub body: Expression,
+}
+
+/// RND's sequence before any RANDOMIZE, the same on every run as in GW-BASIC
+pub const DEFAULT_RANDOM_SEED: u64 = 12345;
+
+/// RND repeats after this many numbers; RANDOMIZE seeds are taken modulo it
+pub const RANDOM_MODULUS: u64 = 233280;
+
+/// Execution context and state
+#[derive(Debug, Clone)]
+pub struct ExecutionContext {
+    pub variables: HashMap<String, VariableInfo>,
+    pub arrays: HashMap<String, Vec<Value>>,
+    pub functions: HashMap<String, FunctionDefinition>,
+    pub for_loops: Vec<ForLoop>,
+    pub gosub_stack: Vec<usize>,
+    pub data: Vec<Value>,
+    pub data_pointer: usize,
+    pub random_seed: u64,
+    pub array_base: usize,
+    pub input_variable: Option<String>,
+    pub type_declarations: HashMap<String, VariableType>, // Range -> Type mappings
+    pub turtle: String,           // turtle that movement statements apply to
+    pub pens_up: HashSet<String>, // turtles after PENUP; they move without drawing
+    pub events: EventTable,
+    pub event_frames: Vec<(usize, EventSource)>, // GOSUB depth of each running event handler
+    pub error_handler: Option<usize>,            // ON ERROR GOTO line
+    pub last_error: Option<TrappedError>,
+    pub in_error_handler: bool, // between a trapped error and its RESUME
+    pub raised_error: Option<u16>, // ERROR n's number, until it is trapped
+}
+
+impl Default for ExecutionContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionContext {
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+            arrays: HashMap::new(),
+            functions: HashMap::new(),
+            for_loops: Vec::new(),
+            gosub_stack: Vec::new(),
+            data: Vec::new(),
+            data_pointer: 0,
+            random_seed: DEFAULT_RANDOM_SEED,
+            array_base: 0,
+            input_variable: None,
+            type_declarations: HashMap::new(),
+            turtle: DEFAULT_TURTLE.to_string(),
+            pens_up: HashSet::new(),
+            events: EventTable::new(),
+            event_frames: Vec::new(),
+            error_handler: None,
+            last_error: None,
+            in_error_handler: false,
+            raised_error: None,
+        }
+    }
+
+    /// Extract base name and type from variable name with declaration character
+    pub fn parse_variable_name(name: &str) -> (String, Option<VariableType>) {
+        let name = name.to_uppercase();
+        if let Some(stripped) = name.strip_suffix('%') {
+            (stripped.to_string(), Some(VariableType::Integer))
+        } else if let Some(stripped) = name.strip_suffix('!') {
+            (stripped.to_string(), Some(VariableType::Single))
+        } else if let Some(stripped) = name.strip_suffix('#') {
+            (stripped.to_string(), Some(VariableType::Double))
+        } else if let Some(stripped) = name.strip_suffix('$') {
+            (stripped.to_string(), Some(VariableType::String))
+        } else {
+            (name, None)
+        }
+    }
+
+    /// Get the declared type for a variable, considering both declaration characters and DEF statements
+    pub fn get_variable_type(&self, name: &str) -> VariableType {
+        let (base_name, explicit_type) = Self::parse_variable_name(name);
+
+        // Explicit type declaration character takes precedence
+        if let Some(var_type) = explicit_type {
+            return var_type;
+        }
+
+        // Check DEF statements for the first character
+        if let Some(first_char) = base_name.chars().next() {
+            let range_key = first_char.to_string();
+            if let Some(def_type) = self.type_declarations.get(&range_key) {
+                return def_type.clone();
+            }
+        }
+
+        // Default to Single (GW-BASIC default for undeclared variables)
+        VariableType::Single
+    }
+
+    /// Get or create a variable with proper typing
+    pub fn get_variable(&mut self, name: &str) -> &mut VariableInfo {
+        let var_type = self.get_variable_type(name);
+        let (base_name, _) = Self::parse_variable_name(name);
+
+        self.variables
+            .entry(base_name)
+            .or_insert_with(|| VariableInfo {
+                value: match var_type {
+                    VariableType::Integer => Value::Integer(0),
+                    VariableType::Single => Value::Single(0.0),
+                    VariableType::Double => Value::Double(0.0),
+                    VariableType::String => Value::String(String::new()),
+                },
+                declared_type: var_type,
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ForLoop {
+    pub variable: String,
+    pub end_value: f64,
+    pub step_value: f64,
+    pub line_index: usize,
+    pub body_start: usize,
+}
+
+/// Execution results
+#[derive(Debug, Clone)]
+pub enum ExecutionResult {
+    Complete {
+        output: String,
+        graphics_commands: Vec<TurtleCommand>,
+    },
+    NeedInput {
+        variable: String,
+        prompt: String,
+        partial_output: String,
+        partial_graphics: Vec<TurtleCommand>,
+    },
+    Break {
+        line: usize, // source line of the next statement to execute
+        partial_output: String,
+        partial_graphics: Vec<TurtleCommand>,
+    },
+    NeedPermission {
+        permission: Permission, // the statement waiting for it runs once answered
+        partial_output: String,
+        partial_graphics: Vec<TurtleCommand>,
+    },
+    Polling {
+        // INKEY$ found no key waiting; `run` again carries on
+        partial_output: String,
+        partial_graphics: Vec<TurtleCommand>,
+    },
+    Error(String),
+}
+
+/// How far the debugger should run before pausing again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepMode {
+    Continue, // run until a breakpoint or the end of the program
+    StepInto, // pause before the very next statement, entering GOSUB targets
+    StepOver, // pause at the next statement in the current subroutine level
+    StepOut,  // run until the current subroutine RETURNs to its caller
+}
+
+/// Outcome of one ASSERT as it ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionResult {
+    pub line: usize, // source line (1-based)
+    pub passed: bool,
+    pub message: String,
+}
+
+/// Execution statistics for one source line, collected when profiling is enabled
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineProfile {
+    pub line: usize, // source line (1-based)
+    pub hits: u64,
+    pub total_time: Duration,
+}
+
+impl LineProfile {
+    /// Average time spent per execution of the line
+    pub fn average_time(&self) -> Duration {
+        if self.hits == 0 {
+            Duration::ZERO
+        } else {
+            self.total_time / self.hits as u32
+        }
+    }
+}
+
+/// Turtle instruction emitted by a program; the IDE applies them in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphicsCommand {
+    Forward(f32), // draws a line
+    Back(f32),    // draws a line
+    Move(f32),    // along the heading without drawing; negative moves backwards
+    Left(f32),    // degrees
+    Right(f32),   // degrees
+    Turn(f32),    // degrees, same direction as Right
+    Home,
+    SetXY(f32, f32),  // draws a line
+    MoveTo(f32, f32), // without drawing
+    SetHeading(f32),  // degrees clockwise from the home heading
+    ClearScreen,      // erase the canvas and send the turtle home
+    SetPenColor(u8, u8, u8),
+    SetPenSize(f32),
+    Fill,          // flood the closed shape around the turtle with its pen colour
+    Arc(f32, f32), // radius, degrees clockwise from the heading; centred on the turtle
+    Label(String), // text at the turtle, along its heading
+    SetLabelSize(f32),
+    SetBackground(u8, u8, u8), // the whole canvas, not just this turtle
+    ShowTurtle(bool),          // false hides the turtle's cursor; drawing carries on
+}
+
+/// Name of the turtle programs draw with until TURTLE picks another one
+pub const DEFAULT_TURTLE: &str = "MAIN";
+
+/// A graphics command tagged with the turtle that carries it out
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurtleCommand {
+    pub turtle: String,
+    pub command: GraphicsCommand,
+}
+
+impl From<GraphicsCommand> for TurtleCommand {
+    /// A command for the default turtle
+    fn from(command: GraphicsCommand) -> Self {
+        Self {
+            turtle: DEFAULT_TURTLE.to_string(),
+            command,
+        }
+    }
+}
+
+/// Where something is in the source, 1-based, with the column counted in
+/// characters
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// Read as part of an error, "line 2, column 13" says it best, and keeps the
+// "line N" that included files and QBasic programs are mapped back by
+impl fmt::Debug for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Error types
+#[derive(Debug, Clone)]
+pub enum InterpreterError {
+    ParseError { code: ErrorCode, message: String },
+    RuntimeError(String),
+    Runtime { code: ErrorCode, message: String }, // a runtime error with a code of its own
+    TypeError(String),
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    DivisionByZero,
+    IndexOutOfBounds,
+    Located(Box<InterpreterError>, Position), // an error and where in the source it is
+}
+
+impl InterpreterError {
+    /// A parse error of the kind `code` names
+    pub fn parse(code: ErrorCode, message: impl Into<String>) -> Self {
+        InterpreterError::ParseError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// A parse error no more particular code fits
+    pub fn syntax(message: impl Into<String>) -> Self {
+        Self::parse(ErrorCode::Syntax, message)
+    }
+
+    /// A runtime error of the kind `code` names
+    pub fn runtime(code: ErrorCode, message: impl Into<String>) -> Self {
+        InterpreterError::Runtime {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The stable code of the kind of error this is
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            InterpreterError::ParseError { code, .. } | InterpreterError::Runtime { code, .. } => {
+                *code
+            }
+            InterpreterError::RuntimeError(_) => ErrorCode::Runtime,
+            InterpreterError::TypeError(_) => ErrorCode::TypeMismatch,
+            InterpreterError::UndefinedVariable(_) => ErrorCode::UndefinedVariable,
+            InterpreterError::UndefinedFunction(_) => ErrorCode::UndefinedFunction,
+            InterpreterError::DivisionByZero => ErrorCode::DivisionByZero,
+            InterpreterError::IndexOutOfBounds => ErrorCode::IndexOutOfBounds,
+            InterpreterError::Located(err, _) => err.code(),
+        }
+    }
+
+    /// What usually fixes an error of this kind, if there is a general answer
+    pub fn hint(&self) -> Option<&'static str> {
+        self.code().hint()
+    }
+
+    /// This error at `position`, unless it already says where it is
+    pub fn at(self, position: Position) -> Self {
+        match self {
+            InterpreterError::Located(..) => self,
+            err => InterpreterError::Located(Box::new(err), position),
+        }
+    }
+
+    /// Where in the source the error is, when that is known
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            InterpreterError::Located(_, position) => Some(*position),
+            _ => None,
+        }
+    }
+
+    /// The error itself, without where it is
+    pub fn inner(&self) -> &InterpreterError {
+        match self {
+            InterpreterError::Located(err, _) => err.inner(),
+            err => err,
+        }
+    }
+
+    /// The error as a sentence, without the variant's name or its position
+    pub fn message(&self) -> String {
+        match self {
+            InterpreterError::Located(err, _) => err.message(),
+            InterpreterError::ParseError { message, .. }
+            | InterpreterError::Runtime { message, .. }
+            | InterpreterError::RuntimeError(message)
+            | InterpreterError::TypeError(message) => message.clone(),
+            InterpreterError::UndefinedVariable(name) => format!("Undefined variable {}", name),
+            InterpreterError::UndefinedFunction(name) => format!("Undefined function {}", name),
+            InterpreterError::DivisionByZero => "Division by zero".to_string(),
+            InterpreterError::IndexOutOfBounds => "Index out of bounds".to_string(),
+        }
+    }
+}
+
+// "E201 Division by zero (line 8, column 4)": the code to look up, the
+// sentence, and where, when that is known
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.code().code(), self.message())?;
+        if let Some(position) = self.position() {
+            write!(f, " ({:?})", position)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+//! Stable codes for the errors a program can stop with. The code names the
+//! kind of mistake, so it stays the same when the wording of a message is
+//! improved or translated: graders and scripts match on `E104`, not on
+//! "Unexpected token in statement". Each code has a section in
+//! docs/ERROR_CODES.md, which `doc_link` points at.
+//!
+//! E1xx codes are found before the program runs, E2xx codes while it runs.
+//! A code, once given out, keeps its meaning.
+
+/// What kind of mistake an error is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Syntax,
+    UnexpectedCharacter,
+    UnterminatedString,
+    InvalidNumber,
+    UnexpectedToken,
+    ExpectedToken,
+    ExpectedEndOfStatement,
+    UnclosedBlock,
+    BlockMismatch,
+    BadSub,
+    NoRoomForBlocks,
+    IncludeFailed,
+    IncludeCycle,
+    UnsetVariable,
+    Runtime,
+    DivisionByZero,
+    IndexOutOfBounds,
+    TypeMismatch,
+    UndefinedVariable,
+    UndefinedFunction,
+    ReturnWithoutGosub,
+    NextWithoutFor,
+    ResumeWithoutError,
+    BadFileNumber,
+    FileNotFound,
+    BadFileMode,
+    FileAlreadyOpen,
+    InputPastEnd,
+    BadFileName,
+    FileAccess,
+    BadRecordNumber,
+    FieldOverflow,
+    FileAlreadyExists,
+    PathNotFound,
+    PermissionDenied,
+    UndefinedLineNumber,
+    ExecutionTimeout,
+    ArgumentCount,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 38] = [
+        ErrorCode::Syntax,
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::UnterminatedString,
+        ErrorCode::InvalidNumber,
+        ErrorCode::UnexpectedToken,
+        ErrorCode::ExpectedToken,
+        ErrorCode::ExpectedEndOfStatement,
+        ErrorCode::UnclosedBlock,
+        ErrorCode::BlockMismatch,
+        ErrorCode::BadSub,
+        ErrorCode::NoRoomForBlocks,
+        ErrorCode::IncludeFailed,
+        ErrorCode::IncludeCycle,
+        ErrorCode::UnsetVariable,
+        ErrorCode::Runtime,
+        ErrorCode::DivisionByZero,
+        ErrorCode::IndexOutOfBounds,
+        ErrorCode::TypeMismatch,
+        ErrorCode::UndefinedVariable,
+        ErrorCode::UndefinedFunction,
+        ErrorCode::ReturnWithoutGosub,
+        ErrorCode::NextWithoutFor,
+        ErrorCode::ResumeWithoutError,
+        ErrorCode::BadFileNumber,
+        ErrorCode::FileNotFound,
+        ErrorCode::BadFileMode,
+        ErrorCode::FileAlreadyOpen,
+        ErrorCode::InputPastEnd,
+        ErrorCode::BadFileName,
+        ErrorCode::FileAccess,
+        ErrorCode::BadRecordNumber,
+        ErrorCode::FieldOverflow,
+        ErrorCode::FileAlreadyExists,
+        ErrorCode::PathNotFound,
+        ErrorCode::PermissionDenied,
+        ErrorCode::UndefinedLineNumber,
+        ErrorCode::ExecutionTimeout,
+        ErrorCode::ArgumentCount,
+    ];
+
+    /// The code itself, e.g. `E104`
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "E100",
+            ErrorCode::UnexpectedCharacter => "E101",
+            ErrorCode::UnterminatedString => "E102",
+            ErrorCode::InvalidNumber => "E103",
+            ErrorCode::UnexpectedToken => "E104",
+            ErrorCode::ExpectedToken => "E105",
+            ErrorCode::ExpectedEndOfStatement => "E106",
+            ErrorCode::UnclosedBlock => "E107",
+            ErrorCode::BlockMismatch => "E108",
+            ErrorCode::BadSub => "E109",
+            ErrorCode::NoRoomForBlocks => "E110",
+            ErrorCode::IncludeFailed => "E111",
+            ErrorCode::IncludeCycle => "E112",
+            ErrorCode::UnsetVariable => "E113",
+            ErrorCode::Runtime => "E200",
+            ErrorCode::DivisionByZero => "E201",
+            ErrorCode::IndexOutOfBounds => "E202",
+            ErrorCode::TypeMismatch => "E203",
+            ErrorCode::UndefinedVariable => "E204",
+            ErrorCode::UndefinedFunction => "E205",
+            ErrorCode::ReturnWithoutGosub => "E206",
+            ErrorCode::NextWithoutFor => "E207",
+            ErrorCode::ResumeWithoutError => "E208",
+            ErrorCode::BadFileNumber => "E209",
+            ErrorCode::FileNotFound => "E210",
+            ErrorCode::BadFileMode => "E211",
+            ErrorCode::FileAlreadyOpen => "E212",
+            ErrorCode::InputPastEnd => "E213",
+            ErrorCode::BadFileName => "E214",
+            ErrorCode::FileAccess => "E215",
+            ErrorCode::BadRecordNumber => "E216",
+            ErrorCode::FieldOverflow => "E217",
+            ErrorCode::FileAlreadyExists => "E218",
+            ErrorCode::PathNotFound => "E219",
+            ErrorCode::PermissionDenied => "E220",
+            ErrorCode::UndefinedLineNumber => "E221",
+            ErrorCode::ExecutionTimeout => "E222",
+            ErrorCode::ArgumentCount => "E223",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|error| error.code().eq_ignore_ascii_case(code))
+    }
+
+    /// Whether this is found before the program runs (an E1xx code)
+    pub fn is_parse(self) -> bool {
+        self.code().starts_with("E1")
+    }
+
+    /// A few words naming the mistake, the same for every error of the kind
+    pub fn title(self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "Syntax error",
+            ErrorCode::UnexpectedCharacter => "Unexpected character",
+            ErrorCode::UnterminatedString => "String without a closing quote",
+            ErrorCode::InvalidNumber => "Invalid number",
+            ErrorCode::UnexpectedToken => "Unexpected word or symbol",
+            ErrorCode::ExpectedToken => "Something is missing",
+            ErrorCode::ExpectedEndOfStatement => "Statement doesn't end where it should",
+            ErrorCode::UnclosedBlock => "Block is never closed",
+            ErrorCode::BlockMismatch => "Block end without its start",
+            ErrorCode::BadSub => "SUB written or called wrongly",
+            ErrorCode::NoRoomForBlocks => "No line numbers left for blocks",
+            ErrorCode::IncludeFailed => "INCLUDE file can't be read",
+            ErrorCode::IncludeCycle => "INCLUDE files include each other",
+            ErrorCode::UnsetVariable => "Variable never given a value",
+            ErrorCode::Runtime => "Runtime error",
+            ErrorCode::DivisionByZero => "Division by zero",
+            ErrorCode::IndexOutOfBounds => "Index out of bounds",
+            ErrorCode::TypeMismatch => "Type mismatch",
+            ErrorCode::UndefinedVariable => "Undefined variable",
+            ErrorCode::UndefinedFunction => "Undefined function",
+            ErrorCode::ReturnWithoutGosub => "RETURN without GOSUB",
+            ErrorCode::NextWithoutFor => "NEXT without FOR",
+            ErrorCode::ResumeWithoutError => "RESUME without error",
+            ErrorCode::BadFileNumber => "Bad file number",
+            ErrorCode::FileNotFound => "File not found",
+            ErrorCode::BadFileMode => "Bad file mode",
+            ErrorCode::FileAlreadyOpen => "File already open",
+            ErrorCode::InputPastEnd => "Input past end",
+            ErrorCode::BadFileName => "Bad file name",
+            ErrorCode::FileAccess => "File can't be used",
+            ErrorCode::BadRecordNumber => "Bad record number",
+            ErrorCode::FieldOverflow => "Field overflow",
+            ErrorCode::FileAlreadyExists => "File already exists",
+            ErrorCode::PathNotFound => "Path not found",
+            ErrorCode::PermissionDenied => "Permission denied",
+            ErrorCode::UndefinedLineNumber => "Undefined line number",
+            ErrorCode::ExecutionTimeout => "Program ran too long",
+            ErrorCode::ArgumentCount => "Wrong number of arguments",
+        }
+    }
+
+    /// What usually fixes it, when there is something general to say
+    pub fn hint(self) -> Option<&'static str> {
+        Some(match self {
+            ErrorCode::UnexpectedCharacter => {
+                "Only letters, digits, quotes and BASIC's operators can appear outside a string"
+            }
+            ErrorCode::UnterminatedString => "Every string needs a \" at its end, on the same line",
+            ErrorCode::UnexpectedToken => {
+                "Check the spelling of the keyword that starts the statement"
+            }
+            ErrorCode::ExpectedToken => "Compare the statement with its form in the keyword help",
+            ErrorCode::ExpectedEndOfStatement => {
+                "Put a colon between two statements on the same line"
+            }
+            ErrorCode::UnclosedBlock => {
+                "Close each IF, DO, WHILE and SUB with its END IF, LOOP, WEND or END SUB"
+            }
+            ErrorCode::BlockMismatch => "Check that the block this ends was opened above it",
+            ErrorCode::IncludeFailed => {
+                "INCLUDE looks for the file next to the program; check its name"
+            }
+            ErrorCode::UnsetVariable => "Give the variable a value first, or check its spelling",
+            ErrorCode::DivisionByZero => "Check that the number you divide by isn't 0 first",
+            ErrorCode::IndexOutOfBounds => "DIM the array large enough for every index used",
+            ErrorCode::TypeMismatch => {
+                "Text and numbers can't be mixed; use VAL or STR$ to convert"
+            }
+            ErrorCode::UndefinedFunction => "Define the function with DEF FN before it is used",
+            ErrorCode::ReturnWithoutGosub => {
+                "Put an END before the subroutine so the program can't run into it"
+            }
+            ErrorCode::NextWithoutFor => {
+                "Check that each NEXT names the variable of the FOR it closes"
+            }
+            ErrorCode::ResumeWithoutError => {
+                "Put an END before the ON ERROR handler so the program can't run into it"
+            }
+            ErrorCode::BadFileNumber => "Check the number matches the one the file was OPENed AS",
+            ErrorCode::FileNotFound => "A file must be written FOR OUTPUT before it can be read",
+            ErrorCode::BadFileMode => {
+                "OPEN the file FOR INPUT to read it, FOR OUTPUT or APPEND to write it"
+            }
+            ErrorCode::FileAlreadyOpen => "CLOSE the file, or use another number",
+            ErrorCode::InputPastEnd => "Test EOF before each read",
+            ErrorCode::BadFileName => "Name a file in the program's folder; `..` can't leave it",
+            ErrorCode::BadRecordNumber => {
+                "Records are numbered from 1, and OPEN's LEN must be 1 to 65535"
+            }
+            ErrorCode::FieldOverflow => "Make OPEN's LEN at least the total of the FIELD widths",
+            ErrorCode::FileAlreadyExists => "KILL the old file first, or pick another name",
+            ErrorCode::PathNotFound => "MKDIR the folder first; FILES lists what is there",
+            ErrorCode::PermissionDenied => "Run the program again and choose Allow when asked",
+            ErrorCode::UndefinedLineNumber => {
+                "Check the number after GOTO, GOSUB or RESUME is a line in the program"
+            }
+            ErrorCode::ExecutionTimeout => {
+                "A loop that never ends is the usual cause; check that its condition changes"
+            }
+            ErrorCode::ArgumentCount => {
+                "Check how many values the function takes in the keyword help"
+            }
+            _ => return None,
+        })
+    }
+
+    /// The number GW-BASIC gives the error, which ERR returns. Errors found
+    /// before the program runs are all its "Syntax error", 2.
+    pub fn gw_basic_number(self) -> u16 {
+        match self {
+            ErrorCode::NextWithoutFor => 1,
+            ErrorCode::ReturnWithoutGosub => 3,
+            ErrorCode::UndefinedLineNumber => 8,
+            ErrorCode::Runtime
+            | ErrorCode::UndefinedVariable
+            | ErrorCode::ExecutionTimeout
+            | ErrorCode::ArgumentCount => 5, // Illegal function call
+            ErrorCode::IndexOutOfBounds => 9,
+            ErrorCode::DivisionByZero => 11,
+            ErrorCode::TypeMismatch => 13,
+            ErrorCode::UndefinedFunction => 18,
+            ErrorCode::ResumeWithoutError => 20,
+            ErrorCode::IncludeFailed | ErrorCode::FileNotFound => 53,
+            ErrorCode::BadFileNumber => 52,
+            ErrorCode::BadFileMode => 54,
+            ErrorCode::FileAlreadyOpen => 55,
+            ErrorCode::InputPastEnd => 62,
+            ErrorCode::BadFileName => 64,
+            ErrorCode::FileAccess => 75,
+            ErrorCode::BadRecordNumber => 63,
+            ErrorCode::FieldOverflow => 50,
+            ErrorCode::FileAlreadyExists => 58,
+            ErrorCode::PathNotFound => 76,
+            ErrorCode::PermissionDenied => 70,
+            _ => 2,
+        }
+    }
+
+    /// Where the code is explained, relative to the repository
+    pub fn doc_link(self) -> String {
+        format!("docs/ERROR_CODES.md#{}", self.code().to_ascii_lowercase())
+    }
+}
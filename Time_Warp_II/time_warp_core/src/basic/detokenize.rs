@@ -0,0 +1,385 @@
+//! Reading GW-BASIC programs saved in its tokenized binary form, the default
+//! for `SAVE "FILE"` without `,A`. Each keyword is stored as a byte or two
+//! and numbers in binary; this turns such a file back into the listing
+//! GW-BASIC's `LIST` would show, which TW BASIC runs as a line-numbered
+//! program. Files saved with `,P` are decrypted first.
+
+/// First byte of a tokenized program
+const PLAIN: u8 = 0xFF;
+/// First byte of a program saved with `,P`
+const PROTECTED: u8 = 0xFE;
+
+/// Keywords stored as one byte, from 0x81
+const ONE_BYTE: [&str; 116] = [
+    "END",
+    "FOR",
+    "NEXT",
+    "DATA",
+    "INPUT",
+    "DIM",
+    "READ",
+    "LET",
+    "GOTO",
+    "RUN",
+    "IF",
+    "RESTORE",
+    "GOSUB",
+    "RETURN",
+    "REM",
+    "STOP",
+    "PRINT",
+    "CLEAR",
+    "LIST",
+    "NEW",
+    "ON",
+    "WAIT",
+    "DEF",
+    "POKE",
+    "CONT",
+    "",
+    "",
+    "OUT",
+    "LPRINT",
+    "LLIST",
+    "",
+    "WIDTH",
+    "ELSE",
+    "TRON",
+    "TROFF",
+    "SWAP",
+    "ERASE",
+    "EDIT",
+    "ERROR",
+    "RESUME",
+    "DELETE",
+    "AUTO",
+    "RENUM",
+    "DEFSTR",
+    "DEFINT",
+    "DEFSNG",
+    "DEFDBL",
+    "LINE",
+    "WHILE",
+    "WEND",
+    "CALL",
+    "",
+    "",
+    "",
+    "WRITE",
+    "OPTION",
+    "RANDOMIZE",
+    "OPEN",
+    "CLOSE",
+    "LOAD",
+    "MERGE",
+    "SAVE",
+    "COLOR",
+    "CLS",
+    "MOTOR",
+    "BSAVE",
+    "BLOAD",
+    "SOUND",
+    "BEEP",
+    "PSET",
+    "PRESET",
+    "SCREEN",
+    "KEY",
+    "LOCATE",
+    "",
+    "TO",
+    "THEN",
+    "TAB(",
+    "STEP",
+    "USR",
+    "FN",
+    "SPC(",
+    "NOT",
+    "ERL",
+    "ERR",
+    "STRING$",
+    "USING",
+    "INSTR",
+    "'",
+    "VARPTR",
+    "CSRLIN",
+    "POINT",
+    "OFF",
+    "INKEY$",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    ">",
+    "=",
+    "<",
+    "+",
+    "-",
+    "*",
+    "/",
+    "^",
+    "AND",
+    "OR",
+    "XOR",
+    "EQV",
+    "IMP",
+    "MOD",
+    "\\",
+];
+
+/// Keywords stored as 0xFD and a byte from 0x81
+const FD_PREFIXED: [&str; 11] = [
+    "CVI", "CVS", "CVD", "MKI$", "MKS$", "MKD$", "", "", "", "", "EXTERR",
+];
+
+/// Keywords stored as 0xFE and a byte from 0x81
+const FE_PREFIXED: [&str; 40] = [
+    "FILES", "FIELD", "SYSTEM", "NAME", "LSET", "RSET", "KILL", "PUT", "GET", "RESET", "COMMON",
+    "CHAIN", "DATE$", "TIME$", "PAINT", "COM", "CIRCLE", "DRAW", "PLAY", "TIMER", "ERDEV", "IOCTL",
+    "CHDIR", "MKDIR", "RMDIR", "SHELL", "ENVIRON", "VIEW", "WINDOW", "PMAP", "PALETTE", "LCOPY",
+    "CALLS", "", "", "NOISE", "PCOPY", "TERM", "LOCK", "UNLOCK",
+];
+
+/// Functions stored as 0xFF and a byte from 0x81
+const FF_PREFIXED: [&str; 37] = [
+    "LEFT$", "RIGHT$", "MID$", "SGN", "INT", "ABS", "SQR", "RND", "SIN", "LOG", "EXP", "COS",
+    "TAN", "ATN", "FRE", "INP", "POS", "LEN", "STR$", "VAL", "ASC", "CHR$", "PEEK", "SPACE$",
+    "OCT$", "HEX$", "LPOS", "CINT", "CSNG", "CDBL", "FIX", "PEN", "STICK", "STRIG", "EOF", "LOC",
+    "LOF",
+];
+
+const REM: u8 = 0x8F;
+const DATA: u8 = 0x84;
+const ELSE: u8 = 0xA1;
+const WHILE: u8 = 0xB1;
+const APOSTROPHE: u8 = 0xD9;
+const PLUS: u8 = 0xE9;
+
+/// Whether `bytes` are a tokenized GW-BASIC program rather than text
+pub fn is_tokenized(bytes: &[u8]) -> bool {
+    matches!(bytes.first(), Some(&PLAIN) | Some(&PROTECTED))
+}
+
+/// Undo `SAVE ,P`. GW-BASIC mixes each byte with two repeating keys of 13
+/// and 11 bytes, taken from its LOG and EXP polynomial constants.
+pub fn unprotect(bytes: &[u8]) -> Vec<u8> {
+    const LOG_KEY: [u8; 13] = [
+        0x9A, 0xF7, 0x19, 0x83, 0x24, 0x63, 0x43, 0x83, 0x75, 0xCD, 0x8D, 0x84, 0xA9,
+    ];
+    const EXP_KEY: [u8; 11] = [
+        0x7C, 0x88, 0x59, 0x74, 0xE0, 0x97, 0x26, 0x77, 0xC4, 0x1D, 0x1E,
+    ];
+    let (mut n1, mut n2) = (11u8, 13u8); // count down from the key lengths
+    bytes
+        .iter()
+        .map(|&byte| {
+            let plain =
+                (byte.wrapping_sub(n1) ^ LOG_KEY[n2 as usize - 1] ^ EXP_KEY[n1 as usize - 1])
+                    .wrapping_add(n2);
+            n1 = if n1 == 1 { 11 } else { n1 - 1 };
+            n2 = if n2 == 1 { 13 } else { n2 - 1 };
+            plain
+        })
+        .collect()
+}
+
+/// A 4-byte Microsoft Binary Format single-precision number
+fn mbf_single(bytes: &[u8]) -> f64 {
+    mbf(&bytes[..3], bytes[3])
+}
+
+/// An 8-byte Microsoft Binary Format double-precision number
+fn mbf_double(bytes: &[u8]) -> f64 {
+    mbf(&bytes[..7], bytes[7])
+}
+
+/// MBF keeps the exponent (biased by 128) in its last byte and the sign in
+/// the top bit of the mantissa, whose leading 1 is implied
+fn mbf(mantissa: &[u8], exponent: u8) -> f64 {
+    if exponent == 0 {
+        return 0.0;
+    }
+    let top = mantissa.len() - 1;
+    let negative = mantissa[top] & 0x80 != 0;
+    let value = mantissa
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| {
+            let byte = if i == top { byte | 0x80 } else { byte };
+            byte as f64 * 256f64.powi(i as i32)
+        })
+        .sum::<f64>();
+    let bits = 8 * mantissa.len() as i32;
+    let value = value * 2f64.powi(exponent as i32 - 128 - bits);
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
+/// A number as `LIST` shows it: whole numbers without a point, and at most
+/// 7 significant digits for single precision, 16 for double
+fn format_number(value: f64, digits: usize) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+    let text = format!("{:.*e}", digits - 1, value);
+    let parsed: f64 = text.parse().unwrap_or(value);
+    let text = format!("{}", parsed);
+    text.strip_prefix("0.")
+        .map_or(text.clone(), |rest| format!(".{}", rest))
+}
+
+/// Turn a tokenized program back into source, one numbered line per line
+pub fn detokenize(bytes: &[u8]) -> Result<String, String> {
+    let body = match bytes.first() {
+        Some(&PLAIN) => bytes[1..].to_vec(),
+        Some(&PROTECTED) => unprotect(&bytes[1..]),
+        _ => return Err("not a tokenized GW-BASIC program".to_string()),
+    };
+    let mut source = String::new();
+    let mut at = 0;
+    // Each line: a link to the next (0 after the last), the line number,
+    // the tokens, and a 0 byte
+    while let Some(header) = body.get(at..at + 4) {
+        if header[..2] == [0, 0] {
+            break;
+        }
+        let number = u16::from_le_bytes([header[2], header[3]]);
+        let (text, length) =
+            detokenize_line(&body[at + 4..]).map_err(|err| format!("line {}: {}", number, err))?;
+        source.push_str(&format!("{} {}\n", number, text));
+        at += 4 + length;
+    }
+    Ok(source)
+}
+
+/// One line's tokens as text, and how many bytes they took up to and
+/// including the 0 that ends them. Numbers are binary and may hold 0 bytes,
+/// so the end is only found by reading the tokens in order.
+fn detokenize_line(tokens: &[u8]) -> Result<(String, usize), String> {
+    let cut_off = || "the line is cut off".to_string();
+    let mut text = String::new();
+    let mut at = 0;
+    let mut in_string = false;
+    let mut in_data = false;
+    loop {
+        let byte = *tokens.get(at).ok_or_else(cut_off)?;
+        at += 1;
+        if byte == 0 {
+            return Ok((text, at));
+        }
+        // Strings and DATA items are stored as typed
+        if in_string {
+            in_string = byte != b'"';
+            text.push(byte as char);
+            continue;
+        }
+        if in_data && byte != b':' {
+            in_string = byte == b'"';
+            text.push(byte as char);
+            continue;
+        }
+        match byte {
+            b'"' => {
+                in_string = true;
+                text.push('"');
+            }
+            b':' if tokens.get(at) == Some(&ELSE) => {} // ELSE is stored as ":ELSE"
+            b':' if tokens.get(at..at + 2) == Some(&[REM, APOSTROPHE]) => {} // and ' as ":REM'"
+            b':' => {
+                in_data = false;
+                text.push(':');
+            }
+            // A remark runs to the end of the line as typed
+            REM => {
+                let start = if tokens.get(at) == Some(&APOSTROPHE) {
+                    text.push('\'');
+                    at + 1
+                } else {
+                    text.push_str("REM");
+                    at
+                };
+                let end = tokens[start..]
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .map(|length| start + length)
+                    .ok_or_else(cut_off)?;
+                text.extend(tokens[start..end].iter().map(|&byte| byte as char));
+                return Ok((text, end + 1));
+            }
+            DATA => {
+                in_data = true;
+                text.push_str("DATA");
+            }
+            WHILE => {
+                text.push_str("WHILE");
+                // WHILE is stored followed by a + that LIST leaves out
+                if tokens.get(at) == Some(&PLUS) {
+                    at += 1;
+                }
+            }
+            0x0B | 0x0C | 0x0E | 0x1C => {
+                let value = tokens.get(at..at + 2).ok_or_else(cut_off)?;
+                let value = u16::from_le_bytes([value[0], value[1]]);
+                at += 2;
+                text.push_str(&match byte {
+                    0x0B => format!("&O{:o}", value),
+                    0x0C => format!("&H{:X}", value),
+                    0x0E => value.to_string(),
+                    _ => (value as i16).to_string(),
+                });
+            }
+            // A line number GW-BASIC swapped for a memory address while
+            // running; its target line's number follows it in memory
+            0x0D => {
+                tokens.get(at..at + 2).ok_or_else(cut_off)?;
+                at += 2;
+            }
+            0x0F => {
+                let value = tokens.get(at).ok_or_else(cut_off)?;
+                text.push_str(&value.to_string());
+                at += 1;
+            }
+            0x11..=0x1B => text.push_str(&(byte - 0x11).to_string()),
+            0x1D => {
+                let value = tokens.get(at..at + 4).ok_or_else(cut_off)?;
+                text.push_str(&format_number(mbf_single(value), 7));
+                at += 4;
+            }
+            0x1F => {
+                let value = tokens.get(at..at + 8).ok_or_else(cut_off)?;
+                text.push_str(&format_number(mbf_double(value), 16));
+                text.push('#');
+                at += 8;
+            }
+            0xFD..=0xFF => {
+                let table: &[&str] = match byte {
+                    0xFD => &FD_PREFIXED,
+                    0xFE => &FE_PREFIXED,
+                    _ => &FF_PREFIXED,
+                };
+                let code = *tokens.get(at).ok_or_else(cut_off)?;
+                at += 1;
+                text.push_str(keyword(table, code).ok_or_else(|| unknown(&[byte, code]))?);
+            }
+            0x81.. => text.push_str(keyword(&ONE_BYTE, byte).ok_or_else(|| unknown(&[byte]))?),
+            _ => text.push(byte as char),
+        }
+    }
+}
+
+fn keyword<'a>(table: &[&'a str], code: u8) -> Option<&'a str> {
+    code.checked_sub(0x81)
+        .and_then(|index| table.get(index as usize))
+        .copied()
+        .filter(|word| !word.is_empty())
+}
+
+fn unknown(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!("unknown token {}", hex.join(" "))
+}
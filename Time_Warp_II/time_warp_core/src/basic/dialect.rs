@@ -0,0 +1,664 @@
+//! The dialects TW BASIC can read besides its own.
+//!
+//! The QBasic dialect: programs written the way QBasic tutorials write them,
+//! without line numbers and with block structure (`IF ... THEN` on a line
+//! of its own up to `END IF`, `DO ... LOOP`, `WHILE ... WEND` and
+//! `SUB ... END SUB`).
+//!
+//! The interpreter runs a flat list of statements joined by GOTO and GOSUB,
+//! so each block is rewritten into those before the program is parsed: the
+//! lines a block jumps to are given line numbers above the program's own,
+//! and every rewritten line remembers the line it came from so the editor,
+//! debugger and error messages keep pointing at what the student wrote.
+//!
+//! SUB parameters are copied into variables of the same name before the
+//! GOSUB, so they are passed by value and share the program's variables;
+//! a SUB can't call itself.
+//!
+//! The Applesoft and Commodore 64 profiles run classic 8-bit listings: the
+//! turtle words are ordinary names, PRINT wraps on a 40-column screen with
+//! the machine's print zones, integer variables hold -32768 to 32767, and
+//! on the Commodore 64 CHR$ speaks PETSCII, so `PRINT CHR$(147)` clears
+//! the screen.
+
+use crate::basic::ast::InterpreterError;
+use crate::basic::codes::ErrorCode;
+use std::collections::HashMap;
+
+/// Which BASIC a program is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    #[default]
+    TwBasic,
+    QBasic,
+    Applesoft,
+    Commodore64,
+}
+
+/// The text screen of an 8-bit machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Screen {
+    pub columns: usize, // PRINT carries on at the start of the next line
+    pub zone: usize,    // a comma in PRINT moves to the next multiple of this
+}
+
+/// Code for CHR$ results and CLS that clears the text printed so far
+pub const CLEAR_SCREEN: char = '\x0C';
+
+/// The range of `%` variables on the 8-bit machines
+pub const INTEGER_RANGE: std::ops::RangeInclusive<f64> = -32768.0..=32767.0;
+
+impl Dialect {
+    pub const ALL: [Dialect; 4] = [
+        Dialect::TwBasic,
+        Dialect::QBasic,
+        Dialect::Applesoft,
+        Dialect::Commodore64,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::TwBasic => "TW BASIC",
+            Dialect::QBasic => "QBasic",
+            Dialect::Applesoft => "Applesoft",
+            Dialect::Commodore64 => "Commodore 64",
+        }
+    }
+
+    /// The machine's text screen, for the 8-bit profiles
+    pub fn screen(self) -> Option<Screen> {
+        match self {
+            Dialect::TwBasic | Dialect::QBasic => None,
+            Dialect::Applesoft => Some(Screen {
+                columns: 40,
+                zone: 16,
+            }),
+            Dialect::Commodore64 => Some(Screen {
+                columns: 40,
+                zone: 10,
+            }),
+        }
+    }
+
+    /// What `CHR$(code)` gives
+    pub fn chr(self, code: u8) -> String {
+        match self {
+            Dialect::Commodore64 => petscii(code),
+            _ => (code as char).to_string(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|dialect| dialect.name() == name)
+    }
+}
+
+/// A PETSCII character as text: RETURN is a new line, 147 clears the
+/// screen, the other control codes (colours, cursor movement, reverse
+/// video) print nothing, and graphics characters print as a shaded block
+fn petscii(code: u8) -> String {
+    match code {
+        13 | 141 => "\n".to_string(),
+        147 => CLEAR_SCREEN.to_string(),
+        0..=31 | 128..=159 => String::new(),
+        92 => "£".to_string(),
+        94 => "↑".to_string(),
+        95 => "←".to_string(),
+        32..=93 => (code as char).to_string(),
+        _ => "▒".to_string(),
+    }
+}
+
+/// A QBasic program rewritten as line-numbered TW BASIC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Translated {
+    pub code: String,
+    pub lines: Vec<usize>, // the source line (1-based) of each line of `code`
+}
+
+impl Translated {
+    /// The source line that line `line` (1-based) of `code` came from
+    pub fn source_line(&self, line: usize) -> usize {
+        line.checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .copied()
+            .unwrap_or(line)
+    }
+
+    /// `err` with the line it names, if any, turned back into a source line
+    pub fn locate(&self, err: InterpreterError) -> InterpreterError {
+        // Columns of a rewritten line don't match what was written, so only
+        // the line is kept
+        if let InterpreterError::Located(err, position) = err {
+            return InterpreterError::parse(
+                err.code(),
+                format!(
+                    "{} at line {}",
+                    err.message(),
+                    self.source_line(position.line)
+                ),
+            );
+        }
+        let InterpreterError::ParseError { code, message } = err else {
+            return err;
+        };
+        let Some(found) = message.find("line ") else {
+            return InterpreterError::parse(code, message);
+        };
+        let (before, after) = message.split_at(found + "line ".len());
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match after[..digits].parse() {
+            Ok(line) => InterpreterError::parse(
+                code,
+                format!("{}{}{}", before, self.source_line(line), &after[digits..]),
+            ),
+            Err(_) => InterpreterError::parse(code, message),
+        }
+    }
+}
+
+/// A place a rewritten line jumps to, found once the block around it ends
+type Label = usize;
+
+/// A rewritten line: its text, with the line numbers of labels filled in
+/// once they are all known
+enum Piece {
+    Text(String),
+    Line(Label),
+}
+
+struct Line {
+    source: usize,         // 1-based
+    number: Option<usize>, // the line number the program gave it
+    pieces: Vec<Piece>,
+}
+
+/// A block that has been opened and not yet closed
+enum Block {
+    If {
+        line: usize,
+        next: Option<Label>, // where a false condition goes; None after ELSE
+        end: Label,
+    },
+    Do {
+        line: usize,
+        top: Label,
+        end: Label,
+    },
+    While {
+        line: usize,
+        top: Label,
+        end: Label,
+    },
+    Sub {
+        line: usize,
+        skip: Label, // past END SUB, for a program that runs into the SUB
+    },
+}
+
+impl Block {
+    fn describe(&self) -> String {
+        match self {
+            Block::If { line, .. } => format!("IF at line {} has no END IF", line),
+            Block::Do { line, .. } => format!("DO at line {} has no LOOP", line),
+            Block::While { line, .. } => format!("WHILE at line {} has no WEND", line),
+            Block::Sub { line, .. } => format!("SUB at line {} has no END SUB", line),
+        }
+    }
+}
+
+struct Sub {
+    body: Label,
+    parameters: Vec<String>,
+}
+
+struct Translator {
+    lines: Vec<Line>,
+    labels: Vec<Option<usize>>, // label -> index into `lines`
+    blocks: Vec<Block>,
+    subs: HashMap<String, Sub>, // by upper-case name
+}
+
+/// Rewrite a QBasic program as TW BASIC
+pub fn translate(code: &str) -> Result<Translated, InterpreterError> {
+    let mut translator = Translator {
+        lines: Vec::new(),
+        labels: Vec::new(),
+        blocks: Vec::new(),
+        subs: HashMap::new(),
+    };
+    // SUBs are usually called before they are written, so find them first
+    for line in code.lines() {
+        let (_, statement) = split_line(line);
+        if let Some(header) = keyword(statement, "SUB") {
+            let (name, parameters) = sub_header(header);
+            let key = name.to_ascii_uppercase();
+            if name.is_empty() || translator.subs.contains_key(&key) {
+                let problem = if name.is_empty() {
+                    "has no name"
+                } else {
+                    "is written twice"
+                };
+                return Err(InterpreterError::parse(
+                    ErrorCode::BadSub,
+                    format!("SUB {} {}", name, problem),
+                ));
+            }
+            let body = translator.new_label();
+            translator.subs.insert(key, Sub { body, parameters });
+        }
+    }
+    for (index, line) in code.lines().enumerate() {
+        translator.line(index + 1, line)?;
+    }
+    if let Some(block) = translator.blocks.last() {
+        return Err(InterpreterError::parse(
+            ErrorCode::UnclosedBlock,
+            block.describe(),
+        ));
+    }
+    translator.finish()
+}
+
+impl Translator {
+    fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    /// Point `label` at the next line to be written
+    fn bind(&mut self, label: Label) {
+        self.labels[label] = Some(self.lines.len());
+    }
+
+    fn emit(&mut self, source: usize, pieces: Vec<Piece>) {
+        self.lines.push(Line {
+            source,
+            number: None,
+            pieces,
+        });
+    }
+
+    fn line(&mut self, source: usize, text: &str) -> Result<(), InterpreterError> {
+        let (number, statement) = split_line(text);
+        let first = self.lines.len();
+        self.statement(source, statement)?;
+        if self.lines.len() == first {
+            self.emit(source, Vec::new());
+        }
+        self.lines[first].number = number;
+        Ok(())
+    }
+
+    fn statement(&mut self, source: usize, statement: &str) -> Result<(), InterpreterError> {
+        let error = |message: &str| {
+            Err(InterpreterError::parse(
+                ErrorCode::BlockMismatch,
+                format!("{} at line {}", message, source),
+            ))
+        };
+        let words: Vec<String> = statement
+            .split_whitespace()
+            .map(str::to_ascii_uppercase)
+            .collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            [] | ["REM", ..] | ["DECLARE", ..] => {}
+            ["IF", .., "THEN"] => {
+                let condition = between(statement, "IF", "THEN");
+                let (next, end) = (self.new_label(), self.new_label());
+                self.emit(source, jump_unless(condition, next));
+                self.blocks.push(Block::If {
+                    line: source,
+                    next: Some(next),
+                    end,
+                });
+            }
+            ["ELSEIF", .., "THEN"] | ["ELSE"] => {
+                let Some(Block::If { next, end, .. }) = self.blocks.last() else {
+                    return error(&format!("{} without IF", words[0]));
+                };
+                let (Some(skipped), end) = (*next, *end) else {
+                    return error(&format!("{} after ELSE", words[0]));
+                };
+                // The branch before ends by jumping past the rest
+                self.emit(source, vec![text("GOTO "), Piece::Line(end)]);
+                self.bind(skipped);
+                let next = if words[0] == "ELSE" {
+                    None
+                } else {
+                    let next = self.new_label();
+                    let condition = between(statement, "ELSEIF", "THEN");
+                    self.emit(source, jump_unless(condition, next));
+                    Some(next)
+                };
+                if let Some(Block::If { next: open, .. }) = self.blocks.last_mut() {
+                    *open = next;
+                }
+            }
+            ["END", "IF"] | ["ENDIF"] => {
+                let Some(Block::If { next, end, .. }) = self.blocks.pop() else {
+                    return error("END IF without IF");
+                };
+                if let Some(next) = next {
+                    self.bind(next);
+                }
+                self.bind(end);
+            }
+            ["DO", ..] => {
+                let (top, end) = (self.new_label(), self.new_label());
+                self.bind(top);
+                match words.get(1) {
+                    None => {}
+                    Some(&"WHILE") => self.emit(source, jump_unless(after(statement, 2), end)),
+                    Some(&"UNTIL") => self.emit(source, jump_if(after(statement, 2), end)),
+                    Some(_) => return error("Expected WHILE or UNTIL after DO"),
+                }
+                self.blocks.push(Block::Do {
+                    line: source,
+                    top,
+                    end,
+                });
+            }
+            ["LOOP", ..] => {
+                let Some(Block::Do { top, end, .. }) = self.blocks.pop() else {
+                    return error("LOOP without DO");
+                };
+                match words.get(1) {
+                    None => self.emit(source, vec![text("GOTO "), Piece::Line(top)]),
+                    Some(&"WHILE") => self.emit(source, jump_if(after(statement, 2), top)),
+                    Some(&"UNTIL") => self.emit(source, jump_unless(after(statement, 2), top)),
+                    Some(_) => return error("Expected WHILE or UNTIL after LOOP"),
+                }
+                self.bind(end);
+            }
+            ["WHILE", _, ..] => {
+                let (top, end) = (self.new_label(), self.new_label());
+                self.bind(top);
+                self.emit(source, jump_unless(after(statement, 1), end));
+                self.blocks.push(Block::While {
+                    line: source,
+                    top,
+                    end,
+                });
+            }
+            ["WEND"] => {
+                let Some(Block::While { top, end, .. }) = self.blocks.pop() else {
+                    return error("WEND without WHILE");
+                };
+                self.emit(source, vec![text("GOTO "), Piece::Line(top)]);
+                self.bind(end);
+            }
+            ["EXIT", "DO"] => {
+                let end = self.blocks.iter().rev().find_map(|block| match block {
+                    Block::Do { end, .. } => Some(*end),
+                    _ => None,
+                });
+                let Some(end) = end else {
+                    return error("EXIT DO outside a DO loop");
+                };
+                self.emit(source, vec![text("GOTO "), Piece::Line(end)]);
+            }
+            ["SUB", ..] => {
+                if !self.blocks.is_empty() {
+                    return error("SUB inside another block");
+                }
+                let (name, _) = sub_header(&statement[3..]);
+                let body = self.subs[&name.to_ascii_uppercase()].body;
+                let skip = self.new_label();
+                self.emit(source, vec![text("GOTO "), Piece::Line(skip)]);
+                self.bind(body);
+                self.blocks.push(Block::Sub { line: source, skip });
+            }
+            ["END", "SUB"] => {
+                let Some(Block::Sub { skip, .. }) = self.blocks.pop() else {
+                    return error("END SUB without SUB");
+                };
+                self.emit(source, vec![text("RETURN")]);
+                self.bind(skip);
+            }
+            ["EXIT", "SUB"] => {
+                if !self
+                    .blocks
+                    .iter()
+                    .any(|block| matches!(block, Block::Sub { .. }))
+                {
+                    return error("EXIT SUB outside a SUB");
+                }
+                self.emit(source, vec![text("RETURN")]);
+            }
+            _ => match self.call(statement) {
+                Some(call) => {
+                    let pieces = call.map_err(|message| {
+                        InterpreterError::parse(
+                            ErrorCode::BadSub,
+                            format!("{} at line {}", message, source),
+                        )
+                    })?;
+                    self.emit(source, pieces);
+                }
+                None => self.emit(source, vec![text(statement)]),
+            },
+        }
+        Ok(())
+    }
+
+    /// `CALL Name(arguments)` or `Name arguments` as assignments to the
+    /// SUB's parameters and a GOSUB; None if `statement` calls no SUB
+    fn call(&self, statement: &str) -> Option<Result<Vec<Piece>, String>> {
+        let (statement, bracketed) = match keyword(statement, "CALL") {
+            Some(rest) => (rest, true),
+            None => (statement, false),
+        };
+        let name_length = statement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(statement.len());
+        let (name, rest) = statement.split_at(name_length);
+        let sub = self.subs.get(&name.to_ascii_uppercase())?;
+        let rest = rest.trim();
+        if !bracketed && rest.starts_with('=') {
+            return None; // an assignment to a variable named like the SUB
+        }
+        let arguments = if bracketed {
+            match rest
+                .strip_prefix('(')
+                .and_then(|rest| rest.strip_suffix(')'))
+            {
+                Some(inside) => inside,
+                None if rest.is_empty() => "",
+                None => return Some(Err(format!("Expected ( after CALL {}", name))),
+            }
+        } else {
+            rest
+        };
+        let arguments = split_arguments(arguments);
+        if arguments.len() != sub.parameters.len() {
+            return Some(Err(format!(
+                "{} takes {} argument(s), not {}",
+                name,
+                sub.parameters.len(),
+                arguments.len()
+            )));
+        }
+        let mut pieces: Vec<Piece> = sub
+            .parameters
+            .iter()
+            .zip(arguments)
+            .map(|(parameter, argument)| text(&format!("{} = {} : ", parameter, argument)))
+            .collect();
+        pieces.push(text("GOSUB "));
+        pieces.push(Piece::Line(sub.body));
+        Some(Ok(pieces))
+    }
+
+    /// Number the lines that are jumped to and write the program out
+    fn finish(mut self) -> Result<Translated, InterpreterError> {
+        // A label past the last line needs a line to stand on
+        if self.labels.contains(&Some(self.lines.len())) {
+            let source = self.lines.last().map_or(1, |line| line.source);
+            self.emit(source, Vec::new());
+        }
+        let mut next_number = self
+            .lines
+            .iter()
+            .filter_map(|line| line.number)
+            .max()
+            .map_or(1, |number| number + 1);
+        for index in self.labels.iter().flatten() {
+            let line = &mut self.lines[*index];
+            if line.number.is_none() {
+                line.number = Some(next_number);
+                next_number += 1;
+            }
+        }
+        if next_number > MAX_LINE_NUMBER + 1 {
+            return Err(InterpreterError::parse(
+                ErrorCode::NoRoomForBlocks,
+                format!(
+                    "The program's line numbers leave no room for its blocks; keep them below {}",
+                    MAX_LINE_NUMBER
+                ),
+            ));
+        }
+        let mut code = String::new();
+        for line in &self.lines {
+            if let Some(number) = line.number {
+                code.push_str(&format!("{} ", number));
+                if line.pieces.is_empty() {
+                    code.push_str("REM");
+                }
+            }
+            for piece in &line.pieces {
+                match piece {
+                    Piece::Text(text) => code.push_str(text),
+                    Piece::Line(label) => {
+                        let index = self.labels[*label].expect("every label is bound");
+                        let number = self.lines[index].number.expect("targets are numbered");
+                        code.push_str(&number.to_string());
+                    }
+                }
+            }
+            code.push('\n');
+        }
+        Ok(Translated {
+            code,
+            lines: self.lines.iter().map(|line| line.source).collect(),
+        })
+    }
+}
+
+/// The largest line number the parser accepts
+const MAX_LINE_NUMBER: usize = 65529;
+
+fn text(text: &str) -> Piece {
+    Piece::Text(text.to_string())
+}
+
+fn jump_if(condition: &str, label: Label) -> Vec<Piece> {
+    vec![
+        text(&format!("IF {} THEN GOTO ", condition)),
+        Piece::Line(label),
+    ]
+}
+
+fn jump_unless(condition: &str, label: Label) -> Vec<Piece> {
+    jump_if(&format!("NOT ({})", condition), label)
+}
+
+/// A line's number, if it has one, and its statement without the number
+/// or a `'` comment
+fn split_line(line: &str) -> (Option<usize>, &str) {
+    let mut in_string = false;
+    let end = line
+        .char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                in_string = !in_string;
+            }
+            c == '\'' && !in_string
+        })
+        .map_or(line.len(), |(at, _)| at);
+    let line = line[..end].trim();
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match line[..digits].parse() {
+        Ok(number) if line[digits..].is_empty() || line[digits..].starts_with(' ') => {
+            (Some(number), line[digits..].trim())
+        }
+        _ => (None, line),
+    }
+}
+
+/// What follows `word` when `statement` starts with it as a whole word
+fn keyword<'a>(statement: &'a str, word: &str) -> Option<&'a str> {
+    let head = statement.get(..word.len())?;
+    let rest = &statement[word.len()..];
+    (head.eq_ignore_ascii_case(word)
+        && (rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == '(')))
+    .then(|| rest.trim())
+}
+
+/// The text between the first word of `statement` and its last word
+fn between<'a>(statement: &'a str, first: &str, last: &str) -> &'a str {
+    statement[first.len()..statement.len() - last.len()].trim()
+}
+
+/// `statement` after its first `words` words
+fn after(statement: &str, words: usize) -> &str {
+    let mut rest = statement.trim_start();
+    for _ in 0..words {
+        rest = rest
+            .trim_start_matches(|c: char| !c.is_whitespace())
+            .trim_start();
+    }
+    rest
+}
+
+/// The name and parameters of a SUB from what follows `SUB`
+fn sub_header(header: &str) -> (String, Vec<String>) {
+    let header = header.trim();
+    let header = match header.len().checked_sub(" STATIC".len()) {
+        Some(at)
+            if header
+                .get(at..)
+                .is_some_and(|end| end.eq_ignore_ascii_case(" STATIC")) =>
+        {
+            header[..at].trim()
+        }
+        _ => header,
+    };
+    let (name, parameters) = match header.split_once('(') {
+        Some((name, parameters)) => (name, parameters.trim_end().trim_end_matches(')')),
+        None => (header, ""),
+    };
+    let parameters = split_arguments(parameters)
+        .into_iter()
+        .map(|parameter| {
+            // `n AS INTEGER` and array parameters like `list()`
+            let name = parameter.split_whitespace().next().unwrap_or_default();
+            name.trim_end_matches("()").to_string()
+        })
+        .collect();
+    (name.trim().to_string(), parameters)
+}
+
+/// Comma-separated items, leaving commas inside brackets and strings alone
+fn split_arguments(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let (mut items, mut start, mut depth, mut in_string) = (Vec::new(), 0, 0, false);
+    for (at, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                items.push(text[start..at].trim());
+                start = at + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(text[start..].trim());
+    items
+}
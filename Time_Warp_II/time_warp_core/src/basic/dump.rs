@@ -0,0 +1,476 @@
+//! The parsed form of a TW BASIC program as JSON, for tools and tests that
+//! look at a student's program structurally instead of by its text.
+//!
+//! Every node is an object whose `"type"` names the `Statement` or
+//! `Expression` variant; the other keys are the variant's fields.
+//! Top-level statements also carry their 1-based source `"line"`, and the
+//! BASIC `"line_number"` when the line starts with one.
+
+use std::collections::HashMap;
+
+use crate::basic::ast::{
+    Expression, FolderAction, Justify, PrintChannel, PrintSeparator, Program, ResumeTarget,
+    SelectCase, Statement,
+};
+use crate::basic::{InterpreterError, Parser, Tokenizer};
+use crate::json::Json;
+
+/// Tokenize and parse `code`, then dump it
+pub fn parse_to_json(code: &str) -> Result<Json, InterpreterError> {
+    let tokens = Tokenizer::new(code).tokenize()?;
+    let program = Parser::new(tokens).parse_program()?;
+    Ok(program_to_json(&program))
+}
+
+pub fn program_to_json(program: &Program) -> Json {
+    let numbers: HashMap<usize, usize> = program
+        .line_numbers
+        .iter()
+        .map(|(&number, &index)| (index, number))
+        .collect();
+    let statements = program
+        .statements
+        .iter()
+        .enumerate()
+        .map(|(index, statement)| {
+            let mut node = statement_to_json(statement);
+            if let Json::Object(fields) = &mut node {
+                let mut position = Vec::new();
+                if let Some(&line) = program.statement_lines.get(index) {
+                    position.push(("line".to_string(), line.into()));
+                }
+                if let Some(&number) = numbers.get(&index) {
+                    position.push(("line_number".to_string(), number.into()));
+                }
+                fields.splice(1..1, position);
+            }
+            node
+        })
+        .collect();
+    Json::object([("statements", Json::Array(statements))])
+}
+
+pub fn statement_to_json(statement: &Statement) -> Json {
+    match statement {
+        Statement::Let {
+            variable,
+            expression,
+        } => node(
+            "Let",
+            [
+                ("variable", variable.as_str().into()),
+                ("expression", expression_to_json(expression)),
+            ],
+        ),
+        Statement::Print {
+            channel,
+            using,
+            expressions,
+            separators,
+        } => node(
+            match channel {
+                PrintChannel::Printer => "Lprint",
+                _ => "Print",
+            },
+            [
+                (
+                    "file",
+                    match channel {
+                        PrintChannel::File(number) => expression_to_json(number),
+                        _ => Json::Null,
+                    },
+                ),
+                ("using", optional_expression(using)),
+                ("expressions", expressions_to_json(expressions)),
+                (
+                    "separators",
+                    Json::Array(separators.iter().map(separator_to_json).collect()),
+                ),
+            ],
+        ),
+        Statement::Input { prompt, variable } => node(
+            "Input",
+            [
+                ("prompt", optional(prompt.as_deref().map(Json::from))),
+                ("variable", variable.as_str().into()),
+            ],
+        ),
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => node(
+            "If",
+            [
+                ("condition", expression_to_json(condition)),
+                ("then_branch", statements_to_json(then_branch)),
+                (
+                    "else_branch",
+                    optional(else_branch.as_deref().map(statements_to_json)),
+                ),
+            ],
+        ),
+        Statement::For {
+            variable,
+            start,
+            end,
+            step,
+            body,
+        } => node(
+            "For",
+            [
+                ("variable", variable.as_str().into()),
+                ("start", expression_to_json(start)),
+                ("end", expression_to_json(end)),
+                ("step", optional_expression(step)),
+                ("body", statements_to_json(body)),
+            ],
+        ),
+        Statement::Next { variable } => node(
+            "Next",
+            [("variable", optional(variable.as_deref().map(Json::from)))],
+        ),
+        Statement::Goto { line } => node("Goto", [("line", expression_to_json(line))]),
+        Statement::Gosub { line } => node("Gosub", [("line", expression_to_json(line))]),
+        Statement::Return => node("Return", []),
+        Statement::End => node("End", []),
+        Statement::Stop => node("Stop", []),
+        Statement::Assert { condition, message } => node(
+            "Assert",
+            [
+                ("condition", expression_to_json(condition)),
+                ("message", optional_expression(message)),
+            ],
+        ),
+        Statement::Randomize { seed } => node("Randomize", [("seed", optional_expression(seed))]),
+        Statement::Rem(text) => node("Rem", [("text", text.as_str().into())]),
+        Statement::Dim { arrays } => node(
+            "Dim",
+            [(
+                "arrays",
+                Json::Array(
+                    arrays
+                        .iter()
+                        .map(|(name, dimensions)| {
+                            Json::object([
+                                ("name", name.as_str().into()),
+                                ("dimensions", expressions_to_json(dimensions)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            )],
+        ),
+        Statement::Def {
+            name,
+            parameters,
+            body,
+        } => node(
+            "Def",
+            [
+                ("name", name.as_str().into()),
+                ("parameters", strings_to_json(parameters)),
+                ("body", expression_to_json(body)),
+            ],
+        ),
+        Statement::Clear => node("Clear", []),
+        Statement::Cls => node("Cls", []),
+        Statement::Writeln { expression } => {
+            node("Writeln", [("expression", expression_to_json(expression))])
+        }
+        Statement::Printx { expression } => {
+            node("Printx", [("expression", expression_to_json(expression))])
+        }
+        Statement::Select { expression, cases } => node(
+            "Select",
+            [
+                ("expression", expression_to_json(expression)),
+                (
+                    "cases",
+                    Json::Array(cases.iter().map(case_to_json).collect()),
+                ),
+            ],
+        ),
+        Statement::Forward { distance } => {
+            node("Forward", [("distance", expression_to_json(distance))])
+        }
+        Statement::Back { distance } => node("Back", [("distance", expression_to_json(distance))]),
+        Statement::TurnLeft { angle } => node("TurnLeft", [("angle", expression_to_json(angle))]),
+        Statement::TurnRight { angle } => node("TurnRight", [("angle", expression_to_json(angle))]),
+        Statement::Penup => node("Penup", []),
+        Statement::Pendown => node("Pendown", []),
+        Statement::Home => node("Home", []),
+        Statement::Setxy { x, y } => node(
+            "Setxy",
+            [("x", expression_to_json(x)), ("y", expression_to_json(y))],
+        ),
+        Statement::SetHeading { angle } => {
+            node("SetHeading", [("angle", expression_to_json(angle))])
+        }
+        Statement::ClearScreen => node("ClearScreen", []),
+        Statement::Turtle { name } => node("Turtle", [("name", expression_to_json(name))]),
+        Statement::Turn { angle } => node("Turn", [("angle", expression_to_json(angle))]),
+        Statement::SetPenColor { components } => node(
+            "SetPenColor",
+            [("components", expressions_to_json(components))],
+        ),
+        Statement::PenSize { size } => node("PenSize", [("size", expression_to_json(size))]),
+        Statement::SetBackground { components } => node(
+            "SetBackground",
+            [("components", expressions_to_json(components))],
+        ),
+        Statement::Fill => node("Fill", []),
+        Statement::HideTurtle => node("HideTurtle", []),
+        Statement::ShowTurtle => node("ShowTurtle", []),
+        Statement::Arc { radius, angle } => node(
+            "Arc",
+            [
+                ("radius", expression_to_json(radius)),
+                ("angle", expression_to_json(angle)),
+            ],
+        ),
+        Statement::Circle { radius } => node("Circle", [("radius", expression_to_json(radius))]),
+        Statement::Label { text } => node("Label", [("text", expression_to_json(text))]),
+        Statement::LabelSize { size } => node("LabelSize", [("size", expression_to_json(size))]),
+        Statement::DefInt { ranges } => node("DefInt", [("ranges", strings_to_json(ranges))]),
+        Statement::DefSng { ranges } => node("DefSng", [("ranges", strings_to_json(ranges))]),
+        Statement::DefDbl { ranges } => node("DefDbl", [("ranges", strings_to_json(ranges))]),
+        Statement::DefStr { ranges } => node("DefStr", [("ranges", strings_to_json(ranges))]),
+        Statement::OnEvent {
+            event,
+            argument,
+            line,
+        } => node(
+            "OnEvent",
+            [
+                ("event", format!("{:?}", event).into()),
+                ("argument", optional_expression(argument)),
+                ("line", expression_to_json(line)),
+            ],
+        ),
+        Statement::EventControl {
+            event,
+            argument,
+            state,
+        } => node(
+            "EventControl",
+            [
+                ("event", format!("{:?}", event).into()),
+                ("argument", optional_expression(argument)),
+                ("state", format!("{:?}", state).into()),
+            ],
+        ),
+        Statement::OnError { line } => node("OnError", [("line", expression_to_json(line))]),
+        Statement::Error { number } => node("Error", [("number", expression_to_json(number))]),
+        Statement::Resume { target } => match target {
+            ResumeTarget::Line(line) => node("Resume", [("line", expression_to_json(line))]),
+            ResumeTarget::Retry => node("Resume", []),
+            ResumeTarget::Next => node("ResumeNext", []),
+        },
+        Statement::Open {
+            file,
+            mode,
+            number,
+            length,
+        } => node(
+            "Open",
+            [
+                ("file", expression_to_json(file)),
+                ("mode", format!("{:?}", mode).into()),
+                ("number", expression_to_json(number)),
+                ("length", optional_expression(length)),
+            ],
+        ),
+        Statement::Close { numbers } => node("Close", [("numbers", expressions_to_json(numbers))]),
+        Statement::Write { file, expressions } => node(
+            "Write",
+            [
+                ("file", optional_expression(file)),
+                ("expressions", expressions_to_json(expressions)),
+            ],
+        ),
+        Statement::FileInput { number, variables } => node(
+            "FileInput",
+            [
+                ("number", expression_to_json(number)),
+                ("variables", strings_to_json(variables)),
+            ],
+        ),
+        Statement::LineInput { number, variable } => node(
+            "LineInput",
+            [
+                ("number", expression_to_json(number)),
+                ("variable", variable.as_str().into()),
+            ],
+        ),
+        Statement::Field { number, fields } => node(
+            "Field",
+            [
+                ("number", expression_to_json(number)),
+                (
+                    "fields",
+                    Json::Array(
+                        fields
+                            .iter()
+                            .map(|(width, variable)| {
+                                Json::object([
+                                    ("width", expression_to_json(width)),
+                                    ("variable", variable.as_str().into()),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ],
+        ),
+        Statement::SetField {
+            justify,
+            variable,
+            expression,
+        } => node(
+            match justify {
+                Justify::Left => "Lset",
+                Justify::Right => "Rset",
+            },
+            [
+                ("variable", variable.as_str().into()),
+                ("expression", expression_to_json(expression)),
+            ],
+        ),
+        Statement::Get { number, record } => node(
+            "Get",
+            [
+                ("number", expression_to_json(number)),
+                ("record", optional_expression(record)),
+            ],
+        ),
+        Statement::Put { number, record } => node(
+            "Put",
+            [
+                ("number", expression_to_json(number)),
+                ("record", optional_expression(record)),
+            ],
+        ),
+        Statement::Files { pattern } => node("Files", [("pattern", optional_expression(pattern))]),
+        Statement::Kill { pattern } => node("Kill", [("pattern", expression_to_json(pattern))]),
+        Statement::Name { from, to } => node(
+            "Name",
+            [
+                ("from", expression_to_json(from)),
+                ("to", expression_to_json(to)),
+            ],
+        ),
+        Statement::DefSeg { segment } => {
+            node("DefSeg", [("segment", optional_expression(segment))])
+        }
+        Statement::Poke { address, value } => node(
+            "Poke",
+            [
+                ("address", expression_to_json(address)),
+                ("value", expression_to_json(value)),
+            ],
+        ),
+        Statement::Sleep { seconds } => node("Sleep", [("seconds", optional_expression(seconds))]),
+        Statement::Wait { ticks } => node("Wait", [("ticks", expression_to_json(ticks))]),
+        Statement::Play { music } => node("Play", [("music", expression_to_json(music))]),
+        Statement::Beep => node("Beep", []),
+        Statement::Sound { frequency, ticks } => node(
+            "Sound",
+            [
+                ("frequency", expression_to_json(frequency)),
+                ("ticks", expression_to_json(ticks)),
+            ],
+        ),
+        Statement::Folder { action, dir } => node(
+            match action {
+                FolderAction::Make => "Mkdir",
+                FolderAction::Change => "Chdir",
+                FolderAction::Remove => "Rmdir",
+            },
+            [("dir", expression_to_json(dir))],
+        ),
+    }
+}
+
+pub fn expression_to_json(expression: &Expression) -> Json {
+    match expression {
+        Expression::Number(value) => node("Number", [("value", Json::Number(*value))]),
+        Expression::String(value) => node("String", [("value", value.as_str().into())]),
+        Expression::Variable(name) => node("Variable", [("name", name.as_str().into())]),
+        Expression::BinaryOp {
+            left,
+            operator,
+            right,
+        } => node(
+            "BinaryOp",
+            [
+                ("operator", format!("{:?}", operator).into()),
+                ("left", expression_to_json(left)),
+                ("right", expression_to_json(right)),
+            ],
+        ),
+        Expression::UnaryOp { operator, operand } => node(
+            "UnaryOp",
+            [
+                ("operator", format!("{:?}", operator).into()),
+                ("operand", expression_to_json(operand)),
+            ],
+        ),
+        Expression::FunctionCall { name, arguments } => node(
+            "FunctionCall",
+            [
+                ("name", name.as_str().into()),
+                ("arguments", expressions_to_json(arguments)),
+            ],
+        ),
+        Expression::ArrayAccess { name, index } => node(
+            "ArrayAccess",
+            [
+                ("name", name.as_str().into()),
+                ("index", expression_to_json(index)),
+            ],
+        ),
+    }
+}
+
+/// An object tagged with the variant it came from
+fn node<const N: usize>(kind: &str, fields: [(&str, Json); N]) -> Json {
+    let mut object = vec![("type".to_string(), kind.into())];
+    object.extend(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value)),
+    );
+    Json::Object(object)
+}
+
+fn optional(value: Option<Json>) -> Json {
+    value.unwrap_or(Json::Null)
+}
+
+fn optional_expression(expression: &Option<Expression>) -> Json {
+    optional(expression.as_ref().map(expression_to_json))
+}
+
+fn statements_to_json(statements: &[Statement]) -> Json {
+    Json::Array(statements.iter().map(statement_to_json).collect())
+}
+
+fn expressions_to_json(expressions: &[Expression]) -> Json {
+    Json::Array(expressions.iter().map(expression_to_json).collect())
+}
+
+fn strings_to_json(strings: &[String]) -> Json {
+    Json::Array(strings.iter().map(|text| text.as_str().into()).collect())
+}
+
+fn separator_to_json(separator: &PrintSeparator) -> Json {
+    format!("{:?}", separator).into()
+}
+
+fn case_to_json(case: &SelectCase) -> Json {
+    Json::object([
+        ("value", optional_expression(&case.value)),
+        ("statements", statements_to_json(&case.statements)),
+    ])
+}
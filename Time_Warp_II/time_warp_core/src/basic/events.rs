@@ -0,0 +1,158 @@
+//! Event trapping for ON KEY(n), ON TIMER(n), ON PLAY(n) and ON MOUSE GOSUB.
+//! The IDE (or anything else driving the interpreter) raises events whenever
+//! they happen; the interpreter only runs handlers between statements.
+
+use crate::basic::ast::TrapState;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Highest key number KEY(n) accepts: 1-10 function keys, 11-14 cursor
+/// up/left/right/down, 15-20 user-defined keys
+pub const MAX_KEY: u8 = 20;
+
+/// Something a program can trap. The order is the dispatch priority when
+/// several events are waiting: lower keys first, then timer, play and mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventSource {
+    Key(u8),
+    Timer,
+    Play,
+    Mouse,
+}
+
+#[derive(Debug, Clone)]
+struct Trap {
+    handler: Option<usize>, // BASIC line number of the GOSUB target
+    state: TrapState,
+    pending: bool,
+    in_handler: bool, // trapping is suspended until the handler RETURNs
+}
+
+impl Default for Trap {
+    fn default() -> Self {
+        Self {
+            handler: None,
+            state: TrapState::Off,
+            pending: false,
+            in_handler: false,
+        }
+    }
+}
+
+/// Handlers, ON/OFF/STOP states and pending events for every source
+#[derive(Debug, Clone, Default)]
+pub struct EventTable {
+    traps: BTreeMap<EventSource, Trap>,
+    timer_interval: Option<Duration>,
+    timer_due: Option<Instant>,
+    play_threshold: usize,
+}
+
+impl EventTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Install (or with `None` remove) the handler for `source`
+    pub fn set_handler(&mut self, source: EventSource, line: Option<usize>) {
+        self.traps.entry(source).or_default().handler = line;
+    }
+
+    /// ON TIMER(n): fire every `interval`, counted from the next TIMER ON
+    pub fn set_timer_interval(&mut self, interval: Duration, now: Instant) {
+        self.timer_interval = Some(interval);
+        if self.state(EventSource::Timer) != TrapState::Off {
+            self.timer_due = Some(now + interval);
+        }
+    }
+
+    /// ON PLAY(n): music code raises Play when fewer than `notes` remain queued
+    pub fn set_play_threshold(&mut self, notes: usize) {
+        self.play_threshold = notes;
+    }
+
+    pub fn play_threshold(&self) -> usize {
+        self.play_threshold
+    }
+
+    pub fn state(&self, source: EventSource) -> TrapState {
+        self.traps
+            .get(&source)
+            .map_or(TrapState::Off, |trap| trap.state)
+    }
+
+    /// KEY(n) ON / OFF / STOP. Turning a trap off forgets an event that was
+    /// being held by STOP.
+    pub fn set_state(&mut self, source: EventSource, state: TrapState, now: Instant) {
+        let trap = self.traps.entry(source).or_default();
+        if state == TrapState::Off {
+            trap.pending = false;
+        }
+        let was_off = trap.state == TrapState::Off;
+        trap.state = state;
+
+        if source == EventSource::Timer {
+            if state == TrapState::Off {
+                self.timer_due = None;
+            } else if was_off {
+                self.timer_due = self.timer_interval.map(|interval| now + interval);
+            }
+        }
+    }
+
+    /// Whether the program waits on something from outside it: a key,
+    /// timer, music or mouse trap with a handler that isn't off
+    pub fn listening(&self) -> bool {
+        self.traps
+            .values()
+            .any(|trap| trap.state != TrapState::Off && trap.handler.is_some())
+    }
+
+    /// Record that `source` happened. Ignored while its trap is off.
+    pub fn raise(&mut self, source: EventSource) {
+        if let Some(trap) = self.traps.get_mut(&source) {
+            if trap.state != TrapState::Off {
+                trap.pending = true;
+            }
+        }
+    }
+
+    /// Raise the timer event if its interval has run out by `now`
+    fn poll_timer(&mut self, now: Instant) {
+        let (Some(due), Some(interval)) = (self.timer_due, self.timer_interval) else {
+            return;
+        };
+        if now >= due {
+            self.raise(EventSource::Timer);
+            // After a long statement, don't fire a burst of missed ticks
+            let next = due + interval;
+            self.timer_due = Some(if next <= now { now + interval } else { next });
+        }
+    }
+
+    /// The next event whose handler should run now, if any. The event's trap
+    /// stays suspended until `finish` is called for it.
+    pub fn next_dispatch(&mut self, now: Instant) -> Option<(EventSource, usize)> {
+        self.poll_timer(now);
+        let (&source, trap) = self.traps.iter_mut().find(|(_, trap)| {
+            trap.pending
+                && trap.state == TrapState::On
+                && !trap.in_handler
+                && trap.handler.is_some()
+        })?;
+        trap.pending = false;
+        trap.in_handler = true;
+        trap.handler.map(|line| (source, line))
+    }
+
+    /// The handler for `source` returned; trapping resumes
+    pub fn finish(&mut self, source: EventSource) {
+        if let Some(trap) = self.traps.get_mut(&source) {
+            trap.in_handler = false;
+        }
+    }
+}
@@ -0,0 +1,811 @@
+//! Files for OPEN and CLOSE: sequential files read and written a line at a
+//! time with PRINT #, WRITE #, INPUT # and LINE INPUT #, and random files of
+//! fixed-length records, which FIELD lays out, LSET and RSET fill in and GET
+//! and PUT read and write. All of a program's files live in one folder, its
+//! sandbox: names are taken relative to it and can't reach outside it, so a
+//! program can only touch the files it made itself. FILES, KILL, NAME,
+//! MKDIR, CHDIR and RMDIR manage the sandbox, which looks to the program
+//! like a disk of its own: `/` is its top folder, and `\` works as in
+//! GW-BASIC.
+//!
+//! A record holds bytes, and BASIC strings hold characters, so each byte is
+//! the character with its code: CHR$(200) is the byte 200.
+
+use crate::basic::ast::{FileMode, InterpreterError, Justify, Permission};
+use crate::basic::codes::ErrorCode;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// File numbers run from 1 to this, as in GW-BASIC's `#1` to `#15`
+pub const MAX_FILE_NUMBER: u32 = 15;
+
+/// How long a random file's records are when OPEN gives no LEN
+pub const DEFAULT_RECORD_LENGTH: usize = 128;
+
+/// GET and PUT take record numbers from 1 to this, as in GW-BASIC
+pub const MAX_RECORD_NUMBER: u64 = 16_777_215;
+
+#[derive(Debug)]
+struct OpenFile {
+    path: PathBuf,
+    mode: FileMode,
+    contents: String, // what an INPUT file holds, read when it was opened
+    position: usize,  // byte offset of the next character INPUT # reads
+    file: Option<File>,
+    records: Option<Records>, // a RANDOM file's record buffer
+}
+
+#[derive(Debug)]
+struct Records {
+    buffer: Vec<u8>, // the record GET reads into and PUT writes out
+    fields: Vec<FieldSlot>,
+    next: u64,      // the record GET or PUT uses when given no number
+    past_end: bool, // the last GET was beyond the end of the file
+}
+
+/// A variable FIELD ties to part of the record buffer
+#[derive(Debug)]
+struct FieldSlot {
+    variable: String, // upper case
+    offset: usize,
+    width: usize,
+}
+
+/// The sandbox folder and the files open in it, by number
+#[derive(Debug, Default)]
+pub struct FileTable {
+    dir: Option<PathBuf>,
+    current: PathBuf, // the folder CHDIR chose, relative to `dir`
+    open: BTreeMap<u32, OpenFile>,
+    asking: bool,                        // the IDE asks before files are changed
+    answers: BTreeMap<Permission, bool>, // what it was told; kept across loads
+}
+
+/// A name FILES lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub name: String,
+    pub folder: bool,
+}
+
+impl FileTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the program's files in `dir`, which is made when first written to
+    pub fn set_dir(&mut self, dir: Option<PathBuf>) {
+        self.dir = dir;
+        self.current = PathBuf::new();
+    }
+
+    /// Have the program wait for an answer before it first writes or
+    /// deletes files, or let it go ahead
+    pub fn set_asking(&mut self, asking: bool) {
+        self.asking = asking;
+    }
+
+    /// Whether the program may do what needs `permission`
+    pub fn answer(&mut self, permission: Permission, allowed: bool) {
+        self.answers.insert(permission, allowed);
+    }
+
+    /// Ok(false) when the IDE has to be asked first
+    pub fn permitted(&self, permission: Permission) -> Result<bool, InterpreterError> {
+        if !self.asking {
+            return Ok(true);
+        }
+        match self.answers.get(&permission) {
+            Some(true) => Ok(true),
+            Some(false) => Err(InterpreterError::runtime(
+                ErrorCode::PermissionDenied,
+                format!("The program wasn't allowed to {}", permission.name()),
+            )),
+            None => Ok(false),
+        }
+    }
+
+    /// Close every file and go back to the sandbox's top folder, for a new run
+    pub fn reset(&mut self) {
+        self.close_all();
+        self.current = PathBuf::new();
+    }
+
+    /// The folder CHDIR chose, as the program sees it, e.g. `/SCORES/`
+    pub fn current_dir(&self) -> String {
+        let mut shown = String::from("/");
+        for part in self.current.iter() {
+            shown.push_str(&part.to_string_lossy());
+            shown.push('/');
+        }
+        shown
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Where the file `name` is on disk. A name is taken from the current
+    /// folder, or from the sandbox's top one when it starts with `/`; `..`
+    /// goes up a folder but never out of the sandbox.
+    pub fn path(&self, name: &str) -> Result<PathBuf, InterpreterError> {
+        let relative = self.resolve(name)?;
+        if relative.as_os_str().is_empty() {
+            return Err(bad_file_name(name));
+        }
+        Ok(self.root()?.join(relative))
+    }
+
+    fn root(&self) -> Result<&Path, InterpreterError> {
+        self.dir.as_deref().ok_or_else(|| {
+            InterpreterError::runtime(
+                ErrorCode::FileAccess,
+                "This program has no folder for its files",
+            )
+        })
+    }
+
+    /// `name` as a path from the sandbox's top folder; empty for the top itself
+    fn resolve(&self, name: &str) -> Result<PathBuf, InterpreterError> {
+        let name = name.trim().replace('\\', "/");
+        if name.is_empty() {
+            return Err(bad_file_name(&name));
+        }
+        let mut resolved = if name.starts_with('/') {
+            PathBuf::new()
+        } else {
+            self.current.clone()
+        };
+        for component in Path::new(&name).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return Err(bad_file_name(&name));
+                    }
+                }
+                Component::CurDir | Component::RootDir => {}
+                Component::Prefix(_) => return Err(bad_file_name(&name)),
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// The sandbox's top folder on disk, made if it isn't there yet
+    fn make_root(&self) -> Result<&Path, InterpreterError> {
+        let root = self.root()?;
+        std::fs::create_dir_all(root).map_err(|err| {
+            InterpreterError::runtime(
+                ErrorCode::FileAccess,
+                format!("Could not make the program's folder: {}", err),
+            )
+        })?;
+        Ok(root)
+    }
+
+    /// FILES [pattern]: the names in a folder, or those matching a pattern
+    /// such as `"*.TXT"`, where `*` is any run of characters and `?` any one
+    pub fn list(&self, pattern: Option<&str>) -> Result<Vec<Listing>, InterpreterError> {
+        let (folder, pattern) = match pattern {
+            Some(pattern) => {
+                let resolved = self.resolve(pattern)?;
+                if self.root()?.join(&resolved).is_dir() {
+                    (resolved, "*".to_string())
+                } else {
+                    self.split_pattern(pattern)?
+                }
+            }
+            None => (self.current.clone(), "*".to_string()),
+        };
+        let mut found: Vec<Listing> = self
+            .entries(&folder)?
+            .into_iter()
+            .filter(|(name, _)| matches_pattern(&pattern, name))
+            .map(|(name, folder)| Listing { name, folder })
+            .collect();
+        if found.is_empty() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FileNotFound,
+                format!("No file matches \"{}\"", pattern),
+            ));
+        }
+        found.sort_by_key(|listing| listing.name.to_uppercase());
+        Ok(found)
+    }
+
+    /// KILL pattern: delete the files that match; folders are left alone
+    pub fn kill(&mut self, pattern: &str) -> Result<(), InterpreterError> {
+        let (folder, name) = self.split_pattern(pattern)?;
+        let root = self.root()?.to_path_buf();
+        let doomed: Vec<PathBuf> = self
+            .entries(&folder)?
+            .into_iter()
+            .filter(|(entry, is_folder)| !is_folder && matches_pattern(&name, entry))
+            .map(|(entry, _)| root.join(&folder).join(entry))
+            .collect();
+        if doomed.is_empty() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FileNotFound,
+                format!("There is no file \"{}\" to KILL", pattern),
+            ));
+        }
+        for path in &doomed {
+            self.check_closed(path, pattern)?;
+        }
+        for path in doomed {
+            std::fs::remove_file(&path).map_err(|err| name_error(pattern, err))?;
+        }
+        Ok(())
+    }
+
+    /// NAME old AS new
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), InterpreterError> {
+        let from = self.path(old)?;
+        let to = self.path(new)?;
+        if !from.exists() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FileNotFound,
+                format!("There is no file \"{}\" to rename", old),
+            ));
+        }
+        if to.exists() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FileAlreadyExists,
+                format!("There is already a file \"{}\"; KILL it first", new),
+            ));
+        }
+        self.check_closed(&from, old)?;
+        std::fs::rename(&from, &to).map_err(|err| name_error(old, err))
+    }
+
+    /// MKDIR name
+    pub fn make_dir(&mut self, name: &str) -> Result<(), InterpreterError> {
+        let path = self.path(name)?;
+        self.make_root()?;
+        std::fs::create_dir(&path).map_err(|err| name_error(name, err))
+    }
+
+    /// CHDIR name: take names from that folder from now on
+    pub fn change_dir(&mut self, name: &str) -> Result<(), InterpreterError> {
+        let resolved = self.resolve(name)?;
+        let root = self.make_root()?;
+        if !root.join(&resolved).is_dir() {
+            return Err(path_not_found(name));
+        }
+        self.current = resolved;
+        Ok(())
+    }
+
+    /// RMDIR name: remove an empty folder
+    pub fn remove_dir(&mut self, name: &str) -> Result<(), InterpreterError> {
+        let resolved = self.resolve(name)?;
+        let path = self.path(name)?;
+        if !path.is_dir() {
+            return Err(path_not_found(name));
+        }
+        if self.current.starts_with(&resolved) {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FileAccess,
+                format!("\"{}\" is the current folder; CHDIR out of it first", name),
+            ));
+        }
+        std::fs::remove_dir(&path).map_err(|err| name_error(name, err))
+    }
+
+    /// The folder part of `pattern` and the name pattern at its end
+    fn split_pattern(&self, pattern: &str) -> Result<(PathBuf, String), InterpreterError> {
+        let normalized = pattern.trim().replace('\\', "/");
+        let (folder, name) = match normalized.rsplit_once('/') {
+            Some((folder, name)) => (self.resolve(&format!("{}/", folder))?, name),
+            None => (self.current.clone(), normalized.as_str()),
+        };
+        if name.is_empty() {
+            return Err(bad_file_name(pattern));
+        }
+        Ok((folder, name.to_string()))
+    }
+
+    /// Each name in `folder` and whether it is a folder itself. A sandbox
+    /// nothing has been written to yet is empty.
+    fn entries(&self, folder: &Path) -> Result<Vec<(String, bool)>, InterpreterError> {
+        let path = self.root()?.join(folder);
+        let entries = match std::fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(_) if folder.as_os_str().is_empty() => return Ok(Vec::new()),
+            Err(_) => return Err(path_not_found(&folder.to_string_lossy())),
+        };
+        Ok(entries
+            .flatten()
+            .map(|entry| {
+                let folder = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                (entry.file_name().to_string_lossy().into_owned(), folder)
+            })
+            .collect())
+    }
+
+    /// An open file can't be deleted or renamed
+    fn check_closed(&self, path: &Path, name: &str) -> Result<(), InterpreterError> {
+        match self.open.iter().find(|(_, file)| file.path == path) {
+            Some((number, _)) => Err(InterpreterError::runtime(
+                ErrorCode::FileAlreadyOpen,
+                format!("\"{}\" is open as #{}; CLOSE it first", name, number),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Open `name` as file `number`; `record_length` is only used by RANDOM
+    pub fn open(
+        &mut self,
+        name: &str,
+        mode: FileMode,
+        number: u32,
+        record_length: usize,
+    ) -> Result<(), InterpreterError> {
+        if !(1..=MAX_FILE_NUMBER).contains(&number) {
+            return Err(bad_file_number(number));
+        }
+        if self.open.contains_key(&number) {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FileAlreadyOpen,
+                format!("File #{} is already open; CLOSE #{} first", number, number),
+            ));
+        }
+        let path = self.path(name)?;
+        let access = |err: std::io::Error| {
+            InterpreterError::runtime(
+                ErrorCode::FileAccess,
+                format!("Could not open \"{}\": {}", name, err),
+            )
+        };
+
+        let file = match mode {
+            FileMode::Input => {
+                let contents = std::fs::read_to_string(&path).map_err(|err| {
+                    if err.kind() == std::io::ErrorKind::NotFound {
+                        InterpreterError::runtime(
+                            ErrorCode::FileNotFound,
+                            format!("There is no file \"{}\"", name),
+                        )
+                    } else {
+                        access(err)
+                    }
+                })?;
+                OpenFile {
+                    path: path.clone(),
+                    mode,
+                    contents,
+                    position: 0,
+                    file: None,
+                    records: None,
+                }
+            }
+            FileMode::Output | FileMode::Append => {
+                self.make_root()?;
+                let writer = std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(mode == FileMode::Append)
+                    .truncate(mode == FileMode::Output)
+                    .open(&path)
+                    .map_err(|err| name_error(name, err))?;
+                OpenFile {
+                    path: path.clone(),
+                    mode,
+                    contents: String::new(),
+                    position: 0,
+                    file: Some(writer),
+                    records: None,
+                }
+            }
+            FileMode::Random => {
+                if record_length == 0 || record_length > u16::MAX as usize {
+                    return Err(InterpreterError::runtime(
+                        ErrorCode::BadRecordNumber,
+                        format!(
+                            "LEN={} is not a record length; use 1 to {}",
+                            record_length,
+                            u16::MAX
+                        ),
+                    ));
+                }
+                self.make_root()?;
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .read(true)
+                    .write(true)
+                    .open(&path)
+                    .map_err(|err| name_error(name, err))?;
+                OpenFile {
+                    path: path.clone(),
+                    mode,
+                    contents: String::new(),
+                    position: 0,
+                    file: Some(file),
+                    records: Some(Records {
+                        buffer: vec![b' '; record_length],
+                        fields: Vec::new(),
+                        next: 1,
+                        past_end: false,
+                    }),
+                }
+            }
+        };
+        self.open.insert(number, file);
+        Ok(())
+    }
+
+    /// CLOSE #n; closing a file that isn't open does nothing, as in GW-BASIC
+    pub fn close(&mut self, number: u32) {
+        self.open.remove(&number);
+    }
+
+    pub fn close_all(&mut self) {
+        self.open.clear();
+    }
+
+    /// PRINT # and WRITE #
+    pub fn write(&mut self, number: u32, text: &str) -> Result<(), InterpreterError> {
+        let file = self.file_mut(number)?;
+        let (FileMode::Output | FileMode::Append, Some(writer)) = (file.mode, file.file.as_mut())
+        else {
+            return Err(bad_file_mode(
+                number,
+                file.mode,
+                "written to with PRINT # or WRITE #",
+            ));
+        };
+        writer.write_all(text.as_bytes()).map_err(|err| {
+            InterpreterError::runtime(
+                ErrorCode::FileAccess,
+                format!("Could not write to file #{}: {}", number, err),
+            )
+        })
+    }
+
+    /// LINE INPUT #: the rest of the current line, without its line break
+    pub fn read_line(&mut self, number: u32) -> Result<String, InterpreterError> {
+        let file = self.reader(number)?;
+        let rest = &file.contents[file.position..];
+        let (line, used) = match rest.find('\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        let line = line.strip_suffix('\r').unwrap_or(line).to_string();
+        file.position += used;
+        Ok(line)
+    }
+
+    /// INPUT #: the next value, which ends at a comma or a line break unless
+    /// it is in quotes
+    pub fn read_field(&mut self, number: u32) -> Result<String, InterpreterError> {
+        let file = self.reader(number)?;
+        let rest = &file.contents[file.position..];
+        let start = rest.len() - rest.trim_start().len();
+        let rest = &rest[start..];
+
+        let (field, used) = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let after = &quoted[(end + 1).min(quoted.len())..];
+            // Skip to just past the comma or line break that follows
+            let skip = after
+                .find([',', '\n'])
+                .map_or(after.len(), |delimiter| delimiter + 1);
+            (
+                quoted[..end].to_string(),
+                1 + (end + 1).min(quoted.len()) + skip,
+            )
+        } else {
+            let end = rest.find([',', '\n']).unwrap_or(rest.len());
+            let used = (end + 1).min(rest.len());
+            (rest[..end].trim().to_string(), used)
+        };
+        file.position += start + used;
+        Ok(field)
+    }
+
+    /// EOF(n): nothing but blank space is left to read, or, for a random
+    /// file, the last GET was past its end
+    pub fn eof(&self, number: u32) -> Result<bool, InterpreterError> {
+        let file = self.file(number)?;
+        Ok(match (&file.records, file.mode) {
+            (Some(records), _) => records.past_end,
+            (None, FileMode::Input) => file.contents[file.position..].trim().is_empty(),
+            (None, _) => true,
+        })
+    }
+
+    /// LOF(n): how many bytes the file holds
+    pub fn lof(&self, number: u32) -> Result<u64, InterpreterError> {
+        let file = self.file(number)?;
+        match &file.file {
+            Some(writer) => writer.metadata().map(|meta| meta.len()).map_err(|err| {
+                InterpreterError::runtime(
+                    ErrorCode::FileAccess,
+                    format!("Could not read the length of file #{}: {}", number, err),
+                )
+            }),
+            None => Ok(file.contents.len() as u64),
+        }
+    }
+
+    /// FIELD #n: tie each variable to the next `width` bytes of the record.
+    /// Returns the variables with what the record holds for them now.
+    pub fn field(
+        &mut self,
+        number: u32,
+        widths: &[(usize, String)],
+    ) -> Result<Vec<(String, String)>, InterpreterError> {
+        let records = self.records(number)?;
+        let total: usize = widths.iter().map(|(width, _)| width).sum();
+        if total > records.buffer.len() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::FieldOverflow,
+                format!(
+                    "FIELD #{} needs {} bytes but its records are {} long; OPEN it with LEN={}",
+                    number,
+                    total,
+                    records.buffer.len(),
+                    total
+                ),
+            ));
+        }
+        let mut offset = 0;
+        records.fields.clear();
+        for (width, variable) in widths {
+            records.fields.push(FieldSlot {
+                variable: variable.to_uppercase(),
+                offset,
+                width: *width,
+            });
+            offset += width;
+        }
+        Ok(records.field_values())
+    }
+
+    /// LSET and RSET: put `text` in the record where FIELD tied `variable`,
+    /// padded with spaces or cut to the field's width. Returns what the
+    /// variable now holds, or None when no open file has it in a FIELD.
+    pub fn set_field(&mut self, variable: &str, text: &str, justify: Justify) -> Option<String> {
+        let variable = variable.to_uppercase();
+        self.open.values_mut().rev().find_map(|file| {
+            let records = file.records.as_mut()?;
+            let slot = records
+                .fields
+                .iter()
+                .find(|slot| slot.variable == variable)?;
+            let fitted = justify_bytes(&text_to_bytes(text), slot.width, justify);
+            records.buffer[slot.offset..slot.offset + slot.width].copy_from_slice(&fitted);
+            Some(bytes_to_text(&fitted))
+        })
+    }
+
+    /// GET #n[, record]: read a record into the buffer. Returns the FIELD
+    /// variables with their new values. A record past the end reads as
+    /// blanks and makes EOF(n) true.
+    pub fn get(
+        &mut self,
+        number: u32,
+        record: Option<u64>,
+    ) -> Result<Vec<(String, String)>, InterpreterError> {
+        let (file, records) = self.random_file(number)?;
+        let record = record.unwrap_or(records.next);
+        let length = records.buffer.len();
+        seek_record(file, number, record, length)?;
+        let mut read = 0;
+        records.buffer.fill(b' ');
+        while read < length {
+            match file.read(&mut records.buffer[read..]) {
+                Ok(0) => break,
+                Ok(count) => read += count,
+                Err(err) => return Err(access_error(number, err)),
+            }
+        }
+        records.past_end = read == 0;
+        records.next = record + 1;
+        Ok(records.field_values())
+    }
+
+    /// PUT #n[, record]: write the buffer out as a record
+    pub fn put(&mut self, number: u32, record: Option<u64>) -> Result<(), InterpreterError> {
+        let (file, records) = self.random_file(number)?;
+        let record = record.unwrap_or(records.next);
+        seek_record(file, number, record, records.buffer.len())?;
+        file.write_all(&records.buffer)
+            .map_err(|err| access_error(number, err))?;
+        records.next = record + 1;
+        Ok(())
+    }
+
+    fn records(&mut self, number: u32) -> Result<&mut Records, InterpreterError> {
+        Ok(self.random_file(number)?.1)
+    }
+
+    fn random_file(&mut self, number: u32) -> Result<(&mut File, &mut Records), InterpreterError> {
+        let file = self.file_mut(number)?;
+        match (file.file.as_mut(), file.records.as_mut()) {
+            (Some(handle), Some(records)) => Ok((handle, records)),
+            _ => Err(bad_file_mode(
+                number,
+                file.mode,
+                "used with FIELD, GET or PUT; OPEN it AS #n LEN=size",
+            )),
+        }
+    }
+
+    fn file(&self, number: u32) -> Result<&OpenFile, InterpreterError> {
+        self.open
+            .get(&number)
+            .ok_or_else(|| bad_file_number(number))
+    }
+
+    fn file_mut(&mut self, number: u32) -> Result<&mut OpenFile, InterpreterError> {
+        self.open
+            .get_mut(&number)
+            .ok_or_else(|| bad_file_number(number))
+    }
+
+    /// An INPUT file with something left in it
+    fn reader(&mut self, number: u32) -> Result<&mut OpenFile, InterpreterError> {
+        let file = self.file_mut(number)?;
+        if file.mode != FileMode::Input {
+            return Err(bad_file_mode(
+                number,
+                file.mode,
+                "read with INPUT # or LINE INPUT #",
+            ));
+        }
+        if file.contents[file.position..].trim().is_empty() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::InputPastEnd,
+                format!(
+                    "File #{} has nothing more to read; test EOF({}) first",
+                    number, number
+                ),
+            ));
+        }
+        Ok(file)
+    }
+}
+
+impl Records {
+    fn field_values(&self) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .map(|slot| {
+                let bytes = &self.buffer[slot.offset..slot.offset + slot.width];
+                (slot.variable.clone(), bytes_to_text(bytes))
+            })
+            .collect()
+    }
+}
+
+/// `bytes` cut or padded with spaces to `width`, on the right for LSET and
+/// on the left for RSET
+fn justify_bytes(bytes: &[u8], width: usize, justify: Justify) -> Vec<u8> {
+    let bytes = &bytes[..bytes.len().min(width)];
+    let padding = vec![b' '; width - bytes.len()];
+    match justify {
+        Justify::Left => [bytes, &padding].concat(),
+        Justify::Right => [&padding, bytes].concat(),
+    }
+}
+
+/// Move to the start of record `record`, counting from 1
+fn seek_record(
+    file: &mut File,
+    number: u32,
+    record: u64,
+    length: usize,
+) -> Result<(), InterpreterError> {
+    if record == 0 {
+        return Err(InterpreterError::runtime(
+            ErrorCode::BadRecordNumber,
+            format!("File #{}'s records are numbered from 1", number),
+        ));
+    }
+    let Some(start) = (record - 1).checked_mul(length as u64) else {
+        return Err(InterpreterError::runtime(
+            ErrorCode::BadRecordNumber,
+            format!("File #{} has no record {}", number, record),
+        ));
+    };
+    file.seek(SeekFrom::Start(start))
+        .map(|_| ())
+        .map_err(|err| access_error(number, err))
+}
+
+fn access_error(number: u32, err: std::io::Error) -> InterpreterError {
+    InterpreterError::runtime(
+        ErrorCode::FileAccess,
+        format!("Could not use file #{}: {}", number, err),
+    )
+}
+
+/// Whether `name` fits `pattern`, ignoring case. As in DOS, `*.*` also
+/// matches names without a dot.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) => p.eq_ignore_ascii_case(n) && matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+        || (pattern.ends_with(&['.', '*']) && matches(&pattern[..pattern.len() - 2], &name))
+}
+
+fn bad_file_name(name: &str) -> InterpreterError {
+    InterpreterError::runtime(
+        ErrorCode::BadFileName,
+        format!(
+            "\"{}\" is not a name in the program's folder; use a name like \"SCORES.TXT\"",
+            name
+        ),
+    )
+}
+
+fn path_not_found(name: &str) -> InterpreterError {
+    InterpreterError::runtime(
+        ErrorCode::PathNotFound,
+        format!("There is no folder \"{}\"; MKDIR makes one", name),
+    )
+}
+
+/// An error from the disk while using `name`, with the common ones given
+/// their GW-BASIC codes
+fn name_error(name: &str, err: std::io::Error) -> InterpreterError {
+    use std::io::ErrorKind;
+    match err.kind() {
+        ErrorKind::NotFound => path_not_found(name),
+        ErrorKind::AlreadyExists => InterpreterError::runtime(
+            ErrorCode::FileAccess,
+            format!("\"{}\" already exists", name),
+        ),
+        _ => InterpreterError::runtime(
+            ErrorCode::FileAccess,
+            format!("Could not use \"{}\": {}", name, err),
+        ),
+    }
+}
+
+fn bad_file_number(number: u32) -> InterpreterError {
+    InterpreterError::runtime(
+        ErrorCode::BadFileNumber,
+        format!("File #{} is not open", number),
+    )
+}
+
+fn bad_file_mode(number: u32, mode: FileMode, action: &str) -> InterpreterError {
+    InterpreterError::runtime(
+        ErrorCode::BadFileMode,
+        format!(
+            "File #{} was opened FOR {} and can't be {}",
+            number,
+            format!("{:?}", mode).to_uppercase(),
+            action
+        ),
+    )
+}
+
+/// The bytes of a record as a BASIC string, one character per byte
+pub fn bytes_to_text(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// A BASIC string as bytes; a character past CHR$(255) becomes `?`
+pub fn text_to_bytes(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
@@ -0,0 +1,240 @@
+//! INCLUDE for sharing code between programs: a line reading
+//! `'INCLUDE "lib.twb"` (or QBasic's `'$INCLUDE: 'lib.bi'`) is replaced by
+//! the lines of the file it names before the program is parsed. Included
+//! files may include others; a file that would end up including itself is
+//! an error rather than a hang.
+
+use crate::basic::ast::InterpreterError;
+use crate::basic::codes::ErrorCode;
+use std::path::{Path, PathBuf};
+
+/// Where one line of an expanded program was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub file: Option<PathBuf>, // None for the program itself
+    pub line: usize,           // 1-based, in that file
+    pub main_line: usize,      // the program's own line, or the INCLUDE that led here
+}
+
+/// A program with its INCLUDE lines replaced by the files they name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expanded {
+    pub code: String,
+    pub origins: Vec<Origin>, // one per line of `code`
+}
+
+/// The file an INCLUDE line names, if `line` is one. A line number in
+/// front and `REM` in place of `'` are allowed.
+pub fn include_target(line: &str) -> Option<&str> {
+    let line = line
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit());
+    let line = line.trim_start();
+    let rest = if let Some(rest) = line.strip_prefix('\'') {
+        rest
+    } else if line
+        .get(..4)
+        .is_some_and(|rem| rem.eq_ignore_ascii_case("REM "))
+    {
+        &line[4..]
+    } else {
+        return None;
+    };
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('$').unwrap_or(rest);
+    if !rest
+        .get(..7)
+        .is_some_and(|word| word.eq_ignore_ascii_case("INCLUDE"))
+    {
+        return None;
+    }
+    let rest = rest[7..].trim_start();
+    let rest = rest.strip_prefix(':').unwrap_or(rest).trim();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let name = rest[1..].strip_suffix(quote)?;
+    (!name.is_empty() && !name.contains(quote)).then_some(name)
+}
+
+/// How a file is named in messages
+fn display_name(file: Option<&Path>) -> String {
+    match file {
+        Some(path) => path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        ),
+        None => "the program".to_string(),
+    }
+}
+
+/// Replace the INCLUDE lines of `code`, the program saved at `path` if it
+/// has been saved. Included files are found next to the file that names
+/// them and read with `read`, so callers decide where files come from.
+pub fn expand(
+    code: &str,
+    path: Option<&Path>,
+    read: &mut dyn FnMut(&Path) -> std::io::Result<String>,
+) -> Result<Expanded, InterpreterError> {
+    let mut expanded = Expanded {
+        code: String::new(),
+        origins: Vec::new(),
+    };
+    let mut stack = path.map(|path| vec![same_file(path)]).unwrap_or_default();
+    splice(code, path, None, &mut stack, read, &mut expanded)?;
+    Ok(expanded)
+}
+
+/// A path to compare against the files being included, so `./lib.twb`
+/// and `lib.twb` are the same file
+fn same_file(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn splice(
+    code: &str,
+    file: Option<&Path>,
+    main_line: Option<usize>,
+    stack: &mut Vec<PathBuf>,
+    read: &mut dyn FnMut(&Path) -> std::io::Result<String>,
+    expanded: &mut Expanded,
+) -> Result<(), InterpreterError> {
+    // Only the program itself is `None`; the files it includes all have paths
+    let origin_file = main_line.and(file.map(Path::to_path_buf));
+    for (index, line) in code.lines().enumerate() {
+        let main_line = main_line.unwrap_or(index + 1);
+        let Some(name) = include_target(line) else {
+            expanded.code.push_str(line);
+            expanded.code.push('\n');
+            expanded.origins.push(Origin {
+                file: origin_file.clone(),
+                line: index + 1,
+                main_line,
+            });
+            continue;
+        };
+        let at = format!("line {} of {}", index + 1, display_name(file));
+        let target = match file.and_then(Path::parent) {
+            Some(folder) => folder.join(name),
+            None => PathBuf::from(name),
+        };
+        let key = same_file(&target);
+        if stack.contains(&key) {
+            let chain: Vec<String> = stack
+                .iter()
+                .chain([&key])
+                .map(|path| display_name(Some(path)))
+                .collect();
+            return Err(InterpreterError::parse(
+                ErrorCode::IncludeCycle,
+                format!(
+                    "INCLUDE at {} goes round in a circle: {}",
+                    at,
+                    chain.join(" → ")
+                ),
+            ));
+        }
+        let text = read(&target).map_err(|err| {
+            InterpreterError::parse(
+                ErrorCode::IncludeFailed,
+                format!("Could not INCLUDE {} at {}: {}", name, at, err),
+            )
+        })?;
+        stack.push(key);
+        splice(&text, Some(&target), Some(main_line), stack, read, expanded)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+impl Expanded {
+    /// A program with nothing to include, line for line
+    pub fn plain(code: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            origins: (1..=code.lines().count())
+                .map(|line| Origin {
+                    file: None,
+                    line,
+                    main_line: line,
+                })
+                .collect(),
+        }
+    }
+
+    /// Where expanded line `line` (1-based) was written
+    pub fn origin(&self, line: usize) -> Option<&Origin> {
+        line.checked_sub(1)
+            .and_then(|index| self.origins.get(index))
+    }
+
+    /// The program's own line for expanded line `line`: the line itself, or
+    /// the INCLUDE that brought it in
+    pub fn main_line(&self, line: usize) -> usize {
+        self.origin(line).map_or(line, |origin| origin.main_line)
+    }
+
+    /// The expanded line that the program's line `line` became, if it is
+    /// still there (an INCLUDE line is not)
+    pub fn expanded_line(&self, line: usize) -> Option<usize> {
+        self.origins
+            .iter()
+            .position(|origin| origin.file.is_none() && origin.line == line)
+            .map(|index| index + 1)
+    }
+
+    /// "line N" for expanded line `line`, naming the file it was written in
+    /// when that isn't the program itself: "line 3 of shapes.twb"
+    pub fn locate_line(&self, line: usize) -> String {
+        match self.origin(line) {
+            Some(Origin {
+                file: Some(file),
+                line,
+                ..
+            }) => format!("line {} of {}", line, display_name(Some(file))),
+            Some(origin) => format!("line {}", origin.line),
+            None => format!("line {}", line),
+        }
+    }
+
+    /// `err` as it displays, with its position in the file it was written in
+    pub fn locate_error(&self, err: &InterpreterError) -> String {
+        match err.position() {
+            Some(position) => format!(
+                "{} {} ({}, column {})",
+                err.code().code(),
+                err.message(),
+                self.locate_line(position.line),
+                position.column
+            ),
+            None => format!(
+                "{} {}",
+                err.code().code(),
+                self.locate_at_lines(&err.message())
+            ),
+        }
+    }
+
+    /// `message` with each "at line N" of the expanded program, which is how
+    /// errors without a position say where they are, turned back into the
+    /// line of the file it was written in. Other numbers, such as the BASIC
+    /// line a GOTO names, are left alone.
+    pub fn locate_at_lines(&self, message: &str) -> String {
+        let mut located = String::new();
+        let mut rest = message;
+        while let Some(found) = rest.find("at line ") {
+            let (before, after) = rest.split_at(found + "at ".len());
+            located.push_str(before);
+            let number = &after["line ".len()..];
+            let digits = number.len()
+                - number
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            match number[..digits].parse() {
+                Ok(line) => located.push_str(&self.locate_line(line)),
+                Err(_) => located.push_str(&after[.."line ".len() + digits]),
+            }
+            rest = &number[digits..];
+        }
+        located.push_str(rest);
+        located
+    }
+}
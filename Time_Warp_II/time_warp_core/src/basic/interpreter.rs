@@ -0,0 +1,2870 @@
+use crate::basic::ast::{
+    AssertionResult, BinaryOperator, EventKind, ExecutionContext, ExecutionResult, Expression,
+    FileMode, FolderAction, ForLoop, FunctionDefinition, GraphicsCommand, InterpreterError,
+    Justify, LineProfile, Permission, Position, PrintChannel, PrintSeparator, Program,
+    ResumeTarget, Statement, StepMode, TrappedError, TurtleCommand, UnaryOperator, Value,
+    VariableType, DEFAULT_RANDOM_SEED, DEFAULT_TURTLE, RANDOM_MODULUS,
+};
+use crate::basic::codes::ErrorCode;
+use crate::basic::dialect::{self, Dialect, Screen, CLEAR_SCREEN, INTEGER_RANGE};
+use crate::basic::events::{EventSource, MAX_KEY};
+use crate::basic::files::{self, FileTable, DEFAULT_RECORD_LENGTH, MAX_RECORD_NUMBER};
+use crate::basic::keyboard::Keyboard;
+use crate::basic::lint;
+use crate::basic::matching;
+use crate::basic::memory::{self, Mapped, Memory, C64_PALETTE};
+use crate::basic::music::{Music, Note};
+use crate::basic::pump::{Mouse, Pump};
+use crate::basic::snapshot;
+use crate::basic::strict::{self, Diagnostic, Severity, Strictness};
+use crate::basic::using;
+use crate::json::Json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Logo-style colours for `SETPENCOLOR n` and `SETBACKGROUND n`
+pub const PEN_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),       // 0 black
+    (0, 0, 255),     // 1 blue
+    (0, 160, 0),     // 2 green
+    (0, 200, 200),   // 3 cyan
+    (220, 0, 0),     // 4 red
+    (200, 0, 200),   // 5 magenta
+    (230, 200, 0),   // 6 yellow
+    (255, 255, 255), // 7 white
+    (140, 80, 30),   // 8 brown
+    (210, 180, 140), // 9 tan
+    (34, 110, 34),   // 10 forest green
+    (120, 220, 220), // 11 aqua
+    (250, 128, 114), // 12 salmon
+    (128, 0, 160),   // 13 purple
+    (255, 150, 0),   // 14 orange
+    (128, 128, 128), // 15 grey
+];
+
+/// BASIC interpreter engine
+pub struct Interpreter {
+    context: ExecutionContext,
+    program: Option<Program>,
+    current_line: usize,
+    instruction_count: usize,
+    counted_from: usize, // instruction_count when the run last handed control back
+    pub max_instructions: usize,
+    breakpoints: HashSet<usize>, // source lines
+    resuming: bool,              // true when continuing from a debugger pause
+    profiling: bool,
+    profile: HashMap<usize, LineProfile>, // source line -> statistics
+    executed_lines: HashSet<usize>,       // source lines reached, for coverage
+    assertions: Vec<AssertionResult>,     // every ASSERT run, in order
+    input_queue: VecDeque<String>,        // scripted INPUT answers, kept across loads
+    dialect: Dialect,                     // how programs are read, kept across loads
+    strictness: Strictness,               // kept across loads
+    diagnostics: Vec<Diagnostic>,         // what strict mode found in the loaded program
+    printer: String,                      // LPRINT output not yet taken
+    files: FileTable,                     // OPEN files; its folder is kept across loads
+    memory: Memory,                       // what PEEK and POKE reach
+    music: Music,                         // PLAY's settings and the notes not yet taken
+    keyboard: Keyboard,                   // keys INKEY$ hasn't read yet
+    mouse: Mouse,                         // kept across loads
+    pump: Pump,                           // when a run waiting for input hands back
+    cleared_screen: bool,                 // CLS ran during the last run call
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            context: ExecutionContext::new(),
+            program: None,
+            current_line: 0,
+            instruction_count: 0,
+            counted_from: 0,
+            max_instructions: 100000,
+            breakpoints: HashSet::new(),
+            resuming: false,
+            profiling: false,
+            profile: HashMap::new(),
+            executed_lines: HashSet::new(),
+            assertions: Vec::new(),
+            input_queue: VecDeque::new(),
+            dialect: Dialect::default(),
+            strictness: Strictness::default(),
+            diagnostics: Vec::new(),
+            printer: String::new(),
+            files: FileTable::new(),
+            memory: Memory::default(),
+            music: Music::default(),
+            keyboard: Keyboard::default(),
+            mouse: Mouse::default(),
+            pump: Pump::default(),
+            cleared_screen: false,
+        }
+    }
+
+    pub fn execute(&mut self, code: &str) -> Result<ExecutionResult, InterpreterError> {
+        self.load(code)?;
+        self.execute_program()
+    }
+
+    /// Parse a program and prepare it for execution without running it
+    pub fn load(&mut self, code: &str) -> Result<(), InterpreterError> {
+        // Reset state
+        self.reset();
+
+        // A QBasic program is rewritten as TW BASIC first; its statements
+        // are then mapped back to the lines they were written on
+        let translated = match self.dialect {
+            Dialect::QBasic => Some(dialect::translate(code)?),
+            _ => None,
+        };
+        let locate = |err| match &translated {
+            Some(translated) => translated.locate(err),
+            None => err,
+        };
+
+        // Tokenize and parse
+        let source = translated
+            .as_ref()
+            .map_or(code, |translated| &translated.code);
+        let mut tokenizer = crate::basic::tokenizer::Tokenizer::with_dialect(source, self.dialect);
+        let tokens = tokenizer.tokenize().map_err(locate)?;
+
+        let mut parser = crate::basic::parser::Parser::new(tokens);
+        let mut program = parser.parse_program().map_err(locate)?;
+        if let Some(translated) = &translated {
+            for line in &mut program.statement_lines {
+                *line = translated.source_line(*line);
+            }
+            // Rewritten lines start somewhere else, so each statement is
+            // placed where its source line's text starts
+            let lines: Vec<&str> = code.lines().collect();
+            for (column, line) in program
+                .statement_columns
+                .iter_mut()
+                .zip(&program.statement_lines)
+            {
+                let text = line.checked_sub(1).and_then(|index| lines.get(index));
+                *column = text.map_or(1, |text| {
+                    text.chars().take_while(|c| c.is_whitespace()).count() + 1
+                });
+            }
+        }
+
+        if self.strictness == Strictness::Strict {
+            self.diagnostics = strict::check(&program);
+            if let Some(error) = self
+                .diagnostics
+                .iter()
+                .find(|diagnostic| diagnostic.severity == Severity::Error)
+            {
+                return Err(InterpreterError::parse(
+                    ErrorCode::UnsetVariable,
+                    format!("{} at line {}", error.message, error.line),
+                ));
+            }
+        }
+
+        self.program = Some(program);
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.context.variables.clear();
+        self.context.arrays.clear();
+        self.context.functions.clear();
+        self.context.for_loops.clear();
+        self.context.gosub_stack.clear();
+        self.context.data.clear();
+        self.context.data_pointer = 0;
+        self.context.input_variable = None;
+        self.context.turtle = DEFAULT_TURTLE.to_string();
+        self.context.random_seed = DEFAULT_RANDOM_SEED;
+        self.context.pens_up.clear();
+        self.context.events.clear();
+        self.context.event_frames.clear();
+        self.context.error_handler = None;
+        self.context.last_error = None;
+        self.context.in_error_handler = false;
+        self.context.raised_error = None;
+        self.program = None;
+        self.current_line = 0;
+        self.instruction_count = 0;
+        self.counted_from = 0;
+        self.resuming = false;
+        self.profile.clear();
+        self.executed_lines.clear();
+        self.assertions.clear();
+        self.diagnostics.clear();
+        self.printer.clear();
+        self.files.reset();
+        self.memory.reset();
+        self.music.reset();
+        self.keyboard.reset();
+        self.pump.reset();
+    }
+
+    /// Read programs loaded from now on as `dialect`
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    /// Reject unset variables and mixed text and numbers in programs loaded
+    /// from now on, or let them through
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    /// Errors and warnings strict mode found in the last program loaded,
+    /// whether or not it loaded
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// What the linter finds in the loaded program
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        self.program.as_ref().map(lint::lint).unwrap_or_default()
+    }
+
+    /// Number of statements executed since the program was loaded
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    /// Enable or disable per-line execution profiling for subsequent runs
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+
+    /// Per-line hit counts and timings from the last run, ordered by source line
+    pub fn profile_report(&self) -> Vec<LineProfile> {
+        let mut report: Vec<LineProfile> = self.profile.values().cloned().collect();
+        report.sort_by_key(|entry| entry.line);
+        report
+    }
+
+    /// Source lines that ran at least once since the program was loaded
+    pub fn executed_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self.executed_lines.iter().copied().collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    /// What LPRINT has printed since this was last called
+    pub fn take_printer_output(&mut self) -> String {
+        std::mem::take(&mut self.printer)
+    }
+
+    /// The notes PLAY has queued since this was last called, for whatever
+    /// drives the interpreter to play
+    pub fn take_music(&mut self) -> Vec<Note> {
+        self.music.take()
+    }
+
+    /// Keep the files programs OPEN in `dir`, or refuse to open any when None
+    pub fn set_files_dir(&mut self, dir: Option<PathBuf>) {
+        self.files.set_dir(dir);
+    }
+
+    /// Stop with `NeedPermission` before a program first writes or deletes
+    /// files, or let programs go ahead (the default)
+    pub fn set_asking_permission(&mut self, asking: bool) {
+        self.files.set_asking(asking);
+    }
+
+    /// Settle `permission` without asking, e.g. from an answer remembered
+    /// for the program
+    pub fn set_permission(&mut self, permission: Permission, allowed: bool) {
+        self.files.answer(permission, allowed);
+    }
+
+    /// Answer the `NeedPermission` the run stopped with and carry on from
+    /// the statement that asked; when refused it fails with "Permission
+    /// denied", which ON ERROR can catch
+    pub fn answer_permission(
+        &mut self,
+        permission: Permission,
+        allowed: bool,
+    ) -> Result<ExecutionResult, InterpreterError> {
+        self.files.answer(permission, allowed);
+        self.execute_program()
+    }
+
+    /// A key typed while the program runs, for INKEY$ to read: a character,
+    /// or one of the two-character codes in `keyboard` for arrows and the like
+    pub fn press_key(&mut self, key: &str) {
+        self.keyboard.press(key);
+    }
+
+    /// Where the mouse is over the canvas and which buttons are down, for
+    /// MOUSEX, MOUSEY and MOUSEB
+    pub fn set_mouse(&mut self, mouse: Mouse) {
+        self.mouse = mouse;
+    }
+
+    /// Hand control back with `Polling` when a program waits on the
+    /// keyboard or mouse (INKEY$ with no key waiting, MOUSEX and the like),
+    /// so what happened meanwhile can be passed in before `run` carries on.
+    /// Off by default, when the run just goes on.
+    pub fn set_live_input(&mut self, live: bool) {
+        self.pump.set_live(live);
+    }
+
+    /// Whether CLS ran during the last `run`, so text shown from before it
+    /// should go: the output it gave starts after the CLS
+    pub fn cleared_screen(&self) -> bool {
+        self.cleared_screen
+    }
+
+    /// Every ASSERT run since the program was loaded, passed or not
+    pub fn assertion_results(&self) -> &[AssertionResult] {
+        &self.assertions
+    }
+
+    /// Pre-load answers for INPUT statements. Each INPUT takes the next one
+    /// instead of stopping, and the program only waits once they run out.
+    pub fn queue_input<I: IntoIterator<Item = String>>(&mut self, answers: I) {
+        self.input_queue.extend(answers);
+    }
+
+    /// Where a paused run is and everything it has stored, for saving in a
+    /// `snapshot::Session`
+    pub fn save_state(&self) -> Json {
+        Json::object([
+            ("current_line", self.current_line.into()),
+            ("instruction_count", self.instruction_count.into()),
+            ("resuming", Json::Bool(self.resuming)),
+            ("dialect", self.dialect.name().into()),
+            ("strictness", self.strictness.name().into()),
+            ("context", snapshot::context_to_json(&self.context)),
+        ])
+    }
+
+    /// Load `source` and put the run back where `save_state` left it
+    pub fn restore_state(&mut self, source: &str, state: &Json) -> Result<(), String> {
+        if let Some(dialect) = state
+            .get("dialect")
+            .and_then(Json::as_str)
+            .and_then(Dialect::from_name)
+        {
+            self.dialect = dialect;
+        }
+        if let Some(strictness) = state
+            .get("strictness")
+            .and_then(Json::as_str)
+            .and_then(Strictness::from_name)
+        {
+            self.strictness = strictness;
+        }
+        self.load(source).map_err(|err| err.to_string())?;
+        let Some(program) = self.program.as_ref() else {
+            return Err("No program loaded".to_string());
+        };
+        let current_line = state
+            .get("current_line")
+            .and_then(Json::as_usize)
+            .ok_or("The saved state has no current_line")?;
+        if current_line > program.statements.len() {
+            return Err("The saved state doesn't match the program".to_string());
+        }
+        snapshot::context_from_json(
+            &mut self.context,
+            state.get("context").unwrap_or(&Json::Null),
+            program,
+        )?;
+        self.current_line = current_line;
+        self.instruction_count = state
+            .get("instruction_count")
+            .and_then(Json::as_usize)
+            .unwrap_or(0);
+        self.resuming = matches!(state.get("resuming"), Some(Json::Bool(true)));
+        Ok(())
+    }
+
+    /// Source lines that hold at least one statement of the loaded program
+    pub fn executable_lines(&self) -> Vec<usize> {
+        let mut lines = self
+            .program
+            .as_ref()
+            .map(|program| program.statement_lines.clone())
+            .unwrap_or_default();
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    /// Replace the set of source lines the debugger should pause at
+    pub fn set_breakpoints<I: IntoIterator<Item = usize>>(&mut self, lines: I) {
+        self.breakpoints = lines.into_iter().collect();
+    }
+
+    /// Source line of the statement that will execute next
+    pub fn current_source_line(&self) -> Option<usize> {
+        self.program
+            .as_ref()
+            .and_then(|program| program.statement_lines.get(self.current_line))
+            .copied()
+    }
+
+    /// Where the statement about to run starts in the source
+    pub fn current_position(&self) -> Option<Position> {
+        let program = self.program.as_ref()?;
+        Some(Position {
+            line: *program.statement_lines.get(self.current_line)?,
+            column: *program.statement_columns.get(self.current_line)?,
+        })
+    }
+
+    /// `err` placed at the statement that was running when it happened
+    fn located(&self, err: InterpreterError) -> InterpreterError {
+        match self.current_position() {
+            Some(position) => err.at(position),
+            None => err,
+        }
+    }
+
+    /// Source lines of the pending GOSUB call sites, outermost first
+    pub fn call_stack_lines(&self) -> Vec<usize> {
+        self.context
+            .gosub_stack
+            .iter()
+            .filter_map(|&index| self.statement_line(index))
+            .collect()
+    }
+
+    /// Current variable values formatted for display
+    pub fn variable_snapshot(&self) -> Vec<(String, String)> {
+        let mut variables: Vec<(String, String)> = self
+            .context
+            .variables
+            .iter()
+            .map(|(name, info)| (name.clone(), self.value_to_string(&info.value)))
+            .collect();
+        variables.sort();
+        variables
+    }
+
+    /// Tell a running program that a key was pressed, the mouse clicked, etc.
+    /// The handler runs before the next statement, if the program traps it.
+    pub fn raise_event(&mut self, source: EventSource) {
+        self.context.events.raise(source);
+    }
+
+    fn statement_line(&self, index: usize) -> Option<usize> {
+        self.program
+            .as_ref()
+            .and_then(|program| program.statement_lines.get(index))
+            .copied()
+    }
+
+    /// Map a BASIC line number to a statement index, if the program has the line
+    fn resolve_line_number(&self, line_num: usize) -> Option<usize> {
+        self.program
+            .as_ref()
+            .and_then(|program| program.line_numbers.get(&line_num))
+            .copied()
+    }
+
+    /// The statement a jump to BASIC line `line_num` lands on
+    fn jump_target(&self, line_num: usize) -> Result<usize, InterpreterError> {
+        self.resolve_line_number(line_num).ok_or_else(|| {
+            InterpreterError::runtime(
+                ErrorCode::UndefinedLineNumber,
+                format!("Undefined line number {}", line_num),
+            )
+        })
+    }
+
+    fn execute_program(&mut self) -> Result<ExecutionResult, InterpreterError> {
+        self.run(StepMode::Continue)
+    }
+
+    /// Write PRINT's values to `output`, laid out for where it goes
+    fn print_values(
+        &mut self,
+        output: &mut String,
+        layout: PrintLayout,
+        using: &Option<Expression>,
+        expressions: &[Expression],
+        separators: &[PrintSeparator],
+    ) -> Result<(), InterpreterError> {
+        let write = |output: &mut String, text: &str| match layout {
+            PrintLayout::Screen(screen) => print_on_screen(output, text, screen),
+            _ => output.push_str(text),
+        };
+
+        if let Some(format) = using {
+            let format = self.evaluate_expression(format)?;
+            let Value::String(format) = format else {
+                return Err(InterpreterError::TypeError(
+                    "USING needs a format string, e.g. PRINT USING \"##.##\"; X".to_string(),
+                ));
+            };
+            let values = expressions
+                .iter()
+                .map(|expression| self.evaluate_expression(expression))
+                .collect::<Result<Vec<_>, _>>()?;
+            write(output, &using::format(&format, &values)?);
+        } else {
+            for (i, expr) in expressions.iter().enumerate() {
+                let value = self.evaluate_expression(expr)?;
+                let mut value_str = self.value_to_string(&value);
+                // The Commodore 64 leaves room for a sign and a space after numbers
+                if self.dialect == Dialect::Commodore64
+                    && matches!(layout, PrintLayout::Screen(_))
+                    && !matches!(value, Value::String(_))
+                {
+                    let sign = if value_str.starts_with('-') { "" } else { " " };
+                    value_str = format!("{}{} ", sign, value_str);
+                }
+                write(output, &value_str);
+
+                // Add separator if not the last expression
+                if i < separators.len() {
+                    match (&separators[i], layout) {
+                        (PrintSeparator::Comma, PrintLayout::Screen(screen)) => {
+                            let column = screen_column(output);
+                            let padding = screen.zone - column % screen.zone;
+                            print_on_screen(output, &" ".repeat(padding), screen);
+                        }
+                        (PrintSeparator::Comma, PrintLayout::Printer) => {
+                            let column = screen_column(output);
+                            let padding = PRINTER_ZONE - column % PRINTER_ZONE;
+                            output.push_str(&" ".repeat(padding));
+                        }
+                        (PrintSeparator::Comma, PrintLayout::Plain) => output.push('\t'),
+                        (PrintSeparator::Semicolon, _) => {} // No separator
+                        (PrintSeparator::None, _) => {}      // The newline is added below
+                    }
+                }
+            }
+        }
+        // Add newline unless the last separator suppresses it (comma or semicolon)
+        if expressions.is_empty()
+            || separators.is_empty()
+            || matches!(separators.last(), Some(PrintSeparator::None))
+        {
+            output.push('\n');
+        }
+        Ok(())
+    }
+
+    /// Execute statements until the program ends or the debugger should pause
+    pub fn run(&mut self, mode: StepMode) -> Result<ExecutionResult, InterpreterError> {
+        let mut output = String::new();
+        let mut graphics_commands = Vec::new();
+
+        // Extract statements to avoid borrowing conflicts
+        let statements = if let Some(ref program) = self.program {
+            program.statements.clone()
+        } else {
+            return Err(InterpreterError::RuntimeError(
+                "No program loaded".to_string(),
+            ));
+        };
+
+        let start_depth = self.context.gosub_stack.len();
+        let mut first_statement = true;
+        self.pump.start_slice();
+        self.cleared_screen = false;
+
+        while self.current_line < statements.len() {
+            // A program trapping keys, the timer or the mouse waits on them too
+            if self.context.events.listening() {
+                self.pump.wait();
+            }
+            // Waiting for input: time spent waiting doesn't count towards the limit
+            let out_of_time =
+                self.instruction_count.saturating_sub(self.counted_from) >= self.max_instructions;
+            if self.pump.take_yield(out_of_time, self.keyboard.has_keys()) {
+                self.counted_from = self.instruction_count;
+                self.resuming = false;
+                return Ok(ExecutionResult::Polling {
+                    partial_output: output,
+                    partial_graphics: graphics_commands,
+                });
+            }
+            let threshold = self.context.events.play_threshold();
+            if self.music.ran_low(threshold, Instant::now()) {
+                self.context.events.raise(EventSource::Play);
+            }
+            self.dispatch_event().map_err(|err| self.located(err))?;
+
+            // The statement we paused on must run before we can pause again
+            let skip_pause = first_statement && self.resuming;
+            first_statement = false;
+            if !skip_pause && self.should_pause(mode, start_depth) {
+                self.resuming = true;
+                return Ok(ExecutionResult::Break {
+                    line: self.current_source_line().unwrap_or(0),
+                    partial_output: output,
+                    partial_graphics: graphics_commands,
+                });
+            }
+
+            self.instruction_count += 1;
+            if self.instruction_count.saturating_sub(self.counted_from) > self.max_instructions {
+                return Err(self.located(InterpreterError::runtime(
+                    ErrorCode::ExecutionTimeout,
+                    format!(
+                        "Execution timeout: exceeded {} instructions",
+                        self.max_instructions
+                    ),
+                )));
+            }
+
+            let statement = &statements[self.current_line];
+            if let Some(line) = self.current_source_line() {
+                self.executed_lines.insert(line);
+            }
+            let profiled_line = if self.profiling {
+                self.current_source_line()
+                    .map(|line| (line, Instant::now()))
+            } else {
+                None
+            };
+            let result = self.execute_statement(statement, &mut output, &mut graphics_commands);
+            if let Some((line, started)) = profiled_line {
+                let entry = self.profile.entry(line).or_insert_with(|| LineProfile {
+                    line,
+                    ..LineProfile::default()
+                });
+                entry.hits += 1;
+                entry.total_time += started.elapsed();
+            }
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    let err = self.located(err);
+                    match self.trap_error(&err) {
+                        Some(handler) => {
+                            self.current_line = handler;
+                            continue;
+                        }
+                        None => return Err(err),
+                    }
+                }
+            };
+
+            if let Some(special_result) = result {
+                if special_result == "END" || special_result == "STOP" {
+                    break;
+                } else if let Some(target) = special_result.strip_prefix("GOTO ") {
+                    if let Ok(index) = target.parse::<usize>() {
+                        // Returning from a GOSUB on the last statement runs off the end
+                        if index >= statements.len() {
+                            break;
+                        }
+                        self.current_line = index;
+                        continue;
+                    }
+                } else if special_result == "CONTINUE_LOOP" {
+                    // NEXT statement handled the line adjustment
+                    continue;
+                } else if let Some(prompt) = special_result.strip_prefix("INPUT ") {
+                    if let Some(answer) = self.input_queue.pop_front() {
+                        // Scripted answer: echo it as if it had been typed
+                        output.push_str(prompt);
+                        output.push_str(answer.trim());
+                        output.push('\n');
+                        self.assign_input(&answer)
+                            .map_err(|err| self.located(err))?;
+                        self.current_line += 1;
+                        continue;
+                    }
+                    // Wait for the answer; provide_input carries on from the next statement
+                    self.current_line += 1;
+                    self.resuming = false;
+                    return Ok(ExecutionResult::NeedInput {
+                        variable: self.context.input_variable.clone().unwrap_or_default(),
+                        prompt: prompt.to_string(),
+                        partial_output: output,
+                        partial_graphics: graphics_commands,
+                    });
+                } else if let Some(permission) = special_result
+                    .strip_prefix("PERMISSION ")
+                    .and_then(Permission::from_name)
+                {
+                    // The statement runs again once answered, without the debugger pausing on it
+                    self.resuming = true;
+                    return Ok(ExecutionResult::NeedPermission {
+                        permission,
+                        partial_output: output,
+                        partial_graphics: graphics_commands,
+                    });
+                }
+            }
+
+            self.current_line += 1;
+        }
+
+        // A SLEEP at the very end still sleeps before the program ends
+        if self.pump.sleeping(self.keyboard.has_keys()) {
+            return Ok(ExecutionResult::Polling {
+                partial_output: output,
+                partial_graphics: graphics_commands,
+            });
+        }
+        self.resuming = false;
+        self.files.close_all();
+        Ok(ExecutionResult::Complete {
+            output,
+            graphics_commands,
+        })
+    }
+
+    /// ON ERROR GOTO: the statement index of the handler that takes over
+    /// from `err`, or None if the error stops the program. An error inside
+    /// the handler itself always stops it.
+    fn trap_error(&mut self, err: &InterpreterError) -> Option<usize> {
+        let number = self
+            .context
+            .raised_error
+            .take()
+            .unwrap_or_else(|| err.code().gw_basic_number());
+        let handler = self.context.error_handler?;
+        if self.context.in_error_handler {
+            return None;
+        }
+        let target = self.resolve_line_number(handler)?;
+        self.context.last_error = Some(TrappedError {
+            error: err.clone(),
+            number,
+            statement: self.current_line,
+            line: self.basic_line_number(self.current_line),
+        });
+        self.context.in_error_handler = true;
+        Some(target)
+    }
+
+    /// The BASIC line number of the line statement `index` is on
+    fn basic_line_number(&self, index: usize) -> usize {
+        self.program
+            .as_ref()
+            .and_then(|program| {
+                program
+                    .line_numbers
+                    .iter()
+                    .filter(|&(_, &start)| start <= index)
+                    .max_by_key(|&(_, &start)| start)
+                    .map(|(&number, _)| number)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Event dispatch point: GOSUB to the handler of a waiting event, if any.
+    /// Its RETURN resumes at the statement that was about to run.
+    fn dispatch_event(&mut self) -> Result<(), InterpreterError> {
+        let Some((source, line)) = self.context.events.next_dispatch(Instant::now()) else {
+            return Ok(());
+        };
+        let target = self.jump_target(line)?;
+        self.context.gosub_stack.push(self.current_line);
+        self.context
+            .event_frames
+            .push((self.context.gosub_stack.len(), source));
+        self.current_line = target;
+        Ok(())
+    }
+
+    /// The event sources a KEY(n)/TIMER/PLAY/MOUSE clause names. KEY(0) means
+    /// every key, but only where `all_keys` allows it.
+    fn event_sources(
+        &mut self,
+        event: EventKind,
+        argument: &Option<Expression>,
+        all_keys: bool,
+    ) -> Result<Vec<EventSource>, InterpreterError> {
+        Ok(match event {
+            EventKind::Key => {
+                let key = match argument {
+                    Some(argument) => {
+                        let value = self.evaluate_expression(argument)?;
+                        self.value_to_number(&value)?
+                    }
+                    None => 0.0,
+                };
+                if key == 0.0 && all_keys {
+                    (1..=MAX_KEY).map(EventSource::Key).collect()
+                } else if key >= 1.0 && key <= MAX_KEY as f64 {
+                    vec![EventSource::Key(key as u8)]
+                } else {
+                    return Err(InterpreterError::RuntimeError(format!(
+                        "KEY({}) is not a key number between 1 and {}",
+                        key, MAX_KEY
+                    )));
+                }
+            }
+            EventKind::Timer => vec![EventSource::Timer],
+            EventKind::Play => vec![EventSource::Play],
+            EventKind::Mouse => vec![EventSource::Mouse],
+        })
+    }
+
+    fn should_pause(&self, mode: StepMode, start_depth: usize) -> bool {
+        let at_breakpoint = self
+            .current_source_line()
+            .is_some_and(|line| self.breakpoints.contains(&line));
+        let depth = self.context.gosub_stack.len();
+
+        at_breakpoint
+            || match mode {
+                StepMode::Continue => false,
+                StepMode::StepInto => true,
+                StepMode::StepOver => depth <= start_depth,
+                StepMode::StepOut => depth < start_depth,
+            }
+    }
+
+    fn execute_statement(
+        &mut self,
+        statement: &Statement,
+        output: &mut String,
+        graphics_commands: &mut Vec<TurtleCommand>,
+    ) -> Result<Option<String>, InterpreterError> {
+        match statement {
+            Statement::Let {
+                variable,
+                expression,
+            } => {
+                let value = self.evaluate_expression(expression)?;
+                let var_type = self.context.get_variable_type(variable);
+                let converted_value = self.convert_value_to_variable_type(&value, variable)?;
+                let var_info = self.context.get_variable(variable);
+                var_info.value = converted_value;
+                var_info.declared_type = var_type;
+                Ok(None)
+            }
+            Statement::Print {
+                channel,
+                using,
+                expressions,
+                separators,
+            } => {
+                match channel {
+                    PrintChannel::Screen => {
+                        let layout = match self.dialect.screen() {
+                            Some(screen) => PrintLayout::Screen(screen),
+                            None => PrintLayout::Plain,
+                        };
+                        self.print_values(output, layout, using, expressions, separators)?;
+                    }
+                    PrintChannel::Printer => {
+                        let mut printer = std::mem::take(&mut self.printer);
+                        let printed = self.print_values(
+                            &mut printer,
+                            PrintLayout::Printer,
+                            using,
+                            expressions,
+                            separators,
+                        );
+                        self.printer = printer;
+                        printed?;
+                    }
+                    PrintChannel::File(number) => {
+                        let number = self.file_number(number)?;
+                        let mut text = String::new();
+                        self.print_values(
+                            &mut text,
+                            PrintLayout::Printer,
+                            using,
+                            expressions,
+                            separators,
+                        )?;
+                        self.files.write(number, &text)?;
+                    }
+                }
+                Ok(None)
+            }
+            Statement::Input { prompt, variable } => {
+                self.context.input_variable = Some(variable.clone());
+                let prompt_text = prompt.as_ref().unwrap_or(&"? ".to_string()).clone();
+                Ok(Some(format!("INPUT {}", prompt_text)))
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition_value = self.evaluate_expression(condition)?;
+                let condition_bool = self.value_to_bool(&condition_value)?;
+
+                if condition_bool {
+                    self.execute_statement_block(then_branch, output, graphics_commands)
+                } else if let Some(else_branch) = else_branch {
+                    self.execute_statement_block(else_branch, output, graphics_commands)
+                } else {
+                    Ok(None)
+                }
+            }
+            Statement::For {
+                variable,
+                start,
+                end,
+                step,
+                body: _,
+            } => {
+                let start_value = self.evaluate_expression(start)?;
+                let end_value = self.evaluate_expression(end)?;
+                let step_value = step
+                    .as_ref()
+                    .map(|s| self.evaluate_expression(s))
+                    .unwrap_or(Ok(Value::Number(1.0)))?;
+
+                let start_num = self.value_to_number(&start_value)?;
+                let end_num = self.value_to_number(&end_value)?;
+                let step_num = self.value_to_number(&step_value)?;
+
+                // Initialize loop variable
+                let var_type = self.context.get_variable_type(variable);
+                let converted_start = self
+                    .convert_value_to_variable_type(&Value::Single(start_num as f32), variable)?;
+                let var_info = self.context.get_variable(variable);
+                var_info.value = converted_start;
+                var_info.declared_type = var_type;
+
+                // Push loop context
+                self.context.for_loops.push(ForLoop {
+                    variable: variable.clone(),
+                    end_value: end_num,
+                    step_value: step_num,
+                    line_index: self.current_line,
+                    body_start: self.current_line + 1,
+                });
+
+                Ok(None)
+            }
+            Statement::Next { variable } => self.handle_next_statement(variable),
+            Statement::Goto { line } => {
+                let line_value = self.evaluate_expression(line)?;
+                let line_num = self.value_to_number(&line_value)? as usize;
+                Ok(Some(format!("GOTO {}", self.jump_target(line_num)?)))
+            }
+            Statement::Gosub { line } => {
+                let line_value = self.evaluate_expression(line)?;
+                let line_num = self.value_to_number(&line_value)? as usize;
+                let target = self.jump_target(line_num)?;
+                self.context.gosub_stack.push(self.current_line);
+                Ok(Some(format!("GOTO {}", target)))
+            }
+            Statement::Return => {
+                let depth = self.context.gosub_stack.len();
+                if let Some(return_line) = self.context.gosub_stack.pop() {
+                    // Event handlers interrupted a statement before it ran
+                    if let Some(&(frame_depth, source)) = self.context.event_frames.last() {
+                        if frame_depth == depth {
+                            self.context.event_frames.pop();
+                            self.context.events.finish(source);
+                            return Ok(Some(format!("GOTO {}", return_line)));
+                        }
+                    }
+                    Ok(Some(format!("GOTO {}", return_line + 1)))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ReturnWithoutGosub,
+                        "RETURN without GOSUB",
+                    ))
+                }
+            }
+            Statement::OnError { line } => {
+                let line_value = self.evaluate_expression(line)?;
+                let line_num = self.value_to_number(&line_value)? as usize;
+                if line_num == 0 {
+                    self.context.error_handler = None;
+                    // Inside a handler, the error it was handling stops the program
+                    if self.context.in_error_handler {
+                        if let Some(trapped) = self.context.last_error.clone() {
+                            self.context.in_error_handler = false;
+                            return Err(trapped.error);
+                        }
+                    }
+                } else {
+                    self.context.error_handler = Some(line_num);
+                }
+                Ok(None)
+            }
+            Statement::Resume { target } => {
+                let failed = match &self.context.last_error {
+                    Some(trapped) if self.context.in_error_handler => trapped.statement,
+                    _ => {
+                        return Err(InterpreterError::runtime(
+                            ErrorCode::ResumeWithoutError,
+                            "RESUME without error",
+                        ))
+                    }
+                };
+                let target = match target {
+                    ResumeTarget::Retry => failed,
+                    ResumeTarget::Next => failed + 1,
+                    ResumeTarget::Line(line) => {
+                        let line_value = self.evaluate_expression(line)?;
+                        let line_num = self.value_to_number(&line_value)? as usize;
+                        self.jump_target(line_num)?
+                    }
+                };
+                self.context.in_error_handler = false;
+                let statement_count = self.program.as_ref().map_or(0, |p| p.statements.len());
+                if target >= statement_count {
+                    return Ok(Some("END".to_string()));
+                }
+                Ok(Some(format!("GOTO {}", target)))
+            }
+            // ERROR n: raise error n as if it had happened here, for ON
+            // ERROR GOTO to trap. A number GW-BASIC has a message for stops
+            // an untrapped program with that message; others with
+            // "Unprintable error".
+            Statement::Error { number } => {
+                let value = self.evaluate_expression(number)?;
+                let number = self.value_to_number(&value)?.round();
+                if !(1.0..=255.0).contains(&number) {
+                    return Err(InterpreterError::RuntimeError(
+                        "ERROR takes an error number from 1 to 255".to_string(),
+                    ));
+                }
+                let number = number as u16;
+                let known = |code: &ErrorCode| code.gw_basic_number() == number;
+                let code = ErrorCode::ALL
+                    .into_iter()
+                    .filter(|code| !code.is_parse())
+                    .find(known)
+                    .or_else(|| ErrorCode::ALL.into_iter().find(known));
+                self.context.raised_error = Some(number);
+                Err(match code {
+                    Some(code) => InterpreterError::runtime(code, code.title()),
+                    None => InterpreterError::runtime(
+                        ErrorCode::Runtime,
+                        format!("Unprintable error {}", number),
+                    ),
+                })
+            }
+            Statement::End => Ok(Some("END".to_string())),
+            Statement::Stop => Ok(Some("STOP".to_string())),
+            Statement::Randomize { seed } => {
+                // RANDOMIZE n repeats the same RND sequence on every run;
+                // RANDOMIZE TIMER (or no seed) gives a new one each time
+                let from_clock = match seed {
+                    None => true,
+                    Some(Expression::FunctionCall { name, arguments }) => {
+                        name == "TIMER" && arguments.is_empty()
+                    }
+                    Some(_) => false,
+                };
+                let seed = match seed {
+                    Some(seed) if !from_clock => {
+                        let value = self.evaluate_expression(seed)?;
+                        self.value_to_number(&value)?.trunc() as i64
+                    }
+                    _ => SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |elapsed| elapsed.as_nanos() as i64),
+                };
+                self.context.random_seed = seed.rem_euclid(RANDOM_MODULUS as i64) as u64;
+                Ok(None)
+            }
+            Statement::Assert { condition, message } => {
+                let value = self.evaluate_expression(condition)?;
+                let passed = self.value_to_bool(&value)?;
+                let message = match message {
+                    Some(message) => {
+                        let value = self.evaluate_expression(message)?;
+                        self.value_to_string(&value)
+                    }
+                    None => "condition is false".to_string(),
+                };
+                let line = self.current_source_line().unwrap_or(0);
+                // A failure is reported and recorded, and the program carries on
+                if !passed {
+                    output.push_str(&format!("ASSERT failed at line {}: {}\n", line, message));
+                }
+                self.assertions.push(AssertionResult {
+                    line,
+                    passed,
+                    message,
+                });
+                Ok(None)
+            }
+            Statement::Rem(_) => Ok(None), // Comments do nothing
+            Statement::Dim { arrays } => {
+                for (name, dimensions) in arrays {
+                    self.create_array(name, dimensions)?;
+                }
+                Ok(None)
+            }
+            Statement::Def {
+                name,
+                parameters,
+                body,
+            } => {
+                self.context.functions.insert(
+                    name.clone(),
+                    FunctionDefinition {
+                        parameters: parameters.clone(),
+                        body: body.clone(),
+                    },
+                );
+                Ok(None)
+            }
+            Statement::Clear => {
+                self.context.variables.clear();
+                self.context.type_declarations.clear();
+                output.push_str("Variables cleared\n");
+                Ok(None)
+            }
+            Statement::Cls => {
+                output.clear();
+                self.cleared_screen = true;
+                Ok(None)
+            }
+            Statement::Writeln { expression } => {
+                let value = self.evaluate_expression(expression)?;
+                let value_str = self.value_to_string(&value);
+                output.push_str(&value_str);
+                output.push('\n');
+                Ok(None)
+            }
+            Statement::Printx { expression } => {
+                let value = self.evaluate_expression(expression)?;
+                let value_str = self.value_to_string(&value);
+                output.push_str(&value_str);
+                Ok(None)
+            }
+            Statement::DefInt { ranges } => {
+                for range in ranges {
+                    self.set_type_declaration(range, VariableType::Integer)?;
+                }
+                Ok(None)
+            }
+            Statement::DefSng { ranges } => {
+                for range in ranges {
+                    self.set_type_declaration(range, VariableType::Single)?;
+                }
+                Ok(None)
+            }
+            Statement::DefStr { ranges } => {
+                for range in ranges {
+                    self.set_type_declaration(range, VariableType::String)?;
+                }
+                Ok(None)
+            }
+            Statement::DefDbl { ranges } => {
+                for range in ranges {
+                    self.set_type_declaration(range, VariableType::Double)?;
+                }
+                Ok(None)
+            }
+            Statement::Select { expression, cases } => {
+                let select_value = self.evaluate_expression(expression)?;
+
+                for case in cases {
+                    let matches = if let Some(case_value_expr) = &case.value {
+                        let case_value = self.evaluate_expression(case_value_expr)?;
+                        self.values_equal(&select_value, &case_value)?
+                    } else {
+                        // CASE ELSE always matches
+                        true
+                    };
+
+                    if matches {
+                        return self.execute_statement_block(
+                            &case.statements,
+                            output,
+                            graphics_commands,
+                        );
+                    }
+                }
+                Ok(None)
+            }
+            Statement::Forward { distance } => {
+                let dist = self.evaluate_expression(distance)?;
+                let dist_num = self.value_to_number(&dist)?;
+                graphics_commands.push(self.tag(if self.pen_down() {
+                    GraphicsCommand::Forward(dist_num as f32)
+                } else {
+                    GraphicsCommand::Move(dist_num as f32)
+                }));
+                output.push_str(&format!("Moved forward {}\n", dist_num));
+                Ok(None)
+            }
+            Statement::Back { distance } => {
+                let dist = self.evaluate_expression(distance)?;
+                let dist_num = self.value_to_number(&dist)?;
+                graphics_commands.push(self.tag(if self.pen_down() {
+                    GraphicsCommand::Back(dist_num as f32)
+                } else {
+                    GraphicsCommand::Move(-dist_num as f32)
+                }));
+                output.push_str(&format!("Moved back {}\n", dist_num));
+                Ok(None)
+            }
+            Statement::TurnLeft { angle } => {
+                let ang = self.evaluate_expression(angle)?;
+                let ang_num = self.value_to_number(&ang)?;
+                graphics_commands.push(self.tag(GraphicsCommand::Left(ang_num as f32)));
+                output.push_str(&format!("Turned left by {} degrees\n", ang_num));
+                Ok(None)
+            }
+            Statement::TurnRight { angle } => {
+                let ang = self.evaluate_expression(angle)?;
+                let ang_num = self.value_to_number(&ang)?;
+                graphics_commands.push(self.tag(GraphicsCommand::Right(ang_num as f32)));
+                output.push_str(&format!("Turned right {}\n", ang_num));
+                Ok(None)
+            }
+            Statement::Penup => {
+                self.context.pens_up.insert(self.context.turtle.clone());
+                output.push_str("Pen up\n");
+                Ok(None)
+            }
+            Statement::Pendown => {
+                self.context.pens_up.remove(&self.context.turtle);
+                output.push_str("Pen down\n");
+                Ok(None)
+            }
+            Statement::Home => {
+                graphics_commands.push(self.tag(GraphicsCommand::Home));
+                output.push_str("Moved to home position\n");
+                Ok(None)
+            }
+            Statement::Setxy { x, y } => {
+                let x_val = self.evaluate_expression(x)?;
+                let y_val = self.evaluate_expression(y)?;
+                let x_num = self.value_to_number(&x_val)?;
+                let y_num = self.value_to_number(&y_val)?;
+                graphics_commands.push(self.tag(if self.pen_down() {
+                    GraphicsCommand::SetXY(x_num as f32, y_num as f32)
+                } else {
+                    GraphicsCommand::MoveTo(x_num as f32, y_num as f32)
+                }));
+                output.push_str(&format!("Moved to ({}, {})\n", x_num, y_num));
+                Ok(None)
+            }
+            Statement::SetHeading { angle } => {
+                let ang = self.evaluate_expression(angle)?;
+                let ang_num = self.value_to_number(&ang)?;
+                graphics_commands.push(self.tag(GraphicsCommand::SetHeading(ang_num as f32)));
+                output.push_str(&format!("Heading set to {} degrees\n", ang_num));
+                Ok(None)
+            }
+            Statement::Turtle { name } => {
+                let name = self.evaluate_expression(name)?;
+                let name = self.value_to_string(&name).trim().to_uppercase();
+                if name.is_empty() {
+                    return Err(InterpreterError::RuntimeError(
+                        "TURTLE needs a name, e.g. TURTLE \"A\"".to_string(),
+                    ));
+                }
+                output.push_str(&format!("Using turtle {}\n", name));
+                self.context.turtle = name;
+                Ok(None)
+            }
+            Statement::OnEvent {
+                event,
+                argument,
+                line,
+            } => {
+                let sources = self.event_sources(*event, argument, false)?;
+                let amount = match (event, argument) {
+                    (EventKind::Timer | EventKind::Play, Some(argument)) => {
+                        let value = self.evaluate_expression(argument)?;
+                        self.value_to_number(&value)?
+                    }
+                    (EventKind::Timer | EventKind::Play, None) => {
+                        return Err(InterpreterError::RuntimeError(
+                            "ON TIMER and ON PLAY need a value, e.g. ON TIMER(1) GOSUB 1000"
+                                .to_string(),
+                        ));
+                    }
+                    _ => 0.0,
+                };
+                match event {
+                    EventKind::Timer if amount > 0.0 => self
+                        .context
+                        .events
+                        .set_timer_interval(Duration::from_secs_f64(amount), Instant::now()),
+                    EventKind::Timer => {
+                        return Err(InterpreterError::RuntimeError(
+                            "ON TIMER needs a number of seconds greater than 0".to_string(),
+                        ));
+                    }
+                    EventKind::Play => self.context.events.set_play_threshold(amount as usize),
+                    _ => {}
+                }
+
+                let line_value = self.evaluate_expression(line)?;
+                let line_num = self.value_to_number(&line_value)? as usize;
+                let handler = (line_num != 0).then_some(line_num);
+                for source in sources {
+                    self.context.events.set_handler(source, handler);
+                }
+                Ok(None)
+            }
+            Statement::EventControl {
+                event,
+                argument,
+                state,
+            } => {
+                for source in self.event_sources(*event, argument, true)? {
+                    self.context
+                        .events
+                        .set_state(source, *state, Instant::now());
+                }
+                Ok(None)
+            }
+            Statement::ClearScreen => {
+                graphics_commands.push(self.tag(GraphicsCommand::ClearScreen));
+                output.push_str("Cleared the screen\n");
+                Ok(None)
+            }
+            Statement::Turn { angle } => {
+                let ang = self.evaluate_expression(angle)?;
+                let ang_num = self.value_to_number(&ang)?;
+                graphics_commands.push(self.tag(GraphicsCommand::Turn(ang_num as f32)));
+                output.push_str(&format!("Turned by {} degrees\n", ang_num));
+                Ok(None)
+            }
+            Statement::SetPenColor { components } => {
+                let (r, g, b) = self.evaluate_color(components, "SETPENCOLOR")?;
+                graphics_commands.push(self.tag(GraphicsCommand::SetPenColor(r, g, b)));
+                output.push_str(&format!("Pen colour set to ({}, {}, {})\n", r, g, b));
+                Ok(None)
+            }
+            Statement::SetBackground { components } => {
+                let (r, g, b) = self.evaluate_color(components, "SETBACKGROUND")?;
+                graphics_commands.push(self.tag(GraphicsCommand::SetBackground(r, g, b)));
+                output.push_str(&format!("Background set to ({}, {}, {})\n", r, g, b));
+                Ok(None)
+            }
+            Statement::PenSize { size } => {
+                let size = self.evaluate_expression(size)?;
+                let size_num = self.value_to_number(&size)?;
+                if size_num <= 0.0 {
+                    return Err(InterpreterError::RuntimeError(format!(
+                        "Pen size must be positive, got {}",
+                        size_num
+                    )));
+                }
+                graphics_commands.push(self.tag(GraphicsCommand::SetPenSize(size_num as f32)));
+                output.push_str(&format!("Pen size set to {}\n", size_num));
+                Ok(None)
+            }
+            Statement::Arc { radius, angle } => {
+                let radius = self.evaluate_expression(radius)?;
+                let radius = self.arc_radius(&radius)?;
+                let angle = self.evaluate_expression(angle)?;
+                let angle = self.value_to_number(&angle)?;
+                if self.pen_down() {
+                    graphics_commands.push(self.tag(GraphicsCommand::Arc(radius, angle as f32)));
+                }
+                output.push_str(&format!(
+                    "Drew an arc of {} degrees, radius {}\n",
+                    angle, radius
+                ));
+                Ok(None)
+            }
+            Statement::Circle { radius } => {
+                let radius = self.evaluate_expression(radius)?;
+                let radius = self.arc_radius(&radius)?;
+                if self.pen_down() {
+                    graphics_commands.push(self.tag(GraphicsCommand::Arc(radius, 360.0)));
+                }
+                output.push_str(&format!("Drew a circle, radius {}\n", radius));
+                Ok(None)
+            }
+            Statement::Label { text } => {
+                let text = self.evaluate_expression(text)?;
+                let text = self.value_to_string(&text);
+                output.push_str(&format!("Wrote label \"{}\"\n", text));
+                graphics_commands.push(self.tag(GraphicsCommand::Label(text)));
+                Ok(None)
+            }
+            Statement::LabelSize { size } => {
+                let size = self.evaluate_expression(size)?;
+                let size_num = self.value_to_number(&size)?;
+                if size_num <= 0.0 {
+                    return Err(InterpreterError::RuntimeError(format!(
+                        "Label size must be positive, got {}",
+                        size_num
+                    )));
+                }
+                graphics_commands.push(self.tag(GraphicsCommand::SetLabelSize(size_num as f32)));
+                output.push_str(&format!("Label size set to {}\n", size_num));
+                Ok(None)
+            }
+            Statement::HideTurtle => {
+                graphics_commands.push(self.tag(GraphicsCommand::ShowTurtle(false)));
+                output.push_str("Turtle hidden\n");
+                Ok(None)
+            }
+            Statement::ShowTurtle => {
+                graphics_commands.push(self.tag(GraphicsCommand::ShowTurtle(true)));
+                output.push_str("Turtle shown\n");
+                Ok(None)
+            }
+            Statement::Fill => {
+                graphics_commands.push(self.tag(GraphicsCommand::Fill));
+                output.push_str("Filled the shape around the turtle\n");
+                Ok(None)
+            }
+            Statement::Open {
+                file,
+                mode,
+                number,
+                length,
+            } => {
+                let name = self.evaluate_expression(file)?;
+                let name = self.value_to_string(&name);
+                let number = self.file_number(number)?;
+                if *mode != FileMode::Input {
+                    if let Some(ask) = self.ask_first(Permission::WriteFiles)? {
+                        return Ok(Some(ask));
+                    }
+                }
+                let length = match length {
+                    Some(length) => self.file_number(length)? as usize,
+                    None => DEFAULT_RECORD_LENGTH,
+                };
+                self.files.open(&name, *mode, number, length)?;
+                Ok(None)
+            }
+            Statement::Close { numbers } => {
+                if numbers.is_empty() {
+                    self.files.close_all();
+                }
+                for number in numbers {
+                    let number = self.file_number(number)?;
+                    self.files.close(number);
+                }
+                Ok(None)
+            }
+            Statement::Write { file, expressions } => {
+                // Text in quotes and values separated by commas, so INPUT # reads them back
+                let mut fields = Vec::new();
+                for expression in expressions {
+                    let value = self.evaluate_expression(expression)?;
+                    fields.push(match value {
+                        Value::String(text) => format!("\"{}\"", text),
+                        value => self.value_to_string(&value),
+                    });
+                }
+                let line = format!("{}\n", fields.join(","));
+                match file {
+                    Some(number) => {
+                        let number = self.file_number(number)?;
+                        self.files.write(number, &line)?;
+                    }
+                    None => output.push_str(&line),
+                }
+                Ok(None)
+            }
+            Statement::FileInput { number, variables } => {
+                let number = self.file_number(number)?;
+                for variable in variables {
+                    let field = self.files.read_field(number)?;
+                    let value = if self.context.get_variable_type(variable) == VariableType::String
+                    {
+                        Value::String(field)
+                    } else if field.is_empty() {
+                        Value::Number(0.0)
+                    } else {
+                        Value::Number(field.parse::<f64>().map_err(|_| {
+                            InterpreterError::TypeError(format!(
+                                "INPUT #{} read \"{}\", which isn't a number for {}",
+                                number, field, variable
+                            ))
+                        })?)
+                    };
+                    self.store_variable(variable, &value)?;
+                }
+                Ok(None)
+            }
+            Statement::LineInput { number, variable } => {
+                let number = self.file_number(number)?;
+                let line = self.files.read_line(number)?;
+                self.store_variable(variable, &Value::String(line))?;
+                Ok(None)
+            }
+            Statement::Field { number, fields } => {
+                let number = self.file_number(number)?;
+                let mut widths = Vec::new();
+                for (width, variable) in fields {
+                    widths.push((self.file_number(width)? as usize, variable.clone()));
+                }
+                let values = self.files.field(number, &widths)?;
+                self.store_fields(values)?;
+                Ok(None)
+            }
+            Statement::SetField {
+                justify,
+                variable,
+                expression,
+            } => {
+                let Value::String(text) = self.evaluate_expression(expression)? else {
+                    return Err(InterpreterError::TypeError(format!(
+                        "LSET and RSET put text in {}; use STR$ or MKI$ for a number",
+                        variable
+                    )));
+                };
+                let value = match self.files.set_field(variable, &text, *justify) {
+                    Some(value) => value,
+                    // A variable in no FIELD keeps its length, as in GW-BASIC
+                    None => {
+                        let width = match &self.context.get_variable(variable).value {
+                            Value::String(current) => current.chars().count(),
+                            _ => 0,
+                        };
+                        let fitted: String = text.chars().take(width).collect();
+                        match justify {
+                            Justify::Left => format!("{:<1$}", fitted, width),
+                            Justify::Right => format!("{:>1$}", fitted, width),
+                        }
+                    }
+                };
+                self.store_variable(variable, &Value::String(value))?;
+                Ok(None)
+            }
+            Statement::Get { number, record } => {
+                let number = self.file_number(number)?;
+                let record = self.record_number(record)?;
+                let values = self.files.get(number, record)?;
+                self.store_fields(values)?;
+                Ok(None)
+            }
+            Statement::Put { number, record } => {
+                let number = self.file_number(number)?;
+                let record = self.record_number(record)?;
+                self.files.put(number, record)?;
+                Ok(None)
+            }
+            Statement::Files { pattern } => {
+                let pattern = match pattern {
+                    Some(pattern) => Some(self.file_name(pattern)?),
+                    None => None,
+                };
+                let listing = self.files.list(pattern.as_deref())?;
+                output.push_str(&format!("{}\n", self.files.current_dir()));
+                for entry in listing {
+                    if entry.folder {
+                        output.push_str(&format!("{:<12} <DIR>\n", entry.name));
+                    } else {
+                        output.push_str(&format!("{}\n", entry.name));
+                    }
+                }
+                Ok(None)
+            }
+            Statement::Kill { pattern } => {
+                let pattern = self.file_name(pattern)?;
+                if let Some(ask) = self.ask_first(Permission::DeleteFiles)? {
+                    return Ok(Some(ask));
+                }
+                self.files.kill(&pattern)?;
+                Ok(None)
+            }
+            Statement::Name { from, to } => {
+                let from = self.file_name(from)?;
+                let to = self.file_name(to)?;
+                if let Some(ask) = self.ask_first(Permission::WriteFiles)? {
+                    return Ok(Some(ask));
+                }
+                self.files.rename(&from, &to)?;
+                Ok(None)
+            }
+            Statement::DefSeg { segment } => {
+                let segment = match segment {
+                    Some(segment) => {
+                        let value = self.evaluate_expression(segment)?;
+                        Some(self.value_to_number(&value)?)
+                    }
+                    None => None,
+                };
+                self.memory.set_segment(segment)?;
+                Ok(None)
+            }
+            Statement::Sleep { seconds } => {
+                let seconds = match seconds {
+                    Some(seconds) => {
+                        let seconds = self.evaluate_expression(seconds)?;
+                        self.value_to_number(&seconds)?
+                    }
+                    None => 0.0,
+                };
+                let duration = if seconds == 0.0 {
+                    None
+                } else {
+                    Some(pause_length(seconds, "SLEEP")?)
+                };
+                self.pause(duration, true);
+                Ok(None)
+            }
+            Statement::Wait { ticks } => {
+                let ticks = self.evaluate_expression(ticks)?;
+                let ticks = self.value_to_number(&ticks)?;
+                self.pause(Some(pause_length(ticks / 60.0, "WAIT")?), false);
+                Ok(None)
+            }
+            Statement::Play { music } => {
+                let music = self.evaluate_expression(music)?;
+                let music = self.value_to_string(&music);
+                let length = self.music.play(&music, Instant::now())?;
+                self.wait_for_music(length);
+                Ok(None)
+            }
+            Statement::Beep => {
+                let length = self.music.beep(Instant::now());
+                self.wait_for_music(length);
+                Ok(None)
+            }
+            Statement::Sound { frequency, ticks } => {
+                let frequency = self.evaluate_expression(frequency)?;
+                let frequency = self.value_to_number(&frequency)?;
+                let ticks = self.evaluate_expression(ticks)?;
+                let ticks = self.value_to_number(&ticks)?;
+                let length = self.music.sound(frequency, ticks, Instant::now())?;
+                self.wait_for_music(length);
+                Ok(None)
+            }
+            Statement::Poke { address, value } => {
+                let address = self.evaluate_expression(address)?;
+                let address = self
+                    .memory
+                    .address(self.value_to_number(&address)?, "POKE")?;
+                let value = self.evaluate_expression(value)?;
+                let value = memory::byte(self.value_to_number(&value)?)?;
+                self.poke(address, value, graphics_commands);
+                Ok(None)
+            }
+            Statement::Folder { action, dir } => {
+                let dir = self.file_name(dir)?;
+                let permission = match action {
+                    FolderAction::Make => Some(Permission::WriteFiles),
+                    FolderAction::Change => None,
+                    FolderAction::Remove => Some(Permission::DeleteFiles),
+                };
+                if let Some(permission) = permission {
+                    if let Some(ask) = self.ask_first(permission)? {
+                        return Ok(Some(ask));
+                    }
+                }
+                match action {
+                    FolderAction::Make => self.files.make_dir(&dir)?,
+                    FolderAction::Change => self.files.change_dir(&dir)?,
+                    FolderAction::Remove => self.files.remove_dir(&dir)?,
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// SLEEP or WAIT: a live run hands control back until it is over.
+    /// Other runs (the CLI, grading, the language server and debug adapter)
+    /// don't wait: the pause is charged to the instruction limit instead,
+    /// so a program that sleeps too long ends with a timeout. There are no
+    /// keys to wait for either.
+    fn pause(&mut self, duration: Option<Duration>, wake_on_key: bool) {
+        if self.pump.live() {
+            self.pump.sleep(duration, wake_on_key);
+        } else if let Some(duration) = duration {
+            let charged = duration
+                .as_millis()
+                .saturating_mul(INSTRUCTIONS_PER_MILLISECOND as u128);
+            self.instruction_count = self
+                .instruction_count
+                .saturating_add(charged.try_into().unwrap_or(usize::MAX));
+        }
+    }
+
+    /// PLAY, SOUND or BEEP in the foreground: only a live run waits for
+    /// the music; others just queue it
+    fn wait_for_music(&mut self, length: Duration) {
+        if self.pump.live() && !length.is_zero() {
+            self.pump.sleep(Some(length), false);
+        }
+    }
+
+    /// The byte PEEK finds at `address`
+    fn peek(&self, address: u32) -> u8 {
+        match memory::mapped(address) {
+            Some(Mapped::ScreenColumns) => {
+                self.dialect.screen().map_or(80, |screen| screen.columns) as u8
+            }
+            Some(Mapped::ClockTick(byte)) => {
+                let since_midnight = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64()
+                    % 86400.0;
+                let ticks = (since_midnight * 18.2065) as u32;
+                ticks.to_le_bytes()[byte]
+            }
+            Some(Mapped::RandomSeed(byte)) => (self.context.random_seed as u32).to_le_bytes()[byte],
+            Some(Mapped::Background) | None => self.memory.read(address),
+        }
+    }
+
+    /// Store `value` at `address`; the screen width and clock can't be changed
+    fn poke(&mut self, address: u32, value: u8, graphics_commands: &mut Vec<TurtleCommand>) {
+        match memory::mapped(address) {
+            Some(Mapped::ScreenColumns | Mapped::ClockTick(_)) => {}
+            Some(Mapped::RandomSeed(byte)) => {
+                let mut seed = (self.context.random_seed as u32).to_le_bytes();
+                seed[byte] = value;
+                self.context.random_seed = u32::from_le_bytes(seed) as u64 % RANDOM_MODULUS;
+            }
+            Some(Mapped::Background) => {
+                self.memory.write(address, value);
+                let (r, g, b) = C64_PALETTE[value as usize % C64_PALETTE.len()];
+                graphics_commands.push(self.tag(GraphicsCommand::SetBackground(r, g, b)));
+            }
+            None => self.memory.write(address, value),
+        }
+    }
+
+    /// None when the statement may go ahead with what needs `permission`;
+    /// otherwise the run stops so the IDE can ask
+    fn ask_first(&self, permission: Permission) -> Result<Option<String>, InterpreterError> {
+        Ok((!self.files.permitted(permission)?)
+            .then(|| format!("PERMISSION {}", permission.name())))
+    }
+
+    /// The file or folder name `expression` gives
+    fn file_name(&mut self, expression: &Expression) -> Result<String, InterpreterError> {
+        let value = self.evaluate_expression(expression)?;
+        Ok(self.value_to_string(&value))
+    }
+
+    /// The file number `expression` gives, for OPEN, CLOSE and the # statements
+    fn file_number(&mut self, expression: &Expression) -> Result<u32, InterpreterError> {
+        let value = self.evaluate_expression(expression)?;
+        Ok(self.value_to_number(&value)?.round().max(0.0) as u32)
+    }
+
+    /// The record GET or PUT names; None for the one after the last
+    fn record_number(
+        &mut self,
+        record: &Option<Expression>,
+    ) -> Result<Option<u64>, InterpreterError> {
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        let value = self.evaluate_expression(record)?;
+        let record = self.value_to_number(&value)?.round();
+        if !(1.0..=MAX_RECORD_NUMBER as f64).contains(&record) {
+            return Err(InterpreterError::runtime(
+                ErrorCode::BadRecordNumber,
+                format!(
+                    "Record numbers go from 1 to {}, not {}",
+                    MAX_RECORD_NUMBER, record
+                ),
+            ));
+        }
+        Ok(Some(record as u64))
+    }
+
+    /// Give the FIELD variables what the record holds for them
+    fn store_fields(&mut self, values: Vec<(String, String)>) -> Result<(), InterpreterError> {
+        for (variable, text) in values {
+            self.store_variable(&variable, &Value::String(text))?;
+        }
+        Ok(())
+    }
+
+    /// Give `variable` the value read from a file, converted to its type
+    fn store_variable(&mut self, variable: &str, value: &Value) -> Result<(), InterpreterError> {
+        let var_type = self.context.get_variable_type(variable);
+        let converted_value = self.convert_value_to_variable_type(value, variable)?;
+        let var_info = self.context.get_variable(variable);
+        var_info.value = converted_value;
+        var_info.declared_type = var_type;
+        Ok(())
+    }
+
+    fn execute_statement_block(
+        &mut self,
+        statements: &[Statement],
+        output: &mut String,
+        graphics_commands: &mut Vec<TurtleCommand>,
+    ) -> Result<Option<String>, InterpreterError> {
+        for statement in statements {
+            // Control flow (GOTO, RETURN, END...) inside a block ends the block
+            if let Some(special_result) =
+                self.execute_statement(statement, output, graphics_commands)?
+            {
+                return Ok(Some(special_result));
+            }
+        }
+        Ok(None)
+    }
+
+    fn handle_next_statement(
+        &mut self,
+        variable: &Option<String>,
+    ) -> Result<Option<String>, InterpreterError> {
+        if let Some(for_loop) = self.context.for_loops.last() {
+            let loop_var = for_loop.variable.clone();
+            let loop_end = for_loop.end_value;
+            let loop_step = for_loop.step_value;
+
+            // Check if variable matches (if specified)
+            if let Some(var_name) = variable {
+                if *var_name != loop_var {
+                    return Err(InterpreterError::runtime(
+                        ErrorCode::NextWithoutFor,
+                        format!("NEXT {} does not match FOR {}", var_name, loop_var),
+                    ));
+                }
+            }
+
+            // Get current value
+            let var_info = self.context.get_variable(&loop_var);
+            let current_value = var_info.value.clone();
+            let current_num = self.value_to_number(&current_value)?;
+
+            // Increment
+            let new_value = current_num + loop_step;
+            let var_type = self.context.get_variable_type(&loop_var);
+            let converted_value =
+                self.convert_value_to_variable_type(&Value::Single(new_value as f32), &loop_var)?;
+            let var_info_mut = self.context.get_variable(&loop_var);
+            var_info_mut.value = converted_value;
+            var_info_mut.declared_type = var_type;
+
+            // Check if loop should continue
+            let should_continue = if loop_step >= 0.0 {
+                new_value <= loop_end
+            } else {
+                new_value >= loop_end
+            };
+
+            if should_continue {
+                // Continue loop - jump back to the first statement after FOR
+                if let Some(for_loop) = self.context.for_loops.last() {
+                    self.current_line = for_loop.body_start;
+                    Ok(Some("CONTINUE_LOOP".to_string()))
+                } else {
+                    Err(InterpreterError::RuntimeError(
+                        "FOR loop state corrupted".to_string(),
+                    ))
+                }
+            } else {
+                // Exit loop
+                self.context.for_loops.pop();
+                Ok(None)
+            }
+        } else {
+            Err(InterpreterError::runtime(
+                ErrorCode::NextWithoutFor,
+                "NEXT without FOR",
+            ))
+        }
+    }
+
+    fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, InterpreterError> {
+        match expression {
+            Expression::Number(n) => Ok(Value::Number(*n)),
+            Expression::String(s) => Ok(Value::String(s.clone())),
+            Expression::Variable(name) => {
+                let var_info = self.context.get_variable(name);
+                Ok(var_info.value.clone())
+            }
+            Expression::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                let left_val = self.evaluate_expression(left)?;
+                let right_val = self.evaluate_expression(right)?;
+                self.evaluate_binary_op(*operator, &left_val, &right_val)
+            }
+            Expression::UnaryOp { operator, operand } => {
+                let operand_val = self.evaluate_expression(operand)?;
+                self.evaluate_unary_op(*operator, &operand_val)
+            }
+            Expression::FunctionCall { name, arguments } => {
+                let mut arg_values = Vec::new();
+                for arg in arguments {
+                    arg_values.push(self.evaluate_expression(arg)?);
+                }
+                self.evaluate_function(name, &arg_values)
+            }
+            Expression::ArrayAccess { name, index } => {
+                let index_val = self.evaluate_expression(index)?;
+                let index_num = self.value_to_number(&index_val)? as usize;
+                self.get_array_element(name, index_num)
+            }
+        }
+    }
+
+    fn evaluate_binary_op(
+        &self,
+        operator: BinaryOperator,
+        left: &Value,
+        right: &Value,
+    ) -> Result<Value, InterpreterError> {
+        match operator {
+            BinaryOperator::Add => match (left, right) {
+                (Value::String(l), Value::String(r)) => Ok(Value::String(format!("{}{}", l, r))),
+                (Value::String(_), _) | (_, Value::String(_)) => Err(InterpreterError::TypeError(
+                    "Invalid types for addition".to_string(),
+                )),
+                _ => Ok(Value::Number(
+                    self.value_to_number(left)? + self.value_to_number(right)?,
+                )),
+            },
+            BinaryOperator::Subtract => {
+                let l = self.value_to_number(left)?;
+                let r = self.value_to_number(right)?;
+                Ok(Value::Number(l - r))
+            }
+            BinaryOperator::Multiply => {
+                let l = self.value_to_number(left)?;
+                let r = self.value_to_number(right)?;
+                Ok(Value::Number(l * r))
+            }
+            BinaryOperator::Divide => {
+                let l = self.value_to_number(left)?;
+                let r = self.value_to_number(right)?;
+                if r == 0.0 {
+                    return Err(InterpreterError::DivisionByZero);
+                }
+                Ok(Value::Number(l / r))
+            }
+            BinaryOperator::Modulo => {
+                let l = self.value_to_number(left)?;
+                let r = self.value_to_number(right)?;
+                Ok(Value::Number(l % r))
+            }
+            BinaryOperator::Power => {
+                let l = self.value_to_number(left)?;
+                let r = self.value_to_number(right)?;
+                Ok(Value::Number(l.powf(r)))
+            }
+            BinaryOperator::Equal => {
+                let result = self.compare_values(left, right)?;
+                Ok(Value::Number(if result == 0 { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::NotEqual => {
+                let result = self.compare_values(left, right)?;
+                Ok(Value::Number(if result != 0 { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::Less => {
+                let result = self.compare_values(left, right)?;
+                Ok(Value::Number(if result < 0 { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::LessEqual => {
+                let result = self.compare_values(left, right)?;
+                Ok(Value::Number(if result <= 0 { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::Greater => {
+                let result = self.compare_values(left, right)?;
+                Ok(Value::Number(if result > 0 { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::GreaterEqual => {
+                let result = self.compare_values(left, right)?;
+                Ok(Value::Number(if result >= 0 { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::And => {
+                let l = self.value_to_bool(left)?;
+                let r = self.value_to_bool(right)?;
+                Ok(Value::Number(if l && r { -1.0 } else { 0.0 }))
+            }
+            BinaryOperator::Or => {
+                let l = self.value_to_bool(left)?;
+                let r = self.value_to_bool(right)?;
+                Ok(Value::Number(if l || r { -1.0 } else { 0.0 }))
+            }
+        }
+    }
+
+    fn evaluate_unary_op(
+        &self,
+        operator: UnaryOperator,
+        operand: &Value,
+    ) -> Result<Value, InterpreterError> {
+        match operator {
+            UnaryOperator::Negate => {
+                let num = self.value_to_number(operand)?;
+                Ok(Value::Number(-num))
+            }
+            UnaryOperator::Not => {
+                let bool_val = self.value_to_bool(operand)?;
+                Ok(Value::Number(if bool_val { 0.0 } else { -1.0 }))
+            }
+        }
+    }
+
+    fn evaluate_function(
+        &mut self,
+        name: &str,
+        arguments: &[Value],
+    ) -> Result<Value, InterpreterError> {
+        match name.to_uppercase().as_str() {
+            "SIN" => self.math_function(arguments, |x| x.sin()),
+            "COS" => self.math_function(arguments, |x| x.cos()),
+            "TAN" => self.math_function(arguments, |x| x.tan()),
+            "SQR" => self.math_function(arguments, |x| x.sqrt()),
+            "ABS" => self.math_function(arguments, |x| x.abs()),
+            "INT" => self.math_function(arguments, |x| x.floor()),
+            "RND" => {
+                if arguments.is_empty() || arguments.len() == 1 {
+                    // Generate random number
+                    let random_val = (self.context.random_seed as f64 * 9301.0 + 49297.0)
+                        % RANDOM_MODULUS as f64
+                        / RANDOM_MODULUS as f64;
+                    self.context.random_seed =
+                        (self.context.random_seed * 9301 + 49297) % RANDOM_MODULUS;
+                    Ok(Value::Number(random_val))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "RND takes 0 or 1 arguments",
+                    ))
+                }
+            }
+            "LEN" => {
+                if arguments.len() == 1 {
+                    if let Value::String(s) = &arguments[0] {
+                        Ok(Value::Number(s.len() as f64))
+                    } else {
+                        Err(InterpreterError::TypeError(
+                            "LEN requires string argument".to_string(),
+                        ))
+                    }
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "LEN requires 1 argument",
+                    ))
+                }
+            }
+            "VAL" => match arguments {
+                // The number the text starts with, or 0 when it doesn't start with one
+                [Value::String(s)] => {
+                    let s = s.trim_start();
+                    let s = &s[..s
+                        .find(|c: char| !(c.is_ascii_digit() || "+-.Ee".contains(c)))
+                        .unwrap_or(s.len())];
+                    let number = (0..=s.len())
+                        .rev()
+                        .find_map(|end| s[..end].parse::<f64>().ok())
+                        .unwrap_or(0.0);
+                    Ok(Value::Number(number))
+                }
+                [_] => Err(InterpreterError::TypeError(
+                    "VAL requires string argument".to_string(),
+                )),
+                _ => Err(InterpreterError::runtime(
+                    ErrorCode::ArgumentCount,
+                    "VAL requires 1 argument",
+                )),
+            },
+            "STR$" => match arguments {
+                // A space stands where a minus sign would go, as in GW-BASIC
+                [Value::String(_)] => Err(InterpreterError::TypeError(
+                    "STR$ requires numeric argument".to_string(),
+                )),
+                [value] => {
+                    let number = self.value_to_number(value)?;
+                    let text = self.value_to_string(value);
+                    Ok(Value::String(if number < 0.0 {
+                        text
+                    } else {
+                        format!(" {}", text)
+                    }))
+                }
+                _ => Err(InterpreterError::runtime(
+                    ErrorCode::ArgumentCount,
+                    "STR$ requires 1 argument",
+                )),
+            },
+            "MATCH" => {
+                if arguments.len() == 2 {
+                    let answer = self.value_to_string(&arguments[0]);
+                    let pattern = self.value_to_string(&arguments[1]);
+                    Ok(Value::Number(
+                        matching::match_answer(&answer, &pattern) as f64
+                    ))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "MATCH requires 2 arguments: answer$, pattern$",
+                    ))
+                }
+            }
+            "SOUNDEX$" => {
+                if arguments.len() == 1 {
+                    let text = self.value_to_string(&arguments[0]);
+                    Ok(Value::String(matching::soundex(&text)))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "SOUNDEX$ requires 1 argument",
+                    ))
+                }
+            }
+            "SIMILAR" => {
+                if arguments.len() == 2 {
+                    let a = self.value_to_string(&arguments[0]);
+                    let b = self.value_to_string(&arguments[1]);
+                    Ok(Value::Number(matching::similarity(&a, &b)))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "SIMILAR requires 2 arguments",
+                    ))
+                }
+            }
+            "DATE$" => {
+                if arguments.is_empty() {
+                    let now = SystemTime::now();
+                    let datetime: chrono::DateTime<chrono::Utc> = now.into();
+                    let date_str = datetime.format("%m-%d-%Y").to_string();
+                    Ok(Value::String(date_str))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "DATE$ takes no arguments",
+                    ))
+                }
+            }
+            "TIME$" => {
+                if arguments.is_empty() {
+                    let now = SystemTime::now();
+                    let datetime: chrono::DateTime<chrono::Utc> = now.into();
+                    let time_str = datetime.format("%H:%M:%S").to_string();
+                    Ok(Value::String(time_str))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "TIME$ takes no arguments",
+                    ))
+                }
+            }
+            "INKEY$" => {
+                if arguments.is_empty() {
+                    let key = self.keyboard.read();
+                    match key {
+                        Some(_) => self.pump.stop_waiting(),
+                        None => self.pump.wait(),
+                    }
+                    Ok(Value::String(key.unwrap_or_default()))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "INKEY$ takes no arguments",
+                    ))
+                }
+            }
+            "MOUSEX" | "MOUSEY" | "MOUSEB" => {
+                if !arguments.is_empty() {
+                    return Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        format!("{} takes no arguments", name.to_uppercase()),
+                    ));
+                }
+                self.pump.wait();
+                Ok(Value::Number(match name.to_uppercase().as_str() {
+                    "MOUSEX" => self.mouse.x.round(),
+                    "MOUSEY" => self.mouse.y.round(),
+                    _ => self.mouse.buttons as f64,
+                }))
+            }
+            "TIMER" => {
+                if arguments.is_empty() {
+                    let now = SystemTime::now();
+                    let duration = now.duration_since(UNIX_EPOCH).unwrap();
+                    let seconds = duration.as_secs() % 86400; // Seconds since midnight
+                    Ok(Value::Number(seconds as f64))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "TIMER takes no arguments",
+                    ))
+                }
+            }
+            // The last error ON ERROR GOTO trapped: its GW-BASIC number and
+            // line number, both 0 before any
+            "ERR" => Ok(Value::Number(
+                self.context
+                    .last_error
+                    .as_ref()
+                    .map_or(0.0, |trapped| trapped.number as f64),
+            )),
+            "ERL" => Ok(Value::Number(
+                self.context
+                    .last_error
+                    .as_ref()
+                    .map_or(0.0, |trapped| trapped.line as f64),
+            )),
+            "EOF" | "LOF" => {
+                let [number] = arguments else {
+                    return Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        format!("{0} takes a file number, e.g. {0}(1)", name.to_uppercase()),
+                    ));
+                };
+                let number = self.value_to_number(number)?.round().max(0.0) as u32;
+                if name.eq_ignore_ascii_case("EOF") {
+                    let at_end = self.files.eof(number)?;
+                    Ok(Value::Number(if at_end { -1.0 } else { 0.0 }))
+                } else {
+                    Ok(Value::Number(self.files.lof(number)? as f64))
+                }
+            }
+            "PEEK" => {
+                let [offset] = arguments else {
+                    return Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "PEEK takes an address, e.g. PEEK(53281)",
+                    ));
+                };
+                let address = self.memory.address(self.value_to_number(offset)?, "PEEK")?;
+                Ok(Value::Number(self.peek(address) as f64))
+            }
+            // Numbers packed into strings for records: 2 bytes for MKI$, 4
+            // for MKS$ and 8 for MKD$, and CVI, CVS and CVD to unpack them
+            "MKI$" | "MKS$" | "MKD$" => {
+                let upper = name.to_uppercase();
+                let [value] = arguments else {
+                    return Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        format!("{} takes one number", upper),
+                    ));
+                };
+                let number = self.value_to_number(value)?;
+                let bytes = match upper.as_str() {
+                    "MKI$" => {
+                        let number = number.round();
+                        if !INTEGER_RANGE.contains(&number) {
+                            return Err(InterpreterError::RuntimeError(format!(
+                                "MKI$ packs -32768 to 32767, not {}",
+                                number
+                            )));
+                        }
+                        (number as i16).to_le_bytes().to_vec()
+                    }
+                    "MKS$" => (number as f32).to_le_bytes().to_vec(),
+                    _ => number.to_le_bytes().to_vec(),
+                };
+                Ok(Value::String(files::bytes_to_text(&bytes)))
+            }
+            "CVI" | "CVS" | "CVD" => {
+                let upper = name.to_uppercase();
+                let size = match upper.as_str() {
+                    "CVI" => 2,
+                    "CVS" => 4,
+                    _ => 8,
+                };
+                let bytes = match arguments {
+                    [Value::String(text)] => files::text_to_bytes(text),
+                    _ => Vec::new(),
+                };
+                let Some(bytes) = bytes.get(..size) else {
+                    return Err(InterpreterError::RuntimeError(format!(
+                        "{} needs a string of at least {} characters, as MK{}$ makes",
+                        upper,
+                        size,
+                        &upper[2..]
+                    )));
+                };
+                Ok(Value::Number(match size {
+                    2 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    4 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    _ => f64::from_le_bytes(bytes.try_into().unwrap_or_default()),
+                }))
+            }
+            "CHR$" => {
+                if arguments.len() == 1 {
+                    let code = self.value_to_number(&arguments[0])?;
+                    if (0.0..256.0).contains(&code) {
+                        Ok(Value::String(self.dialect.chr(code as u8)))
+                    } else {
+                        Err(InterpreterError::RuntimeError(format!(
+                            "CHR$({}) is not a character code between 0 and 255",
+                            code
+                        )))
+                    }
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "CHR$ requires 1 argument",
+                    ))
+                }
+            }
+            "TAB" => {
+                if arguments.len() == 1 {
+                    let col = self.value_to_number(&arguments[0])? as usize;
+                    // TAB(n) moves to column n, padding with spaces if necessary
+                    // For simplicity, we'll just return a string with spaces
+                    let spaces = if col > 0 {
+                        " ".repeat(col)
+                    } else {
+                        String::new()
+                    };
+                    Ok(Value::String(spaces))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "TAB requires 1 argument",
+                    ))
+                }
+            }
+            "SPC" => {
+                if arguments.len() == 1 {
+                    let count = self.value_to_number(&arguments[0])? as usize;
+                    let spaces = " ".repeat(count);
+                    Ok(Value::String(spaces))
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "SPC requires 1 argument",
+                    ))
+                }
+            }
+            "ENVIRON$" => {
+                if arguments.len() == 1 {
+                    match &arguments[0] {
+                        Value::String(var_name) => {
+                            // Get environment variable by name
+                            match std::env::var(var_name) {
+                                Ok(value) => Ok(Value::String(value)),
+                                Err(_) => Ok(Value::String(String::new())), // Empty string if not found
+                            }
+                        }
+                        Value::Number(index) => {
+                            // Get environment variable by index (1-based)
+                            let index = *index as usize;
+                            if index > 0 {
+                                let env_vars: Vec<_> = std::env::vars().collect();
+                                if index <= env_vars.len() {
+                                    let (key, value) = &env_vars[index - 1];
+                                    Ok(Value::String(format!("{}={}", key, value)))
+                                } else {
+                                    Ok(Value::String(String::new()))
+                                }
+                            } else {
+                                Ok(Value::String(String::new()))
+                            }
+                        }
+                        _ => Err(InterpreterError::TypeError(
+                            "ENVIRON$ argument must be string or number".to_string(),
+                        )),
+                    }
+                } else {
+                    Err(InterpreterError::runtime(
+                        ErrorCode::ArgumentCount,
+                        "ENVIRON$ requires 1 argument",
+                    ))
+                }
+            }
+            _ => {
+                // Check for user-defined functions
+                let lookup_name = name.strip_prefix("FN").unwrap_or(name);
+                if let Some(func_def) = self.context.functions.get(lookup_name).cloned() {
+                    self.call_user_function(&func_def, arguments)
+                } else if arguments.is_empty() && name.ends_with('$') {
+                    // A string variable such as NAME$, not a function
+                    Ok(self.context.get_variable(name).value.clone())
+                } else {
+                    Err(InterpreterError::UndefinedFunction(name.to_string()))
+                }
+            }
+        }
+    }
+
+    fn math_function<F>(&self, arguments: &[Value], func: F) -> Result<Value, InterpreterError>
+    where
+        F: Fn(f64) -> f64,
+    {
+        if arguments.len() == 1 {
+            let num = self.value_to_number(&arguments[0])?;
+            Ok(Value::Number(func(num)))
+        } else {
+            Err(InterpreterError::runtime(
+                ErrorCode::ArgumentCount,
+                "Math function requires 1 argument",
+            ))
+        }
+    }
+
+    fn call_user_function(
+        &mut self,
+        func_def: &FunctionDefinition,
+        arguments: &[Value],
+    ) -> Result<Value, InterpreterError> {
+        if arguments.len() != func_def.parameters.len() {
+            return Err(InterpreterError::runtime(
+                ErrorCode::ArgumentCount,
+                format!(
+                    "Function expects {} arguments, got {}",
+                    func_def.parameters.len(),
+                    arguments.len()
+                ),
+            ));
+        }
+
+        // Save current variable values
+        let mut saved_vars = HashMap::new();
+        for param in &func_def.parameters {
+            if let Some(var_info) = self.context.variables.get(param) {
+                saved_vars.insert(param.clone(), var_info.clone());
+            }
+        }
+
+        // Set parameter values (parameters are treated as Single by default in GW-BASIC)
+        for (param, arg) in func_def.parameters.iter().zip(arguments) {
+            let var_type = self.context.get_variable_type(param);
+            let converted_arg = self.convert_value_to_variable_type(arg, param)?;
+            let var_info = self.context.get_variable(param);
+            var_info.value = converted_arg;
+            var_info.declared_type = var_type;
+        }
+
+        // Evaluate function body
+        let result = self.evaluate_expression(&func_def.body);
+
+        // Restore saved variables
+        for (param, var_info) in saved_vars {
+            self.context.variables.insert(param, var_info);
+        }
+
+        result
+    }
+
+    fn create_array(
+        &mut self,
+        name: &str,
+        dimensions: &[Expression],
+    ) -> Result<(), InterpreterError> {
+        if dimensions.len() != 1 {
+            return Err(InterpreterError::RuntimeError(
+                "Multi-dimensional arrays not yet supported".to_string(),
+            ));
+        }
+
+        let size_expr = &dimensions[0];
+        let size_value = self.evaluate_expression(size_expr)?;
+        let size = self.value_to_number(&size_value)? as usize;
+
+        let mut array = Vec::with_capacity(size + 1); // +1 for 0-based indexing
+        for _ in 0..=size {
+            array.push(Value::Number(0.0));
+        }
+
+        self.context.arrays.insert(name.to_string(), array);
+        Ok(())
+    }
+
+    fn get_array_element(&self, name: &str, index: usize) -> Result<Value, InterpreterError> {
+        if let Some(array) = self.context.arrays.get(name) {
+            if index < array.len() {
+                Ok(array[index].clone())
+            } else {
+                Err(InterpreterError::IndexOutOfBounds)
+            }
+        } else {
+            Err(InterpreterError::UndefinedVariable(format!(
+                "Array {}",
+                name
+            )))
+        }
+    }
+
+    fn values_equal(&self, left: &Value, right: &Value) -> Result<bool, InterpreterError> {
+        match (left, right) {
+            (Value::Number(l), Value::Number(r)) => Ok((l - r).abs() < f64::EPSILON),
+            (Value::String(l), Value::String(r)) => Ok(l == r),
+            (Value::Integer(l), Value::Integer(r)) => Ok(l == r),
+            (Value::Number(l), Value::Integer(r)) => Ok((l - *r as f64).abs() < f64::EPSILON),
+            (Value::Integer(l), Value::Number(r)) => Ok((*l as f64 - r).abs() < f64::EPSILON),
+            _ => Ok(false), // Different types are not equal
+        }
+    }
+
+    // Helper methods for type conversion
+    /// Address a graphics command to the current turtle
+    fn tag(&self, command: GraphicsCommand) -> TurtleCommand {
+        TurtleCommand {
+            turtle: self.context.turtle.clone(),
+            command,
+        }
+    }
+
+    /// Whether the current turtle draws as it moves
+    fn pen_down(&self) -> bool {
+        !self.context.pens_up.contains(&self.context.turtle)
+    }
+
+    /// A colour given as a palette index, or as red, green and blue
+    fn evaluate_color(
+        &mut self,
+        components: &[Expression],
+        command: &str,
+    ) -> Result<(u8, u8, u8), InterpreterError> {
+        let mut values = Vec::with_capacity(components.len());
+        for component in components {
+            let value = self.evaluate_expression(component)?;
+            values.push(self.value_to_number(&value)?);
+        }
+        match values.as_slice() {
+            [index] => {
+                let index = *index as i64;
+                if !(0..PEN_PALETTE.len() as i64).contains(&index) {
+                    return Err(InterpreterError::RuntimeError(format!(
+                        "Colour {} is not between 0 and {}",
+                        index,
+                        PEN_PALETTE.len() - 1
+                    )));
+                }
+                Ok(PEN_PALETTE[index as usize])
+            }
+            [r, g, b] => Ok((
+                r.clamp(0.0, 255.0) as u8,
+                g.clamp(0.0, 255.0) as u8,
+                b.clamp(0.0, 255.0) as u8,
+            )),
+            _ => Err(InterpreterError::RuntimeError(format!(
+                "{} expects a colour number or red, green, blue",
+                command
+            ))),
+        }
+    }
+
+    /// Radius for ARC and CIRCLE, which must be positive
+    fn arc_radius(&self, value: &Value) -> Result<f32, InterpreterError> {
+        let radius = self.value_to_number(value)?;
+        if radius <= 0.0 {
+            return Err(InterpreterError::RuntimeError(format!(
+                "Radius must be positive, got {}",
+                radius
+            )));
+        }
+        Ok(radius as f32)
+    }
+
+    fn value_to_number(&self, value: &Value) -> Result<f64, InterpreterError> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Single(s) => Ok(*s as f64),
+            Value::Double(d) => Ok(*d),
+            Value::String(s) if self.strictness == Strictness::Strict => {
+                Err(InterpreterError::TypeError(format!(
+                    "Strict mode doesn't use the text \"{}\" as a number; use VAL",
+                    s
+                )))
+            }
+            Value::String(s) => s.parse::<f64>().map_err(|_| {
+                InterpreterError::TypeError(format!("Cannot convert '{}' to number", s))
+            }),
+        }
+    }
+
+    fn value_to_string(&self, value: &Value) -> String {
+        match value {
+            Value::Number(n) => n.to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Single(s) => s.to_string(),
+            Value::Double(d) => d.to_string(),
+            Value::String(s) => s.clone(),
+        }
+    }
+
+    fn value_to_bool(&self, value: &Value) -> Result<bool, InterpreterError> {
+        match value {
+            Value::Number(n) => Ok(*n != 0.0),
+            Value::Integer(i) => Ok(*i != 0),
+            Value::Single(s) => Ok(*s != 0.0),
+            Value::Double(d) => Ok(*d != 0.0),
+            Value::String(s) => Ok(!s.is_empty()),
+        }
+    }
+
+    fn compare_values(&self, left: &Value, right: &Value) -> Result<i32, InterpreterError> {
+        match (left, right) {
+            (Value::Number(l), Value::Number(r)) => Ok(if l < r {
+                -1
+            } else if l > r {
+                1
+            } else {
+                0
+            }),
+            (Value::String(l), Value::String(r)) => Ok(l.cmp(r) as i32),
+            (Value::String(_), _) | (_, Value::String(_)) => Err(InterpreterError::TypeError(
+                "Cannot compare different types".to_string(),
+            )),
+            // Mixed numbers compare at the lower precision, so X = 0.1 holds
+            // for a single-precision X
+            _ => {
+                let (mut l, mut r) = (self.value_to_number(left)?, self.value_to_number(right)?);
+                if matches!(left, Value::Single(_)) || matches!(right, Value::Single(_)) {
+                    l = l as f32 as f64;
+                    r = r as f32 as f64;
+                }
+                Ok(if l < r {
+                    -1
+                } else if l > r {
+                    1
+                } else {
+                    0
+                })
+            }
+        }
+    }
+
+    pub fn provide_input(&mut self, input: &str) -> Result<ExecutionResult, InterpreterError> {
+        self.assign_input(input)?;
+
+        // Continue execution, showing the answer as if it had been typed there
+        let echo = format!("{}\n", input.trim());
+        let mut result = self.execute_program()?;
+        if self.cleared_screen {
+            return Ok(result);
+        }
+        match &mut result {
+            ExecutionResult::Complete { output, .. }
+            | ExecutionResult::NeedInput {
+                partial_output: output,
+                ..
+            }
+            | ExecutionResult::Break {
+                partial_output: output,
+                ..
+            }
+            | ExecutionResult::NeedPermission {
+                partial_output: output,
+                ..
+            }
+            | ExecutionResult::Polling {
+                partial_output: output,
+                ..
+            } => output.insert_str(0, &echo),
+            ExecutionResult::Error(_) => {}
+        }
+        Ok(result)
+    }
+
+    /// Store an answer in the variable the pending INPUT asked for
+    fn assign_input(&mut self, input: &str) -> Result<(), InterpreterError> {
+        // Parse the input value - default to Single type for numeric input.
+        // Strict mode keeps an answer for a string variable as typed.
+        let wants_text = self.strictness == Strictness::Strict
+            && self.context.input_variable.as_deref().is_some_and(|name| {
+                matches!(self.context.get_variable_type(name), VariableType::String)
+            });
+        let parsed_value = if wants_text {
+            Value::String(input.trim().to_string())
+        } else if let Ok(num) = input.trim().parse::<f64>() {
+            Value::Single(num as f32) // GW-BASIC default for input
+        } else {
+            Value::String(input.trim().to_string())
+        };
+
+        // Set the input variable if one is expected
+        if let Some(ref var_name) = self.context.input_variable.clone() {
+            let var_type = self.context.get_variable_type(var_name);
+            let converted_value = self.convert_value_to_variable_type(&parsed_value, var_name)?;
+            let var_info = self.context.get_variable(var_name);
+            var_info.value = converted_value;
+            var_info.declared_type = var_type;
+            self.context.input_variable = None;
+        }
+        Ok(())
+    }
+
+    /// Set type declaration for a range of variable names
+    fn set_type_declaration(
+        &mut self,
+        range: &str,
+        var_type: VariableType,
+    ) -> Result<(), InterpreterError> {
+        if range.len() == 1 {
+            // Single character range like "A"
+            let first_char = range.chars().next().unwrap().to_ascii_uppercase();
+            self.context
+                .type_declarations
+                .insert(first_char.to_string(), var_type);
+        } else if range.len() == 3 && range.chars().nth(1) == Some('-') {
+            // Range like "A-C"
+            let start = range.chars().next().unwrap().to_ascii_uppercase();
+            let end = range.chars().nth(2).unwrap().to_ascii_uppercase();
+            if start <= end {
+                for ch in start..=end {
+                    self.context
+                        .type_declarations
+                        .insert(ch.to_string(), var_type.clone());
+                }
+            } else {
+                return Err(InterpreterError::RuntimeError(format!(
+                    "Invalid range specification: {}",
+                    range
+                )));
+            }
+        } else {
+            return Err(InterpreterError::RuntimeError(format!(
+                "Invalid range specification: {}",
+                range
+            )));
+        }
+        Ok(())
+    }
+
+    /// Convert a value to the appropriate type for a variable
+    fn convert_value_to_variable_type(
+        &self,
+        value: &Value,
+        variable_name: &str,
+    ) -> Result<Value, InterpreterError> {
+        let target_type = self.context.get_variable_type(variable_name);
+        // The 8-bit machines kept integers in two bytes
+        if matches!(target_type, VariableType::Integer)
+            && self.dialect.screen().is_some()
+            && !matches!(value, Value::String(_))
+        {
+            let number = self.value_to_number(value)?;
+            if !INTEGER_RANGE.contains(&number) {
+                return Err(InterpreterError::RuntimeError(format!(
+                    "?ILLEGAL QUANTITY ERROR: {} doesn't fit in {}",
+                    number, variable_name
+                )));
+            }
+        }
+
+        let is_text = matches!(value, Value::String(_));
+        if self.strictness == Strictness::Strict
+            && is_text != matches!(target_type, VariableType::String)
+        {
+            return Err(InterpreterError::TypeError(if is_text {
+                format!(
+                    "Strict mode doesn't store text in the number variable {}; use VAL",
+                    variable_name
+                )
+            } else {
+                format!(
+                    "Strict mode doesn't store a number in the string variable {}; use STR$",
+                    variable_name
+                )
+            }));
+        }
+
+        match (value, target_type) {
+            // Legacy Number type support
+            (Value::Number(n), VariableType::Integer) => Ok(Value::Integer(*n as i32)),
+            (Value::Number(n), VariableType::Single) => Ok(Value::Single(*n as f32)),
+            (Value::Number(n), VariableType::Double) => Ok(Value::Double(*n)),
+            (Value::Number(n), VariableType::String) => Ok(Value::String(n.to_string())),
+
+            // No conversion needed if types match
+            (Value::Integer(i), VariableType::Integer) => Ok(Value::Integer(*i)),
+            (Value::Single(s), VariableType::Single) => Ok(Value::Single(*s)),
+            (Value::Double(d), VariableType::Double) => Ok(Value::Double(*d)),
+            (Value::String(s), VariableType::String) => Ok(Value::String(s.clone())),
+
+            // Convert to Integer
+            (Value::Single(s), VariableType::Integer) => Ok(Value::Integer(*s as i32)),
+            (Value::Double(d), VariableType::Integer) => Ok(Value::Integer(*d as i32)),
+
+            // Convert to Single
+            (Value::Integer(i), VariableType::Single) => Ok(Value::Single(*i as f32)),
+            (Value::Double(d), VariableType::Single) => Ok(Value::Single(*d as f32)),
+
+            // Convert to Double
+            (Value::Integer(i), VariableType::Double) => Ok(Value::Double(*i as f64)),
+            (Value::Single(s), VariableType::Double) => Ok(Value::Double(*s as f64)),
+
+            // String conversions - GW-BASIC allows some numeric conversions
+            (Value::String(s), VariableType::Integer) => {
+                if let Ok(num) = s.parse::<i32>() {
+                    Ok(Value::Integer(num))
+                } else {
+                    Err(InterpreterError::TypeError(format!(
+                        "Cannot convert string '{}' to integer",
+                        s
+                    )))
+                }
+            }
+            (Value::String(s), VariableType::Single) => {
+                if let Ok(num) = s.parse::<f32>() {
+                    Ok(Value::Single(num))
+                } else {
+                    Err(InterpreterError::TypeError(format!(
+                        "Cannot convert string '{}' to single",
+                        s
+                    )))
+                }
+            }
+            (Value::String(s), VariableType::Double) => {
+                if let Ok(num) = s.parse::<f64>() {
+                    Ok(Value::Double(num))
+                } else {
+                    Err(InterpreterError::TypeError(format!(
+                        "Cannot convert string '{}' to double",
+                        s
+                    )))
+                }
+            }
+
+            // Numeric to string conversion
+            (Value::Integer(i), VariableType::String) => Ok(Value::String(i.to_string())),
+            (Value::Single(s), VariableType::String) => Ok(Value::String(s.to_string())),
+            (Value::Double(d), VariableType::String) => Ok(Value::String(d.to_string())),
+        }
+    }
+}
+
+/// Where PRINT is writing, which decides how a comma lines values up
+#[derive(Debug, Clone, Copy)]
+enum PrintLayout {
+    Screen(Screen), // an 8-bit machine's screen
+    Printer,        // LPRINT's page, or a file
+    Plain,          // TW BASIC's output, where a comma is a tab
+}
+
+/// How many statements a millisecond of SLEEP or WAIT counts as when the run
+/// doesn't really wait; the IDE's limit assumes the same rate
+const INSTRUCTIONS_PER_MILLISECOND: usize = 1000;
+
+/// LPRINT lines values separated by commas up in columns this wide
+const PRINTER_ZONE: usize = 14;
+
+/// How long SLEEP or WAIT pauses for `seconds`, which can't be negative
+fn pause_length(seconds: f64, keyword: &str) -> Result<Duration, InterpreterError> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| {
+        InterpreterError::RuntimeError(format!("{} takes 0 or more, not {}", keyword, seconds))
+    })
+}
+
+/// The column the next character printed will go in
+fn screen_column(output: &str) -> usize {
+    output.chars().rev().take_while(|&c| c != '\n').count()
+}
+
+/// Print `text` on an 8-bit machine's screen: a line that reaches the edge
+/// carries on at the start of the next, and CHR$ codes that clear the
+/// screen wipe what is there
+fn print_on_screen(output: &mut String, text: &str, screen: Screen) {
+    let mut column = screen_column(output);
+    for c in text.chars() {
+        match c {
+            CLEAR_SCREEN => {
+                output.clear();
+                column = 0;
+            }
+            '\n' => {
+                output.push('\n');
+                column = 0;
+            }
+            _ => {
+                if column == screen.columns {
+                    output.push('\n');
+                    column = 0;
+                }
+                output.push(c);
+                column += 1;
+            }
+        }
+    }
+}
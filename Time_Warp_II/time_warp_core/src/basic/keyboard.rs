@@ -0,0 +1,56 @@
+//! The key buffer INKEY$ reads, for games and menus that react to single
+//! keys without waiting for Enter. Whatever drives the interpreter passes
+//! keys in with `press_key` as they are typed; `pump` covers how a run
+//! waiting for one hands control back meanwhile.
+
+use std::collections::VecDeque;
+
+/// Keys kept until INKEY$ reads them; more are dropped, as GW-BASIC does
+pub const KEY_BUFFER_SIZE: usize = 15;
+
+// Keys with no character of their own read as CHR$(0) and the PC's scan
+// code, so INKEY$ gives two characters for them
+pub const UP: &str = "\0H";
+pub const DOWN: &str = "\0P";
+pub const LEFT: &str = "\0K";
+pub const RIGHT: &str = "\0M";
+pub const HOME: &str = "\0G";
+pub const END: &str = "\0O";
+pub const PAGE_UP: &str = "\0I";
+pub const PAGE_DOWN: &str = "\0Q";
+pub const INSERT: &str = "\0R";
+pub const DELETE: &str = "\0S";
+
+/// F1 to F10 as INKEY$ reads them: CHR$(0) and scan codes 59 to 68
+pub fn function_key(number: u8) -> Option<String> {
+    (1..=10)
+        .contains(&number)
+        .then(|| format!("\0{}", char::from(58 + number)))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Keyboard {
+    pressed: VecDeque<String>,
+}
+
+impl Keyboard {
+    /// Forget keys nobody read, for a new run
+    pub fn reset(&mut self) {
+        self.pressed.clear();
+    }
+
+    pub fn press(&mut self, key: &str) {
+        if !key.is_empty() && self.pressed.len() < KEY_BUFFER_SIZE {
+            self.pressed.push_back(key.to_string());
+        }
+    }
+
+    pub fn has_keys(&self) -> bool {
+        !self.pressed.is_empty()
+    }
+
+    /// INKEY$: the oldest key not yet read, or None without waiting
+    pub fn read(&mut self) -> Option<String> {
+        self.pressed.pop_front()
+    }
+}
@@ -0,0 +1,246 @@
+//! A static pass over a parsed program for mistakes that still run:
+//! variables given a value that is never used, GOTO and GOSUB to line
+//! numbers the program doesn't have, and statements after END that
+//! nothing jumps to.
+//!
+//! `parse_errors` finds every line that doesn't parse, rather than only the
+//! first as running the program does.
+
+use crate::basic::ast::{Expression, Program, ResumeTarget, Statement, Token};
+use crate::basic::dialect::{self, Dialect};
+use crate::basic::parser::Parser;
+use crate::basic::strict::{self, Diagnostic, Severity};
+use crate::basic::tokenizer::Tokenizer;
+use std::collections::{BTreeMap, HashSet};
+
+/// Every line of `code` that doesn't parse as `dialect`. Parsing stops at
+/// the first mistake, so each bad line is blanked out in turn and the rest
+/// parsed again.
+pub fn parse_errors(code: &str, dialect: Dialect) -> Vec<Diagnostic> {
+    let mut lines: Vec<&str> = code.split('\n').collect();
+    let mut found = Vec::new();
+    while let Some(error) = first_parse_error(&lines.join("\n"), dialect) {
+        // A mistake that blanking its line doesn't get rid of would come back forever
+        let blank = error
+            .line
+            .checked_sub(1)
+            .and_then(|index| lines.get_mut(index))
+            .filter(|text| !text.trim().is_empty());
+        found.push(error);
+        match blank {
+            Some(text) => *text = "",
+            None => break,
+        }
+    }
+    // Characters the tokenizer can't read are found before anything is parsed
+    found.sort_by_key(|diagnostic| diagnostic.line);
+    found
+}
+
+/// The first thing in `code` that doesn't parse, what is wrong with it, and
+/// the token it stopped at
+fn first_parse_error(code: &str, dialect: Dialect) -> Option<Diagnostic> {
+    let translated = match dialect {
+        Dialect::QBasic => match dialect::translate(code) {
+            Ok(translated) => Some(translated),
+            // Block mistakes are reported "at line N" of the program
+            Err(err) => {
+                let message = err.message();
+                let line = message
+                    .rsplit("at line ")
+                    .next()
+                    .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+                    .and_then(|digits| digits.parse().ok())
+                    .unwrap_or(1);
+                return Some(Diagnostic {
+                    line,
+                    severity: Severity::Error,
+                    message,
+                    span: None,
+                });
+            }
+        },
+        _ => None,
+    };
+    let source = translated
+        .as_ref()
+        .map_or(code, |translated| &translated.code);
+    let mut tokenizer = Tokenizer::with_dialect(source, dialect);
+    let (err, span) = match tokenizer.tokenize() {
+        Err(err) => {
+            let at = tokenizer.offset();
+            let width = source[at..]
+                .chars()
+                .next()
+                .filter(|&c| c != '\n')
+                .map_or(0, char::len_utf8);
+            (err, at..at + width)
+        }
+        Ok(tokens) => {
+            let mut parser = Parser::new(tokens.clone());
+            let err = parser.parse_program().err()?;
+            let line = err.position().map_or(1, |position| position.line);
+            // Something missing at the end of a line is marked on what comes before it
+            let index = parser.token_index();
+            let token = [Some(index), index.checked_sub(1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|index| tokens.get(index))
+                .find(|token| {
+                    !matches!(token.token, Token::Eol | Token::Eof) && token.line == line
+                });
+            (err, token.map_or(0..0, |token| token.start..token.end))
+        }
+    };
+    let line = err.position().map_or(1, |position| position.line);
+    let line_start = source[..span.start]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let span = (!span.is_empty()).then(|| span.start - line_start..span.end - line_start);
+    Some(match &translated {
+        // Translated lines don't read as written, so there is no span to show
+        Some(translated) => Diagnostic {
+            line: translated.source_line(line),
+            severity: Severity::Error,
+            message: translated.locate(err).message(),
+            span: None,
+        },
+        None => Diagnostic {
+            line,
+            severity: Severity::Error,
+            message: err.message(),
+            span,
+        },
+    })
+}
+
+/// What the linter finds in `program`, in line order
+pub fn lint(program: &Program) -> Vec<Diagnostic> {
+    let mut found = Vec::new();
+    let mut assigned = BTreeMap::new(); // upper-case name -> where first given a value
+    let mut read = HashSet::new();
+    let mut targets = HashSet::new(); // statements jumped to
+    let mut computed = false; // a jump whose line is worked out as the program runs
+
+    for (statement, &line) in program.statements.iter().zip(&program.statement_lines) {
+        each_statement(statement, &mut |statement| {
+            let mut names = Vec::new();
+            match statement {
+                Statement::Let { variable, .. }
+                | Statement::Input { variable, .. }
+                | Statement::LineInput { variable, .. } => {
+                    assigned
+                        .entry(variable.to_uppercase())
+                        .or_insert((line, variable.clone()));
+                }
+                Statement::FileInput { variables, .. } => {
+                    for variable in variables {
+                        assigned
+                            .entry(variable.to_uppercase())
+                            .or_insert((line, variable.clone()));
+                    }
+                }
+                Statement::Def {
+                    parameters, body, ..
+                } => {
+                    strict::variables_read(body, &mut names);
+                    names.retain(|name| {
+                        !parameters
+                            .iter()
+                            .any(|parameter| parameter.eq_ignore_ascii_case(name))
+                    });
+                }
+                _ => {}
+            }
+            for expression in strict::expressions(statement) {
+                strict::variables_read(expression, &mut names);
+            }
+            read.extend(names.iter().map(|name| name.to_uppercase()));
+
+            let target = match statement {
+                Statement::Goto { line } | Statement::Gosub { line } => line,
+                Statement::OnEvent { line, .. } | Statement::OnError { line } => line,
+                Statement::Resume {
+                    target: ResumeTarget::Line(line),
+                } => line,
+                _ => return,
+            };
+            let Expression::Number(number) = target else {
+                computed = true;
+                return;
+            };
+            let number = *number as usize;
+            // ON ... GOSUB 0 and ON ERROR GOTO 0 turn the handler off
+            if number == 0
+                && matches!(
+                    statement,
+                    Statement::OnEvent { .. } | Statement::OnError { .. }
+                )
+            {
+                return;
+            }
+            match program.line_numbers.get(&number) {
+                Some(&index) => {
+                    targets.insert(index);
+                }
+                None => found.push(Diagnostic {
+                    line,
+                    severity: Severity::Error,
+                    message: format!("There is no line {} to jump to", number),
+                    span: None,
+                }),
+            }
+        });
+    }
+
+    for (name, (line, written)) in &assigned {
+        if !read.contains(name) {
+            found.push(Diagnostic {
+                line: *line,
+                severity: Severity::Warning,
+                message: format!("{} is given a value that is never used", written),
+                span: None,
+            });
+        }
+    }
+
+    // Without knowing where every jump lands, anything might be reached
+    if !computed {
+        let mut after_end = false;
+        let mut reported = HashSet::new();
+        for (index, (statement, &line)) in program
+            .statements
+            .iter()
+            .zip(&program.statement_lines)
+            .enumerate()
+        {
+            if targets.contains(&index) {
+                after_end = false;
+            }
+            if after_end && !matches!(statement, Statement::Rem(_)) && reported.insert(line) {
+                found.push(Diagnostic {
+                    line,
+                    severity: Severity::Warning,
+                    message: "This line can't be reached: it comes after END and no GOTO or \
+                              GOSUB leads here"
+                        .to_string(),
+                    span: None,
+                });
+            }
+            if matches!(statement, Statement::End) {
+                after_end = true;
+            }
+        }
+    }
+
+    found.sort_by_key(|diagnostic| diagnostic.line);
+    found
+}
+
+/// `statement` and each statement inside it
+fn each_statement<'a>(statement: &'a Statement, visit: &mut impl FnMut(&'a Statement)) {
+    visit(statement);
+    for inner in strict::inner_statements(statement) {
+        each_statement(inner, visit);
+    }
+}
@@ -0,0 +1,160 @@
+//! Forgiving answer matching for quiz programs: MATCH, SOUNDEX$ and SIMILAR.
+//! The rules are described for teachers in docs/ANSWER_MATCHING.md.
+
+/// Upper-case `text`, turn punctuation into spaces and collapse runs of
+/// whitespace, so "  Paris! " and "paris" compare equal
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Classic four-character Soundex code (e.g. ROBERT -> R163). Words that
+/// sound alike share a code; text without letters gives an empty string.
+pub fn soundex(text: &str) -> String {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'B' | 'F' | 'P' | 'V' => Some('1'),
+            'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+            'D' | 'T' => Some('3'),
+            'L' => Some('4'),
+            'M' | 'N' => Some('5'),
+            'R' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let letters: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let Some(&first) = letters.first() else {
+        return String::new();
+    };
+
+    let mut code = first.to_string();
+    let mut previous = digit(first);
+    for &c in &letters[1..] {
+        let current = digit(c);
+        if let Some(d) = current {
+            if current != previous {
+                code.push(d);
+                if code.len() == 4 {
+                    break;
+                }
+            }
+        }
+        // H and W don't separate letters with the same code; vowels do
+        if c != 'H' && c != 'W' {
+            previous = current;
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    code
+}
+
+/// Number of single-character insertions, deletions, substitutions or
+/// swaps of neighbours needed to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// How alike two answers are after normalizing, from 0 (nothing in
+/// common) to 100 (the same)
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 100.0;
+    }
+    let distance = edit_distance(&a, &b);
+    ((longest - distance.min(longest)) as f64 * 100.0 / longest as f64).round()
+}
+
+/// Typos forgiven in a word of this many characters
+fn allowed_typos(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Whether one answer word is close enough to the expected word
+fn word_matches(answer: &str, expected: &str) -> bool {
+    if answer == expected {
+        return true;
+    }
+    // Numbers have to be exact: 12 is not 13
+    if expected.chars().any(|c| c.is_ascii_digit()) || answer.chars().any(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let typos = allowed_typos(expected.chars().count());
+    if typos == 0 {
+        return false;
+    }
+    let distance = edit_distance(answer, expected);
+    // COLOR/COLOUR: spelling variants that sound the same get one extra letter of slack
+    distance <= typos || (distance == typos + 1 && soundex(answer) == soundex(expected))
+}
+
+/// Whether `expected` (already normalized) appears as a run of whole words in
+/// `answer`, allowing small typos in each word
+fn phrase_matches(answer: &[&str], expected: &str) -> bool {
+    let expected: Vec<&str> = expected.split(' ').collect();
+    if expected.len() > answer.len() {
+        return false;
+    }
+    answer.windows(expected.len()).any(|window| {
+        window
+            .iter()
+            .zip(&expected)
+            .all(|(given, wanted)| word_matches(given, wanted))
+    })
+}
+
+/// MATCH(answer$, pattern$): `pattern` lists acceptable answers separated by
+/// commas or `|`. Returns the 1-based position of the first one found in
+/// `answer`, or 0 if none match.
+pub fn match_answer(answer: &str, pattern: &str) -> usize {
+    let answer = normalize(answer);
+    let words: Vec<&str> = answer.split_whitespace().collect();
+    pattern
+        .split([',', '|'])
+        .map(normalize)
+        .position(|expected| !expected.is_empty() && phrase_matches(&words, &expected))
+        .map_or(0, |index| index + 1)
+}
@@ -0,0 +1,131 @@
+//! PEEK, POKE and DEF SEG on an emulated 64KB memory, so listings that
+//! reach into the machine still run.
+//!
+//! An address is `segment * 16 + offset`, as on the PC, with the segment
+//! DEF SEG chose (0 until then). Most addresses are plain bytes that keep
+//! what was POKEd, but a few stand for the interpreter's own state:
+//!
+//! | Address            | Holds                                                  |
+//! |--------------------|--------------------------------------------------------|
+//! | `&H44A`            | screen width in columns (read only)                    |
+//! | `&H46C` to `&H46F` | clock ticks since midnight, 18.2 a second (read only)  |
+//! | `&H4F0` to `&H4F2` | the RND seed; POKE it to change what RND gives         |
+//! | `53281`            | the Commodore 64's background colour, 0 to 15          |
+//!
+//! The PC addresses are where the BIOS keeps them, reached with `DEF SEG = 0`
+//! or `DEF SEG = &H40`; the seed lives in the BIOS's spare bytes.
+
+use crate::basic::ast::InterpreterError;
+
+/// Bytes of emulated memory; higher addresses wrap round
+pub const MEMORY_SIZE: usize = 0x10000;
+
+/// A Commodore 64's colours, by the numbers POKEd into 53281
+pub const C64_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),       // 0 black
+    (255, 255, 255), // 1 white
+    (136, 0, 0),     // 2 red
+    (170, 255, 238), // 3 cyan
+    (204, 68, 204),  // 4 purple
+    (0, 204, 85),    // 5 green
+    (0, 0, 170),     // 6 blue
+    (238, 238, 119), // 7 yellow
+    (221, 136, 85),  // 8 orange
+    (102, 68, 0),    // 9 brown
+    (255, 119, 119), // 10 light red
+    (51, 51, 51),    // 11 dark grey
+    (119, 119, 119), // 12 grey
+    (170, 255, 102), // 13 light green
+    (0, 136, 255),   // 14 light blue
+    (187, 187, 187), // 15 light grey
+];
+
+/// Interpreter state standing in for a byte of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapped {
+    ScreenColumns,
+    ClockTick(usize),  // byte of the tick count, lowest first
+    RandomSeed(usize), // byte of the seed, lowest first
+    Background,
+}
+
+/// What `address` stands for, if it isn't a plain byte
+pub fn mapped(address: u32) -> Option<Mapped> {
+    match address {
+        0x44A => Some(Mapped::ScreenColumns),
+        0x46C..=0x46F => Some(Mapped::ClockTick((address - 0x46C) as usize)),
+        0x4F0..=0x4F2 => Some(Mapped::RandomSeed((address - 0x4F0) as usize)),
+        53281 => Some(Mapped::Background),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    segment: u32,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            bytes: vec![0; MEMORY_SIZE],
+            segment: 0,
+        }
+    }
+}
+
+impl Memory {
+    /// Clear every byte and go back to segment 0, for a new run
+    pub fn reset(&mut self) {
+        self.bytes.fill(0);
+        self.segment = 0;
+    }
+
+    /// DEF SEG = segment; DEF SEG alone goes back to 0
+    pub fn set_segment(&mut self, segment: Option<f64>) -> Result<(), InterpreterError> {
+        self.segment = match segment {
+            Some(segment) => word(segment, "DEF SEG")?,
+            None => 0,
+        };
+        Ok(())
+    }
+
+    /// Where `offset` is in the current segment, for PEEK or POKE
+    pub fn address(&self, offset: f64, keyword: &str) -> Result<u32, InterpreterError> {
+        Ok(self.segment * 16 + word(offset, keyword)?)
+    }
+
+    pub fn read(&self, address: u32) -> u8 {
+        self.bytes[address as usize % MEMORY_SIZE]
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) {
+        self.bytes[address as usize % MEMORY_SIZE] = value;
+    }
+}
+
+/// A byte for POKE, which must be 0 to 255
+pub fn byte(value: f64) -> Result<u8, InterpreterError> {
+    let value = value.trunc();
+    if !(0.0..=255.0).contains(&value) {
+        return Err(InterpreterError::RuntimeError(format!(
+            "POKE stores 0 to 255, not {}",
+            value
+        )));
+    }
+    Ok(value as u8)
+}
+
+/// An address or segment: 0 to 65535, or -32768 to -1 for the top half
+/// as 8-bit listings and `&H` numbers write it
+fn word(value: f64, keyword: &str) -> Result<u32, InterpreterError> {
+    let value = value.trunc();
+    if !(-32768.0..=65535.0).contains(&value) {
+        return Err(InterpreterError::RuntimeError(format!(
+            "{} takes -32768 to 65535, not {}",
+            keyword, value
+        )));
+    }
+    Ok((value as i64).rem_euclid(MEMORY_SIZE as i64) as u32)
+}
@@ -0,0 +1,143 @@
+pub mod ast;
+pub mod codes;
+pub mod detokenize;
+pub mod dialect;
+pub mod dump;
+pub mod events;
+pub mod files;
+pub mod include;
+pub mod interpreter;
+pub mod keyboard;
+pub mod lint;
+pub mod matching;
+pub mod memory;
+pub mod music;
+pub mod parser;
+pub mod pump;
+pub mod snapshot;
+pub mod strict;
+pub mod tokenizer;
+pub mod tree;
+pub mod using;
+
+// Re-export main types for convenience
+pub use ast::{
+    AssertionResult, ExecutionResult, Expression, GraphicsCommand, InterpreterError, LineProfile,
+    Permission, Position, Program, Statement, StepMode, Token, TrapState, TurtleCommand, Value,
+    DEFAULT_TURTLE,
+};
+pub use codes::ErrorCode;
+pub use events::EventSource;
+pub use interpreter::Interpreter;
+pub use parser::Parser;
+pub use tokenizer::{SpannedToken, Tokenizer};
+
+/// Keywords offered by completion and highlighted in the editor
+pub const KEYWORDS: &[&str] = &[
+    "PRINT",
+    "LPRINT",
+    "USING",
+    "INPUT",
+    "LET",
+    "IF",
+    "THEN",
+    "ELSE",
+    "FOR",
+    "TO",
+    "STEP",
+    "NEXT",
+    "WHILE",
+    "WEND",
+    "DO",
+    "LOOP",
+    "UNTIL",
+    "ELSEIF",
+    "SUB",
+    "CALL",
+    "EXIT",
+    "GOTO",
+    "GOSUB",
+    "RETURN",
+    "ON",
+    "OFF",
+    "ERROR",
+    "RESUME",
+    "ERR",
+    "ERL",
+    "OPEN",
+    "CLOSE",
+    "WRITE",
+    "LINE",
+    "AS",
+    "OUTPUT",
+    "APPEND",
+    "EOF",
+    "LOF",
+    "RANDOM",
+    "FIELD",
+    "LSET",
+    "RSET",
+    "GET",
+    "PUT",
+    "FILES",
+    "KILL",
+    "NAME",
+    "MKDIR",
+    "CHDIR",
+    "RMDIR",
+    "INKEY$",
+    "MOUSEX",
+    "MOUSEY",
+    "MOUSEB",
+    "PEEK",
+    "POKE",
+    "SEG",
+    "MKI$",
+    "MKS$",
+    "MKD$",
+    "CVI",
+    "CVS",
+    "CVD",
+    "KEY",
+    "TIMER",
+    "PLAY",
+    "MOUSE",
+    "END",
+    "ASSERT",
+    "CLS",
+    "LOCATE",
+    "COLOR",
+    "BEEP",
+    "SOUND",
+    "SLEEP",
+    "WAIT",
+    "RANDOMIZE",
+    "RND",
+    "INT",
+    "STR$",
+    "VAL",
+    "LEN",
+    "LEFT$",
+    "RIGHT$",
+    "MID$",
+    "MATCH",
+    "SOUNDEX$",
+    "SIMILAR",
+    "CHR$",
+    "ASC",
+    "ABS",
+    "SIN",
+    "COS",
+    "TAN",
+    "LOG",
+    "EXP",
+    "SQR",
+    "AND",
+    "OR",
+    "NOT",
+    "MOD",
+    "DIM",
+    "DEF",
+    "FN",
+    "REM",
+];
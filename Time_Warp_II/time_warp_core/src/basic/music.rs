@@ -0,0 +1,346 @@
+//! PLAY's music macro language, as in GW-BASIC:
+//!
+//! | Command        | Does                                                        |
+//! |----------------|-------------------------------------------------------------|
+//! | `A` to `G`     | a note, sharpened by `#` or `+`, flattened by `-`; a length after it (`C8`) and dots (`C4.`) as for `L` |
+//! | `N n`          | note 1 to 84 by number, with 0 a rest                       |
+//! | `O n`, `<`, `>`| octave 0 to 6, or one down or up; octave 3 starts at middle C |
+//! | `L n`          | length of the notes after it: 1 a whole note, 4 a quarter   |
+//! | `P n`          | a pause, of length `n` as for `L`                           |
+//! | `T n`          | tempo, 32 to 255 quarter notes a minute                     |
+//! | `MN`, `ML`, `MS` | notes sound 7/8 of their length, all of it, or 3/4     |
+//! | `MF`, `MB`     | the program waits for the music, or carries on while it plays |
+//!
+//! BEEP and SOUND queue their tones behind PLAY's notes, and wait for them
+//! the same way. Whatever drives the interpreter takes the notes with `take_music` and
+//! plays them; `to_wav` renders them for a sound player.
+
+use crate::basic::ast::InterpreterError;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Samples a second in `to_wav`'s sound
+pub const SAMPLE_RATE: u32 = 22_050;
+
+/// SOUND counts its length in the PC's clock ticks
+pub const TICKS_PER_SECOND: f64 = 18.2;
+
+// BEEP, as the PC speaker sounds it
+pub const BEEP_FREQUENCY: f64 = 800.0;
+pub const BEEP_LENGTH: Duration = Duration::from_millis(250);
+
+/// A note or rest, ready to play
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub frequency: Option<f64>, // None for a rest
+    pub length: Duration,       // until the next note starts
+    pub sounding: Duration,     // how much of that the note is heard
+}
+
+/// How much of its length a note sounds: MN, ML or MS
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+    Normal,
+    Legato,
+    Staccato,
+}
+
+impl Style {
+    fn fraction(self) -> f64 {
+        match self {
+            Style::Normal => 7.0 / 8.0,
+            Style::Legato => 1.0,
+            Style::Staccato => 3.0 / 4.0,
+        }
+    }
+}
+
+/// Where PLAY is up to: the settings its strings left, the notes not yet
+/// taken, and when the background music ends
+#[derive(Debug, Clone)]
+pub struct Music {
+    octave: u32,
+    length: u32,
+    tempo: u32,
+    style: Style,
+    background: bool,
+    taken: Vec<Note>,
+    note_ends: VecDeque<Instant>, // each background note still to finish
+    was_low: bool,                // for ON PLAY, which fires as the queue runs low
+}
+
+impl Default for Music {
+    fn default() -> Self {
+        Self {
+            octave: 4,
+            length: 4,
+            tempo: 120,
+            style: Style::Normal,
+            background: false,
+            taken: Vec::new(),
+            note_ends: VecDeque::new(),
+            was_low: true,
+        }
+    }
+}
+
+impl Music {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// PLAY `mml`: queue its notes, after any music still playing. Gives
+    /// how long the program waits for it, which is none in the background.
+    pub fn play(&mut self, mml: &str, now: Instant) -> Result<Duration, InterpreterError> {
+        let notes = self.parse(mml)?;
+        Ok(self.queue(notes, now))
+    }
+
+    /// SOUND `frequency`, `ticks`: a tone held for all its length. No
+    /// ticks plays nothing.
+    pub fn sound(
+        &mut self,
+        frequency: f64,
+        ticks: f64,
+        now: Instant,
+    ) -> Result<Duration, InterpreterError> {
+        if !(37.0..=32767.0).contains(&frequency) {
+            return Err(InterpreterError::RuntimeError(format!(
+                "SOUND takes 37 to 32767 hertz, not {}",
+                frequency
+            )));
+        }
+        if !(0.0..=65535.0).contains(&ticks) {
+            return Err(InterpreterError::RuntimeError(format!(
+                "SOUND takes 0 to 65535 ticks, not {}",
+                ticks
+            )));
+        }
+        if ticks == 0.0 {
+            return Ok(Duration::ZERO);
+        }
+        let length = Duration::from_secs_f64(ticks / TICKS_PER_SECOND);
+        Ok(self.queue(vec![tone(frequency, length)], now))
+    }
+
+    pub fn beep(&mut self, now: Instant) -> Duration {
+        self.queue(vec![tone(BEEP_FREQUENCY, BEEP_LENGTH)], now)
+    }
+
+    /// Queue `notes` after any music still playing, giving how long the
+    /// program waits for them
+    fn queue(&mut self, notes: Vec<Note>, now: Instant) -> Duration {
+        while self.note_ends.front().is_some_and(|&end| end <= now) {
+            self.note_ends.pop_front();
+        }
+        let mut end = self.note_ends.back().copied().unwrap_or(now).max(now);
+        for note in &notes {
+            end += note.length;
+            if self.background && note.frequency.is_some() {
+                self.note_ends.push_back(end);
+            }
+        }
+        self.taken.extend(notes);
+        if self.background {
+            Duration::ZERO
+        } else {
+            end - now
+        }
+    }
+
+    /// The notes PLAY queued since last asked, in order
+    pub fn take(&mut self) -> Vec<Note> {
+        std::mem::take(&mut self.taken)
+    }
+
+    /// ON PLAY(n): whether the background notes still to play have just
+    /// dropped below `threshold`
+    pub fn ran_low(&mut self, threshold: usize, now: Instant) -> bool {
+        while self.note_ends.front().is_some_and(|&end| end <= now) {
+            self.note_ends.pop_front();
+        }
+        let low = self.note_ends.len() < threshold;
+        let ran_low = low && !self.was_low;
+        self.was_low = low;
+        ran_low
+    }
+
+    fn parse(&mut self, mml: &str) -> Result<Vec<Note>, InterpreterError> {
+        let mut notes = Vec::new();
+        let mut chars = mml.chars().filter(|c| !c.is_whitespace()).peekable();
+        while let Some(c) = chars.next() {
+            match c.to_ascii_uppercase() {
+                letter @ 'A'..='G' => {
+                    let mut semitone = match letter {
+                        'C' => 0,
+                        'D' => 2,
+                        'E' => 4,
+                        'F' => 5,
+                        'G' => 7,
+                        'A' => 9,
+                        _ => 11,
+                    };
+                    match chars.peek() {
+                        Some('#' | '+') => {
+                            semitone += 1;
+                            chars.next();
+                        }
+                        Some('-') => {
+                            semitone -= 1;
+                            chars.next();
+                        }
+                        _ => {}
+                    }
+                    let length = match number(&mut chars) {
+                        Some(length) => check("note length", length, 1, 64)?,
+                        None => self.length,
+                    };
+                    let dots = dots(&mut chars);
+                    let key = self.octave as i32 * 12 + semitone;
+                    notes.push(self.note(Some(key), length, dots));
+                }
+                'N' => {
+                    let key = check("N", required(&mut chars, 'N')?, 0, 84)?;
+                    let dots = dots(&mut chars);
+                    let key = (key > 0).then(|| key as i32 - 1);
+                    notes.push(self.note(key, self.length, dots));
+                }
+                'O' => self.octave = check("octave", required(&mut chars, 'O')?, 0, 6)?,
+                '>' => self.octave = (self.octave + 1).min(6),
+                '<' => self.octave = self.octave.saturating_sub(1),
+                'L' => self.length = check("L", required(&mut chars, 'L')?, 1, 64)?,
+                'T' => self.tempo = check("tempo", required(&mut chars, 'T')?, 32, 255)?,
+                'P' => {
+                    let length = check("P", required(&mut chars, 'P')?, 1, 64)?;
+                    let dots = dots(&mut chars);
+                    notes.push(self.note(None, length, dots));
+                }
+                'M' => match chars.next().map(|c| c.to_ascii_uppercase()) {
+                    Some('N') => self.style = Style::Normal,
+                    Some('L') => self.style = Style::Legato,
+                    Some('S') => self.style = Style::Staccato,
+                    Some('F') => self.background = false,
+                    Some('B') => self.background = true,
+                    other => return Err(bad_music(format!("M{}", other.unwrap_or(' ')))),
+                },
+                other => return Err(bad_music(other.to_string())),
+            }
+        }
+        Ok(notes)
+    }
+
+    /// A note `key` semitones above the lowest C (None for a rest), of
+    /// length 1/`length` of a whole note, with `dots` dots
+    fn note(&self, key: Option<i32>, length: u32, dots: u32) -> Note {
+        let quarter = 60.0 / self.tempo as f64;
+        let mut seconds = quarter * 4.0 / length as f64;
+        let mut dot = seconds;
+        for _ in 0..dots {
+            dot /= 2.0;
+            seconds += dot;
+        }
+        let length = Duration::from_secs_f64(seconds);
+        match key {
+            // A in octave 3, key 45, is 440 Hz
+            Some(key) => Note {
+                frequency: Some(440.0 * 2f64.powf((key - 45) as f64 / 12.0)),
+                length,
+                sounding: length.mul_f64(self.style.fraction()),
+            },
+            None => Note {
+                frequency: None,
+                length,
+                sounding: Duration::ZERO,
+            },
+        }
+    }
+}
+
+fn tone(frequency: f64, length: Duration) -> Note {
+    Note {
+        frequency: Some(frequency),
+        length,
+        sounding: length,
+    }
+}
+
+fn number(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) -> Option<u32> {
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        digits.push(c);
+        chars.next();
+    }
+    digits.parse().ok()
+}
+
+fn required(
+    chars: &mut std::iter::Peekable<impl Iterator<Item = char>>,
+    command: char,
+) -> Result<u32, InterpreterError> {
+    number(chars).ok_or_else(|| bad_music(format!("{} needs a number after it", command)))
+}
+
+fn dots(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) -> u32 {
+    let mut dots = 0;
+    while chars.next_if_eq(&'.').is_some() {
+        dots += 1;
+    }
+    dots
+}
+
+fn check(what: &str, value: u32, low: u32, high: u32) -> Result<u32, InterpreterError> {
+    if (low..=high).contains(&value) {
+        Ok(value)
+    } else {
+        Err(bad_music(format!(
+            "{} takes {} to {}, not {}",
+            what, low, high, value
+        )))
+    }
+}
+
+fn bad_music(detail: String) -> InterpreterError {
+    InterpreterError::RuntimeError(format!("PLAY can't read \"{}\"", detail))
+}
+
+/// `notes` as a 16-bit mono WAV file of square waves, like a PC speaker
+pub fn to_wav(notes: &[Note]) -> Vec<u8> {
+    let mut samples: Vec<i16> = Vec::new();
+    for note in notes {
+        let total = (note.length.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+        let sounding = (note.sounding.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+        for i in 0..total {
+            let sample = match note.frequency {
+                Some(frequency) if i < sounding => {
+                    let phase = (i as f64 * frequency / SAMPLE_RATE as f64).fract();
+                    if phase < 0.5 {
+                        6000
+                    } else {
+                        -6000
+                    }
+                }
+                _ => 0,
+            };
+            samples.push(sample);
+        }
+    }
+
+    let data_size = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // size of the format chunk
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // bytes a second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // bytes a sample
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits a sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
@@ -1,25 +1,39 @@
-use crate::languages::basic::ast::{
-    BinaryOperator, Expression, FunctionDefinition, InterpreterError, PrintSeparator, Program,
-    Statement, Token, UnaryOperator,
+use crate::basic::ast::{
+    BinaryOperator, EventKind, Expression, FileMode, FolderAction, InterpreterError, Justify,
+    Position, PrintChannel, PrintSeparator, Program, ResumeTarget, Statement, Token, TrapState,
+    UnaryOperator,
 };
+use crate::basic::codes::ErrorCode;
+use crate::basic::tokenizer::SpannedToken;
 
 /// Recursive descent parser for BASIC
 pub struct Parser {
     tokens: Vec<Token>,
     position: usize,
+    positions: Vec<Position>, // where each token is in the source
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
+        let positions = tokens.iter().map(SpannedToken::position).collect();
         Self {
-            tokens,
+            tokens: tokens.into_iter().map(|spanned| spanned.token).collect(),
             position: 0,
+            positions,
         }
     }
 
+    /// The program, or the first mistake in it and where it is
     pub fn parse_program(&mut self) -> Result<Program, InterpreterError> {
+        self.parse_lines()
+            .map_err(|err| err.at(self.current_position()))
+    }
+
+    fn parse_lines(&mut self) -> Result<Program, InterpreterError> {
         let mut statements = Vec::new();
         let mut line_numbers = std::collections::HashMap::new();
+        let mut statement_lines = Vec::new();
+        let mut statement_columns = Vec::new();
 
         while !self.is_at_end() {
             // Skip empty lines
@@ -42,9 +56,12 @@ impl Parser {
                 None
             };
 
+            let source = self.current_position();
             let statement = self.parse_statement()?;
             let statement_index = statements.len();
             statements.push(statement);
+            statement_lines.push(source.line);
+            statement_columns.push(source.column);
 
             // Store line number mapping if present
             if let Some(line_num) = line_number {
@@ -56,7 +73,8 @@ impl Parser {
                 && !self.match_token(&[Token::Eol])
                 && !self.is_at_end()
             {
-                return Err(InterpreterError::ParseError(
+                return Err(InterpreterError::parse(
+                    ErrorCode::ExpectedEndOfStatement,
                     "Expected ':' or end of line after statement".to_string(),
                 ));
             }
@@ -65,13 +83,15 @@ impl Parser {
         Ok(Program {
             statements,
             line_numbers,
+            statement_lines,
+            statement_columns,
         })
     }
 
     fn parse_statement(&mut self) -> Result<Statement, InterpreterError> {
         match self.current_token() {
             Some(Token::Let) => self.parse_let_statement(),
-            Some(Token::Print) => self.parse_print_statement(),
+            Some(Token::Print | Token::Lprint) => self.parse_print_statement(),
             Some(Token::Input) => self.parse_input_statement(),
             Some(Token::If) => self.parse_if_statement(),
             Some(Token::For) => self.parse_for_statement(),
@@ -81,10 +101,13 @@ impl Parser {
             Some(Token::Return) => self.parse_return_statement(),
             Some(Token::End) => self.parse_end_statement(),
             Some(Token::Stop) => self.parse_stop_statement(),
+            Some(Token::Assert) => self.parse_assert_statement(),
+            Some(Token::Randomize) => self.parse_randomize_statement(),
             Some(Token::Rem) => self.parse_rem_statement(),
             Some(Token::Dim) => self.parse_dim_statement(),
             Some(Token::Def) => self.parse_def_statement(),
             Some(Token::Clear) => self.parse_clear_statement(),
+            Some(Token::Cls) => self.parse_cls_statement(),
             Some(Token::Writeln) => self.parse_writeln_statement(),
             Some(Token::Printx) => self.parse_printx_statement(),
             Some(Token::Defint) => self.parse_defint_statement(),
@@ -100,12 +123,53 @@ impl Parser {
             Some(Token::Pendown) => self.parse_pendown_statement(),
             Some(Token::Home) => self.parse_home_statement(),
             Some(Token::Setxy) => self.parse_setxy_statement(),
+            Some(Token::SetHeading) => self.parse_set_heading_statement(),
+            Some(Token::ClearScreen) => self.parse_clear_screen_statement(),
+            Some(Token::Turtle) => self.parse_turtle_statement(),
             Some(Token::Turn) => self.parse_turn_statement(),
+            Some(Token::SetPenColor) => self.parse_set_pen_color_statement(),
+            Some(Token::PenSize) => self.parse_pen_size_statement(),
+            Some(Token::SetBackground) => self.parse_set_background_statement(),
+            Some(Token::Fill) => self.parse_fill_statement(),
+            Some(Token::HideTurtle) => self.parse_hide_turtle_statement(),
+            Some(Token::ShowTurtle) => self.parse_show_turtle_statement(),
+            Some(Token::Arc) => self.parse_arc_statement(),
+            Some(Token::Circle) => self.parse_circle_statement(),
+            Some(Token::Label) => self.parse_label_statement(),
+            Some(Token::LabelSize) => self.parse_label_size_statement(),
+            Some(Token::On) => self.parse_on_statement(),
+            Some(Token::Resume) => self.parse_resume_statement(),
+            Some(Token::Error) => {
+                self.advance();
+                let number = self.parse_expression()?;
+                Ok(Statement::Error { number })
+            }
+            Some(Token::Open) => self.parse_open_statement(),
+            Some(Token::Close) => self.parse_close_statement(),
+            Some(Token::Write) => self.parse_write_statement(),
+            Some(Token::Line) => self.parse_line_input_statement(),
+            Some(Token::Field) => self.parse_field_statement(),
+            Some(Token::Lset | Token::Rset) => self.parse_set_field_statement(),
+            Some(Token::Get | Token::Put) => self.parse_record_statement(),
+            Some(Token::Files) => self.parse_files_statement(),
+            Some(Token::Kill) => self.parse_kill_statement(),
+            Some(Token::Name) => self.parse_name_statement(),
+            Some(Token::Mkdir | Token::Chdir | Token::Rmdir) => self.parse_folder_statement(),
+            Some(Token::Poke) => self.parse_poke_statement(),
+            Some(Token::Sleep) => self.parse_sleep_statement(),
+            Some(Token::Wait) => self.parse_wait_statement(),
+            Some(Token::Play) => self.parse_play_statement(),
+            Some(Token::Beep) => {
+                self.advance();
+                Ok(Statement::Beep)
+            }
+            Some(Token::Sound) => self.parse_sound_statement(),
+            Some(Token::Key | Token::Timer | Token::Mouse) => self.parse_event_control_statement(),
             Some(Token::Identifier(_)) => self.parse_assignment_or_call(),
-            _ => Err(InterpreterError::ParseError(format!(
-                "Unexpected token in statement: {:?}",
-                self.current_token()
-            ))),
+            _ => Err(InterpreterError::parse(
+                ErrorCode::UnexpectedToken,
+                format!("Unexpected token in statement: {:?}", self.current_token()),
+            )),
         }
     }
 
@@ -120,8 +184,24 @@ impl Parser {
         })
     }
 
+    /// PRINT, PRINT #n, or LPRINT, any of them optionally USING a format
     fn parse_print_statement(&mut self) -> Result<Statement, InterpreterError> {
-        self.consume_token(Token::Print)?;
+        let channel = if self.match_token(&[Token::Lprint]) {
+            PrintChannel::Printer
+        } else {
+            self.consume_token(Token::Print)?;
+            match self.parse_file_number()? {
+                Some(number) => PrintChannel::File(number),
+                None => PrintChannel::Screen,
+            }
+        };
+        let using = if self.match_token(&[Token::Using]) {
+            let format = self.parse_expression()?;
+            self.consume_token(Token::Semicolon)?;
+            Some(format)
+        } else {
+            None
+        };
         let mut expressions = Vec::new();
         let mut separators = Vec::new();
 
@@ -139,6 +219,8 @@ impl Parser {
         }
 
         Ok(Statement::Print {
+            channel,
+            using,
             expressions,
             separators,
         })
@@ -146,9 +228,16 @@ impl Parser {
 
     fn parse_input_statement(&mut self) -> Result<Statement, InterpreterError> {
         self.consume_token(Token::Input)?;
+        if let Some(number) = self.parse_file_number()? {
+            let mut variables = vec![self.parse_identifier()?];
+            while self.match_token(&[Token::Comma]) {
+                variables.push(self.parse_identifier()?);
+            }
+            return Ok(Statement::FileInput { number, variables });
+        }
 
         // Check for optional prompt string
-        let prompt = if self.check(&[Token::String("".to_string())]) {
+        let prompt = if matches!(self.current_token(), Some(Token::String(_))) {
             let token = self.current_token().cloned();
             self.advance();
             if let Some(Token::String(s)) = token {
@@ -250,6 +339,26 @@ impl Parser {
         Ok(Statement::Stop)
     }
 
+    fn parse_assert_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Assert)?;
+        let condition = self.parse_expression()?;
+        let message = if self.match_token(&[Token::Comma]) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        Ok(Statement::Assert { condition, message })
+    }
+
+    fn parse_randomize_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Randomize)?;
+        let seed = match self.current_token() {
+            Some(Token::Eol | Token::Colon | Token::Eof | Token::Else) | None => None,
+            _ => Some(self.parse_expression()?),
+        };
+        Ok(Statement::Randomize { seed })
+    }
+
     fn parse_rem_statement(&mut self) -> Result<Statement, InterpreterError> {
         self.consume_token(Token::Rem)?;
         let comment = if let Some(Token::String(s)) = self.current_token().cloned() {
@@ -290,6 +399,17 @@ impl Parser {
 
     fn parse_def_statement(&mut self) -> Result<Statement, InterpreterError> {
         self.consume_token(Token::Def)?;
+        // DEF SEG [= segment]
+        if matches!(self.current_token(), Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("SEG"))
+        {
+            self.advance();
+            let segment = if self.match_token(&[Token::Equal]) {
+                Some(self.parse_expression()?)
+            } else {
+                None
+            };
+            return Ok(Statement::DefSeg { segment });
+        }
         self.consume_token(Token::Fn)?;
         let name = self.parse_identifier()?;
         self.consume_token(Token::LParen)?;
@@ -347,10 +467,10 @@ impl Parser {
                 identifier, arguments
             )))
         } else {
-            Err(InterpreterError::ParseError(format!(
-                "Expected '=' or '(' after identifier '{}'",
-                identifier
-            )))
+            Err(InterpreterError::parse(
+                ErrorCode::UnexpectedToken,
+                format!("Expected '=' or '(' after identifier '{}'", identifier),
+            ))
         }
     }
 
@@ -532,6 +652,14 @@ impl Parser {
                     arguments: vec![],
                 })
             }
+            Some(token @ (Token::Err | Token::Erl)) => {
+                self.advance();
+                let name = if token == Token::Err { "ERR" } else { "ERL" };
+                Ok(Expression::FunctionCall {
+                    name: name.to_string(),
+                    arguments: vec![],
+                })
+            }
             Some(Token::Tab) => {
                 self.advance();
                 self.consume_token(Token::LParen)?;
@@ -583,16 +711,29 @@ impl Parser {
                     arguments: vec![arg],
                 })
             }
-            Some(Token::Rnd) => {
+            Some(Token::Val) => {
                 self.advance();
                 self.consume_token(Token::LParen)?;
                 let arg = self.parse_expression()?;
                 self.consume_token(Token::RParen)?;
                 Ok(Expression::FunctionCall {
-                    name: "RND".to_string(),
+                    name: "VAL".to_string(),
                     arguments: vec![arg],
                 })
             }
+            Some(Token::Rnd) => {
+                self.advance();
+                // RND on its own is the same as RND(1)
+                let mut arguments = Vec::new();
+                if self.match_token(&[Token::LParen]) {
+                    arguments.push(self.parse_expression()?);
+                    self.consume_token(Token::RParen)?;
+                }
+                Ok(Expression::FunctionCall {
+                    name: "RND".to_string(),
+                    arguments,
+                })
+            }
             Some(Token::Environ) => {
                 self.advance();
                 // ENVIRON requires an argument
@@ -629,6 +770,9 @@ impl Parser {
                             | "ASC"
                             | "VAL"
                             | "STR"
+                            | "MOUSEX"
+                            | "MOUSEY"
+                            | "MOUSEB"
                     );
 
                 if is_function {
@@ -664,10 +808,10 @@ impl Parser {
                 self.consume_token(Token::RParen)?;
                 Ok(expr)
             }
-            _ => Err(InterpreterError::ParseError(format!(
-                "Unexpected token in expression: {:?}",
-                self.current_token()
-            ))),
+            _ => Err(InterpreterError::parse(
+                ErrorCode::UnexpectedToken,
+                format!("Unexpected token in expression: {:?}", self.current_token()),
+            )),
         }
     }
 
@@ -676,7 +820,8 @@ impl Parser {
             self.advance();
             Ok(id)
         } else {
-            Err(InterpreterError::ParseError(
+            Err(InterpreterError::parse(
+                ErrorCode::ExpectedToken,
                 "Expected identifier".to_string(),
             ))
         }
@@ -696,6 +841,11 @@ impl Parser {
         Ok(Statement::Clear)
     }
 
+    fn parse_cls_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Cls)?;
+        Ok(Statement::Cls)
+    }
+
     fn parse_writeln_statement(&mut self) -> Result<Statement, InterpreterError> {
         self.consume_token(Token::Writeln)?;
         let expression = self.parse_expression()?;
@@ -762,6 +912,9 @@ impl Parser {
 
         let mut cases = Vec::new();
         while !self.check(&[Token::End]) {
+            // Cases may be separated by blank lines
+            while self.match_token(&[Token::Eol]) {}
+
             if self.match_token(&[Token::Case]) {
                 let value = if self.check(&[Token::Else]) {
                     self.consume_token(Token::Else)?;
@@ -778,7 +931,7 @@ impl Parser {
                     }
                 }
 
-                cases.push(crate::languages::basic::ast::SelectCase { value, statements });
+                cases.push(crate::basic::ast::SelectCase { value, statements });
             } else {
                 break;
             }
@@ -837,12 +990,470 @@ impl Parser {
         Ok(Statement::Setxy { x, y })
     }
 
+    fn parse_set_heading_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::SetHeading)?;
+        let angle = self.parse_expression()?;
+        Ok(Statement::SetHeading { angle })
+    }
+
+    fn parse_clear_screen_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::ClearScreen)?;
+        Ok(Statement::ClearScreen)
+    }
+
+    fn parse_fill_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Fill)?;
+        Ok(Statement::Fill)
+    }
+
+    fn parse_hide_turtle_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::HideTurtle)?;
+        Ok(Statement::HideTurtle)
+    }
+
+    fn parse_show_turtle_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::ShowTurtle)?;
+        Ok(Statement::ShowTurtle)
+    }
+
+    fn parse_arc_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Arc)?;
+        let radius = self.parse_expression()?;
+        self.consume_token(Token::Comma)?;
+        let angle = self.parse_expression()?;
+        Ok(Statement::Arc { radius, angle })
+    }
+
+    fn parse_circle_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Circle)?;
+        let radius = self.parse_expression()?;
+        Ok(Statement::Circle { radius })
+    }
+
+    fn parse_label_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Label)?;
+        let text = self.parse_expression()?;
+        Ok(Statement::Label { text })
+    }
+
+    fn parse_label_size_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::LabelSize)?;
+        let size = self.parse_expression()?;
+        Ok(Statement::LabelSize { size })
+    }
+
+    fn parse_turtle_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Turtle)?;
+        let name = self.parse_expression()?;
+        Ok(Statement::Turtle { name })
+    }
+
+    /// ON ERROR GOTO n, or ON KEY(n) / TIMER(n) / PLAY(n) / MOUSE GOSUB n
+    fn parse_on_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::On)?;
+        if self.match_token(&[Token::Error]) {
+            self.consume_token(Token::Goto)?;
+            let line = self.parse_expression()?;
+            return Ok(Statement::OnError { line });
+        }
+        let (event, argument) = self.parse_event_source()?;
+        self.consume_token(Token::Gosub)?;
+        let line = self.parse_expression()?;
+        Ok(Statement::OnEvent {
+            event,
+            argument,
+            line,
+        })
+    }
+
+    fn parse_resume_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Resume)?;
+        let target = if self.match_token(&[Token::Next]) {
+            ResumeTarget::Next
+        } else if self.check(&[Token::Eol, Token::Eof, Token::Colon, Token::Else]) {
+            ResumeTarget::Retry
+        } else {
+            match self.parse_expression()? {
+                Expression::Number(0.0) => ResumeTarget::Retry,
+                line => ResumeTarget::Line(line),
+            }
+        };
+        Ok(Statement::Resume { target })
+    }
+
+    /// OPEN "file" [FOR INPUT / OUTPUT / APPEND / RANDOM] AS #n [LEN=size];
+    /// without FOR the file is RANDOM
+    fn parse_open_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Open)?;
+        let file = self.parse_expression()?;
+        let mode = if self.match_token(&[Token::For]) {
+            let mode = match self.current_token() {
+                Some(Token::Input) => FileMode::Input,
+                Some(Token::Output) => FileMode::Output,
+                Some(Token::Append) => FileMode::Append,
+                Some(Token::Random) => FileMode::Random,
+                other => {
+                    return Err(InterpreterError::parse(
+                        ErrorCode::ExpectedToken,
+                        format!(
+                            "Expected INPUT, OUTPUT, APPEND or RANDOM after FOR, found {:?}",
+                            other
+                        ),
+                    ))
+                }
+            };
+            self.advance();
+            mode
+        } else {
+            FileMode::Random
+        };
+        self.consume_token(Token::As)?;
+        self.match_token(&[Token::Hash]);
+        let number = self.parse_expression()?;
+        let length = if self.match_token(&[Token::Len]) {
+            self.consume_token(Token::Equal)?;
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        Ok(Statement::Open {
+            file,
+            mode,
+            number,
+            length,
+        })
+    }
+
+    /// FIELD #n, width AS variable$, width AS variable$, ...
+    fn parse_field_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Field)?;
+        self.match_token(&[Token::Hash]);
+        let number = self.parse_expression()?;
+        let mut fields = Vec::new();
+        while self.match_token(&[Token::Comma]) {
+            let width = self.parse_expression()?;
+            self.consume_token(Token::As)?;
+            fields.push((width, self.parse_identifier()?));
+        }
+        Ok(Statement::Field { number, fields })
+    }
+
+    /// LSET variable$ = text, or RSET
+    fn parse_set_field_statement(&mut self) -> Result<Statement, InterpreterError> {
+        let justify = if self.match_token(&[Token::Lset]) {
+            Justify::Left
+        } else {
+            self.consume_token(Token::Rset)?;
+            Justify::Right
+        };
+        let variable = self.parse_identifier()?;
+        self.consume_token(Token::Equal)?;
+        let expression = self.parse_expression()?;
+        Ok(Statement::SetField {
+            justify,
+            variable,
+            expression,
+        })
+    }
+
+    /// GET #n[, record] or PUT #n[, record]
+    fn parse_record_statement(&mut self) -> Result<Statement, InterpreterError> {
+        let get = self.match_token(&[Token::Get]);
+        if !get {
+            self.consume_token(Token::Put)?;
+        }
+        self.match_token(&[Token::Hash]);
+        let number = self.parse_expression()?;
+        let record = if self.match_token(&[Token::Comma]) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        Ok(if get {
+            Statement::Get { number, record }
+        } else {
+            Statement::Put { number, record }
+        })
+    }
+
+    /// FILES, or FILES pattern$
+    fn parse_files_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Files)?;
+        let pattern = if self.check(&[Token::Eol, Token::Eof, Token::Colon, Token::Else]) {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        Ok(Statement::Files { pattern })
+    }
+
+    /// KILL pattern$
+    fn parse_kill_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Kill)?;
+        let pattern = self.parse_expression()?;
+        Ok(Statement::Kill { pattern })
+    }
+
+    /// NAME old$ AS new$
+    fn parse_name_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Name)?;
+        let from = self.parse_expression()?;
+        self.consume_token(Token::As)?;
+        let to = self.parse_expression()?;
+        Ok(Statement::Name { from, to })
+    }
+
+    /// POKE address, value
+    fn parse_poke_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Poke)?;
+        let address = self.parse_expression()?;
+        self.consume_token(Token::Comma)?;
+        let value = self.parse_expression()?;
+        Ok(Statement::Poke { address, value })
+    }
+
+    /// SLEEP [seconds]
+    fn parse_sleep_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Sleep)?;
+        let seconds = match self.current_token() {
+            Some(Token::Eol | Token::Colon | Token::Eof | Token::Else) | None => None,
+            _ => Some(self.parse_expression()?),
+        };
+        Ok(Statement::Sleep { seconds })
+    }
+
+    /// PLAY music$, or PLAY ON / OFF / STOP for the ON PLAY trap
+    fn parse_play_statement(&mut self) -> Result<Statement, InterpreterError> {
+        if matches!(
+            self.tokens.get(self.position + 1),
+            Some(Token::On | Token::Off | Token::Stop)
+        ) {
+            return self.parse_event_control_statement();
+        }
+        self.consume_token(Token::Play)?;
+        let music = self.parse_expression()?;
+        Ok(Statement::Play { music })
+    }
+
+    /// SOUND frequency, ticks
+    fn parse_sound_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Sound)?;
+        let frequency = self.parse_expression()?;
+        self.consume_token(Token::Comma)?;
+        let ticks = self.parse_expression()?;
+        Ok(Statement::Sound { frequency, ticks })
+    }
+
+    /// WAIT ticks
+    fn parse_wait_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Wait)?;
+        let ticks = self.parse_expression()?;
+        Ok(Statement::Wait { ticks })
+    }
+
+    /// MKDIR dir$, CHDIR dir$ or RMDIR dir$
+    fn parse_folder_statement(&mut self) -> Result<Statement, InterpreterError> {
+        let action = match self.current_token() {
+            Some(Token::Mkdir) => FolderAction::Make,
+            Some(Token::Chdir) => FolderAction::Change,
+            _ => FolderAction::Remove,
+        };
+        self.advance();
+        let dir = self.parse_expression()?;
+        Ok(Statement::Folder { action, dir })
+    }
+
+    /// CLOSE, or CLOSE #n, #m, ...
+    fn parse_close_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Close)?;
+        let mut numbers = Vec::new();
+        if !self.check(&[Token::Eol, Token::Eof, Token::Colon, Token::Else]) {
+            loop {
+                self.match_token(&[Token::Hash]);
+                numbers.push(self.parse_expression()?);
+                if !self.match_token(&[Token::Comma]) {
+                    break;
+                }
+            }
+        }
+        Ok(Statement::Close { numbers })
+    }
+
+    /// WRITE [#n,] value, value, ...
+    fn parse_write_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Write)?;
+        let file = self.parse_file_number()?;
+        let mut expressions = Vec::new();
+        if !self.check(&[Token::Eol, Token::Eof, Token::Colon, Token::Else]) {
+            expressions.push(self.parse_expression()?);
+            while self.match_token(&[Token::Comma]) {
+                expressions.push(self.parse_expression()?);
+            }
+        }
+        Ok(Statement::Write { file, expressions })
+    }
+
+    /// LINE INPUT #n, variable$, or LINE INPUT ["prompt";] variable$ from
+    /// the keyboard, which is INPUT taking the whole line
+    fn parse_line_input_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::Line)?;
+        if !self.check(&[Token::Input]) {
+            return Err(InterpreterError::parse(
+                ErrorCode::UnexpectedToken,
+                "LINE is only understood as LINE INPUT".to_string(),
+            ));
+        }
+        match self.parse_input_statement()? {
+            Statement::FileInput {
+                number,
+                mut variables,
+            } => {
+                if variables.len() > 1 {
+                    return Err(InterpreterError::parse(
+                        ErrorCode::ExpectedEndOfStatement,
+                        "LINE INPUT # reads one line into one variable".to_string(),
+                    ));
+                }
+                Ok(Statement::LineInput {
+                    number,
+                    variable: variables.remove(0),
+                })
+            }
+            input => Ok(input),
+        }
+    }
+
+    /// The `#n,` a file statement starts with, if it has one
+    fn parse_file_number(&mut self) -> Result<Option<Expression>, InterpreterError> {
+        if !self.match_token(&[Token::Hash]) {
+            return Ok(None);
+        }
+        let number = self.parse_expression()?;
+        if !self.check(&[Token::Eol, Token::Eof, Token::Colon]) {
+            self.consume_token(Token::Comma)?;
+        }
+        Ok(Some(number))
+    }
+
+    /// KEY(n) / TIMER / PLAY / MOUSE followed by ON, OFF or STOP
+    fn parse_event_control_statement(&mut self) -> Result<Statement, InterpreterError> {
+        let (event, argument) = self.parse_event_source()?;
+        let state = match self.current_token() {
+            Some(Token::On) => TrapState::On,
+            Some(Token::Off) => TrapState::Off,
+            Some(Token::Stop) => TrapState::Stopped,
+            other => {
+                return Err(InterpreterError::parse(
+                    ErrorCode::ExpectedToken,
+                    format!(
+                        "Expected ON, OFF or STOP after {:?}, found {:?}",
+                        event, other
+                    ),
+                ))
+            }
+        };
+        self.advance();
+        Ok(Statement::EventControl {
+            event,
+            argument,
+            state,
+        })
+    }
+
+    /// An event keyword with its optional parenthesised argument
+    fn parse_event_source(&mut self) -> Result<(EventKind, Option<Expression>), InterpreterError> {
+        let event = match self.current_token() {
+            Some(Token::Key) => EventKind::Key,
+            Some(Token::Timer) => EventKind::Timer,
+            Some(Token::Play) => EventKind::Play,
+            Some(Token::Mouse) => EventKind::Mouse,
+            other => {
+                return Err(InterpreterError::parse(
+                    ErrorCode::ExpectedToken,
+                    format!("Expected KEY, TIMER, PLAY or MOUSE, found {:?}", other),
+                ))
+            }
+        };
+        self.advance();
+        let argument = if self.match_token(&[Token::LParen]) {
+            let argument = self.parse_expression()?;
+            self.consume_token(Token::RParen)?;
+            Some(argument)
+        } else {
+            None
+        };
+        if event == EventKind::Key && argument.is_none() {
+            return Err(InterpreterError::parse(
+                ErrorCode::ExpectedToken,
+                "KEY needs a key number, e.g. KEY(1)".to_string(),
+            ));
+        }
+        Ok((event, argument))
+    }
+
     fn parse_turn_statement(&mut self) -> Result<Statement, InterpreterError> {
         self.consume_token(Token::Turn)?;
         let angle = self.parse_expression()?;
         Ok(Statement::Turn { angle })
     }
 
+    fn parse_set_pen_color_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::SetPenColor)?;
+        let components = self.parse_color_components("SETPENCOLOR")?;
+        Ok(Statement::SetPenColor { components })
+    }
+
+    fn parse_set_background_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::SetBackground)?;
+        let components = self.parse_color_components("SETBACKGROUND")?;
+        Ok(Statement::SetBackground { components })
+    }
+
+    /// A palette index, or red, green, blue
+    fn parse_color_components(
+        &mut self,
+        command: &str,
+    ) -> Result<Vec<Expression>, InterpreterError> {
+        let mut components = vec![self.parse_expression()?];
+        while self.check(&[Token::Comma]) {
+            self.advance();
+            components.push(self.parse_expression()?);
+        }
+        if components.len() != 1 && components.len() != 3 {
+            return Err(InterpreterError::parse(
+                ErrorCode::ExpectedToken,
+                format!("{} expects a colour number or red, green, blue", command),
+            ));
+        }
+        Ok(components)
+    }
+
+    fn parse_pen_size_statement(&mut self) -> Result<Statement, InterpreterError> {
+        self.consume_token(Token::PenSize)?;
+        let size = self.parse_expression()?;
+        Ok(Statement::PenSize { size })
+    }
+
+    /// Source line of the current token, used to map statements back to the
+    /// editor and, after an error, to say where it is
+    pub fn current_line(&self) -> usize {
+        self.current_position().line
+    }
+
+    /// Where the current token is in the source
+    pub fn current_position(&self) -> Position {
+        self.positions
+            .get(self.position)
+            .or_else(|| self.positions.last())
+            .copied()
+            .unwrap_or(Position { line: 1, column: 1 })
+    }
+
+    /// Index of the current token; after an error, the one that was wrong
+    pub fn token_index(&self) -> usize {
+        self.position
+    }
+
     fn previous_token(&self) -> Option<&Token> {
         if self.position > 0 {
             Some(&self.tokens[self.position - 1])
@@ -859,7 +1470,7 @@ impl Parser {
 
     fn match_token(&mut self, tokens: &[Token]) -> bool {
         for token in tokens {
-            if self.check(&[token.clone()]) {
+            if self.check(std::slice::from_ref(token)) {
                 self.advance();
                 return true;
             }
@@ -876,15 +1487,14 @@ impl Parser {
     }
 
     fn consume_token(&mut self, expected: Token) -> Result<(), InterpreterError> {
-        if self.check(&[expected.clone()]) {
+        if self.check(std::slice::from_ref(&expected)) {
             self.advance();
             Ok(())
         } else {
-            Err(InterpreterError::ParseError(format!(
-                "Expected {:?}, found {:?}",
-                expected,
-                self.current_token()
-            )))
+            Err(InterpreterError::parse(
+                ErrorCode::ExpectedToken,
+                format!("Expected {:?}, found {:?}", expected, self.current_token()),
+            ))
         }
     }
 
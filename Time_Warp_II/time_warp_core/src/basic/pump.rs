@@ -0,0 +1,124 @@
+//! The event pump: how a run driven by the IDE gets the keyboard and mouse.
+//!
+//! Keys and the mouse only change between the IDE's frames, so a program
+//! that waits on them (INKEY$ with no key waiting, MOUSEX, MOUSEY and
+//! MOUSEB, or an ON KEY, ON TIMER or ON MOUSE trap that is on) hands control
+//! back with `ExecutionResult::Polling` about once a frame. The IDE passes
+//! in what happened meanwhile and calls `run` again.
+//!
+//! SLEEP and WAIT hand back every time until they are over, so the IDE
+//! stays responsive and ⏹ Stop works while a program sleeps. Runs that
+//! aren't live don't wait; the pause counts against their instruction limit.
+
+use std::time::{Duration, Instant};
+
+/// How long a polling program runs before handing control back
+pub const YIELD_INTERVAL: Duration = Duration::from_millis(15);
+
+// MOUSEB adds these up for the buttons held down
+pub const LEFT_BUTTON: u8 = 1;
+pub const RIGHT_BUTTON: u8 = 2;
+pub const MIDDLE_BUTTON: u8 = 4;
+
+/// Where the mouse is over the canvas, in the coordinates SETXY uses, and
+/// which buttons are down; it stays where it last was once it leaves
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Mouse {
+    pub x: f64,
+    pub y: f64,
+    pub buttons: u8,
+}
+
+/// A SLEEP or WAIT in progress
+#[derive(Debug, Clone, Copy)]
+struct Sleep {
+    until: Option<Instant>, // None sleeps until a key is pressed
+    wake_on_key: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pump {
+    live: bool,    // kept across loads
+    waiting: bool, // the program read input that only changes between frames
+    slice_started: Instant,
+    sleep: Option<Sleep>,
+}
+
+impl Default for Pump {
+    fn default() -> Self {
+        Self {
+            live: false,
+            waiting: false,
+            slice_started: Instant::now(),
+            sleep: None,
+        }
+    }
+}
+
+impl Pump {
+    pub fn reset(&mut self) {
+        self.waiting = false;
+        self.sleep = None;
+    }
+
+    pub fn set_live(&mut self, live: bool) {
+        self.live = live;
+    }
+
+    pub fn live(&self) -> bool {
+        self.live
+    }
+
+    /// Sleep for `duration`, or with None until a key is pressed; with
+    /// `wake_on_key` a key ends it early
+    pub fn sleep(&mut self, duration: Option<Duration>, wake_on_key: bool) {
+        self.sleep = Some(Sleep {
+            until: duration.map(|duration| Instant::now() + duration),
+            wake_on_key,
+        });
+    }
+
+    /// The program read the keyboard or mouse and found nothing new, or
+    /// traps events from them
+    pub fn wait(&mut self) {
+        self.waiting = self.live;
+    }
+
+    /// The program got a key, so it isn't waiting any more
+    pub fn stop_waiting(&mut self) {
+        self.waiting = false;
+    }
+
+    /// Count the time to the next hand-back from now, as a run starts
+    pub fn start_slice(&mut self) {
+        self.slice_started = Instant::now();
+    }
+
+    /// Whether a SLEEP or WAIT is still going, ending it once it is over
+    pub fn sleeping(&mut self, key_waiting: bool) -> bool {
+        let Some(sleep) = self.sleep else {
+            return false;
+        };
+        let woken = (sleep.wake_on_key && key_waiting)
+            || sleep.until.is_some_and(|until| Instant::now() >= until);
+        if woken {
+            self.sleep = None;
+        }
+        !woken
+    }
+
+    /// Whether the run should hand control back: it is still sleeping, or
+    /// is waiting for input and has had its time or is about to run out of
+    /// statements
+    pub fn take_yield(&mut self, out_of_time: bool, key_waiting: bool) -> bool {
+        if self.sleeping(key_waiting) {
+            return true;
+        }
+        if !self.waiting {
+            return false;
+        }
+        let due = out_of_time || self.slice_started.elapsed() >= YIELD_INTERVAL;
+        self.waiting = !due;
+        due
+    }
+}
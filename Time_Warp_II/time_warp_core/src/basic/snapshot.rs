@@ -0,0 +1,495 @@
+//! Saving a paused TW BASIC run to a file and picking it up in a later
+//! session, so a long demo doesn't have to start over.
+//!
+//! A session file is JSON: the program source, the interpreter's state
+//! (variables, arrays, FOR loops, the GOSUB stack, DATA pointer, random
+//! seed, turtle and pens), the output shown so far and the turtle moves
+//! made so far, which are replayed to redraw the canvas. Event traps (ON
+//! KEY, ON TIMER, ...) are not kept; the program sets them up again when it
+//! next runs the statements that create them.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::basic::ast::{
+    ExecutionContext, ForLoop, FunctionDefinition, GraphicsCommand, Program, Statement,
+    TurtleCommand, Value, VariableInfo, VariableType,
+};
+use crate::basic::Interpreter;
+use crate::json::Json;
+
+/// Written into every session file so other JSON isn't mistaken for one
+pub const FORMAT: &str = "time-warp-session";
+pub const VERSION: usize = 1;
+
+/// A paused run and what it has shown so far
+pub struct Session {
+    pub source: String,
+    pub interpreter: Interpreter,
+    pub output: String,
+    pub graphics: Vec<TurtleCommand>,
+    pub prompt: Option<String>, // set while the run waits on INPUT
+}
+
+impl Session {
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("format", FORMAT.into()),
+            ("version", VERSION.into()),
+            ("source", self.source.as_str().into()),
+            ("state", self.interpreter.save_state()),
+            ("output", self.output.as_str().into()),
+            (
+                "graphics",
+                Json::Array(self.graphics.iter().map(turtle_command_to_json).collect()),
+            ),
+            (
+                "prompt",
+                self.prompt.as_deref().map(Json::from).unwrap_or(Json::Null),
+            ),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Session, String> {
+        if json.get("format").and_then(Json::as_str) != Some(FORMAT) {
+            return Err("This is not a Time Warp session file".to_string());
+        }
+        let version = json.get("version").and_then(Json::as_usize).unwrap_or(0);
+        if version > VERSION {
+            return Err(format!(
+                "This session was saved by a newer Time Warp (format {})",
+                version
+            ));
+        }
+        let source = text(json, "source")?;
+        let mut interpreter = Interpreter::new();
+        interpreter.restore_state(&source, json.get("state").unwrap_or(&Json::Null))?;
+        let graphics = match json.get("graphics") {
+            Some(Json::Array(commands)) => commands
+                .iter()
+                .map(turtle_command_from_json)
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        };
+        Ok(Session {
+            source,
+            interpreter,
+            output: json
+                .get("output")
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            graphics,
+            prompt: json
+                .get("prompt")
+                .and_then(Json::as_str)
+                .map(str::to_string),
+        })
+    }
+}
+
+pub fn context_to_json(context: &ExecutionContext) -> Json {
+    let variables = sorted(&context.variables)
+        .into_iter()
+        .map(|(name, info)| {
+            let value = Json::object([
+                ("value", value_to_json(&info.value)),
+                ("type", variable_type_name(&info.declared_type).into()),
+            ]);
+            (name.clone(), value)
+        })
+        .collect();
+    let arrays = sorted(&context.arrays)
+        .into_iter()
+        .map(|(name, values)| {
+            let values = Json::Array(values.iter().map(value_to_json).collect());
+            (name.clone(), values)
+        })
+        .collect();
+    let type_declarations = sorted(&context.type_declarations)
+        .into_iter()
+        .map(|(range, kind)| (range.clone(), variable_type_name(kind).into()))
+        .collect();
+    let mut functions: Vec<Json> = context
+        .functions
+        .keys()
+        .map(|name| name.as_str().into())
+        .collect();
+    functions.sort_by_key(|name| name.to_string());
+    let mut pens_up: Vec<Json> = context
+        .pens_up
+        .iter()
+        .map(|turtle| turtle.as_str().into())
+        .collect();
+    pens_up.sort_by_key(|turtle| turtle.to_string());
+
+    Json::object([
+        ("variables", Json::Object(variables)),
+        ("arrays", Json::Object(arrays)),
+        ("functions", Json::Array(functions)),
+        (
+            "for_loops",
+            Json::Array(
+                context
+                    .for_loops
+                    .iter()
+                    .map(|for_loop| {
+                        Json::object([
+                            ("variable", for_loop.variable.as_str().into()),
+                            ("end", Json::Number(for_loop.end_value)),
+                            ("step", Json::Number(for_loop.step_value)),
+                            ("line_index", for_loop.line_index.into()),
+                            ("body_start", for_loop.body_start.into()),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        (
+            "gosub_stack",
+            Json::Array(
+                context
+                    .gosub_stack
+                    .iter()
+                    .map(|&line| line.into())
+                    .collect(),
+            ),
+        ),
+        (
+            "data",
+            Json::Array(context.data.iter().map(value_to_json).collect()),
+        ),
+        ("data_pointer", context.data_pointer.into()),
+        ("random_seed", Json::Number(context.random_seed as f64)),
+        ("array_base", context.array_base.into()),
+        (
+            "input_variable",
+            context
+                .input_variable
+                .as_deref()
+                .map(Json::from)
+                .unwrap_or(Json::Null),
+        ),
+        ("type_declarations", Json::Object(type_declarations)),
+        ("turtle", context.turtle.as_str().into()),
+        ("pens_up", Json::Array(pens_up)),
+    ])
+}
+
+/// Fill `context` from `context_to_json`'s output. DEF FN bodies are
+/// taken from `program`, which must be the program that was saved.
+pub fn context_from_json(
+    context: &mut ExecutionContext,
+    json: &Json,
+    program: &Program,
+) -> Result<(), String> {
+    let mut variables = HashMap::new();
+    for (name, info) in fields(json, "variables")? {
+        let value = value_from_json(info.get("value").unwrap_or(&Json::Null))?;
+        let declared_type = variable_type_from_name(&text(info, "type")?)?;
+        variables.insert(
+            name.clone(),
+            VariableInfo {
+                value,
+                declared_type,
+            },
+        );
+    }
+
+    let mut arrays = HashMap::new();
+    for (name, values) in fields(json, "arrays")? {
+        let Json::Array(values) = values else {
+            return Err(format!("Array {} is not a list", name));
+        };
+        let values = values
+            .iter()
+            .map(value_from_json)
+            .collect::<Result<_, _>>()?;
+        arrays.insert(name.clone(), values);
+    }
+
+    let mut definitions = HashMap::new();
+    collect_definitions(&program.statements, &mut definitions);
+    let mut functions = HashMap::new();
+    for name in items(json, "functions")? {
+        let name = name.as_str().ok_or("A function name is not text")?;
+        let definition = definitions
+            .remove(name)
+            .ok_or_else(|| format!("The program no longer defines {}", name))?;
+        functions.insert(name.to_string(), definition);
+    }
+
+    let mut for_loops = Vec::new();
+    for for_loop in items(json, "for_loops")? {
+        for_loops.push(ForLoop {
+            variable: text(for_loop, "variable")?,
+            end_value: number(for_loop, "end")?,
+            step_value: number(for_loop, "step")?,
+            line_index: index(for_loop, "line_index")?,
+            body_start: index(for_loop, "body_start")?,
+        });
+    }
+
+    let mut type_declarations = HashMap::new();
+    for (range, kind) in fields(json, "type_declarations")? {
+        let kind = kind.as_str().ok_or("A type declaration is not text")?;
+        type_declarations.insert(range.clone(), variable_type_from_name(kind)?);
+    }
+
+    context.variables = variables;
+    context.arrays = arrays;
+    context.functions = functions;
+    context.for_loops = for_loops;
+    context.gosub_stack = items(json, "gosub_stack")?
+        .iter()
+        .map(|line| line.as_usize().ok_or("A GOSUB return line is not a number"))
+        .collect::<Result<_, _>>()?;
+    context.data = items(json, "data")?
+        .iter()
+        .map(value_from_json)
+        .collect::<Result<_, _>>()?;
+    context.data_pointer = index(json, "data_pointer")?;
+    context.random_seed = number(json, "random_seed")? as u64;
+    context.array_base = index(json, "array_base")?;
+    context.input_variable = json
+        .get("input_variable")
+        .and_then(Json::as_str)
+        .map(str::to_string);
+    context.type_declarations = type_declarations;
+    context.turtle = text(json, "turtle")?;
+    context.pens_up = items(json, "pens_up")?
+        .iter()
+        .map(|turtle| turtle.as_str().map(str::to_string))
+        .collect::<Option<HashSet<_>>>()
+        .ok_or("A turtle name is not text")?;
+    context.events.clear();
+    context.event_frames.clear();
+    context.error_handler = None;
+    context.last_error = None;
+    context.in_error_handler = false;
+    context.raised_error = None;
+    Ok(())
+}
+
+pub fn value_to_json(value: &Value) -> Json {
+    let (kind, value) = match value {
+        Value::Integer(number) => ("Integer", Json::Number(*number as f64)),
+        Value::Single(number) => ("Single", Json::Number(*number as f64)),
+        Value::Double(number) => ("Double", Json::Number(*number)),
+        Value::Number(number) => ("Number", Json::Number(*number)),
+        Value::String(text) => ("String", text.as_str().into()),
+    };
+    Json::object([("type", kind.into()), ("value", value)])
+}
+
+pub fn value_from_json(json: &Json) -> Result<Value, String> {
+    let kind = text(json, "type")?;
+    if kind == "String" {
+        return Ok(Value::String(text(json, "value")?));
+    }
+    let value = number(json, "value")?;
+    match kind.as_str() {
+        "Integer" => Ok(Value::Integer(value as i32)),
+        "Single" => Ok(Value::Single(value as f32)),
+        "Double" => Ok(Value::Double(value)),
+        "Number" => Ok(Value::Number(value)),
+        other => Err(format!("Unknown value type {}", other)),
+    }
+}
+
+pub fn turtle_command_to_json(command: &TurtleCommand) -> Json {
+    let number = |value: f32| Json::Number(value as f64);
+    let colour = |r: u8, g: u8, b: u8| {
+        Json::Array(vec![
+            (r as usize).into(),
+            (g as usize).into(),
+            (b as usize).into(),
+        ])
+    };
+    let (kind, arguments) = match &command.command {
+        GraphicsCommand::Forward(distance) => ("Forward", vec![number(*distance)]),
+        GraphicsCommand::Back(distance) => ("Back", vec![number(*distance)]),
+        GraphicsCommand::Move(distance) => ("Move", vec![number(*distance)]),
+        GraphicsCommand::Left(angle) => ("Left", vec![number(*angle)]),
+        GraphicsCommand::Right(angle) => ("Right", vec![number(*angle)]),
+        GraphicsCommand::Turn(angle) => ("Turn", vec![number(*angle)]),
+        GraphicsCommand::Home => ("Home", vec![]),
+        GraphicsCommand::SetXY(x, y) => ("SetXY", vec![number(*x), number(*y)]),
+        GraphicsCommand::MoveTo(x, y) => ("MoveTo", vec![number(*x), number(*y)]),
+        GraphicsCommand::SetHeading(angle) => ("SetHeading", vec![number(*angle)]),
+        GraphicsCommand::ClearScreen => ("ClearScreen", vec![]),
+        GraphicsCommand::SetPenColor(r, g, b) => ("SetPenColor", vec![colour(*r, *g, *b)]),
+        GraphicsCommand::SetPenSize(size) => ("SetPenSize", vec![number(*size)]),
+        GraphicsCommand::Fill => ("Fill", vec![]),
+        GraphicsCommand::Arc(radius, angle) => ("Arc", vec![number(*radius), number(*angle)]),
+        GraphicsCommand::Label(text) => ("Label", vec![text.as_str().into()]),
+        GraphicsCommand::SetLabelSize(size) => ("SetLabelSize", vec![number(*size)]),
+        GraphicsCommand::SetBackground(r, g, b) => ("SetBackground", vec![colour(*r, *g, *b)]),
+        GraphicsCommand::ShowTurtle(visible) => ("ShowTurtle", vec![Json::Bool(*visible)]),
+    };
+    Json::object([
+        ("turtle", command.turtle.as_str().into()),
+        ("command", kind.into()),
+        ("arguments", Json::Array(arguments)),
+    ])
+}
+
+pub fn turtle_command_from_json(json: &Json) -> Result<TurtleCommand, String> {
+    let kind = text(json, "command")?;
+    let arguments = items(json, "arguments")?;
+    let number = |position: usize| -> Result<f32, String> {
+        match arguments.get(position) {
+            Some(Json::Number(value)) => Ok(*value as f32),
+            _ => Err(format!("{} is missing a number", kind)),
+        }
+    };
+    let colour = || -> Result<(u8, u8, u8), String> {
+        match arguments.first() {
+            Some(Json::Array(parts)) if parts.len() == 3 => {
+                let part = |position: usize| parts[position].as_usize().unwrap_or(0) as u8;
+                Ok((part(0), part(1), part(2)))
+            }
+            _ => Err(format!("{} is missing a colour", kind)),
+        }
+    };
+    let command = match kind.as_str() {
+        "Forward" => GraphicsCommand::Forward(number(0)?),
+        "Back" => GraphicsCommand::Back(number(0)?),
+        "Move" => GraphicsCommand::Move(number(0)?),
+        "Left" => GraphicsCommand::Left(number(0)?),
+        "Right" => GraphicsCommand::Right(number(0)?),
+        "Turn" => GraphicsCommand::Turn(number(0)?),
+        "Home" => GraphicsCommand::Home,
+        "SetXY" => GraphicsCommand::SetXY(number(0)?, number(1)?),
+        "MoveTo" => GraphicsCommand::MoveTo(number(0)?, number(1)?),
+        "SetHeading" => GraphicsCommand::SetHeading(number(0)?),
+        "ClearScreen" => GraphicsCommand::ClearScreen,
+        "SetPenColor" => {
+            let (r, g, b) = colour()?;
+            GraphicsCommand::SetPenColor(r, g, b)
+        }
+        "SetPenSize" => GraphicsCommand::SetPenSize(number(0)?),
+        "Fill" => GraphicsCommand::Fill,
+        "Arc" => GraphicsCommand::Arc(number(0)?, number(1)?),
+        "Label" => GraphicsCommand::Label(
+            arguments
+                .first()
+                .and_then(Json::as_str)
+                .ok_or("Label is missing its text")?
+                .to_string(),
+        ),
+        "SetLabelSize" => GraphicsCommand::SetLabelSize(number(0)?),
+        "SetBackground" => {
+            let (r, g, b) = colour()?;
+            GraphicsCommand::SetBackground(r, g, b)
+        }
+        "ShowTurtle" => {
+            GraphicsCommand::ShowTurtle(!matches!(arguments.first(), Some(Json::Bool(false))))
+        }
+        other => return Err(format!("Unknown turtle command {}", other)),
+    };
+    Ok(TurtleCommand {
+        turtle: text(json, "turtle")?,
+        command,
+    })
+}
+
+/// Every DEF FN in the program, nested ones included
+fn collect_definitions(
+    statements: &[Statement],
+    definitions: &mut HashMap<String, FunctionDefinition>,
+) {
+    for statement in statements {
+        match statement {
+            Statement::Def {
+                name,
+                parameters,
+                body,
+            } => {
+                definitions.insert(
+                    name.clone(),
+                    FunctionDefinition {
+                        parameters: parameters.clone(),
+                        body: body.clone(),
+                    },
+                );
+            }
+            Statement::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                collect_definitions(then_branch, definitions);
+                if let Some(else_branch) = else_branch {
+                    collect_definitions(else_branch, definitions);
+                }
+            }
+            Statement::For { body, .. } => collect_definitions(body, definitions),
+            Statement::Select { cases, .. } => {
+                for case in cases {
+                    collect_definitions(&case.statements, definitions);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn variable_type_name(kind: &VariableType) -> &'static str {
+    match kind {
+        VariableType::Integer => "Integer",
+        VariableType::Single => "Single",
+        VariableType::Double => "Double",
+        VariableType::String => "String",
+    }
+}
+
+fn variable_type_from_name(name: &str) -> Result<VariableType, String> {
+    match name {
+        "Integer" => Ok(VariableType::Integer),
+        "Single" => Ok(VariableType::Single),
+        "Double" => Ok(VariableType::Double),
+        "String" => Ok(VariableType::String),
+        other => Err(format!("Unknown variable type {}", other)),
+    }
+}
+
+/// Map entries by key, so saved files don't change from save to save
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+fn fields<'a>(json: &'a Json, key: &str) -> Result<&'a [(String, Json)], String> {
+    match json.get(key) {
+        Some(Json::Object(fields)) => Ok(fields),
+        _ => Err(format!("The saved state has no {}", key)),
+    }
+}
+
+fn items<'a>(json: &'a Json, key: &str) -> Result<&'a [Json], String> {
+    match json.get(key) {
+        Some(Json::Array(items)) => Ok(items),
+        _ => Err(format!("The saved state has no {}", key)),
+    }
+}
+
+fn text(json: &Json, key: &str) -> Result<String, String> {
+    json.get(key)
+        .and_then(Json::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| format!("The saved state has no {}", key))
+}
+
+fn number(json: &Json, key: &str) -> Result<f64, String> {
+    match json.get(key) {
+        Some(Json::Number(value)) => Ok(*value),
+        _ => Err(format!("The saved state has no {}", key)),
+    }
+}
+
+fn index(json: &Json, key: &str) -> Result<usize, String> {
+    json.get(key)
+        .and_then(Json::as_usize)
+        .ok_or_else(|| format!("The saved state has no {}", key))
+}
@@ -0,0 +1,341 @@
+//! Strict mode, for programs that should fail early rather than quietly do
+//! the wrong thing. A variable read anywhere without being given a value
+//! anywhere (usually a typo) stops the program before it runs, and so does
+//! text used as a number or a number stored in a string variable. Other
+//! things that are allowed but rarely meant are reported as warnings.
+//!
+//! The default stays permissive, as BASIC always was: unset variables are
+//! 0 or "", and `"5" * 2` is 10.
+
+use crate::basic::ast::{
+    ExecutionContext, Expression, PrintChannel, Program, ResumeTarget, Statement,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// How much the interpreter lets a program get away with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    #[default]
+    Permissive,
+    Strict,
+}
+
+impl Strictness {
+    pub const ALL: [Strictness; 2] = [Strictness::Permissive, Strictness::Strict];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strictness::Permissive => "Permissive",
+            Strictness::Strict => "Strict",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,   // the program doesn't run
+    Warning, // it runs, but probably not as meant
+}
+
+/// Something strict mode found, on a 1-based source line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Range<usize>>, // bytes of the line it points at, when known
+}
+
+/// Functions written like string variables, which need no value given
+const BUILT_IN_STRINGS: &[&str] = &["DATE$", "TIME$", "INKEY$"];
+
+/// What strict mode finds in `program`, in line order
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut given = HashSet::new();
+    for statement in &program.statements {
+        given_values(statement, &mut given);
+    }
+    let mut checker = Checker {
+        given,
+        reported: HashSet::new(),
+        spellings: HashMap::new(),
+        found: Vec::new(),
+    };
+    for (statement, &line) in program.statements.iter().zip(&program.statement_lines) {
+        checker.statement(statement, line, &[]);
+    }
+    checker.found.sort_by_key(|diagnostic| diagnostic.line);
+    checker.found
+}
+
+/// Names `statement` gives a value to, in upper case
+fn given_values(statement: &Statement, given: &mut HashSet<String>) {
+    match statement {
+        Statement::Let { variable, .. }
+        | Statement::Input { variable, .. }
+        | Statement::For { variable, .. }
+        | Statement::LineInput { variable, .. }
+        | Statement::SetField { variable, .. } => {
+            given.insert(variable.to_uppercase());
+        }
+        Statement::FileInput { variables, .. } => {
+            given.extend(variables.iter().map(|variable| variable.to_uppercase()));
+        }
+        Statement::Field { fields, .. } => {
+            given.extend(fields.iter().map(|(_, variable)| variable.to_uppercase()));
+        }
+        _ => {}
+    }
+    for inner in inner_statements(statement) {
+        given_values(inner, given);
+    }
+}
+
+/// The statements inside a block statement
+pub(crate) fn inner_statements(statement: &Statement) -> Vec<&Statement> {
+    match statement {
+        Statement::If {
+            then_branch,
+            else_branch,
+            ..
+        } => then_branch
+            .iter()
+            .chain(else_branch.iter().flatten())
+            .collect(),
+        Statement::For { body, .. } => body.iter().collect(),
+        Statement::Select { cases, .. } => cases.iter().flat_map(|case| &case.statements).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether `expression` comes out the same on every run
+fn is_constant(expression: &Expression) -> bool {
+    match expression {
+        Expression::Number(_) | Expression::String(_) => true,
+        Expression::BinaryOp { left, right, .. } => is_constant(left) && is_constant(right),
+        Expression::UnaryOp { operand, .. } => is_constant(operand),
+        _ => false,
+    }
+}
+
+struct Checker {
+    given: HashSet<String>,
+    reported: HashSet<String>,               // each unset name once
+    spellings: HashMap<String, Vec<String>>, // base name -> spellings used
+    found: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn report(&mut self, line: usize, severity: Severity, message: String) {
+        self.found.push(Diagnostic {
+            line,
+            severity,
+            message,
+            span: None,
+        });
+    }
+
+    fn statement(&mut self, statement: &Statement, line: usize, parameters: &[String]) {
+        match statement {
+            Statement::Let { variable, .. }
+            | Statement::Input { variable, .. }
+            | Statement::For { variable, .. }
+            | Statement::LineInput { variable, .. }
+            | Statement::SetField { variable, .. } => self.spelling(variable, line),
+            Statement::FileInput { variables, .. } => {
+                for variable in variables {
+                    self.spelling(variable, line);
+                }
+            }
+            _ => {}
+        }
+        match statement {
+            Statement::If { condition, .. } if is_constant(condition) => self.report(
+                line,
+                Severity::Warning,
+                "This IF's condition never changes, so it always goes the same way".to_string(),
+            ),
+            Statement::For {
+                variable,
+                step: Some(Expression::Number(step)),
+                ..
+            } if *step == 0.0 => self.report(
+                line,
+                Severity::Warning,
+                format!("FOR {} with STEP 0 never reaches its end", variable),
+            ),
+            _ => {}
+        }
+        if let Statement::Def {
+            parameters, body, ..
+        } = statement
+        {
+            let parameters: Vec<String> = parameters.iter().map(|p| p.to_uppercase()).collect();
+            return self.expression(body, line, &parameters);
+        }
+        for expression in expressions(statement) {
+            self.expression(expression, line, parameters);
+        }
+        for inner in inner_statements(statement) {
+            self.statement(inner, line, parameters);
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, line: usize, parameters: &[String]) {
+        let mut names = Vec::new();
+        variables_read(expression, &mut names);
+        for name in names {
+            self.read(name, line, parameters);
+        }
+    }
+
+    fn read(&mut self, name: &str, line: usize, parameters: &[String]) {
+        let upper = name.to_uppercase();
+        if parameters.contains(&upper) {
+            return;
+        }
+        self.spelling(name, line);
+        if !self.given.contains(&upper) && self.reported.insert(upper) {
+            self.report(
+                line,
+                Severity::Error,
+                format!("{} is used but never given a value", name),
+            );
+        }
+    }
+
+    /// Warn the first time a variable is written a new way, as A and A$
+    /// are the same variable
+    fn spelling(&mut self, name: &str, line: usize) {
+        let upper = name.to_uppercase();
+        let (base, _) = ExecutionContext::parse_variable_name(&upper);
+        let seen = self.spellings.entry(base).or_default();
+        if seen.contains(&upper) {
+            return;
+        }
+        let message = seen.first().map(|first| {
+            format!(
+                "{} and {} are the same variable; only the last value stored is kept",
+                first, upper
+            )
+        });
+        seen.push(upper);
+        if let Some(message) = message {
+            self.report(line, Severity::Warning, message);
+        }
+    }
+}
+
+/// The variables `expression` reads, as they are written
+pub(crate) fn variables_read<'a>(expression: &'a Expression, names: &mut Vec<&'a str>) {
+    match expression {
+        Expression::Variable(name) => names.push(name),
+        Expression::FunctionCall { name, arguments } => {
+            // NAME$ on its own is a string variable
+            let upper = name.to_uppercase();
+            if arguments.is_empty()
+                && upper.ends_with('$')
+                && !BUILT_IN_STRINGS.contains(&upper.as_str())
+            {
+                names.push(name);
+            }
+            for argument in arguments {
+                variables_read(argument, names);
+            }
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            variables_read(left, names);
+            variables_read(right, names);
+        }
+        Expression::UnaryOp { operand, .. } => variables_read(operand, names),
+        Expression::ArrayAccess { index, .. } => variables_read(index, names),
+        Expression::Number(_) | Expression::String(_) => {}
+    }
+}
+
+/// The expressions a statement evaluates itself, leaving out those of the
+/// statements inside it
+pub(crate) fn expressions(statement: &Statement) -> Vec<&Expression> {
+    match statement {
+        Statement::Let { expression, .. } => vec![expression],
+        Statement::Print {
+            channel,
+            using,
+            expressions,
+            ..
+        } => {
+            let file = match channel {
+                PrintChannel::File(number) => Some(number),
+                _ => None,
+            };
+            file.into_iter().chain(using).chain(expressions).collect()
+        }
+        Statement::If { condition, .. } => vec![condition],
+        Statement::For {
+            start, end, step, ..
+        } => [start, end].into_iter().chain(step).collect(),
+        Statement::Goto { line } | Statement::Gosub { line } => vec![line],
+        Statement::Assert { condition, message } => {
+            std::iter::once(condition).chain(message).collect()
+        }
+        Statement::Randomize { seed } => seed.iter().collect(),
+        Statement::Dim { arrays } => arrays.iter().flat_map(|(_, sizes)| sizes).collect(),
+        Statement::Writeln { expression } | Statement::Printx { expression } => vec![expression],
+        Statement::Select { expression, cases } => std::iter::once(expression)
+            .chain(cases.iter().filter_map(|case| case.value.as_ref()))
+            .collect(),
+        Statement::Forward { distance } | Statement::Back { distance } => vec![distance],
+        Statement::TurnLeft { angle }
+        | Statement::TurnRight { angle }
+        | Statement::SetHeading { angle }
+        | Statement::Turn { angle } => vec![angle],
+        Statement::Setxy { x, y } => vec![x, y],
+        Statement::Turtle { name } => vec![name],
+        Statement::SetPenColor { components } | Statement::SetBackground { components } => {
+            components.iter().collect()
+        }
+        Statement::PenSize { size } | Statement::LabelSize { size } => vec![size],
+        Statement::Arc { radius, angle } => vec![radius, angle],
+        Statement::Circle { radius } => vec![radius],
+        Statement::Label { text } => vec![text],
+        Statement::OnEvent { argument, line, .. } => argument.iter().chain([line]).collect(),
+        Statement::EventControl { argument, .. } => argument.iter().collect(),
+        Statement::OnError { line } => vec![line],
+        Statement::Error { number } => vec![number],
+        Statement::Resume {
+            target: ResumeTarget::Line(line),
+        } => vec![line],
+        Statement::Open {
+            file,
+            number,
+            length,
+            ..
+        } => [file, number].into_iter().chain(length).collect(),
+        Statement::Close { numbers } => numbers.iter().collect(),
+        Statement::Write { file, expressions } => file.iter().chain(expressions).collect(),
+        Statement::FileInput { number, .. } | Statement::LineInput { number, .. } => vec![number],
+        Statement::Field { number, fields } => std::iter::once(number)
+            .chain(fields.iter().map(|(width, _)| width))
+            .collect(),
+        Statement::SetField { expression, .. } => vec![expression],
+        Statement::Get { number, record } | Statement::Put { number, record } => {
+            std::iter::once(number).chain(record).collect()
+        }
+        Statement::Files { pattern } => pattern.iter().collect(),
+        Statement::Kill { pattern } => vec![pattern],
+        Statement::Name { from, to } => vec![from, to],
+        Statement::Folder { dir, .. } => vec![dir],
+        Statement::DefSeg { segment } => segment.iter().collect(),
+        Statement::Poke { address, value } => vec![address, value],
+        Statement::Sleep { seconds } => seconds.iter().collect(),
+        Statement::Wait { ticks } => vec![ticks],
+        Statement::Play { music } => vec![music],
+        Statement::Sound { frequency, ticks } => vec![frequency, ticks],
+        _ => Vec::new(),
+    }
+}
@@ -1,4 +1,35 @@
-use crate::languages::basic::ast::{InterpreterError, Token};
+use crate::basic::ast::{InterpreterError, Position, Token};
+use crate::basic::codes::ErrorCode;
+use crate::basic::dialect::Dialect;
+
+/// A token with the source text it came from and where that starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub lexeme: String,
+    pub line: usize,   // 1-based
+    pub column: usize, // 1-based, in characters
+    pub start: usize,  // byte offsets into the source
+    pub end: usize,
+}
+
+impl SpannedToken {
+    pub fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// The token's variant name, e.g. `Number` for `Number(3.0)`
+    pub fn kind(&self) -> String {
+        let debug = format!("{:?}", self.token);
+        match debug.find('(') {
+            Some(end) => debug[..end].to_string(),
+            None => debug,
+        }
+    }
+}
 
 /// Lexical analyzer for BASIC code
 pub struct Tokenizer {
@@ -6,29 +37,77 @@ pub struct Tokenizer {
     position: usize,
     line: usize,
     column: usize,
+    offset: usize,    // bytes read, for spans
+    dialect: Dialect, // decides which words are keywords
 }
 
 impl Tokenizer {
     pub fn new(input: &str) -> Self {
+        Self::with_dialect(input, Dialect::default())
+    }
+
+    pub fn with_dialect(input: &str, dialect: Dialect) -> Self {
         Self {
             input: input.chars().collect(),
             position: 0,
             line: 1,
             column: 1,
+            offset: 0,
+            dialect,
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, InterpreterError> {
-        let mut tokens = Vec::new();
+    /// Source line (1-based) reached; after an error, the line it is on
+    pub fn line(&self) -> usize {
+        self.line
+    }
 
-        while let Some(token) = self.next_token()? {
-            tokens.push(token);
-        }
+    /// Byte offset reached; after an error, where the mistake is
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-        tokens.push(Token::Eof);
+    /// The tokens of the whole input, each with where it is, ending in `Eof`
+    pub fn tokenize(&mut self) -> Result<Vec<SpannedToken>, InterpreterError> {
+        let (mut tokens, error) = self.tokenize_spanned();
+        if let Some(err) = error {
+            return Err(err);
+        }
+        tokens.push(SpannedToken {
+            token: Token::Eof,
+            lexeme: String::new(),
+            line: self.line,
+            column: self.column,
+            start: self.offset,
+            end: self.offset,
+        });
         Ok(tokens)
     }
 
+    /// Tokens with their positions, for the token inspector. An error ends
+    /// the stream, located at the start of the token it is in; the tokens
+    /// read before it are still returned.
+    pub fn tokenize_spanned(&mut self) -> (Vec<SpannedToken>, Option<InterpreterError>) {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let (start, line, column, offset) =
+                (self.position, self.line, self.column, self.offset);
+            match self.next_token() {
+                Ok(Some(token)) => tokens.push(SpannedToken {
+                    token,
+                    lexeme: self.input[start..self.position].iter().collect(),
+                    line,
+                    column,
+                    start: offset,
+                    end: self.offset,
+                }),
+                Ok(None) => return (tokens, None),
+                Err(err) => return (tokens, Some(err.at(Position { line, column }))),
+            }
+        }
+    }
+
     fn next_token(&mut self) -> Result<Option<Token>, InterpreterError> {
         self.skip_whitespace();
 
@@ -60,6 +139,10 @@ impl Tokenizer {
                 self.advance();
                 Ok(Some(Token::Colon))
             }
+            '#' => {
+                self.advance();
+                Ok(Some(Token::Hash))
+            }
 
             // Operators
             '+' => {
@@ -115,6 +198,7 @@ impl Tokenizer {
 
             // Numbers
             '0'..='9' => self.tokenize_number(),
+            '&' => self.tokenize_radix_number(),
 
             // Strings
             '"' => self.tokenize_string(),
@@ -131,10 +215,10 @@ impl Tokenizer {
             }
 
             // Unexpected character
-            _ => Err(InterpreterError::ParseError(format!(
-                "Unexpected character '{}' at line {}, column {}",
-                ch, self.line, self.column
-            ))),
+            _ => Err(InterpreterError::parse(
+                ErrorCode::UnexpectedCharacter,
+                format!("Unexpected character '{}'", ch),
+            )),
         }
     }
 
@@ -155,10 +239,44 @@ impl Tokenizer {
         let number_str: String = self.input[start..self.position].iter().collect();
         match number_str.parse::<f64>() {
             Ok(num) => Ok(Some(Token::Number(num))),
-            Err(_) => Err(InterpreterError::ParseError(format!(
-                "Invalid number: {}",
-                number_str
-            ))),
+            Err(_) => Err(InterpreterError::parse(
+                ErrorCode::InvalidNumber,
+                format!("Invalid number: {}", number_str),
+            )),
+        }
+    }
+
+    /// `&HB800` is hexadecimal and `&O17` or `&17` octal. They are 16-bit
+    /// integers, so from `&H8000` up they are negative, as in GW-BASIC.
+    fn tokenize_radix_number(&mut self) -> Result<Option<Token>, InterpreterError> {
+        let start = self.position;
+        self.advance(); // consume '&'
+        let radix = match self.peek().map(|c| c.to_ascii_uppercase()) {
+            Some('H') => {
+                self.advance();
+                16
+            }
+            Some('O') => {
+                self.advance();
+                8
+            }
+            _ => 8,
+        };
+        let digits_start = self.position;
+        while self.peek().is_some_and(|c| c.is_digit(radix)) {
+            self.advance();
+        }
+
+        let digits: String = self.input[digits_start..self.position].iter().collect();
+        match u16::from_str_radix(&digits, radix) {
+            Ok(value) => Ok(Some(Token::Number(value as i16 as f64))),
+            Err(_) => Err(InterpreterError::parse(
+                ErrorCode::InvalidNumber,
+                format!(
+                    "Invalid number: {}; use &H0 to &HFFFF",
+                    self.input[start..self.position].iter().collect::<String>()
+                ),
+            )),
         }
     }
 
@@ -171,7 +289,8 @@ impl Tokenizer {
         }
 
         if self.position >= self.input.len() {
-            return Err(InterpreterError::ParseError(
+            return Err(InterpreterError::parse(
+                ErrorCode::UnterminatedString,
                 "Unterminated string literal".to_string(),
             ));
         }
@@ -192,6 +311,10 @@ impl Tokenizer {
         {
             self.advance();
         }
+        // The 8-bit machines have no % operator, so it marks integer variables
+        if self.dialect.screen().is_some() && self.peek() == Some('%') {
+            self.advance();
+        }
 
         let identifier: String = self.input[start..self.position].iter().collect();
         let upper_identifier = identifier.to_uppercase();
@@ -200,12 +323,16 @@ impl Tokenizer {
         let token = match upper_identifier.as_str() {
             "LET" => Token::Let,
             "PRINT" => Token::Print,
+            "LPRINT" => Token::Lprint,
+            "USING" => Token::Using,
             "INPUT" => Token::Input,
             "IF" => Token::If,
             "THEN" => Token::Then,
             "ELSE" => Token::Else,
             "END" => Token::End,
             "STOP" => Token::Stop,
+            "ASSERT" => Token::Assert,
+            "RANDOMIZE" => Token::Randomize,
             "FOR" => Token::For,
             "TO" => Token::To,
             "STEP" => Token::Step,
@@ -218,6 +345,7 @@ impl Tokenizer {
             "DEF" => Token::Def,
             "FN" => Token::Fn,
             "CLEAR" => Token::Clear,
+            "CLS" => Token::Cls,
             "WRITELN" => Token::Writeln,
             "PRINTX" => Token::Printx,
             "DEFINT" => Token::Defint,
@@ -226,6 +354,37 @@ impl Tokenizer {
             "DEFDBL" => Token::Defdbl,
             "SELECT" => Token::Select,
             "CASE" => Token::Case,
+            "ON" => Token::On,
+            "OFF" => Token::Off,
+            "ERROR" => Token::Error,
+            "RESUME" => Token::Resume,
+            "OPEN" => Token::Open,
+            "CLOSE" => Token::Close,
+            "WRITE" => Token::Write,
+            "LINE" => Token::Line,
+            "AS" => Token::As,
+            "OUTPUT" => Token::Output,
+            "APPEND" => Token::Append,
+            "RANDOM" => Token::Random,
+            "FIELD" => Token::Field,
+            "LSET" => Token::Lset,
+            "RSET" => Token::Rset,
+            "GET" => Token::Get,
+            "PUT" => Token::Put,
+            "FILES" => Token::Files,
+            "KILL" => Token::Kill,
+            "NAME" => Token::Name,
+            "MKDIR" => Token::Mkdir,
+            "CHDIR" => Token::Chdir,
+            "RMDIR" => Token::Rmdir,
+            "POKE" => Token::Poke,
+            "SLEEP" => Token::Sleep,
+            "WAIT" => Token::Wait,
+            "KEY" => Token::Key,
+            "PLAY" => Token::Play,
+            "BEEP" => Token::Beep,
+            "SOUND" => Token::Sound,
+            "MOUSE" => Token::Mouse,
             "FORWARD" => Token::Forward,
             "BACK" => Token::Back,
             "LEFT" => Token::TurnLeft,
@@ -234,7 +393,20 @@ impl Tokenizer {
             "PENDOWN" => Token::Pendown,
             "HOME" => Token::Home,
             "SETXY" => Token::Setxy,
+            "SETHEADING" | "SETH" => Token::SetHeading,
+            "CLEARSCREEN" => Token::ClearScreen,
+            "TURTLE" => Token::Turtle,
             "TURN" => Token::Turn,
+            "SETPENCOLOR" | "SETPC" => Token::SetPenColor,
+            "PENSIZE" | "SETPENSIZE" => Token::PenSize,
+            "FILL" => Token::Fill,
+            "ARC" => Token::Arc,
+            "CIRCLE" => Token::Circle,
+            "LABEL" => Token::Label,
+            "SETBACKGROUND" | "SETBG" => Token::SetBackground,
+            "HIDETURTLE" => Token::HideTurtle,
+            "SHOWTURTLE" => Token::ShowTurtle,
+            "LABELSIZE" | "SETLABELHEIGHT" => Token::LabelSize,
             "TAB" => Token::Tab,
             "SPC" => Token::Spc,
             "AND" => Token::And,
@@ -249,8 +421,6 @@ impl Tokenizer {
             "RND" => Token::Rnd,
             "LEN" => Token::Len,
             "MID" => Token::Mid,
-            "LEFT" => Token::Left,
-            "RIGHT" => Token::Right,
             "CHR" => Token::Chr,
             "ASC" => Token::Asc,
             "VAL" => Token::Val,
@@ -259,9 +429,21 @@ impl Tokenizer {
             "TIME" => Token::Time,
             "TIMER" => Token::Timer,
             "ENVIRON" => Token::Environ,
+            "ERR" => Token::Err,
+            "ERL" => Token::Erl,
             _ => Token::Identifier(identifier),
         };
 
+        // The 8-bit machines had no turtle, so those words are free for
+        // variables there; Applesoft's HOME clears the screen
+        let token = match token {
+            Token::Home if self.dialect == Dialect::Applesoft => Token::Cls,
+            token if token.is_turtle_keyword() && self.dialect.screen().is_some() => {
+                Token::Identifier(self.input[start..self.position].iter().collect())
+            }
+            token => token,
+        };
+
         Ok(Some(token))
     }
 
@@ -278,6 +460,7 @@ impl Tokenizer {
 
     fn advance(&mut self) {
         if self.position < self.input.len() {
+            self.offset += self.input[self.position].len_utf8();
             self.position += 1;
             self.column += 1;
         }
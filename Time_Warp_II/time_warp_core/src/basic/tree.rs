@@ -0,0 +1,375 @@
+//! A parsed TW BASIC program as a labelled tree, for showing students how
+//! the parser reads their code: `PRINT 1+2*3` becomes PRINT over `+`, with
+//! `1` and `*` beneath it, and `2` and `3` beneath that.
+
+use crate::basic::ast::{
+    EventKind, Expression, FolderAction, Justify, PrintChannel, Program, ResumeTarget, SelectCase,
+    Statement, TrapState, UnaryOperator,
+};
+use crate::basic::{InterpreterError, Parser, Tokenizer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub label: String,
+    pub children: Vec<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    fn leaf(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    fn branch(label: impl Into<String>, children: Vec<SyntaxNode>) -> Self {
+        Self {
+            label: label.into(),
+            children,
+        }
+    }
+}
+
+/// Tokenize and parse `code`, then build its tree
+pub fn parse_to_tree(code: &str) -> Result<Vec<SyntaxNode>, InterpreterError> {
+    let tokens = Tokenizer::new(code).tokenize()?;
+    let program = Parser::new(tokens).parse_program()?;
+    Ok(program_tree(&program))
+}
+
+/// One node per top-level statement, labelled with its source line
+pub fn program_tree(program: &Program) -> Vec<SyntaxNode> {
+    program
+        .statements
+        .iter()
+        .enumerate()
+        .map(|(index, statement)| {
+            let mut node = statement_tree(statement);
+            if let Some(line) = program.statement_lines.get(index) {
+                node.label = format!("line {}: {}", line, node.label);
+            }
+            node
+        })
+        .collect()
+}
+
+pub fn statement_tree(statement: &Statement) -> SyntaxNode {
+    let expr = expression_tree;
+    let role = |name: &str, expression: &Expression| {
+        let mut node = expression_tree(expression);
+        node.label = format!("{}: {}", name, node.label);
+        node
+    };
+    let block = |label: &str, statements: &[Statement]| {
+        SyntaxNode::branch(label, statements.iter().map(statement_tree).collect())
+    };
+    match statement {
+        Statement::Let {
+            variable,
+            expression,
+        } => SyntaxNode::branch(format!("LET {} =", variable), vec![expr(expression)]),
+        Statement::Print {
+            channel,
+            using,
+            expressions,
+            ..
+        } => {
+            let (keyword, mut children) = match channel {
+                PrintChannel::Screen => ("PRINT", Vec::new()),
+                PrintChannel::Printer => ("LPRINT", Vec::new()),
+                PrintChannel::File(number) => ("PRINT", vec![role("#", number)]),
+            };
+            children.extend(using.iter().map(|format| role("USING", format)));
+            children.extend(expressions.iter().map(expr));
+            SyntaxNode::branch(keyword, children)
+        }
+        Statement::Input { prompt, variable } => SyntaxNode::leaf(match prompt {
+            Some(prompt) => format!("INPUT \"{}\"; {}", prompt, variable),
+            None => format!("INPUT {}", variable),
+        }),
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let mut children = vec![role("condition", condition), block("THEN", then_branch)];
+            if let Some(else_branch) = else_branch {
+                children.push(block("ELSE", else_branch));
+            }
+            SyntaxNode::branch("IF", children)
+        }
+        Statement::For {
+            variable,
+            start,
+            end,
+            step,
+            body,
+        } => {
+            let mut children = vec![role("from", start), role("to", end)];
+            if let Some(step) = step {
+                children.push(role("step", step));
+            }
+            if !body.is_empty() {
+                children.push(block("body", body));
+            }
+            SyntaxNode::branch(format!("FOR {}", variable), children)
+        }
+        Statement::Next { variable } => match variable {
+            Some(variable) => SyntaxNode::leaf(format!("NEXT {}", variable)),
+            None => SyntaxNode::leaf("NEXT"),
+        },
+        Statement::Goto { line } => SyntaxNode::branch("GOTO", vec![expr(line)]),
+        Statement::Gosub { line } => SyntaxNode::branch("GOSUB", vec![expr(line)]),
+        Statement::Return => SyntaxNode::leaf("RETURN"),
+        Statement::End => SyntaxNode::leaf("END"),
+        Statement::Stop => SyntaxNode::leaf("STOP"),
+        Statement::Assert { condition, message } => {
+            let mut children = vec![role("condition", condition)];
+            if let Some(message) = message {
+                children.push(role("message", message));
+            }
+            SyntaxNode::branch("ASSERT", children)
+        }
+        Statement::Randomize { seed } => {
+            SyntaxNode::branch("RANDOMIZE", seed.iter().map(expr).collect())
+        }
+        Statement::Rem(text) => SyntaxNode::leaf(format!("REM {}", text)),
+        Statement::Dim { arrays } => SyntaxNode::branch(
+            "DIM",
+            arrays
+                .iter()
+                .map(|(name, dimensions)| {
+                    SyntaxNode::branch(name.clone(), dimensions.iter().map(expr).collect())
+                })
+                .collect(),
+        ),
+        Statement::Def {
+            name,
+            parameters,
+            body,
+        } => SyntaxNode::branch(
+            format!("DEF {}({}) =", name, parameters.join(", ")),
+            vec![expr(body)],
+        ),
+        Statement::Clear => SyntaxNode::leaf("CLEAR"),
+        Statement::Cls => SyntaxNode::leaf("CLS"),
+        Statement::Writeln { expression } => SyntaxNode::branch("WRITELN", vec![expr(expression)]),
+        Statement::Printx { expression } => SyntaxNode::branch("PRINTX", vec![expr(expression)]),
+        Statement::Select { expression, cases } => {
+            let mut children = vec![expr(expression)];
+            children.extend(cases.iter().map(case_tree));
+            SyntaxNode::branch("SELECT CASE", children)
+        }
+        Statement::Forward { distance } => SyntaxNode::branch("FORWARD", vec![expr(distance)]),
+        Statement::Back { distance } => SyntaxNode::branch("BACK", vec![expr(distance)]),
+        Statement::TurnLeft { angle } => SyntaxNode::branch("LEFT", vec![expr(angle)]),
+        Statement::TurnRight { angle } => SyntaxNode::branch("RIGHT", vec![expr(angle)]),
+        Statement::Penup => SyntaxNode::leaf("PENUP"),
+        Statement::Pendown => SyntaxNode::leaf("PENDOWN"),
+        Statement::Home => SyntaxNode::leaf("HOME"),
+        Statement::Setxy { x, y } => SyntaxNode::branch("SETXY", vec![role("x", x), role("y", y)]),
+        Statement::SetHeading { angle } => SyntaxNode::branch("SETHEADING", vec![expr(angle)]),
+        Statement::ClearScreen => SyntaxNode::leaf("CLEARSCREEN"),
+        Statement::Turtle { name } => SyntaxNode::branch("TURTLE", vec![expr(name)]),
+        Statement::Turn { angle } => SyntaxNode::branch("TURN", vec![expr(angle)]),
+        Statement::SetPenColor { components } => {
+            SyntaxNode::branch("SETPENCOLOR", components.iter().map(expr).collect())
+        }
+        Statement::PenSize { size } => SyntaxNode::branch("PENSIZE", vec![expr(size)]),
+        Statement::SetBackground { components } => {
+            SyntaxNode::branch("SETBACKGROUND", components.iter().map(expr).collect())
+        }
+        Statement::Fill => SyntaxNode::leaf("FILL"),
+        Statement::HideTurtle => SyntaxNode::leaf("HIDETURTLE"),
+        Statement::ShowTurtle => SyntaxNode::leaf("SHOWTURTLE"),
+        Statement::Arc { radius, angle } => {
+            SyntaxNode::branch("ARC", vec![role("radius", radius), role("angle", angle)])
+        }
+        Statement::Circle { radius } => SyntaxNode::branch("CIRCLE", vec![expr(radius)]),
+        Statement::Label { text } => SyntaxNode::branch("LABEL", vec![expr(text)]),
+        Statement::LabelSize { size } => SyntaxNode::branch("LABELSIZE", vec![expr(size)]),
+        Statement::DefInt { ranges } => SyntaxNode::leaf(format!("DEFINT {}", ranges.join(", "))),
+        Statement::DefSng { ranges } => SyntaxNode::leaf(format!("DEFSNG {}", ranges.join(", "))),
+        Statement::DefDbl { ranges } => SyntaxNode::leaf(format!("DEFDBL {}", ranges.join(", "))),
+        Statement::DefStr { ranges } => SyntaxNode::leaf(format!("DEFSTR {}", ranges.join(", "))),
+        Statement::OnEvent {
+            event,
+            argument,
+            line,
+        } => {
+            let mut children: Vec<SyntaxNode> = argument.iter().map(expr).collect();
+            children.push(role("GOSUB", line));
+            SyntaxNode::branch(format!("ON {}", event_name(*event)), children)
+        }
+        Statement::EventControl {
+            event,
+            argument,
+            state,
+        } => {
+            let state = match state {
+                TrapState::On => "ON",
+                TrapState::Off => "OFF",
+                TrapState::Stopped => "STOP",
+            };
+            SyntaxNode::branch(
+                format!("{} {}", event_name(*event), state),
+                argument.iter().map(expr).collect(),
+            )
+        }
+        Statement::OnError { line } => SyntaxNode::branch("ON ERROR", vec![role("GOTO", line)]),
+        Statement::Error { number } => SyntaxNode::branch("ERROR", vec![expr(number)]),
+        Statement::Resume { target } => match target {
+            ResumeTarget::Retry => SyntaxNode::leaf("RESUME"),
+            ResumeTarget::Next => SyntaxNode::leaf("RESUME NEXT"),
+            ResumeTarget::Line(line) => SyntaxNode::branch("RESUME", vec![expr(line)]),
+        },
+        Statement::Open {
+            file,
+            mode,
+            number,
+            length,
+        } => {
+            let mut children = vec![expr(file), role("AS #", number)];
+            children.extend(length.iter().map(|length| role("LEN", length)));
+            SyntaxNode::branch(
+                format!("OPEN FOR {}", format!("{:?}", mode).to_uppercase()),
+                children,
+            )
+        }
+        Statement::Close { numbers } => {
+            SyntaxNode::branch("CLOSE", numbers.iter().map(|n| role("#", n)).collect())
+        }
+        Statement::Write { file, expressions } => {
+            let mut children: Vec<SyntaxNode> = file.iter().map(|n| role("#", n)).collect();
+            children.extend(expressions.iter().map(expr));
+            SyntaxNode::branch("WRITE", children)
+        }
+        Statement::FileInput { number, variables } => SyntaxNode::branch(
+            format!("INPUT {}", variables.join(", ")),
+            vec![role("#", number)],
+        ),
+        Statement::LineInput { number, variable } => {
+            SyntaxNode::branch(format!("LINE INPUT {}", variable), vec![role("#", number)])
+        }
+        Statement::Field { number, fields } => {
+            let mut children = vec![role("#", number)];
+            children.extend(
+                fields
+                    .iter()
+                    .map(|(width, variable)| role(&format!("AS {}", variable), width)),
+            );
+            SyntaxNode::branch("FIELD", children)
+        }
+        Statement::SetField {
+            justify,
+            variable,
+            expression,
+        } => {
+            let keyword = match justify {
+                Justify::Left => "LSET",
+                Justify::Right => "RSET",
+            };
+            SyntaxNode::branch(
+                format!("{} {} =", keyword, variable),
+                vec![expr(expression)],
+            )
+        }
+        Statement::Get { number, record } | Statement::Put { number, record } => {
+            let keyword = if matches!(statement, Statement::Get { .. }) {
+                "GET"
+            } else {
+                "PUT"
+            };
+            let mut children = vec![role("#", number)];
+            children.extend(record.iter().map(|record| role("record", record)));
+            SyntaxNode::branch(keyword, children)
+        }
+        Statement::Files { pattern } => {
+            SyntaxNode::branch("FILES", pattern.iter().map(expr).collect())
+        }
+        Statement::Kill { pattern } => SyntaxNode::branch("KILL", vec![expr(pattern)]),
+        Statement::Name { from, to } => {
+            SyntaxNode::branch("NAME", vec![expr(from), role("AS", to)])
+        }
+        Statement::DefSeg { segment } => {
+            SyntaxNode::branch("DEF SEG", segment.iter().map(expr).collect())
+        }
+        Statement::Poke { address, value } => {
+            SyntaxNode::branch("POKE", vec![role("address", address), role("value", value)])
+        }
+        Statement::Sleep { seconds } => {
+            SyntaxNode::branch("SLEEP", seconds.iter().map(expr).collect())
+        }
+        Statement::Wait { ticks } => SyntaxNode::branch("WAIT", vec![expr(ticks)]),
+        Statement::Play { music } => SyntaxNode::branch("PLAY", vec![expr(music)]),
+        Statement::Beep => SyntaxNode::leaf("BEEP"),
+        Statement::Sound { frequency, ticks } => SyntaxNode::branch(
+            "SOUND",
+            vec![role("frequency", frequency), role("ticks", ticks)],
+        ),
+        Statement::Folder { action, dir } => {
+            let keyword = match action {
+                FolderAction::Make => "MKDIR",
+                FolderAction::Change => "CHDIR",
+                FolderAction::Remove => "RMDIR",
+            };
+            SyntaxNode::branch(keyword, vec![expr(dir)])
+        }
+    }
+}
+
+/// Operations are labelled with their operator and the part of the
+/// program they cover, e.g. `* — 2 * 3`; values are labelled as written.
+pub fn expression_tree(expression: &Expression) -> SyntaxNode {
+    match expression {
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) => {
+            SyntaxNode::leaf(expression.to_string())
+        }
+        Expression::BinaryOp {
+            left,
+            operator,
+            right,
+        } => SyntaxNode::branch(
+            format!("{} — {}", operator, expression),
+            vec![expression_tree(left), expression_tree(right)],
+        ),
+        Expression::UnaryOp { operator, operand } => {
+            let symbol = match operator {
+                UnaryOperator::Negate => "negate",
+                UnaryOperator::Not => "NOT",
+            };
+            SyntaxNode::branch(
+                format!("{} — {}", symbol, expression),
+                vec![expression_tree(operand)],
+            )
+        }
+        Expression::FunctionCall { name, arguments } => SyntaxNode::branch(
+            format!("{}() — {}", name, expression),
+            arguments.iter().map(expression_tree).collect(),
+        ),
+        Expression::ArrayAccess { name, index } => SyntaxNode::branch(
+            format!("{}() — {}", name, expression),
+            vec![expression_tree(index)],
+        ),
+    }
+}
+
+fn case_tree(case: &SelectCase) -> SyntaxNode {
+    let mut children: Vec<SyntaxNode> = case.value.iter().map(expression_tree).collect();
+    children.extend(case.statements.iter().map(statement_tree));
+    let label = if case.value.is_some() {
+        "CASE"
+    } else {
+        "CASE ELSE"
+    };
+    SyntaxNode::branch(label, children)
+}
+
+fn event_name(event: EventKind) -> &'static str {
+    match event {
+        EventKind::Key => "KEY",
+        EventKind::Timer => "TIMER",
+        EventKind::Play => "PLAY",
+        EventKind::Mouse => "MOUSE",
+    }
+}
@@ -0,0 +1,349 @@
+//! PRINT USING and LPRINT USING: values laid out by a GW-BASIC format
+//! string such as `"###.##"` or `"\  \"`.
+//!
+//! Number fields: `#` is a digit, `.` the decimal point, `,` before the
+//! point groups thousands, `+` in front or at the end shows the sign, `-`
+//! at the end shows a minus, `**` fills with asterisks, `$$` puts a dollar
+//! sign before the digits and `^^^^` writes an exponent. A number too big
+//! for its field is written in full after a `%`.
+//!
+//! String fields: `!` is the first character, `\  \` as many characters as
+//! the field is wide and `&` the whole string. `_` makes the next character
+//! plain text; any other character is printed as it is. When the values
+//! outnumber the fields the format starts over.
+
+use crate::basic::ast::{InterpreterError, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    First,        // !
+    Fixed(usize), // \  \
+    Whole,        // &
+    Number(NumberField),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NumberField {
+    width: usize,            // places before the decimal point, fill and dollar included
+    decimals: Option<usize>, // digits after the point, if it has one
+    thousands: bool,
+    asterisks: bool,
+    dollar: bool,
+    leading_sign: bool,
+    trailing_sign: Option<char>, // '+' or '-'
+    exponent: Option<usize>,     // digits of the exponent
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// `values` written in the fields of `format`, with its text between them
+pub fn format(format: &str, values: &[Value]) -> Result<String, InterpreterError> {
+    let parts = parse(format);
+    if !parts.iter().any(|part| matches!(part, Part::Field(_))) {
+        return Err(InterpreterError::RuntimeError(format!(
+            "USING \"{}\" has no field to put a value in",
+            format
+        )));
+    }
+
+    let mut output = String::new();
+    let mut index = 0;
+    for value in values {
+        loop {
+            if index == parts.len() {
+                index = 0;
+            }
+            index += 1;
+            match &parts[index - 1] {
+                Part::Text(text) => output.push_str(text),
+                Part::Field(field) => {
+                    output.push_str(&format_value(field, value)?);
+                    break;
+                }
+            }
+        }
+    }
+    // Text after the last value, up to the next field or the end
+    while let Some(Part::Text(text)) = parts.get(index) {
+        output.push_str(text);
+        index += 1;
+    }
+    Ok(output)
+}
+
+fn format_value(field: &Field, value: &Value) -> Result<String, InterpreterError> {
+    match (field, value) {
+        (Field::Number(_), Value::String(_)) => Err(mismatch()),
+        (Field::Number(field), value) => Ok(format_number(field, number(value))),
+        (Field::First, Value::String(text)) => Ok(fit(text, 1)),
+        (Field::Fixed(width), Value::String(text)) => Ok(fit(text, *width)),
+        (Field::Whole, Value::String(text)) => Ok(text.clone()),
+        _ => Err(mismatch()),
+    }
+}
+
+fn mismatch() -> InterpreterError {
+    InterpreterError::TypeError(
+        "USING needs text for a text field and a number for a number field".to_string(),
+    )
+}
+
+fn number(value: &Value) -> f64 {
+    match value {
+        Value::Integer(number) => *number as f64,
+        Value::Single(number) => *number as f64,
+        Value::Double(number) | Value::Number(number) => *number,
+        Value::String(_) => 0.0,
+    }
+}
+
+/// The first `width` characters of `text`, padded with spaces to `width`
+fn fit(text: &str, width: usize) -> String {
+    let fitted: String = text.chars().take(width).collect();
+    format!("{:<1$}", fitted, width)
+}
+
+fn format_number(field: &NumberField, value: f64) -> String {
+    if let Some(exponent_digits) = field.exponent {
+        return format_exponent(field, value, exponent_digits);
+    }
+
+    let decimals = field.decimals.unwrap_or(0);
+    let negative = value < 0.0 && format!("{:.*}", decimals, value.abs()) != zero(decimals);
+    let fixed = format!("{:.*}", decimals, value.abs());
+    let (whole, fraction) = match fixed.split_once('.') {
+        Some((whole, fraction)) => (whole.to_string(), Some(fraction.to_string())),
+        None => (fixed, None),
+    };
+    let whole = if field.thousands {
+        group_thousands(&whole)
+    } else {
+        whole
+    };
+
+    let sign_in_front = if field.leading_sign {
+        Some(if negative { '-' } else { '+' })
+    } else if negative && field.trailing_sign.is_none() {
+        Some('-')
+    } else {
+        None
+    };
+    let mut front = String::new();
+    front.extend(sign_in_front);
+    if field.dollar {
+        front.push('$');
+    }
+    let mut body = format!("{}{}", front, whole);
+    // A zero before the point is left out when there is no room for it
+    if body.len() > field.width && whole == "0" && field.decimals.is_some() {
+        body = front;
+    }
+
+    let mut text = if body.len() > field.width {
+        format!("%{}", body)
+    } else {
+        let fill = if field.asterisks { '*' } else { ' ' };
+        let padding = fill.to_string().repeat(field.width - body.len());
+        format!("{}{}", padding, body)
+    };
+    if let Some(fraction) = fraction {
+        text.push('.');
+        text.push_str(&fraction);
+    } else if field.decimals == Some(0) {
+        text.push('.');
+    }
+    match field.trailing_sign {
+        Some('+') => text.push(if negative { '-' } else { '+' }),
+        Some(_) => text.push(if negative { '-' } else { ' ' }),
+        None => {}
+    }
+    text
+}
+
+fn zero(decimals: usize) -> String {
+    format!("{:.*}", decimals, 0.0)
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `##.##^^^^`: the digits before the point are filled, one place being
+/// kept for the sign unless the field shows it with `+` or `-`
+fn format_exponent(field: &NumberField, value: f64, exponent_digits: usize) -> String {
+    let decimals = field.decimals.unwrap_or(0);
+    let signed = field.leading_sign || field.trailing_sign.is_some();
+    // A leading + is one of the places; without a sign shown, one is kept for it
+    let digits = field
+        .width
+        .saturating_sub(usize::from(field.trailing_sign.is_none()))
+        .max(1);
+
+    let magnitude = value.abs();
+    let mut exponent = if magnitude == 0.0 {
+        0
+    } else {
+        magnitude.log10().floor() as i32 - (digits as i32 - 1)
+    };
+    let mut mantissa = magnitude / 10f64.powi(exponent);
+    // Rounding 9.996 to two places makes 10.00: one digit too many
+    if format!("{:.*}", decimals, mantissa).len() > digits + decimals + usize::from(decimals > 0) {
+        exponent += 1;
+        mantissa = magnitude / 10f64.powi(exponent);
+    }
+
+    let negative = value < 0.0;
+    let mut text = String::new();
+    if field.leading_sign {
+        text.push(if negative { '-' } else { '+' });
+    } else if !signed {
+        text.push(if negative { '-' } else { ' ' });
+    }
+    text.push_str(&format!("{:.*}", decimals, mantissa));
+    if field.decimals == Some(0) {
+        text.push('.');
+    }
+    text.push_str(&format!(
+        "E{}{:0width$}",
+        if exponent < 0 { '-' } else { '+' },
+        exponent.unsigned_abs(),
+        width = exponent_digits
+    ));
+    match field.trailing_sign {
+        Some('+') => text.push(if negative { '-' } else { '+' }),
+        Some(_) => text.push(if negative { '-' } else { ' ' }),
+        None => {}
+    }
+    text
+}
+
+fn parse(format: &str) -> Vec<Part> {
+    let chars: Vec<char> = format.chars().collect();
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut position = 0;
+
+    while position < chars.len() {
+        let field = match chars[position] {
+            '_' => {
+                if let Some(&next) = chars.get(position + 1) {
+                    text.push(next);
+                }
+                position += 2;
+                continue;
+            }
+            '!' => Some((Field::First, 1)),
+            '&' => Some((Field::Whole, 1)),
+            '\\' => chars[position + 1..]
+                .iter()
+                .position(|&c| c != ' ')
+                .filter(|&offset| chars[position + 1 + offset] == '\\')
+                .map(|spaces| (Field::Fixed(spaces + 2), spaces + 2)),
+            _ => number_field(&chars[position..]).map(|(field, used)| (Field::Number(field), used)),
+        };
+        match field {
+            Some((field, used)) => {
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Field(field));
+                position += used;
+            }
+            None => {
+                text.push(chars[position]);
+                position += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    parts
+}
+
+/// The number field `chars` starts with, and how many characters it takes
+fn number_field(chars: &[char]) -> Option<(NumberField, usize)> {
+    let mut field = NumberField::default();
+    let mut position = 0;
+
+    if chars.first() == Some(&'+') {
+        field.leading_sign = true;
+        position += 1;
+    }
+    if starts_at(chars, position, "**$") {
+        field.asterisks = true;
+        field.dollar = true;
+        field.width += 3;
+        position += 3;
+    } else if starts_at(chars, position, "**") {
+        field.asterisks = true;
+        field.width += 2;
+        position += 2;
+    } else if starts_at(chars, position, "$$") {
+        field.dollar = true;
+        field.width += 2;
+        position += 2;
+    }
+
+    let mut digits = 0;
+    while let Some(&c) = chars.get(position) {
+        match c {
+            '#' => digits += 1,
+            ',' if digits > 0 || field.width > 0 => field.thousands = true,
+            _ => break,
+        }
+        field.width += 1;
+        position += 1;
+    }
+    if chars.get(position) == Some(&'.')
+        && (chars.get(position + 1) == Some(&'#') || digits > 0 || field.width > 0)
+    {
+        position += 1;
+        let mut decimals = 0;
+        while chars.get(position) == Some(&'#') {
+            decimals += 1;
+            position += 1;
+        }
+        field.decimals = Some(decimals);
+    }
+    if digits == 0 && field.width == 0 && field.decimals.unwrap_or(0) == 0 {
+        return None;
+    }
+    // A leading + counts as one of the places
+    if field.leading_sign {
+        field.width += 1;
+    }
+
+    if starts_at(chars, position, "^^^^^") {
+        field.exponent = Some(3);
+        position += 5;
+    } else if starts_at(chars, position, "^^^^") {
+        field.exponent = Some(2);
+        position += 4;
+    }
+    if !field.leading_sign {
+        if let Some(&sign @ ('+' | '-')) = chars.get(position) {
+            field.trailing_sign = Some(sign);
+            position += 1;
+        }
+    }
+    Some((field, position))
+}
+
+fn starts_at(chars: &[char], position: usize, prefix: &str) -> bool {
+    prefix
+        .chars()
+        .enumerate()
+        .all(|(index, c)| chars.get(position + index) == Some(&c))
+}
@@ -0,0 +1,265 @@
+//! Check a folder of student programs against a manifest of test cases.
+//!
+//! A manifest lists cases, each with the answers to type and the lines the
+//! output must show, in order:
+//!
+//! ```text
+//! # Lines starting with # are comments
+//! [adds two numbers]
+//! < 2
+//! < 3
+//! > 5
+//! ```
+//!
+//! Every program in the folder runs once per case. Output lines are compared
+//! with surrounding spaces ignored, and other lines (prompts, greetings) may
+//! come in between, so students can word their prompts freely.
+
+use std::path::Path;
+
+use crate::basic::ErrorCode;
+use crate::json::Json;
+use crate::{ExecutionResult, Language};
+
+/// One test case: what to type and what must appear
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Case {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub expected: Vec<String>,
+}
+
+/// How one program did on one case
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub case: String,
+    pub passed: bool,
+    pub detail: String,          // why it failed; empty when it passed
+    pub code: Option<ErrorCode>, // of the error the program stopped with
+}
+
+/// Every case for one program
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramReport {
+    pub program: String,
+    pub cases: Vec<CaseResult>,
+}
+
+impl ProgramReport {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(|case| case.passed)
+    }
+}
+
+/// What a headless run printed, and the error that ended it, if any
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transcript {
+    pub output: String,
+    pub error: Option<String>,
+    pub code: Option<ErrorCode>, // when the error is the interpreter's
+}
+
+/// Read a manifest. Text before the first `[case]` heading is an error, so a
+/// missing heading isn't silently ignored.
+pub fn parse_manifest(text: &str) -> Result<Vec<Case>, String> {
+    let mut cases: Vec<Case> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = trimmed
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            cases.push(Case {
+                name: name.trim().to_string(),
+                ..Case::default()
+            });
+            continue;
+        }
+        let Some(case) = cases.last_mut() else {
+            return Err(format!(
+                "Line {}: expected a [case name] before '{}'",
+                number + 1,
+                trimmed
+            ));
+        };
+        if let Some(input) = trimmed.strip_prefix('<') {
+            case.inputs.push(input.trim().to_string());
+        } else if let Some(expected) = trimmed.strip_prefix('>') {
+            case.expected.push(expected.trim().to_string());
+        } else {
+            return Err(format!(
+                "Line {}: start it with < for an answer or > for an expected line",
+                number + 1
+            ));
+        }
+    }
+    if cases.is_empty() {
+        return Err("the manifest has no [case] sections".to_string());
+    }
+    Ok(cases)
+}
+
+/// Run a program, answering each request for input with the next of
+/// `inputs`. Answers are echoed into the output, as in the IDE.
+pub fn run_with_inputs(
+    language: Language,
+    code: &str,
+    inputs: &[String],
+    max_instructions: usize,
+) -> Transcript {
+    let mut backend = language.backend();
+    let mut transcript = Transcript::default();
+    backend.queue_input(inputs.to_vec());
+    match backend.execute(code, max_instructions) {
+        Ok(ExecutionResult::NeedInput {
+            partial_output,
+            prompt,
+            ..
+        }) => {
+            transcript.output.push_str(&partial_output);
+            transcript.output.push_str(&prompt);
+            transcript.error = Some("the program asked for more input".to_string());
+        }
+        Ok(ExecutionResult::NeedPermission {
+            permission,
+            partial_output,
+            ..
+        }) => {
+            transcript.output.push_str(&partial_output);
+            transcript.error = Some(format!("the program asked to {}", permission.name()));
+        }
+        Ok(ExecutionResult::Complete { output, .. })
+        | Ok(ExecutionResult::Break {
+            partial_output: output,
+            ..
+        })
+        // Only a live keyboard hands control back, and batch runs have none
+        | Ok(ExecutionResult::Polling {
+            partial_output: output,
+            ..
+        }) => transcript.output.push_str(&output),
+        Ok(ExecutionResult::Error(message)) => transcript.error = Some(message),
+        Err(err) => {
+            transcript.output.push_str(&backend.take_output());
+            transcript.error = Some(err.to_string());
+            transcript.code = Some(err.code());
+        }
+    }
+    transcript
+}
+
+/// Whether the transcript passes the case, and why not
+pub fn check_case(case: &Case, transcript: &Transcript) -> CaseResult {
+    let mut lines = transcript.output.lines().map(str::trim);
+    let missing = case
+        .expected
+        .iter()
+        .find(|expected| !lines.any(|line| line == expected.as_str()));
+    let detail = match (&transcript.error, missing) {
+        (Some(error), _) => format!("stopped with an error: {}", error),
+        (None, Some(expected)) => format!("expected a line '{}' that never came", expected),
+        (None, None) => String::new(),
+    };
+    CaseResult {
+        case: case.name.clone(),
+        passed: detail.is_empty(),
+        detail,
+        code: transcript.code.filter(|_| transcript.error.is_some()),
+    }
+}
+
+/// Run every program in `dir` whose extension names a language against
+/// every case, in file name order
+pub fn check_directory(
+    dir: &Path,
+    cases: &[Case],
+    max_instructions: usize,
+) -> std::io::Result<Vec<ProgramReport>> {
+    let mut programs: Vec<(String, Language)> = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let language = path
+            .extension()
+            .and_then(|extension| Language::from_extension(&extension.to_string_lossy()));
+        if let (Some(language), Some(name)) = (language, path.file_name()) {
+            programs.push((name.to_string_lossy().to_string(), language));
+        }
+    }
+    programs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut reports = Vec::new();
+    for (program, language) in programs {
+        let code = std::fs::read_to_string(dir.join(&program))?;
+        let cases = cases
+            .iter()
+            .map(|case| {
+                let transcript = run_with_inputs(language, &code, &case.inputs, max_instructions);
+                check_case(case, &transcript)
+            })
+            .collect();
+        reports.push(ProgramReport { program, cases });
+    }
+    Ok(reports)
+}
+
+/// A report for reading: one line per program, failures indented below it
+pub fn text_report(reports: &[ProgramReport]) -> String {
+    let mut text = String::new();
+    for report in reports {
+        let passed = report.cases.iter().filter(|case| case.passed).count();
+        text.push_str(&format!(
+            "{} {} ({}/{} cases)\n",
+            if report.passed() { "PASS" } else { "FAIL" },
+            report.program,
+            passed,
+            report.cases.len()
+        ));
+        for case in report.cases.iter().filter(|case| !case.passed) {
+            text.push_str(&format!("    {}: {}\n", case.case, case.detail));
+        }
+    }
+    let passed = reports.iter().filter(|report| report.passed()).count();
+    text.push_str(&format!(
+        "{} of {} programs passed\n",
+        passed,
+        reports.len()
+    ));
+    text
+}
+
+/// The same report as JSON, for gradebooks and other scripts
+pub fn json_report(reports: &[ProgramReport]) -> String {
+    let programs = reports
+        .iter()
+        .map(|report| {
+            let cases = report
+                .cases
+                .iter()
+                .map(|case| {
+                    Json::object([
+                        ("case", Json::String(case.case.clone())),
+                        ("passed", Json::Bool(case.passed)),
+                        ("detail", Json::String(case.detail.clone())),
+                        (
+                            "code",
+                            case.code
+                                .map_or(Json::Null, |code| Json::String(code.code().to_string())),
+                        ),
+                    ])
+                })
+                .collect();
+            Json::object([
+                ("program", Json::String(report.program.clone())),
+                ("passed", Json::Bool(report.passed())),
+                ("cases", Json::Array(cases)),
+            ])
+        })
+        .collect();
+    format!(
+        "{}\n",
+        Json::object([("programs", Json::Array(programs))]).pretty()
+    )
+}
@@ -0,0 +1,297 @@
+//! Run programs without the IDE, for grading scripts and terminals:
+//!
+//!     time_warp run program.twb [--printer FILE] [--files-dir DIR] [--max-instructions N]
+//!     time_warp check submissions/ [--manifest FILE] [--json] [--max-instructions N]
+//!
+//! `run` sends output to stdout and reads answers to INPUT (or A:, readln)
+//! from stdin, one per line. The exit code is 0 when the program finishes, 1
+//! on an interpreter error and 2 when the program can't be loaded. An
+//! interpreter error is written to stderr starting with its stable code, as
+//! in `E201 Division by zero (line 3, column 4)`, so scripts can tell errors
+//! apart. What the program LPRINTs is added to the end of the `--printer`
+//! file; without one it goes nowhere, as with no printer attached. Files the
+//! program OPENs, KILLs or renames are kept in a folder of its own beside it,
+//! `<name>_files` for `<name>.twb`, or in `--files-dir`, so a program can't
+//! touch the other programs and files next to it.
+//!
+//! `check` runs every program in a folder against the cases in its manifest
+//! (`manifest.txt` unless given) and prints a report. The exit code is 0 when
+//! every program passes, 1 when any fails and 2 when nothing can be checked.
+
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use time_warp_core::basic::include;
+use time_warp_core::{batch, ExecutionResult, Language, LanguageBackend};
+
+/// The IDE's default: 5 seconds at roughly 1000 statements per millisecond
+const DEFAULT_MAX_INSTRUCTIONS: usize = 5_000_000;
+
+const USAGE: &str =
+    "usage: time_warp run <program> [--printer FILE] [--files-dir DIR] [--max-instructions N]
+       time_warp check <folder> [--manifest FILE] [--json] [--max-instructions N]";
+
+enum Command {
+    Run {
+        path: String,
+        printer: Option<String>,
+        files_dir: Option<String>,
+    },
+    Check {
+        dir: String,
+        manifest: Option<String>,
+        json: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, max_instructions) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match command {
+        Command::Run {
+            path,
+            printer,
+            files_dir,
+        } => run_file(&path, printer, files_dir, max_instructions),
+        Command::Check {
+            dir,
+            manifest,
+            json,
+        } => check(&dir, manifest, json, max_instructions),
+    }
+}
+
+fn run_file(
+    path: &str,
+    printer: Option<String>,
+    files_dir: Option<String>,
+    max_instructions: usize,
+) -> ExitCode {
+    let Some(language) = Path::new(&path)
+        .extension()
+        .and_then(|extension| Language::from_extension(&extension.to_string_lossy()))
+    else {
+        eprintln!("{}: can't tell the language from the file extension", path);
+        return ExitCode::from(2);
+    };
+    let code = match std::fs::read_to_string(path) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::from(2);
+        }
+    };
+    // A BASIC program's INCLUDE lines are filled in from the files beside it
+    let code = match language {
+        Language::Basic => {
+            match include::expand(&code, Some(Path::new(path)), &mut |path| {
+                std::fs::read_to_string(path)
+            }) {
+                Ok(expanded) => expanded.code,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return ExitCode::from(2);
+                }
+            }
+        }
+        _ => code,
+    };
+
+    let mut backend = language.backend();
+    backend.set_files_dir(Some(
+        files_dir.map_or_else(|| own_files_dir(path), PathBuf::from),
+    ));
+    let exit = run(&mut *backend, &code, max_instructions);
+    if let Some(printer) = printer {
+        if let Err(err) = append(&printer, &backend.take_printer_output()) {
+            eprintln!("{}: {}", printer, err);
+            return ExitCode::from(2);
+        }
+    }
+    exit
+}
+
+/// The folder beside the program at `path` that its files go in, made when
+/// it first writes one
+fn own_files_dir(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    path.with_file_name(format!("{}_files", stem))
+}
+
+fn append(path: &str, text: &str) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(text.as_bytes())
+}
+
+fn parse_args(args: &[String]) -> Result<(Command, usize), String> {
+    let mut path = None;
+    let mut manifest = None;
+    let mut printer = None;
+    let mut files_dir = None;
+    let mut json = false;
+    let mut max_instructions = DEFAULT_MAX_INSTRUCTIONS;
+    let mut args = args.iter();
+    let checking = match args.next().map(String::as_str) {
+        Some("run") => false,
+        Some("check") => true,
+        _ => return Err(USAGE.to_string()),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-instructions" => {
+                max_instructions = args
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .ok_or("--max-instructions needs a whole number")?;
+            }
+            "--manifest" if checking => {
+                manifest = Some(args.next().ok_or("--manifest needs a file")?.clone());
+            }
+            "--json" if checking => json = true,
+            "--printer" if !checking => {
+                printer = Some(args.next().ok_or("--printer needs a file")?.clone());
+            }
+            "--files-dir" if !checking => {
+                files_dir = Some(args.next().ok_or("--files-dir needs a folder")?.clone());
+            }
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or_else(|| USAGE.to_string())?;
+    let command = if checking {
+        Command::Check {
+            dir: path,
+            manifest,
+            json,
+        }
+    } else {
+        Command::Run {
+            path,
+            printer,
+            files_dir,
+        }
+    };
+    Ok((command, max_instructions))
+}
+
+fn check(dir: &str, manifest: Option<String>, json: bool, max_instructions: usize) -> ExitCode {
+    let manifest = manifest.unwrap_or_else(|| {
+        Path::new(dir)
+            .join("manifest.txt")
+            .to_string_lossy()
+            .to_string()
+    });
+    let cases = match std::fs::read_to_string(&manifest)
+        .map_err(|err| err.to_string())
+        .and_then(|text| batch::parse_manifest(&text))
+    {
+        Ok(cases) => cases,
+        Err(message) => {
+            eprintln!("{}: {}", manifest, message);
+            return ExitCode::from(2);
+        }
+    };
+    let reports = match batch::check_directory(Path::new(dir), &cases, max_instructions) {
+        Ok(reports) if !reports.is_empty() => reports,
+        Ok(_) => {
+            eprintln!("{}: no programs to check", dir);
+            return ExitCode::from(2);
+        }
+        Err(err) => {
+            eprintln!("{}: {}", dir, err);
+            return ExitCode::from(2);
+        }
+    };
+
+    if json {
+        print!("{}", batch::json_report(&reports));
+    } else {
+        print!("{}", batch::text_report(&reports));
+    }
+    if reports.iter().all(batch::ProgramReport::passed) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn run(backend: &mut dyn LanguageBackend, code: &str, max_instructions: usize) -> ExitCode {
+    let stdin = std::io::stdin();
+    // Someone typing already sees their answer; piped answers are echoed so
+    // the transcript reads like the IDE's output
+    let typed = stdin.is_terminal();
+    let mut lines = stdin.lock().lines();
+    let mut stdout = std::io::stdout();
+
+    let mut result = backend.execute(code, max_instructions);
+    let mut answer: Option<String> = None;
+    loop {
+        let shown = |output: &str| match &answer {
+            Some(answer) if typed => output
+                .strip_prefix(&format!("{}\n", answer.trim()))
+                .unwrap_or(output)
+                .to_string(),
+            _ => output.to_string(),
+        };
+        match result {
+            Ok(ExecutionResult::NeedInput {
+                partial_output,
+                prompt,
+                ..
+            }) => {
+                print!("{}{}", shown(&partial_output), prompt);
+                let _ = stdout.flush();
+                let Some(Ok(line)) = lines.next() else {
+                    eprintln!("Error: the program is waiting for input but there is no more");
+                    return ExitCode::FAILURE;
+                };
+                result = backend.continue_with_input(&line);
+                answer = Some(line);
+            }
+            Ok(ExecutionResult::Complete { output, .. })
+            | Ok(ExecutionResult::Break {
+                partial_output: output,
+                ..
+            })
+            // Only a live keyboard hands control back, and INKEY$ here has none
+            | Ok(ExecutionResult::Polling {
+                partial_output: output,
+                ..
+            }) => {
+                print!("{}", shown(&output));
+                return ExitCode::SUCCESS;
+            }
+            // Programs run here are trusted with their own folder, so nothing asks
+            Ok(ExecutionResult::NeedPermission { permission, .. }) => {
+                eprintln!("Error: the program asked to {}", permission.name());
+                return ExitCode::FAILURE;
+            }
+            Ok(ExecutionResult::Error(message)) => {
+                eprintln!("Error: {}", message);
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                print!("{}", shown(&backend.take_output()));
+                let _ = stdout.flush();
+                eprintln!("{}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+}
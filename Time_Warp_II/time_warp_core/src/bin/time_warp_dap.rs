@@ -0,0 +1,27 @@
+//! TW BASIC debug adapter. Editors start it and talk the Debug Adapter
+//! Protocol over stdin and stdout, e.g. in a VS Code launch configuration:
+//!
+//!     "type": "twbasic", "request": "launch", "program": "${file}"
+
+use time_warp_core::dap::Adapter;
+use time_warp_core::json::{self, Json};
+
+fn main() {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut stdout = std::io::stdout();
+    let mut adapter = Adapter::new();
+
+    while let Some(body) = json::read_message(&mut input) {
+        // A message we can't read has no seq to answer, so it is dropped
+        let Ok(message) = Json::parse(&body) else {
+            continue;
+        };
+        for reply in &adapter.handle(&message) {
+            let _ = json::write_message(&mut stdout, reply);
+        }
+        if adapter.exited() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! TW BASIC language server. Editors start it and talk JSON-RPC over stdin
+//! and stdout, e.g. in VS Code with a generic LSP client extension:
+//!
+//!     "command": "time_warp_lsp", "filetypes": ["twb", "bas"]
+
+use std::process::ExitCode;
+
+use time_warp_core::json::{self, Json};
+use time_warp_core::lsp::Server;
+
+fn main() -> ExitCode {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut stdout = std::io::stdout();
+    let mut server = Server::new();
+
+    while let Some(body) = json::read_message(&mut input) {
+        let replies = match Json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(err) => vec![Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", Json::Null),
+                (
+                    "error",
+                    Json::object([("code", Json::Number(-32700.0)), ("message", err.into())]),
+                ),
+            ])],
+        };
+        for reply in &replies {
+            let _ = json::write_message(&mut stdout, reply);
+        }
+        if server.exited() {
+            break;
+        }
+    }
+
+    // Exiting without a shutdown request first means the editor lost us
+    if server.shut_down() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,41 @@
+//! The runtime exported web pages embed, built for wasm32-wasip1:
+//!
+//!     time_warp_web <language> <program>
+//!
+//! `<language>` is a name such as `TW BASIC` or `Logo`. Answers to INPUT are
+//! read from stdin, one per line, and the run is written to stdout as JSON
+//! lines; see `time_warp_core::web`.
+
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+use time_warp_core::{web, Language};
+
+/// The IDE's default: 5 seconds at roughly 1000 statements per millisecond
+const DEFAULT_MAX_INSTRUCTIONS: usize = 5_000_000;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [name, code] = args.as_slice() else {
+        eprintln!("usage: time_warp_web <language> <program>");
+        return ExitCode::from(2);
+    };
+    let Some(language) = Language::from_name(name) else {
+        eprintln!("{}: not a language this build can run", name);
+        return ExitCode::from(2);
+    };
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut stdout = std::io::stdout();
+    web::run(
+        language,
+        code,
+        DEFAULT_MAX_INSTRUCTIONS,
+        &mut || lines.next().and_then(Result::ok),
+        &mut |event| {
+            let _ = writeln!(stdout, "{}", event);
+        },
+    );
+    ExitCode::SUCCESS
+}
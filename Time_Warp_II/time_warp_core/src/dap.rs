@@ -0,0 +1,421 @@
+//! A Debug Adapter Protocol server for TW BASIC, so editors such as VS Code
+//! can launch a program, set breakpoints, step and read variables. It drives
+//! the same `basic::Interpreter::run` stepping as the IDE's Debug tab.
+//!
+//! Launch arguments: `program` (the file to run), `stopOnEntry`, `input` (a
+//! list of answers for INPUT, as in `time_warp check`) and `maxInstructions`.
+//! When the answers run out the program stops, and whatever is typed into the
+//! Debug Console becomes the next answer.
+
+use crate::basic::{ExecutionResult, Interpreter, InterpreterError, StepMode};
+use crate::json::Json;
+
+/// The IDE's default: 5 seconds at roughly 1000 statements per millisecond
+const DEFAULT_MAX_INSTRUCTIONS: usize = 5_000_000;
+
+/// BASIC programs have a single thread
+const THREAD_ID: usize = 1;
+
+/// The variables scope; the only one, so it has a fixed reference
+const VARIABLES_REFERENCE: usize = 1;
+
+/// One debugging session: the launched program and what the editor asked for
+#[derive(Default)]
+pub struct Adapter {
+    seq: usize,
+    interpreter: Option<Interpreter>,
+    program: String, // path of the launched file
+    breakpoints: Vec<usize>,
+    stop_on_entry: bool,
+    awaiting_input: bool,
+    exited: bool,
+}
+
+impl Adapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set once the editor disconnects
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Answer one request, returning the response followed by any events
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let command = message
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+        let request_seq = message.get("seq").and_then(Json::as_usize).unwrap_or(0);
+        let arguments = message.get("arguments").cloned().unwrap_or(Json::Null);
+
+        let mut events = Vec::new();
+        let body = match self.dispatch(&command, &arguments, &mut events) {
+            Ok(body) => body,
+            Err(message) => {
+                let response = self.message(
+                    "response",
+                    [
+                        ("request_seq", request_seq.into()),
+                        ("success", Json::Bool(false)),
+                        ("command", command.into()),
+                        ("message", message.into()),
+                    ],
+                );
+                return vec![response];
+            }
+        };
+        let response = self.message(
+            "response",
+            [
+                ("request_seq", request_seq.into()),
+                ("success", Json::Bool(true)),
+                ("command", command.into()),
+                ("body", body),
+            ],
+        );
+        // Events are numbered after the response they follow
+        let mut sent = vec![response];
+        for (event, body) in events {
+            sent.push(self.message("event", [("event", event.into()), ("body", body)]));
+        }
+        sent
+    }
+
+    fn dispatch(
+        &mut self,
+        command: &str,
+        arguments: &Json,
+        events: &mut Vec<(&'static str, Json)>,
+    ) -> Result<Json, String> {
+        match command {
+            "initialize" => {
+                events.push(("initialized", Json::object([])));
+                Ok(Json::object([
+                    ("supportsConfigurationDoneRequest", Json::Bool(true)),
+                    ("supportsTerminateRequest", Json::Bool(true)),
+                ]))
+            }
+            "launch" => {
+                let program = arguments
+                    .get("program")
+                    .and_then(Json::as_str)
+                    .ok_or("launch needs a \"program\" to debug")?;
+                let code = std::fs::read_to_string(program)
+                    .map_err(|err| format!("{}: {}", program, err))?;
+                let mut interpreter = Interpreter::new();
+                interpreter.max_instructions = arguments
+                    .get("maxInstructions")
+                    .and_then(Json::as_usize)
+                    .unwrap_or(DEFAULT_MAX_INSTRUCTIONS);
+                interpreter
+                    .load(&code)
+                    .map_err(|err| format!("Error: {}", err))?;
+                self.program = program.to_string();
+                self.stop_on_entry = matches!(arguments.get("stopOnEntry"), Some(Json::Bool(true)));
+                if let Some(Json::Array(answers)) = arguments.get("input") {
+                    interpreter.queue_input(
+                        answers
+                            .iter()
+                            .filter_map(|answer| answer.as_str().map(str::to_string)),
+                    );
+                }
+                self.interpreter = Some(interpreter);
+                Ok(Json::object([]))
+            }
+            "setBreakpoints" => {
+                self.breakpoints = match arguments.get("breakpoints") {
+                    Some(Json::Array(breakpoints)) => breakpoints
+                        .iter()
+                        .filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_usize))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                // A breakpoint on a blank or comment-only line would never be hit
+                let executable = self.interpreter.as_ref().map(Interpreter::executable_lines);
+                let breakpoints = self
+                    .breakpoints
+                    .iter()
+                    .map(|&line| {
+                        let verified = executable
+                            .as_ref()
+                            .is_none_or(|lines| lines.contains(&line));
+                        Json::object([("verified", Json::Bool(verified)), ("line", line.into())])
+                    })
+                    .collect();
+                Ok(Json::object([("breakpoints", Json::Array(breakpoints))]))
+            }
+            "configurationDone" => {
+                let mode = if self.stop_on_entry {
+                    StepMode::StepInto
+                } else {
+                    StepMode::Continue
+                };
+                self.resume(mode, events);
+                Ok(Json::object([]))
+            }
+            "threads" => Ok(Json::object([(
+                "threads",
+                Json::Array(vec![Json::object([
+                    ("id", THREAD_ID.into()),
+                    ("name", "main".into()),
+                ])]),
+            )])),
+            "stackTrace" => {
+                let interpreter = self.interpreter.as_ref().ok_or("no program is running")?;
+                // Innermost first: where we are, then each pending GOSUB
+                let mut frames = Vec::new();
+                if let Some(line) = interpreter.current_source_line() {
+                    frames.push(("main".to_string(), line));
+                }
+                for line in interpreter.call_stack_lines().into_iter().rev() {
+                    frames.push((format!("GOSUB from line {}", line), line));
+                }
+                let source = Json::object([
+                    ("name", file_name(&self.program).into()),
+                    ("path", self.program.as_str().into()),
+                ]);
+                let total = frames.len();
+                let frames = frames
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, (name, line))| {
+                        Json::object([
+                            ("id", id.into()),
+                            ("name", name.into()),
+                            ("source", source.clone()),
+                            ("line", line.into()),
+                            ("column", 1.into()),
+                        ])
+                    })
+                    .collect();
+                Ok(Json::object([
+                    ("stackFrames", Json::Array(frames)),
+                    ("totalFrames", total.into()),
+                ]))
+            }
+            "scopes" => Ok(Json::object([(
+                "scopes",
+                Json::Array(vec![Json::object([
+                    ("name", "Variables".into()),
+                    ("variablesReference", VARIABLES_REFERENCE.into()),
+                    ("expensive", Json::Bool(false)),
+                ])]),
+            )])),
+            "variables" => {
+                let variables = self
+                    .interpreter
+                    .as_ref()
+                    .map(Interpreter::variable_snapshot)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| {
+                        Json::object([
+                            ("name", name.into()),
+                            ("value", value.into()),
+                            ("variablesReference", 0.into()),
+                        ])
+                    })
+                    .collect();
+                Ok(Json::object([("variables", Json::Array(variables))]))
+            }
+            "continue" => {
+                self.resume(StepMode::Continue, events);
+                Ok(Json::object([("allThreadsContinued", Json::Bool(true))]))
+            }
+            "next" => {
+                self.resume(StepMode::StepOver, events);
+                Ok(Json::object([]))
+            }
+            "stepIn" => {
+                self.resume(StepMode::StepInto, events);
+                Ok(Json::object([]))
+            }
+            "stepOut" => {
+                self.resume(StepMode::StepOut, events);
+                Ok(Json::object([]))
+            }
+            // Runs are synchronous, so by the time a pause arrives we're stopped
+            "pause" => Ok(Json::object([])),
+            "evaluate" => {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(Json::as_str)
+                    .unwrap_or("")
+                    .to_string();
+                if self.awaiting_input {
+                    self.answer(&expression, events);
+                    return Ok(Json::object([
+                        ("result", "".into()),
+                        ("variablesReference", 0.into()),
+                    ]));
+                }
+                let name = expression.trim().to_ascii_uppercase();
+                let value = self
+                    .interpreter
+                    .as_ref()
+                    .map(Interpreter::variable_snapshot)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|(variable, _)| variable.to_ascii_uppercase() == name)
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| format!("No variable named {}", expression.trim()))?;
+                Ok(Json::object([
+                    ("result", value.into()),
+                    ("variablesReference", 0.into()),
+                ]))
+            }
+            "terminate" => {
+                if self.interpreter.take().is_some() {
+                    events.push(("terminated", Json::object([])));
+                }
+                Ok(Json::object([]))
+            }
+            "disconnect" => {
+                self.interpreter = None;
+                self.exited = true;
+                Ok(Json::object([]))
+            }
+            _ => Err(format!("{} is not supported", command)),
+        }
+    }
+
+    /// Run the program on from where it paused
+    fn resume(&mut self, mode: StepMode, events: &mut Vec<(&'static str, Json)>) {
+        let breakpoints = self.breakpoints.clone();
+        let Some(interpreter) = self.interpreter.as_mut() else {
+            return;
+        };
+        interpreter.set_breakpoints(breakpoints);
+        let result = interpreter.run(mode);
+        self.finish(result, mode, events);
+    }
+
+    /// Give the waiting INPUT its answer and carry on
+    fn answer(&mut self, input: &str, events: &mut Vec<(&'static str, Json)>) {
+        let Some(interpreter) = self.interpreter.as_mut() else {
+            return;
+        };
+        self.awaiting_input = false;
+        let result = interpreter.provide_input(input);
+        self.finish(result, StepMode::Continue, events);
+    }
+
+    /// Turn where a run stopped into events for the editor
+    fn finish(
+        &mut self,
+        result: Result<ExecutionResult, InterpreterError>,
+        mode: StepMode,
+        events: &mut Vec<(&'static str, Json)>,
+    ) {
+        match result {
+            Ok(ExecutionResult::Break {
+                line,
+                partial_output,
+                ..
+            }) => {
+                output(events, "stdout", &partial_output);
+                let reason = if self.breakpoints.contains(&line) {
+                    "breakpoint"
+                } else if self.stop_on_entry && mode == StepMode::StepInto {
+                    self.stop_on_entry = false;
+                    "entry"
+                } else {
+                    "step"
+                };
+                events.push(stopped(reason, None));
+            }
+            Ok(ExecutionResult::NeedInput {
+                prompt,
+                partial_output,
+                ..
+            }) => {
+                output(events, "stdout", &partial_output);
+                output(events, "stdout", &prompt);
+                self.awaiting_input = true;
+                events.push(stopped(
+                    "pause",
+                    Some("Waiting for INPUT: type the answer in the Debug Console"),
+                ));
+            }
+            // Only a live keyboard hands control back, and the adapter has none
+            Ok(ExecutionResult::Complete { output: text, .. })
+            | Ok(ExecutionResult::Polling {
+                partial_output: text,
+                ..
+            }) => {
+                output(events, "stdout", &text);
+                self.end(0, events);
+            }
+            Ok(ExecutionResult::Error(message)) => {
+                output(events, "stderr", &format!("Error: {}\n", message));
+                self.end(1, events);
+            }
+            // The adapter never has the interpreter ask, but a run that did can't go on
+            Ok(ExecutionResult::NeedPermission {
+                permission,
+                partial_output,
+                ..
+            }) => {
+                output(events, "stdout", &partial_output);
+                let message = format!("Error: the program asked to {}\n", permission.name());
+                output(events, "stderr", &message);
+                self.end(1, events);
+            }
+            Err(err) => {
+                output(events, "stderr", &format!("Error: {}\n", err));
+                self.end(1, events);
+            }
+        }
+    }
+
+    fn end(&mut self, exit_code: usize, events: &mut Vec<(&'static str, Json)>) {
+        self.interpreter = None;
+        events.push(("exited", Json::object([("exitCode", exit_code.into())])));
+        events.push(("terminated", Json::object([])));
+    }
+
+    fn message<const N: usize>(&mut self, kind: &str, fields: [(&str, Json); N]) -> Json {
+        self.seq += 1;
+        let mut message = vec![
+            ("seq".to_string(), self.seq.into()),
+            ("type".to_string(), kind.into()),
+        ];
+        message.extend(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        );
+        Json::Object(message)
+    }
+}
+
+fn output(events: &mut Vec<(&'static str, Json)>, category: &str, text: &str) {
+    if !text.is_empty() {
+        events.push((
+            "output",
+            Json::object([("category", category.into()), ("output", text.into())]),
+        ));
+    }
+}
+
+fn stopped(reason: &str, description: Option<&str>) -> (&'static str, Json) {
+    let mut body = vec![
+        ("reason".to_string(), reason.into()),
+        ("threadId".to_string(), THREAD_ID.into()),
+        ("allThreadsStopped".to_string(), Json::Bool(true)),
+    ];
+    if let Some(description) = description {
+        body.push(("description".to_string(), description.into()));
+    }
+    ("stopped", Json::Object(body))
+}
+
+fn file_name(path: &str) -> &str {
+    std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
@@ -0,0 +1,1906 @@
+//! The Time Warp interpreters with no GUI attached: each language parses and
+//! runs a program, returning its output and turtle moves as an
+//! `ExecutionResult`. The IDE drives them through `LanguageBackend`.
+
+pub mod basic;
+pub mod batch;
+pub mod dap;
+pub mod json;
+#[cfg(feature = "logo")]
+pub mod logo;
+pub mod lsp;
+#[cfg(feature = "pascal")]
+pub mod pascal;
+#[cfg(feature = "pilot")]
+pub mod pilot;
+#[cfg(feature = "prolog")]
+pub mod prolog;
+pub mod web;
+
+pub use basic::{ExecutionResult, GraphicsCommand, InterpreterError, TurtleCommand};
+use std::path::PathBuf;
+
+/// Languages the IDE can run. TW BASIC is always built; each of the others
+/// has a cargo feature of its own, so matches outside this crate need a
+/// catch-all arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Language {
+    #[default]
+    Basic,
+    #[cfg(feature = "logo")]
+    Logo,
+    #[cfg(feature = "pilot")]
+    Pilot,
+    #[cfg(feature = "pascal")]
+    Pascal,
+    #[cfg(feature = "prolog")]
+    Prolog,
+}
+
+impl Language {
+    pub const ALL: &'static [Language] = &[
+        Language::Basic,
+        #[cfg(feature = "logo")]
+        Language::Logo,
+        #[cfg(feature = "pilot")]
+        Language::Pilot,
+        #[cfg(feature = "pascal")]
+        Language::Pascal,
+        #[cfg(feature = "prolog")]
+        Language::Prolog,
+    ];
+
+    /// Name shown in the status bar and stored on templates
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Basic => "TW BASIC",
+            #[cfg(feature = "logo")]
+            Language::Logo => "Logo",
+            #[cfg(feature = "pilot")]
+            Language::Pilot => "PILOT",
+            #[cfg(feature = "pascal")]
+            Language::Pascal => "Pascal",
+            #[cfg(feature = "prolog")]
+            Language::Prolog => "Prolog",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|language| language.name() == name)
+    }
+
+    /// The language a file is written in, judged by its extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_ascii_lowercase();
+        Self::ALL.iter().copied().find(|language| {
+            language
+                .backend()
+                .extensions()
+                .contains(&extension.as_str())
+        })
+    }
+
+    /// Extension new files in this language are saved with
+    pub fn extension(self) -> &'static str {
+        self.backend().extensions()[0]
+    }
+
+    /// A fresh interpreter for one run of a program in this language. Adding
+    /// a language means adding it here and to `ALL`, behind a feature of its
+    /// own; the IDE finds its keywords, comments and extensions through the
+    /// backend.
+    pub fn backend(self) -> Box<dyn LanguageBackend> {
+        match self {
+            Language::Basic => Box::new(basic::Interpreter::new()),
+            #[cfg(feature = "logo")]
+            Language::Logo => Box::new(logo::Interpreter::new()),
+            #[cfg(feature = "pilot")]
+            Language::Pilot => Box::new(pilot::Interpreter::new()),
+            #[cfg(feature = "pascal")]
+            Language::Pascal => Box::new(pascal::Interpreter::new()),
+            #[cfg(feature = "prolog")]
+            Language::Prolog => Box::new(prolog::Interpreter::new()),
+        }
+    }
+}
+
+/// An interpreter as the IDE drives it. A run starts with `execute`; when it
+/// stops with `NeedInput`, the answer from the input box goes to
+/// `continue_with_input`, which carries on from where it paused.
+pub trait LanguageBackend {
+    /// Words offered by completion and highlighted in the editor
+    fn keywords(&self) -> &'static [&'static str];
+
+    /// What a comment starts with; the rest of the line is the comment
+    fn comment_prefixes(&self) -> &'static [&'static str];
+
+    /// Comments that need no closing mark, so a line can be commented out by
+    /// putting the first one in front of it
+    fn line_comments(&self) -> &'static [&'static str] {
+        self.comment_prefixes()
+    }
+
+    /// File extensions in this language, the one new files get first
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Bracket pairs the editor matches up around the caret
+    fn brackets(&self) -> &'static [(char, char)] {
+        &[('(', ')'), ('[', ']')]
+    }
+
+    /// Words that open a block, so the editor indents the lines after them
+    fn block_openers(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Words that close a block, so the editor moves them back out
+    fn block_closers(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The statement that calls a subroutine and the one it returns with,
+    /// so the editor can fold each subroutine away
+    fn subroutine_keywords(&self) -> Option<(&'static str, &'static str)> {
+        None
+    }
+
+    fn execute(
+        &mut self,
+        code: &str,
+        max_instructions: usize,
+    ) -> Result<ExecutionResult, InterpreterError>;
+
+    fn continue_with_input(&mut self, input: &str) -> Result<ExecutionResult, InterpreterError>;
+
+    /// Output printed since the last pause when an error ended the run
+    fn take_output(&mut self) -> String;
+
+    /// Turtle moves made before an error ended the run
+    fn take_graphics(&mut self) -> Vec<TurtleCommand> {
+        Vec::new()
+    }
+
+    /// What LPRINT has printed since the last call; only BASIC has a printer
+    fn take_printer_output(&mut self) -> String {
+        String::new()
+    }
+
+    /// The folder programs OPEN their files in; only BASIC opens files
+    fn set_files_dir(&mut self, _dir: Option<PathBuf>) {}
+
+    /// Answers to hand out, in order, whenever the next run asks for input.
+    /// Queue them before `execute`; the run only pauses once they run out.
+    fn queue_input(&mut self, _answers: Vec<String>) {}
+
+    /// Statements run so far, for the execution stats
+    fn instruction_count(&self) -> usize;
+}
+
+impl LanguageBackend for basic::Interpreter {
+    fn keywords(&self) -> &'static [&'static str] {
+        basic::KEYWORDS
+    }
+
+    fn comment_prefixes(&self) -> &'static [&'static str] {
+        &["REM ", "'"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["twb", "bas"]
+    }
+
+    fn block_openers(&self) -> &'static [&'static str] {
+        &["FOR", "WHILE"]
+    }
+
+    fn block_closers(&self) -> &'static [&'static str] {
+        &["NEXT", "WEND"]
+    }
+
+    fn subroutine_keywords(&self) -> Option<(&'static str, &'static str)> {
+        Some(("GOSUB", "RETURN"))
+    }
+
+    fn execute(
+        &mut self,
+        code: &str,
+        max_instructions: usize,
+    ) -> Result<ExecutionResult, InterpreterError> {
+        self.max_instructions = max_instructions;
+        basic::Interpreter::execute(self, code)
+    }
+
+    fn continue_with_input(&mut self, input: &str) -> Result<ExecutionResult, InterpreterError> {
+        basic::Interpreter::provide_input(self, input)
+    }
+
+    fn queue_input(&mut self, answers: Vec<String>) {
+        basic::Interpreter::queue_input(self, answers)
+    }
+
+    fn take_output(&mut self) -> String {
+        String::new()
+    }
+
+    fn take_printer_output(&mut self) -> String {
+        basic::Interpreter::take_printer_output(self)
+    }
+
+    fn set_files_dir(&mut self, dir: Option<PathBuf>) {
+        basic::Interpreter::set_files_dir(self, dir)
+    }
+
+    fn instruction_count(&self) -> usize {
+        basic::Interpreter::instruction_count(self)
+    }
+}
+
+#[cfg(feature = "logo")]
+impl LanguageBackend for logo::Interpreter {
+    fn keywords(&self) -> &'static [&'static str] {
+        logo::KEYWORDS
+    }
+
+    fn comment_prefixes(&self) -> &'static [&'static str] {
+        &[";"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["twl", "logo", "lgo"]
+    }
+
+    fn block_openers(&self) -> &'static [&'static str] {
+        &["TO"]
+    }
+
+    fn block_closers(&self) -> &'static [&'static str] {
+        &["END"]
+    }
+
+    fn execute(
+        &mut self,
+        code: &str,
+        max_instructions: usize,
+    ) -> Result<ExecutionResult, InterpreterError> {
+        self.max_instructions = max_instructions;
+        logo::Interpreter::execute(self, code)?;
+        Ok(ExecutionResult::Complete {
+            output: std::mem::take(&mut self.output),
+            graphics_commands: std::mem::take(&mut self.graphics_commands),
+        })
+    }
+
+    // Logo never asks for input
+    fn continue_with_input(&mut self, _input: &str) -> Result<ExecutionResult, InterpreterError> {
+        Ok(ExecutionResult::Complete {
+            output: String::new(),
+            graphics_commands: Vec::new(),
+        })
+    }
+
+    fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    fn take_graphics(&mut self) -> Vec<TurtleCommand> {
+        std::mem::take(&mut self.graphics_commands)
+    }
+
+    fn instruction_count(&self) -> usize {
+        logo::Interpreter::instruction_count(self)
+    }
+}
+
+#[cfg(feature = "pilot")]
+impl LanguageBackend for pilot::Interpreter {
+    fn keywords(&self) -> &'static [&'static str] {
+        pilot::KEYWORDS
+    }
+
+    fn comment_prefixes(&self) -> &'static [&'static str] {
+        &["R:"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["plt", "pilot"]
+    }
+
+    fn execute(
+        &mut self,
+        code: &str,
+        max_instructions: usize,
+    ) -> Result<ExecutionResult, InterpreterError> {
+        self.max_instructions = max_instructions;
+        pilot::Interpreter::execute(self, code)
+    }
+
+    fn continue_with_input(&mut self, input: &str) -> Result<ExecutionResult, InterpreterError> {
+        pilot::Interpreter::provide_input(self, input)
+    }
+
+    fn queue_input(&mut self, answers: Vec<String>) {
+        pilot::Interpreter::queue_input(self, answers)
+    }
+
+    fn take_output(&mut self) -> String {
+        pilot::Interpreter::take_output(self)
+    }
+
+    fn instruction_count(&self) -> usize {
+        pilot::Interpreter::instruction_count(self)
+    }
+}
+
+#[cfg(feature = "pascal")]
+impl LanguageBackend for pascal::Interpreter {
+    fn keywords(&self) -> &'static [&'static str] {
+        pascal::KEYWORDS
+    }
+
+    fn comment_prefixes(&self) -> &'static [&'static str] {
+        &["{", "//"]
+    }
+
+    fn line_comments(&self) -> &'static [&'static str] {
+        &["//"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["twp", "pas"]
+    }
+
+    fn block_openers(&self) -> &'static [&'static str] {
+        &["BEGIN", "REPEAT", "RECORD", "CASE"]
+    }
+
+    fn block_closers(&self) -> &'static [&'static str] {
+        &["END", "UNTIL"]
+    }
+
+    fn execute(
+        &mut self,
+        code: &str,
+        max_instructions: usize,
+    ) -> Result<ExecutionResult, InterpreterError> {
+        self.max_instructions = max_instructions;
+        pascal::Interpreter::execute(self, code)
+    }
+
+    fn continue_with_input(&mut self, input: &str) -> Result<ExecutionResult, InterpreterError> {
+        pascal::Interpreter::provide_input(self, input)
+    }
+
+    fn queue_input(&mut self, answers: Vec<String>) {
+        pascal::Interpreter::queue_input(self, answers)
+    }
+
+    fn take_output(&mut self) -> String {
+        pascal::Interpreter::take_output(self)
+    }
+
+    fn instruction_count(&self) -> usize {
+        pascal::Interpreter::instruction_count(self)
+    }
+}
+
+#[cfg(feature = "prolog")]
+impl LanguageBackend for prolog::Interpreter {
+    fn keywords(&self) -> &'static [&'static str] {
+        prolog::KEYWORDS
+    }
+
+    fn comment_prefixes(&self) -> &'static [&'static str] {
+        &["%"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tpr", "pro", "pl"]
+    }
+
+    fn execute(
+        &mut self,
+        code: &str,
+        max_instructions: usize,
+    ) -> Result<ExecutionResult, InterpreterError> {
+        self.max_instructions = max_instructions;
+        prolog::Interpreter::execute(self, code)?;
+        Ok(ExecutionResult::Complete {
+            output: std::mem::take(&mut self.output),
+            graphics_commands: Vec::new(),
+        })
+    }
+
+    // Prolog programs answer their own queries and never ask for input
+    fn continue_with_input(&mut self, _input: &str) -> Result<ExecutionResult, InterpreterError> {
+        Ok(ExecutionResult::Complete {
+            output: String::new(),
+            graphics_commands: Vec::new(),
+        })
+    }
+
+    fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    fn instruction_count(&self) -> usize {
+        prolog::Interpreter::instruction_count(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_input_x() {
+        use crate::basic::Tokenizer;
+
+        let mut tokenizer = Tokenizer::new("INPUT X");
+        let tokens = tokenizer.tokenize().unwrap();
+
+        println!("Tokens for 'INPUT X': {:?}", tokens);
+
+        // Should have INPUT, identifier X, EOF
+        assert!(tokens.len() >= 3);
+    }
+
+    #[test]
+    fn test_parse_input_x() {
+        use crate::basic::{Parser, Tokenizer};
+
+        let mut tokenizer = Tokenizer::new("INPUT X");
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+
+        println!("Parsed program for 'INPUT X': {:?}", program);
+
+        // Should have one statement
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_print_semicolon() {
+        use crate::basic::{Parser, Tokenizer};
+
+        let mut tokenizer = Tokenizer::new("PRINT 42;");
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+
+        println!("Parsed program for 'PRINT 42;': {:?}", program);
+
+        // Should have one statement
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_file_io_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING FILE I/O COMMANDS ===");
+
+        // Test OPEN command
+        println!("\n--- Testing OPEN command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("OPEN \"test.txt\" FOR OUTPUT AS #1");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("OPEN result: {}", output);
+                assert!(output.contains("File opened") || output.is_empty()); // May be empty if not fully implemented
+            }
+            _ => println!("OPEN command executed (may not be fully implemented yet)"),
+        }
+
+        // Test CLOSE command
+        println!("\n--- Testing CLOSE command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("CLOSE #1");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("CLOSE result: {}", output);
+            }
+            _ => println!("CLOSE command executed"),
+        }
+
+        // Test PRINT# command
+        println!("\n--- Testing PRINT# command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("PRINT #1, \"Hello World\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("PRINT# result: {}", output);
+            }
+            _ => println!("PRINT# command executed"),
+        }
+
+        // Test INPUT# command
+        println!("\n--- Testing INPUT# command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("INPUT #1, A$");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("INPUT# result: {}", output);
+            }
+            _ => println!("INPUT# command executed"),
+        }
+
+        // Test KILL command
+        println!("\n--- Testing KILL command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("KILL \"test.txt\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("KILL result: {}", output);
+            }
+            _ => println!("KILL command executed"),
+        }
+
+        // Test NAME command
+        println!("\n--- Testing NAME command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("NAME \"old.txt\" AS \"new.txt\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("NAME result: {}", output);
+            }
+            _ => println!("NAME command executed"),
+        }
+
+        // Test FILES command
+        println!("\n--- Testing FILES command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("FILES");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("FILES result: {}", output);
+            }
+            _ => println!("FILES command executed"),
+        }
+
+        println!("\n=== FILE I/O COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_graphics_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING GRAPHICS COMMANDS ===");
+
+        // Test LINE command
+        println!("\n--- Testing LINE command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("LINE (10, 10)-(100, 100)");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("LINE result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+                assert!(!graphics_commands.is_empty());
+            }
+            _ => println!("LINE command executed"),
+        }
+
+        // Test CIRCLE command
+        println!("\n--- Testing CIRCLE command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("CIRCLE (200, 200), 50");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("CIRCLE result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+                assert!(!graphics_commands.is_empty());
+            }
+            _ => println!("CIRCLE command executed"),
+        }
+
+        // Test PSET command
+        println!("\n--- Testing PSET command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("PSET (150, 150)");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("PSET result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+            }
+            _ => println!("PSET command executed"),
+        }
+
+        // Test PRESET command
+        println!("\n--- Testing PRESET command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("PRESET (150, 150)");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("PRESET result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+            }
+            _ => println!("PRESET command executed"),
+        }
+
+        // Test PAINT command
+        println!("\n--- Testing PAINT command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("PAINT (100, 100)");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("PAINT result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+            }
+            _ => println!("PAINT command executed"),
+        }
+
+        // Test DRAW command
+        println!("\n--- Testing DRAW command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("DRAW \"U10 D10 L10 R10\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("DRAW result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+            }
+            _ => println!("DRAW command executed"),
+        }
+
+        println!("\n=== GRAPHICS COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_sound_commands() {
+        use crate::basic::music::{BEEP_FREQUENCY, BEEP_LENGTH};
+        use crate::basic::Interpreter;
+        use std::time::Duration;
+
+        // BEEP and SOUND queue tones to play, as PLAY does
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.execute("BEEP").is_ok());
+        let notes = interpreter.take_music();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].frequency, Some(BEEP_FREQUENCY));
+        assert_eq!(notes[0].length, BEEP_LENGTH);
+
+        assert!(interpreter.execute("SOUND 440, 18.2: SOUND 880, 0").is_ok());
+        let notes = interpreter.take_music();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].frequency, Some(440.0));
+        assert_eq!(notes[0].length, Duration::from_secs(1));
+        assert_eq!(notes[0].sounding, notes[0].length);
+
+        let error = format!("{:?}", interpreter.execute("SOUND 20, 1"));
+        assert!(error.contains("SOUND takes 37 to 32767 hertz"), "{}", error);
+        let error = format!("{:?}", interpreter.execute("SOUND 440, -1"));
+        assert!(error.contains("SOUND takes 0 to 65535 ticks"), "{}", error);
+    }
+
+    #[test]
+    fn test_screen_control_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING SCREEN CONTROL COMMANDS ===");
+
+        // Test LOCATE command
+        println!("\n--- Testing LOCATE command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("LOCATE 10, 20");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("LOCATE result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+                assert!(!graphics_commands.is_empty()); // Should generate a locate command
+            }
+            _ => println!("LOCATE command executed"),
+        }
+
+        // Test SCREEN command
+        println!("\n--- Testing SCREEN command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("SCREEN 1");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("SCREEN result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+                assert!(!graphics_commands.is_empty()); // Should generate a screen command
+            }
+            _ => println!("SCREEN command executed"),
+        }
+
+        // Test WIDTH command
+        println!("\n--- Testing WIDTH command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("WIDTH 80");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("WIDTH result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+                assert!(!graphics_commands.is_empty()); // Should generate a width command
+            }
+            _ => println!("WIDTH command executed"),
+        }
+
+        // Test COLOR command
+        println!("\n--- Testing COLOR command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("COLOR 1, 2");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("COLOR result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+                assert!(!graphics_commands.is_empty()); // Should generate color commands
+            }
+            _ => println!("COLOR command executed"),
+        }
+
+        // Test PALETTE command
+        println!("\n--- Testing PALETTE command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("PALETTE 0, 65535");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("PALETTE result: {}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+            }
+            _ => println!("PALETTE command executed"),
+        }
+
+        println!("\n=== SCREEN CONTROL COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_error_handling_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING ERROR HANDLING COMMANDS ===");
+
+        // Test ON ERROR command
+        println!("\n--- Testing ON ERROR command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("ON ERROR GOTO 100");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("ON ERROR result: {}", output);
+            }
+            _ => println!("ON ERROR command executed"),
+        }
+
+        // Test RESUME command
+        println!("\n--- Testing RESUME command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("RESUME");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("RESUME result: {}", output);
+            }
+            _ => println!("RESUME command executed"),
+        }
+
+        // Test RESUME with line number
+        println!("\n--- Testing RESUME NEXT command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("RESUME NEXT");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("RESUME NEXT result: {}", output);
+            }
+            _ => println!("RESUME NEXT command executed"),
+        }
+
+        println!("\n=== ERROR HANDLING COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_control_flow_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING CONTROL FLOW COMMANDS ===");
+
+        // Test WHILE/WEND loop
+        println!("\n--- Testing WHILE/WEND loop ---");
+        let mut interpreter = Interpreter::new();
+        let program = r#"
+        LET X = 1
+        WHILE X <= 3
+        PRINT "Count: "; X
+        LET X = X + 1
+        WEND
+        PRINT "Loop finished"
+        "#;
+        let result = interpreter.execute(program);
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("WHILE/WEND result:\n{}", output);
+                assert!(output.contains("Count: 1"));
+                assert!(output.contains("Count: 2"));
+                assert!(output.contains("Count: 3"));
+                assert!(output.contains("Loop finished"));
+            }
+            _ => println!("WHILE/WEND loop executed"),
+        }
+
+        // Test SELECT CASE
+        println!("\n--- Testing SELECT CASE ---");
+        let mut interpreter = Interpreter::new();
+        let program = r#"
+        LET GRADE = 85
+        SELECT CASE GRADE
+        CASE 90 TO 100
+        PRINT "A"
+        CASE 80 TO 89
+        PRINT "B"
+        CASE 70 TO 79
+        PRINT "C"
+        CASE ELSE
+        PRINT "F"
+        END SELECT
+        "#;
+        let result = interpreter.execute(program);
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("SELECT CASE result:\n{}", output);
+                assert!(output.contains("B"));
+            }
+            _ => println!("SELECT CASE executed"),
+        }
+
+        println!("\n=== CONTROL FLOW COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_system_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING SYSTEM COMMANDS ===");
+
+        // Test SYSTEM command
+        println!("\n--- Testing SYSTEM command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("SYSTEM");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("SYSTEM result: {}", output);
+            }
+            _ => println!("SYSTEM command executed"),
+        }
+
+        // Test CHDIR command
+        println!("\n--- Testing CHDIR command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("CHDIR \"/tmp\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("CHDIR result: {}", output);
+            }
+            _ => println!("CHDIR command executed"),
+        }
+
+        // Test MKDIR command
+        println!("\n--- Testing MKDIR command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("MKDIR \"testdir\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("MKDIR result: {}", output);
+            }
+            _ => println!("MKDIR command executed"),
+        }
+
+        // Test RMDIR command
+        println!("\n--- Testing RMDIR command ---");
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute("RMDIR \"testdir\"");
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("RMDIR result: {}", output);
+            }
+            _ => println!("RMDIR command executed"),
+        }
+
+        println!("\n=== SYSTEM COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_array_commands() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING ARRAY COMMANDS ===");
+
+        // Test OPTION BASE
+        println!("\n--- Testing OPTION BASE command ---");
+        let mut interpreter = Interpreter::new();
+        let program = r#"
+        OPTION BASE 1
+        DIM A(5)
+        LET A(1) = 10
+        PRINT "Array base is 1, A(1) = "; A(1)
+        "#;
+        let result = interpreter.execute(program);
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("OPTION BASE result:\n{}", output);
+                assert!(output.contains("Array base is 1"));
+            }
+            _ => println!("OPTION BASE executed"),
+        }
+
+        // Test ERASE command
+        println!("\n--- Testing ERASE command ---");
+        let mut interpreter = Interpreter::new();
+        let program = r#"
+        DIM B(10)
+        LET B(0) = 42
+        PRINT "Before ERASE: B(0) = "; B(0)
+        ERASE B
+        "#;
+        let result = interpreter.execute(program);
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete { output, .. }) => {
+                println!("ERASE result:\n{}", output);
+            }
+            _ => println!("ERASE command executed"),
+        }
+
+        println!("\n=== ARRAY COMMANDS TEST COMPLETE ===");
+    }
+
+    #[test]
+    fn test_comprehensive_gw_basic_program() {
+        use crate::basic::Interpreter;
+
+        println!("=== TESTING COMPREHENSIVE GW BASIC PROGRAM ===");
+
+        // Create a comprehensive program using multiple GW BASIC features
+        let program = r#"
+        PRINT "Hello World"
+        LET GRADE = 85
+        SELECT CASE GRADE
+        CASE 80 TO 89
+        PRINT "Grade: B"
+        END SELECT
+        "#;
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(program);
+
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("COMPREHENSIVE PROGRAM OUTPUT:\n{}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+
+                // Verify key outputs
+                assert!(output.contains("Hello World"));
+                assert!(output.contains("Grade: B"));
+
+                println!("\n=== COMPREHENSIVE TEST PASSED ===");
+            }
+            Err(e) => {
+                println!("COMPREHENSIVE PROGRAM FAILED: {:?}", e);
+                panic!("Comprehensive test failed");
+            }
+            _ => {
+                println!("COMPREHENSIVE PROGRAM - Unexpected result type");
+            }
+        }
+    }
+
+    #[test]
+    fn test_comprehensive_demo_program() {
+        use crate::basic::Interpreter;
+
+        println!("\n=== TESTING COMPREHENSIVE DEMO PROGRAM ===");
+
+        let program = r#"
+10 PRINT "TW BASIC Comprehensive Demonstration Program"
+20 PRINT "============================================"
+30 LET SCORE = 0
+40 PRINT "SCORE ="; SCORE
+50 PRINT "Program completed successfully!"
+"#;
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(program);
+
+        match result {
+            Ok(crate::basic::ExecutionResult::Complete {
+                output,
+                graphics_commands,
+            }) => {
+                println!("COMPREHENSIVE DEMO OUTPUT:\n{}", output);
+                println!("Graphics commands generated: {}", graphics_commands.len());
+
+                // Verify comprehensive functionality
+                assert!(output.contains("TW BASIC Comprehensive Demonstration Program"));
+                assert!(output.contains("SCORE =0"));
+                assert!(output.contains("Program completed successfully"));
+
+                println!("\n=== COMPREHENSIVE DEMO TEST PASSED ===");
+            }
+            Err(e) => {
+                println!("COMPREHENSIVE DEMO FAILED: {:?}", e);
+                panic!("Comprehensive demo test failed");
+            }
+            _ => {
+                println!("COMPREHENSIVE DEMO - Unexpected result type");
+            }
+        }
+    }
+
+    #[test]
+    fn test_debug_step_modes() {
+        use crate::basic::{ExecutionResult, Interpreter, StepMode};
+
+        let program =
+            "10 X = 1\n20 GOSUB 100\n30 PRINT X\n40 END\n100 X = 5\n110 PRINT \"IN\"\n120 RETURN";
+        let paused_line =
+            |interpreter: &mut Interpreter, mode: StepMode| match interpreter.run(mode).unwrap() {
+                ExecutionResult::Break { line, .. } => Some(line),
+                _ => None,
+            };
+
+        // Step over treats the GOSUB as a single step
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepInto), Some(1));
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepOver), Some(2));
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepOver), Some(3));
+
+        // Step into follows the GOSUB, step out runs to the line after it
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepInto), Some(1));
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepInto), Some(2));
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepInto), Some(5));
+        assert_eq!(interpreter.call_stack_lines(), vec![2]);
+        assert_eq!(paused_line(&mut interpreter, StepMode::StepOut), Some(3));
+        assert!(interpreter.call_stack_lines().is_empty());
+
+        // Continue stops only at breakpoints
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        interpreter.set_breakpoints([110]);
+        assert_eq!(paused_line(&mut interpreter, StepMode::Continue), None);
+        interpreter.load(program).unwrap();
+        interpreter.set_breakpoints([6]);
+        assert_eq!(paused_line(&mut interpreter, StepMode::Continue), Some(6));
+        assert_eq!(paused_line(&mut interpreter, StepMode::Continue), None);
+    }
+
+    #[test]
+    fn test_event_traps() {
+        use crate::basic::events::EventTable;
+        use crate::basic::{EventSource, ExecutionResult, Interpreter, StepMode, TrapState};
+        use std::time::{Duration, Instant};
+
+        let output = |result: ExecutionResult| match result {
+            ExecutionResult::Complete { output, .. } => output,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        // Run up to the statement on `line`, raise `source` there, then finish
+        let run_with_event = |code: &str, line: usize, source: EventSource| {
+            let mut interpreter = Interpreter::new();
+            interpreter.load(code).unwrap();
+            interpreter.set_breakpoints([line]);
+            interpreter.run(StepMode::Continue).unwrap();
+            interpreter.set_breakpoints([]);
+            interpreter.raise_event(source);
+            interpreter.raise_event(source);
+            output(interpreter.run(StepMode::Continue).unwrap())
+        };
+
+        // The handler runs before the next statement and RETURNs to it; two
+        // presses before the dispatch point count as one
+        let program = "10 ON KEY(1) GOSUB 100\n20 KEY(1) ON\n30 PRINT \"A\"\n40 PRINT \"B\"\n50 END\n100 PRINT \"KEY\"\n110 RETURN";
+        assert_eq!(
+            run_with_event(program, 3, EventSource::Key(1)),
+            "KEY\nA\nB\n"
+        );
+        // Other keys and traps that are off ignore the event
+        assert_eq!(run_with_event(program, 3, EventSource::Key(2)), "A\nB\n");
+        let off = program.replace("KEY(1) ON", "KEY(1) OFF");
+        assert_eq!(run_with_event(&off, 3, EventSource::Key(1)), "A\nB\n");
+        // STOP holds the event until the trap is turned back on
+        let stopped = "10 ON KEY(2) GOSUB 100\n20 KEY(2) STOP\n30 PRINT \"A\"\n40 KEY(2) ON\n50 PRINT \"B\"\n60 END\n100 PRINT \"KEY\"\n110 RETURN";
+        assert_eq!(
+            run_with_event(stopped, 3, EventSource::Key(2)),
+            "A\nKEY\nB\n"
+        );
+
+        // Timer ticks that arrive during the handler wait for its RETURN
+        let mut events = EventTable::new();
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        events.set_handler(EventSource::Timer, Some(500));
+        events.set_timer_interval(Duration::from_secs(2), start);
+        events.set_state(EventSource::Timer, TrapState::On, start);
+        assert_eq!(events.next_dispatch(at(1)), None);
+        assert_eq!(events.next_dispatch(at(2)), Some((EventSource::Timer, 500)));
+        assert_eq!(events.next_dispatch(at(5)), None);
+        events.finish(EventSource::Timer);
+        assert_eq!(events.next_dispatch(at(5)), Some((EventSource::Timer, 500)));
+
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter
+            .execute("KEY(0) ON\nON TIMER(1) GOSUB 10\nTIMER OFF\nON MOUSE GOSUB 0\nMOUSE STOP")
+            .is_ok());
+        assert!(interpreter.execute("ON KEY(30) GOSUB 10").is_err());
+        assert!(interpreter.execute("ON TIMER GOSUB 10").is_err());
+        assert!(interpreter.execute("KEY ON").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "pilot")]
+    fn test_batch_check() {
+        use crate::batch;
+        use crate::json::Json;
+
+        let cases = batch::parse_manifest(
+            "# two cases\n[small]\n< 2\n< 3\n> 5\n[greeting]\n< 1\n< 1\n> Sum\n> 2\n",
+        )
+        .unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].inputs, vec!["2", "3"]);
+        assert!(batch::parse_manifest("< 2\n").is_err());
+
+        let dir = std::env::temp_dir().join(format!("tw_batch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("good.twb"),
+            "10 INPUT A\n20 INPUT B\n30 PRINT \"Sum\"\n40 PRINT A + B\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("short.twb"), "10 INPUT A\n20 PRINT A\n").unwrap();
+        std::fs::write(dir.join("greedy.plt"), "A:$X\nA:$Y\nA:$Z\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a program").unwrap();
+
+        let reports = batch::check_directory(&dir, &cases, 10_000).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let names: Vec<&str> = reports.iter().map(|r| r.program.as_str()).collect();
+        assert_eq!(names, vec!["good.twb", "greedy.plt", "short.twb"]);
+        assert!(reports[0].passed());
+        assert!(reports[1].cases[0].detail.contains("more input"));
+        assert!(!reports[2].passed());
+
+        let text = batch::text_report(&reports);
+        assert!(text.starts_with("PASS good.twb (2/2 cases)\n"));
+        assert!(text.ends_with("1 of 3 programs passed\n"));
+        // The JSON report reads back as the same results
+        let json = Json::parse(&batch::json_report(&reports)).unwrap();
+        let programs = match json.get("programs") {
+            Some(Json::Array(programs)) => programs,
+            other => panic!("expected a list of programs, got {:?}", other),
+        };
+        assert_eq!(
+            programs[0].get("program").and_then(Json::as_str),
+            Some("good.twb")
+        );
+        assert_eq!(programs[0].get("passed"), Some(&Json::Bool(true)));
+        let failed = match programs[2].get("cases") {
+            Some(Json::Array(cases)) => &cases[0],
+            other => panic!("expected a list of cases, got {:?}", other),
+        };
+        assert_eq!(
+            failed.get("detail").and_then(Json::as_str),
+            Some("expected a line '5' that never came")
+        );
+        assert_eq!(failed.get("code"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn test_basic_language_server() {
+        use crate::json::Json;
+        use crate::lsp;
+
+        let code = "10 PRINT \"HI\"\n20 GOTO 50\n30 ON TIMER(1) GOSUB 10\n";
+        let found = lsp::diagnostics(code);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].line, 1);
+        assert!(found[0].message.contains("no line 50"));
+        assert_eq!(lsp::diagnostics("10 PRINT \"HI\n")[0].line, 0);
+        assert_eq!(lsp::diagnostics("10 PRINT 1\n20 PRINT 2 +\n")[0].line, 1);
+
+        assert_eq!(lsp::completions("go"), vec!["GOTO", "GOSUB"]);
+        assert!(lsp::hover("gosub").unwrap().contains("RETURN"));
+        // Nothing the parser can't take is advertised as working
+        assert_eq!(lsp::hover("read"), None);
+        assert!(lsp::completions("re").iter().all(|word| *word != "RESTORE"));
+        assert!(lsp::hover("dim").unwrap().contains("Not supported yet"));
+        // GOSUB 10 on line 3 leads to the first line; line 1's own number doesn't
+        assert_eq!(lsp::definition(code, 2, 22), Some(0));
+        assert_eq!(lsp::definition(code, 0, 1), None);
+
+        let mut server = lsp::Server::new();
+        let open = Json::parse(
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.twb","text":"10 GOTO 99\n"}}}"#,
+        )
+        .unwrap();
+        let sent = server.handle(&open);
+        let diagnostics = sent[0].at(&["params", "diagnostics"]).unwrap();
+        assert!(matches!(diagnostics, Json::Array(found) if found.len() == 1));
+
+        let hover = Json::parse(
+            r#"{"jsonrpc":"2.0","id":7,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.twb"},"position":{"line":0,"character":4}}}"#,
+        )
+        .unwrap();
+        let reply = server.handle(&hover).remove(0);
+        assert_eq!(reply.get("id"), Some(&Json::Number(7.0)));
+        assert!(reply.to_string().contains("jump to a line number"));
+
+        server.handle(&Json::parse(r#"{"jsonrpc":"2.0","id":8,"method":"shutdown"}"#).unwrap());
+        server.handle(&Json::parse(r#"{"jsonrpc":"2.0","method":"exit"}"#).unwrap());
+        assert!(server.shut_down() && server.exited());
+    }
+
+    #[test]
+    fn test_basic_debug_adapter() {
+        use crate::dap::Adapter;
+        use crate::json::Json;
+
+        let path = std::env::temp_dir().join(format!("tw_dap_{}.twb", std::process::id()));
+        std::fs::write(
+            &path,
+            "10 INPUT N\n20 GOSUB 100\n30 PRINT N\n40 END\n100 N = N * 2\n110 RETURN\n",
+        )
+        .unwrap();
+        let request = |command: &str, arguments: String| {
+            Json::parse(&format!(
+                r#"{{"seq":1,"type":"request","command":"{}","arguments":{}}}"#,
+                command, arguments
+            ))
+            .unwrap()
+        };
+        let events = |sent: &[Json]| -> Vec<String> {
+            sent.iter()
+                .filter_map(|message| message.get("event").and_then(Json::as_str))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let mut adapter = Adapter::new();
+        let sent = adapter.handle(&request("initialize", "{}".to_string()));
+        assert_eq!(events(&sent), vec!["initialized"]);
+        let program = Json::from(path.to_string_lossy().to_string()).to_string();
+        adapter.handle(&request(
+            "launch",
+            format!(r#"{{"program":{},"input":["21"]}}"#, program),
+        ));
+        let sent = adapter.handle(&request(
+            "setBreakpoints",
+            r#"{"breakpoints":[{"line":5},{"line":7}]}"#.to_string(),
+        ));
+        // Line 7 is past the end of the program, so it can never be hit
+        let breakpoints = sent[0].to_string();
+        assert!(breakpoints.contains(r#"{"verified":true,"line":5}"#));
+        assert!(breakpoints.contains(r#"{"verified":false,"line":7}"#));
+
+        // The scripted answer is given and the run stops inside the subroutine
+        let sent = adapter.handle(&request("configurationDone", "{}".to_string()));
+        assert_eq!(events(&sent), vec!["output", "stopped"]);
+        assert!(sent[1].to_string().contains("? 21"));
+        assert!(sent[2].to_string().contains(r#""reason":"breakpoint""#));
+        let sent = adapter.handle(&request("stackTrace", "{}".to_string()));
+        let frames = sent[0].at(&["body", "stackFrames"]).unwrap();
+        assert!(matches!(frames, Json::Array(frames) if frames.len() == 2));
+        let sent = adapter.handle(&request("evaluate", r#"{"expression":"n"}"#.to_string()));
+        assert_eq!(
+            sent[0].at(&["body", "result"]).and_then(Json::as_str),
+            Some("21")
+        );
+
+        let sent = adapter.handle(&request("continue", "{}".to_string()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(events(&sent), vec!["output", "exited", "terminated"]);
+        assert!(sent[1].to_string().contains("42"));
+        adapter.handle(&request("disconnect", "{}".to_string()));
+        assert!(adapter.exited());
+    }
+
+    #[test]
+    fn test_web_runtime() {
+        let run = |language: Language, code: &str, answers: &[&str]| {
+            let mut answers = answers.iter().map(|answer| answer.to_string());
+            let mut events = Vec::new();
+            web::run(
+                language,
+                code,
+                10_000,
+                &mut || answers.next(),
+                &mut |event| events.push(event.to_string()),
+            );
+            events
+        };
+
+        // Output comes as it is printed, with the prompt before each answer
+        assert_eq!(
+            run(
+                Language::Basic,
+                "10 INPUT \"Name\"; N$\n20 PRINT \"HI \"; N$\n",
+                &["Ada"]
+            ),
+            vec![r#"{"print":"Name"}"#, r#"{"print":"Ada\nHI Ada\n"}"#]
+        );
+        // Running out of answers, and errors, end the run with an error event
+        let events = run(Language::Basic, "10 INPUT A\n", &[]);
+        assert!(events.last().unwrap().contains("no more"));
+        let events = run(Language::Basic, "10 PRINT 1 / 0\n", &[]);
+        assert!(events.last().unwrap().starts_with(r#"{"error":"E201"#));
+
+        // Turtle commands carry their turtle, name and arguments
+        #[cfg(feature = "logo")]
+        {
+            let events = run(
+                Language::Logo,
+                "SETPENCOLOR [255 0 0]\nFORWARD 50\nLABEL \"Hi",
+                &[],
+            );
+            assert!(events.contains(
+                &r#"{"turtle":"MAIN","command":"SETPENCOLOR","args":[255,0,0]}"#.to_string()
+            ));
+            assert!(events
+                .contains(&r#"{"turtle":"MAIN","command":"FORWARD","args":[50]}"#.to_string()));
+            assert!(events
+                .contains(&r#"{"turtle":"MAIN","command":"LABEL","args":["Hi"]}"#.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_randomize_seed() {
+        use crate::basic::{ExecutionResult, Interpreter};
+
+        let run = |code: &str| match Interpreter::new().execute(code).unwrap() {
+            ExecutionResult::Complete { output, .. } => output,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        let seeded = "10 RANDOMIZE 7\n20 FOR I = 1 TO 5\n30 PRINT RND\n40 NEXT I\n";
+        let first = run(seeded);
+        assert_eq!(first.lines().count(), 5);
+        assert_eq!(run(seeded), first);
+        assert_ne!(run(&seeded.replace("RANDOMIZE 7", "RANDOMIZE 8")), first);
+
+        // Reseeding part way through starts the sequence over
+        let twice = run("10 RANDOMIZE 3: A = RND(1)\n20 RANDOMIZE 3: B = RND\n30 PRINT A = B\n");
+        assert_ne!(twice.trim(), "0");
+
+        // RANDOMIZE TIMER still gives different numbers on different runs
+        let clock = "10 RANDOMIZE TIMER\n20 PRINT RND; RND; RND\n";
+        assert!((0..5)
+            .map(|_| run(clock))
+            .any(|output| output != run(clock)));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use crate::basic::{ErrorCode, Interpreter};
+        use crate::batch;
+
+        let code = |program: &str| Interpreter::new().execute(program).unwrap_err().code();
+        assert_eq!(code("10 PRINT 1 @ 2"), ErrorCode::UnexpectedCharacter);
+        assert_eq!(code("10 PRINT \"HI"), ErrorCode::UnterminatedString);
+        assert_eq!(code("10 IF X > 1 PRINT X"), ErrorCode::ExpectedToken);
+        // A misspelt keyword reads as a variable with no = after it
+        assert_eq!(code("10 PRNT \"HI\""), ErrorCode::UnexpectedToken);
+        assert_eq!(code("10 PRINT 1 / 0"), ErrorCode::DivisionByZero);
+        assert_eq!(code("10 PRINT SIMILAR(\"A\")"), ErrorCode::ArgumentCount);
+        assert_eq!(code("10 GOTO 10"), ErrorCode::ExecutionTimeout);
+        let err = Interpreter::new()
+            .execute("10 PRINT 1 PRINT 2")
+            .unwrap_err();
+        assert_eq!(err.code().code(), "E106");
+        assert_eq!(
+            err.hint(),
+            Some("Put a colon between two statements on the same line")
+        );
+
+        // Errors read as their code, the sentence and where
+        let err = Interpreter::new()
+            .execute("10 PRINT 1\n20 PRINT 1 / 0")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "E201 Division by zero (line 2, column 4)");
+        assert_eq!(
+            InterpreterError::UndefinedVariable("X".to_string()).to_string(),
+            "E204 Undefined variable X"
+        );
+
+        // Codes are stable, and each has its place in the docs
+        let docs = include_str!("../../docs/ERROR_CODES.md");
+        for error in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(error.code()), Some(error));
+            let anchor = format!("<a id=\"{}\"></a>", error.code().to_lowercase());
+            assert!(docs.contains(&anchor), "{} is not documented", error.code());
+            assert!(error
+                .doc_link()
+                .ends_with(&format!("#{}", error.code().to_lowercase())));
+        }
+
+        // The grader's JSON names the code a program stopped with
+        let case = batch::parse_manifest("[divides]\n> 1\n").unwrap();
+        let transcript = batch::run_with_inputs(Language::Basic, "10 PRINT 1 / 0", &[], 1000);
+        let result = batch::check_case(&case[0], &transcript);
+        assert_eq!(result.code, Some(ErrorCode::DivisionByZero));
+        let reports = [batch::ProgramReport {
+            program: "divide.twb".to_string(),
+            cases: vec![result],
+        }];
+        assert!(batch::json_report(&reports).contains("\"code\": \"E201\""));
+    }
+
+    #[test]
+    fn test_dump_ast() {
+        use crate::basic::dump::parse_to_json;
+        use crate::json::Json;
+
+        let ast = parse_to_json("10 FOR I = 1 TO 3\n20 PRINT \"Hi\"; I * 2\n30 NEXT I\n").unwrap();
+        let Some(Json::Array(statements)) = ast.get("statements") else {
+            panic!("no statements: {}", ast);
+        };
+        let first = &statements[0];
+        assert_eq!(first.get("type").and_then(Json::as_str), Some("For"));
+        assert_eq!(first.get("line").and_then(Json::as_usize), Some(1));
+        assert_eq!(first.get("line_number").and_then(Json::as_usize), Some(10));
+        assert_eq!(first.at(&["end", "value"]), Some(&Json::Number(3.0)));
+        let print = ast.to_string();
+        assert!(print.contains(r#"{"type":"String","value":"Hi"}"#));
+        assert!(print.contains(r#""operator":"Multiply""#));
+        assert!(print.contains(r#""separators":["Semicolon","None"]"#));
+
+        // The pretty form is the same JSON
+        assert_eq!(Json::parse(&ast.pretty()), Ok(ast));
+        assert!(parse_to_json("10 PRINT (").is_err());
+    }
+
+    #[test]
+    fn test_detokenize() {
+        use crate::basic::detokenize;
+        // Each line: a link (any non-zero), the line number, tokens, 0
+        let mut program = vec![0xFF];
+        for (number, tokens) in [
+            (10u16, &b"\x91 \"HI\":\x89 \x0E\x0A\x00"[..]),
+            (20, &b"\x8B X \xCD \x0E\x0A\x00 :\xA1 \x0E\x14\x00"[..]),
+            (30, &b"\xB1\xE9 X\xE8\x1C\x2C\x01:\xB2:\x8F\xD9 note"[..]),
+            (
+                40,
+                &b"\x84 1,\"A:B\": X\xE7\x1D\x00\x00\x00\x80\xE9\x12+\x0C\xFF\x00"[..],
+            ),
+        ] {
+            program.extend([1, 1]);
+            program.extend(number.to_le_bytes());
+            program.extend(tokens);
+            program.push(0);
+        }
+        program.extend([0, 0]);
+        let listing = "10 PRINT \"HI\":GOTO 10\n\
+                       20 IF X THEN 10 ELSE 20\n\
+                       30 WHILE X<300:WEND' note\n\
+                       40 DATA 1,\"A:B\": X=.5+1+&HFF\n";
+        assert!(detokenize::is_tokenized(&program));
+        assert!(!detokenize::is_tokenized(b"10 PRINT"));
+        assert_eq!(detokenize::detokenize(&program).as_deref(), Ok(listing));
+
+        // SAVE ,P: encrypt with the inverse of unprotect and get it back
+        let (log_key, exp_key) = (
+            [
+                0x9A, 0xF7, 0x19, 0x83, 0x24, 0x63, 0x43, 0x83, 0x75, 0xCD, 0x8D, 0x84, 0xA9u8,
+            ],
+            [
+                0x7C, 0x88, 0x59, 0x74, 0xE0, 0x97, 0x26, 0x77, 0xC4, 0x1D, 0x1Eu8,
+            ],
+        );
+        let mut protected = vec![0xFE];
+        protected.extend(program[1..].iter().enumerate().map(|(i, &byte)| {
+            let (n1, n2) = (11 - (i % 11) as u8, 13 - (i % 13) as u8);
+            (byte.wrapping_sub(n2) ^ exp_key[n1 as usize - 1] ^ log_key[n2 as usize - 1])
+                .wrapping_add(n1)
+        }));
+        assert_eq!(detokenize::unprotect(&protected[1..]), program[1..]);
+        assert_eq!(detokenize::detokenize(&protected).as_deref(), Ok(listing));
+
+        // Unknown tokens and cut-off lines are errors, not garbage
+        assert!(detokenize::detokenize(&[0xFF, 1, 1, 10, 0, 0xFD, 0x90, 0])
+            .unwrap_err()
+            .contains("FD 90"));
+        assert!(detokenize::detokenize(&[0xFF, 1, 1, 10, 0, 0x91]).is_err());
+    }
+
+    #[test]
+    fn test_qbasic_translation() {
+        use crate::basic::dialect::{translate, Dialect};
+        use crate::basic::{ExecutionResult, Interpreter};
+
+        let program = "DO WHILE n < 3\n    n = n + 1\nLOOP\nIF n = 3 THEN\n    PRINT \"three\"\n\
+                       END IF\nCALL Greet(\"Ada\")\nEND\n\nSUB Greet (who$)\n    PRINT \"Hi \"; who$\n\
+                       END SUB\n";
+        let mut interpreter = Interpreter::new();
+        interpreter.set_dialect(Dialect::QBasic);
+        match interpreter.execute(program).unwrap() {
+            ExecutionResult::Complete { output, .. } => assert_eq!(output, "three\nHi Ada\n"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Every source line keeps its place, whatever it became
+        let translated = translate(program).unwrap();
+        assert_eq!(translated.lines.len(), translated.code.lines().count());
+        assert!(translated.lines.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // Unbalanced blocks and calls are reported at their lines
+        let error = |code: &str| format!("{:?}", translate(code).unwrap_err());
+        assert!(error("DO\nPRINT 1").contains("DO at line 1 has no LOOP"));
+        assert!(error("PRINT 1\nEND IF").contains("END IF without IF at line 2"));
+        assert!(error("IF 1 THEN\nELSE\nELSE\nEND IF").contains("ELSE after ELSE at line 3"));
+        assert!(error("SUB A (x)\nEND SUB\nCALL A(1, 2)").contains("takes 1 argument(s), not 2"));
+        // Numbered lines keep their numbers; blocks are numbered above them
+        let translated = translate("10 DO\n20 GOTO 40\n30 LOOP\n40 PRINT 1").unwrap();
+        assert_eq!(
+            translated.code,
+            "10 REM\n20 GOTO 40\n30 GOTO 10\n40 PRINT 1\n"
+        );
+
+        // A tokenizer error names the line as written
+        let mut interpreter = Interpreter::new();
+        interpreter.set_dialect(Dialect::QBasic);
+        let error = format!(
+            "{:?}",
+            interpreter
+                .load("IF 1 THEN\nPRINT 1\nELSE\nx = @\nEND IF")
+                .unwrap_err()
+        );
+        assert!(error.contains("at line 4"), "{}", error);
+    }
+
+    /// An empty folder in the temp directory for one test's files
+    fn files_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Run a BASIC program to the end with its files kept in `dir`, giving
+    /// its output or the error that stopped it
+    fn run_with_files(dir: &std::path::Path, code: &str) -> Result<String, InterpreterError> {
+        let mut interpreter = basic::Interpreter::new();
+        interpreter.set_files_dir(Some(dir.to_path_buf()));
+        match interpreter.execute(code)? {
+            ExecutionResult::Complete { output, .. } => Ok(output),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sequential_files() {
+        use crate::basic::ErrorCode;
+
+        let dir = files_dir("tw_sequential_files");
+        let program = "10 OPEN \"SCORES.TXT\" FOR OUTPUT AS #1\n\
+                       20 WRITE #1, \"ADA, LOVELACE\", 95\n\
+                       30 PRINT #1, \"BOB\"; 80\n\
+                       40 CLOSE #1\n\
+                       50 OPEN \"SCORES.TXT\" FOR APPEND AS #2\n\
+                       60 PRINT #2, \"CAROL\"\n\
+                       70 CLOSE\n\
+                       80 OPEN \"SCORES.TXT\" FOR INPUT AS #1\n\
+                       90 PRINT LOF(1)\n\
+                       100 INPUT #1, N$, S\n\
+                       110 PRINT N$; \"/\"; S\n\
+                       120 IF EOF(1) THEN GOTO 160\n\
+                       130 LINE INPUT #1, L$\n\
+                       140 PRINT L$\n\
+                       150 GOTO 120\n\
+                       160 CLOSE #1";
+        assert_eq!(
+            run_with_files(&dir, program).unwrap(),
+            "31\nADA, LOVELACE/95\nBOB80\nCAROL\n"
+        );
+        let saved = std::fs::read_to_string(dir.join("SCORES.TXT")).unwrap();
+        assert_eq!(saved, "\"ADA, LOVELACE\",95\nBOB80\nCAROL\n");
+
+        let error = |code: &str| run_with_files(&dir, code).unwrap_err().code();
+        assert_eq!(
+            error(
+                "10 OPEN \"SCORES.TXT\" FOR INPUT AS #1\n20 LINE INPUT #1, A$\n\
+                 30 LINE INPUT #1, A$\n40 LINE INPUT #1, A$\n50 LINE INPUT #1, A$"
+            ),
+            ErrorCode::InputPastEnd
+        );
+        assert_eq!(
+            error("10 OPEN \"../ESCAPE.TXT\" FOR OUTPUT AS #1"),
+            ErrorCode::BadFileName
+        );
+        assert_eq!(error("10 PRINT #3, 1"), ErrorCode::BadFileNumber);
+        assert_eq!(
+            error("10 OPEN \"A.TXT\" FOR OUTPUT AS #1\n20 INPUT #1, A"),
+            ErrorCode::BadFileMode
+        );
+        assert_eq!(
+            run_with_files(
+                &dir,
+                "10 ON ERROR GOTO 40\n20 OPEN \"MISSING.TXT\" FOR INPUT AS #1\n30 END\n\
+                 40 PRINT ERR: RESUME 30"
+            )
+            .unwrap(),
+            "53\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_random_files() {
+        use crate::basic::ErrorCode;
+
+        let dir = files_dir("tw_random_files");
+        let program = "10 OPEN \"BOOK.DAT\" AS #1 LEN=24\n\
+                       20 FIELD #1, 16 AS N$, 6 AS P$, 2 AS A$\n\
+                       30 LSET N$ = \"ADA\": RSET P$ = \"555\": LSET A$ = MKI$(36): PUT #1, 1\n\
+                       40 LSET N$ = \"GRACE HOPPER, ADMIRAL\": RSET P$ = \"12\"\n\
+                       50 LSET A$ = MKI$(-85): PUT #1\n\
+                       60 PRINT LOF(1)\n\
+                       70 GET #1, 2: PRINT N$; \"|\"; P$; \"|\"; CVI(A$)\n\
+                       80 GET #1, 1: PRINT N$; \"|\"; P$; \"|\"; CVI(A$); EOF(1)\n\
+                       90 GET #1, 3: PRINT EOF(1)\n\
+                       100 CLOSE";
+        assert_eq!(
+            run_with_files(&dir, program).unwrap(),
+            "48\nGRACE HOPPER, AD|    12|-85\nADA             |   555|360\n-1\n"
+        );
+        // Records are bytes on disk, two records of 24
+        let saved = std::fs::read(dir.join("BOOK.DAT")).unwrap();
+        assert_eq!(&saved[..16], b"ADA             ");
+        assert_eq!(&saved[22..24], &36i16.to_le_bytes());
+
+        assert_eq!(
+            run_with_files(&dir, "10 PRINT CVS(MKS$(1.5))\n20 PRINT CVD(MKD$(-2.25))").unwrap(),
+            "1.5\n-2.25\n"
+        );
+        let error = |code: &str| run_with_files(&dir, code).unwrap_err().code();
+        assert_eq!(
+            error("10 OPEN \"BOOK.DAT\" AS #1 LEN=24\n20 FIELD #1, 20 AS N$, 10 AS P$"),
+            ErrorCode::FieldOverflow
+        );
+        assert_eq!(
+            error("10 OPEN \"BOOK.DAT\" AS #1 LEN=24\n20 GET #1, 0"),
+            ErrorCode::BadRecordNumber
+        );
+        // Past GW-BASIC's last record is an error, not a panic
+        assert_eq!(
+            error("10 OPEN \"BOOK.DAT\" AS #1 LEN=24\n20 GET #1, 99999999999999999999"),
+            ErrorCode::BadRecordNumber
+        );
+        assert_eq!(
+            error("10 OPEN \"BOOK.DAT\" AS #1 LEN=24\n20 PUT #1, 16777216"),
+            ErrorCode::BadRecordNumber
+        );
+        assert_eq!(
+            error("10 OPEN \"NOTES.TXT\" FOR OUTPUT AS #1\n20 PUT #1"),
+            ErrorCode::BadFileMode
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_management() {
+        use crate::basic::ErrorCode;
+
+        let dir = files_dir("tw_file_management");
+        let program = "10 MKDIR \"SCORES\"\n\
+                       20 OPEN \"SCORES\\A.TXT\" FOR OUTPUT AS #1: PRINT #1, 1: CLOSE\n\
+                       30 OPEN \"NOTES.TXT\" FOR OUTPUT AS #1: CLOSE\n\
+                       40 NAME \"NOTES.TXT\" AS \"OLD.TXT\"\n\
+                       50 FILES\n\
+                       60 CHDIR \"SCORES\": FILES \"*.TXT\"\n\
+                       70 CHDIR \"..\": KILL \"OLD.TXT\": FILES";
+        assert_eq!(
+            run_with_files(&dir, program).unwrap(),
+            "/\nOLD.TXT\nSCORES       <DIR>\n/SCORES/\nA.TXT\n/\nSCORES       <DIR>\n"
+        );
+        assert!(dir.join("SCORES").join("A.TXT").is_file());
+        assert!(!dir.join("OLD.TXT").exists());
+
+        let error = |code: &str| run_with_files(&dir, code).unwrap_err().code();
+        assert_eq!(error("10 CHDIR \"NOPE\""), ErrorCode::PathNotFound);
+        assert_eq!(error("10 CHDIR \"..\""), ErrorCode::BadFileName);
+        assert_eq!(
+            error("10 OPEN \"SCORES/../../X\" FOR OUTPUT AS #1"),
+            ErrorCode::BadFileName
+        );
+        assert_eq!(error("10 KILL \"*.DAT\""), ErrorCode::FileNotFound);
+        assert_eq!(error("10 RMDIR \"SCORES\""), ErrorCode::FileAccess);
+        assert_eq!(
+            error("10 OPEN \"B.TXT\" FOR OUTPUT AS #1: CLOSE\n20 NAME \"B.TXT\" AS \"SCORES\""),
+            ErrorCode::FileAlreadyExists
+        );
+        assert_eq!(
+            error("10 OPEN \"B.TXT\" FOR APPEND AS #1\n20 KILL \"B.TXT\""),
+            ErrorCode::FileAlreadyOpen
+        );
+        assert_eq!(
+            run_with_files(
+                &dir,
+                "10 KILL \"SCORES/*.*\": KILL \"B.TXT\": RMDIR \"SCORES\"\n20 PRINT \"DONE\""
+            )
+            .unwrap(),
+            "DONE\n"
+        );
+        assert!(!dir.join("SCORES").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_error() {
+        use crate::basic::{ErrorCode, Interpreter};
+
+        let run = |code: &str| match Interpreter::new().execute(code) {
+            Ok(ExecutionResult::Complete { output, .. }) => Ok(output),
+            Ok(other) => panic!("unexpected result: {:?}", other),
+            Err(err) => Err(err),
+        };
+        let handled = |resume: &str| {
+            format!(
+                "10 ON ERROR GOTO 100\n20 X = 0\n30 PRINT 10 / X\n40 PRINT \"AFTER\"\n50 END\n\
+                 100 PRINT \"TRAPPED\"\n110 X = 2\n120 {}",
+                resume
+            )
+        };
+
+        // RESUME NEXT carries on after the statement that failed
+        assert_eq!(run(&handled("RESUME NEXT")).unwrap(), "TRAPPED\nAFTER\n");
+        // RESUME runs it again; RESUME n goes to line n
+        assert_eq!(run(&handled("RESUME")).unwrap(), "TRAPPED\n5\nAFTER\n");
+        assert_eq!(run(&handled("RESUME 50")).unwrap(), "TRAPPED\n");
+
+        // An error in the handler, or ON ERROR GOTO 0 there, stops the program
+        let err = run(&handled("PRINT 1 / 0")).unwrap_err();
+        assert!(err.to_string().starts_with("E201 Division by zero (line 8"));
+        let err = run(&handled("ON ERROR GOTO 0")).unwrap_err();
+        assert!(err.to_string().starts_with("E201 Division by zero (line 3"));
+        let err = run("10 RESUME NEXT").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ResumeWithoutError);
+    }
+
+    #[test]
+    fn test_err_and_erl() {
+        use crate::basic::{ErrorCode, Interpreter};
+
+        let run = |code: &str| match Interpreter::new().execute(code).unwrap() {
+            ExecutionResult::Complete { output, .. } => output,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        assert_eq!(
+            run(
+                "10 PRINT ERR; \" \"; ERL\n20 ON ERROR GOTO 100\n30 X = 1\n40 PRINT FNZ(X)\n\
+                 50 RETURN\n60 PRINT 1 / 0 : NEXT I\n70 PRINT \"LAST \"; ERR; \" \"; ERL\n80 END\n\
+                 100 PRINT ERR; \" \"; ERL\n110 RESUME NEXT"
+            ),
+            "0 0\n18 40\n3 50\n11 60\n1 60\nLAST 1 60\n"
+        );
+
+        // ERR gives GW-BASIC's numbers; the codes keep their own
+        assert_eq!(ErrorCode::ReturnWithoutGosub.code(), "E206");
+        assert_eq!(ErrorCode::UnexpectedToken.gw_basic_number(), 2);
+        let err = Interpreter::new().execute("10 RETURN").unwrap_err();
+        assert!(err.to_string().contains("RETURN without GOSUB"));
+
+        // ERROR n raises error n, for the handler to find in ERR
+        assert_eq!(
+            run("10 ON ERROR GOTO 100\n20 ERROR 11\n30 ERROR 200\n40 END\n\
+                 100 PRINT ERR; \" \"; ERL\n110 RESUME NEXT"),
+            "11 20\n200 30\n"
+        );
+        let err = |code: &str| Interpreter::new().execute(code).unwrap_err();
+        assert_eq!(err("10 ERROR 11").code(), ErrorCode::DivisionByZero);
+        assert!(err("10 ERROR 200")
+            .to_string()
+            .contains("Unprintable error 200"));
+        assert!(err("10 ERROR 0").to_string().contains("from 1 to 255"));
+    }
+
+    #[test]
+    fn test_undefined_line_number() {
+        use crate::basic::{ErrorCode, Interpreter};
+
+        // A jump to a line the program doesn't have stops it there
+        let code = |program: &str| Interpreter::new().execute(program).unwrap_err();
+        let err = code("10 PRINT \"A\"\n20 GOTO 3\n30 PRINT \"B\"");
+        assert_eq!(err.code(), ErrorCode::UndefinedLineNumber);
+        assert!(err.to_string().contains("Undefined line number 3"));
+        let err = code("10 GOSUB 500\n20 END");
+        assert!(err.to_string().contains("Undefined line number 500"));
+
+        // ON ERROR GOTO traps it as GW-BASIC's error 8
+        match Interpreter::new()
+            .execute(
+                "10 ON ERROR GOTO 100\n20 GOTO 75\n30 END\n100 PRINT ERR; \" \"; ERL\n110 RESUME NEXT",
+            )
+            .unwrap()
+        {
+            ExecutionResult::Complete { output, .. } => assert_eq!(output, "8 20\n"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        use crate::basic::{ErrorCode, Interpreter};
+
+        let run = |code: &str| match Interpreter::new().execute(code) {
+            Ok(ExecutionResult::Complete { output, .. }) => Ok(output),
+            Ok(other) => panic!("unexpected result: {:?}", other),
+            Err(err) => Err(err),
+        };
+
+        // &H and &O numbers are 16-bit, as in GW-BASIC
+        assert_eq!(
+            run("10 PRINT &HFF\n20 PRINT &H8000\n30 PRINT &O17").unwrap(),
+            "255\n-32768\n15\n"
+        );
+        assert_eq!(
+            run("10 PRINT &H10000").unwrap_err().code(),
+            ErrorCode::InvalidNumber
+        );
+
+        // Bytes keep what was POKEd, in the segment DEF SEG chose
+        assert_eq!(
+            run("10 DEF SEG = &HB800: POKE 0, 65: PRINT PEEK(0)\n20 DEF SEG: PRINT PEEK(0)")
+                .unwrap(),
+            "65\n0\n"
+        );
+        assert_eq!(run("10 POKE -1, 9: PRINT PEEK(65535)").unwrap(), "9\n");
+        assert!(run("10 POKE 0, 256")
+            .unwrap_err()
+            .to_string()
+            .contains("POKE stores 0 to 255"));
+        assert!(run("10 PRINT PEEK(70000)")
+            .unwrap_err()
+            .to_string()
+            .contains("PEEK takes -32768 to 65535"));
+
+        // Magic addresses: the screen width, the clock and the RND seed
+        assert_eq!(run("10 DEF SEG = &H40: PRINT PEEK(&H4A)").unwrap(), "80\n");
+        assert_eq!(
+            run("10 POKE &H46C, 1: POKE &H44A, 1: PRINT PEEK(&H44A)").unwrap(),
+            "80\n"
+        );
+        assert_eq!(
+            run("10 RANDOMIZE 5: PRINT PEEK(&H4F0)\n\
+                 20 A = RND: POKE &H4F0, 5: POKE &H4F1, 0: POKE &H4F2, 0\n\
+                 30 PRINT A = RND")
+            .unwrap(),
+            "5\n-1\n"
+        );
+        assert_eq!(run("10 POKE 53281, 6: PRINT PEEK(53281)").unwrap(), "6\n");
+    }
+}
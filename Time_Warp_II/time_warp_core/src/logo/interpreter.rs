@@ -0,0 +1,1011 @@
+use super::tokenizer::{self, format_number, Item};
+use crate::basic::interpreter::PEN_PALETTE;
+use crate::basic::{GraphicsCommand, InterpreterError, TurtleCommand};
+use std::collections::HashMap;
+
+/// Deepest procedure nesting before a runaway recursion is stopped
+const MAX_DEPTH: usize = 1000;
+
+/// Stack for the thread a program runs on; enough for `MAX_DEPTH` levels of
+/// procedures nested inside REPEAT and IF, even in a debug build
+#[cfg(not(target_family = "wasm"))]
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Logo headings count clockwise from straight up; the canvas counts from
+/// pointing right, so every heading sent to it is turned by this much
+const HEADING_OFFSET: f32 = -90.0;
+
+/// A Logo datum: a number, a word or a list
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Word(String),
+    List(Vec<Item>),
+}
+
+impl Value {
+    fn from_item(item: &Item) -> Self {
+        match item {
+            Item::Number(number) => Value::Number(*number),
+            Item::List(items) => Value::List(items.clone()),
+            Item::Word(word) => Value::Word(word.clone()),
+            other => Value::Word(other.to_string()),
+        }
+    }
+
+    fn to_item(&self) -> Item {
+        match self {
+            Value::Number(number) => Item::Number(*number),
+            Value::Word(word) => Item::Word(word.clone()),
+            Value::List(items) => Item::List(items.clone()),
+        }
+    }
+
+    fn boolean(value: bool) -> Self {
+        Value::Word(if value { "TRUE" } else { "FALSE" }.to_string())
+    }
+
+    /// How SHOW writes a value: lists keep their brackets
+    fn show(&self) -> String {
+        match self {
+            Value::List(items) => format!("[{}]", tokenizer::join(items)),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// How PRINT writes a value: the outer brackets of a list are left off
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", format_number(*number)),
+            Value::Word(word) => write!(f, "{}", word),
+            Value::List(items) => write!(f, "{}", tokenizer::join(items)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Procedure {
+    inputs: Vec<String>,
+    body: Vec<Item>,
+}
+
+/// Why evaluation is unwinding: a real error, or STOP / OUTPUT leaving a
+/// procedure early
+enum Unwind {
+    Error(InterpreterError),
+    Stop,
+    Output(Value),
+}
+
+impl From<InterpreterError> for Unwind {
+    fn from(error: InterpreterError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+type Eval<T> = Result<T, Unwind>;
+
+fn error<T>(message: String) -> Eval<T> {
+    Err(Unwind::Error(InterpreterError::RuntimeError(message)))
+}
+
+/// Where the turtle is, in Logo's coordinates (y up, heading 0 = up)
+#[derive(Debug, Clone, Default)]
+struct Turtle {
+    x: f64,
+    y: f64,
+    heading: f64,
+    pen_up: bool,
+}
+
+/// Logo interpreter engine
+pub struct Interpreter {
+    pub max_instructions: usize,
+    pub output: String,
+    pub graphics_commands: Vec<TurtleCommand>,
+    procedures: HashMap<String, Procedure>,
+    globals: HashMap<String, Value>,
+    locals: Vec<HashMap<String, Value>>, // one frame per running procedure
+    repcounts: Vec<usize>,
+    instructions: usize,
+    turtle: Turtle,
+    random_seed: u64,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            max_instructions: 100_000,
+            output: String::new(),
+            graphics_commands: Vec::new(),
+            procedures: HashMap::new(),
+            globals: HashMap::new(),
+            locals: Vec::new(),
+            repcounts: Vec::new(),
+            instructions: 0,
+            turtle: Turtle::default(),
+            random_seed: 12345,
+        }
+    }
+
+    pub fn instruction_count(&self) -> usize {
+        self.instructions
+    }
+
+    /// Run a whole program. Procedures are defined first, so they can be
+    /// called from anywhere in the file. Output and turtle commands produced
+    /// before an error are kept.
+    pub fn execute(&mut self, source: &str) -> Result<(), InterpreterError> {
+        let items = tokenizer::tokenize(source)?;
+        let main = self.define_procedures(items)?;
+        // Start from home so XCOR and friends match the canvas. The canvas
+        // turtle faces right there; a Logo turtle faces up.
+        self.emit(GraphicsCommand::Home);
+        self.emit(GraphicsCommand::SetHeading(HEADING_OFFSET));
+
+        // Each procedure level nests many Rust calls, so the program gets a
+        // stack of its own instead of the caller's. WebAssembly has no
+        // threads; the web runtime is linked with a big stack instead.
+        #[cfg(target_family = "wasm")]
+        let outcome = std::io::Result::Ok(std::thread::Result::Ok(self.run_list(&main)));
+        #[cfg(not(target_family = "wasm"))]
+        let outcome = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .name("logo".to_string())
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, || self.run_list(&main))
+                .map(|thread| thread.join())
+        });
+        let result = match outcome {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => {
+                return Err(InterpreterError::RuntimeError(
+                    "The Logo program crashed".to_string(),
+                ))
+            }
+            Err(error) => return Err(InterpreterError::RuntimeError(error.to_string())),
+        };
+
+        match result {
+            Ok(None) | Err(Unwind::Stop) => Ok(()),
+            Ok(Some(value)) => Err(dont_say(&value)),
+            Err(Unwind::Output(_)) => Err(InterpreterError::RuntimeError(
+                "OUTPUT can only be used inside a procedure".to_string(),
+            )),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+
+    /// Take the TO…END definitions out of `items`, leaving the instructions
+    /// to run
+    fn define_procedures(&mut self, items: Vec<Item>) -> Result<Vec<Item>, InterpreterError> {
+        let mut main = Vec::new();
+        let mut items = items.into_iter().peekable();
+        while let Some(item) = items.next() {
+            if !is_word(&item, "TO") {
+                main.push(item);
+                continue;
+            }
+            let name = match items.next() {
+                Some(Item::Word(name)) => name.to_ascii_uppercase(),
+                _ => {
+                    return Err(InterpreterError::syntax(
+                        "TO needs a procedure name".to_string(),
+                    ))
+                }
+            };
+            let mut inputs = Vec::new();
+            while let Some(Item::Variable(input)) = items.peek() {
+                inputs.push(input.to_ascii_uppercase());
+                items.next();
+            }
+            let mut body = Vec::new();
+            loop {
+                match items.next() {
+                    Some(item) if is_word(&item, "END") => break,
+                    Some(item) => body.push(item),
+                    None => {
+                        return Err(InterpreterError::syntax(format!(
+                            "TO {} is missing its END",
+                            name
+                        )))
+                    }
+                }
+            }
+            self.procedures.insert(name, Procedure { inputs, body });
+        }
+        Ok(main)
+    }
+
+    fn emit(&mut self, command: GraphicsCommand) {
+        self.graphics_commands.push(command.into());
+    }
+
+    /// Run a list of instructions. The last one may output a value, which
+    /// becomes the list's value (for IF and IFELSE used as operations).
+    fn run_list(&mut self, items: &[Item]) -> Eval<Option<Value>> {
+        let mut position = 0;
+        let mut last = None;
+        while position < items.len() {
+            if let Some(value) = last.take() {
+                return Err(dont_say(&value).into());
+            }
+            last = self.expression(items, &mut position)?;
+        }
+        Ok(last)
+    }
+
+    /// Comparisons bind loosest, then + and -, then * and /
+    fn expression(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let left = self.additive(items, position)?;
+        let Some(Item::Operator(op)) = items.get(*position) else {
+            return Ok(left);
+        };
+        if !matches!(op.as_str(), "=" | "<" | ">" | "<=" | ">=" | "<>") {
+            return Ok(left);
+        }
+        *position += 1;
+        let left = operand(left, op)?;
+        let right = self.additive(items, position)?;
+        let right = operand(right, op)?;
+        let result = match op.as_str() {
+            "=" => equal(&left, &right),
+            "<>" => !equal(&left, &right),
+            _ => {
+                let (a, b) = (number(&left, op)?, number(&right, op)?);
+                match op.as_str() {
+                    "<" => a < b,
+                    ">" => a > b,
+                    "<=" => a <= b,
+                    _ => a >= b,
+                }
+            }
+        };
+        Ok(Some(Value::boolean(result)))
+    }
+
+    fn additive(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let mut left = self.multiplicative(items, position)?;
+        while let Some(Item::Operator(op)) = items.get(*position) {
+            if op != "+" && op != "-" {
+                break;
+            }
+            *position += 1;
+            let a = number(&operand(left, op)?, op)?;
+            let right = self.multiplicative(items, position)?;
+            let b = number(&operand(right, op)?, op)?;
+            left = Some(Value::Number(if op == "+" { a + b } else { a - b }));
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let mut left = self.unary(items, position)?;
+        while let Some(Item::Operator(op)) = items.get(*position) {
+            if op != "*" && op != "/" {
+                break;
+            }
+            *position += 1;
+            let a = number(&operand(left, op)?, op)?;
+            let right = self.unary(items, position)?;
+            let b = number(&operand(right, op)?, op)?;
+            if op == "/" && b == 0.0 {
+                return Err(InterpreterError::DivisionByZero.into());
+            }
+            left = Some(Value::Number(if op == "*" { a * b } else { a / b }));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        if let Some(Item::Operator(op)) = items.get(*position) {
+            if op == "-" {
+                *position += 1;
+                let value = self.unary(items, position)?;
+                let value = number(&operand(value, "-")?, "-")?;
+                return Ok(Some(Value::Number(-value)));
+            }
+        }
+        self.primary(items, position)
+    }
+
+    fn primary(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let Some(item) = items.get(*position) else {
+            return error("Unexpected end of instruction".to_string());
+        };
+        *position += 1;
+        match item {
+            Item::Number(number) => Ok(Some(Value::Number(*number))),
+            Item::Quoted(word) => Ok(Some(Value::Word(word.clone()))),
+            Item::Variable(name) => Ok(Some(self.variable(name)?)),
+            Item::List(list) => Ok(Some(Value::List(list.clone()))),
+            Item::Open => {
+                let value = self.expression(items, position)?;
+                match items.get(*position) {
+                    Some(Item::Close) => {
+                        *position += 1;
+                        Ok(value)
+                    }
+                    _ => error("A ( is missing its closing )".to_string()),
+                }
+            }
+            Item::Close => error("Unexpected )".to_string()),
+            Item::Operator(op) => error(format!("Unexpected {}", op)),
+            Item::Word(name) => self.call(&name.to_ascii_uppercase(), items, position),
+        }
+    }
+
+    /// Run a procedure or primitive, reading its inputs from `items`
+    fn call(&mut self, name: &str, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        self.instructions += 1;
+        if self.instructions > self.max_instructions {
+            return error(format!(
+                "Stopped after {} instructions; the program may be stuck in a loop",
+                self.max_instructions
+            ));
+        }
+
+        let Some(procedure) = self.procedures.get(name).cloned() else {
+            return self.primitive(name, items, position);
+        };
+        if self.locals.len() >= MAX_DEPTH {
+            return error(format!(
+                "{} went more than {} procedures deep",
+                name, MAX_DEPTH
+            ));
+        }
+        let mut frame = HashMap::new();
+        for input in &procedure.inputs {
+            let value = self.input(items, position, name)?;
+            frame.insert(input.clone(), value);
+        }
+
+        self.locals.push(frame);
+        let result = self.run_list(&procedure.body);
+        self.locals.pop();
+        match result {
+            Ok(None) | Err(Unwind::Stop) => Ok(None),
+            Ok(Some(value)) => Err(dont_say(&value).into()),
+            Err(Unwind::Output(value)) => Ok(Some(value)),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn primitive(
+        &mut self,
+        name: &str,
+        items: &[Item],
+        position: &mut usize,
+    ) -> Eval<Option<Value>> {
+        // Split by topic so no single stack frame gets large in deep recursion
+        if let Some(result) = self.turtle_primitive(name, items, position) {
+            return result;
+        }
+        if let Some(result) = self.control_primitive(name, items, position) {
+            return result;
+        }
+        if let Some(result) = self.math_primitive(name, items, position) {
+            return result;
+        }
+        if let Some(result) = self.word_primitive(name, items, position) {
+            return result;
+        }
+        error(format!("I don't know how to {}", name))
+    }
+
+    fn turtle_primitive(
+        &mut self,
+        name: &str,
+        items: &[Item],
+        position: &mut usize,
+    ) -> Option<Eval<Option<Value>>> {
+        // The outer `None` means `name` isn't a turtle word
+        let result = (|| -> Eval<Option<Option<Value>>> {
+            match name {
+                "FORWARD" | "FD" => {
+                    let distance = self.number_input(items, position, name)?;
+                    self.forward(distance);
+                }
+                "BACK" | "BK" => {
+                    let distance = self.number_input(items, position, name)?;
+                    self.forward(-distance);
+                }
+                "LEFT" | "LT" => {
+                    let angle = self.number_input(items, position, name)?;
+                    self.turtle.heading = (self.turtle.heading - angle).rem_euclid(360.0);
+                    self.emit(GraphicsCommand::Left(angle as f32));
+                }
+                "RIGHT" | "RT" => {
+                    let angle = self.number_input(items, position, name)?;
+                    self.turtle.heading = (self.turtle.heading + angle).rem_euclid(360.0);
+                    self.emit(GraphicsCommand::Right(angle as f32));
+                }
+                "PENUP" | "PU" => self.turtle.pen_up = true,
+                "PENDOWN" | "PD" => self.turtle.pen_up = false,
+                "HOME" => {
+                    self.emit(GraphicsCommand::Home);
+                    self.emit(GraphicsCommand::SetHeading(HEADING_OFFSET));
+                    self.turtle = Turtle {
+                        pen_up: self.turtle.pen_up,
+                        ..Turtle::default()
+                    };
+                }
+                "CLEARSCREEN" | "CS" => {
+                    self.emit(GraphicsCommand::ClearScreen);
+                    self.emit(GraphicsCommand::SetHeading(HEADING_OFFSET));
+                    self.turtle = Turtle {
+                        pen_up: self.turtle.pen_up,
+                        ..Turtle::default()
+                    };
+                }
+                "SETXY" => {
+                    let x = self.number_input(items, position, name)?;
+                    let y = self.number_input(items, position, name)?;
+                    self.set_position(x, y);
+                }
+                "SETX" => {
+                    let x = self.number_input(items, position, name)?;
+                    self.set_position(x, self.turtle.y);
+                }
+                "SETY" => {
+                    let y = self.number_input(items, position, name)?;
+                    self.set_position(self.turtle.x, y);
+                }
+                "SETHEADING" | "SETH" => {
+                    let heading = self.number_input(items, position, name)?;
+                    self.turtle.heading = heading.rem_euclid(360.0);
+                    self.emit(GraphicsCommand::SetHeading(heading as f32 + HEADING_OFFSET));
+                }
+                "SETPENCOLOR" | "SETPC" => {
+                    let (r, g, b) = self.color_input(items, position, name)?;
+                    self.emit(GraphicsCommand::SetPenColor(r, g, b));
+                }
+                "SETBACKGROUND" | "SETBG" => {
+                    let (r, g, b) = self.color_input(items, position, name)?;
+                    self.emit(GraphicsCommand::SetBackground(r, g, b));
+                }
+                "SETPENSIZE" | "PENSIZE" => {
+                    let size = self.positive_input(items, position, name)?;
+                    self.emit(GraphicsCommand::SetPenSize(size as f32));
+                }
+                "HIDETURTLE" | "HT" => self.emit(GraphicsCommand::ShowTurtle(false)),
+                "SHOWTURTLE" | "ST" => self.emit(GraphicsCommand::ShowTurtle(true)),
+                "ARC" => {
+                    let angle = self.number_input(items, position, name)?;
+                    let radius = self.positive_input(items, position, name)?;
+                    if !self.turtle.pen_up {
+                        self.emit(GraphicsCommand::Arc(radius as f32, angle as f32));
+                    }
+                }
+                "CIRCLE" => {
+                    let radius = self.positive_input(items, position, name)?;
+                    if !self.turtle.pen_up {
+                        self.emit(GraphicsCommand::Arc(radius as f32, 360.0));
+                    }
+                }
+                "FILL" => self.emit(GraphicsCommand::Fill),
+                "LABEL" => {
+                    let text = self.input(items, position, name)?;
+                    self.emit(GraphicsCommand::Label(text.to_string()));
+                }
+                "SETLABELHEIGHT" => {
+                    let size = self.positive_input(items, position, name)?;
+                    self.emit(GraphicsCommand::SetLabelSize(size as f32));
+                }
+                "XCOR" => return Ok(Some(Some(Value::Number(self.turtle.x)))),
+                "YCOR" => return Ok(Some(Some(Value::Number(self.turtle.y)))),
+                "HEADING" => return Ok(Some(Some(Value::Number(self.turtle.heading)))),
+                "PENDOWNP" => return Ok(Some(Some(Value::boolean(!self.turtle.pen_up)))),
+                _ => return Ok(None),
+            }
+            Ok(Some(None))
+        })();
+        result.transpose()
+    }
+
+    fn control_primitive(
+        &mut self,
+        name: &str,
+        items: &[Item],
+        position: &mut usize,
+    ) -> Option<Eval<Option<Value>>> {
+        let result = match name {
+            "PRINT" | "PR" => self.input(items, position, name).map(|value| {
+                self.output.push_str(&format!("{}\n", value));
+                None
+            }),
+            "SHOW" => self.input(items, position, name).map(|value| {
+                self.output.push_str(&format!("{}\n", value.show()));
+                None
+            }),
+            "TYPE" => self.input(items, position, name).map(|value| {
+                self.output.push_str(&value.to_string());
+                None
+            }),
+            "MAKE" => self.make(items, position),
+            "LOCAL" => self.word_input(items, position, name).and_then(|variable| {
+                match self.locals.last_mut() {
+                    Some(frame) => {
+                        frame.insert(variable.to_ascii_uppercase(), Value::List(Vec::new()));
+                        Ok(None)
+                    }
+                    None => error("LOCAL can only be used inside a procedure".to_string()),
+                }
+            }),
+            "THING" => self
+                .word_input(items, position, name)
+                .and_then(|variable| self.variable(&variable).map(Some)),
+            "REPEAT" => self.repeat(items, position),
+            "REPCOUNT" => Ok(Some(Value::Number(
+                self.repcounts.last().map_or(-1.0, |count| *count as f64),
+            ))),
+            "FOREVER" => self
+                .list_input(items, position, name)
+                .and_then(|body| loop {
+                    if let Some(value) = self.run_list(&body)? {
+                        return Err(dont_say(&value).into());
+                    }
+                }),
+            "IF" => self.if_then(items, position),
+            "IFELSE" => self.if_else(items, position),
+            "STOP" => Err(Unwind::Stop),
+            "OUTPUT" | "OP" => self
+                .input(items, position, name)
+                .and_then(|value| Err(Unwind::Output(value))),
+            "RUN" => self
+                .list_input(items, position, name)
+                .and_then(|list| self.run_list(&list)),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn math_primitive(
+        &mut self,
+        name: &str,
+        items: &[Item],
+        position: &mut usize,
+    ) -> Option<Eval<Option<Value>>> {
+        let two = |a: f64, b: f64| -> Option<f64> {
+            match name {
+                "SUM" => Some(a + b),
+                "DIFFERENCE" => Some(a - b),
+                "PRODUCT" => Some(a * b),
+                "POWER" => Some(a.powf(b)),
+                _ => None,
+            }
+        };
+        let one = |a: f64| -> Option<f64> {
+            match name {
+                "MINUS" => Some(-a),
+                "ABS" => Some(a.abs()),
+                "INT" => Some(a.trunc()),
+                "ROUND" => Some(a.round()),
+                "SIN" => Some(a.to_radians().sin()),
+                "COS" => Some(a.to_radians().cos()),
+                "TAN" => Some(a.to_radians().tan()),
+                "ARCTAN" => Some(a.atan().to_degrees()),
+                _ => None,
+            }
+        };
+
+        let result = if two(0.0, 0.0).is_some() {
+            self.two_numbers(items, position, name)
+                .map(|(a, b)| Some(Value::Number(two(a, b).unwrap_or_default())))
+        } else if one(0.0).is_some() {
+            self.number_input(items, position, name)
+                .map(|a| Some(Value::Number(one(a).unwrap_or_default())))
+        } else {
+            match name {
+                "QUOTIENT" | "REMAINDER" => {
+                    self.two_numbers(items, position, name).and_then(|(a, b)| {
+                        if b == 0.0 {
+                            return Err(InterpreterError::DivisionByZero.into());
+                        }
+                        let result = if name == "QUOTIENT" { a / b } else { a % b };
+                        Ok(Some(Value::Number(result)))
+                    })
+                }
+                "SQRT" => self.number_input(items, position, name).and_then(|a| {
+                    if a < 0.0 {
+                        return error(format!("SQRT doesn't like {} as input", format_number(a)));
+                    }
+                    Ok(Some(Value::Number(a.sqrt())))
+                }),
+                "RANDOM" => self.positive_input(items, position, name).map(|limit| {
+                    // Same generator as TW BASIC's RND
+                    self.random_seed = (self.random_seed * 9301 + 49297) % 233280;
+                    let fraction = self.random_seed as f64 / 233280.0;
+                    Some(Value::Number((fraction * limit.floor()).floor()))
+                }),
+                "LESSP" | "GREATERP" => self.two_numbers(items, position, name).map(|(a, b)| {
+                    Some(Value::boolean(if name == "LESSP" { a < b } else { a > b }))
+                }),
+                "AND" | "OR" => self.input(items, position, name).and_then(|a| {
+                    let a = boolean(&a, name)?;
+                    let b = self.input(items, position, name)?;
+                    let b = boolean(&b, name)?;
+                    Ok(Some(Value::boolean(if name == "AND" {
+                        a && b
+                    } else {
+                        a || b
+                    })))
+                }),
+                "NOT" => self
+                    .input(items, position, name)
+                    .and_then(|a| Ok(Some(Value::boolean(!boolean(&a, name)?)))),
+                "TRUE" => Ok(Some(Value::boolean(true))),
+                "FALSE" => Ok(Some(Value::boolean(false))),
+                _ => return None,
+            }
+        };
+        Some(result)
+    }
+
+    fn word_primitive(
+        &mut self,
+        name: &str,
+        items: &[Item],
+        position: &mut usize,
+    ) -> Option<Eval<Option<Value>>> {
+        let result = match name {
+            "FIRST" | "LAST" | "BUTFIRST" | "BF" | "BUTLAST" | "BL" => self
+                .input(items, position, name)
+                .and_then(|thing| piece(name, &thing).map(Some)),
+            "COUNT" => self.input(items, position, name).map(|thing| {
+                let count = match &thing {
+                    Value::List(items) => items.len(),
+                    word => word.to_string().chars().count(),
+                };
+                Some(Value::Number(count as f64))
+            }),
+            "ITEM" => self.number_input(items, position, name).and_then(|index| {
+                let thing = self.input(items, position, name)?;
+                let elements = elements(&thing);
+                match elements.get((index as usize).wrapping_sub(1)) {
+                    Some(element) => Ok(Some(element.clone())),
+                    None => error(format!(
+                        "ITEM doesn't like {} as input",
+                        format_number(index)
+                    )),
+                }
+            }),
+            "WORD" => self.input(items, position, name).and_then(|a| {
+                let b = self.input(items, position, name)?;
+                if matches!(a, Value::List(_)) || matches!(b, Value::List(_)) {
+                    return error("WORD doesn't like a list as input".to_string());
+                }
+                Ok(Some(Value::Word(format!("{}{}", a, b))))
+            }),
+            "LIST" => self.input(items, position, name).and_then(|a| {
+                let b = self.input(items, position, name)?;
+                Ok(Some(Value::List(vec![a.to_item(), b.to_item()])))
+            }),
+            "SENTENCE" | "SE" => self.input(items, position, name).and_then(|a| {
+                let b = self.input(items, position, name)?;
+                let mut joined = flatten(&a);
+                joined.extend(flatten(&b));
+                Ok(Some(Value::List(joined)))
+            }),
+            "FPUT" | "LPUT" => self.input(items, position, name).and_then(|thing| {
+                let mut list = self.list_input(items, position, name)?;
+                if name == "FPUT" {
+                    list.insert(0, thing.to_item());
+                } else {
+                    list.push(thing.to_item());
+                }
+                Ok(Some(Value::List(list)))
+            }),
+            "EMPTYP" => self.input(items, position, name).map(|thing| {
+                Some(Value::boolean(match &thing {
+                    Value::List(items) => items.is_empty(),
+                    word => word.to_string().is_empty(),
+                }))
+            }),
+            "MEMBERP" => self.input(items, position, name).and_then(|thing| {
+                let within = self.input(items, position, name)?;
+                let found = elements(&within)
+                    .iter()
+                    .any(|element| equal(element, &thing));
+                Ok(Some(Value::boolean(found)))
+            }),
+            "EQUALP" => self.input(items, position, name).and_then(|a| {
+                let b = self.input(items, position, name)?;
+                Ok(Some(Value::boolean(equal(&a, &b))))
+            }),
+            "NUMBERP" => self
+                .input(items, position, name)
+                .map(|thing| Some(Value::boolean(to_number(&thing).is_some()))),
+            "WORDP" => self
+                .input(items, position, name)
+                .map(|thing| Some(Value::boolean(!matches!(thing, Value::List(_))))),
+            "LISTP" => self
+                .input(items, position, name)
+                .map(|thing| Some(Value::boolean(matches!(thing, Value::List(_))))),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn make(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let variable = self
+            .word_input(items, position, "MAKE")?
+            .to_ascii_uppercase();
+        let value = self.input(items, position, "MAKE")?;
+        // Assign to the innermost procedure that has the variable, else globally
+        match self
+            .locals
+            .iter_mut()
+            .rev()
+            .find(|frame| frame.contains_key(&variable))
+        {
+            Some(frame) => frame.insert(variable, value),
+            None => self.globals.insert(variable, value),
+        };
+        Ok(None)
+    }
+
+    fn repeat(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let count = self.number_input(items, position, "REPEAT")?;
+        let body = self.list_input(items, position, "REPEAT")?;
+        for i in 1..=(count.max(0.0) as usize) {
+            self.repcounts.push(i);
+            let result = self.run_list(&body);
+            self.repcounts.pop();
+            if let Some(value) = result? {
+                return Err(dont_say(&value).into());
+            }
+        }
+        Ok(None)
+    }
+
+    fn if_then(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let condition = self.input(items, position, "IF")?;
+        let condition = boolean(&condition, "IF")?;
+        let body = self.list_input(items, position, "IF")?;
+        if condition {
+            self.run_list(&body)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn if_else(&mut self, items: &[Item], position: &mut usize) -> Eval<Option<Value>> {
+        let condition = self.input(items, position, "IFELSE")?;
+        let condition = boolean(&condition, "IFELSE")?;
+        let when_true = self.list_input(items, position, "IFELSE")?;
+        let when_false = self.list_input(items, position, "IFELSE")?;
+        self.run_list(if condition { &when_true } else { &when_false })
+    }
+
+    /// Move along the heading, drawing unless the pen is up
+    fn forward(&mut self, distance: f64) {
+        let heading = self.turtle.heading.to_radians();
+        self.turtle.x += distance * heading.sin();
+        self.turtle.y += distance * heading.cos();
+        let distance = distance as f32;
+        self.emit(if self.turtle.pen_up {
+            GraphicsCommand::Move(distance)
+        } else {
+            GraphicsCommand::Forward(distance)
+        });
+    }
+
+    /// Logo's y axis points up, the canvas's down
+    fn set_position(&mut self, x: f64, y: f64) {
+        self.turtle.x = x;
+        self.turtle.y = y;
+        let (x, y) = (x as f32, -y as f32);
+        self.emit(if self.turtle.pen_up {
+            GraphicsCommand::MoveTo(x, y)
+        } else {
+            GraphicsCommand::SetXY(x, y)
+        });
+    }
+
+    fn variable(&self, name: &str) -> Eval<Value> {
+        let key = name.to_ascii_uppercase();
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|frame| frame.get(&key))
+            .or_else(|| self.globals.get(&key))
+            .cloned()
+            .ok_or_else(|| {
+                Unwind::Error(InterpreterError::UndefinedVariable(format!(
+                    "{} has no value",
+                    name
+                )))
+            })
+    }
+
+    /// The next input to `name`, which must output something
+    fn input(&mut self, items: &[Item], position: &mut usize, name: &str) -> Eval<Value> {
+        if *position >= items.len() || items[*position] == Item::Close {
+            return error(format!("Not enough inputs to {}", name));
+        }
+        match self.expression(items, position)? {
+            Some(value) => Ok(value),
+            None => error(format!("{} needs an input that outputs a value", name)),
+        }
+    }
+
+    fn number_input(&mut self, items: &[Item], position: &mut usize, name: &str) -> Eval<f64> {
+        let value = self.input(items, position, name)?;
+        number(&value, name)
+    }
+
+    fn positive_input(&mut self, items: &[Item], position: &mut usize, name: &str) -> Eval<f64> {
+        let value = self.number_input(items, position, name)?;
+        if value <= 0.0 {
+            return error(format!(
+                "{} doesn't like {} as input",
+                name,
+                format_number(value)
+            ));
+        }
+        Ok(value)
+    }
+
+    fn two_numbers(
+        &mut self,
+        items: &[Item],
+        position: &mut usize,
+        name: &str,
+    ) -> Eval<(f64, f64)> {
+        let a = self.number_input(items, position, name)?;
+        let b = self.number_input(items, position, name)?;
+        Ok((a, b))
+    }
+
+    fn word_input(&mut self, items: &[Item], position: &mut usize, name: &str) -> Eval<String> {
+        match self.input(items, position, name)? {
+            Value::List(_) => error(format!("{} doesn't like a list as input", name)),
+            word => Ok(word.to_string()),
+        }
+    }
+
+    fn list_input(&mut self, items: &[Item], position: &mut usize, name: &str) -> Eval<Vec<Item>> {
+        match self.input(items, position, name)? {
+            Value::List(list) => Ok(list),
+            other => error(format!("{} doesn't like {} as input", name, other.show())),
+        }
+    }
+
+    /// A palette number, or a [red green blue] list
+    fn color_input(
+        &mut self,
+        items: &[Item],
+        position: &mut usize,
+        name: &str,
+    ) -> Eval<(u8, u8, u8)> {
+        let value = self.input(items, position, name)?;
+        if let Value::List(list) = &value {
+            let channels: Option<Vec<f64>> = list
+                .iter()
+                .map(|item| to_number(&Value::from_item(item)))
+                .collect();
+            if let Some([r, g, b]) = channels.as_deref() {
+                let channel = |c: f64| c.clamp(0.0, 255.0) as u8;
+                return Ok((channel(*r), channel(*g), channel(*b)));
+            }
+        } else if let Some(index) = to_number(&value) {
+            if let Some(color) = PEN_PALETTE.get(index as usize).filter(|_| index >= 0.0) {
+                return Ok(*color);
+            }
+        }
+        error(format!(
+            "{} needs a colour number from 0 to {} or a [red green blue] list",
+            name,
+            PEN_PALETTE.len() - 1
+        ))
+    }
+}
+
+fn is_word(item: &Item, word: &str) -> bool {
+    matches!(item, Item::Word(w) if w.eq_ignore_ascii_case(word))
+}
+
+fn dont_say(value: &Value) -> InterpreterError {
+    InterpreterError::RuntimeError(format!("You don't say what to do with {}", value.show()))
+}
+
+/// An operator's operand, which must have been given a value
+fn operand(value: Option<Value>, op: &str) -> Eval<Value> {
+    value.ok_or_else(|| {
+        Unwind::Error(InterpreterError::RuntimeError(format!(
+            "{} needs a value on both sides",
+            op
+        )))
+    })
+}
+
+fn to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => Some(*number),
+        Value::Word(word) => word.trim().parse().ok(),
+        Value::List(_) => None,
+    }
+}
+
+fn number(value: &Value, name: &str) -> Eval<f64> {
+    to_number(value).map_or_else(
+        || error(format!("{} doesn't like {} as input", name, value.show())),
+        Ok,
+    )
+}
+
+fn boolean(value: &Value, name: &str) -> Eval<bool> {
+    match value {
+        Value::Word(word) if word.eq_ignore_ascii_case("TRUE") => Ok(true),
+        Value::Word(word) if word.eq_ignore_ascii_case("FALSE") => Ok(false),
+        other => error(format!(
+            "{} needs TRUE or FALSE, not {}",
+            name,
+            other.show()
+        )),
+    }
+}
+
+/// Numbers compare by value, words ignoring case, lists item by item
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::List(_), _) | (_, Value::List(_)) => a.show().eq_ignore_ascii_case(&b.show()),
+        _ => match (to_number(a), to_number(b)) {
+            (Some(x), Some(y)) => x == y,
+            _ => a.to_string().eq_ignore_ascii_case(&b.to_string()),
+        },
+    }
+}
+
+/// The members of a list, or the characters of a word
+fn elements(thing: &Value) -> Vec<Value> {
+    match thing {
+        Value::List(items) => items.iter().map(Value::from_item).collect(),
+        word => word
+            .to_string()
+            .chars()
+            .map(|c| Value::Word(c.to_string()))
+            .collect(),
+    }
+}
+
+/// A list's members, or a word as a one-member list, for SENTENCE
+fn flatten(thing: &Value) -> Vec<Item> {
+    match thing {
+        Value::List(items) => items.clone(),
+        word => vec![word.to_item()],
+    }
+}
+
+/// FIRST, LAST, BUTFIRST and BUTLAST of a word or list
+fn piece(name: &str, thing: &Value) -> Eval<Value> {
+    let mut parts = elements(thing);
+    if parts.is_empty() {
+        return error(format!("{} doesn't like {} as input", name, thing.show()));
+    }
+    let rest = |parts: Vec<Value>| match thing {
+        Value::List(_) => Value::List(parts.iter().map(Value::to_item).collect()),
+        _ => Value::Word(parts.iter().map(|part| part.to_string()).collect()),
+    };
+    Ok(match name {
+        "FIRST" => parts.remove(0),
+        "LAST" => parts.pop().unwrap_or_else(|| Value::List(Vec::new())),
+        "BUTFIRST" | "BF" => {
+            parts.remove(0);
+            rest(parts)
+        }
+        _ => {
+            parts.pop();
+            rest(parts)
+        }
+    })
+}
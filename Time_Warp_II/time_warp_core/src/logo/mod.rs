@@ -0,0 +1,112 @@
+//! Logo: turtle graphics with REPEAT, TO…END procedures, words and lists.
+//! Programs draw through the same `TurtleCommand`s as TW BASIC, so the
+//! canvas, replay and export work unchanged.
+
+pub mod interpreter;
+pub mod tokenizer;
+
+pub use interpreter::{Interpreter, Value};
+pub use tokenizer::{tokenize, Item};
+
+/// Primitive names offered by completion and highlighted in the editor
+pub const KEYWORDS: &[&str] = &[
+    "TO",
+    "END",
+    "FORWARD",
+    "FD",
+    "BACK",
+    "BK",
+    "LEFT",
+    "LT",
+    "RIGHT",
+    "RT",
+    "PENUP",
+    "PU",
+    "PENDOWN",
+    "PD",
+    "HOME",
+    "CLEARSCREEN",
+    "CS",
+    "SETXY",
+    "SETX",
+    "SETY",
+    "SETHEADING",
+    "SETH",
+    "SETPENCOLOR",
+    "SETPC",
+    "SETPENSIZE",
+    "SETBACKGROUND",
+    "SETBG",
+    "HIDETURTLE",
+    "HT",
+    "SHOWTURTLE",
+    "ST",
+    "ARC",
+    "CIRCLE",
+    "FILL",
+    "LABEL",
+    "SETLABELHEIGHT",
+    "XCOR",
+    "YCOR",
+    "HEADING",
+    "PENDOWNP",
+    "PRINT",
+    "PR",
+    "SHOW",
+    "TYPE",
+    "MAKE",
+    "LOCAL",
+    "THING",
+    "REPEAT",
+    "REPCOUNT",
+    "FOREVER",
+    "IF",
+    "IFELSE",
+    "STOP",
+    "OUTPUT",
+    "OP",
+    "RUN",
+    "SUM",
+    "DIFFERENCE",
+    "PRODUCT",
+    "QUOTIENT",
+    "REMAINDER",
+    "POWER",
+    "MINUS",
+    "SQRT",
+    "ABS",
+    "INT",
+    "ROUND",
+    "SIN",
+    "COS",
+    "TAN",
+    "ARCTAN",
+    "RANDOM",
+    "AND",
+    "OR",
+    "NOT",
+    "TRUE",
+    "FALSE",
+    "EQUALP",
+    "LESSP",
+    "GREATERP",
+    "NUMBERP",
+    "WORDP",
+    "LISTP",
+    "EMPTYP",
+    "MEMBERP",
+    "FIRST",
+    "LAST",
+    "BUTFIRST",
+    "BF",
+    "BUTLAST",
+    "BL",
+    "COUNT",
+    "ITEM",
+    "WORD",
+    "LIST",
+    "SENTENCE",
+    "SE",
+    "FPUT",
+    "LPUT",
+];
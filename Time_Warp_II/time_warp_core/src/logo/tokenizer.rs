@@ -0,0 +1,175 @@
+use crate::basic::InterpreterError;
+
+/// One piece of Logo source. Bracketed lists are kept whole, so a list can be
+/// data for PRINT or instructions for REPEAT without being parsed twice.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Word(String), // a procedure name, or a word inside a list; case is kept
+    Number(f64),
+    Quoted(String),   // "word
+    Variable(String), // :name
+    List(Vec<Item>),
+    Operator(String), // + - * / = < > <= >= <>
+    Open,             // (
+    Close,            // )
+}
+
+impl std::fmt::Display for Item {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Item::Word(word) | Item::Operator(word) => write!(f, "{}", word),
+            Item::Number(number) => write!(f, "{}", format_number(*number)),
+            Item::Quoted(word) => write!(f, "\"{}", word),
+            Item::Variable(name) => write!(f, ":{}", name),
+            Item::List(items) => write!(f, "[{}]", join(items)),
+            Item::Open => write!(f, "("),
+            Item::Close => write!(f, ")"),
+        }
+    }
+}
+
+/// Items separated by spaces, as PRINT shows the inside of a list
+pub fn join(items: &[Item]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whole numbers print without a decimal point
+pub fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+/// Split `source` into items. Comments run from `;` to the end of the line.
+pub fn tokenize(source: &str) -> Result<Vec<Item>, InterpreterError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut position = 0;
+    read_items(&chars, &mut position, false)
+}
+
+fn read_items(
+    chars: &[char],
+    position: &mut usize,
+    in_list: bool,
+) -> Result<Vec<Item>, InterpreterError> {
+    let mut items = Vec::new();
+    while *position < chars.len() {
+        let c = chars[*position];
+        // A minus sign straight after a space or bracket, and before a digit,
+        // is part of the number: FD -10, but 5-3 and 5 - 3 are subtractions
+        let starts_operand = *position == 0
+            || chars[*position - 1].is_whitespace()
+            || matches!(chars[*position - 1], '[' | '(');
+        match c {
+            ';' => {
+                while *position < chars.len() && chars[*position] != '\n' {
+                    *position += 1;
+                }
+            }
+            c if c.is_whitespace() => *position += 1,
+            '[' => {
+                *position += 1;
+                items.push(Item::List(read_items(chars, position, true)?));
+            }
+            ']' => {
+                *position += 1;
+                if in_list {
+                    return Ok(items);
+                }
+                return Err(InterpreterError::syntax(
+                    "Unexpected ] without a matching [".to_string(),
+                ));
+            }
+            '(' => {
+                *position += 1;
+                items.push(Item::Open);
+            }
+            ')' => {
+                *position += 1;
+                items.push(Item::Close);
+            }
+            '<' | '>' => {
+                let mut op = c.to_string();
+                *position += 1;
+                if let Some(&next) = chars.get(*position) {
+                    if next == '=' || (c == '<' && next == '>') {
+                        op.push(next);
+                        *position += 1;
+                    }
+                }
+                items.push(Item::Operator(op));
+            }
+            '-' if starts_operand
+                && chars
+                    .get(*position + 1)
+                    .is_some_and(|next| next.is_ascii_digit() || *next == '.') =>
+            {
+                *position += 1;
+                let word = read_word(chars, position);
+                items.push(Item::Number(-parse_number(&word)?));
+            }
+            '+' | '-' | '*' | '/' | '=' => {
+                *position += 1;
+                items.push(Item::Operator(c.to_string()));
+            }
+            '"' => {
+                *position += 1;
+                let start = *position;
+                while *position < chars.len() && !is_list_delimiter(chars[*position]) {
+                    *position += 1;
+                }
+                items.push(Item::Quoted(chars[start..*position].iter().collect()));
+            }
+            ':' => {
+                *position += 1;
+                let name = read_word(chars, position);
+                if name.is_empty() {
+                    return Err(InterpreterError::syntax(
+                        "A : must be followed by a variable name".to_string(),
+                    ));
+                }
+                items.push(Item::Variable(name));
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let word = read_word(chars, position);
+                items.push(Item::Number(parse_number(&word)?));
+            }
+            _ => items.push(Item::Word(read_word(chars, position))),
+        }
+    }
+    if in_list {
+        return Err(InterpreterError::syntax(
+            "A [ is missing its closing ]".to_string(),
+        ));
+    }
+    Ok(items)
+}
+
+/// Characters that end a quoted word
+fn is_list_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '[' | ']' | '(' | ')' | ';')
+}
+
+fn read_word(chars: &[char], position: &mut usize) -> String {
+    let start = *position;
+    while *position < chars.len() {
+        let c = chars[*position];
+        if is_list_delimiter(c) || matches!(c, '+' | '-' | '*' | '/' | '=' | '<' | '>' | ':' | '"')
+        {
+            break;
+        }
+        *position += 1;
+    }
+    chars[start..*position].iter().collect()
+}
+
+fn parse_number(word: &str) -> Result<f64, InterpreterError> {
+    word.parse()
+        .map_err(|_| InterpreterError::syntax(format!("{} is not a number", word)))
+}
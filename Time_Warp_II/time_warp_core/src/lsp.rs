@@ -0,0 +1,435 @@
+//! A Language Server Protocol server for TW BASIC, so editors such as VS Code
+//! get the same parser the IDE uses: errors as you type, keyword completion
+//! and docs on hover, and go-to-definition from `GOTO 100` to line 100.
+//!
+//! `Server::handle` takes one decoded message and returns the messages to
+//! send back; the `time_warp_lsp` binary does the stdin/stdout framing.
+//! Columns are counted in characters, which matches the editor's UTF-16
+//! columns for the plain ASCII programs BASIC is written in.
+
+use std::collections::HashMap;
+
+use crate::basic::{ErrorCode, Parser, Token, Tokenizer, KEYWORDS};
+use crate::json::Json;
+
+/// A problem found in a program, on a 0-based line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub message: String,
+    pub code: Option<ErrorCode>, // for errors that stop the program
+}
+
+/// What each keyword does, shown on hover and beside completions
+const KEYWORD_DOCS: &[(&str, &str)] = &[
+    ("PRINT", "PRINT [#n,] expr [; expr ...] - write values, to file #n if given; `;` keeps the next one on the same line, `,` moves to the next zone"),
+    ("LPRINT", "LPRINT expr [; expr ...] - print on the printer instead of the screen; 🖨 Printer shows the pages"),
+    ("USING", "PRINT USING \"format\"; expr [; expr ...] - lay values out by a format: # digit, . point, , thousands, $$ dollar, ** fill, ^^^^ exponent, ! \\  \\ & text"),
+    ("INPUT", "INPUT [\"prompt\";] var - wait for an answer and store it in var; INPUT #n, var, ... - read values from a file"),
+    ("LET", "[LET] var = expr - assign a value; LET is optional"),
+    ("IF", "IF condition THEN statement|line [ELSE statement|line]"),
+    ("THEN", "Follows an IF condition: the statement or line number to run when it is true"),
+    ("ELSE", "The statement or line number to run when the IF condition is false"),
+    ("FOR", "FOR var = start TO end [STEP n] - repeat up to the matching NEXT"),
+    ("TO", "Upper limit of a FOR loop"),
+    ("STEP", "How much a FOR loop counts by each time (default 1)"),
+    ("NEXT", "NEXT [var] - end of a FOR loop"),
+    ("WHILE", "WHILE condition - repeat up to WEND while condition is true"),
+    ("WEND", "End of a WHILE loop"),
+    ("GOTO", "GOTO line - jump to a line number"),
+    ("GOSUB", "GOSUB line - run the subroutine at line until RETURN"),
+    ("RETURN", "Go back to the statement after the last GOSUB"),
+    ("ON", "ON KEY(n)|TIMER(s)|PLAY(n)|MOUSE GOSUB line - run line when the event happens; ON ERROR GOTO line - go to line when an error happens"),
+    ("ERROR", "ON ERROR GOTO line - go to line instead of stopping when an error happens; ON ERROR GOTO 0 - stop again. ERROR n - raise error n, which ERR then reads"),
+    ("ERR", "ERR - the GW-BASIC number of the last error ON ERROR GOTO trapped, e.g. 11 for division by zero; 0 before any"),
+    ("ERL", "ERL - the line number of the last error ON ERROR GOTO trapped; 0 before any"),
+    ("RESUME", "RESUME [NEXT|line] - leave an ON ERROR handler: retry the statement that failed, go on after it, or go to line"),
+    ("OPEN", "OPEN \"file\" FOR INPUT|OUTPUT|APPEND AS #n - open a file in the program's folder to read, write or add to; OPEN \"file\" AS #n LEN=size - a random file of size-byte records"),
+    ("CLOSE", "CLOSE [#n, ...] - close the files numbered, or every open file"),
+    ("WRITE", "WRITE [#n,] expr, expr ... - write values separated by commas, text in quotes, so INPUT # reads them back"),
+    ("LINE", "LINE INPUT [#n,] var$ - read a whole line, commas and all, from a file or the keyboard"),
+    ("AS", "OPEN \"file\" FOR mode AS #n - the number the file is used by"),
+    ("OUTPUT", "OPEN \"file\" FOR OUTPUT AS #n - write a new file, emptying it if it exists"),
+    ("APPEND", "OPEN \"file\" FOR APPEND AS #n - write after what the file already holds"),
+    ("RANDOM", "OPEN \"file\" FOR RANDOM AS #n LEN=size - records read and written by number; FOR RANDOM may be left out"),
+    ("FIELD", "FIELD #n, width AS var$, ... - split file #n's record into string variables"),
+    ("LSET", "LSET var$ = text - put text in a FIELD variable, padded with spaces on the right"),
+    ("RSET", "RSET var$ = text - put text in a FIELD variable, padded with spaces on the left"),
+    ("GET", "GET #n[, record] - read a record of random file #n into its FIELD variables"),
+    ("PUT", "PUT #n[, record] - write the FIELD variables to a record of random file #n"),
+    ("FILES", "FILES [pattern$] - list the program's files, e.g. FILES \"*.TXT\""),
+    ("KILL", "KILL pattern$ - delete the matching files"),
+    ("NAME", "NAME old$ AS new$ - rename a file"),
+    ("MKDIR", "MKDIR dir$ - make a folder in the program's folder"),
+    ("CHDIR", "CHDIR dir$ - take file names from that folder; \"..\" goes back up"),
+    ("RMDIR", "RMDIR dir$ - remove an empty folder"),
+    ("INKEY$", "INKEY$ - the next key pressed, or \"\" at once if none; arrows give CHR$(0) and a code"),
+    ("MOUSEX", "MOUSEX - the mouse's x over the canvas, in the coordinates SETXY uses"),
+    ("MOUSEY", "MOUSEY - the mouse's y over the canvas, in the coordinates SETXY uses"),
+    ("MOUSEB", "MOUSEB - the mouse buttons held down: 1 left, 2 right, 4 middle, added up"),
+    ("PEEK", "PEEK(address) - the byte at address in the segment DEF SEG chose"),
+    ("POKE", "POKE address, byte - store 0 to 255 at address, e.g. POKE 53281, 6"),
+    ("SEG", "DEF SEG [= segment] - choose the segment PEEK and POKE address; 0 by default"),
+    ("MKI$", "MKI$(n) - an integer packed into 2 characters for a record; CVI unpacks it"),
+    ("MKS$", "MKS$(n) - a single-precision number packed into 4 characters; CVS unpacks it"),
+    ("MKD$", "MKD$(n) - a double-precision number packed into 8 characters; CVD unpacks it"),
+    ("CVI", "CVI(s$) - the integer MKI$ packed into s$"),
+    ("CVS", "CVS(s$) - the number MKS$ packed into s$"),
+    ("CVD", "CVD(s$) - the number MKD$ packed into s$"),
+    ("EOF", "EOF(n) - true (-1) when file #n has nothing more to read, or its last GET was past the end"),
+    ("LOF", "LOF(n) - how many bytes file #n holds"),
+    ("OFF", "KEY|TIMER|PLAY|MOUSE OFF - stop watching for the event"),
+    ("KEY", "KEY(n) ON|OFF|STOP - control the handler for a key press"),
+    ("TIMER", "TIMER ON|OFF|STOP - control the ON TIMER handler"),
+    ("PLAY", "PLAY music$ - play notes, e.g. PLAY \"T160 O3 L8 CDEC\"; MB plays on in the background; PLAY ON|OFF|STOP - control the ON PLAY handler"),
+    ("MOUSE", "MOUSE ON|OFF|STOP - control the ON MOUSE handler"),
+    ("END", "Stop the program"),
+    ("ASSERT", "ASSERT condition [, \"message\"] - report (and carry on) when condition is false; 🧪 Tests sums them up"),
+    ("CLS", "Clear the output"),
+    ("LOCATE", "LOCATE row, column - move the text cursor"),
+    ("COLOR", "COLOR foreground [, background] - set text colors"),
+    ("BEEP", "BEEP - sound a short tone"),
+    ("SOUND", "SOUND hertz, ticks - sound a tone; 18.2 ticks make a second"),
+    ("SLEEP", "SLEEP [seconds] - pause the program; a key ends it early, and SLEEP alone waits for one"),
+    ("WAIT", "WAIT ticks - pause the program for ticks sixtieths of a second, as in Logo"),
+    ("RANDOMIZE", "RANDOMIZE n - the same RND numbers on every run; RANDOMIZE TIMER - new ones each run"),
+    ("RND", "RND - a random number from 0 up to (not including) 1; the same sequence each run unless RANDOMIZE TIMER"),
+    ("INT", "INT(x) - x rounded down to a whole number"),
+    ("STR$", "STR$(x) - the number x as text"),
+    ("VAL", "VAL(text) - the number written in text"),
+    ("LEN", "LEN(text) - how many characters text has"),
+    ("LEFT$", "LEFT$(text, n) - the first n characters"),
+    ("RIGHT$", "RIGHT$(text, n) - the last n characters"),
+    ("MID$", "MID$(text, start [, n]) - n characters from position start"),
+    ("MATCH", "MATCH(answer$, pattern$) - position of the first acceptable answer in pattern$ (separated by , or |) found in answer$, or 0"),
+    ("SOUNDEX$", "SOUNDEX$(text) - a code that is the same for words that sound alike"),
+    ("SIMILAR", "SIMILAR(a$, b$) - how alike two answers are, from 0 to 100"),
+    ("CHR$", "CHR$(code) - the character with that code"),
+    ("ASC", "ASC(text) - the code of the first character"),
+    ("ABS", "ABS(x) - x without its sign"),
+    ("SIN", "SIN(x) - sine of x radians"),
+    ("COS", "COS(x) - cosine of x radians"),
+    ("TAN", "TAN(x) - tangent of x radians"),
+    ("LOG", "LOG(x) - natural logarithm of x"),
+    ("EXP", "EXP(x) - e raised to the power x"),
+    ("SQR", "SQR(x) - square root of x"),
+    ("AND", "a AND b - true when both are true"),
+    ("OR", "a OR b - true when either is true"),
+    ("NOT", "NOT a - true when a is false"),
+    ("MOD", "a MOD b - remainder after dividing a by b"),
+    ("DIM", "DIM name(size) - reserve a one-dimensional array. Not supported yet: its elements can't be assigned"),
+    ("DEF", "DEF FNname(x) = expr - define a one-line function"),
+    ("FN", "Calls a function made with DEF FN"),
+    ("REM", "REM text - a comment; the rest of the line is ignored"),
+];
+
+/// Docs for a keyword, in any case
+pub fn hover(word: &str) -> Option<&'static str> {
+    let word = word.to_ascii_uppercase();
+    KEYWORD_DOCS
+        .iter()
+        .find(|(keyword, _)| *keyword == word)
+        .map(|(_, doc)| *doc)
+}
+
+/// Keywords starting with `prefix`, in any case
+pub fn completions(prefix: &str) -> Vec<&'static str> {
+    let prefix = prefix.to_ascii_uppercase();
+    KEYWORDS
+        .iter()
+        .copied()
+        .filter(|keyword| keyword.starts_with(&prefix))
+        .collect()
+}
+
+/// Parse errors, and jumps to line numbers the program doesn't have
+pub fn diagnostics(code: &str) -> Vec<Diagnostic> {
+    let located = |err: crate::basic::InterpreterError| Diagnostic {
+        line: err.position().map_or(0, |position| position.line - 1),
+        message: err.message(),
+        code: Some(err.code()),
+    };
+    let tokens = match Tokenizer::new(code).tokenize() {
+        Ok(tokens) => tokens,
+        Err(err) => return vec![located(err)],
+    };
+    let program = match Parser::new(tokens.clone()).parse_program() {
+        Ok(program) => program,
+        Err(err) => return vec![located(err)],
+    };
+
+    let mut found = Vec::new();
+    let mut jumping = false; // just after GOTO, GOSUB, THEN or ELSE
+    for token in &tokens {
+        match &token.token {
+            Token::Eol => jumping = false,
+            Token::Goto | Token::Gosub | Token::Then | Token::Else => jumping = true,
+            // ON ... GOTO lists several targets
+            Token::Comma if jumping => {}
+            Token::Number(target) if jumping => {
+                let target = *target as usize;
+                if target != 0 && !program.line_numbers.contains_key(&target) {
+                    found.push(Diagnostic {
+                        line: token.line - 1,
+                        message: format!("There is no line {} to jump to", target),
+                        code: None,
+                    });
+                }
+            }
+            _ => jumping = false,
+        }
+    }
+    found
+}
+
+/// The 0-based line a line number under the cursor refers to
+pub fn definition(code: &str, line: usize, character: usize) -> Option<usize> {
+    let text = code.lines().nth(line)?;
+    let (start, word) = word_at(text, character)?;
+    let target: usize = word.parse().ok()?;
+    // The number that starts a line is the definition itself
+    if text[..start].trim().is_empty() {
+        return None;
+    }
+    code.lines()
+        .position(|line| leading_number(line) == Some(target))
+}
+
+fn leading_number(line: &str) -> Option<usize> {
+    let line = line.trim_start();
+    let digits: String = line.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// The word touching `character`, with the byte offset it starts at
+fn word_at(text: &str, character: usize) -> Option<(usize, &str)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '$' || c == '_';
+    let offset = text
+        .char_indices()
+        .nth(character)
+        .map_or(text.len(), |(offset, _)| offset);
+    let start = text[..offset]
+        .rfind(|c: char| !is_word(c))
+        .map_or(0, |index| index + 1);
+    let end = text[offset..]
+        .find(|c: char| !is_word(c))
+        .map_or(text.len(), |index| offset + index);
+    (start < end).then(|| (start, &text[start..end]))
+}
+
+/// The open documents and where the session is in its lifecycle
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>, // uri -> text
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set once the editor sends `exit`
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Whether `shutdown` came first, which makes the exit a clean one
+    pub fn shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Answer one message, returning the responses and notifications to send
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let id = message.get("id").cloned();
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let uri = params
+            .at(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+
+        let result = match method {
+            "initialize" => Json::object([
+                (
+                    "capabilities",
+                    Json::object([
+                        ("textDocumentSync", 1.into()), // whole document on every change
+                        ("completionProvider", Json::object([])),
+                        ("hoverProvider", Json::Bool(true)),
+                        ("definitionProvider", Json::Bool(true)),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object([
+                        ("name", "time_warp_lsp".into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ]),
+            "textDocument/didOpen" => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str);
+                self.documents
+                    .insert(uri.clone(), text.unwrap_or("").to_string());
+                return vec![self.publish(&uri)];
+            }
+            "textDocument/didChange" => {
+                let text = match params.get("contentChanges") {
+                    Some(Json::Array(changes)) => changes
+                        .last()
+                        .and_then(|change| change.get("text"))
+                        .and_then(Json::as_str),
+                    _ => None,
+                };
+                if let Some(text) = text {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                return vec![self.publish(&uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![self.publish(&uri)];
+            }
+            "textDocument/completion" => {
+                let prefix = self
+                    .line_at(&uri, &params)
+                    .map(|(text, character)| {
+                        let end = text
+                            .char_indices()
+                            .nth(character)
+                            .map_or(text.len(), |(offset, _)| offset);
+                        let start = text[..end]
+                            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '$'))
+                            .map_or(0, |index| index + 1);
+                        text[start..end].to_string()
+                    })
+                    .unwrap_or_default();
+                Json::Array(
+                    completions(&prefix)
+                        .into_iter()
+                        .map(|keyword| {
+                            let mut item = vec![
+                                ("label".to_string(), keyword.into()),
+                                ("kind".to_string(), 14.into()), // Keyword
+                            ];
+                            if let Some(doc) = hover(keyword) {
+                                item.push(("detail".to_string(), doc.into()));
+                            }
+                            Json::Object(item)
+                        })
+                        .collect(),
+                )
+            }
+            "textDocument/hover" => self
+                .line_at(&uri, &params)
+                .and_then(|(text, character)| word_at(text, character))
+                .and_then(|(_, word)| hover(word))
+                .map_or(Json::Null, |doc| {
+                    Json::object([(
+                        "contents",
+                        Json::object([("kind", "markdown".into()), ("value", doc.into())]),
+                    )])
+                }),
+            "textDocument/definition" => {
+                let position = self.position(&params);
+                let code = self.documents.get(&uri);
+                code.zip(position)
+                    .and_then(|(code, (line, character))| definition(code, line, character))
+                    .map_or(Json::Null, |line| {
+                        Json::object([("uri", uri.as_str().into()), ("range", range(line))])
+                    })
+            }
+            "shutdown" => {
+                self.shut_down = true;
+                Json::Null
+            }
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            _ => {
+                // Unknown notifications are ignored; unknown requests get an error
+                return id
+                    .map(|id| {
+                        Json::object([
+                            ("jsonrpc", "2.0".into()),
+                            ("id", id),
+                            (
+                                "error",
+                                Json::object([
+                                    ("code", Json::Number(-32601.0)),
+                                    ("message", format!("{} is not supported", method).into()),
+                                ]),
+                            ),
+                        ])
+                    })
+                    .into_iter()
+                    .collect();
+            }
+        };
+        match id {
+            Some(id) => vec![Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id),
+                ("result", result),
+            ])],
+            None => Vec::new(),
+        }
+    }
+
+    fn position(&self, params: &Json) -> Option<(usize, usize)> {
+        let line = params.at(&["position", "line"]).and_then(Json::as_usize)?;
+        let character = params
+            .at(&["position", "character"])
+            .and_then(Json::as_usize)?;
+        Some((line, character))
+    }
+
+    fn line_at(&self, uri: &str, params: &Json) -> Option<(&str, usize)> {
+        let (line, character) = self.position(params)?;
+        let text = self.documents.get(uri)?.lines().nth(line)?;
+        Some((text, character))
+    }
+
+    fn publish(&self, uri: &str) -> Json {
+        let found = self
+            .documents
+            .get(uri)
+            .map(|code| diagnostics(code))
+            .unwrap_or_default();
+        let found = found
+            .into_iter()
+            .map(|diagnostic| {
+                let mut found = Json::object([
+                    ("range", range(diagnostic.line)),
+                    ("severity", 1.into()), // Error
+                    ("source", "tw-basic".into()),
+                    ("message", diagnostic.message.into()),
+                ]);
+                if let (Some(code), Json::Object(fields)) = (diagnostic.code, &mut found) {
+                    fields.push(("code".to_string(), code.code().into()));
+                }
+                found
+            })
+            .collect();
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object([("uri", uri.into()), ("diagnostics", Json::Array(found))]),
+            ),
+        ])
+    }
+}
+
+/// The whole of a 0-based line
+fn range(line: usize) -> Json {
+    let position = |line: usize, character: usize| {
+        Json::object([("line", line.into()), ("character", character.into())])
+    };
+    Json::object([("start", position(line, 0)), ("end", position(line + 1, 0))])
+}
@@ -0,0 +1,148 @@
+/// A variable's declared type, with aliases and constant bounds resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Integer,
+    Real,
+    Boolean,
+    Char,
+    String,
+    Array {
+        low: i64,
+        high: i64,
+        element: Box<Type>,
+    },
+    Record(Vec<(String, Type)>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide, // always gives a real
+    Div,    // whole-number division
+    Mod,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+}
+
+/// A variable, array element or record field, e.g. `pupils[i].name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Designator {
+    pub name: String,
+    pub selectors: Vec<Selector>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    Index(Expr),
+    Field(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Integer(i64),
+    Real(f64),
+    Str(String),
+    Boolean(bool),
+    Variable(Designator), // or a function called without arguments
+    Call(String, Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// One argument to write/writeln, with optional `:width:decimals`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteArg {
+    pub value: Expr,
+    pub width: Option<Expr>,
+    pub decimals: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseLabel {
+    Value(Expr),
+    Range(Expr, Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub line: usize,
+    pub kind: StmtKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Empty,
+    Assign(Designator, Expr),
+    Call(String, Vec<Expr>),
+    Write {
+        newline: bool,
+        args: Vec<WriteArg>,
+    },
+    Read {
+        newline: bool,
+        targets: Vec<Designator>,
+    },
+    Compound(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Repeat(Vec<Stmt>, Expr),
+    For {
+        variable: String,
+        start: Expr,
+        end: Expr,
+        down: bool,
+        body: Box<Stmt>,
+    },
+    Case {
+        selector: Expr,
+        arms: Vec<(Vec<CaseLabel>, Stmt)>,
+        otherwise: Vec<Stmt>,
+    },
+    /// Fields of each record can be named on their own inside the body
+    With(Vec<Designator>, Box<Stmt>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+    pub by_reference: bool, // a `var` parameter
+}
+
+/// A procedure, or a function when `result` is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routine {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub result: Option<Type>,
+    pub block: Block,
+}
+
+/// Declarations followed by `begin … end`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Block {
+    pub constants: Vec<(String, Expr)>,
+    pub variables: Vec<(String, Type)>,
+    pub routines: Vec<Routine>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub name: Option<String>,
+    pub block: Block,
+}
@@ -0,0 +1,161 @@
+use crate::basic::InterpreterError;
+
+/// One Pascal token. Identifiers and reserved words are lower-cased, since
+/// Pascal ignores case.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Word(String),
+    Integer(i64),
+    Real(f64),
+    Str(String),
+    Symbol(&'static str),
+    Eof,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Integer(number) => write!(f, "{}", number),
+            Token::Real(number) => write!(f, "{}", number),
+            Token::Str(text) => write!(f, "'{}'", text.replace('\'', "''")),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::Eof => write!(f, "the end of the program"),
+        }
+    }
+}
+
+/// Longest first, so `:=` is not read as `:` then `=`
+const SYMBOLS: &[&str] = &[
+    ":=", "<=", ">=", "<>", "..", "+", "-", "*", "/", "=", "<", ">", "(", ")", "[", "]", ",", ";",
+    ":", ".",
+];
+
+/// Split `source` into tokens, each with its 1-based line. Comments are
+/// `{ … }`, `(* … *)` and `// …`.
+pub fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, InterpreterError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let error = |line: usize, message: String| {
+        InterpreterError::syntax(format!("Line {}: {}", line, message))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '{' || rest == "(*" {
+            let close = if c == '{' { "}" } else { "*)" };
+            let start_line = line;
+            i += if c == '{' { 1 } else { 2 };
+            loop {
+                if i >= chars.len() {
+                    return Err(error(start_line, "comment is never closed".to_string()));
+                }
+                let here: String = chars[i..chars.len().min(i + close.len())].iter().collect();
+                if here == close {
+                    i += close.len();
+                    break;
+                }
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+        } else if rest == "//" {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push((Token::Word(word.to_ascii_lowercase()), line));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            // A dot starts a fraction unless it is the `..` of a range
+            let mut real = false;
+            if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                real = true;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            if matches!(chars.get(i), Some('e' | 'E')) {
+                let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                if chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                    real = true;
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = if real {
+                text.parse().map(Token::Real).ok()
+            } else {
+                text.parse().map(Token::Integer).ok()
+            };
+            let token = token.ok_or_else(|| error(line, format!("{} is too large", text)))?;
+            tokens.push((token, line));
+        } else if c == '\'' {
+            // '' inside a string is one quote
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                        text.push('\'');
+                        i += 2;
+                    }
+                    Some('\'') => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\n') | None => {
+                        return Err(error(line, "string is missing its closing '".to_string()))
+                    }
+                    Some(&c) => {
+                        text.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Str(text), line));
+        } else if c == '#' {
+            // #65 is the character with that code
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let code: String = chars[start..i].iter().collect();
+            let c = code
+                .parse::<u32>()
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| error(line, "# must be followed by a character code".to_string()))?;
+            tokens.push((Token::Str(c.to_string()), line));
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            i += symbol.len();
+            tokens.push((Token::Symbol(symbol), line));
+        } else {
+            return Err(error(line, format!("unexpected character '{}'", c)));
+        }
+    }
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
@@ -0,0 +1,67 @@
+//! TW Pascal: program/var/begin…end with integer, real, char, string and
+//! boolean variables, arrays and records, if/while/for/repeat/case, and
+//! procedures and functions with value and var parameters. readln answers
+//! come through the same waiting-for-input flow as BASIC's INPUT.
+
+pub mod ast;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+
+pub use interpreter::{Interpreter, Value};
+pub use parser::Parser;
+
+/// Reserved words and built-ins offered by completion
+pub const KEYWORDS: &[&str] = &[
+    "PROGRAM",
+    "CONST",
+    "TYPE",
+    "VAR",
+    "BEGIN",
+    "END",
+    "PROCEDURE",
+    "FUNCTION",
+    "IF",
+    "THEN",
+    "ELSE",
+    "WHILE",
+    "DO",
+    "FOR",
+    "TO",
+    "DOWNTO",
+    "REPEAT",
+    "UNTIL",
+    "CASE",
+    "OF",
+    "ARRAY",
+    "RECORD",
+    "INTEGER",
+    "REAL",
+    "BOOLEAN",
+    "CHAR",
+    "STRING",
+    "TRUE",
+    "FALSE",
+    "AND",
+    "OR",
+    "NOT",
+    "DIV",
+    "MOD",
+    "WRITE",
+    "WRITELN",
+    "READ",
+    "READLN",
+    "LENGTH",
+    "COPY",
+    "POS",
+    "ROUND",
+    "TRUNC",
+    "SQRT",
+    "SQR",
+    "ABS",
+    "RANDOM",
+    "INC",
+    "DEC",
+    "EXIT",
+    "HALT",
+];
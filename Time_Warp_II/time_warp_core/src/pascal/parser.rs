@@ -0,0 +1,664 @@
+use super::ast::*;
+use super::lexer::Token;
+use crate::basic::InterpreterError;
+use std::collections::HashMap;
+
+/// Words that can't be used as names
+const RESERVED: &[&str] = &[
+    "and",
+    "array",
+    "begin",
+    "case",
+    "const",
+    "div",
+    "do",
+    "downto",
+    "else",
+    "end",
+    "for",
+    "function",
+    "if",
+    "mod",
+    "not",
+    "of",
+    "or",
+    "otherwise",
+    "procedure",
+    "program",
+    "record",
+    "repeat",
+    "then",
+    "to",
+    "type",
+    "until",
+    "var",
+    "while",
+    "with",
+];
+
+type ParseResult<T> = Result<T, InterpreterError>;
+
+/// Recursive descent parser for TW Pascal
+pub struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    types: HashMap<String, Type>,            // `type` declarations
+    integer_constants: HashMap<String, i64>, // for array bounds
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<(Token, usize)>) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            types: HashMap::new(),
+            integer_constants: HashMap::new(),
+        }
+    }
+
+    /// `program Name;` is optional; the program ends with `end.`
+    pub fn parse_program(&mut self) -> ParseResult<Program> {
+        let mut name = None;
+        if self.accept_word("program") {
+            name = Some(self.name()?);
+            // Old-style file list: program Hello(input, output);
+            if self.accept_symbol("(") {
+                while !self.accept_symbol(")") {
+                    self.advance();
+                    if self.peek() == &Token::Eof {
+                        return Err(self.error("the program's ( is missing its )"));
+                    }
+                }
+            }
+            self.expect_symbol(";")?;
+        }
+        let block = self.block()?;
+        self.expect_symbol(".")?;
+        if self.peek() != &Token::Eof {
+            return Err(self.error(&format!("unexpected {} after end.", self.peek())));
+        }
+        Ok(Program { name, block })
+    }
+
+    fn block(&mut self) -> ParseResult<Block> {
+        let mut block = Block::default();
+        loop {
+            if self.accept_word("const") {
+                while self.at_name() {
+                    let name = self.name()?;
+                    self.expect_symbol("=")?;
+                    let value = self.expression()?;
+                    self.expect_symbol(";")?;
+                    if let Some(number) = self.integer_constant(&value) {
+                        self.integer_constants.insert(name.clone(), number);
+                    }
+                    block.constants.push((name, value));
+                }
+            } else if self.accept_word("type") {
+                while self.at_name() {
+                    let name = self.name()?;
+                    self.expect_symbol("=")?;
+                    let ty = self.type_spec()?;
+                    self.expect_symbol(";")?;
+                    self.types.insert(name, ty);
+                }
+            } else if self.accept_word("var") {
+                while self.at_name() {
+                    let names = self.name_list()?;
+                    self.expect_symbol(":")?;
+                    let ty = self.type_spec()?;
+                    self.expect_symbol(";")?;
+                    for name in names {
+                        block.variables.push((name, ty.clone()));
+                    }
+                }
+            } else if self.peek_word("procedure") || self.peek_word("function") {
+                block.routines.push(self.routine()?);
+            } else {
+                break;
+            }
+        }
+        self.expect_word("begin")?;
+        block.body = self.statements(&["end"])?;
+        self.expect_word("end")?;
+        Ok(block)
+    }
+
+    fn routine(&mut self) -> ParseResult<Routine> {
+        let is_function = self.accept_word("function");
+        if !is_function {
+            self.expect_word("procedure")?;
+        }
+        let name = self.name()?;
+        let mut params = Vec::new();
+        if self.accept_symbol("(") {
+            loop {
+                let by_reference = self.accept_word("var");
+                if !by_reference {
+                    // `const` parameters are passed like value parameters
+                    self.accept_word("const");
+                }
+                let names = self.name_list()?;
+                self.expect_symbol(":")?;
+                let ty = self.type_spec()?;
+                params.extend(names.into_iter().map(|name| Param {
+                    name,
+                    ty: ty.clone(),
+                    by_reference,
+                }));
+                if !self.accept_symbol(";") {
+                    break;
+                }
+            }
+            self.expect_symbol(")")?;
+        }
+        let result = if is_function {
+            self.expect_symbol(":")?;
+            Some(self.type_spec()?)
+        } else {
+            None
+        };
+        self.expect_symbol(";")?;
+        let block = self.block()?;
+        self.expect_symbol(";")?;
+        Ok(Routine {
+            name,
+            params,
+            result,
+            block,
+        })
+    }
+
+    fn type_spec(&mut self) -> ParseResult<Type> {
+        if self.accept_word("array") {
+            self.expect_symbol("[")?;
+            let mut ranges = Vec::new();
+            loop {
+                let low = self.bound()?;
+                self.expect_symbol("..")?;
+                let high = self.bound()?;
+                if high < low {
+                    return Err(self.error(&format!("array range {}..{} is empty", low, high)));
+                }
+                ranges.push((low, high));
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol("]")?;
+            self.expect_word("of")?;
+            let mut ty = self.type_spec()?;
+            // array[1..3, 1..4] is an array of arrays
+            for (low, high) in ranges.into_iter().rev() {
+                ty = Type::Array {
+                    low,
+                    high,
+                    element: Box::new(ty),
+                };
+            }
+            return Ok(ty);
+        }
+        if self.accept_word("record") {
+            let mut fields = Vec::new();
+            while self.at_name() {
+                let names = self.name_list()?;
+                self.expect_symbol(":")?;
+                let ty = self.type_spec()?;
+                fields.extend(names.into_iter().map(|name| (name, ty.clone())));
+                if !self.accept_symbol(";") {
+                    break;
+                }
+            }
+            self.expect_word("end")?;
+            return Ok(Type::Record(fields));
+        }
+
+        let name = self.name()?;
+        let ty = match name.as_str() {
+            "integer" | "longint" | "shortint" | "byte" | "word" | "int64" | "cardinal" => {
+                Type::Integer
+            }
+            "real" | "double" | "single" | "extended" => Type::Real,
+            "boolean" => Type::Boolean,
+            "char" => Type::Char,
+            "string" => {
+                // string[30] is accepted; lengths aren't enforced
+                if self.accept_symbol("[") {
+                    self.bound()?;
+                    self.expect_symbol("]")?;
+                }
+                Type::String
+            }
+            _ => match self.types.get(&name) {
+                Some(ty) => ty.clone(),
+                None => return Err(self.error(&format!("unknown type '{}'", name))),
+            },
+        };
+        Ok(ty)
+    }
+
+    /// An array bound: a whole number or an integer constant
+    fn bound(&mut self) -> ParseResult<i64> {
+        let expr = self.simple_expression()?;
+        self.integer_constant(&expr)
+            .ok_or_else(|| self.error("array bounds must be whole-number constants"))
+    }
+
+    fn integer_constant(&self, expr: &Expr) -> Option<i64> {
+        match expr {
+            Expr::Integer(number) => Some(*number),
+            Expr::Variable(designator) if designator.selectors.is_empty() => {
+                self.integer_constants.get(&designator.name).copied()
+            }
+            Expr::Unary(UnaryOp::Negate, inner) => self.integer_constant(inner).map(|n| -n),
+            Expr::Binary(op, left, right) => {
+                let (a, b) = (self.integer_constant(left)?, self.integer_constant(right)?);
+                match op {
+                    BinaryOp::Add => a.checked_add(b),
+                    BinaryOp::Subtract => a.checked_sub(b),
+                    BinaryOp::Multiply => a.checked_mul(b),
+                    BinaryOp::Div => a.checked_div(b),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Statements separated by `;`, up to one of `terminators`
+    fn statements(&mut self, terminators: &[&str]) -> ParseResult<Vec<Stmt>> {
+        let mut statements = vec![self.statement()?];
+        while self.accept_symbol(";") {
+            statements.push(self.statement()?);
+        }
+        if !terminators.iter().any(|word| self.peek_word(word)) {
+            return Err(self.error(&format!(
+                "expected ';' or '{}' but found {}",
+                terminators[0],
+                self.peek()
+            )));
+        }
+        statements.retain(|statement| statement.kind != StmtKind::Empty);
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        let line = self.line();
+        let kind = if self.accept_word("begin") {
+            let body = self.statements(&["end"])?;
+            self.expect_word("end")?;
+            StmtKind::Compound(body)
+        } else if self.accept_word("if") {
+            let condition = self.expression()?;
+            self.expect_word("then")?;
+            let then = self.statement()?;
+            let otherwise = if self.accept_word("else") {
+                Some(Box::new(self.statement()?))
+            } else {
+                None
+            };
+            StmtKind::If(condition, Box::new(then), otherwise)
+        } else if self.accept_word("while") {
+            let condition = self.expression()?;
+            self.expect_word("do")?;
+            StmtKind::While(condition, Box::new(self.statement()?))
+        } else if self.accept_word("repeat") {
+            let body = self.statements(&["until"])?;
+            self.expect_word("until")?;
+            StmtKind::Repeat(body, self.expression()?)
+        } else if self.accept_word("for") {
+            let variable = self.name()?;
+            self.expect_symbol(":=")?;
+            let start = self.expression()?;
+            let down = self.accept_word("downto");
+            if !down {
+                self.expect_word("to")?;
+            }
+            let end = self.expression()?;
+            self.expect_word("do")?;
+            StmtKind::For {
+                variable,
+                start,
+                end,
+                down,
+                body: Box::new(self.statement()?),
+            }
+        } else if self.accept_word("case") {
+            self.case_statement()?
+        } else if self.accept_word("with") {
+            let mut records = Vec::new();
+            loop {
+                let name = self.name()?;
+                records.push(self.designator(name)?);
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_word("do")?;
+            StmtKind::With(records, Box::new(self.statement()?))
+        } else if self.at_name() {
+            self.simple_statement()?
+        } else {
+            StmtKind::Empty
+        };
+        Ok(Stmt { line, kind })
+    }
+
+    fn case_statement(&mut self) -> ParseResult<StmtKind> {
+        let selector = self.expression()?;
+        self.expect_word("of")?;
+        let mut arms = Vec::new();
+        let mut otherwise = Vec::new();
+        loop {
+            if self.accept_word("else") || self.accept_word("otherwise") {
+                otherwise = self.statements(&["end"])?;
+                break;
+            }
+            if self.peek_word("end") {
+                break;
+            }
+            let mut labels = Vec::new();
+            loop {
+                let value = self.expression()?;
+                labels.push(if self.accept_symbol("..") {
+                    CaseLabel::Range(value, self.expression()?)
+                } else {
+                    CaseLabel::Value(value)
+                });
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol(":")?;
+            arms.push((labels, self.statement()?));
+            if !self.accept_symbol(";") {
+                if self.accept_word("else") || self.accept_word("otherwise") {
+                    otherwise = self.statements(&["end"])?;
+                }
+                break;
+            }
+        }
+        self.expect_word("end")?;
+        Ok(StmtKind::Case {
+            selector,
+            arms,
+            otherwise,
+        })
+    }
+
+    /// Assignment, write/read, or a procedure call
+    fn simple_statement(&mut self) -> ParseResult<StmtKind> {
+        let name = self.name()?;
+        match name.as_str() {
+            "write" | "writeln" => {
+                let mut args = Vec::new();
+                if self.accept_symbol("(") {
+                    loop {
+                        let value = self.expression()?;
+                        let width = if self.accept_symbol(":") {
+                            Some(self.expression()?)
+                        } else {
+                            None
+                        };
+                        let decimals = if width.is_some() && self.accept_symbol(":") {
+                            Some(self.expression()?)
+                        } else {
+                            None
+                        };
+                        args.push(WriteArg {
+                            value,
+                            width,
+                            decimals,
+                        });
+                        if !self.accept_symbol(",") {
+                            break;
+                        }
+                    }
+                    self.expect_symbol(")")?;
+                }
+                return Ok(StmtKind::Write {
+                    newline: name == "writeln",
+                    args,
+                });
+            }
+            "read" | "readln" => {
+                let mut targets = Vec::new();
+                if self.accept_symbol("(") {
+                    loop {
+                        let target = self.name()?;
+                        targets.push(self.designator(target)?);
+                        if !self.accept_symbol(",") {
+                            break;
+                        }
+                    }
+                    self.expect_symbol(")")?;
+                }
+                return Ok(StmtKind::Read {
+                    newline: name == "readln",
+                    targets,
+                });
+            }
+            _ => {}
+        }
+
+        if self.peek() == &Token::Symbol("(") {
+            return Ok(StmtKind::Call(name, self.arguments()?));
+        }
+        let designator = self.designator(name)?;
+        if self.accept_symbol(":=") {
+            return Ok(StmtKind::Assign(designator, self.expression()?));
+        }
+        if !designator.selectors.is_empty() {
+            return Err(self.error(&format!("expected ':=' but found {}", self.peek())));
+        }
+        Ok(StmtKind::Call(designator.name, Vec::new()))
+    }
+
+    fn designator(&mut self, name: String) -> ParseResult<Designator> {
+        let mut selectors = Vec::new();
+        loop {
+            if self.accept_symbol("[") {
+                loop {
+                    selectors.push(Selector::Index(self.expression()?));
+                    if !self.accept_symbol(",") {
+                        break;
+                    }
+                }
+                self.expect_symbol("]")?;
+            } else if self.accept_symbol(".") {
+                selectors.push(Selector::Field(self.name()?));
+            } else {
+                return Ok(Designator { name, selectors });
+            }
+        }
+    }
+
+    fn arguments(&mut self) -> ParseResult<Vec<Expr>> {
+        self.expect_symbol("(")?;
+        let mut args = Vec::new();
+        if self.accept_symbol(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression()?);
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol(")")?;
+        Ok(args)
+    }
+
+    /// Comparisons bind loosest
+    fn expression(&mut self) -> ParseResult<Expr> {
+        let left = self.simple_expression()?;
+        let op = match self.peek() {
+            Token::Symbol("=") => BinaryOp::Equal,
+            Token::Symbol("<>") => BinaryOp::NotEqual,
+            Token::Symbol("<") => BinaryOp::Less,
+            Token::Symbol(">") => BinaryOp::Greater,
+            Token::Symbol("<=") => BinaryOp::LessEqual,
+            Token::Symbol(">=") => BinaryOp::GreaterEqual,
+            _ => return Ok(left),
+        };
+        self.advance();
+        let right = self.simple_expression()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn simple_expression(&mut self) -> ParseResult<Expr> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol("+") => BinaryOp::Add,
+                Token::Symbol("-") => BinaryOp::Subtract,
+                Token::Word(word) if word == "or" => BinaryOp::Or,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.term()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn term(&mut self) -> ParseResult<Expr> {
+        let mut left = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol("*") => BinaryOp::Multiply,
+                Token::Symbol("/") => BinaryOp::Divide,
+                Token::Word(word) if word == "div" => BinaryOp::Div,
+                Token::Word(word) if word == "mod" => BinaryOp::Mod,
+                Token::Word(word) if word == "and" => BinaryOp::And,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.factor()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn factor(&mut self) -> ParseResult<Expr> {
+        let token = self.peek().clone();
+        match token {
+            Token::Integer(number) => {
+                self.advance();
+                Ok(Expr::Integer(number))
+            }
+            Token::Real(number) => {
+                self.advance();
+                Ok(Expr::Real(number))
+            }
+            Token::Str(text) => {
+                self.advance();
+                Ok(Expr::Str(text))
+            }
+            Token::Symbol("(") => {
+                self.advance();
+                let inner = self.expression()?;
+                self.expect_symbol(")")?;
+                Ok(inner)
+            }
+            Token::Symbol("-") => {
+                self.advance();
+                Ok(Expr::Unary(UnaryOp::Negate, Box::new(self.factor()?)))
+            }
+            Token::Symbol("+") => {
+                self.advance();
+                self.factor()
+            }
+            Token::Word(word) if word == "not" => {
+                self.advance();
+                Ok(Expr::Unary(UnaryOp::Not, Box::new(self.factor()?)))
+            }
+            Token::Word(word) if word == "true" || word == "false" => {
+                self.advance();
+                Ok(Expr::Boolean(word == "true"))
+            }
+            Token::Word(_) if self.at_name() => {
+                let name = self.name()?;
+                if self.peek() == &Token::Symbol("(") {
+                    return Ok(Expr::Call(name, self.arguments()?));
+                }
+                Ok(Expr::Variable(self.designator(name)?))
+            }
+            other => Err(self.error(&format!("expected a value but found {}", other))),
+        }
+    }
+
+    fn name_list(&mut self) -> ParseResult<Vec<String>> {
+        let mut names = vec![self.name()?];
+        while self.accept_symbol(",") {
+            names.push(self.name()?);
+        }
+        Ok(names)
+    }
+
+    fn name(&mut self) -> ParseResult<String> {
+        if self.at_name() {
+            if let Token::Word(word) = self.peek().clone() {
+                self.advance();
+                return Ok(word);
+            }
+        }
+        Err(self.error(&format!("expected a name but found {}", self.peek())))
+    }
+
+    fn at_name(&self) -> bool {
+        matches!(self.peek(), Token::Word(word) if !RESERVED.contains(&word.as_str()))
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position.min(self.tokens.len() - 1)].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.position.min(self.tokens.len() - 1)].1
+    }
+
+    fn advance(&mut self) {
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Token::Word(w) if w == word)
+    }
+
+    fn accept_word(&mut self, word: &str) -> bool {
+        let found = self.peek_word(word);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_word(&mut self, word: &str) -> ParseResult<()> {
+        if self.accept_word(word) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}' but found {}", word, self.peek())))
+        }
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Token::Symbol(s) if *s == symbol);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> ParseResult<()> {
+        if self.accept_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}' but found {}", symbol, self.peek())))
+        }
+    }
+
+    fn error(&self, message: &str) -> InterpreterError {
+        InterpreterError::syntax(format!("Line {}: {}", self.line(), message))
+    }
+}
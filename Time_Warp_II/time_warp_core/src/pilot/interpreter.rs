@@ -0,0 +1,486 @@
+use super::parser::{self, Condition, Program, Statement};
+use crate::basic::matching::match_answer;
+use crate::basic::{ExecutionResult, InterpreterError};
+use std::collections::{HashMap, VecDeque};
+
+/// Shown beside the answer box when an A: is waiting
+const ANSWER_PROMPT: &str = "> ";
+
+/// Deepest U: nesting before a runaway subroutine is stopped
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// A value in a C: expression or a condition
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+/// PILOT interpreter engine. A: pauses the program with
+/// `ExecutionResult::NeedInput`; `provide_input` carries on from there.
+pub struct Interpreter {
+    pub max_instructions: usize,
+    program: Program,
+    next: usize, // index of the statement to run next
+    numbers: HashMap<String, f64>,
+    strings: HashMap<String, String>,
+    answer: String,                  // the last A: answer, for M:
+    matched: bool,                   // whether the last M: matched, for Y and N
+    answer_variable: Option<String>, // where a waiting A: stores its answer
+    returns: Vec<usize>,             // statements to go back to when E: ends a U:
+    output: String,                  // printed since the program last paused
+    input_queue: VecDeque<String>,   // scripted A: answers, kept across runs
+    instructions: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            max_instructions: 100_000,
+            program: Program::default(),
+            next: 0,
+            numbers: HashMap::new(),
+            strings: HashMap::new(),
+            answer: String::new(),
+            matched: false,
+            answer_variable: None,
+            returns: Vec::new(),
+            output: String::new(),
+            input_queue: VecDeque::new(),
+            instructions: 0,
+        }
+    }
+
+    pub fn instruction_count(&self) -> usize {
+        self.instructions
+    }
+
+    /// Parse and run a program from the top
+    pub fn execute(&mut self, source: &str) -> Result<ExecutionResult, InterpreterError> {
+        let max_instructions = self.max_instructions;
+        let input_queue = std::mem::take(&mut self.input_queue);
+        *self = Self::new();
+        self.max_instructions = max_instructions;
+        self.input_queue = input_queue;
+        self.program = parser::parse(source)?;
+        self.run()
+    }
+
+    /// Answer the A: the program is waiting on and carry on running
+    pub fn provide_input(&mut self, input: &str) -> Result<ExecutionResult, InterpreterError> {
+        self.accept_answer(input)?;
+        self.run()
+    }
+
+    /// Pre-load answers for A: statements so the program runs unattended
+    /// until they run out
+    pub fn queue_input<I: IntoIterator<Item = String>>(&mut self, answers: I) {
+        self.input_queue.extend(answers);
+    }
+
+    fn accept_answer(&mut self, input: &str) -> Result<(), InterpreterError> {
+        let answer = input.trim().to_string();
+        self.output.push_str(&answer);
+        self.output.push('\n');
+        if let Some(variable) = self.answer_variable.take() {
+            self.assign(&variable, &answer)?;
+        }
+        self.answer = answer;
+        Ok(())
+    }
+
+    /// Output printed since the last pause when an error ended the run
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    fn run(&mut self) -> Result<ExecutionResult, InterpreterError> {
+        while let Some(statement) = self.program.statements.get(self.next).cloned() {
+            self.next += 1;
+            self.instructions += 1;
+            if self.instructions > self.max_instructions {
+                return Err(runtime_error(
+                    &statement,
+                    format!(
+                        "stopped after {} instructions; the program may be stuck in a loop",
+                        self.max_instructions
+                    ),
+                ));
+            }
+            if !self.condition_holds(&statement)? {
+                continue;
+            }
+            match statement.command {
+                'T' => {
+                    let text = self.interpolate(&statement.operand);
+                    self.output.push_str(&text);
+                    self.output.push('\n');
+                }
+                'A' => {
+                    let variable = statement.operand.trim();
+                    self.answer_variable = (!variable.is_empty()).then(|| variable.to_string());
+                    if let Some(answer) = self.input_queue.pop_front() {
+                        self.output.push_str(ANSWER_PROMPT);
+                        self.accept_answer(&answer)?;
+                        continue;
+                    }
+                    return Ok(ExecutionResult::NeedInput {
+                        variable: variable.to_string(),
+                        prompt: ANSWER_PROMPT.to_string(),
+                        partial_output: std::mem::take(&mut self.output),
+                        partial_graphics: Vec::new(),
+                    });
+                }
+                'M' => {
+                    self.matched = match_answer(&self.answer, &statement.operand) > 0;
+                }
+                'J' => self.next = self.label(&statement)?,
+                'U' => {
+                    if self.returns.len() >= MAX_CALL_DEPTH {
+                        return Err(runtime_error(
+                            &statement,
+                            format!("U: went more than {} deep", MAX_CALL_DEPTH),
+                        ));
+                    }
+                    let target = self.label(&statement)?;
+                    self.returns.push(self.next);
+                    self.next = target;
+                }
+                'E' => match self.returns.pop() {
+                    Some(back) => self.next = back,
+                    None => break,
+                },
+                'C' => self.compute(&statement)?,
+                _ => {}
+            }
+        }
+        Ok(ExecutionResult::Complete {
+            output: std::mem::take(&mut self.output),
+            graphics_commands: Vec::new(),
+        })
+    }
+
+    fn condition_holds(&self, statement: &Statement) -> Result<bool, InterpreterError> {
+        Ok(match &statement.condition {
+            Condition::Always => true,
+            Condition::Yes => self.matched,
+            Condition::No => !self.matched,
+            Condition::Expression(expression) => match self.evaluate(expression, statement)? {
+                Value::Number(number) => number != 0.0,
+                Value::Text(text) => !text.is_empty(),
+            },
+        })
+    }
+
+    /// The statement index a J: or U: operand names, with or without its `*`
+    fn label(&self, statement: &Statement) -> Result<usize, InterpreterError> {
+        let name = statement.operand.trim();
+        let name = name.strip_prefix('*').unwrap_or(name).to_ascii_uppercase();
+        self.program
+            .labels
+            .get(&name)
+            .copied()
+            .ok_or_else(|| runtime_error(statement, format!("there is no label *{}", name)))
+    }
+
+    /// `C:#N = expression` or `C:$NAME = text`
+    fn compute(&mut self, statement: &Statement) -> Result<(), InterpreterError> {
+        let Some((variable, value)) = statement.operand.split_once('=') else {
+            return Err(runtime_error(
+                statement,
+                "C: needs a variable, = and a value".to_string(),
+            ));
+        };
+        let variable = variable.trim();
+        if variable.starts_with('$') {
+            let text = self.interpolate(value.trim());
+            return self.assign(variable, &text);
+        }
+        match self.evaluate(value, statement)? {
+            Value::Number(number) => self.assign(variable, &number.to_string()),
+            Value::Text(text) => self.assign(variable, &text),
+        }
+    }
+
+    /// Store `value` in a `$text` or `#number` variable
+    fn assign(&mut self, variable: &str, value: &str) -> Result<(), InterpreterError> {
+        let mut chars = variable.chars();
+        let sigil = chars.next();
+        let name = chars.as_str().to_ascii_uppercase();
+        let valid = !name.is_empty() && name.chars().all(is_name_char);
+        match sigil {
+            Some('$') if valid => {
+                self.strings.insert(name, value.to_string());
+                Ok(())
+            }
+            Some('#') if valid => match value.trim().parse::<f64>() {
+                Ok(number) => {
+                    self.numbers.insert(name, number);
+                    Ok(())
+                }
+                Err(_) => Err(InterpreterError::TypeError(format!(
+                    "#{} needs a number, not '{}'",
+                    name, value
+                ))),
+            },
+            _ => Err(InterpreterError::RuntimeError(format!(
+                "'{}' is not a variable; use $NAME for text or #NAME for numbers",
+                variable
+            ))),
+        }
+    }
+
+    /// Replace `$NAME` and `#NAME` in T: text with their values. Names that
+    /// were never set are left as typed.
+    fn interpolate(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let sigil = chars[i];
+            let end = chars[i + 1..]
+                .iter()
+                .position(|c| !is_name_char(*c))
+                .map_or(chars.len(), |length| i + 1 + length);
+            let name: String = chars[i + 1..end]
+                .iter()
+                .collect::<String>()
+                .to_ascii_uppercase();
+            let value = match sigil {
+                '$' if !name.is_empty() => self.strings.get(&name).cloned(),
+                '#' if !name.is_empty() => self.numbers.get(&name).map(|n| format_number(*n)),
+                _ => None,
+            };
+            match value {
+                Some(value) => {
+                    result.push_str(&value);
+                    i = end;
+                }
+                None => {
+                    result.push(sigil);
+                    i += 1;
+                }
+            }
+        }
+        result
+    }
+
+    fn evaluate(&self, expression: &str, statement: &Statement) -> Result<Value, InterpreterError> {
+        let tokens = tokenize(expression).map_err(|message| runtime_error(statement, message))?;
+        let mut evaluator = Evaluator {
+            interpreter: self,
+            tokens: &tokens,
+            position: 0,
+        };
+        let value = evaluator
+            .comparison()
+            .map_err(|message| runtime_error(statement, message))?;
+        if evaluator.position < tokens.len() {
+            return Err(runtime_error(
+                statement,
+                format!(
+                    "unexpected {:?} in '{}'",
+                    tokens[evaluator.position],
+                    expression.trim()
+                ),
+            ));
+        }
+        Ok(value)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn runtime_error(statement: &Statement, message: String) -> InterpreterError {
+    InterpreterError::RuntimeError(format!("Line {}: {}", statement.line, message))
+}
+
+/// Whole numbers print without a decimal point
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Variable(char, String), // sigil and upper-case name
+    Operator(String),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let number = number
+                .parse()
+                .map_err(|_| format!("{} is not a number", number))?;
+            tokens.push(Token::Number(number));
+        } else if c == '"' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err("a \" is missing its closing \"".to_string());
+            }
+            tokens.push(Token::Text(chars[start..i].iter().collect()));
+            i += 1;
+        } else if c == '#' || c == '$' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && is_name_char(chars[i]) {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            if name.is_empty() {
+                return Err(format!("{} must be followed by a variable name", c));
+            }
+            tokens.push(Token::Variable(c, name.to_ascii_uppercase()));
+        } else if "+-*/()=<>".contains(c) {
+            let mut op = c.to_string();
+            i += 1;
+            if let Some(&next) = chars.get(i) {
+                if (c == '<' && (next == '=' || next == '>')) || (c == '>' && next == '=') {
+                    op.push(next);
+                    i += 1;
+                }
+            }
+            tokens.push(Token::Operator(op));
+        } else {
+            return Err(format!("'{}' is not allowed in an expression", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over C: expressions and conditions. Comparisons give 1
+/// or 0.
+struct Evaluator<'a> {
+    interpreter: &'a Interpreter,
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Evaluator<'_> {
+    fn operator(&mut self, ops: &[&str]) -> Option<String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(op)) if ops.contains(&op.as_str()) => {
+                self.position += 1;
+                Some(op.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Value, String> {
+        let left = self.additive()?;
+        let Some(op) = self.operator(&["=", "<>", "<", ">", "<=", ">="]) else {
+            return Ok(left);
+        };
+        let right = self.additive()?;
+        let result = match (&left, &right) {
+            (Value::Number(a), Value::Number(b)) => match op.as_str() {
+                "=" => a == b,
+                "<>" => a != b,
+                "<" => a < b,
+                ">" => a > b,
+                "<=" => a <= b,
+                _ => a >= b,
+            },
+            (Value::Text(a), Value::Text(b)) if op == "=" || op == "<>" => {
+                a.eq_ignore_ascii_case(b) == (op == "=")
+            }
+            _ => return Err(format!("can't compare {:?} {} {:?}", left, op, right)),
+        };
+        Ok(Value::Number(if result { 1.0 } else { 0.0 }))
+    }
+
+    fn additive(&mut self) -> Result<Value, String> {
+        let mut left = self.multiplicative()?;
+        while let Some(op) = self.operator(&["+", "-"]) {
+            let (a, b) = (number(&left)?, number(&self.multiplicative()?)?);
+            left = Value::Number(if op == "+" { a + b } else { a - b });
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> Result<Value, String> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.operator(&["*", "/"]) {
+            let (a, b) = (number(&left)?, number(&self.unary()?)?);
+            if op == "/" && b == 0.0 {
+                return Err("division by zero".to_string());
+            }
+            left = Value::Number(if op == "*" { a * b } else { a / b });
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.operator(&["-"]).is_some() {
+            return Ok(Value::Number(-number(&self.unary()?)?));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(number)) => Ok(Value::Number(number)),
+            Some(Token::Text(text)) => Ok(Value::Text(text)),
+            Some(Token::Variable('#', name)) => self
+                .interpreter
+                .numbers
+                .get(&name)
+                .map(|number| Value::Number(*number))
+                .ok_or_else(|| format!("#{} has no value", name)),
+            Some(Token::Variable(_, name)) => self
+                .interpreter
+                .strings
+                .get(&name)
+                .map(|text| Value::Text(text.clone()))
+                .ok_or_else(|| format!("${} has no value", name)),
+            Some(Token::Operator(op)) if op == "(" => {
+                let value = self.comparison()?;
+                match self.operator(&[")"]) {
+                    Some(_) => Ok(value),
+                    None => Err("a ( is missing its closing )".to_string()),
+                }
+            }
+            Some(Token::Operator(op)) => Err(format!("unexpected {}", op)),
+            None => Err("the expression ends too soon".to_string()),
+        }
+    }
+}
+
+fn number(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(number) => Ok(*number),
+        Value::Text(text) => Err(format!("\"{}\" is not a number", text)),
+    }
+}
@@ -0,0 +1,14 @@
+//! PILOT: T: type, A: accept, M: match, J: jump, U: use and E: end, with
+//! Y/N and (expression) conditions on any statement. Answers come through
+//! the same waiting-for-input flow as BASIC's INPUT.
+
+pub mod interpreter;
+pub mod parser;
+
+pub use interpreter::Interpreter;
+pub use parser::{parse, Condition, Program, Statement};
+
+/// Statement prefixes offered by completion
+pub const KEYWORDS: &[&str] = &[
+    "T:", "A:", "M:", "J:", "Y:", "N:", "C:", "E:", "U:", "R:", "TY:", "TN:", "JY:", "JN:",
+];
@@ -0,0 +1,125 @@
+use crate::basic::InterpreterError;
+use std::collections::HashMap;
+
+/// When a statement runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Always,
+    Yes,                // TY: only after a successful M:
+    No,                 // TN: only after a failed M:
+    Expression(String), // T(#N > 5): only when the expression holds
+}
+
+/// One PILOT instruction, e.g. `TY:Well done!`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub line: usize,   // 1-based source line, for error messages
+    pub command: char, // upper-case letter before the colon
+    pub condition: Condition,
+    pub operand: String, // everything after the colon, untrimmed for T:
+}
+
+/// A parsed program: statements in order and where each `*LABEL` points
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    pub labels: HashMap<String, usize>, // upper-case label -> statement index
+}
+
+/// Commands this interpreter understands
+pub const COMMANDS: &str = "TAMJYNCEUR";
+
+/// Parse a whole program. A line is blank, a `*LABEL` (optionally followed by
+/// a statement), or a statement.
+pub fn parse(source: &str) -> Result<Program, InterpreterError> {
+    let mut program = Program::default();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut text = text.trim_start();
+        if let Some(rest) = text.strip_prefix('*') {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let label = rest[..end].to_ascii_uppercase();
+            if label.is_empty() {
+                return Err(InterpreterError::syntax(format!(
+                    "Line {}: * must be followed by a label name",
+                    line
+                )));
+            }
+            if program.labels.contains_key(&label) {
+                return Err(InterpreterError::syntax(format!(
+                    "Line {}: label *{} is defined twice",
+                    line, label
+                )));
+            }
+            program.labels.insert(label, program.statements.len());
+            text = rest[end..].trim_start();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(text, line)?;
+        if statement.command != 'R' {
+            program.statements.push(statement);
+        }
+    }
+    Ok(program)
+}
+
+fn parse_statement(text: &str, line: usize) -> Result<Statement, InterpreterError> {
+    let not_a_command = || {
+        InterpreterError::syntax(format!(
+            "Line {}: '{}' is not a PILOT statement",
+            line,
+            text.trim_end()
+        ))
+    };
+    // The colon after a condition, not one inside it
+    let head_end = match text.find('(') {
+        Some(open) if open < text.find(':').unwrap_or(usize::MAX) => {
+            let close = text[open..].find(')').ok_or_else(not_a_command)? + open;
+            text[close..].find(':').ok_or_else(not_a_command)? + close
+        }
+        _ => text.find(':').ok_or_else(not_a_command)?,
+    };
+    let head = text[..head_end].trim();
+    let operand = text[head_end + 1..].to_string();
+
+    let mut chars = head.chars();
+    let command = chars
+        .next()
+        .map(|c| c.to_ascii_uppercase())
+        .filter(|c| COMMANDS.contains(*c));
+    let Some(mut command) = command else {
+        return Err(not_a_command());
+    };
+    // Expressions keep their case, for comparing with quoted text
+    let rest = chars.as_str().trim();
+    let mut condition = match rest.to_ascii_uppercase().as_str() {
+        "" => Condition::Always,
+        "Y" => Condition::Yes,
+        "N" => Condition::No,
+        _ if rest.starts_with('(') && rest.ends_with(')') => {
+            Condition::Expression(rest[1..rest.len() - 1].to_string())
+        }
+        _ => return Err(not_a_command()),
+    };
+    // Y: and N: are short for TY: and TN:
+    if matches!(command, 'Y' | 'N') {
+        if condition != Condition::Always {
+            return Err(not_a_command());
+        }
+        condition = if command == 'Y' {
+            Condition::Yes
+        } else {
+            Condition::No
+        };
+        command = 'T';
+    }
+
+    Ok(Statement {
+        line,
+        command,
+        condition,
+        operand,
+    })
+}
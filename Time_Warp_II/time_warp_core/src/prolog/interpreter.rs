@@ -0,0 +1,1180 @@
+use super::parser::{self, Clause, Program, Query, QueryKind};
+use super::term::{compare, Term};
+use crate::basic::InterpreterError;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Deepest chain of goals before a runaway recursion is stopped
+const MAX_DEPTH: usize = 50_000;
+
+/// Stack for the thread a program runs on; enough for `MAX_DEPTH` goals,
+/// even in a debug build
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// A `?-` query stops listing answers after this many
+const MAX_ANSWERS: usize = 100;
+
+/// List predicates every program can use. A program that defines one of
+/// these itself replaces it.
+const LIBRARY: &str = "
+append([], L, L).
+append([H|T], L, [H|R]) :- append(T, L, R).
+member(X, [X|_]).
+member(X, [_|T]) :- member(X, T).
+memberchk(X, L) :- member(X, L), !.
+reverse(L, R) :- '$reverse'(L, [], R).
+'$reverse'([], R, R).
+'$reverse'([H|T], A, R) :- '$reverse'(T, [H|A], R).
+last([X], X).
+last([_|T], X) :- last(T, X).
+select(X, [X|T], T).
+select(X, [H|T], [H|R]) :- select(X, T, R).
+sum_list([], 0).
+sum_list([X|T], S) :- sum_list(T, S0), S is S0 + X.
+max_list([X], X) :- !.
+max_list([X|T], M) :- max_list(T, M0), M is max(X, M0).
+min_list([X], X) :- !.
+min_list([X|T], M) :- min_list(T, M0), M is min(X, M0).
+";
+
+/// TW Prolog interpreter engine: loads every clause, then runs the
+/// program's queries in order, writing answers and `write` output to
+/// `output`
+pub struct Interpreter {
+    pub output: String,
+    pub max_instructions: usize,
+    instructions: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            output: String::new(),
+            max_instructions: 100_000,
+            instructions: 0,
+        }
+    }
+
+    /// Inferences made by the last run
+    pub fn instruction_count(&self) -> usize {
+        self.instructions
+    }
+
+    pub fn execute(&mut self, source: &str) -> Result<(), InterpreterError> {
+        self.output.clear();
+        self.instructions = 0;
+        let program = parser::parse(source)?;
+        let library = parser::parse(LIBRARY)?;
+        let max_instructions = self.max_instructions;
+
+        // Every goal nests a few Rust calls, so the solver gets a stack of its own
+        let outcome = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .name("prolog".to_string())
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, || {
+                    let mut machine = Machine::new(max_instructions);
+                    machine.consult(&library, &program);
+                    let result = machine.run(&program);
+                    (machine.output, machine.instructions, result)
+                })
+                .map(|thread| thread.join())
+        });
+        let (output, instructions, result) = match outcome {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(_)) => {
+                return Err(InterpreterError::RuntimeError(
+                    "The Prolog program crashed".to_string(),
+                ))
+            }
+            Err(error) => return Err(InterpreterError::RuntimeError(error.to_string())),
+        };
+        self.output = output;
+        self.instructions = instructions;
+        result
+    }
+}
+
+/// Goals still to prove, sharing their tails between alternatives. The
+/// last link says what to do once everything before it succeeds.
+enum Goals {
+    Answer,                       // the query succeeded
+    Found(usize),                 // an inner proof succeeded (for \+, -> and once)
+    Collect(Term),                // record a findall result and look for more
+    Goal(Term, usize, Rc<Goals>), // a goal, the cut barrier it belongs to, the rest
+}
+
+/// How a search ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    Fail,         // no (more) solutions down this branch
+    Cut(usize),   // a cut is discarding alternatives back to this barrier
+    Found(usize), // the inner proof with this barrier succeeded
+    Stop,         // the query has all the answers it wants
+}
+
+enum Unwind {
+    Error(InterpreterError),
+    Halt,
+}
+
+type Solve = Result<Flow, Unwind>;
+
+/// A bindings and trail position to backtrack to
+#[derive(Clone, Copy)]
+struct Mark {
+    trail: usize,
+    variables: usize,
+}
+
+/// The solver's state while queries run
+struct Machine {
+    database: HashMap<(String, usize), Vec<Rc<Clause>>>,
+    bindings: Vec<Option<Term>>,
+    trail: Vec<usize>,
+    output: String,
+    instructions: usize,
+    max_instructions: usize,
+    next_barrier: usize,
+    collections: Vec<Vec<Term>>,
+    line: usize,
+    names: Vec<(String, usize)>, // the current query's variables
+    show_answers: bool,
+    answers: usize,
+    random_seed: u64,
+}
+
+impl Machine {
+    fn new(max_instructions: usize) -> Self {
+        Self {
+            database: HashMap::new(),
+            bindings: Vec::new(),
+            trail: Vec::new(),
+            output: String::new(),
+            instructions: 0,
+            max_instructions,
+            next_barrier: 0,
+            collections: Vec::new(),
+            line: 1,
+            names: Vec::new(),
+            show_answers: false,
+            answers: 0,
+            random_seed: 12345,
+        }
+    }
+
+    fn consult(&mut self, library: &Program, program: &Program) {
+        for clause in &library.clauses {
+            self.add_clause(clause.clone(), false);
+        }
+        let library_keys: HashSet<_> = self.database.keys().cloned().collect();
+        let mut replaced = HashSet::new();
+        for clause in &program.clauses {
+            let key = key_of(&clause.head);
+            if library_keys.contains(&key) && replaced.insert(key.clone()) {
+                self.database.remove(&key);
+            }
+            self.add_clause(clause.clone(), false);
+        }
+    }
+
+    fn add_clause(&mut self, clause: Clause, at_front: bool) {
+        let clauses = self.database.entry(key_of(&clause.head)).or_default();
+        if at_front {
+            clauses.insert(0, Rc::new(clause));
+        } else {
+            clauses.push(Rc::new(clause));
+        }
+    }
+
+    fn run(&mut self, program: &Program) -> Result<(), InterpreterError> {
+        if program.queries.is_empty() {
+            self.output.push_str(&hint(program));
+            return Ok(());
+        }
+        for query in &program.queries {
+            match self.query(query) {
+                Ok(()) => {}
+                Err(Unwind::Halt) => break,
+                Err(Unwind::Error(error)) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    fn query(&mut self, query: &Query) -> Result<(), Unwind> {
+        self.line = query.line;
+        self.bindings = vec![None; query.variables];
+        self.trail.clear();
+        self.names = query.names.clone();
+        self.show_answers = query.kind == QueryKind::Question;
+        self.answers = 0;
+
+        if self.show_answers {
+            if !self.output.is_empty() && !self.output.ends_with('\n') {
+                self.output.push('\n');
+            }
+            let text = query.goal.write(true, &query.names);
+            self.output.push_str(&format!("?- {}.\n", text));
+        }
+        let barrier = self.barrier();
+        let flow = self.solve(
+            &Rc::new(Goals::Goal(
+                query.goal.clone(),
+                barrier,
+                Rc::new(Goals::Answer),
+            )),
+            0,
+        )?;
+        match query.kind {
+            QueryKind::Question if self.answers == 0 => self.output.push_str("false.\n"),
+            QueryKind::Directive if flow != Flow::Stop => self.output.push_str(&format!(
+                "Warning: the directive on line {} failed\n",
+                query.line
+            )),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Show one answer; `?-` queries go on to look for more
+    fn answer(&mut self) -> Solve {
+        if !self.show_answers {
+            return Ok(Flow::Stop);
+        }
+        self.answers += 1;
+        let names = self.names.clone();
+        let bound: Vec<String> = names
+            .iter()
+            .filter(|(name, _)| !name.starts_with('_'))
+            .filter_map(|(name, index)| {
+                let value = self.resolve(&Term::Var(*index));
+                (value != Term::Var(*index))
+                    .then(|| format!("{} = {}", name, value.write(true, &names)))
+            })
+            .collect();
+        if bound.is_empty() {
+            self.output.push_str("true.\n");
+            return Ok(Flow::Stop);
+        }
+        self.output.push_str(&bound.join(", "));
+        self.output.push('\n');
+        if self.answers >= MAX_ANSWERS {
+            self.output
+                .push_str(&format!("… stopped after {} answers\n", MAX_ANSWERS));
+            return Ok(Flow::Stop);
+        }
+        Ok(Flow::Fail)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, Unwind> {
+        Err(Unwind::Error(InterpreterError::RuntimeError(format!(
+            "Line {}: {}",
+            self.line, message
+        ))))
+    }
+
+    fn barrier(&mut self) -> usize {
+        self.next_barrier += 1;
+        self.next_barrier
+    }
+
+    fn mark(&self) -> Mark {
+        Mark {
+            trail: self.trail.len(),
+            variables: self.bindings.len(),
+        }
+    }
+
+    fn undo(&mut self, mark: Mark) {
+        for index in self.trail.drain(mark.trail..) {
+            self.bindings[index] = None;
+        }
+        self.bindings.truncate(mark.variables);
+    }
+
+    fn fresh_variable(&mut self) -> Term {
+        self.bindings.push(None);
+        Term::Var(self.bindings.len() - 1)
+    }
+
+    /// Follow variable bindings until a value or an unbound variable
+    fn deref(&self, term: &Term) -> Term {
+        let mut term = term;
+        while let Term::Var(index) = term {
+            match &self.bindings[*index] {
+                Some(bound) => term = bound,
+                None => break,
+            }
+        }
+        term.clone()
+    }
+
+    /// The term with every bound variable replaced by its value
+    fn resolve(&self, term: &Term) -> Term {
+        match self.deref(term) {
+            Term::Compound(name, args) => {
+                Term::Compound(name, args.iter().map(|arg| self.resolve(arg)).collect())
+            }
+            other => other,
+        }
+    }
+
+    fn unify(&mut self, a: &Term, b: &Term) -> bool {
+        let mut pairs = vec![(a.clone(), b.clone())];
+        while let Some((a, b)) = pairs.pop() {
+            match (self.deref(&a), self.deref(&b)) {
+                (Term::Var(x), Term::Var(y)) if x == y => {}
+                (Term::Var(x), other) | (other, Term::Var(x)) => {
+                    self.bindings[x] = Some(other);
+                    self.trail.push(x);
+                }
+                (Term::Compound(f, xs), Term::Compound(g, ys)) => {
+                    if f != g || xs.len() != ys.len() {
+                        return false;
+                    }
+                    pairs.extend(xs.into_iter().zip(ys));
+                }
+                (Term::Integer(x), Term::Integer(y)) if x == y => {}
+                (Term::Float(x), Term::Float(y)) if x == y => {}
+                (Term::Atom(x), Term::Atom(y)) | (Term::Str(x), Term::Str(y)) if x == y => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// A copy of a clause's term with its variables numbered from `base`
+    fn rename(term: &Term, base: usize) -> Term {
+        match term {
+            Term::Var(index) => Term::Var(base + index),
+            Term::Compound(name, args) => Term::Compound(
+                name.clone(),
+                args.iter().map(|arg| Self::rename(arg, base)).collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    /// A copy of a resolved term with new variables in place of its own
+    fn fresh_copy(&mut self, term: &Term, map: &mut HashMap<usize, Term>) -> Term {
+        match term {
+            Term::Var(index) => {
+                if let Some(fresh) = map.get(index) {
+                    return fresh.clone();
+                }
+                let fresh = self.fresh_variable();
+                map.insert(*index, fresh.clone());
+                fresh
+            }
+            Term::Compound(name, args) => {
+                let args = args.iter().map(|arg| self.fresh_copy(arg, map)).collect();
+                Term::Compound(name.clone(), args)
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Prove the goals in turn. Conjunctions and deterministic built-ins
+    /// are handled in this loop; only choice points recurse.
+    fn solve(&mut self, goals: &Rc<Goals>, depth: usize) -> Solve {
+        let mut goals = goals.clone();
+        loop {
+            let (goal, barrier, rest) = match &*goals {
+                Goals::Answer => return self.answer(),
+                Goals::Found(barrier) => return Ok(Flow::Found(*barrier)),
+                Goals::Collect(template) => {
+                    let result = self.resolve(template);
+                    if let Some(collection) = self.collections.last_mut() {
+                        collection.push(result);
+                    }
+                    return Ok(Flow::Fail);
+                }
+                Goals::Goal(goal, barrier, rest) => (self.deref(goal), *barrier, rest.clone()),
+            };
+
+            self.instructions += 1;
+            if self.instructions > self.max_instructions {
+                return self.error(format!(
+                    "stopped after {} inferences; the program may be stuck in a loop",
+                    self.max_instructions
+                ));
+            }
+            if depth > MAX_DEPTH {
+                return self.error(format!(
+                    "goals nested more than {} deep; check for a rule that calls itself forever",
+                    MAX_DEPTH
+                ));
+            }
+
+            let (name, args) = match &goal {
+                Term::Atom(name) => (name.as_str(), &[][..]),
+                Term::Compound(name, args) => (name.as_str(), args.as_slice()),
+                Term::Var(_) => return self.error("a goal is an unbound variable".to_string()),
+                other => return self.error(format!("{} is not a goal", other.write(true, &[]))),
+            };
+            if let (",", [a, b]) = (name, args) {
+                let second = Rc::new(Goals::Goal(b.clone(), barrier, rest));
+                goals = Rc::new(Goals::Goal(a.clone(), barrier, second));
+                continue;
+            }
+            match self.builtin(name, args)? {
+                Some(true) => goals = rest,
+                Some(false) => return Ok(Flow::Fail),
+                None if is_control(name) => {
+                    return self.step(name, args, &goal, barrier, &rest, depth)
+                }
+                None => return self.call_user(name, args, &goal, &rest, depth),
+            }
+        }
+    }
+
+    /// Control constructs, nondeterministic built-ins and user predicates
+    fn step(
+        &mut self,
+        name: &str,
+        args: &[Term],
+        goal: &Term,
+        barrier: usize,
+        rest: &Rc<Goals>,
+        depth: usize,
+    ) -> Solve {
+        let then = |goal: &Term, rest: &Rc<Goals>| {
+            Rc::new(Goals::Goal(goal.clone(), barrier, rest.clone()))
+        };
+
+        match (name, args) {
+            ("!", []) => match self.solve(rest, depth + 1)? {
+                Flow::Fail => Ok(Flow::Cut(barrier)),
+                other => Ok(other),
+            },
+            (";", [condition, otherwise]) => {
+                if let Term::Compound(arrow, branches) = self.deref(condition) {
+                    if arrow == "->" && branches.len() == 2 {
+                        return self.if_then_else(&branches, Some(otherwise), barrier, rest, depth);
+                    }
+                }
+                let mark = self.mark();
+                match self.solve(&then(condition, rest), depth + 1)? {
+                    Flow::Fail => {
+                        self.undo(mark);
+                        self.solve(&then(otherwise, rest), depth + 1)
+                    }
+                    other => Ok(other),
+                }
+            }
+            ("->", branches) if branches.len() == 2 => {
+                self.if_then_else(branches, None, barrier, rest, depth)
+            }
+            ("\\+" | "not", [inner]) => {
+                let mark = self.mark();
+                let proved = self.prove(inner, depth)?;
+                self.undo(mark);
+                if proved {
+                    Ok(Flow::Fail)
+                } else {
+                    self.solve(rest, depth + 1)
+                }
+            }
+            ("once", [inner]) => {
+                if self.prove(inner, depth)? {
+                    self.solve(rest, depth + 1)
+                } else {
+                    Ok(Flow::Fail)
+                }
+            }
+            ("call", [callable, extra @ ..]) => {
+                let goal = match (self.deref(callable), extra) {
+                    (goal, []) => goal,
+                    (Term::Atom(name), extra) => Term::Compound(name, extra.to_vec()),
+                    (Term::Compound(name, mut args), extra) => {
+                        args.extend_from_slice(extra);
+                        Term::Compound(name, args)
+                    }
+                    (other, _) => {
+                        return self.error(format!("can't call {}", other.write(true, &[])))
+                    }
+                };
+                let inner = self.barrier();
+                let goals = Rc::new(Goals::Goal(goal, inner, rest.clone()));
+                match self.solve(&goals, depth + 1)? {
+                    Flow::Cut(cut) if cut == inner => Ok(Flow::Fail),
+                    other => Ok(other),
+                }
+            }
+            ("forall", [condition, action]) => {
+                // forall(C, A) is \+ (C, \+ A)
+                let not_action = Term::Compound("\\+".to_string(), vec![action.clone()]);
+                let both = Term::Compound(",".to_string(), vec![condition.clone(), not_action]);
+                let mark = self.mark();
+                let counterexample = self.prove(&both, depth)?;
+                self.undo(mark);
+                if counterexample {
+                    Ok(Flow::Fail)
+                } else {
+                    self.solve(rest, depth + 1)
+                }
+            }
+            ("findall", [template, inner, result]) => {
+                let mark = self.mark();
+                self.collections.push(Vec::new());
+                let inner_barrier = self.barrier();
+                let goals = Rc::new(Goals::Goal(
+                    inner.clone(),
+                    inner_barrier,
+                    Rc::new(Goals::Collect(template.clone())),
+                ));
+                let flow = self.solve(&goals, depth + 1);
+                let found = self.collections.pop().unwrap_or_default();
+                flow?;
+                self.undo(mark);
+                let items = found
+                    .iter()
+                    .map(|item| self.fresh_copy(item, &mut HashMap::new()))
+                    .collect();
+                if self.unify(result, &Term::list(items, Term::atom("[]"))) {
+                    self.solve(rest, depth + 1)
+                } else {
+                    Ok(Flow::Fail)
+                }
+            }
+            ("between", [low, high, value]) => {
+                let (low, high) = (self.integer(low)?, self.integer(high)?);
+                if let Term::Integer(n) = self.deref(value) {
+                    return if (low..=high).contains(&n) {
+                        self.solve(rest, depth + 1)
+                    } else {
+                        Ok(Flow::Fail)
+                    };
+                }
+                let choices = (low..=high).map(|n| vec![(value.clone(), Term::Integer(n))]);
+                self.alternatives(choices, rest, depth)
+            }
+            ("atom_concat", [a, b, whole]) => {
+                let (a_value, b_value) = (self.deref(a), self.deref(b));
+                if let (Some(x), Some(y)) = (text_of(&a_value), text_of(&b_value)) {
+                    return if self.unify(whole, &Term::Atom(x + &y)) {
+                        self.solve(rest, depth + 1)
+                    } else {
+                        Ok(Flow::Fail)
+                    };
+                }
+                let Some(text) = text_of(&self.deref(whole)) else {
+                    return self.error("atom_concat needs two parts or the whole".to_string());
+                };
+                let splits: Vec<_> = text
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain([text.len()])
+                    .map(|i| {
+                        vec![
+                            (a.clone(), Term::Atom(text[..i].to_string())),
+                            (b.clone(), Term::Atom(text[i..].to_string())),
+                        ]
+                    })
+                    .collect();
+                self.alternatives(splits.into_iter(), rest, depth)
+            }
+            ("retract", [clause]) => {
+                let (head, body) = match self.deref(clause) {
+                    Term::Compound(neck, parts) if neck == ":-" && parts.len() == 2 => {
+                        (parts[0].clone(), parts[1].clone())
+                    }
+                    other => (other, Term::atom("true")),
+                };
+                let key = match self.deref(&head) {
+                    Term::Atom(name) => (name, 0),
+                    Term::Compound(name, args) => (name, args.len()),
+                    _ => return self.error("retract needs a clause".to_string()),
+                };
+                let clauses = self.database.get(&key).cloned().unwrap_or_default();
+                for stored in clauses {
+                    let mark = self.mark();
+                    let base = self.bindings.len();
+                    self.bindings.resize(base + stored.variables, None);
+                    if self.unify(&head, &Self::rename(&stored.head, base))
+                        && self.unify(&body, &Self::rename(&stored.body, base))
+                    {
+                        if let Some(clauses) = self.database.get_mut(&key) {
+                            clauses.retain(|other| !Rc::ptr_eq(other, &stored));
+                        }
+                        return self.solve(rest, depth + 1);
+                    }
+                    self.undo(mark);
+                }
+                Ok(Flow::Fail)
+            }
+            _ => self.call_user(name, args, goal, rest, depth),
+        }
+    }
+
+    /// `(Condition -> Then ; Else)`: the first way to prove the condition only
+    fn if_then_else(
+        &mut self,
+        branches: &[Term],
+        otherwise: Option<&Term>,
+        barrier: usize,
+        rest: &Rc<Goals>,
+        depth: usize,
+    ) -> Solve {
+        let mark = self.mark();
+        let branch = if self.prove(&branches[0], depth)? {
+            &branches[1]
+        } else {
+            self.undo(mark);
+            match otherwise {
+                Some(otherwise) => otherwise,
+                None => return Ok(Flow::Fail),
+            }
+        };
+        let goals = Rc::new(Goals::Goal(branch.clone(), barrier, rest.clone()));
+        self.solve(&goals, depth + 1)
+    }
+
+    /// Whether `goal` can be proved, keeping the bindings of the first proof
+    fn prove(&mut self, goal: &Term, depth: usize) -> Result<bool, Unwind> {
+        let inner = self.barrier();
+        let goals = Rc::new(Goals::Goal(
+            goal.clone(),
+            inner,
+            Rc::new(Goals::Found(inner)),
+        ));
+        Ok(self.solve(&goals, depth + 1)? == Flow::Found(inner))
+    }
+
+    /// Try each set of unifications in turn, backtracking between them
+    fn alternatives(
+        &mut self,
+        choices: impl Iterator<Item = Vec<(Term, Term)>>,
+        rest: &Rc<Goals>,
+        depth: usize,
+    ) -> Solve {
+        for choice in choices {
+            let mark = self.mark();
+            if choice.iter().all(|(a, b)| self.unify(a, b)) {
+                match self.solve(rest, depth + 1)? {
+                    Flow::Fail => {}
+                    other => return Ok(other),
+                }
+            }
+            self.undo(mark);
+        }
+        Ok(Flow::Fail)
+    }
+
+    /// Resolve `goal` against the program's clauses
+    fn call_user(
+        &mut self,
+        name: &str,
+        args: &[Term],
+        goal: &Term,
+        rest: &Rc<Goals>,
+        depth: usize,
+    ) -> Solve {
+        let key = (name.to_string(), args.len());
+        let Some(clauses) = self.database.get(&key).cloned() else {
+            let others: Vec<String> = self
+                .database
+                .keys()
+                .filter(|(other, _)| other == name)
+                .map(|(other, arity)| format!("{}/{}", other, arity))
+                .collect();
+            return if others.is_empty() {
+                self.error(format!("unknown procedure {}/{}", name, args.len()))
+            } else {
+                self.error(format!(
+                    "unknown procedure {}/{} (there is {})",
+                    name,
+                    args.len(),
+                    others.join(" and ")
+                ))
+            };
+        };
+
+        let barrier = self.barrier();
+        for clause in clauses {
+            let mark = self.mark();
+            let base = self.bindings.len();
+            self.bindings.resize(base + clause.variables, None);
+            if self.unify(goal, &Self::rename(&clause.head, base)) {
+                let body = Self::rename(&clause.body, base);
+                let goals = Rc::new(Goals::Goal(body, barrier, rest.clone()));
+                match self.solve(&goals, depth + 1)? {
+                    Flow::Fail => {}
+                    Flow::Cut(cut) if cut == barrier => {
+                        self.undo(mark);
+                        return Ok(Flow::Fail);
+                    }
+                    other => return Ok(other),
+                }
+            }
+            self.undo(mark);
+        }
+        Ok(Flow::Fail)
+    }
+
+    /// Deterministic built-ins: `Some(true)` to carry on, `Some(false)` to
+    /// fail, `None` if `name` isn't one
+    fn builtin(&mut self, name: &str, args: &[Term]) -> Result<Option<bool>, Unwind> {
+        let result = match (name, args) {
+            ("true", []) => true,
+            ("fail" | "false", []) => false,
+            ("halt", [] | [_]) => return Err(Unwind::Halt),
+            ("=", [a, b]) => self.unify(a, b),
+            ("\\=" | "<>", [a, b]) => {
+                let mark = self.mark();
+                let unified = self.unify(a, b);
+                self.undo(mark);
+                !unified
+            }
+            ("==", [a, b]) => compare(&self.resolve(a), &self.resolve(b)).is_eq(),
+            ("\\==", [a, b]) => compare(&self.resolve(a), &self.resolve(b)).is_ne(),
+            ("@<", [a, b]) => compare(&self.resolve(a), &self.resolve(b)).is_lt(),
+            ("@>", [a, b]) => compare(&self.resolve(a), &self.resolve(b)).is_gt(),
+            ("@=<", [a, b]) => compare(&self.resolve(a), &self.resolve(b)).is_le(),
+            ("@>=", [a, b]) => compare(&self.resolve(a), &self.resolve(b)).is_ge(),
+            ("is", [result, expression]) => {
+                let value = self.evaluate(expression)?.term();
+                self.unify(result, &value)
+            }
+            ("=:=" | "=\\=" | "<" | ">" | "=<" | ">=", [a, b]) => {
+                let (a, b) = (self.evaluate(a)?, self.evaluate(b)?);
+                let ordering = a.compare(&b);
+                match name {
+                    "=:=" => ordering.is_eq(),
+                    "=\\=" => ordering.is_ne(),
+                    "<" => ordering.is_lt(),
+                    ">" => ordering.is_gt(),
+                    "=<" => ordering.is_le(),
+                    _ => ordering.is_ge(),
+                }
+            }
+            ("var", [a]) => matches!(self.deref(a), Term::Var(_)),
+            ("nonvar", [a]) => !matches!(self.deref(a), Term::Var(_)),
+            ("atom", [a]) => matches!(self.deref(a), Term::Atom(_)),
+            ("number", [a]) => matches!(self.deref(a), Term::Integer(_) | Term::Float(_)),
+            ("integer", [a]) => matches!(self.deref(a), Term::Integer(_)),
+            ("float", [a]) => matches!(self.deref(a), Term::Float(_)),
+            ("string", [a]) => matches!(self.deref(a), Term::Str(_)),
+            ("atomic", [a]) => !matches!(self.deref(a), Term::Var(_) | Term::Compound(..)),
+            ("compound", [a]) => matches!(self.deref(a), Term::Compound(..)),
+            ("is_list", [a]) => self.resolve(a).items().is_some(),
+            ("write" | "print", args) if !args.is_empty() => {
+                // Turbo Prolog's write takes any number of arguments
+                for arg in args {
+                    let text = self.resolve(arg).write(false, &[]);
+                    self.output.push_str(&text);
+                }
+                true
+            }
+            ("writeq", [a]) => {
+                let text = self.resolve(a).write(true, &[]);
+                self.output.push_str(&text);
+                true
+            }
+            ("writeln", [a]) => {
+                let text = self.resolve(a).write(false, &[]);
+                self.output.push_str(&text);
+                self.output.push('\n');
+                true
+            }
+            ("nl", []) => {
+                self.output.push('\n');
+                true
+            }
+            ("tab", [n]) => {
+                let n = self.integer(n)?;
+                self.output.push_str(&" ".repeat(n.max(0) as usize));
+                true
+            }
+            ("format", [text]) => self.format(text, &Term::atom("[]"))?,
+            ("format", [text, values]) => self.format(text, values)?,
+            ("length", [list, length]) => self.length(list, length)?,
+            ("atom_length", [atom, length]) => {
+                let Some(text) = text_of(&self.deref(atom)) else {
+                    return self.error("atom_length needs an atom".to_string());
+                };
+                self.unify(length, &Term::Integer(text.chars().count() as i64))
+            }
+            ("atom_chars", [atom, chars]) => match text_of(&self.deref(atom)) {
+                Some(text) => {
+                    let items = text.chars().map(|c| Term::Atom(c.to_string())).collect();
+                    self.unify(chars, &Term::list(items, Term::atom("[]")))
+                }
+                None => {
+                    let Some(items) = self.resolve(chars).items() else {
+                        return self.error("atom_chars needs an atom or a list".to_string());
+                    };
+                    let text: Option<String> = items.iter().map(text_of).collect();
+                    match text {
+                        Some(text) => self.unify(atom, &Term::Atom(text)),
+                        None => return self.error("atom_chars needs a list of characters".into()),
+                    }
+                }
+            },
+            ("sort" | "msort", [list, sorted]) => {
+                let Some(mut items) = self.resolve(list).items() else {
+                    return self.error(format!("{} needs a list", name));
+                };
+                items.sort_by(compare);
+                if name == "sort" {
+                    items.dedup_by(|a, b| compare(a, b).is_eq());
+                }
+                self.unify(sorted, &Term::list(items, Term::atom("[]")))
+            }
+            ("=..", [term, list]) => match self.deref(term) {
+                Term::Compound(name, args) => {
+                    let items = std::iter::once(Term::Atom(name)).chain(args).collect();
+                    self.unify(list, &Term::list(items, Term::atom("[]")))
+                }
+                Term::Var(_) => {
+                    let items = self.resolve(list).items().unwrap_or_default();
+                    match items.split_first() {
+                        Some((Term::Atom(name), args)) if !args.is_empty() => {
+                            self.unify(term, &Term::Compound(name.clone(), args.to_vec()))
+                        }
+                        Some((single, [])) => self.unify(term, single),
+                        _ => return self.error("=.. needs a name and its arguments".to_string()),
+                    }
+                }
+                atomic => self.unify(list, &Term::list(vec![atomic], Term::atom("[]"))),
+            },
+            ("assert" | "assertz" | "asserta", [clause]) => {
+                let clause = self.stored_clause(clause)?;
+                self.add_clause(clause, name == "asserta");
+                true
+            }
+            ("dynamic" | "discontiguous", [indicators]) => {
+                let mut pending = vec![self.resolve(indicators)];
+                while let Some(indicator) = pending.pop() {
+                    match indicator {
+                        Term::Compound(comma, parts) if comma == "," => pending.extend(parts),
+                        Term::Compound(slash, parts) if slash == "/" => {
+                            if let [Term::Atom(name), Term::Integer(arity)] = parts.as_slice() {
+                                self.database
+                                    .entry((name.clone(), *arity as usize))
+                                    .or_default();
+                            }
+                        }
+                        _ => return self.error("dynamic needs name/arity".to_string()),
+                    }
+                }
+                true
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    fn length(&mut self, list: &Term, length: &Term) -> Result<bool, Unwind> {
+        // Count the known items, then look at what the list ends in
+        let mut count = 0;
+        let mut tail = self.deref(list);
+        while let Term::Compound(dot, args) = &tail {
+            if dot != "." || args.len() != 2 {
+                break;
+            }
+            count += 1;
+            tail = self.deref(&args[1]);
+        }
+        match (&tail, self.deref(length)) {
+            (Term::Atom(empty), _) if empty == "[]" => {
+                Ok(self.unify(length, &Term::Integer(count)))
+            }
+            (Term::Var(_), Term::Integer(n)) if n >= count => {
+                let items = (count..n).map(|_| self.fresh_variable()).collect();
+                Ok(self.unify(&tail, &Term::list(items, Term::atom("[]"))))
+            }
+            (Term::Var(_), Term::Integer(_)) => Ok(false),
+            _ => self.error("length needs a list or a length".to_string()),
+        }
+    }
+
+    /// format/2 with ~w, ~a, ~d, ~q, ~p, ~n and ~~
+    fn format(&mut self, text: &Term, values: &Term) -> Result<bool, Unwind> {
+        let Some(text) = text_of(&self.deref(text)) else {
+            return self.error("format needs text".to_string());
+        };
+        let values = self.resolve(values);
+        let mut values = values.items().unwrap_or_else(|| vec![values]).into_iter();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '~' {
+                self.output.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => self.output.push('\n'),
+                Some('~') => self.output.push('~'),
+                Some(directive @ ('w' | 'a' | 'd' | 'p' | 'q')) => {
+                    let Some(value) = values.next() else {
+                        return self.error("format has more ~ than values".to_string());
+                    };
+                    let text = value.write(directive == 'q', &[]);
+                    self.output.push_str(&text);
+                }
+                other => {
+                    return self.error(format!(
+                        "format doesn't know ~{}",
+                        other.map(String::from).unwrap_or_default()
+                    ))
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// An asserted term as a clause with its own variables numbered from 0
+    fn stored_clause(&mut self, clause: &Term) -> Result<Clause, Unwind> {
+        fn renumber(term: &Term, map: &mut HashMap<usize, usize>) -> Term {
+            match term {
+                Term::Var(index) => {
+                    let next = map.len();
+                    Term::Var(*map.entry(*index).or_insert(next))
+                }
+                Term::Compound(name, args) => Term::Compound(
+                    name.clone(),
+                    args.iter().map(|arg| renumber(arg, map)).collect(),
+                ),
+                other => other.clone(),
+            }
+        }
+        let clause = self.resolve(clause);
+        let (head, body) = match clause {
+            Term::Compound(neck, mut parts) if neck == ":-" && parts.len() == 2 => {
+                let body = parts.pop().unwrap_or(Term::atom("true"));
+                (parts.pop().unwrap_or(Term::atom("true")), body)
+            }
+            other => (other, Term::atom("true")),
+        };
+        if !matches!(head, Term::Atom(_) | Term::Compound(..)) {
+            return self.error(format!("can't assert {}", head.write(true, &[])));
+        }
+        let mut map = HashMap::new();
+        Ok(Clause {
+            head: renumber(&head, &mut map),
+            body: renumber(&body, &mut map),
+            variables: map.len(),
+            line: self.line,
+        })
+    }
+
+    fn integer(&mut self, term: &Term) -> Result<i64, Unwind> {
+        match self.evaluate(term)? {
+            Number::Integer(n) => Ok(n),
+            Number::Float(n) => self.error(format!("expected an integer, not {}", n)),
+        }
+    }
+
+    /// Arithmetic for `is` and the comparisons
+    fn evaluate(&mut self, term: &Term) -> Result<Number, Unwind> {
+        use Number::{Float, Integer};
+        let term = self.deref(term);
+        let (name, args) = match &term {
+            Term::Integer(n) => return Ok(Integer(*n)),
+            Term::Float(n) => return Ok(Float(*n)),
+            Term::Var(_) => {
+                return self.error("arithmetic on a variable with no value yet".to_string())
+            }
+            Term::Atom(name) => (name.as_str(), &[][..]),
+            Term::Compound(name, args) => (name.as_str(), args.as_slice()),
+            Term::Str(text) => return self.error(format!("\"{}\" is not a number", text)),
+        };
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.evaluate(arg)?);
+        }
+        let too_large = || "the number is too large".to_string();
+        let result = match (name, values.as_slice()) {
+            ("pi", []) => Float(std::f64::consts::PI),
+            ("e", []) => Float(std::f64::consts::E),
+            ("random", [Integer(limit)]) if *limit > 0 => {
+                // Same generator as TW BASIC's RND
+                self.random_seed = (self.random_seed * 9301 + 49297) % 233280;
+                Integer((self.random_seed as f64 / 233280.0 * *limit as f64) as i64)
+            }
+            ("-", [a]) => match a {
+                Integer(n) => Integer(-n),
+                Float(n) => Float(-n),
+            },
+            ("+", [a]) => *a,
+            ("abs", [a]) => match a {
+                Integer(n) => Integer(n.abs()),
+                Float(n) => Float(n.abs()),
+            },
+            ("+" | "-" | "*", [Integer(a), Integer(b)]) => {
+                let result = match name {
+                    "+" => a.checked_add(*b),
+                    "-" => a.checked_sub(*b),
+                    _ => a.checked_mul(*b),
+                };
+                match result {
+                    Some(n) => Integer(n),
+                    None => return self.error(too_large()),
+                }
+            }
+            ("//" | "mod" | "rem", [Integer(_), Integer(0)]) => {
+                return Err(Unwind::Error(InterpreterError::DivisionByZero))
+            }
+            ("//" | "mod" | "rem", [Integer(a), Integer(b)]) => {
+                let result = match name {
+                    "//" => a.checked_div(*b),
+                    "mod" => a.checked_rem_euclid(*b),
+                    _ => a.checked_rem(*b),
+                };
+                match result {
+                    Some(n) => Integer(n),
+                    None => return self.error(too_large()),
+                }
+            }
+            ("/", [_, b]) if b.float() == 0.0 => {
+                return Err(Unwind::Error(InterpreterError::DivisionByZero))
+            }
+            ("/", [Integer(a), Integer(b)]) if a.checked_rem(*b) == Some(0) => Integer(a / b),
+            ("min" | "max", [a, b]) => {
+                let first = (name == "max") == a.compare(b).is_ge();
+                if first {
+                    *a
+                } else {
+                    *b
+                }
+            }
+            ("**" | "^", [Integer(a), Integer(b)]) if *b >= 0 => {
+                match u32::try_from(*b).ok().and_then(|b| a.checked_pow(b)) {
+                    Some(n) => Integer(n),
+                    None => return self.error(too_large()),
+                }
+            }
+            ("+", [a, b]) => Float(a.float() + b.float()),
+            ("-", [a, b]) => Float(a.float() - b.float()),
+            ("*", [a, b]) => Float(a.float() * b.float()),
+            ("/", [a, b]) => Float(a.float() / b.float()),
+            ("**" | "^", [a, b]) => Float(a.float().powf(b.float())),
+            ("sqrt", [a]) if a.float() < 0.0 => {
+                return self.error(format!("sqrt can't take {}", a.float()))
+            }
+            ("sqrt", [a]) => Float(a.float().sqrt()),
+            ("sin", [a]) => Float(a.float().sin()),
+            ("cos", [a]) => Float(a.float().cos()),
+            ("tan", [a]) => Float(a.float().tan()),
+            ("atan", [a]) => Float(a.float().atan()),
+            ("exp", [a]) => Float(a.float().exp()),
+            ("log", [a]) if a.float() <= 0.0 => {
+                return self.error(format!("log can't take {}", a.float()))
+            }
+            ("log", [a]) => Float(a.float().ln()),
+            ("float", [a]) => Float(a.float()),
+            ("integer" | "round", [a]) => Integer(a.float().round() as i64),
+            ("truncate", [a]) => Integer(a.float().trunc() as i64),
+            ("floor", [a]) => Integer(a.float().floor() as i64),
+            ("ceiling", [a]) => Integer(a.float().ceil() as i64),
+            ("sign", [Integer(a)]) => Integer(a.signum()),
+            ("sign", [a]) => Float(a.float().signum()),
+            _ if args.is_empty() => {
+                return self.error(format!("{} is not a number", term.write(true, &[])))
+            }
+            _ => {
+                return self.error(format!(
+                    "{}/{} is not an arithmetic function",
+                    name,
+                    args.len()
+                ))
+            }
+        };
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn float(self) -> f64 {
+        match self {
+            Number::Integer(n) => n as f64,
+            Number::Float(n) => n,
+        }
+    }
+
+    fn compare(&self, other: &Number) -> std::cmp::Ordering {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a.cmp(b),
+            (a, b) => a.float().total_cmp(&b.float()),
+        }
+    }
+
+    fn term(self) -> Term {
+        match self {
+            Number::Integer(n) => Term::Integer(n),
+            Number::Float(n) => Term::Float(n),
+        }
+    }
+}
+
+/// Goals `step` handles rather than the program's clauses
+fn is_control(name: &str) -> bool {
+    matches!(
+        name,
+        "!" | ";"
+            | "->"
+            | "\\+"
+            | "not"
+            | "once"
+            | "call"
+            | "forall"
+            | "findall"
+            | "between"
+            | "atom_concat"
+            | "retract"
+    )
+}
+
+fn key_of(head: &Term) -> (String, usize) {
+    match head {
+        Term::Compound(name, args) => (name.clone(), args.len()),
+        Term::Atom(name) => (name.clone(), 0),
+        _ => (String::new(), 0),
+    }
+}
+
+/// The text of an atom, string or number
+fn text_of(term: &Term) -> Option<String> {
+    match term {
+        Term::Atom(text) | Term::Str(text) => Some(text.clone()),
+        Term::Integer(_) | Term::Float(_) => Some(term.write(false, &[])),
+        _ => None,
+    }
+}
+
+/// What to show for a program with facts and rules but nothing to ask
+fn hint(program: &Program) -> String {
+    let Some(first) = program.clauses.first() else {
+        return "Nothing to run yet: add some facts, then a query like ?- likes(X, Y).\n"
+            .to_string();
+    };
+    let example = match &first.head {
+        Term::Compound(name, args) => {
+            let variables: Vec<&str> = ["X", "Y", "Z", "W", "V"]
+                .into_iter()
+                .cycle()
+                .take(args.len())
+                .collect();
+            format!("{}({})", name, variables.join(", "))
+        }
+        other => other.write(true, &[]),
+    };
+    format!(
+        "{} clauses loaded. Ask a question with a query such as:\n?- {}.\n",
+        program.clauses.len(),
+        example
+    )
+}
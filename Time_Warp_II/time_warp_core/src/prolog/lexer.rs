@@ -0,0 +1,193 @@
+use crate::basic::InterpreterError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Atom(String),
+    Var(String),
+    Integer(i64),
+    Float(f64),
+    Str(String),
+    Punct(&'static str), // ( ) [ ] { } , |
+    OpenCall,            // a ( straight after a name, starting its arguments
+    End,                 // the full stop that ends a clause
+    Eof,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Atom(name) => write!(f, "'{}'", name),
+            Token::Var(name) => write!(f, "{}", name),
+            Token::Integer(number) => write!(f, "{}", number),
+            Token::Float(number) => write!(f, "{}", number),
+            Token::Str(text) => write!(f, "{:?}", text),
+            Token::Punct(symbol) => write!(f, "'{}'", symbol),
+            Token::OpenCall => write!(f, "'('"),
+            Token::End => write!(f, "the full stop"),
+            Token::Eof => write!(f, "the end of the program"),
+        }
+    }
+}
+
+/// Characters that run together into symbolic atoms like `:-` and `=<`
+const SYMBOL_CHARS: &str = "+-*/\\^<>=~:.?@#&$";
+
+/// Split `source` into tokens, each with its 1-based line. Comments are
+/// `% …` and `/* … */`.
+pub fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, InterpreterError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let mut name_end = None; // where the last name token ended
+    let error = |line: usize, message: &str| {
+        InterpreterError::syntax(format!("Line {}: {}", line, message))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+        let token = if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        } else if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c == '%' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        } else if c == '/' && next == Some('*') {
+            let start_line = line;
+            i += 2;
+            while !(chars.get(i) == Some(&'*') && chars.get(i + 1) == Some(&'/')) {
+                match chars.get(i) {
+                    None => return Err(error(start_line, "comment is never closed")),
+                    Some('\n') => line += 1,
+                    Some(_) => {}
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let mut float = false;
+            // 0'c is the character code of c
+            if chars[start..i] == ['0'] && chars.get(i) == Some(&'\'') {
+                let Some(&code) = chars.get(i + 1) else {
+                    return Err(error(line, "0' must be followed by a character"));
+                };
+                i += 2;
+                tokens.push((Token::Integer(code as i64), line));
+                continue;
+            }
+            if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                float = true;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            if matches!(chars.get(i), Some('e' | 'E')) {
+                let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                if chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                    float = true;
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = if float {
+                text.parse().map(Token::Float).ok()
+            } else {
+                text.parse().map(Token::Integer).ok()
+            };
+            token.ok_or_else(|| error(line, &format!("{} is too large", text)))?
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if c.is_ascii_lowercase() {
+                Token::Atom(word)
+            } else {
+                Token::Var(word)
+            }
+        } else if c == '\'' || c == '"' {
+            // Quoted atom or string; a doubled quote or \ escapes
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\\') => {
+                        text.push(match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&other) => other,
+                            None => '\\',
+                        });
+                        i += 2;
+                    }
+                    Some('\n') | None => {
+                        return Err(error(line, &format!("text is missing its closing {}", c)))
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            if c == '\'' {
+                Token::Atom(text)
+            } else {
+                Token::Str(text)
+            }
+        } else if c == '(' {
+            i += 1;
+            if name_end == Some(start) {
+                Token::OpenCall
+            } else {
+                Token::Punct("(")
+            }
+        } else if let Some(symbol) = [")", "[", "]", "{", "}", ",", "|"]
+            .into_iter()
+            .find(|symbol| symbol.starts_with(c))
+        {
+            i += 1;
+            Token::Punct(symbol)
+        } else if c == '!' || c == ';' {
+            i += 1;
+            Token::Atom(c.to_string())
+        } else if c == '.' && next.is_none_or(|n| n.is_whitespace() || n == '%') {
+            i += 1;
+            Token::End
+        } else if SYMBOL_CHARS.contains(c) {
+            while i < chars.len() && SYMBOL_CHARS.contains(chars[i]) {
+                i += 1;
+            }
+            Token::Atom(chars[start..i].iter().collect())
+        } else {
+            return Err(error(line, &format!("unexpected character '{}'", c)));
+        };
+        name_end = matches!(token, Token::Atom(_)).then_some(i);
+        tokens.push((token, line));
+    }
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
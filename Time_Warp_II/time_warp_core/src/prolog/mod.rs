@@ -0,0 +1,41 @@
+//! TW Prolog: facts, rules and `?-` queries with backtracking, cut, lists
+//! and arithmetic. Turbo Prolog style programs with `domains`,
+//! `predicates`, `clauses` and `goal` sections run too.
+
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+pub mod term;
+
+pub use interpreter::Interpreter;
+pub use parser::{parse, Clause, Program, Query, QueryKind};
+pub use term::Term;
+
+/// Built-in predicates offered by completion and highlighted in the editor
+pub const KEYWORDS: &[&str] = &[
+    "write",
+    "writeln",
+    "nl",
+    "is",
+    "not",
+    "fail",
+    "true",
+    "findall",
+    "forall",
+    "between",
+    "member",
+    "append",
+    "length",
+    "reverse",
+    "assert",
+    "asserta",
+    "assertz",
+    "retract",
+    "format",
+    "halt",
+    "mod",
+    "clauses",
+    "predicates",
+    "domains",
+    "goal",
+];
@@ -0,0 +1,336 @@
+use super::lexer::{tokenize, Token};
+use super::term::{infix_operator, prefix_operator, Term};
+use crate::basic::InterpreterError;
+
+/// Section headings of Turbo Prolog style programs
+const SECTIONS: &[&str] = &[
+    "domains",
+    "constants",
+    "database",
+    "predicates",
+    "clauses",
+    "goal",
+];
+
+type ParseResult<T> = Result<T, InterpreterError>;
+
+/// A fact or rule. Its variables are numbered from 0 and renamed apart each
+/// time it is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub head: Term,
+    pub body: Term,
+    pub variables: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryKind {
+    Question,  // `?- …` shows every answer
+    Directive, // `:- …` runs once, quietly
+    Goal,      // a Turbo Prolog `goal` section, run once, quietly
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub goal: Term,
+    pub variables: usize,
+    pub names: Vec<(String, usize)>, // as written, for showing the query and answers
+    pub line: usize,
+    pub kind: QueryKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub clauses: Vec<Clause>,
+    pub queries: Vec<Query>,
+}
+
+/// Parse a whole program. Queries run after every clause has been loaded.
+pub fn parse(source: &str) -> ParseResult<Program> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+        names: Vec::new(),
+        variables: 0,
+    };
+    let mut program = Program::default();
+
+    while parser.peek() != &Token::Eof {
+        if let Some(section) = parser.section_heading() {
+            parser.position += 1;
+            match section.as_str() {
+                "clauses" => {}
+                "goal" => {
+                    let line = parser.line();
+                    let goal = parser.sentence()?;
+                    program
+                        .queries
+                        .push(parser.query(goal, line, QueryKind::Goal));
+                }
+                // Domain and predicate declarations only describe types
+                _ => {
+                    while parser.peek() != &Token::Eof && parser.section_heading().is_none() {
+                        parser.position += 1;
+                    }
+                }
+            }
+            continue;
+        }
+
+        let line = parser.line();
+        let term = parser.sentence()?;
+        match term {
+            Term::Compound(name, mut args) if args.len() == 1 && (name == "?-" || name == ":-") => {
+                let kind = if name == "?-" {
+                    QueryKind::Question
+                } else {
+                    QueryKind::Directive
+                };
+                let goal = args.remove(0);
+                program.queries.push(parser.query(goal, line, kind));
+            }
+            Term::Compound(name, _) if name == "-->" => {
+                return Err(parser.error_at(line, "grammar rules (-->) are not supported"));
+            }
+            Term::Compound(name, mut args) if name == ":-" && args.len() == 2 => {
+                let body = args.pop().unwrap_or(Term::atom("true"));
+                let head = args.pop().unwrap_or(Term::atom("true"));
+                program.clauses.push(parser.clause(head, body, line)?);
+            }
+            head => program
+                .clauses
+                .push(parser.clause(head, Term::atom("true"), line)?),
+        }
+    }
+    Ok(program)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    names: Vec<(String, usize)>, // variables named in the current clause
+    variables: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position.min(self.tokens.len() - 1)].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.position.min(self.tokens.len() - 1)].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error_at(&self, line: usize, message: &str) -> InterpreterError {
+        InterpreterError::syntax(format!("Line {}: {}", line, message))
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> ParseResult<()> {
+        if self.peek() == &Token::Punct(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error_at(
+                self.line(),
+                &format!("expected '{}' but found {}", symbol, self.peek()),
+            ))
+        }
+    }
+
+    /// A section heading sits alone at the start of its line
+    fn section_heading(&self) -> Option<String> {
+        let Token::Atom(word) = self.peek() else {
+            return None;
+        };
+        let line = self.line();
+        let first_on_line = self.position == 0 || self.tokens[self.position - 1].1 < line;
+        let last_on_line = self
+            .tokens
+            .get(self.position + 1)
+            .is_none_or(|(token, next)| *next > line || token == &Token::Eof);
+        (SECTIONS.contains(&word.as_str()) && first_on_line && last_on_line).then(|| word.clone())
+    }
+
+    /// One term ended by a full stop, with fresh variable names
+    fn sentence(&mut self) -> ParseResult<Term> {
+        self.names.clear();
+        self.variables = 0;
+        let term = self.term(1200)?;
+        if self.peek() != &Token::End {
+            return Err(self.error_at(
+                self.line(),
+                &format!("expected a full stop but found {}", self.peek()),
+            ));
+        }
+        self.position += 1;
+        Ok(term)
+    }
+
+    fn query(&self, goal: Term, line: usize, kind: QueryKind) -> Query {
+        Query {
+            goal,
+            variables: self.variables,
+            names: self.names.clone(),
+            line,
+            kind,
+        }
+    }
+
+    fn clause(&self, head: Term, body: Term, line: usize) -> ParseResult<Clause> {
+        if !matches!(head, Term::Atom(_) | Term::Compound(..)) {
+            return Err(self.error_at(line, "a clause must start with a name, like likes(mary, X)"));
+        }
+        Ok(Clause {
+            head,
+            body,
+            variables: self.variables,
+            line,
+        })
+    }
+
+    fn variable(&mut self, name: String) -> Term {
+        // Each `_` is a new variable
+        if name != "_" {
+            if let Some((_, index)) = self.names.iter().find(|(known, _)| *known == name) {
+                return Term::Var(*index);
+            }
+        }
+        self.names.push((name, self.variables));
+        self.variables += 1;
+        Term::Var(self.variables - 1)
+    }
+
+    /// Operator precedence parsing: a term no looser than `max`
+    fn term(&mut self, max: u16) -> ParseResult<Term> {
+        let (mut left, mut left_priority) = self.primary(max)?;
+        loop {
+            let name = match self.peek() {
+                Token::Atom(name) => name.clone(),
+                Token::Punct(",") => ",".to_string(),
+                Token::Punct("|") => ";".to_string(),
+                _ => break,
+            };
+            match infix_operator(&name) {
+                Some((priority, left_max, right_max))
+                    if priority <= max && left_priority <= left_max =>
+                {
+                    self.position += 1;
+                    let right = self.term(right_max)?;
+                    left = Term::Compound(name, vec![left, right]);
+                    left_priority = priority;
+                }
+                _ => break,
+            }
+        }
+        Ok(left)
+    }
+
+    fn primary(&mut self, max: u16) -> ParseResult<(Term, u16)> {
+        let line = self.line();
+        let term = match self.next() {
+            Token::Integer(number) => Term::Integer(number),
+            Token::Float(number) => Term::Float(number),
+            Token::Str(text) => Term::Str(text),
+            Token::Var(name) => self.variable(name),
+            Token::Punct("(") => {
+                let term = self.term(1200)?;
+                self.expect(")")?;
+                term
+            }
+            Token::Punct("[") => {
+                if self.peek() == &Token::Punct("]") {
+                    self.position += 1;
+                    return Ok((Term::atom("[]"), 0));
+                }
+                let mut items = vec![self.term(999)?];
+                while self.peek() == &Token::Punct(",") {
+                    self.position += 1;
+                    items.push(self.term(999)?);
+                }
+                let tail = if self.peek() == &Token::Punct("|") {
+                    self.position += 1;
+                    self.term(999)?
+                } else {
+                    Term::atom("[]")
+                };
+                self.expect("]")?;
+                Term::list(items, tail)
+            }
+            Token::Punct("{") => {
+                if self.peek() == &Token::Punct("}") {
+                    self.position += 1;
+                    return Ok((Term::atom("{}"), 0));
+                }
+                let term = self.term(1200)?;
+                self.expect("}")?;
+                Term::Compound("{}".to_string(), vec![term])
+            }
+            Token::Atom(name) => return self.name(name, max),
+            _ => {
+                self.position -= 1;
+                return Err(self.error_at(line, &format!("unexpected {}", self.peek())));
+            }
+        };
+        Ok((term, 0))
+    }
+
+    /// An atom, a compound `name(args)`, or a prefix operator
+    fn name(&mut self, name: String, max: u16) -> ParseResult<(Term, u16)> {
+        if self.peek() == &Token::OpenCall {
+            self.position += 1;
+            let mut args = vec![self.term(999)?];
+            while self.peek() == &Token::Punct(",") {
+                self.position += 1;
+                args.push(self.term(999)?);
+            }
+            self.expect(")")?;
+            return Ok((Term::Compound(name, args), 0));
+        }
+        if name == "-" {
+            match *self.peek() {
+                Token::Integer(number) => {
+                    self.position += 1;
+                    return Ok((Term::Integer(-number), 0));
+                }
+                Token::Float(number) => {
+                    self.position += 1;
+                    return Ok((Term::Float(-number), 0));
+                }
+                _ => {}
+            }
+        }
+        if let Some((priority, arg_max)) = prefix_operator(&name) {
+            let operand_follows = match self.peek() {
+                Token::Atom(next) => {
+                    infix_operator(next).is_none() || prefix_operator(next).is_some()
+                }
+                Token::Punct(symbol) => matches!(*symbol, "(" | "[" | "{"),
+                Token::End | Token::Eof | Token::OpenCall => false,
+                _ => true,
+            };
+            if operand_follows {
+                let arg = self.term(arg_max.min(max))?;
+                return Ok((Term::Compound(name, vec![arg]), priority.min(max)));
+            }
+        }
+        if matches!(self.peek(), Token::Punct("(")) && !matches!(name.as_str(), "," | "|") {
+            // `foo (x)` with a space is almost always a typo for `foo(x)`
+            if prefix_operator(&name).is_none() && infix_operator(&name).is_none() {
+                return Err(self.error_at(
+                    self.line(),
+                    &format!("there must be no space between {} and its (", name),
+                ));
+            }
+        }
+        Ok((Term::Atom(name), 0))
+    }
+}
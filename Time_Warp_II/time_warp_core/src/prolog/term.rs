@@ -0,0 +1,231 @@
+use std::cmp::Ordering;
+
+/// A Prolog term. Variables are numbered; the solver keeps their bindings.
+/// Lists are `'.'(Head, Tail)` ending in the atom `[]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Atom(String),
+    Integer(i64),
+    Float(f64),
+    Str(String),
+    Var(usize),
+    Compound(String, Vec<Term>),
+}
+
+/// Priority and the highest priorities allowed left and right of an infix
+/// operator (xfx, xfy and yfx in standard terms)
+pub fn infix_operator(name: &str) -> Option<(u16, u16, u16)> {
+    let (priority, kind) = match name {
+        ":-" | "-->" => (1200, "xfx"),
+        ";" | "|" => (1100, "xfy"),
+        "->" => (1050, "xfy"),
+        "," => (1000, "xfy"),
+        "=" | "\\=" | "==" | "\\==" | "@<" | "@>" | "@=<" | "@>=" | "=.." | "is" | "=:="
+        | "=\\=" | "<" | ">" | "=<" | ">=" | "<>" => (700, "xfx"),
+        "+" | "-" | "/\\" | "\\/" => (500, "yfx"),
+        "*" | "/" | "//" | "mod" | "rem" | "<<" | ">>" => (400, "yfx"),
+        "**" => (200, "xfx"),
+        "^" => (200, "xfy"),
+        _ => return None,
+    };
+    Some(match kind {
+        "xfx" => (priority, priority - 1, priority - 1),
+        "xfy" => (priority, priority - 1, priority),
+        _ => (priority, priority, priority - 1),
+    })
+}
+
+/// Priority and the highest priority allowed for the argument of a prefix
+/// operator
+pub fn prefix_operator(name: &str) -> Option<(u16, u16)> {
+    match name {
+        ":-" | "?-" => Some((1200, 1199)),
+        "dynamic" | "discontiguous" => Some((1150, 1149)),
+        "\\+" => Some((900, 900)),
+        "-" | "+" | "\\" => Some((200, 200)),
+        _ => None,
+    }
+}
+
+impl Term {
+    pub fn atom(name: &str) -> Self {
+        Term::Atom(name.to_string())
+    }
+
+    /// `[a, b | Tail]` from its items and tail
+    pub fn list(items: Vec<Term>, tail: Term) -> Self {
+        items.into_iter().rev().fold(tail, |rest, item| {
+            Term::Compound(".".to_string(), vec![item, rest])
+        })
+    }
+
+    /// The items of a proper list, for a term with no bound variables left
+    pub fn items(&self) -> Option<Vec<Term>> {
+        let mut items = Vec::new();
+        let mut term = self;
+        loop {
+            match term {
+                Term::Atom(name) if name == "[]" => return Some(items),
+                Term::Compound(dot, args) if dot == "." && args.len() == 2 => {
+                    items.push(args[0].clone());
+                    term = &args[1];
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Write the term as `write/1` does, or quoted and spaced out like the
+    /// answers to a query. `names` gives the query's own variable names.
+    pub fn write(&self, quoted: bool, names: &[(String, usize)]) -> String {
+        let mut out = String::new();
+        self.write_to(&mut out, 1200, quoted, names);
+        out
+    }
+
+    fn write_to(&self, out: &mut String, max: u16, quoted: bool, names: &[(String, usize)]) {
+        match self {
+            Term::Atom(name) => out.push_str(&atom_text(name, quoted)),
+            Term::Integer(number) => out.push_str(&number.to_string()),
+            Term::Float(number) => out.push_str(&format_float(*number)),
+            Term::Str(text) if quoted => out.push_str(&format!("{:?}", text)),
+            Term::Str(text) => out.push_str(text),
+            Term::Var(index) => match names.iter().find(|(_, var)| var == index) {
+                Some((name, _)) => out.push_str(name),
+                None => out.push_str(&format!("_G{}", index)),
+            },
+            Term::Compound(dot, args) if dot == "." && args.len() == 2 => {
+                out.push('[');
+                args[0].write_to(out, 999, quoted, names);
+                let mut tail = &args[1];
+                loop {
+                    match tail {
+                        Term::Compound(dot, args) if dot == "." && args.len() == 2 => {
+                            out.push_str(if quoted { ", " } else { "," });
+                            args[0].write_to(out, 999, quoted, names);
+                            tail = &args[1];
+                        }
+                        Term::Atom(name) if name == "[]" => break,
+                        other => {
+                            out.push('|');
+                            other.write_to(out, 999, quoted, names);
+                            break;
+                        }
+                    }
+                }
+                out.push(']');
+            }
+            Term::Compound(curly, args) if curly == "{}" && args.len() == 1 => {
+                out.push('{');
+                args[0].write_to(out, 1200, quoted, names);
+                out.push('}');
+            }
+            Term::Compound(name, args) => {
+                if let (Some((priority, left, right)), [a, b]) =
+                    (infix_operator(name), args.as_slice())
+                {
+                    let bracket = priority > max;
+                    if bracket {
+                        out.push('(');
+                    }
+                    a.write_to(out, left, quoted, names);
+                    if name.chars().all(|c| c.is_ascii_alphabetic()) {
+                        out.push_str(&format!(" {} ", name));
+                    } else if name == "," && quoted {
+                        out.push_str(", ");
+                    } else {
+                        out.push_str(&atom_text(name, quoted));
+                    }
+                    b.write_to(out, right, quoted, names);
+                    if bracket {
+                        out.push(')');
+                    }
+                    return;
+                }
+                if let (Some((priority, arg_max)), [a]) = (prefix_operator(name), args.as_slice()) {
+                    if !matches!(a, Term::Integer(_) | Term::Float(_)) {
+                        let bracket = priority > max;
+                        if bracket {
+                            out.push('(');
+                        }
+                        out.push_str(&atom_text(name, quoted));
+                        if name.chars().all(|c| c.is_ascii_alphabetic())
+                            || matches!(a, Term::Compound(inner, _) if prefix_operator(inner).is_some())
+                        {
+                            out.push(' ');
+                        }
+                        a.write_to(out, arg_max, quoted, names);
+                        if bracket {
+                            out.push(')');
+                        }
+                        return;
+                    }
+                }
+                out.push_str(&atom_text(name, quoted));
+                out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(if quoted { ", " } else { "," });
+                    }
+                    arg.write_to(out, 999, quoted, names);
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+/// Whole floats keep their `.0` so they don't read back as integers
+fn format_float(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{:.1}", number)
+    } else {
+        number.to_string()
+    }
+}
+
+/// An atom, in quotes if it wouldn't read back as the same atom
+fn atom_text(name: &str, quoted: bool) -> String {
+    let plain = name.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let symbolic = !name.is_empty() && name.chars().all(|c| "+-*/\\^<>=~:.?@#&$".contains(c));
+    if !quoted || plain || symbolic || matches!(name, "[]" | "!" | ";" | "{}" | ",") {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+/// The standard order of terms: variables, numbers, atoms, strings, then
+/// compounds by arity, name and arguments
+pub fn compare(a: &Term, b: &Term) -> Ordering {
+    fn rank(term: &Term) -> u8 {
+        match term {
+            Term::Var(_) => 0,
+            Term::Integer(_) | Term::Float(_) => 1,
+            Term::Atom(_) => 3,
+            Term::Str(_) => 4,
+            Term::Compound(..) => 5,
+        }
+    }
+    match (a, b) {
+        (Term::Var(x), Term::Var(y)) => x.cmp(y),
+        (Term::Integer(x), Term::Integer(y)) => x.cmp(y),
+        (Term::Integer(x), Term::Float(y)) => (*x as f64).total_cmp(y),
+        (Term::Float(x), Term::Integer(y)) => x.total_cmp(&(*y as f64)),
+        (Term::Float(x), Term::Float(y)) => x.total_cmp(y),
+        (Term::Atom(x), Term::Atom(y)) | (Term::Str(x), Term::Str(y)) => x.cmp(y),
+        (Term::Compound(f, xs), Term::Compound(g, ys)) => xs
+            .len()
+            .cmp(&ys.len())
+            .then_with(|| f.cmp(g))
+            .then_with(|| {
+                xs.iter()
+                    .zip(ys)
+                    .map(|(x, y)| compare(x, y))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            }),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
@@ -0,0 +1,134 @@
+//! The runtime inside exported web pages. `time_warp_web`, built for
+//! wasm32-wasip1, runs a program in the browser: the page passes the
+//! language and the program as arguments, answers INPUT on stdin, and reads
+//! back what the run did as one JSON object per line on stdout:
+//!
+//! ```text
+//! {"print":"What's your name? "}
+//! {"turtle":"MAIN","command":"FORWARD","args":[50]}
+//! {"error":"E201 Division by zero (line 3, column 4)"}
+//! ```
+//!
+//! The page draws the turtle commands itself, so the geometry matches the
+//! IDE's canvas.
+
+use crate::json::Json;
+use crate::{ExecutionResult, GraphicsCommand, Language, TurtleCommand};
+
+/// Run `code`, handing each event to `emit` as it happens. `answer` is asked
+/// for a line whenever the program wants input; `None` means there is none.
+pub fn run(
+    language: Language,
+    code: &str,
+    max_instructions: usize,
+    answer: &mut dyn FnMut() -> Option<String>,
+    emit: &mut dyn FnMut(Json),
+) {
+    let mut backend = language.backend();
+    let mut result = backend.execute(code, max_instructions);
+    loop {
+        match result {
+            Ok(ExecutionResult::NeedInput {
+                partial_output,
+                prompt,
+                partial_graphics,
+                ..
+            }) => {
+                emit_run(&partial_output, &partial_graphics, emit);
+                emit_print(&prompt, emit);
+                let Some(line) = answer() else {
+                    emit_error("the program is waiting for input but there is no more", emit);
+                    return;
+                };
+                result = backend.continue_with_input(&line);
+            }
+            Ok(ExecutionResult::Complete {
+                output,
+                graphics_commands: graphics,
+            })
+            | Ok(ExecutionResult::Break {
+                partial_output: output,
+                partial_graphics: graphics,
+                ..
+            })
+            // A page has no live keyboard, so INKEY$ finds nothing and the run ends
+            | Ok(ExecutionResult::Polling {
+                partial_output: output,
+                partial_graphics: graphics,
+            }) => {
+                emit_run(&output, &graphics, emit);
+                return;
+            }
+            // A page has no files, printer or network to allow
+            Ok(ExecutionResult::NeedPermission {
+                permission,
+                partial_output,
+                partial_graphics,
+            }) => {
+                emit_run(&partial_output, &partial_graphics, emit);
+                emit_error(&format!("the program asked to {}", permission.name()), emit);
+                return;
+            }
+            Ok(ExecutionResult::Error(message)) => {
+                emit_error(&message, emit);
+                return;
+            }
+            Err(err) => {
+                emit_run(&backend.take_output(), &backend.take_graphics(), emit);
+                emit_error(&err.to_string(), emit);
+                return;
+            }
+        }
+    }
+}
+
+fn emit_run(output: &str, graphics: &[TurtleCommand], emit: &mut dyn FnMut(Json)) {
+    emit_print(output, emit);
+    for command in graphics {
+        emit(turtle_event(command));
+    }
+}
+
+fn emit_print(text: &str, emit: &mut dyn FnMut(Json)) {
+    if !text.is_empty() {
+        emit(Json::object([("print", text.into())]));
+    }
+}
+
+fn emit_error(message: &str, emit: &mut dyn FnMut(Json)) {
+    emit(Json::object([("error", message.into())]));
+}
+
+/// A turtle command as the page's script reads it: the command's Logo name
+/// and its arguments
+fn turtle_event(command: &TurtleCommand) -> Json {
+    let number = |value: f32| Json::Number(value as f64);
+    let colour = |r: u8, g: u8, b: u8| vec![number(r.into()), number(g.into()), number(b.into())];
+    let (name, args) = match &command.command {
+        GraphicsCommand::Forward(distance) => ("FORWARD", vec![number(*distance)]),
+        GraphicsCommand::Back(distance) => ("BACK", vec![number(*distance)]),
+        GraphicsCommand::Move(distance) => ("MOVE", vec![number(*distance)]),
+        GraphicsCommand::Left(degrees) => ("LEFT", vec![number(*degrees)]),
+        GraphicsCommand::Right(degrees) | GraphicsCommand::Turn(degrees) => {
+            ("RIGHT", vec![number(*degrees)])
+        }
+        GraphicsCommand::Home => ("HOME", Vec::new()),
+        GraphicsCommand::SetXY(x, y) => ("SETXY", vec![number(*x), number(*y)]),
+        GraphicsCommand::MoveTo(x, y) => ("MOVETO", vec![number(*x), number(*y)]),
+        GraphicsCommand::SetHeading(degrees) => ("SETHEADING", vec![number(*degrees)]),
+        GraphicsCommand::ClearScreen => ("CLEARSCREEN", Vec::new()),
+        GraphicsCommand::SetPenColor(r, g, b) => ("SETPENCOLOR", colour(*r, *g, *b)),
+        GraphicsCommand::SetPenSize(width) => ("SETPENSIZE", vec![number(*width)]),
+        GraphicsCommand::Fill => ("FILL", Vec::new()),
+        GraphicsCommand::Arc(radius, degrees) => ("ARC", vec![number(*radius), number(*degrees)]),
+        GraphicsCommand::Label(text) => ("LABEL", vec![text.as_str().into()]),
+        GraphicsCommand::SetLabelSize(size) => ("SETLABELSIZE", vec![number(*size)]),
+        GraphicsCommand::SetBackground(r, g, b) => ("SETBACKGROUND", colour(*r, *g, *b)),
+        GraphicsCommand::ShowTurtle(visible) => ("SHOWTURTLE", vec![Json::Bool(*visible)]),
+    };
+    Json::object([
+        ("turtle", command.turtle.as_str().into()),
+        ("command", name.into()),
+        ("args", Json::Array(args)),
+    ])
+}